@@ -0,0 +1,52 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+package com.google.nearby.fastpair.bluetooth;
+
+import android.bluetooth.le.ScanCallback;
+import android.bluetooth.le.ScanResult;
+import java.util.List;
+
+/**
+ * Forwards BLE scan results to the Rust `bluetooth` crate. Instances are created by native code,
+ * which identifies the scan they belong to by {@code scanId}.
+ */
+final class NativeScanCallback extends ScanCallback {
+  private final long scanId;
+
+  NativeScanCallback(long scanId) {
+    this.scanId = scanId;
+  }
+
+  @Override
+  public void onScanResult(int callbackType, ScanResult result) {
+    nativeOnScanResult(scanId, result);
+  }
+
+  @Override
+  public void onBatchScanResults(List<ScanResult> results) {
+    for (ScanResult result : results) {
+      nativeOnScanResult(scanId, result);
+    }
+  }
+
+  @Override
+  public void onScanFailed(int errorCode) {
+    nativeOnScanFailed(scanId, errorCode);
+  }
+
+  private static native void nativeOnScanResult(long scanId, ScanResult result);
+
+  private static native void nativeOnScanFailed(long scanId, int errorCode);
+}
@@ -0,0 +1,233 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    env, fs,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use bluetooth::{BleAddress, BleAddressKind, BleAdvertisement, BleDataTypeId};
+use criterion::{
+    black_box, criterion_group, criterion_main, BatchSize, Criterion,
+};
+
+/// Environment variable naming a capture file to benchmark instead of
+/// `DEFAULT_CAPTURE`, in the same format.
+const CAPTURE_VAR: &str = "BLE_ADVERTISEMENT_CAPTURE";
+
+/// Capture benchmarked by default, relative to the crate root.
+const DEFAULT_CAPTURE: &str = "benches/data/busy_environment.txt";
+
+const DATATYPE_SELECTOR: [BleDataTypeId; 5] = [
+    BleDataTypeId::Flags,
+    BleDataTypeId::ShortenedLocalName,
+    BleDataTypeId::CompleteLocalName,
+    BleDataTypeId::TxPowerLevel,
+    BleDataTypeId::ServiceData16BitUuid,
+];
+
+/// Allocator counting the allocations made by the benchmarked code.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Print the number of allocations `f` makes per advertisement of the
+/// capture, alongside the timings.
+fn report_allocations(name: &str, advertisements: usize, f: impl FnOnce()) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    println!(
+        "{}: {:.1} allocations per advertisement",
+        name,
+        allocations as f64 / advertisements as f64
+    );
+}
+
+/// Advertisements of a busy environment, read from the file named by
+/// `CAPTURE_VAR` if set, or else from `DEFAULT_CAPTURE`. Lines starting with
+/// '#' are comments, the others hold an address, its kind (public or
+/// random) and the raw payload in hexadecimal, separated by spaces.
+fn busy_environment() -> Vec<(BleAddress, Vec<u8>)> {
+    let path = env::var_os(CAPTURE_VAR).map_or_else(
+        || PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(DEFAULT_CAPTURE),
+        PathBuf::from,
+    );
+    let capture = fs::read_to_string(&path).unwrap_or_else(|err| {
+        panic!("cannot read {}: {}", path.display(), err)
+    });
+
+    capture
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(parse_captured_advertisement)
+        .collect()
+}
+
+/// Parse a line of a capture file, see `busy_environment()`.
+fn parse_captured_advertisement(line: &str) -> (BleAddress, Vec<u8>) {
+    let invalid = || panic!("invalid capture line {:?}", line);
+    let mut fields = line.split_whitespace();
+    let (Some(address), Some(kind), Some(payload), None) =
+        (fields.next(), fields.next(), fields.next(), fields.next())
+    else {
+        invalid()
+    };
+
+    let address = u64::from_str_radix(&address.replace(':', ""), 16)
+        .unwrap_or_else(|_| invalid());
+    let kind = match kind {
+        "public" => BleAddressKind::Public,
+        "random" => BleAddressKind::Random,
+        _ => invalid(),
+    };
+    let payload = (0..payload.len())
+        .step_by(2)
+        .map(|i| {
+            payload
+                .get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .unwrap_or_else(|| invalid())
+        })
+        .collect();
+
+    (BleAddress::new(address, kind), payload)
+}
+
+fn parse_advertisements(c: &mut Criterion) {
+    let capture = busy_environment();
+    let parse = |capture: Vec<(BleAddress, Vec<u8>)>| {
+        for (address, raw_data) in capture {
+            let advertisement = BleAdvertisement::from_raw_data(
+                address,
+                Some(-70),
+                raw_data,
+                &DATATYPE_SELECTOR,
+            )
+            .unwrap();
+            black_box(advertisement);
+        }
+    };
+
+    // The payloads are copied before timing, as a scanner hands them over.
+    let input = capture.clone();
+    report_allocations("parse busy environment", capture.len(), || {
+        parse(input)
+    });
+    c.bench_function("parse busy environment", |b| {
+        b.iter_batched(|| capture.clone(), parse, BatchSize::SmallInput)
+    });
+}
+
+fn clone_advertisements(c: &mut Criterion) {
+    // Advertisements are cloned when handed out in batches or forwarded to
+    // several consumers.
+    let advertisements: Vec<BleAdvertisement> = busy_environment()
+        .into_iter()
+        .map(|(address, raw_data)| {
+            BleAdvertisement::from_raw_data(
+                address,
+                Some(-70),
+                raw_data,
+                &DATATYPE_SELECTOR,
+            )
+            .unwrap()
+        })
+        .collect();
+
+    // The first clone of a payload allocates its reference count, which
+    // later clones share, as in the timed loop.
+    black_box(advertisements.clone());
+    report_allocations("clone busy environment", advertisements.len(), || {
+        black_box(advertisements.clone());
+    });
+    c.bench_function("clone busy environment", |b| {
+        b.iter(|| black_box(advertisements.clone()))
+    });
+}
+
+/// Benchmark a scan on the mock platform, which hands the captured
+/// advertisements to the scan queue as a platform's scan callback would:
+/// copying the payload, queuing the advertisement, then loading the selected
+/// data types and numbering it once the consumer takes it from the stream.
+#[cfg(feature = "mock")]
+fn scan_advertisements(c: &mut Criterion) {
+    use bluetooth::{
+        api::BleAdapter, MockAdvertisement, MockFixtures, Platform,
+        ScanSettings,
+    };
+    use futures::{executor::block_on, future, StreamExt};
+
+    fn scan(fixtures: MockFixtures) {
+        fixtures.install();
+        let adapter = block_on(Platform::default_adapter()).unwrap();
+        let advertisements = adapter
+            .scan(None, ScanSettings::new(), DATATYPE_SELECTOR.to_vec())
+            .unwrap();
+        block_on(advertisements.for_each(|advertisement| {
+            black_box(advertisement.unwrap());
+            future::ready(())
+        }));
+    }
+
+    let capture = busy_environment();
+    let fixtures = || {
+        capture.iter().fold(
+            MockFixtures::new(),
+            |fixtures, (address, raw_data)| {
+                fixtures.with_advertisement(MockAdvertisement::new(
+                    *address,
+                    Some(-70),
+                    raw_data.clone(),
+                ))
+            },
+        )
+    };
+
+    let input = fixtures();
+    report_allocations("scan busy environment", capture.len(), || scan(input));
+    c.bench_function("scan busy environment", |b| {
+        b.iter_batched(fixtures, scan, BatchSize::SmallInput)
+    });
+}
+
+#[cfg(not(feature = "mock"))]
+criterion_group!(benches, parse_advertisements, clone_advertisements);
+// The scan is only benchmarked on the mock platform, with
+// `cargo bench --features mock`.
+#[cfg(feature = "mock")]
+criterion_group!(
+    benches,
+    parse_advertisements,
+    clone_advertisements,
+    scan_advertisements
+);
+criterion_main!(benches);
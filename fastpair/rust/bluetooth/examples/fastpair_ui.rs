@@ -29,7 +29,7 @@ extern crate bluetooth;
 
 use bluetooth::{
     api::{BleAdapter, BleDevice, ClassicDevice},
-    BleDataTypeId, ClassicAddress, Platform,
+    BleDataTypeId, ClassicAddress, Platform, Uuid,
 };
 
 async fn get_user_input(
@@ -74,7 +74,7 @@ async fn get_user_input(
 fn main() -> Result<(), Box<dyn Error>> {
     let run = async {
         let mut adapter = Platform::default_adapter().await?;
-        adapter.start_scan()?;
+        adapter.start_scan(None)?;
 
         let mut addr_set = HashSet::new();
         let device_vec = Arc::new(Mutex::new(Vec::new()));
@@ -95,7 +95,7 @@ fn main() -> Result<(), Box<dyn Error>> {
                 let uuid = service_data.uuid();
 
                 // This is a Fast Pair device.
-                if uuid == 0x2cfe {
+                if uuid == Uuid::from_u16(0xFE2C) {
                     let addr = advertisement.address();
                     let ble_device = Platform::new_ble_device(addr).await?;
                     let name = ble_device.name()?;
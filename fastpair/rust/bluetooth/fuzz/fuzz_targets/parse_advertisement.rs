@@ -0,0 +1,40 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#![no_main]
+
+use bluetooth::{BleAddress, BleAddressKind, BleAdvertisement, BleDataTypeId};
+use libfuzzer_sys::fuzz_target;
+
+const DATATYPE_SELECTOR: [BleDataTypeId; 5] = [
+    BleDataTypeId::Flags,
+    BleDataTypeId::ShortenedLocalName,
+    BleDataTypeId::CompleteLocalName,
+    BleDataTypeId::TxPowerLevel,
+    BleDataTypeId::ServiceData16BitUuid,
+];
+
+// Parse arbitrary bytes as the raw payload of a received advertisement.
+// Malformed payloads may fail to parse, but must never panic.
+fuzz_target!(|raw_data: &[u8]| {
+    let address = BleAddress::new(0x112233445566, BleAddressKind::Public);
+    if let Ok(advertisement) = BleAdvertisement::from_raw_data(
+        address,
+        None,
+        raw_data.to_vec(),
+        &DATATYPE_SELECTOR,
+    ) {
+        let _ = advertisement.ad_structures();
+    }
+});
@@ -0,0 +1,640 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Mutex,
+    },
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::{stream, StreamExt};
+use jni::{
+    objects::{GlobalRef, JByteArray, JClass, JObject, JString, JValue},
+    sys::{jint, jlong},
+    JNIEnv,
+};
+use tracing::{error, info, warn};
+
+use super::{
+    address::{address_kind, format_address, parse_address},
+    jvm::{attach, check_exception, default_adapter, scan_callback_class},
+};
+use crate::{
+    api,
+    common::{
+        scan_queue, AdapterStateStream, AdvertisementStream, BleAddress,
+        BleAddressKind, BleAdvertisement, BleDataTypeId, BluetoothError,
+        DiscoveredClassicDevice, RetryPolicy, ScanFilter, ScanQueue,
+        ScanQueueSender, ScanSettings, Uuid,
+    },
+};
+
+/// `ScanSettings.SCAN_MODE_LOW_POWER`, listening 10% of the time.
+const SCAN_MODE_LOW_POWER: jint = 0;
+/// `ScanSettings.SCAN_MODE_BALANCED`, listening 25% of the time.
+const SCAN_MODE_BALANCED: jint = 1;
+/// `ScanSettings.SCAN_MODE_LOW_LATENCY`, listening continuously.
+const SCAN_MODE_LOW_LATENCY: jint = 2;
+/// `ScanResult.TX_POWER_NOT_PRESENT`.
+const TX_POWER_NOT_PRESENT: jint = 127;
+
+/// Senders for the advertisements of every running scan, keyed by the ID
+/// handed to its `NativeScanCallback`. Removing a sender closes the channel.
+static SCAN_SENDERS: Mutex<BTreeMap<jlong, ScanQueueSender<ScanEvent>>> =
+    Mutex::new(BTreeMap::new());
+static NEXT_SCAN_ID: AtomicI64 = AtomicI64::new(0);
+
+/// Data copied out of an `android.bluetooth.le.ScanResult` on the Java
+/// callback thread, since the Java object can't outlive the callback.
+struct ScanEvent {
+    /// Advertisement holding the raw payload, whose data types are loaded
+    /// once the selector is known.
+    advertisement: BleAdvertisement,
+    connectable: bool,
+}
+
+impl ScanEvent {
+    /// Retrieve the advertisement, loaded with the selected data types.
+    /// Non-connectable advertisements are skipped.
+    fn into_advertisement(
+        self,
+        datatype_selector: Option<&Vec<BleDataTypeId>>,
+    ) -> Result<Option<BleAdvertisement>, BluetoothError> {
+        if !self.connectable {
+            return Ok(None);
+        }
+
+        let mut advertisement = self.advertisement;
+        if let Some(datatype_selector) = datatype_selector {
+            advertisement.load_raw_data(datatype_selector)?;
+        }
+
+        Ok(Some(advertisement))
+    }
+}
+
+/// Struct holding the necessary fields for listening to and handling incoming
+/// BLE advertisements.
+struct AdvListener {
+    /// `android.bluetooth.le.BluetoothLeScanner` running the scan.
+    scanner: GlobalRef,
+    /// `NativeScanCallback` sending scan results to `receiver`.
+    callback: GlobalRef,
+    scan_id: jlong,
+    /// Can be polled to consume incoming advertisement events.
+    receiver: ScanQueue<ScanEvent>,
+    /// Settings of the scan, of which the in-range threshold must be checked
+    /// on received advertisements.
+    settings: ScanSettings,
+    /// Sequence number of the next advertisement delivered by the scan.
+    next_sequence_number: u64,
+}
+
+impl AdvListener {
+    /// Wait for the next advertisement delivered by the scan, loaded with
+    /// the selected data types, or `None` once the scan stopped.
+    /// Non-connectable advertisements are skipped, as on other platforms.
+    async fn next_advertisement(
+        &mut self,
+        datatype_selector: Option<&Vec<BleDataTypeId>>,
+    ) -> Result<Option<BleAdvertisement>, BluetoothError> {
+        while let Some(event) = self.receiver.next().await {
+            if let Some(advertisement) =
+                self.deliver(event, datatype_selector)?
+            {
+                return Ok(Some(advertisement));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Stop the scan, closing `receiver`.
+    fn stop(&self) -> Result<(), BluetoothError> {
+        let mut env = attach()?;
+        let res = check_exception(&mut env, |env| {
+            env.call_method(
+                &self.scanner,
+                "stopScan",
+                "(Landroid/bluetooth/le/ScanCallback;)V",
+                &[JValue::Object(self.callback.as_obj())],
+            )
+        });
+        // Drop the sender, closing the queue.
+        SCAN_SENDERS.lock().unwrap().remove(&self.scan_id);
+        info!("Scanner stopped receiving BLE advertisements.");
+
+        res.map(|_| ())
+    }
+
+    /// Convert a scan event with `ScanEvent::into_advertisement()`, numbering
+    /// the advertisements delivered. Advertisements weaker than the in-range
+    /// threshold are skipped.
+    fn deliver(
+        &mut self,
+        event: ScanEvent,
+        datatype_selector: Option<&Vec<BleDataTypeId>>,
+    ) -> Result<Option<BleAdvertisement>, BluetoothError> {
+        let advertisement =
+            event.into_advertisement(datatype_selector)?.filter(
+                |advertisement| self.settings.is_in_range(advertisement.rssi()),
+            );
+        Ok(advertisement.map(|mut advertisement| {
+            advertisement.set_sequence_number(self.next_sequence_number);
+            self.next_sequence_number += 1;
+            advertisement
+        }))
+    }
+}
+
+/// Scan delivering its advertisements to an `AdvertisementStream`, stopped
+/// when the stream is dropped.
+struct StreamedScan(AdvListener);
+
+impl Drop for StreamedScan {
+    fn drop(&mut self) {
+        if let Err(err) = self.0.stop() {
+            warn!("Failed to stop scanning. Error: {}", err);
+        }
+    }
+}
+
+/// Concrete type implementing `api::BleAdapter`, used for Android BLE.
+pub struct BleAdapter {
+    /// `android.bluetooth.BluetoothAdapter`.
+    inner: GlobalRef,
+    listener: Option<AdvListener>,
+}
+
+#[async_trait]
+impl api::BleAdapter for BleAdapter {
+    /// Android doesn't report transient failures here, so `retry_policy` is
+    /// ignored.
+    async fn default_with_retry_policy(
+        _retry_policy: RetryPolicy,
+    ) -> Result<Self, BluetoothError> {
+        let mut env = attach()?;
+        let adapter = default_adapter(&mut env)?;
+        let inner = env.new_global_ref(adapter)?;
+
+        Ok(BleAdapter {
+            inner,
+            listener: None,
+        })
+    }
+
+    /// Android always scans actively, and maps the duty cycle hinted by the
+    /// interval and window to the closest scan mode. Android can't filter on
+    /// the signal strength, so only the in-range threshold is applied, after
+    /// advertisements are received.
+    fn start_scan_with_settings(
+        &mut self,
+        filter: Option<ScanFilter>,
+        settings: ScanSettings,
+    ) -> Result<(), BluetoothError> {
+        if self.listener.is_some() {
+            self.stop_scan()?;
+        }
+
+        self.listener = Some(self.listen(filter, settings)?);
+        Ok(())
+    }
+
+    fn stop_scan(&mut self) -> Result<(), BluetoothError> {
+        if let Some(listener) = self.listener.take() {
+            listener.stop()
+        } else {
+            Err(BluetoothError::FailedPrecondition(String::from(
+                "device scanning hasn't started, please call `start_scan()`",
+            )))
+        }
+    }
+
+    async fn next_advertisement(
+        &mut self,
+        datatype_selector: Option<&Vec<BleDataTypeId>>,
+    ) -> Result<BleAdvertisement, BluetoothError> {
+        if let Some(listener) = &mut self.listener {
+            listener.next_advertisement(datatype_selector).await?.ok_or(
+                BluetoothError::Internal(String::from(
+                    "Event returned from stream is None.",
+                )),
+            )
+        } else {
+            Err(BluetoothError::FailedPrecondition(String::from(
+                "device scanning hasn't started, please call `start_scan()`",
+            )))
+        }
+    }
+
+    fn scan(
+        &self,
+        filter: Option<ScanFilter>,
+        settings: ScanSettings,
+        datatype_selector: Vec<BleDataTypeId>,
+    ) -> Result<AdvertisementStream, BluetoothError> {
+        let scan = StreamedScan(self.listen(filter, settings)?);
+        Ok(AdvertisementStream::new(stream::unfold(
+            (scan, datatype_selector),
+            |(mut scan, datatype_selector)| async move {
+                match scan.0.next_advertisement(Some(&datatype_selector)).await
+                {
+                    Ok(Some(advertisement)) => {
+                        Some((Ok(advertisement), (scan, datatype_selector)))
+                    }
+                    Ok(None) => None,
+                    Err(err) => Some((Err(err), (scan, datatype_selector))),
+                }
+            },
+        )))
+    }
+
+    async fn next_advertisement_batch(
+        &mut self,
+        datatype_selector: Option<&Vec<BleDataTypeId>>,
+    ) -> Result<Vec<BleAdvertisement>, BluetoothError> {
+        let first = self.next_advertisement(datatype_selector).await?;
+        let mut batch = vec![first];
+
+        if let Some(listener) = &mut self.listener {
+            // Take the advertisements already received, e.g. the rest of a
+            // batch delivered by `onBatchScanResults()`, without waiting.
+            while let Some(event) = listener.receiver.try_recv() {
+                if let Some(advertisement) =
+                    listener.deliver(event, datatype_selector)?
+                {
+                    batch.push(advertisement);
+                }
+            }
+        }
+
+        Ok(batch)
+    }
+
+    fn dropped_advertisements(&self) -> u64 {
+        self.listener
+            .as_ref()
+            .map_or(0, |listener| listener.receiver.dropped())
+    }
+
+    async fn watch_state(
+        &mut self,
+    ) -> Result<AdapterStateStream, BluetoothError> {
+        Err(BluetoothError::NotSupported(String::from(
+            "adapter state changes on Android",
+        )))
+    }
+}
+
+/// Pick the scan mode listening at least as often as hinted by `settings`.
+fn scan_mode(settings: &ScanSettings) -> jint {
+    let duty_cycle = settings.duty_cycle();
+    if duty_cycle > 0.25 {
+        SCAN_MODE_LOW_LATENCY
+    } else if duty_cycle > 0.1 {
+        SCAN_MODE_BALANCED
+    } else {
+        SCAN_MODE_LOW_POWER
+    }
+}
+
+/// Build the `java.util.List<android.bluetooth.le.ScanFilter>` applying
+/// `filter`. Empty data matches any service or manufacturer data, as long as
+/// the section is present.
+fn scan_filters<'local>(
+    env: &mut JNIEnv<'local>,
+    filter: &ScanFilter,
+) -> Result<JObject<'local>, BluetoothError> {
+    check_exception(env, |env| {
+        let builder = env.new_object(
+            "android/bluetooth/le/ScanFilter$Builder",
+            "()V",
+            &[],
+        )?;
+
+        if let Some(uuid) = filter.service_data_uuid() {
+            let uuid = env.new_string(Uuid::from_u16(uuid).to_string())?;
+            let uuid = env
+                .call_static_method(
+                    "android/os/ParcelUuid",
+                    "fromString",
+                    "(Ljava/lang/String;)Landroid/os/ParcelUuid;",
+                    &[JValue::Object(&uuid)],
+                )?
+                .l()?;
+            let data = env.new_byte_array(0)?;
+            env.call_method(
+                &builder,
+                "setServiceData",
+                "(Landroid/os/ParcelUuid;[B)\
+                Landroid/bluetooth/le/ScanFilter$Builder;",
+                &[JValue::Object(&uuid), JValue::Object(&data)],
+            )?;
+        }
+
+        if let Some(company_id) = filter.manufacturer_id() {
+            let data = env.new_byte_array(0)?;
+            env.call_method(
+                &builder,
+                "setManufacturerData",
+                "(I[B)Landroid/bluetooth/le/ScanFilter$Builder;",
+                &[JValue::Int(jint::from(company_id)), JValue::Object(&data)],
+            )?;
+        }
+
+        if let Some(addr) = filter.address() {
+            let addr = env.new_string(format_address(u64::from(addr)))?;
+            env.call_method(
+                &builder,
+                "setDeviceAddress",
+                "(Ljava/lang/String;)Landroid/bluetooth/le/ScanFilter$Builder;",
+                &[JValue::Object(&addr)],
+            )?;
+        }
+
+        let scan_filter = env
+            .call_method(
+                &builder,
+                "build",
+                "()Landroid/bluetooth/le/ScanFilter;",
+                &[],
+            )?
+            .l()?;
+        env.call_static_method(
+            "java/util/Collections",
+            "singletonList",
+            "(Ljava/lang/Object;)Ljava/util/List;",
+            &[JValue::Object(&scan_filter)],
+        )?
+        .l()
+    })
+}
+
+impl BleAdapter {
+    /// Start a scan, whose advertisements are received by the returned
+    /// listener.
+    fn listen(
+        &self,
+        filter: Option<ScanFilter>,
+        settings: ScanSettings,
+    ) -> Result<AdvListener, BluetoothError> {
+        let mut env = attach()?;
+        let scanner = check_exception(&mut env, |env| {
+            env.call_method(
+                &self.inner,
+                "getBluetoothLeScanner",
+                "()Landroid/bluetooth/le/BluetoothLeScanner;",
+                &[],
+            )?
+            .l()
+        })?;
+        if scanner.is_null() {
+            return Err(BluetoothError::FailedPrecondition(String::from(
+                "Bluetooth is turned off",
+            )));
+        }
+
+        let scan_settings = check_exception(&mut env, |env| {
+            let builder = env.new_object(
+                "android/bluetooth/le/ScanSettings$Builder",
+                "()V",
+                &[],
+            )?;
+            env.call_method(
+                &builder,
+                "setScanMode",
+                "(I)Landroid/bluetooth/le/ScanSettings$Builder;",
+                &[JValue::Int(scan_mode(&settings))],
+            )?;
+            if let Some(delay) = settings.report_delay() {
+                let delay =
+                    jlong::try_from(delay.as_millis()).unwrap_or(jlong::MAX);
+                env.call_method(
+                    &builder,
+                    "setReportDelay",
+                    "(J)Landroid/bluetooth/le/ScanSettings$Builder;",
+                    &[JValue::Long(delay)],
+                )?;
+            }
+            env.call_method(
+                &builder,
+                "build",
+                "()Landroid/bluetooth/le/ScanSettings;",
+                &[],
+            )?
+            .l()
+        })?;
+
+        let filters = match filter {
+            Some(filter) => scan_filters(&mut env, &filter)?,
+            None => JObject::null(),
+        };
+
+        let scan_id = NEXT_SCAN_ID.fetch_add(1, Ordering::Relaxed);
+        let class = scan_callback_class()?;
+        let callback = check_exception(&mut env, |env| {
+            env.new_object(class, "(J)V", &[JValue::Long(scan_id)])
+        })?;
+
+        let (sender, receiver) = scan_queue(&settings);
+        SCAN_SENDERS.lock().unwrap().insert(scan_id, sender);
+
+        let res = check_exception(&mut env, |env| {
+            env.call_method(
+                &scanner,
+                "startScan",
+                "(Ljava/util/List;Landroid/bluetooth/le/ScanSettings;\
+                Landroid/bluetooth/le/ScanCallback;)V",
+                &[
+                    JValue::Object(&filters),
+                    JValue::Object(&scan_settings),
+                    JValue::Object(&callback),
+                ],
+            )
+        });
+        if let Err(err) = res {
+            SCAN_SENDERS.lock().unwrap().remove(&scan_id);
+            return Err(err);
+        }
+
+        Ok(AdvListener {
+            scanner: env.new_global_ref(scanner)?,
+            callback: env.new_global_ref(callback)?,
+            scan_id,
+            receiver,
+            settings,
+            next_sequence_number: 0,
+        })
+    }
+}
+
+impl Drop for BleAdapter {
+    fn drop(&mut self) {
+        // The scanner keeps the callback alive, so a running scan would
+        // otherwise outlive the adapter.
+        if self.listener.is_some() {
+            if let Err(err) = api::BleAdapter::stop_scan(self) {
+                warn!("Failed to stop scanning. Error: {}", err);
+            }
+        }
+    }
+}
+
+/// Type implementing `api::ClassicAdapter` for Android. Discovery results are
+/// broadcast to `BroadcastReceiver`s, which the Android backend doesn't
+/// register yet, so `default()` always fails and no instance can exist.
+pub enum ClassicAdapter {}
+
+#[async_trait]
+impl api::ClassicAdapter for ClassicAdapter {
+    async fn default() -> Result<Self, BluetoothError> {
+        Err(BluetoothError::NotSupported(String::from(
+            "BT Classic discovery on Android",
+        )))
+    }
+
+    fn start_discovery(&mut self) -> Result<(), BluetoothError> {
+        match *self {}
+    }
+
+    fn stop_discovery(&mut self) -> Result<(), BluetoothError> {
+        match *self {}
+    }
+
+    async fn next_discovered_device(
+        &mut self,
+    ) -> Result<DiscoveredClassicDevice, BluetoothError> {
+        match *self {}
+    }
+}
+
+/// Copy the fields of an `android.bluetooth.le.ScanResult`.
+fn scan_event(
+    env: &mut JNIEnv,
+    result: &JObject,
+) -> Result<ScanEvent, BluetoothError> {
+    let device = check_exception(env, |env| {
+        env.call_method(
+            result,
+            "getDevice",
+            "()Landroid/bluetooth/BluetoothDevice;",
+            &[],
+        )?
+        .l()
+    })?;
+    let addr = JString::from(check_exception(env, |env| {
+        env.call_method(&device, "getAddress", "()Ljava/lang/String;", &[])?
+            .l()
+    })?);
+    let addr = parse_address(&String::from(env.get_string(&addr)?))?;
+    // `getAddressType()` was added in API level 34, older releases only
+    // report public addresses through `ScanResult`.
+    let kind = match check_exception(env, |env| {
+        env.call_method(&device, "getAddressType", "()I", &[])?.i()
+    }) {
+        Ok(kind) => address_kind(kind)?,
+        Err(_) => BleAddressKind::Public,
+    };
+
+    let rssi = check_exception(env, |env| {
+        env.call_method(result, "getRssi", "()I", &[])?.i()
+    })?;
+    let tx_power = check_exception(env, |env| {
+        env.call_method(result, "getTxPower", "()I", &[])?.i()
+    })?;
+    let tx_power = (tx_power != TX_POWER_NOT_PRESENT)
+        .then(|| i16::try_from(tx_power).ok())
+        .flatten();
+    let connectable = check_exception(env, |env| {
+        env.call_method(result, "isConnectable", "()Z", &[])?.z()
+    })?;
+
+    let record = check_exception(env, |env| {
+        env.call_method(
+            result,
+            "getScanRecord",
+            "()Landroid/bluetooth/le/ScanRecord;",
+            &[],
+        )?
+        .l()
+    })?;
+    let raw_data = if record.is_null() {
+        Vec::new()
+    } else {
+        let bytes = JByteArray::from(check_exception(env, |env| {
+            env.call_method(&record, "getBytes", "()[B", &[])?.l()
+        })?);
+        env.convert_byte_array(bytes)?
+    };
+
+    let mut advertisement = BleAdvertisement::new(
+        BleAddress::new(addr, kind),
+        i16::try_from(rssi).ok(),
+        tx_power,
+    );
+    advertisement.set_raw_data(Bytes::from(raw_data));
+
+    Ok(ScanEvent {
+        advertisement,
+        connectable,
+    })
+}
+
+/// Called by `NativeScanCallback.onScanResult()`.
+#[no_mangle]
+pub extern "system" fn Java_com_google_nearby_fastpair_bluetooth_NativeScanCallback_nativeOnScanResult(
+    mut env: JNIEnv,
+    _class: JClass,
+    scan_id: jlong,
+    result: JObject,
+) {
+    let event = match scan_event(&mut env, &result) {
+        Ok(event) => event,
+        Err(err) => {
+            warn!("Failed to read scan result. Error: {}", err);
+            return;
+        }
+    };
+
+    if let Ok(senders) = SCAN_SENDERS.lock() {
+        if let Some(sender) = senders.get(&scan_id) {
+            sender.push(event);
+        }
+    }
+}
+
+/// Called by `NativeScanCallback.onScanFailed()`.
+#[no_mangle]
+pub extern "system" fn Java_com_google_nearby_fastpair_bluetooth_NativeScanCallback_nativeOnScanFailed(
+    _env: JNIEnv,
+    _class: JClass,
+    scan_id: jlong,
+    error_code: jint,
+) {
+    error!("BLE scan failed with error code {}", error_code);
+    // Drop the sender, closing the channel so that `next_advertisement()`
+    // doesn't wait forever.
+    if let Ok(mut senders) = SCAN_SENDERS.lock() {
+        senders.remove(&scan_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // TODO b/288592509 unit tests
+}
@@ -0,0 +1,105 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::common::{BleAddressKind, BluetoothError};
+
+/// `BluetoothDevice.ADDRESS_TYPE_PUBLIC`.
+const ADDRESS_TYPE_PUBLIC: i32 = 0;
+/// `BluetoothDevice.ADDRESS_TYPE_RANDOM`.
+const ADDRESS_TYPE_RANDOM: i32 = 1;
+
+/// Convert an `android.bluetooth.BluetoothDevice` address type into a
+/// `BleAddressKind`.
+pub(crate) fn address_kind(
+    kind: i32,
+) -> Result<BleAddressKind, BluetoothError> {
+    match kind {
+        ADDRESS_TYPE_PUBLIC => Ok(BleAddressKind::Public),
+        ADDRESS_TYPE_RANDOM => Ok(BleAddressKind::Random),
+        _ => Err(BluetoothError::BadTypeConversion(format!(
+            "Attempting to construct `BleAddressKind` with device \
+            advertising invalid address type {}.",
+            kind,
+        ))),
+    }
+}
+
+/// Parse an address formatted like `BluetoothDevice.getAddress()`, e.g.
+/// "00:11:22:AA:BB:CC", most significant byte first.
+pub(crate) fn parse_address(addr: &str) -> Result<u64, BluetoothError> {
+    let bytes = addr
+        .split(':')
+        .map(|byte| u8::from_str_radix(byte, 16))
+        .collect::<Result<Vec<_>, _>>()
+        .ok()
+        .filter(|bytes| bytes.len() == 6)
+        .ok_or_else(|| {
+            BluetoothError::BadTypeConversion(format!(
+                "invalid Bluetooth address {}",
+                addr
+            ))
+        })?;
+
+    Ok(bytes
+        .into_iter()
+        .fold(0, |addr, byte| (addr << 8) | u64::from(byte)))
+}
+
+/// Format an address the way `BluetoothAdapter.getRemoteDevice()` expects.
+pub(crate) fn format_address(addr: u64) -> String {
+    (0..6)
+        .rev()
+        .map(|i| format!("{:02X}", (addr >> (8 * i)) as u8))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn address_kind_from_android() {
+        assert_eq!(address_kind(0).unwrap(), BleAddressKind::Public);
+        assert_eq!(address_kind(1).unwrap(), BleAddressKind::Random);
+        assert!(matches!(
+            address_kind(0xFFFF),
+            Err(BluetoothError::BadTypeConversion(_))
+        ));
+    }
+
+    #[test]
+    fn parse_valid_address() {
+        assert_eq!(parse_address("11:22:33:44:55:66").unwrap(), 0x112233445566);
+        assert_eq!(parse_address("aa:bb:cc:dd:ee:ff").unwrap(), 0xAABBCCDDEEFF);
+    }
+
+    #[test]
+    fn parse_invalid_address() {
+        assert!(parse_address("").is_err());
+        assert!(parse_address("11:22:33:44:55").is_err());
+        assert!(parse_address("11:22:33:44:55:66:77").is_err());
+        assert!(parse_address("11:22:33:44:55:GG").is_err());
+    }
+
+    #[test]
+    fn format_address_round_trip() {
+        assert_eq!(format_address(0x112233445566), "11:22:33:44:55:66");
+        assert_eq!(format_address(0x0A), "00:00:00:00:00:0A");
+        assert_eq!(
+            parse_address(&format_address(0xAABBCCDDEEFF)).unwrap(),
+            0xAABBCCDDEEFF
+        );
+    }
+}
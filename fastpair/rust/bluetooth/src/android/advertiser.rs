@@ -0,0 +1,46 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+
+use crate::{
+    api,
+    common::{AdvertisementPayload, AdvertisingParameters, BluetoothError},
+};
+
+/// Type implementing `api::BleAdvertiser` for Android. Advertising isn't
+/// supported by the Android backend yet, so `default()` always fails and no
+/// instance can exist.
+pub enum BleAdvertiser {}
+
+#[async_trait]
+impl api::BleAdvertiser for BleAdvertiser {
+    async fn default() -> Result<Self, BluetoothError> {
+        Err(BluetoothError::NotSupported(String::from(
+            "BLE advertising on Android",
+        )))
+    }
+
+    fn start_advertising_with_parameters(
+        &mut self,
+        _payload: AdvertisementPayload,
+        _parameters: AdvertisingParameters,
+    ) -> Result<(), BluetoothError> {
+        match *self {}
+    }
+
+    fn stop_advertising(&mut self) -> Result<(), BluetoothError> {
+        match *self {}
+    }
+}
@@ -0,0 +1,374 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use jni::{
+    objects::{GlobalRef, JObject, JObjectArray, JString},
+    sys::jint,
+    JNIEnv,
+};
+use tracing::info;
+
+use super::{
+    address::{address_kind, parse_address},
+    jvm::{
+        attach, check_exception, default_adapter, device_name, device_uuids,
+        remote_device,
+    },
+    rfcomm::connect_rfcomm,
+};
+use crate::{
+    api,
+    common::{
+        BleAddress, BleAddressKind, BluetoothError, ClassicAddress,
+        ConnectionStatusStream, DeviceAddress, PairedDevice, PairingResult,
+        ProtectionLevel, RetryPolicy, RfcommSocket, SdpRecord, Uuid,
+    },
+};
+
+/// `BluetoothDevice.BOND_NONE`.
+const BOND_NONE: jint = 10;
+/// `BluetoothDevice.BOND_BONDING`.
+const BOND_BONDING: jint = 11;
+/// `BluetoothDevice.BOND_BONDED`.
+const BOND_BONDED: jint = 12;
+
+/// Interval at which the bond state is polled while pairing. Bond state
+/// changes are only broadcast as intents, which can't be received without a
+/// `Context`.
+const BOND_STATE_POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// Maximum time given to the user to accept the pairing dialog.
+const PAIRING_TIMEOUT: Duration = Duration::from_secs(60);
+/// `BluetoothDevice.DEVICE_TYPE_LE`.
+const DEVICE_TYPE_LE: jint = 2;
+
+/// Concrete type implementing `Device`, used for Android BLE.
+pub struct BleDevice {
+    /// `android.bluetooth.BluetoothDevice`.
+    inner: GlobalRef,
+    addr: BleAddress,
+}
+
+/// Concrete type implementing `Device`, used for Android Bluetooth Classic.
+pub struct ClassicDevice {
+    /// `android.bluetooth.BluetoothDevice`.
+    inner: GlobalRef,
+    addr: ClassicAddress,
+}
+
+#[async_trait]
+impl api::BleDevice for BleDevice {
+    /// Android doesn't report transient failures here, so `retry_policy` is
+    /// ignored.
+    async fn new_with_retry_policy(
+        addr: BleAddress,
+        _retry_policy: RetryPolicy,
+    ) -> Result<Self, BluetoothError> {
+        let mut env = attach()?;
+        let device = remote_device(&mut env, u64::from(addr))?;
+        let inner = env.new_global_ref(device)?;
+
+        Ok(BleDevice { inner, addr })
+    }
+
+    fn name(&self) -> Result<String, BluetoothError> {
+        let mut env = attach()?;
+        device_name(&mut env, &self.inner)
+    }
+
+    fn address(&self) -> BleAddress {
+        self.addr
+    }
+
+    async fn rssi(&self) -> Result<i16, BluetoothError> {
+        Err(BluetoothError::NotSupported(String::from(
+            "RSSI of a remote device on Android",
+        )))
+    }
+
+    async fn watch_connection_status(
+        &mut self,
+    ) -> Result<ConnectionStatusStream, BluetoothError> {
+        Err(BluetoothError::NotSupported(String::from(
+            "connection status changes on Android",
+        )))
+    }
+
+    /// Android bonds over LE with devices it discovered through LE scans.
+    async fn pair_with_protection_level(
+        &self,
+        protection_level: ProtectionLevel,
+    ) -> Result<PairingResult, BluetoothError> {
+        create_bond(&self.inner, protection_level).await
+    }
+
+    async fn pair_with_delegate_and_protection_level<
+        D: api::PairingDelegate,
+    >(
+        &self,
+        _delegate: D,
+        protection_level: ProtectionLevel,
+    ) -> Result<PairingResult, BluetoothError> {
+        // Android runs every pairing ceremony through its own pairing dialog,
+        // so there's nothing for the delegate to answer.
+        create_bond(&self.inner, protection_level).await
+    }
+
+    /// Android holds no link for a `BluetoothDevice`, links being owned by
+    /// GATT clients and profile connections, so this only releases the
+    /// reference to it.
+    fn close(self) -> Result<(), BluetoothError> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl api::ClassicDevice for ClassicDevice {
+    /// Android doesn't report transient failures here, so `retry_policy` is
+    /// ignored.
+    async fn new_with_retry_policy(
+        addr: ClassicAddress,
+        _retry_policy: RetryPolicy,
+    ) -> Result<Self, BluetoothError> {
+        let mut env = attach()?;
+        let device = remote_device(&mut env, u64::from(addr))?;
+        let inner = env.new_global_ref(device)?;
+
+        Ok(ClassicDevice { inner, addr })
+    }
+
+    fn name(&self) -> Result<String, BluetoothError> {
+        let mut env = attach()?;
+        device_name(&mut env, &self.inner)
+    }
+
+    fn address(&self) -> ClassicAddress {
+        self.addr
+    }
+
+    async fn rssi(&self) -> Result<i16, BluetoothError> {
+        Err(BluetoothError::NotSupported(String::from(
+            "RSSI of a remote device on Android",
+        )))
+    }
+
+    /// Android only exposes the service class UUIDs cached from the last SDP
+    /// query, without their RFCOMM channel.
+    async fn discover_services(
+        &self,
+    ) -> Result<Vec<SdpRecord>, BluetoothError> {
+        let mut env = attach()?;
+        let uuids = device_uuids(&mut env, &self.inner)?;
+
+        Ok(uuids
+            .into_iter()
+            .map(|uuid| SdpRecord::new(uuid, None))
+            .collect())
+    }
+
+    async fn connect_rfcomm(
+        &self,
+        service_uuid: Uuid,
+    ) -> Result<RfcommSocket, BluetoothError> {
+        connect_rfcomm(&self.inner, service_uuid).await
+    }
+
+    async fn pair_with_protection_level(
+        &self,
+        protection_level: ProtectionLevel,
+    ) -> Result<PairingResult, BluetoothError> {
+        create_bond(&self.inner, protection_level).await
+    }
+
+    async fn pair_with_delegate_and_protection_level<
+        D: api::PairingDelegate,
+    >(
+        &self,
+        _delegate: D,
+        protection_level: ProtectionLevel,
+    ) -> Result<PairingResult, BluetoothError> {
+        // Android runs every pairing ceremony through its own pairing dialog,
+        // so there's nothing for the delegate to answer.
+        create_bond(&self.inner, protection_level).await
+    }
+
+    /// Android holds no link for a `BluetoothDevice`, links being owned by
+    /// GATT clients and profile connections, so this only releases the
+    /// reference to it.
+    fn close(self) -> Result<(), BluetoothError> {
+        Ok(())
+    }
+}
+
+/// List the devices bonded with the default adapter. Dual-mode devices are
+/// listed once, as BT Classic devices.
+pub async fn list_paired_devices() -> Result<Vec<PairedDevice>, BluetoothError>
+{
+    let mut env = attach()?;
+    let adapter = default_adapter(&mut env)?;
+    let bonded = check_exception(&mut env, |env| {
+        env.call_method(&adapter, "getBondedDevices", "()Ljava/util/Set;", &[])?
+            .l()
+    })?;
+    // `getBondedDevices()` returns null while Bluetooth is turned off.
+    if bonded.is_null() {
+        return Ok(Vec::new());
+    }
+
+    let bonded = JObjectArray::from(check_exception(&mut env, |env| {
+        env.call_method(&bonded, "toArray", "()[Ljava/lang/Object;", &[])?
+            .l()
+    })?);
+    let len = env.get_array_length(&bonded)?;
+    let mut devices = Vec::new();
+    for i in 0..len {
+        let device = env.get_object_array_element(&bonded, i)?;
+        devices.push(paired_device(&mut env, &device)?);
+    }
+
+    Ok(devices)
+}
+
+/// Convert a bonded `android.bluetooth.BluetoothDevice`.
+fn paired_device(
+    env: &mut JNIEnv,
+    device: &JObject,
+) -> Result<PairedDevice, BluetoothError> {
+    let addr = JString::from(check_exception(env, |env| {
+        env.call_method(device, "getAddress", "()Ljava/lang/String;", &[])?
+            .l()
+    })?);
+    let addr = parse_address(&String::from(env.get_string(&addr)?))?;
+    let device_type = check_exception(env, |env| {
+        env.call_method(device, "getType", "()I", &[])?.i()
+    })?;
+
+    let addr = if device_type == DEVICE_TYPE_LE {
+        // `getAddressType()` was added in API level 34, devices bonded on
+        // older releases are assumed to use public addresses.
+        let kind = match check_exception(env, |env| {
+            env.call_method(device, "getAddressType", "()I", &[])?.i()
+        }) {
+            Ok(kind) => address_kind(kind)?,
+            Err(_) => BleAddressKind::Public,
+        };
+        DeviceAddress::Ble(BleAddress::new(addr, kind))
+    } else {
+        DeviceAddress::Classic(ClassicAddress::from(addr))
+    };
+    let name = device_name(env, device)?;
+
+    Ok(PairedDevice::new(addr, (!name.is_empty()).then_some(name)))
+}
+
+/// Bond with a device, waiting for the ceremony which Android runs through
+/// its own pairing dialog. Bonds are always encrypted, but Android doesn't
+/// tell whether they are authenticated, so they are reported with
+/// `ProtectionLevel::Encryption` and requiring more fails.
+async fn create_bond(
+    device: &GlobalRef,
+    protection_level: ProtectionLevel,
+) -> Result<PairingResult, BluetoothError> {
+    if protection_level > ProtectionLevel::Encryption {
+        return Err(BluetoothError::NotSupported(String::from(
+            "checking that a bond is authenticated on Android",
+        )));
+    }
+
+    {
+        let mut env = attach()?;
+        match bond_state(&mut env, device)? {
+            BOND_BONDED => {
+                info!("Device already paired");
+                return Ok(PairingResult::AlreadyPaired);
+            }
+            BOND_BONDING => {
+                info!("Device pairing already in progress");
+                return Ok(PairingResult::AlreadyInProgress);
+            }
+            _ => (),
+        }
+
+        let started = check_exception(&mut env, |env| {
+            env.call_method(device, "createBond", "()Z", &[])?.z()
+        })?;
+        if !started {
+            return Err(BluetoothError::PairingFailed(String::from(
+                "failed to start pairing",
+            )));
+        }
+    }
+
+    // Wait for the pairing ceremony, which Android runs through its own
+    // pairing dialog, without blocking the executor.
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    let device = device.clone();
+    thread::spawn(move || {
+        let _ = sender.send(wait_for_bond(&device));
+    });
+    let status = receiver.await.map_err(|_| {
+        BluetoothError::Internal(String::from(
+            "pairing thread exited without a result",
+        ))
+    })??;
+
+    match status {
+        PairingResult::Failure(msg) => Err(BluetoothError::PairingFailed(msg)),
+        _ => Ok(status),
+    }
+}
+
+fn bond_state(
+    env: &mut JNIEnv,
+    device: &GlobalRef,
+) -> Result<jint, BluetoothError> {
+    check_exception(env, |env| {
+        env.call_method(device, "getBondState", "()I", &[])?.i()
+    })
+}
+
+/// Poll the bond state of a device until bonding completes.
+fn wait_for_bond(device: &GlobalRef) -> Result<PairingResult, BluetoothError> {
+    let mut env = attach()?;
+    let deadline = Instant::now() + PAIRING_TIMEOUT;
+
+    loop {
+        match bond_state(&mut env, device)? {
+            BOND_BONDED => {
+                break Ok(PairingResult::Success(ProtectionLevel::Encryption))
+            }
+            BOND_NONE => {
+                break Ok(PairingResult::Failure(String::from(
+                    "the device rejected or canceled the pairing.",
+                )))
+            }
+            _ if Instant::now() >= deadline => {
+                break Ok(PairingResult::Failure(String::from(
+                    "the pairing process timed out before it could complete.",
+                )))
+            }
+            _ => thread::sleep(BOND_STATE_POLL_INTERVAL),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // TODO b/288592509 unit tests
+}
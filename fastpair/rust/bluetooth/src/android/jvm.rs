@@ -0,0 +1,200 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::OnceLock;
+
+use jni::{
+    errors::Error,
+    objects::{GlobalRef, JObject, JObjectArray, JValue},
+    AttachGuard, JNIEnv, JavaVM,
+};
+
+use super::address::format_address;
+use crate::common::{BluetoothError, Uuid};
+
+/// Java class receiving `android.bluetooth.le.ScanCallback` events and
+/// forwarding them to native code, see `NativeScanCallback.java`.
+const SCAN_CALLBACK_CLASS: &str =
+    "com/google/nearby/fastpair/bluetooth/NativeScanCallback";
+
+/// JVM state shared by every Android backend object.
+struct JvmContext {
+    vm: JavaVM,
+    /// `FindClass` only sees system classes when called from a native thread,
+    /// so application classes must be resolved ahead of time.
+    scan_callback_class: GlobalRef,
+}
+
+static JVM_CONTEXT: OnceLock<JvmContext> = OnceLock::new();
+
+/// Initialize the Android backend. This must be called once from a thread
+/// created by the JVM (e.g. from `JNI_OnLoad` or a native method) before any
+/// `Platform` function is used.
+pub fn init_android(env: &mut JNIEnv) -> Result<(), BluetoothError> {
+    let vm = env.get_java_vm()?;
+    let class =
+        check_exception(env, |env| env.find_class(SCAN_CALLBACK_CLASS))?;
+    let scan_callback_class = env.new_global_ref(class)?;
+
+    JVM_CONTEXT
+        .set(JvmContext {
+            vm,
+            scan_callback_class,
+        })
+        .map_err(|_| {
+            BluetoothError::FailedPrecondition(String::from(
+                "Android backend is already initialized",
+            ))
+        })
+}
+
+fn context() -> Result<&'static JvmContext, BluetoothError> {
+    JVM_CONTEXT.get().ok_or_else(|| {
+        BluetoothError::FailedPrecondition(String::from(
+            "Android backend isn't initialized, please call `init_android()`",
+        ))
+    })
+}
+
+/// Attach the current thread to the JVM, detaching it again when the returned
+/// guard is dropped if it wasn't attached before.
+pub(crate) fn attach() -> Result<AttachGuard<'static>, BluetoothError> {
+    Ok(context()?.vm.attach_current_thread()?)
+}
+
+/// Retrieve the class used to receive scan results.
+pub(crate) fn scan_callback_class() -> Result<&'static GlobalRef, BluetoothError>
+{
+    Ok(&context()?.scan_callback_class)
+}
+
+/// Run a JNI call, turning a thrown Java exception into a `BluetoothError`.
+/// The exception is cleared, since no further JNI calls can be made while it
+/// is pending.
+pub(crate) fn check_exception<'local, T>(
+    env: &mut JNIEnv<'local>,
+    f: impl FnOnce(&mut JNIEnv<'local>) -> Result<T, Error>,
+) -> Result<T, BluetoothError> {
+    match f(env) {
+        Err(Error::JavaException) => {
+            let throwable = env.exception_occurred()?;
+            env.exception_clear()?;
+            let description = env
+                .call_method(
+                    &throwable,
+                    "toString",
+                    "()Ljava/lang/String;",
+                    &[],
+                )
+                .and_then(|val| val.l())
+                .and_then(|val| Ok(env.get_string(&val.into())?.into()))
+                .unwrap_or_else(|_| String::from("unknown Java exception"));
+            // `toString()` itself might have thrown.
+            env.exception_clear()?;
+
+            Err(BluetoothError::System(description))
+        }
+        res => Ok(res?),
+    }
+}
+
+/// Retrieve the system-default `android.bluetooth.BluetoothAdapter`.
+pub(crate) fn default_adapter<'local>(
+    env: &mut JNIEnv<'local>,
+) -> Result<JObject<'local>, BluetoothError> {
+    let adapter = check_exception(env, |env| {
+        env.call_static_method(
+            "android/bluetooth/BluetoothAdapter",
+            "getDefaultAdapter",
+            "()Landroid/bluetooth/BluetoothAdapter;",
+            &[],
+        )?
+        .l()
+    })?;
+
+    if adapter.is_null() {
+        Err(BluetoothError::NotSupported(String::from(
+            "Bluetooth adapter",
+        )))
+    } else {
+        Ok(adapter)
+    }
+}
+
+/// Retrieve the `android.bluetooth.BluetoothDevice` with the given address.
+pub(crate) fn remote_device<'local>(
+    env: &mut JNIEnv<'local>,
+    addr: u64,
+) -> Result<JObject<'local>, BluetoothError> {
+    let adapter = default_adapter(env)?;
+    let addr = env.new_string(format_address(addr))?;
+
+    check_exception(env, |env| {
+        env.call_method(
+            &adapter,
+            "getRemoteDevice",
+            "(Ljava/lang/String;)Landroid/bluetooth/BluetoothDevice;",
+            &[JValue::Object(&addr)],
+        )?
+        .l()
+    })
+}
+
+/// Retrieve the name of an `android.bluetooth.BluetoothDevice`, which is empty
+/// if the name isn't known yet.
+pub(crate) fn device_name(
+    env: &mut JNIEnv,
+    device: &JObject,
+) -> Result<String, BluetoothError> {
+    let name = check_exception(env, |env| {
+        env.call_method(device, "getName", "()Ljava/lang/String;", &[])?
+            .l()
+    })?;
+
+    if name.is_null() {
+        Ok(String::new())
+    } else {
+        Ok(env.get_string(&name.into())?.into())
+    }
+}
+
+/// Retrieve the service class UUIDs of an `android.bluetooth.BluetoothDevice`,
+/// as cached from its last SDP query.
+pub(crate) fn device_uuids(
+    env: &mut JNIEnv,
+    device: &JObject,
+) -> Result<Vec<Uuid>, BluetoothError> {
+    let uuids = check_exception(env, |env| {
+        env.call_method(device, "getUuids", "()[Landroid/os/ParcelUuid;", &[])?
+            .l()
+    })?;
+    if uuids.is_null() {
+        return Ok(Vec::new());
+    }
+
+    let uuids = JObjectArray::from(uuids);
+    let len = env.get_array_length(&uuids)?;
+    let mut res = Vec::new();
+    for i in 0..len {
+        let uuid = env.get_object_array_element(&uuids, i)?;
+        let uuid = check_exception(env, |env| {
+            env.call_method(&uuid, "toString", "()Ljava/lang/String;", &[])?
+                .l()
+        })?;
+        let uuid: String = env.get_string(&uuid.into())?.into();
+        res.push(uuid.parse()?);
+    }
+
+    Ok(res)
+}
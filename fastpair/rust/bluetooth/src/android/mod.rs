@@ -0,0 +1,32 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// Bluetooth module for Android devices, calling into `android.bluetooth`
+/// through JNI.
+mod adapter;
+mod address;
+mod advertiser;
+mod device;
+mod error;
+mod gatt;
+mod gatt_server;
+mod jvm;
+mod rfcomm;
+
+pub use adapter::*;
+pub use advertiser::*;
+pub use device::*;
+pub use gatt::*;
+pub use gatt_server::*;
+pub use jvm::init_android;
@@ -0,0 +1,218 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{sync::Arc, thread};
+
+use futures::{
+    channel::{
+        mpsc::{self, UnboundedSender},
+        oneshot,
+    },
+    sink, stream, StreamExt,
+};
+use jni::{
+    objects::{GlobalRef, JObject, JValue},
+    sys::jint,
+    JNIEnv,
+};
+use tracing::warn;
+
+use super::jvm::{attach, check_exception};
+use crate::common::{BluetoothError, RfcommSocket, Uuid};
+
+/// Size of the buffer incoming data is read into, in bytes.
+const READ_BUFFER_SIZE: jint = 1024;
+
+/// `android.bluetooth.BluetoothSocket`, closed when dropped, which makes the
+/// read pending on it fail.
+struct Socket(GlobalRef);
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        let res = attach().and_then(|mut env| {
+            check_exception(&mut env, |env| {
+                env.call_method(&self.0, "close", "()V", &[])?.v()
+            })
+        });
+        if let Err(err) = res {
+            warn!("Failed to close RFCOMM socket: {}", err);
+        }
+    }
+}
+
+/// Open an RFCOMM connection to the service `service_uuid` of `device`, an
+/// `android.bluetooth.BluetoothDevice`. The connection is authenticated and
+/// encrypted, which requires the device to be bonded.
+pub(crate) async fn connect_rfcomm(
+    device: &GlobalRef,
+    service_uuid: Uuid,
+) -> Result<RfcommSocket, BluetoothError> {
+    let socket = {
+        let mut env = attach()?;
+        let socket = create_socket(&mut env, device, service_uuid)?;
+        Arc::new(Socket(env.new_global_ref(socket)?))
+    };
+
+    // `BluetoothSocket.connect()` blocks until the connection is made, so run
+    // it on its own thread, without blocking the executor.
+    let (sender, receiver) = oneshot::channel();
+    let inner = socket.0.clone();
+    thread::spawn(move || {
+        let _ = sender.send(connect(&inner));
+    });
+    let (input, output) = receiver.await.map_err(|_| {
+        BluetoothError::Internal(String::from(
+            "RFCOMM connection thread exited without a result",
+        ))
+    })??;
+
+    // Reads block as well, so they run on their own thread, which exits once
+    // the socket is closed.
+    let (data_sender, data_receiver) = mpsc::unbounded();
+    thread::spawn(move || read_loop(&input, data_sender));
+
+    let incoming = stream::unfold(
+        (data_receiver, socket.clone()),
+        |(mut receiver, socket)| async move {
+            receiver.next().await.map(|data| (data, (receiver, socket)))
+        },
+    );
+    // Writes only block while the send buffer of the socket is full.
+    let outgoing = sink::unfold(
+        (output, socket),
+        |(output, socket), data: Vec<u8>| async move {
+            write(&output, &data)?;
+            Ok((output, socket))
+        },
+    );
+
+    Ok(RfcommSocket::new(incoming, outgoing))
+}
+
+/// Create an unconnected `android.bluetooth.BluetoothSocket` to the service
+/// `service_uuid` of `device`.
+fn create_socket<'local>(
+    env: &mut JNIEnv<'local>,
+    device: &GlobalRef,
+    service_uuid: Uuid,
+) -> Result<JObject<'local>, BluetoothError> {
+    let uuid = env.new_string(service_uuid.to_string())?;
+
+    check_exception(env, |env| {
+        let uuid = env
+            .call_static_method(
+                "java/util/UUID",
+                "fromString",
+                "(Ljava/lang/String;)Ljava/util/UUID;",
+                &[JValue::Object(&uuid)],
+            )?
+            .l()?;
+        env.call_method(
+            device,
+            "createRfcommSocketToServiceRecord",
+            "(Ljava/util/UUID;)Landroid/bluetooth/BluetoothSocket;",
+            &[JValue::Object(&uuid)],
+        )?
+        .l()
+    })
+}
+
+/// Connect `socket`, then retrieve its `java.io.InputStream` and
+/// `java.io.OutputStream`.
+fn connect(
+    socket: &GlobalRef,
+) -> Result<(GlobalRef, GlobalRef), BluetoothError> {
+    let mut env = attach()?;
+    check_exception(&mut env, |env| {
+        env.call_method(socket, "connect", "()V", &[])?.v()
+    })?;
+
+    let input = check_exception(&mut env, |env| {
+        env.call_method(
+            socket,
+            "getInputStream",
+            "()Ljava/io/InputStream;",
+            &[],
+        )?
+        .l()
+    })?;
+    let output = check_exception(&mut env, |env| {
+        env.call_method(
+            socket,
+            "getOutputStream",
+            "()Ljava/io/OutputStream;",
+            &[],
+        )?
+        .l()
+    })?;
+
+    Ok((env.new_global_ref(input)?, env.new_global_ref(output)?))
+}
+
+/// Send the data read from `input`, a `java.io.InputStream`, through
+/// `sender` until the stream ends or fails, or the receiver is dropped.
+fn read_loop(
+    input: &GlobalRef,
+    sender: UnboundedSender<Result<Vec<u8>, BluetoothError>>,
+) {
+    if let Err(err) = read_until_end(input, &sender) {
+        // Nobody listens anymore if the socket was dropped.
+        let _ = sender.unbounded_send(Err(err));
+    }
+}
+
+fn read_until_end(
+    input: &GlobalRef,
+    sender: &UnboundedSender<Result<Vec<u8>, BluetoothError>>,
+) -> Result<(), BluetoothError> {
+    let mut env = attach()?;
+    let buffer = env.new_byte_array(READ_BUFFER_SIZE)?;
+
+    loop {
+        let len = check_exception(&mut env, |env| {
+            env.call_method(input, "read", "([B)I", &[JValue::Object(&buffer)])?
+                .i()
+        })?;
+        // `read()` returns -1 once the stream ended.
+        if len < 0 {
+            break Ok(());
+        }
+
+        let mut data = env.convert_byte_array(&buffer)?;
+        data.truncate(len as usize);
+        if sender.unbounded_send(Ok(data)).is_err() {
+            break Ok(());
+        }
+    }
+}
+
+/// Write `data` to `output`, a `java.io.OutputStream`, flushing it so that
+/// the data is sent right away.
+fn write(output: &GlobalRef, data: &[u8]) -> Result<(), BluetoothError> {
+    let mut env = attach()?;
+    // Free the array right away, the thread may stay attached for long.
+    env.with_local_frame(1, |env| {
+        let data = env.byte_array_from_slice(data)?;
+        check_exception(env, |env| {
+            env.call_method(
+                output,
+                "write",
+                "([B)V",
+                &[JValue::Object(&data)],
+            )?
+            .v()?;
+            env.call_method(output, "flush", "()V", &[])?.v()
+        })
+    })
+}
@@ -14,24 +14,110 @@
 
 use async_trait::async_trait;
 
-use crate::common::{BleAdvertisement, BleDataTypeId, BluetoothError};
+use crate::common::{
+    AdapterStateStream, AdvertisementStream, BleAdvertisement, BleDataTypeId,
+    BluetoothError, DiscoveredClassicDevice, RetryPolicy, ScanFilter,
+    ScanSettings,
+};
 
 /// Concrete types implementing this trait are Bluetooth Central devices.
 /// They provide methods for retrieving nearby connections and device info.
 #[async_trait]
-pub trait BleAdapter: Sized {
-    /// Retrieve the system-default Bluetooth adapter.
-    async fn default() -> Result<Self, BluetoothError>;
+pub trait BleAdapter: Sized + Send {
+    /// Retrieve the system-default Bluetooth adapter, retrying transient
+    /// failures with the default `RetryPolicy`.
+    async fn default() -> Result<Self, BluetoothError> {
+        Self::default_with_retry_policy(RetryPolicy::default()).await
+    }
+
+    /// Retrieve the system-default Bluetooth adapter, retrying operations
+    /// which fail transiently, e.g. because the radio is busy, as described
+    /// by `retry_policy`. Platforms without such failures ignore it.
+    async fn default_with_retry_policy(
+        retry_policy: RetryPolicy,
+    ) -> Result<Self, BluetoothError>;
 
-    /// Begin scanning for nearby advertisements.
-    fn start_scan(&mut self) -> Result<(), BluetoothError>;
+    /// Begin scanning for nearby advertisements with the default settings,
+    /// only delivering those matching `filter`, if any.
+    fn start_scan(
+        &mut self,
+        filter: Option<ScanFilter>,
+    ) -> Result<(), BluetoothError> {
+        self.start_scan_with_settings(filter, ScanSettings::default())
+    }
+
+    /// Begin scanning for nearby advertisements with the given settings,
+    /// e.g. to scan passively in the background, only delivering those
+    /// matching `filter`, if any.
+    fn start_scan_with_settings(
+        &mut self,
+        filter: Option<ScanFilter>,
+        settings: ScanSettings,
+    ) -> Result<(), BluetoothError>;
 
     /// Stop scanning for nearby advertisements.
     fn stop_scan(&mut self) -> Result<(), BluetoothError>;
 
-    /// Poll next discovered device.
+    /// Begin a scan delivering its advertisements, loaded with the data
+    /// types in `datatype_selector`, to the returned stream rather than to
+    /// `next_advertisement()`. The stream doesn't borrow the adapter, so it
+    /// can be consumed by its own task while other tasks connect to or pair
+    /// with the devices found. The scan runs until the stream is dropped,
+    /// independently of `start_scan()` and `stop_scan()`.
+    fn scan(
+        &self,
+        filter: Option<ScanFilter>,
+        settings: ScanSettings,
+        datatype_selector: Vec<BleDataTypeId>,
+    ) -> Result<AdvertisementStream, BluetoothError>;
+
+    /// Poll next discovered device. Fails with
+    /// `BluetoothError::ScanInterrupted` when the scan had to be restarted
+    /// after stopping unexpectedly, or with `BluetoothError::ScanAborted`
+    /// when it couldn't be.
     async fn next_advertisement(
         &mut self,
         data_selector: Option<&Vec<BleDataTypeId>>,
     ) -> Result<BleAdvertisement, BluetoothError>;
+
+    /// Poll the next batch of discovered devices: wait for an advertisement,
+    /// then take the ones already received along with it, e.g. when the scan
+    /// settings let the system batch them.
+    async fn next_advertisement_batch(
+        &mut self,
+        data_selector: Option<&Vec<BleDataTypeId>>,
+    ) -> Result<Vec<BleAdvertisement>, BluetoothError>;
+
+    /// Retrieve the number of advertisements the current scan dropped
+    /// because they were received while its queue was full, as configured by
+    /// `ScanSettings::with_queue_capacity()`. Zero when not scanning.
+    fn dropped_advertisements(&self) -> u64;
+
+    /// Watch the power state of the adapter. The returned stream yields the
+    /// current state, then every change, until `watch_state()` is called
+    /// again or the adapter is dropped.
+    async fn watch_state(
+        &mut self,
+    ) -> Result<AdapterStateStream, BluetoothError>;
+}
+
+/// Concrete types implementing this trait discover nearby BT Classic devices
+/// through inquiry, e.g. to find headphones which don't advertise over BLE.
+#[async_trait]
+pub trait ClassicAdapter: Sized + Send {
+    /// Retrieve the system-default Bluetooth adapter.
+    async fn default() -> Result<Self, BluetoothError>;
+
+    /// Begin discovering nearby BT Classic devices. Discovery keeps running
+    /// until `stop_discovery()` is called or the adapter is dropped.
+    fn start_discovery(&mut self) -> Result<(), BluetoothError>;
+
+    /// Stop discovering nearby BT Classic devices.
+    fn stop_discovery(&mut self) -> Result<(), BluetoothError>;
+
+    /// Poll the next discovered device. Each device is only yielded once per
+    /// discovery, even if it is found again.
+    async fn next_discovered_device(
+        &mut self,
+    ) -> Result<DiscoveredClassicDevice, BluetoothError>;
 }
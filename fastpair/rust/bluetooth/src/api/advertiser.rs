@@ -0,0 +1,54 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+
+use crate::common::{
+    AdvertisementPayload, AdvertisingParameters, BluetoothError,
+};
+
+/// Concrete types implementing this trait are Bluetooth Broadcaster devices.
+/// They provide methods for broadcasting BLE advertisements to nearby
+/// devices.
+#[async_trait]
+pub trait BleAdvertiser: Sized + Send {
+    /// Retrieve an advertiser using the system-default Bluetooth adapter.
+    async fn default() -> Result<Self, BluetoothError>;
+
+    /// Begin broadcasting the given payload with the default parameters,
+    /// replacing the one currently broadcast, if any.
+    fn start_advertising(
+        &mut self,
+        payload: AdvertisementPayload,
+    ) -> Result<(), BluetoothError> {
+        self.start_advertising_with_parameters(
+            payload,
+            AdvertisingParameters::default(),
+        )
+    }
+
+    /// Begin broadcasting the given payload with the given parameters, e.g.
+    /// to use BT5 extended advertising, replacing the one currently
+    /// broadcast, if any. Parameters the platform can't apply are rejected
+    /// with `BluetoothError::NotSupported`.
+    fn start_advertising_with_parameters(
+        &mut self,
+        payload: AdvertisementPayload,
+        parameters: AdvertisingParameters,
+    ) -> Result<(), BluetoothError>;
+
+    /// Stop broadcasting. Advertising also stops when the advertiser is
+    /// dropped.
+    fn stop_advertising(&mut self) -> Result<(), BluetoothError>;
+}
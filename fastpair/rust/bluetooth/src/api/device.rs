@@ -15,35 +15,115 @@
 use async_trait::async_trait;
 
 use crate::common::{
-    BleAddress, BluetoothError, ClassicAddress, PairingResult,
+    BleAddress, BluetoothError, ClassicAddress, ConnectionStatusStream,
+    PairingResult, ProtectionLevel, RetryPolicy, RfcommSocket, SdpRecord, Uuid,
 };
 
 /// Concrete types implementing this trait represent BLE Peripheral devices.
 /// They provide methods for retrieving device info and running device actions,
 /// such as pairing.
 #[async_trait]
-pub trait BleDevice: Sized {
+pub trait BleDevice: Sized + Send {
     /// Create a new `BleDevice` instance from a `BleAddress`, typically
     /// enabled through locally cached data retrieved from a Bluetooth adapter's
-    /// scanning functionality.
-    async fn new(addr: BleAddress) -> Result<Self, BluetoothError>;
+    /// scanning functionality. Transient failures are retried with the
+    /// default `RetryPolicy`.
+    async fn new(addr: BleAddress) -> Result<Self, BluetoothError> {
+        Self::new_with_retry_policy(addr, RetryPolicy::default()).await
+    }
+
+    /// Create a new `BleDevice` instance from a `BleAddress`, retrying operations
+    /// which fail transiently, e.g. because the device is busy, as described
+    /// by `retry_policy`. Platforms without such failures ignore it.
+    async fn new_with_retry_policy(
+        addr: BleAddress,
+        retry_policy: RetryPolicy,
+    ) -> Result<Self, BluetoothError>;
 
     /// Retrieve the name advertised by this device.
     fn name(&self) -> Result<String, BluetoothError>;
 
     /// Retrieve this device's Bluetooth address information.
     fn address(&self) -> BleAddress;
+
+    /// Retrieve the current signal strength of this device in dBm, e.g. to
+    /// check its proximity once it has stopped advertising after connecting.
+    async fn rssi(&self) -> Result<i16, BluetoothError>;
+
+    /// Watch the connection status of this device, e.g. to confirm that
+    /// pairing led to a profile connection. Calling this method again ends
+    /// the previously returned stream.
+    async fn watch_connection_status(
+        &mut self,
+    ) -> Result<ConnectionStatusStream, BluetoothError>;
+
+    /// Attempt bonding with the peripheral device over LE, using LE Secure
+    /// Connections when supported. Only ceremonies that don't require user
+    /// interaction are accepted.
+    async fn pair(&self) -> Result<PairingResult, BluetoothError> {
+        self.pair_with_protection_level(ProtectionLevel::None).await
+    }
+
+    /// Attempt bonding with the peripheral device over LE, failing unless
+    /// the link gets at least `protection_level`. Only ceremonies that don't
+    /// require user interaction are accepted.
+    async fn pair_with_protection_level(
+        &self,
+        protection_level: ProtectionLevel,
+    ) -> Result<PairingResult, BluetoothError>;
+
+    /// Attempt bonding with the peripheral device over LE, letting
+    /// `delegate` take part in ceremonies that require user interaction,
+    /// such as passkey entry or numeric comparison.
+    async fn pair_with_delegate<D: PairingDelegate>(
+        &self,
+        delegate: D,
+    ) -> Result<PairingResult, BluetoothError> {
+        self.pair_with_delegate_and_protection_level(
+            delegate,
+            ProtectionLevel::None,
+        )
+        .await
+    }
+
+    /// Attempt bonding with the peripheral device over LE, letting
+    /// `delegate` take part in ceremonies that require user interaction,
+    /// and failing unless the link gets at least `protection_level`, e.g.
+    /// `ProtectionLevel::EncryptionAndAuthentication` for Fast Pair.
+    async fn pair_with_delegate_and_protection_level<D: PairingDelegate>(
+        &self,
+        delegate: D,
+        protection_level: ProtectionLevel,
+    ) -> Result<PairingResult, BluetoothError>;
+
+    /// Close this device, releasing its handle to the system, which drops
+    /// the link once nothing else uses it, e.g. before handing the device
+    /// over to the audio stack after pairing. Unlike dropping the device,
+    /// failures are reported. Streams returned by `watch_connection_status()`
+    /// end.
+    fn close(self) -> Result<(), BluetoothError>;
 }
 
 /// Concrete types implementing this trait represent BT Classic Peripheral
 /// devices. They provide methods for retrieving device info and running device
 /// actions, such as pairing.
 #[async_trait]
-pub trait ClassicDevice: Sized {
+pub trait ClassicDevice: Sized + Send {
     /// Create a new `ClassicDevice` instance from a `ClassicAddress`, typically
     /// enabled through locally cached data retrieved from a Bluetooth adapter's
-    /// scanning functionality.
-    async fn new(addr: ClassicAddress) -> Result<Self, BluetoothError>;
+    /// scanning functionality. Transient failures are retried with the
+    /// default `RetryPolicy`.
+    async fn new(addr: ClassicAddress) -> Result<Self, BluetoothError> {
+        Self::new_with_retry_policy(addr, RetryPolicy::default()).await
+    }
+
+    /// Create a new `ClassicDevice` instance from a `ClassicAddress`, retrying operations
+    /// which fail transiently, e.g. because the device is busy, as described
+    /// by `retry_policy`. Platforms without such failures ignore it.
+    async fn new_with_retry_policy(
+        addr: ClassicAddress,
+        retry_policy: RetryPolicy,
+    ) -> Result<Self, BluetoothError>;
 
     /// Retrieve the name advertised by this device.
     fn name(&self) -> Result<String, BluetoothError>;
@@ -51,6 +131,80 @@ pub trait ClassicDevice: Sized {
     /// Retrieve this device's Bluetooth address information.
     fn address(&self) -> ClassicAddress;
 
-    /// Attempt pairing with the peripheral device.
-    async fn pair(&self) -> Result<PairingResult, BluetoothError>;
+    /// Retrieve the current signal strength of this device in dBm.
+    async fn rssi(&self) -> Result<i16, BluetoothError>;
+
+    /// Discover the services offered by this device through SDP, e.g. to
+    /// find the RFCOMM channel of the Fast Pair Message Stream service.
+    async fn discover_services(&self)
+        -> Result<Vec<SdpRecord>, BluetoothError>;
+
+    /// Open an RFCOMM connection to the service of this device identified by
+    /// `service_uuid`, e.g. the Fast Pair Message Stream. The device is
+    /// typically paired beforehand.
+    async fn connect_rfcomm(
+        &self,
+        service_uuid: Uuid,
+    ) -> Result<RfcommSocket, BluetoothError>;
+
+    /// Attempt pairing with the peripheral device. Only ceremonies that don't
+    /// require user interaction are accepted.
+    async fn pair(&self) -> Result<PairingResult, BluetoothError> {
+        self.pair_with_protection_level(ProtectionLevel::None).await
+    }
+
+    /// Attempt pairing with the peripheral device, failing unless the link
+    /// gets at least `protection_level`. Only ceremonies that don't require
+    /// user interaction are accepted.
+    async fn pair_with_protection_level(
+        &self,
+        protection_level: ProtectionLevel,
+    ) -> Result<PairingResult, BluetoothError>;
+
+    /// Attempt pairing with the peripheral device, letting `delegate` take
+    /// part in ceremonies that require user interaction, such as PIN entry or
+    /// numeric comparison.
+    async fn pair_with_delegate<D: PairingDelegate>(
+        &self,
+        delegate: D,
+    ) -> Result<PairingResult, BluetoothError> {
+        self.pair_with_delegate_and_protection_level(
+            delegate,
+            ProtectionLevel::None,
+        )
+        .await
+    }
+
+    /// Attempt pairing with the peripheral device, letting `delegate` take
+    /// part in ceremonies that require user interaction, and failing unless
+    /// the link gets at least `protection_level`, e.g.
+    /// `ProtectionLevel::EncryptionAndAuthentication` for Fast Pair.
+    async fn pair_with_delegate_and_protection_level<D: PairingDelegate>(
+        &self,
+        delegate: D,
+        protection_level: ProtectionLevel,
+    ) -> Result<PairingResult, BluetoothError>;
+
+    /// Close this device, releasing its handle to the system, which drops
+    /// the link once nothing else uses it, e.g. before handing the device
+    /// over to the audio stack after pairing. Unlike dropping the device,
+    /// failures are reported.
+    fn close(self) -> Result<(), BluetoothError>;
+}
+
+/// Implemented by callers of `ClassicDevice::pair_with_delegate()` to answer
+/// the requests of the pairing ceremony, typically by prompting the user.
+/// Methods are called from a system thread and may block until the user
+/// answers.
+pub trait PairingDelegate: Send + Sync + 'static {
+    /// Confirm that `passkey` matches the one displayed by the peripheral
+    /// (numeric comparison). Returning `false` rejects the pairing.
+    fn confirm_passkey(&self, passkey: u32) -> bool;
+
+    /// Provide the PIN displayed by the peripheral, or `None` to reject the
+    /// pairing.
+    fn provide_pin(&self) -> Option<String>;
+
+    /// Display `pin` so that the user can enter it on the peripheral.
+    fn display_pin(&self, pin: &str);
 }
@@ -0,0 +1,207 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+
+use crate::common::{
+    parse_device_info_string, BatteryInfo, BleAddress, BluetoothError,
+    CharacteristicValueStream, ConnectionPriority, DeviceInfo,
+    GattCharacteristic, GattService, ServicesChangedStream, Uuid, WriteType,
+};
+
+/// UUID of the standard Battery Service.
+const BATTERY_SERVICE_UUID: Uuid = Uuid::from_u16(0x180F);
+/// UUID of the Battery Level characteristic of the Battery Service.
+const BATTERY_LEVEL_UUID: Uuid = Uuid::from_u16(0x2A19);
+/// UUID of the standard Device Information Service.
+const DEVICE_INFORMATION_SERVICE_UUID: Uuid = Uuid::from_u16(0x180A);
+/// UUIDs of the characteristics of the Device Information Service.
+const MANUFACTURER_NAME_UUID: Uuid = Uuid::from_u16(0x2A29);
+const MODEL_NUMBER_UUID: Uuid = Uuid::from_u16(0x2A24);
+const FIRMWARE_REVISION_UUID: Uuid = Uuid::from_u16(0x2A26);
+
+/// Concrete types implementing this trait are GATT clients connected to the
+/// GATT server of a BLE Peripheral device. They provide methods for
+/// discovering the services and characteristics offered by the peripheral,
+/// accessing characteristic values and subscribing to their changes.
+#[async_trait]
+pub trait GattClient: Sized + Send {
+    /// Connect to the GATT server of the peripheral with the given address.
+    async fn connect(addr: BleAddress) -> Result<Self, BluetoothError>;
+
+    /// Retrieve the Bluetooth address of the connected peripheral.
+    fn address(&self) -> BleAddress;
+
+    /// Retrieve the ATT MTU negotiated with the peripheral, which bounds the
+    /// size of the values exchanged in a single request.
+    fn current_mtu(&self) -> Result<u16, BluetoothError>;
+
+    /// Ask for an ATT MTU of `mtu` bytes. Returns the MTU actually
+    /// negotiated, which may be lower if the peripheral or the platform don't
+    /// support it.
+    async fn request_mtu(&mut self, mtu: u16) -> Result<u16, BluetoothError>;
+
+    /// Ask for the connection parameters matching `priority`, which stay in
+    /// effect until another priority is requested or the client is dropped.
+    fn request_connection_priority(
+        &mut self,
+        priority: ConnectionPriority,
+    ) -> Result<(), BluetoothError>;
+
+    /// Discover the primary services offered by the peripheral, bypassing any
+    /// cache of the platform. The client returned by `Platform::connect_gatt()`
+    /// keeps the result in memory for as long as it's connected, along with
+    /// the characteristics discovered afterwards, until the services change
+    /// or `clear_discovery_cache()` is called, so that repeated lookups don't
+    /// query the peripheral again.
+    async fn discover_services(
+        &mut self,
+    ) -> Result<Vec<GattService>, BluetoothError>;
+
+    /// Discover the characteristics of a service returned by
+    /// `discover_services()`. The client returned by
+    /// `Platform::connect_gatt()` caches the result along with the services.
+    async fn discover_characteristics(
+        &mut self,
+        service: &GattService,
+    ) -> Result<Vec<GattCharacteristic>, BluetoothError>;
+
+    /// Clear the services and characteristics cached by previous discoveries,
+    /// so that the next discovery queries the peripheral. Does nothing if
+    /// the client doesn't cache discoveries.
+    fn clear_discovery_cache(&mut self) {}
+
+    /// Read the value of a characteristic returned by
+    /// `discover_characteristics()`, bypassing any cached value.
+    async fn read_characteristic(
+        &mut self,
+        characteristic: &GattCharacteristic,
+    ) -> Result<Vec<u8>, BluetoothError>;
+
+    /// Write the value of a characteristic returned by
+    /// `discover_characteristics()`.
+    async fn write_characteristic(
+        &mut self,
+        characteristic: &GattCharacteristic,
+        value: &[u8],
+        write_type: WriteType,
+    ) -> Result<(), BluetoothError>;
+
+    /// Subscribe to the value changes of a characteristic returned by
+    /// `discover_characteristics()`, through notifications if the
+    /// characteristic supports them, or else through indications.
+    async fn subscribe(
+        &mut self,
+        characteristic: &GattCharacteristic,
+    ) -> Result<CharacteristicValueStream, BluetoothError>;
+
+    /// Cancel a subscription made by `subscribe()`, ending its stream.
+    async fn unsubscribe(
+        &mut self,
+        characteristic: &GattCharacteristic,
+    ) -> Result<(), BluetoothError>;
+
+    /// Watch the services offered by the peripheral, which may be rearranged
+    /// e.g. by a firmware update. The returned stream yields each time the
+    /// peripheral indicates Service Changed or its services are enumerated
+    /// again. The services and characteristics discovered so far are then
+    /// invalidated, and using them fails until `discover_services()` is
+    /// called again. Calling this method again ends the previous stream.
+    async fn watch_services_changed(
+        &mut self,
+    ) -> Result<ServicesChangedStream, BluetoothError>;
+
+    /// Read the battery level of the peripheral from its standard Battery
+    /// Service.
+    async fn read_battery_info(
+        &mut self,
+    ) -> Result<BatteryInfo, BluetoothError> {
+        let service = self
+            .discover_services()
+            .await?
+            .into_iter()
+            .find(|service| service.uuid() == BATTERY_SERVICE_UUID)
+            .ok_or_else(|| {
+                BluetoothError::NotSupported(String::from(
+                    "Battery Service on the device",
+                ))
+            })?;
+        let characteristic = self
+            .discover_characteristics(&service)
+            .await?
+            .into_iter()
+            .find(|characteristic| characteristic.uuid() == BATTERY_LEVEL_UUID)
+            .ok_or_else(|| {
+                BluetoothError::NotSupported(String::from(
+                    "Battery Level characteristic on the device",
+                ))
+            })?;
+
+        let value = self.read_characteristic(&characteristic).await?;
+        BatteryInfo::try_from(value.as_slice())
+    }
+
+    /// Read the manufacturer name, model number and firmware revision of the
+    /// peripheral from its standard Device Information Service, e.g. to
+    /// display its firmware version or check which features it supports.
+    async fn read_device_info(&mut self) -> Result<DeviceInfo, BluetoothError> {
+        let service = self
+            .discover_services()
+            .await?
+            .into_iter()
+            .find(|service| service.uuid() == DEVICE_INFORMATION_SERVICE_UUID)
+            .ok_or_else(|| {
+                BluetoothError::NotSupported(String::from(
+                    "Device Information Service on the device",
+                ))
+            })?;
+        let characteristics = self.discover_characteristics(&service).await?;
+
+        Ok(DeviceInfo::new(
+            read_device_info_string(
+                self,
+                &characteristics,
+                MANUFACTURER_NAME_UUID,
+            )
+            .await?,
+            read_device_info_string(self, &characteristics, MODEL_NUMBER_UUID)
+                .await?,
+            read_device_info_string(
+                self,
+                &characteristics,
+                FIRMWARE_REVISION_UUID,
+            )
+            .await?,
+        ))
+    }
+}
+
+/// Read the string characteristic of the Device Information Service with the
+/// given UUID, if the peripheral exposes it.
+async fn read_device_info_string<C: GattClient>(
+    client: &mut C,
+    characteristics: &[GattCharacteristic],
+    uuid: Uuid,
+) -> Result<Option<String>, BluetoothError> {
+    match characteristics
+        .iter()
+        .find(|characteristic| characteristic.uuid() == uuid)
+    {
+        Some(characteristic) => {
+            let value = client.read_characteristic(characteristic).await?;
+            parse_device_info_string(&value).map(Some)
+        }
+        None => Ok(None),
+    }
+}
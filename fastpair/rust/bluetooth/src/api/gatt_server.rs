@@ -0,0 +1,62 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+
+use crate::common::{
+    AttErrorCode, BluetoothError, GattCharacteristic, LocalService,
+};
+
+/// Concrete types implementing this trait are GATT servers hosted by the
+/// local device, acting as a BLE Peripheral. They publish services for remote
+/// GATT clients, e.g. to emulate a Fast Pair provider.
+#[async_trait]
+pub trait GattServer: Sized + Send {
+    /// Publish the given services, forwarding the requests of remote clients
+    /// to `handler`. The services are withdrawn when the server is dropped.
+    async fn new<H: GattRequestHandler>(
+        services: &[LocalService],
+        handler: H,
+    ) -> Result<Self, BluetoothError>;
+
+    /// Send a new characteristic value to the clients subscribed to it,
+    /// through a notification or an indication depending on what they
+    /// subscribed to.
+    async fn notify(
+        &mut self,
+        characteristic: &GattCharacteristic,
+        value: &[u8],
+    ) -> Result<(), BluetoothError>;
+}
+
+/// Implemented by users of `GattServer` to answer the requests of remote
+/// clients. Methods are called from a system thread, so they shouldn't block.
+pub trait GattRequestHandler: Send + Sync + 'static {
+    /// Provide the value of a characteristic. Long reads spanning several
+    /// requests are handled by the server, which calls this method for each
+    /// of them.
+    fn on_read(
+        &self,
+        characteristic: &GattCharacteristic,
+    ) -> Result<Vec<u8>, AttErrorCode>;
+
+    /// Handle a write of a characteristic value. For writes without response
+    /// the returned error isn't reported to the client. Long writes aren't
+    /// supported and are rejected by the server.
+    fn on_write(
+        &self,
+        characteristic: &GattCharacteristic,
+        value: &[u8],
+    ) -> Result<(), AttErrorCode>;
+}
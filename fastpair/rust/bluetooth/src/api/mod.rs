@@ -13,7 +13,13 @@
 // limitations under the License.
 
 mod adapter;
+mod advertiser;
 mod device;
+mod gatt;
+mod gatt_server;
 
 pub use adapter::*;
+pub use advertiser::*;
 pub use device::*;
+pub use gatt::*;
+pub use gatt_server::*;
@@ -0,0 +1,202 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{BleDataTypeId, BluetoothError, Uuid};
+
+/// Iterate over the (data type, data) pairs of the AD structures in a raw
+/// advertisement. Each structure starts with a length byte covering the data
+/// type byte and the data. A zero length marks the start of padding. The
+/// payload is received over the air, so malformed structures yield an error
+/// rather than panic.
+/// See: Bluetooth Core Specification, Vol 3, Part C, Section 11.
+pub(crate) fn ad_structures(
+    mut raw_data: &[u8],
+) -> impl Iterator<Item = Result<(u8, &[u8]), BluetoothError>> {
+    std::iter::from_fn(move || {
+        let (&len, rest) = raw_data.split_first()?;
+        if len == 0 {
+            return None;
+        }
+        let len = usize::from(len);
+        if len > rest.len() {
+            raw_data = &[];
+            return Some(Err(BluetoothError::Internal(String::from(
+                "advertisement data structure exceeds advertisement length",
+            ))));
+        }
+        let (structure, rest) = rest.split_at(len);
+        raw_data = rest;
+
+        structure
+            .split_first()
+            .map(|(&datatype, data)| Ok((datatype, data)))
+    })
+}
+
+/// Find the data of the first AD structure of the given type, for data types
+/// which appear at most once in an advertisement.
+pub(crate) fn find_ad_structure(
+    raw_data: &[u8],
+    datatype_id: BleDataTypeId,
+) -> Result<Option<&[u8]>, BluetoothError> {
+    for structure in ad_structures(raw_data) {
+        let (datatype, data) = structure?;
+        if datatype == datatype_id as u8 {
+            return Ok(Some(data));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Iterate over the advertisement's service data as (UUID, data) pairs, the
+/// data borrowing from `raw_data` rather than being copied.
+/// Further Reading:
+/// * Bluetooth Supplement to the Core Specification, Part A, Section 1.11.
+pub(crate) fn parse_service_data_16bit_uuid(
+    raw_data: &[u8],
+) -> impl Iterator<Item = Result<(Uuid, &[u8]), BluetoothError>> {
+    ad_structures(raw_data).filter_map(|structure| {
+        let (datatype, data) = match structure {
+            Ok(structure) => structure,
+            Err(err) => return Some(Err(err)),
+        };
+        if datatype != BleDataTypeId::ServiceData16BitUuid as u8 {
+            return None;
+        }
+        if data.len() < 2 {
+            return Some(Err(BluetoothError::Internal(String::from(
+                "service data is too short to hold a 16-bit UUID",
+            ))));
+        }
+        let (uuid, data) = data.split_at(2);
+        let uuid = Uuid::from_u16(u16::from_le_bytes([uuid[0], uuid[1]]));
+
+        Some(Ok((uuid, data)))
+    })
+}
+
+/// Parse the data of a Flags AD structure, which holds at least one byte.
+/// See: Bluetooth Supplement to the Core Specification, Part A, Section 1.3.
+pub(crate) fn parse_flags(data: &[u8]) -> Result<u8, BluetoothError> {
+    data.first().copied().ok_or_else(|| {
+        BluetoothError::Internal(String::from("flags data type is empty"))
+    })
+}
+
+/// Parse the data of a Complete or Shortened Local Name AD structure, a UTF-8
+/// string which may be truncated mid-character.
+/// See: Bluetooth Supplement to the Core Specification, Part A, Section 1.2.
+pub(crate) fn parse_local_name(data: &[u8]) -> String {
+    String::from_utf8_lossy(data).into_owned()
+}
+
+/// Parse the data of a TX Power Level AD structure, a single signed byte
+/// expressed in dBm.
+/// See: Bluetooth Supplement to the Core Specification, Part A, Section 1.5.
+pub(crate) fn parse_tx_power_level(data: &[u8]) -> Result<i16, BluetoothError> {
+    match data {
+        [tx_power] => Ok(i16::from(*tx_power as i8)),
+        _ => Err(BluetoothError::Internal(format!(
+            "TX power level data type has invalid length {}",
+            data.len()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iterate_ad_structures() {
+        let raw_data = [
+            0x02, 0x01, 0x06, // Flags
+            0x01, 0xFF, // Empty manufacturer specific data
+            0x00, 0x03, // Padding
+        ];
+        let structures: Vec<_> =
+            ad_structures(&raw_data).collect::<Result<_, _>>().unwrap();
+        assert_eq!(structures, vec![(0x01, &[0x06][..]), (0xFF, &[][..])]);
+
+        let mut structures = ad_structures(&[0x02, 0x01, 0x06, 0x05, 0x16]);
+        assert!(matches!(structures.next(), Some(Ok((0x01, [0x06])))));
+        assert!(matches!(
+            structures.next(),
+            Some(Err(BluetoothError::Internal(_)))
+        ));
+        assert!(structures.next().is_none());
+    }
+
+    #[test]
+    fn parse_flags_data() {
+        assert_eq!(parse_flags(&[0x06]).unwrap(), 0x06);
+        assert!(matches!(parse_flags(&[]), Err(BluetoothError::Internal(_))));
+    }
+
+    #[test]
+    fn parse_local_name_data() {
+        assert_eq!(parse_local_name(b"Pixel Buds"), "Pixel Buds");
+        assert_eq!(parse_local_name(&[0x41, 0xC3]), "A\u{FFFD}");
+    }
+
+    #[test]
+    fn parse_tx_power_level_data() {
+        assert_eq!(parse_tx_power_level(&[0xF6]).unwrap(), -10);
+        assert_eq!(parse_tx_power_level(&[0x14]).unwrap(), 20);
+        assert!(parse_tx_power_level(&[]).is_err());
+        assert!(parse_tx_power_level(&[0x01, 0x02]).is_err());
+    }
+
+    #[test]
+    fn parse_service_data() {
+        let raw_data = [
+            0x02, 0x01, 0x06, // Flags
+            0x06, 0x16, 0x2C, 0xFE, 0x01, 0x02, 0x03, // Service data
+            0x03, 0x16, 0x34, 0x12, // Service data without payload
+            0x00, 0x00, // Padding
+        ];
+
+        let service_data = parse_service_data_16bit_uuid(&raw_data)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            service_data,
+            vec![
+                (Uuid::from_u16(0xFE2C), &[0x01, 0x02, 0x03][..]),
+                (Uuid::from_u16(0x1234), &[][..]),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_service_data_without_sections() {
+        assert!(parse_service_data_16bit_uuid(&[]).next().is_none());
+        assert!(parse_service_data_16bit_uuid(&[0x02, 0x01, 0x06])
+            .next()
+            .is_none());
+    }
+
+    #[test]
+    fn parse_truncated_service_data() {
+        assert!(matches!(
+            parse_service_data_16bit_uuid(&[0x06, 0x16, 0x2C, 0xFE]).next(),
+            Some(Err(BluetoothError::Internal(_)))
+        ));
+        assert!(matches!(
+            parse_service_data_16bit_uuid(&[0x02, 0x16, 0x2C]).next(),
+            Some(Err(BluetoothError::Internal(_)))
+        ));
+    }
+}
@@ -0,0 +1,490 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{channel::mpsc::Receiver, Stream, StreamExt};
+
+use super::{BleAddress, BleAdvertisement, BluetoothError};
+
+/// Power state of a Bluetooth adapter.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub enum AdapterState {
+    /// The adapter is turned on and can be used.
+    PoweredOn,
+    /// The adapter is turned off, but can be turned on by the user.
+    PoweredOff,
+    /// The adapter can't be used, e.g. it's disabled by policy or its state
+    /// is unknown.
+    Unavailable,
+}
+
+/// Stream of the states of a Bluetooth adapter, starting with its current
+/// state and followed by every change.
+pub struct AdapterStateStream {
+    receiver: Receiver<AdapterState>,
+}
+
+impl AdapterStateStream {
+    /// Construct a stream yielding the states sent through the channel.
+    #[cfg_attr(not(any(windows, feature = "mock")), allow(dead_code))]
+    pub(crate) fn new(receiver: Receiver<AdapterState>) -> Self {
+        AdapterStateStream { receiver }
+    }
+}
+
+impl Stream for AdapterStateStream {
+    type Item = AdapterState;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_next_unpin(cx)
+    }
+}
+
+/// Stream of the advertisements delivered by a scan started with
+/// `BleAdapter::scan()`, loaded with the selected data types. Errors are
+/// yielded like those of `BleAdapter::next_advertisement()`, and the stream
+/// ends once the scan stops for good, e.g. after being aborted. Dropping the
+/// stream stops the scan.
+pub struct AdvertisementStream {
+    inner: Pin<
+        Box<dyn Stream<Item = Result<BleAdvertisement, BluetoothError>> + Send>,
+    >,
+}
+
+impl AdvertisementStream {
+    /// Construct a stream yielding the advertisements of `stream`, which
+    /// stops its scan when dropped.
+    #[cfg_attr(
+        not(any(windows, target_os = "android", feature = "mock")),
+        allow(dead_code)
+    )]
+    pub(crate) fn new(
+        stream: impl Stream<Item = Result<BleAdvertisement, BluetoothError>>
+            + Send
+            + 'static,
+    ) -> Self {
+        AdvertisementStream {
+            inner: Box::pin(stream),
+        }
+    }
+}
+
+impl Stream for AdvertisementStream {
+    type Item = Result<BleAdvertisement, BluetoothError>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+/// Criteria restricting the advertisements delivered by a scan, applied by the
+/// Bluetooth controller where the platform supports it. An advertisement must
+/// match every criterion that is set.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct ScanFilter {
+    service_data_uuid: Option<u16>,
+    manufacturer_id: Option<u16>,
+    address: Option<BleAddress>,
+}
+
+impl ScanFilter {
+    /// Construct a `ScanFilter` matching every advertisement.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match advertisements with service data for the given 16-bit
+    /// UUID, e.g. 0xFE2C for Fast Pair.
+    pub fn with_service_data_uuid(mut self, uuid: u16) -> Self {
+        self.service_data_uuid = Some(uuid);
+        self
+    }
+
+    /// Only match advertisements with manufacturer specific data for the
+    /// given company identifier.
+    pub fn with_manufacturer_id(mut self, company_id: u16) -> Self {
+        self.manufacturer_id = Some(company_id);
+        self
+    }
+
+    /// Only match advertisements sent from the given address.
+    pub fn with_address(mut self, addr: BleAddress) -> Self {
+        self.address = Some(addr);
+        self
+    }
+
+    /// Retrieve the service data UUID to match, if any.
+    pub fn service_data_uuid(&self) -> Option<u16> {
+        self.service_data_uuid
+    }
+
+    /// Retrieve the manufacturer company identifier to match, if any.
+    pub fn manufacturer_id(&self) -> Option<u16> {
+        self.manufacturer_id
+    }
+
+    /// Retrieve the address to match, if any.
+    pub fn address(&self) -> Option<BleAddress> {
+        self.address
+    }
+
+    /// Check the address criterion, for platforms which can't have the
+    /// controller filter by address.
+    #[cfg_attr(not(any(windows, feature = "mock")), allow(dead_code))]
+    pub(crate) fn matches_address(&self, addr: BleAddress) -> bool {
+        self.address.is_none_or(|address| address == addr)
+    }
+}
+
+/// Scan interval and window of `ScanSettings::background()`, listening 10% of
+/// the time.
+const BACKGROUND_SCAN_INTERVAL: Duration = Duration::from_millis(5120);
+const BACKGROUND_SCAN_WINDOW: Duration = Duration::from_millis(512);
+/// Maximum delay before delivering the advertisements of a background scan.
+const BACKGROUND_REPORT_DELAY: Duration = Duration::from_secs(5);
+/// Number of received advertisements queued by default until the consumer
+/// takes them.
+const DEFAULT_QUEUE_CAPACITY: usize = 16;
+
+/// How the scanner reacts to received advertisements.
+/// See: Bluetooth Core Specification, Vol 6, Part B, Section 4.4.3.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash, Default)]
+pub enum ScanMode {
+    /// Send scan requests to scannable advertisers, to also receive their
+    /// scan responses.
+    #[default]
+    Active,
+    /// Only listen, which uses less power but misses scan responses.
+    Passive,
+}
+
+/// What a scan does with the advertisements received while its queue is
+/// full, i.e. while the consumer doesn't keep up with them.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash, Default)]
+pub enum OverflowPolicy {
+    /// Drop the advertisements received while the queue is full.
+    #[default]
+    DropNewest,
+    /// Drop the oldest queued advertisement to make room, so that the
+    /// consumer gets the latest advertisements once it catches up.
+    DropOldest,
+    /// Grow the queue, never dropping advertisements, at the cost of
+    /// unbounded memory use.
+    Grow,
+}
+
+/// Settings of a scan, trading discovery latency for power consumption.
+/// Interval, window and batching are hints which each platform maps to the
+/// closest setting it offers, or ignores. The default settings scan actively
+/// and continuously, delivering every advertisement as soon as it's received.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct ScanSettings {
+    mode: ScanMode,
+    interval: Option<Duration>,
+    window: Option<Duration>,
+    report_delay: Option<Duration>,
+    in_range_threshold: Option<i16>,
+    out_of_range_threshold: Option<i16>,
+    out_of_range_timeout: Option<Duration>,
+    queue_capacity: Option<usize>,
+    overflow_policy: OverflowPolicy,
+}
+
+impl ScanSettings {
+    /// Construct the default `ScanSettings`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Construct settings suited to long-running background scans, e.g. for
+    /// subsequent pairing: a passive scan with a low duty cycle, whose
+    /// advertisements are batched by the system.
+    pub fn background() -> Self {
+        ScanSettings {
+            mode: ScanMode::Passive,
+            interval: Some(BACKGROUND_SCAN_INTERVAL),
+            window: Some(BACKGROUND_SCAN_WINDOW),
+            report_delay: Some(BACKGROUND_REPORT_DELAY),
+            ..Self::default()
+        }
+    }
+
+    /// Select the scan mode.
+    pub fn with_mode(mut self, mode: ScanMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Hint the scan duty cycle: the controller listens for `window` every
+    /// `interval`. A window shorter than the interval saves power but delays
+    /// discovery.
+    pub fn with_interval_and_window(
+        mut self,
+        interval: Duration,
+        window: Duration,
+    ) -> Self {
+        self.interval = Some(interval);
+        self.window = Some(window.min(interval));
+        self
+    }
+
+    /// Hint that advertisements can be batched and delivered up to `delay`
+    /// after they're received, letting the system sleep in between.
+    pub fn with_report_delay(mut self, delay: Duration) -> Self {
+        self.report_delay = Some(delay);
+        self
+    }
+
+    /// Only deliver the advertisements of a device once its signal reaches
+    /// `in_range` dBm, and until it stays below `out_of_range` dBm for the
+    /// out-of-range timeout, so that the system drops weak advertisements in
+    /// crowded environments. Platforms which can't filter on the signal
+    /// strength themselves only drop advertisements weaker than `in_range`.
+    pub fn with_signal_strength_thresholds(
+        mut self,
+        in_range: i16,
+        out_of_range: i16,
+    ) -> Self {
+        self.in_range_threshold = Some(in_range);
+        self.out_of_range_threshold = Some(out_of_range.min(in_range));
+        self
+    }
+
+    /// Set how long the signal of a device must stay below the out-of-range
+    /// threshold before its advertisements stop being delivered.
+    pub fn with_out_of_range_timeout(mut self, timeout: Duration) -> Self {
+        self.out_of_range_timeout = Some(timeout);
+        self
+    }
+
+    /// Set how many received advertisements are queued until the consumer
+    /// takes them, at least 1 and 16 by default. Advertisements received
+    /// while the queue is full are handled as selected by
+    /// `with_overflow_policy()`, and those dropped are counted by
+    /// `BleAdapter::dropped_advertisements()`.
+    pub fn with_queue_capacity(mut self, capacity: usize) -> Self {
+        self.queue_capacity = Some(capacity.max(1));
+        self
+    }
+
+    /// Select what happens to the advertisements received while the queue is
+    /// full.
+    pub fn with_overflow_policy(mut self, policy: OverflowPolicy) -> Self {
+        self.overflow_policy = policy;
+        self
+    }
+
+    /// Retrieve the scan mode.
+    pub fn mode(&self) -> ScanMode {
+        self.mode
+    }
+
+    /// Retrieve the scan interval hint, if any.
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+
+    /// Retrieve the scan window hint, if any.
+    pub fn window(&self) -> Option<Duration> {
+        self.window
+    }
+
+    /// Retrieve the batching delay hint, if any.
+    pub fn report_delay(&self) -> Option<Duration> {
+        self.report_delay
+    }
+
+    /// Retrieve the signal strength in dBm from which a device is in range,
+    /// if any.
+    pub fn in_range_threshold(&self) -> Option<i16> {
+        self.in_range_threshold
+    }
+
+    /// Retrieve the signal strength in dBm below which a device goes out of
+    /// range, if any.
+    pub fn out_of_range_threshold(&self) -> Option<i16> {
+        self.out_of_range_threshold
+    }
+
+    /// Retrieve the out-of-range timeout, if any.
+    pub fn out_of_range_timeout(&self) -> Option<Duration> {
+        self.out_of_range_timeout
+    }
+
+    /// Retrieve the number of received advertisements queued until the
+    /// consumer takes them.
+    pub fn queue_capacity(&self) -> usize {
+        self.queue_capacity.unwrap_or(DEFAULT_QUEUE_CAPACITY)
+    }
+
+    /// Retrieve what happens to the advertisements received while the queue
+    /// is full.
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.overflow_policy
+    }
+
+    /// Check whether an advertisement received with `rssi` passes the
+    /// in-range threshold, on platforms filtering it in software.
+    /// Advertisements without a signal strength always pass.
+    #[cfg_attr(
+        not(any(target_os = "android", feature = "mock")),
+        allow(dead_code)
+    )]
+    pub(crate) fn is_in_range(&self, rssi: Option<i16>) -> bool {
+        match (self.in_range_threshold, rssi) {
+            (Some(threshold), Some(rssi)) => rssi >= threshold,
+            _ => true,
+        }
+    }
+
+    /// Retrieve the fraction of time spent listening, from 0 to 1. Scans
+    /// without an interval and window hint listen continuously.
+    #[cfg_attr(not(target_os = "android"), allow(dead_code))]
+    pub(crate) fn duty_cycle(&self) -> f64 {
+        match (self.interval, self.window) {
+            (Some(interval), Some(window)) if !interval.is_zero() => {
+                window.as_secs_f64() / interval.as_secs_f64()
+            }
+            _ => 1.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::BleAddressKind;
+
+    #[test]
+    fn adapter_state_stream() {
+        let (mut sender, receiver) = futures::channel::mpsc::channel(2);
+        let mut stream = AdapterStateStream::new(receiver);
+
+        sender.try_send(AdapterState::PoweredOn).unwrap();
+        sender.try_send(AdapterState::PoweredOff).unwrap();
+        drop(sender);
+
+        futures::executor::block_on(async {
+            assert_eq!(stream.next().await, Some(AdapterState::PoweredOn));
+            assert_eq!(stream.next().await, Some(AdapterState::PoweredOff));
+            assert_eq!(stream.next().await, None);
+        });
+    }
+
+    #[test]
+    fn scan_filter_criteria() {
+        let addr = BleAddress::new(0x112233445566, BleAddressKind::Public);
+        let filter = ScanFilter::new()
+            .with_service_data_uuid(0xFE2C)
+            .with_manufacturer_id(0x00E0)
+            .with_address(addr);
+
+        assert_eq!(filter.service_data_uuid(), Some(0xFE2C));
+        assert_eq!(filter.manufacturer_id(), Some(0x00E0));
+        assert_eq!(filter.address(), Some(addr));
+    }
+
+    #[test]
+    fn scan_settings_duty_cycle() {
+        assert_eq!(ScanSettings::new().mode(), ScanMode::Active);
+        assert_eq!(ScanSettings::new().duty_cycle(), 1.0);
+
+        let settings = ScanSettings::new().with_interval_and_window(
+            Duration::from_millis(4000),
+            Duration::from_millis(1000),
+        );
+        assert_eq!(settings.duty_cycle(), 0.25);
+
+        // The window can't be longer than the interval.
+        let settings = ScanSettings::new().with_interval_and_window(
+            Duration::from_millis(100),
+            Duration::from_millis(200),
+        );
+        assert_eq!(settings.window(), Some(Duration::from_millis(100)));
+        assert_eq!(settings.duty_cycle(), 1.0);
+    }
+
+    #[test]
+    fn scan_settings_background() {
+        let settings = ScanSettings::background();
+        assert_eq!(settings.mode(), ScanMode::Passive);
+        assert_eq!(settings.duty_cycle(), 0.1);
+        assert!(settings.report_delay().is_some());
+    }
+
+    #[test]
+    fn scan_settings_signal_strength_thresholds() {
+        let settings = ScanSettings::new();
+        assert_eq!(settings.in_range_threshold(), None);
+        assert!(settings.is_in_range(Some(-100)));
+
+        let settings = ScanSettings::new()
+            .with_signal_strength_thresholds(-70, -80)
+            .with_out_of_range_timeout(Duration::from_secs(2));
+        assert_eq!(settings.in_range_threshold(), Some(-70));
+        assert_eq!(settings.out_of_range_threshold(), Some(-80));
+        assert_eq!(
+            settings.out_of_range_timeout(),
+            Some(Duration::from_secs(2))
+        );
+        assert!(settings.is_in_range(Some(-70)));
+        assert!(!settings.is_in_range(Some(-71)));
+        assert!(settings.is_in_range(None));
+
+        // The out-of-range threshold can't be above the in-range one.
+        let settings =
+            ScanSettings::new().with_signal_strength_thresholds(-70, -60);
+        assert_eq!(settings.out_of_range_threshold(), Some(-70));
+    }
+
+    #[test]
+    fn scan_settings_queue() {
+        let settings = ScanSettings::new();
+        assert_eq!(settings.queue_capacity(), 16);
+        assert_eq!(settings.overflow_policy(), OverflowPolicy::DropNewest);
+
+        let settings = ScanSettings::new()
+            .with_queue_capacity(64)
+            .with_overflow_policy(OverflowPolicy::DropOldest);
+        assert_eq!(settings.queue_capacity(), 64);
+        assert_eq!(settings.overflow_policy(), OverflowPolicy::DropOldest);
+
+        // The queue holds at least one advertisement.
+        assert_eq!(
+            ScanSettings::new().with_queue_capacity(0).queue_capacity(),
+            1
+        );
+    }
+
+    #[test]
+    fn scan_filter_matches_address() {
+        let addr = BleAddress::new(0x112233445566, BleAddressKind::Public);
+        let other = BleAddress::new(0x112233445566, BleAddressKind::Random);
+
+        assert!(ScanFilter::new().matches_address(addr));
+        assert!(ScanFilter::new().with_address(addr).matches_address(addr));
+        assert!(!ScanFilter::new().with_address(addr).matches_address(other));
+    }
+}
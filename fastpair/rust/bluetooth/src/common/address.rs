@@ -12,18 +12,44 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::{fmt, str::FromStr};
+
 use super::BluetoothError;
 
 /// BLE Addresses can either be the peripheral's public MAC address, or various
 /// types of random addresses.
 #[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum BleAddressKind {
     Public,
     Random,
 }
 
+/// Sub-types of BLE random addresses, encoded in the two most significant bits
+/// of the address.
+/// See: Bluetooth Core Specification, Vol 6, Part B, Section 1.3.2.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub enum RandomAddressKind {
+    /// Address which doesn't change until the device power cycles.
+    Static,
+    /// Private address which changes periodically, and can be resolved into
+    /// the device identity by a peer holding its Identity Resolving Key.
+    ResolvablePrivate,
+    /// Private address which changes periodically and can't be resolved.
+    NonResolvablePrivate,
+}
+
 /// Struct representing a 48-bit BLE Address and its type.
 #[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "SerializedBleAddress", into = "SerializedBleAddress")
+)]
 pub struct BleAddress {
     val: [u8; 6],
     kind: BleAddressKind,
@@ -45,6 +71,20 @@ impl BleAddress {
     pub fn get_kind(&self) -> BleAddressKind {
         self.kind
     }
+
+    /// Retrieve the sub-type of a random address. Returns `None` for public
+    /// addresses and random addresses using the reserved sub-type.
+    pub fn random_kind(&self) -> Option<RandomAddressKind> {
+        match self.kind {
+            BleAddressKind::Public => None,
+            BleAddressKind::Random => match self.val[5] >> 6 {
+                0b11 => Some(RandomAddressKind::Static),
+                0b01 => Some(RandomAddressKind::ResolvablePrivate),
+                0b00 => Some(RandomAddressKind::NonResolvablePrivate),
+                _ => None,
+            },
+        }
+    }
 }
 
 /// Function for converting the six LSB of a u64 into a 6-byte array.
@@ -55,6 +95,136 @@ fn u64_to_6lsb(num: u64) -> [u8; 6] {
         .expect("Sanity check, slice length matches array length")
 }
 
+/// Suffix appended to random BLE addresses in their alternate string form,
+/// e.g. "AA:BB:CC:DD:EE:FF (random)".
+const RANDOM_ADDRESS_SUFFIX: &str = " (random)";
+
+/// Format a 6-byte little-endian address as "AA:BB:CC:DD:EE:FF", most
+/// significant byte first.
+fn fmt_address(val: &[u8; 6], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+        f,
+        "{:02X}:{:02X}:{:02X}:{:02X}:{:02X}:{:02X}",
+        val[5], val[4], val[3], val[2], val[1], val[0]
+    )
+}
+
+/// Parse an address in the "AA:BB:CC:DD:EE:FF" form, case insensitive, into
+/// a 6-byte little-endian array.
+fn parse_address(s: &str) -> Result<[u8; 6], BluetoothError> {
+    let err =
+        || BluetoothError::BadTypeConversion(format!("invalid address {}", s));
+
+    let mut val = [0u8; 6];
+    let mut groups = s.split(':');
+    for byte in val.iter_mut().rev() {
+        let group = groups.next().ok_or_else(err)?;
+        if group.len() != 2 || !group.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(err());
+        }
+        *byte = u8::from_str_radix(group, 16).map_err(|_| err())?;
+    }
+    if groups.next().is_some() {
+        return Err(err());
+    }
+
+    Ok(val)
+}
+
+impl fmt::Display for BleAddress {
+    /// Format as "AA:BB:CC:DD:EE:FF", whatever the kind of the address. The
+    /// alternate form, `{:#}`, tells random addresses apart by appending
+    /// " (random)", e.g. for logs.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_address(&self.val, f)?;
+        match self.kind {
+            BleAddressKind::Random if f.alternate() => {
+                f.write_str(RANDOM_ADDRESS_SUFFIX)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl FromStr for BleAddress {
+    type Err = BluetoothError;
+
+    /// Parse an address in the "AA:BB:CC:DD:EE:FF" form as a public address.
+    /// A random address is built from the parsed one with `BleAddress::new()`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(BleAddress {
+            val: parse_address(s)?,
+            kind: BleAddressKind::Public,
+        })
+    }
+}
+
+impl fmt::Display for ClassicAddress {
+    /// Format as "AA:BB:CC:DD:EE:FF".
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_address(&self.0, f)
+    }
+}
+
+impl FromStr for ClassicAddress {
+    type Err = BluetoothError;
+
+    /// Parse an address in the "AA:BB:CC:DD:EE:FF" form.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_address(s).map(ClassicAddress)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ClassicAddress {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ClassicAddress {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Serialized form of a `BleAddress`, holding the address in the
+/// "AA:BB:CC:DD:EE:FF" form and its kind in separate fields, e.g.
+/// `{"address": "AA:BB:CC:DD:EE:FF", "kind": "random"}`.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SerializedBleAddress {
+    address: ClassicAddress,
+    kind: BleAddressKind,
+}
+
+#[cfg(feature = "serde")]
+impl From<BleAddress> for SerializedBleAddress {
+    fn from(addr: BleAddress) -> Self {
+        SerializedBleAddress {
+            address: ClassicAddress(addr.val),
+            kind: addr.kind,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<SerializedBleAddress> for BleAddress {
+    fn from(addr: SerializedBleAddress) -> Self {
+        BleAddress {
+            val: addr.address.0,
+            kind: addr.kind,
+        }
+    }
+}
+
 impl From<u64> for ClassicAddress {
     fn from(addr: u64) -> Self {
         let addr = u64_to_6lsb(addr);
@@ -116,6 +286,30 @@ mod tests {
         assert_eq!(addr_random.get_kind(), BleAddressKind::Random);
     }
 
+    #[test]
+    fn ble_address_random_kind() {
+        let addr = BleAddress::new(0xC12233445566, BleAddressKind::Public);
+        assert_eq!(addr.random_kind(), None);
+
+        let addr = BleAddress::new(0xC12233445566, BleAddressKind::Random);
+        assert_eq!(addr.random_kind(), Some(RandomAddressKind::Static));
+
+        let addr = BleAddress::new(0x412233445566, BleAddressKind::Random);
+        assert_eq!(
+            addr.random_kind(),
+            Some(RandomAddressKind::ResolvablePrivate)
+        );
+
+        let addr = BleAddress::new(0x012233445566, BleAddressKind::Random);
+        assert_eq!(
+            addr.random_kind(),
+            Some(RandomAddressKind::NonResolvablePrivate)
+        );
+
+        let addr = BleAddress::new(0x812233445566, BleAddressKind::Random);
+        assert_eq!(addr.random_kind(), None);
+    }
+
     #[test]
     fn ble_address_into_u64() {
         let ble_addr = BleAddress::new(0x112233445566, BleAddressKind::Public);
@@ -149,6 +343,61 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn address_display() {
+        let addr = BleAddress::new(0x112233445566, BleAddressKind::Public);
+        assert_eq!(addr.to_string(), "11:22:33:44:55:66");
+
+        assert_eq!(format!("{:#}", addr), "11:22:33:44:55:66");
+
+        let addr = BleAddress::new(0xAABBCCDDEEFF, BleAddressKind::Random);
+        assert_eq!(addr.to_string(), "AA:BB:CC:DD:EE:FF");
+        assert_eq!(format!("{:#}", addr), "AA:BB:CC:DD:EE:FF (random)");
+
+        let addr = ClassicAddress::from(0x0A0B0C0D0E0F);
+        assert_eq!(addr.to_string(), "0A:0B:0C:0D:0E:0F");
+    }
+
+    #[test]
+    fn address_from_str() {
+        assert_eq!(
+            "11:22:33:44:55:66".parse::<BleAddress>().unwrap(),
+            BleAddress::new(0x112233445566, BleAddressKind::Public)
+        );
+        assert_eq!(
+            "aa:bb:cc:dd:ee:ff".parse::<BleAddress>().unwrap(),
+            BleAddress::new(0xAABBCCDDEEFF, BleAddressKind::Public)
+        );
+        assert_eq!(
+            "0A:0B:0C:0D:0E:0F".parse::<ClassicAddress>().unwrap(),
+            ClassicAddress::from(0x0A0B0C0D0E0F)
+        );
+    }
+
+    #[test]
+    fn address_from_invalid_str() {
+        for s in [
+            "",
+            "11:22:33:44:55",
+            "11:22:33:44:55:66:77",
+            "11-22-33-44-55-66",
+            "112:2:33:44:55:66",
+            "11:22:33:44:55:6G",
+            "11:22:33:44:55:+6",
+            "11:22:33:44:55:66 (public)",
+            "11:22:33:44:55:66 (random)",
+        ] {
+            assert!(matches!(
+                s.parse::<ClassicAddress>(),
+                Err(BluetoothError::BadTypeConversion(_))
+            ));
+            assert!(matches!(
+                s.parse::<BleAddress>(),
+                Err(BluetoothError::BadTypeConversion(_))
+            ));
+        }
+    }
+
     #[test]
     fn test_u64_to_6lsb() {
         // Test a case where the input number is smaller than 6 bytes
@@ -12,7 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use super::{BleAddress, BluetoothError};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+
+use super::{
+    ad_structures, find_ad_structure, parse_flags, parse_local_name,
+    parse_service_data_16bit_uuid, parse_tx_power_level, BleAddress,
+    BluetoothError, Uuid,
+};
 
 /// Holds data related to an incoming BLE Advertisement. This includes
 /// information about the advertisement (e.g. address of sender) as well as
@@ -23,7 +31,15 @@ pub struct BleAdvertisement {
     address: BleAddress,
     rssi: Option<DecibelMilliwatts>,
     tx_power: Option<DecibelMilliwatts>,
-    service_data_16bit_uuid: Option<Vec<ServiceData<u16>>>,
+    service_data_16bit_uuid: Option<ServiceDataSections>,
+    flags: Option<u8>,
+    complete_local_name: Option<String>,
+    shortened_local_name: Option<String>,
+    /// Shared with the data of the loaded service data sections, so that
+    /// cloning an advertisement doesn't copy its payload.
+    raw_data: Bytes,
+    received_at: Instant,
+    sequence_number: u64,
 }
 
 /// Decibel-milliwatt or dBm is a dimensionless absolute unit expressing the
@@ -43,9 +59,31 @@ impl BleAdvertisement {
             rssi,
             tx_power,
             service_data_16bit_uuid: None,
+            flags: None,
+            complete_local_name: None,
+            shortened_local_name: None,
+            raw_data: Bytes::new(),
+            received_at: Instant::now(),
+            sequence_number: 0,
         }
     }
 
+    /// Construct a `BleAdvertisement` from the raw payload of an
+    /// advertisement, e.g. one received outside of this crate, loading the
+    /// data of the selected data types. Fails if the payload is malformed.
+    pub fn from_raw_data(
+        address: BleAddress,
+        rssi: Option<DecibelMilliwatts>,
+        raw_data: Vec<u8>,
+        datatype_ids: &[BleDataTypeId],
+    ) -> Result<Self, BluetoothError> {
+        let mut advertisement = BleAdvertisement::new(address, rssi, None);
+        advertisement.set_raw_data(Bytes::from(raw_data));
+        advertisement.load_raw_data(datatype_ids)?;
+
+        Ok(advertisement)
+    }
+
     /// Retrieve the `BleAddress` that emitted this advertisement.
     pub fn address(&self) -> BleAddress {
         self.address
@@ -64,10 +102,111 @@ impl BleAdvertisement {
         self.tx_power
     }
 
+    /// Retrieve the name advertised by this device, preferring the complete
+    /// local name over the shortened one. Requires loading the
+    /// `CompleteLocalName` or `ShortenedLocalName` data type.
+    pub fn advertised_name(&self) -> Option<&str> {
+        self.complete_local_name
+            .as_deref()
+            .or(self.shortened_local_name.as_deref())
+    }
+
+    /// Retrieve the advertised flags, e.g. the discoverable mode. Requires
+    /// loading the `Flags` data type.
+    /// See: Bluetooth Supplement to the Core Specification, Part A, Section
+    /// 1.3.
+    pub fn flags(&self) -> Option<u8> {
+        self.flags
+    }
+
+    /// Retrieve the raw payload of this advertisement, i.e. its AD structures
+    /// as sent over the air, e.g. to parse data types this crate doesn't
+    /// understand. Loaded whatever the selected data types.
+    /// See: Bluetooth Core Specification, Vol 3, Part C, Section 11.
+    pub fn raw_data(&self) -> &[u8] {
+        &self.raw_data
+    }
+
+    /// Retrieve when this advertisement was received, on the monotonic clock,
+    /// e.g. to expire it after a TTL. Defaults to the construction time.
+    pub fn received_at(&self) -> Instant {
+        self.received_at
+    }
+
+    /// Retrieve the position of this advertisement among those delivered
+    /// since the scan started, starting at 0, e.g. to order advertisements
+    /// or deduplicate them.
+    pub fn sequence_number(&self) -> u64 {
+        self.sequence_number
+    }
+
+    /// Parse the raw payload of this advertisement into its AD structures.
+    pub fn ad_structures(&self) -> Result<Vec<AdStructure>, BluetoothError> {
+        ad_structures(&self.raw_data)
+            .map(|structure| {
+                let (datatype, data) = structure?;
+                Ok(AdStructure::new(datatype, data.to_vec()))
+            })
+            .collect()
+    }
+
+    /// Setter for the reception time, when the platform reports
+    /// advertisements some time after receiving them.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub(crate) fn set_received_at(&mut self, received_at: Instant) {
+        self.received_at = received_at;
+    }
+
+    /// Setter for the sequence number.
+    #[cfg_attr(
+        not(any(windows, target_os = "android", feature = "mock")),
+        allow(dead_code)
+    )]
+    pub(crate) fn set_sequence_number(&mut self, sequence_number: u64) {
+        self.sequence_number = sequence_number;
+    }
+
+    /// Setter for the raw payload.
+    pub(crate) fn set_raw_data(&mut self, raw_data: Bytes) {
+        self.raw_data = raw_data;
+    }
+
+    /// Load the data of an AD structure whose type appears at most once in an
+    /// advertisement, i.e. any type but service data. The transmit power
+    /// reported by the platform takes precedence over the TX Power Level
+    /// data type, if any.
+    fn load_single_data_type(
+        &mut self,
+        datatype_id: BleDataTypeId,
+        data: &[u8],
+    ) -> Result<(), BluetoothError> {
+        match datatype_id {
+            BleDataTypeId::Flags => self.flags = Some(parse_flags(data)?),
+            BleDataTypeId::ShortenedLocalName => {
+                self.shortened_local_name = Some(parse_local_name(data))
+            }
+            BleDataTypeId::CompleteLocalName => {
+                self.complete_local_name = Some(parse_local_name(data))
+            }
+            BleDataTypeId::TxPowerLevel => {
+                let tx_power = parse_tx_power_level(data)?;
+                self.tx_power.get_or_insert(tx_power);
+            }
+            BleDataTypeId::ServiceData16BitUuid => {
+                return Err(BluetoothError::Internal(String::from(
+                    "service data may appear several times in an \
+                    advertisement",
+                )))
+            }
+        }
+
+        Ok(())
+    }
+
     /// Setter for `ServiceData` field with 16bit UUID.
-    pub(crate) fn set_service_data_16bit_uuid(
+    fn set_service_data_16bit_uuid(
         &mut self,
-        data_sections: Vec<ServiceData<u16>>,
+        data_sections: ServiceDataSections,
     ) {
         self.service_data_16bit_uuid = Some(data_sections);
     }
@@ -75,14 +214,107 @@ impl BleAdvertisement {
     /// Getter for `ServiceData` field with 16bit UUID.
     pub fn service_data_16bit_uuid(
         &self,
-    ) -> Result<&Vec<ServiceData<u16>>, BluetoothError> {
+    ) -> Result<&[ServiceData], BluetoothError> {
         match &self.service_data_16bit_uuid {
-            Some(service_data) => Ok(&service_data),
+            Some(service_data) => Ok(service_data.as_slice()),
             None => Err(BluetoothError::FailedPrecondition(String::from(
                 "No service data has been loaded into this advertisement.",
             ))),
         }
     }
+
+    /// Load data of selected data types into self by parsing the raw payload
+    /// set with `set_raw_data()`, e.g. as returned by
+    /// `android.bluetooth.le.ScanRecord.getBytes()`. Service data sections
+    /// reference the payload rather than copying it.
+    /// See: Supplement to the Bluetooth Core Specification Part A, Section 1.
+    pub(crate) fn load_raw_data(
+        &mut self,
+        datatype_ids: &[BleDataTypeId],
+    ) -> Result<(), BluetoothError> {
+        // Taken out while parsing rather than cloned, as the first clone of a
+        // payload allocates its reference count, which is only worth it for
+        // the service data sections referencing the payload.
+        let raw_data = std::mem::take(&mut self.raw_data);
+        let result = self.load_data_types(&raw_data, datatype_ids);
+        self.raw_data = raw_data;
+
+        result
+    }
+
+    fn load_data_types(
+        &mut self,
+        raw_data: &Bytes,
+        datatype_ids: &[BleDataTypeId],
+    ) -> Result<(), BluetoothError> {
+        for datatype_id in datatype_ids {
+            match datatype_id {
+                BleDataTypeId::ServiceData16BitUuid => {
+                    let mut service_data =
+                        ServiceDataSections::Many(Vec::new());
+                    for section in parse_service_data_16bit_uuid(raw_data) {
+                        let (uuid, data) = section?;
+                        service_data.push(ServiceData::from_shared(
+                            uuid,
+                            raw_data.slice_ref(data),
+                        ));
+                    }
+                    self.set_service_data_16bit_uuid(service_data)
+                }
+                _ => {
+                    if let Some(data) =
+                        find_ad_structure(raw_data, *datatype_id)?
+                    {
+                        self.load_single_data_type(*datatype_id, data)?
+                    }
+                }
+            };
+        }
+
+        Ok(())
+    }
+}
+
+/// An AD structure of an advertisement, holding the data of a single data
+/// type, as listed in the Bluetooth Assigned Numbers.
+/// See: Bluetooth Core Specification, Vol 3, Part C, Section 11.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct AdStructure {
+    datatype: u8,
+    data: Vec<u8>,
+}
+
+impl AdStructure {
+    /// Construct a new `AdStructure` instance.
+    pub fn new(datatype: u8, data: Vec<u8>) -> Self {
+        AdStructure { datatype, data }
+    }
+
+    /// Retrieve the assigned number of the data type.
+    pub fn datatype(&self) -> u8 {
+        self.datatype
+    }
+
+    /// Retrieve the data, excluding the length and data type bytes.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Encode as in a raw advertisement, i.e. preceded by a length byte
+    /// covering the data type byte and the data.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub(crate) fn to_bytes(&self) -> Result<Vec<u8>, BluetoothError> {
+        let len = u8::try_from(self.data.len() + 1).map_err(|_| {
+            BluetoothError::Internal(format!(
+                "AD structure of data type {:#04x} is too long",
+                self.datatype
+            ))
+        })?;
+
+        let mut bytes = vec![len, self.datatype];
+        bytes.extend_from_slice(&self.data);
+        Ok(bytes)
+    }
 }
 
 /// Enum denoting the assigned number of Bluetooth common data types. Used for
@@ -90,37 +322,355 @@ impl BleAdvertisement {
 /// Bluetooth Assigned Numbers, Section 2.3
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BleDataTypeId {
+    Flags = 0x01,
+    ShortenedLocalName = 0x08,
+    CompleteLocalName = 0x09,
+    TxPowerLevel = 0x0A,
     ServiceData16BitUuid = 0x16,
 }
 
-/// Struct representing the Bluetooth Service Data common data type. `U` should
-/// be one of the valid uuid sizes, specified in:
+/// Service data sections loaded into an advertisement. Advertisements seldom
+/// carry more than one, which is then kept inline rather than allocating.
+#[derive(Clone, Debug)]
+enum ServiceDataSections {
+    One(ServiceData),
+    Many(Vec<ServiceData>),
+}
+
+impl ServiceDataSections {
+    fn push(&mut self, service_data: ServiceData) {
+        let sections =
+            std::mem::replace(self, ServiceDataSections::Many(Vec::new()));
+        *self = match sections {
+            ServiceDataSections::Many(sections) if sections.is_empty() => {
+                ServiceDataSections::One(service_data)
+            }
+            ServiceDataSections::One(first) => {
+                ServiceDataSections::Many(vec![first, service_data])
+            }
+            ServiceDataSections::Many(mut sections) => {
+                sections.push(service_data);
+                ServiceDataSections::Many(sections)
+            }
+        };
+    }
+
+    fn as_slice(&self) -> &[ServiceData] {
+        match self {
+            ServiceDataSections::One(service_data) => {
+                std::slice::from_ref(service_data)
+            }
+            ServiceDataSections::Many(sections) => sections,
+        }
+    }
+}
+
+/// Struct representing the Bluetooth Service Data common data type. The UUID
+/// is carried in its 16-bit, 32-bit or 128-bit form, see:
 /// Bluetooth Supplement to the Core Specification, Part A, Section 1.11.
 #[derive(Clone, PartialEq, Eq, Debug)]
-pub struct ServiceData<U: Copy> {
-    uuid: U,
-    data: Vec<u8>,
+pub struct ServiceData {
+    uuid: Uuid,
+    data: Bytes,
 }
 
-impl<U: Copy> ServiceData<U> {
-    pub fn new(uuid: U, data: Vec<u8>) -> Self {
+impl ServiceData {
+    pub fn new(uuid: Uuid, data: Vec<u8>) -> Self {
+        ServiceData::from_shared(uuid, Bytes::from(data))
+    }
+
+    /// Construct a `ServiceData` referencing a slice of a received payload.
+    pub(crate) fn from_shared(uuid: Uuid, data: Bytes) -> Self {
         ServiceData { uuid, data }
     }
 
-    pub fn uuid(&self) -> U {
+    pub fn uuid(&self) -> Uuid {
         self.uuid
     }
 
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Encode as the payload of a Service Data - 16-bit UUID AD structure,
+    /// i.e. the little-endian UUID followed by the data. Fails if the UUID
+    /// has no 16-bit form.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub(crate) fn to_16bit_uuid_bytes(
+        &self,
+    ) -> Result<Vec<u8>, BluetoothError> {
+        let uuid = self.uuid.as_u16().ok_or_else(|| {
+            BluetoothError::BadTypeConversion(format!(
+                "service data UUID {} has no 16-bit form",
+                self.uuid
+            ))
+        })?;
+
+        let mut bytes = uuid.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&self.data);
+        Ok(bytes)
+    }
+}
+
+/// Struct representing the Bluetooth Manufacturer Specific Data common data
+/// type, identified by a company identifier assigned by the Bluetooth SIG.
+/// Bluetooth Supplement to the Core Specification, Part A, Section 1.4.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ManufacturerData {
+    company_id: u16,
+    data: Vec<u8>,
+}
+
+impl ManufacturerData {
+    pub fn new(company_id: u16, data: Vec<u8>) -> Self {
+        ManufacturerData { company_id, data }
+    }
+
+    pub fn company_id(&self) -> u16 {
+        self.company_id
+    }
+
     pub fn data(&self) -> &Vec<u8> {
         &self.data
     }
 }
 
+/// Data sections of an outgoing BLE advertisement, broadcast with
+/// `api::BleAdvertiser`.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct AdvertisementPayload {
+    service_data_16bit_uuid: Vec<ServiceData>,
+    manufacturer_data: Vec<ManufacturerData>,
+}
+
+impl AdvertisementPayload {
+    /// Construct an empty `AdvertisementPayload`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a service data section with a 16-bit UUID. Advertising fails if
+    /// the UUID of `service_data` has no 16-bit form.
+    pub fn with_service_data_16bit_uuid(
+        mut self,
+        service_data: ServiceData,
+    ) -> Self {
+        self.service_data_16bit_uuid.push(service_data);
+        self
+    }
+
+    /// Add a manufacturer specific data section.
+    pub fn with_manufacturer_data(
+        mut self,
+        manufacturer_data: ManufacturerData,
+    ) -> Self {
+        self.manufacturer_data.push(manufacturer_data);
+        self
+    }
+
+    /// Retrieve the service data sections with a 16-bit UUID.
+    pub fn service_data_16bit_uuid(&self) -> &[ServiceData] {
+        &self.service_data_16bit_uuid
+    }
+
+    /// Retrieve the manufacturer specific data sections.
+    pub fn manufacturer_data(&self) -> &[ManufacturerData] {
+        &self.manufacturer_data
+    }
+}
+
+/// Physical layer used to broadcast advertisements.
+/// See: Bluetooth Core Specification, Vol 6, Part A, Section 3.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash, Default)]
+pub enum Phy {
+    /// LE 1M, the only PHY of legacy advertising.
+    #[default]
+    Le1M,
+    /// LE 2M, only available as the secondary PHY of extended advertising.
+    Le2M,
+    /// LE Coded, trading throughput for range.
+    LeCoded,
+}
+
+/// Range of the advertising TX power which can be requested, in dBm.
+/// See: Bluetooth Core Specification, Vol 4, Part E, Section 7.8.53.
+const ADVERTISING_TX_POWER_RANGE: std::ops::RangeInclusive<DecibelMilliwatts> =
+    -127..=20;
+
+/// Parameters of an outgoing BLE advertisement, broadcast with
+/// `api::BleAdvertiser`. The default parameters select legacy advertising,
+/// leaving the interval and TX power to the platform.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub struct AdvertisingParameters {
+    extended: bool,
+    anonymous: bool,
+    primary_phy: Phy,
+    secondary_phy: Phy,
+    interval: Option<Duration>,
+    tx_power: Option<DecibelMilliwatts>,
+    include_tx_power: bool,
+}
+
+impl AdvertisingParameters {
+    /// Construct the default `AdvertisingParameters`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use BT5 extended advertising, e.g. to broadcast payloads longer than
+    /// the 31 bytes of legacy advertisements.
+    pub fn with_extended_advertising(mut self) -> Self {
+        self.extended = true;
+        self
+    }
+
+    /// Omit the advertiser's address from the advertisements. Only available
+    /// with extended advertising, which this turns on.
+    pub fn with_anonymous_advertising(mut self) -> Self {
+        self.extended = true;
+        self.anonymous = true;
+        self
+    }
+
+    /// Select the PHYs of extended advertising: `primary` carries the
+    /// advertising indications, `secondary` the auxiliary packets holding
+    /// the payload.
+    pub fn with_phys(mut self, primary: Phy, secondary: Phy) -> Self {
+        self.primary_phy = primary;
+        self.secondary_phy = secondary;
+        self
+    }
+
+    /// Hint the interval between advertising events. Platforms which don't
+    /// let applications pick it ignore the hint.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    /// Request the TX power of the advertisements, from -127 to 20 dBm. The
+    /// controller uses the closest power it supports.
+    pub fn with_tx_power(mut self, tx_power: DecibelMilliwatts) -> Self {
+        self.tx_power = Some(tx_power);
+        self
+    }
+
+    /// Advertise the TX power in use, which receivers compare with the
+    /// signal strength to estimate their distance, e.g. for presence
+    /// ranging.
+    pub fn with_tx_power_advertised(mut self) -> Self {
+        self.include_tx_power = true;
+        self
+    }
+
+    /// Check whether extended advertising is used.
+    pub fn extended(&self) -> bool {
+        self.extended
+    }
+
+    /// Check whether the advertiser's address is omitted.
+    pub fn anonymous(&self) -> bool {
+        self.anonymous
+    }
+
+    /// Retrieve the primary advertising PHY.
+    pub fn primary_phy(&self) -> Phy {
+        self.primary_phy
+    }
+
+    /// Retrieve the secondary advertising PHY.
+    pub fn secondary_phy(&self) -> Phy {
+        self.secondary_phy
+    }
+
+    /// Retrieve the advertising interval hint, if any.
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+
+    /// Retrieve the requested TX power in dBm, if any.
+    pub fn tx_power(&self) -> Option<DecibelMilliwatts> {
+        self.tx_power
+    }
+
+    /// Check whether the TX power in use is advertised.
+    pub fn tx_power_advertised(&self) -> bool {
+        self.include_tx_power
+    }
+
+    /// Check that the parameters can be used together: legacy advertising
+    /// only uses LE 1M, LE 2M can't be the primary PHY, and the TX power must
+    /// be in range.
+    /// See: Bluetooth Core Specification, Vol 6, Part B, Section 2.3.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub(crate) fn validate(&self) -> Result<(), BluetoothError> {
+        if !self.extended
+            && (self.primary_phy != Phy::Le1M
+                || self.secondary_phy != Phy::Le1M)
+        {
+            return Err(BluetoothError::FailedPrecondition(String::from(
+                "legacy advertising only uses the LE 1M PHY",
+            )));
+        }
+        if self.primary_phy == Phy::Le2M {
+            return Err(BluetoothError::FailedPrecondition(String::from(
+                "LE 2M can't be the primary advertising PHY",
+            )));
+        }
+        if let Some(tx_power) = self.tx_power {
+            if !ADVERTISING_TX_POWER_RANGE.contains(&tx_power) {
+                return Err(BluetoothError::FailedPrecondition(format!(
+                    "advertising TX power {} dBm is out of range",
+                    tx_power
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::common::BleAddressKind;
 
+    #[test]
+    fn advertising_parameters_validate() {
+        assert!(AdvertisingParameters::new().validate().is_ok());
+        assert!(AdvertisingParameters::new()
+            .with_extended_advertising()
+            .with_phys(Phy::LeCoded, Phy::Le2M)
+            .validate()
+            .is_ok());
+
+        let anonymous =
+            AdvertisingParameters::new().with_anonymous_advertising();
+        assert!(anonymous.extended());
+        assert!(anonymous.validate().is_ok());
+
+        let ranging = AdvertisingParameters::new()
+            .with_tx_power(-20)
+            .with_tx_power_advertised();
+        assert_eq!(ranging.tx_power(), Some(-20));
+        assert!(ranging.tx_power_advertised());
+        assert!(ranging.validate().is_ok());
+
+        for parameters in [
+            AdvertisingParameters::new().with_phys(Phy::LeCoded, Phy::LeCoded),
+            AdvertisingParameters::new()
+                .with_extended_advertising()
+                .with_phys(Phy::Le2M, Phy::Le2M),
+            AdvertisingParameters::new().with_tx_power(21),
+            AdvertisingParameters::new().with_tx_power(-128),
+        ] {
+            assert!(matches!(
+                parameters.validate(),
+                Err(BluetoothError::FailedPrecondition(_))
+            ));
+        }
+    }
+
     #[test]
     fn ble_advertisement_new() {
         let address = BleAddress::new(0x112233445566, BleAddressKind::Public);
@@ -131,17 +681,33 @@ mod tests {
         assert!(ad.service_data_16bit_uuid.is_none());
     }
 
+    #[test]
+    fn ble_advertisement_reception() {
+        let address = BleAddress::new(0x112233445566, BleAddressKind::Public);
+        let before = Instant::now();
+        let mut ad = BleAdvertisement::new(address, None, None);
+        assert!(ad.received_at() >= before);
+        assert_eq!(ad.sequence_number(), 0);
+
+        ad.set_received_at(before);
+        ad.set_sequence_number(7);
+        assert_eq!(ad.received_at(), before);
+        assert_eq!(ad.sequence_number(), 7);
+    }
+
     #[test]
     fn ble_advertisement_set_and_get_service_data() {
         let address = BleAddress::new(0x112233445566, BleAddressKind::Public);
         let mut ad = BleAdvertisement::new(address, Some(-60), Some(10));
 
         let service_data = vec![
-            ServiceData::new(0x1234, vec![0x01, 0x02, 0x03]),
-            ServiceData::new(0x5678, vec![0x04, 0x05]),
+            ServiceData::new(Uuid::from_u16(0x1234), vec![0x01, 0x02, 0x03]),
+            ServiceData::new(Uuid::from_u16(0x5678), vec![0x04, 0x05]),
         ];
 
-        ad.set_service_data_16bit_uuid(service_data.clone());
+        ad.set_service_data_16bit_uuid(ServiceDataSections::Many(
+            service_data.clone(),
+        ));
 
         let retrieved_service_data = ad.service_data_16bit_uuid().unwrap();
         assert_eq!(*retrieved_service_data, service_data);
@@ -160,13 +726,221 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn ble_advertisement_advertised_name() {
+        let address = BleAddress::new(0x112233445566, BleAddressKind::Public);
+        let mut ad = BleAdvertisement::new(address, None, None);
+        assert_eq!(ad.advertised_name(), None);
+
+        ad.load_single_data_type(BleDataTypeId::ShortenedLocalName, b"Pixel")
+            .unwrap();
+        assert_eq!(ad.advertised_name(), Some("Pixel"));
+
+        ad.load_single_data_type(
+            BleDataTypeId::CompleteLocalName,
+            b"Pixel Buds",
+        )
+        .unwrap();
+        assert_eq!(ad.advertised_name(), Some("Pixel Buds"));
+    }
+
+    #[test]
+    fn ble_advertisement_advertised_tx_power() {
+        let address = BleAddress::new(0x112233445566, BleAddressKind::Public);
+
+        let mut ad = BleAdvertisement::new(address, None, None);
+        ad.load_single_data_type(BleDataTypeId::TxPowerLevel, &[0xF6])
+            .unwrap();
+        assert_eq!(ad.tx_power(), Some(-10));
+
+        let mut ad = BleAdvertisement::new(address, None, Some(10));
+        ad.load_single_data_type(BleDataTypeId::TxPowerLevel, &[0xF6])
+            .unwrap();
+        assert_eq!(ad.tx_power(), Some(10));
+    }
+
+    #[test]
+    fn ble_advertisement_load_flags() {
+        let address = BleAddress::new(0x112233445566, BleAddressKind::Public);
+        let mut ad = BleAdvertisement::new(address, None, None);
+
+        ad.load_single_data_type(BleDataTypeId::Flags, &[0x06])
+            .unwrap();
+        assert_eq!(ad.flags(), Some(0x06));
+        assert!(ad
+            .load_single_data_type(BleDataTypeId::ServiceData16BitUuid, &[])
+            .is_err());
+    }
+
     #[test]
     fn service_data_new() {
-        let uuid = 0x1234;
+        let uuid = Uuid::from_u16(0x1234);
         let data = vec![0x01, 0x02, 0x03];
 
         let service_data = ServiceData::new(uuid, data.clone());
         assert_eq!(service_data.uuid(), uuid);
-        assert_eq!(*service_data.data(), data);
+        assert_eq!(service_data.data(), data.as_slice());
+    }
+
+    #[test]
+    fn service_data_to_bytes() {
+        let service_data =
+            ServiceData::new(Uuid::from_u16(0xFE2C), vec![0x01, 0x02, 0x03]);
+        assert_eq!(
+            service_data.to_16bit_uuid_bytes().unwrap(),
+            vec![0x2C, 0xFE, 0x01, 0x02, 0x03]
+        );
+        let service_data =
+            ServiceData::new(Uuid::from_u128(0x1234), vec![0x01]);
+        assert!(matches!(
+            service_data.to_16bit_uuid_bytes(),
+            Err(BluetoothError::BadTypeConversion(_))
+        ));
+    }
+
+    #[test]
+    fn advertisement_payload_sections() {
+        let payload = AdvertisementPayload::new()
+            .with_service_data_16bit_uuid(ServiceData::new(
+                Uuid::from_u16(0xFE2C),
+                vec![0x01],
+            ))
+            .with_manufacturer_data(ManufacturerData::new(0x00E0, vec![0x02]));
+
+        assert_eq!(
+            payload.service_data_16bit_uuid(),
+            [ServiceData::new(Uuid::from_u16(0xFE2C), vec![0x01])]
+        );
+        assert_eq!(payload.manufacturer_data().len(), 1);
+        assert_eq!(payload.manufacturer_data()[0].company_id(), 0x00E0);
+        assert_eq!(*payload.manufacturer_data()[0].data(), vec![0x02]);
+    }
+
+    #[test]
+    fn load_single_data_types() {
+        let raw_data = [
+            0x02, 0x01, 0x06, // Flags
+            0x02, 0x0A, 0xF6, // TX power level
+            0x06, 0x08, 0x50, 0x69, 0x78, 0x65,
+            0x6C, // Shortened local name
+        ];
+        let address = BleAddress::new(0x112233445566, BleAddressKind::Public);
+        let mut advertisement = BleAdvertisement::new(address, None, None);
+        advertisement.set_raw_data(Bytes::copy_from_slice(&raw_data));
+
+        advertisement
+            .load_raw_data(&[
+                BleDataTypeId::Flags,
+                BleDataTypeId::CompleteLocalName,
+                BleDataTypeId::ShortenedLocalName,
+                BleDataTypeId::TxPowerLevel,
+            ])
+            .unwrap();
+        assert_eq!(advertisement.flags(), Some(0x06));
+        assert_eq!(advertisement.advertised_name(), Some("Pixel"));
+        assert_eq!(advertisement.tx_power(), Some(-10));
+    }
+
+    #[test]
+    fn ble_advertisement_from_raw_data() {
+        let raw_data = vec![
+            0x02, 0x01, 0x06, // Flags
+            0x05, 0x16, 0x2C, 0xFE, 0x01, 0x02, // Service data
+        ];
+        let address = BleAddress::new(0x112233445566, BleAddressKind::Public);
+
+        let advertisement = BleAdvertisement::from_raw_data(
+            address,
+            Some(-60),
+            raw_data.clone(),
+            &[BleDataTypeId::Flags, BleDataTypeId::ServiceData16BitUuid],
+        )
+        .unwrap();
+        assert_eq!(advertisement.rssi(), Some(-60));
+        assert_eq!(advertisement.flags(), Some(0x06));
+        assert_eq!(
+            advertisement.service_data_16bit_uuid().unwrap(),
+            &vec![ServiceData::new(Uuid::from_u16(0xFE2C), vec![0x01, 0x02])]
+        );
+        assert_eq!(advertisement.raw_data(), raw_data.as_slice());
+        // The service data references the payload rather than a copy.
+        assert_eq!(
+            advertisement.service_data_16bit_uuid().unwrap()[0]
+                .data()
+                .as_ptr(),
+            advertisement.raw_data()[7..].as_ptr()
+        );
+
+        assert!(BleAdvertisement::from_raw_data(
+            address,
+            None,
+            vec![0x05, 0x16, 0x2C],
+            &[BleDataTypeId::ServiceData16BitUuid],
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn load_service_data_sections() {
+        let address = BleAddress::new(0x112233445566, BleAddressKind::Public);
+        let load = |raw_data: &[u8]| {
+            let mut advertisement = BleAdvertisement::new(address, None, None);
+            advertisement.set_raw_data(Bytes::copy_from_slice(raw_data));
+            advertisement
+                .load_raw_data(&[BleDataTypeId::ServiceData16BitUuid])
+                .unwrap();
+            advertisement
+        };
+
+        let advertisement = load(&[0x02, 0x01, 0x06]);
+        assert!(advertisement.service_data_16bit_uuid().unwrap().is_empty());
+
+        let advertisement = load(&[
+            0x04, 0x16, 0x2C, 0xFE, 0x01, // Service data
+            0x03, 0x16, 0x0F, 0x18, // Service data without payload
+            0x04, 0x16, 0x34, 0x12, 0x02, // Service data
+        ]);
+        assert_eq!(
+            advertisement.service_data_16bit_uuid().unwrap(),
+            [
+                ServiceData::new(Uuid::from_u16(0xFE2C), vec![0x01]),
+                ServiceData::new(Uuid::from_u16(0x180F), vec![]),
+                ServiceData::new(Uuid::from_u16(0x1234), vec![0x02]),
+            ]
+        );
+    }
+
+    #[test]
+    fn ble_advertisement_raw_data() {
+        let raw_data = vec![
+            0x02, 0x01, 0x06, // Flags
+            0x04, 0xFF, 0xE0, 0x00, 0x01, // Manufacturer specific data
+        ];
+        let address = BleAddress::new(0x112233445566, BleAddressKind::Public);
+        let mut advertisement = BleAdvertisement::new(address, None, None);
+        assert!(advertisement.raw_data().is_empty());
+        assert!(advertisement.ad_structures().unwrap().is_empty());
+
+        advertisement.set_raw_data(Bytes::from(raw_data.clone()));
+        assert_eq!(advertisement.raw_data(), raw_data.as_slice());
+
+        let structures = advertisement.ad_structures().unwrap();
+        assert_eq!(
+            structures,
+            vec![
+                AdStructure::new(0x01, vec![0x06]),
+                AdStructure::new(0xFF, vec![0xE0, 0x00, 0x01]),
+            ]
+        );
+        let encoded: Vec<u8> = structures
+            .iter()
+            .flat_map(|structure| structure.to_bytes().unwrap())
+            .collect();
+        assert_eq!(encoded, raw_data);
+
+        assert!(matches!(
+            AdStructure::new(0xFF, vec![0; 255]).to_bytes(),
+            Err(BluetoothError::Internal(_))
+        ));
     }
 }
@@ -0,0 +1,358 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{channel::mpsc::Receiver, Stream, StreamExt};
+
+use super::{BleAddress, ClassicAddress, Uuid};
+
+/// SDP attribute ID of the ProtocolDescriptorList, which describes how to
+/// reach a service, e.g. over RFCOMM on a given channel.
+/// See: Bluetooth Core Specification, Vol 3, Part B, Section 5.1.5.
+#[cfg_attr(not(windows), allow(dead_code))]
+pub(crate) const PROTOCOL_DESCRIPTOR_LIST_ATTRIBUTE_ID: u16 = 0x0004;
+
+/// Protocol UUID of RFCOMM, see the Bluetooth Assigned Numbers.
+const RFCOMM_PROTOCOL_UUID: u16 = 0x0003;
+
+/// Types of SDP data elements.
+/// See: Bluetooth Core Specification, Vol 3, Part B, Section 3.2.
+const SDP_TYPE_NIL: u8 = 0;
+const SDP_TYPE_UINT: u8 = 1;
+const SDP_TYPE_UUID: u8 = 3;
+const SDP_TYPE_SEQUENCE: u8 = 6;
+
+/// Whether a remote device is connected to the local adapter.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub enum ConnectionStatus {
+    Connected,
+    Disconnected,
+}
+
+/// Stream of the connection statuses of a remote device, starting with its
+/// current status and followed by every change.
+pub struct ConnectionStatusStream {
+    receiver: Receiver<ConnectionStatus>,
+}
+
+impl ConnectionStatusStream {
+    /// Construct a stream yielding the statuses sent through the channel.
+    #[cfg_attr(not(any(windows, feature = "mock")), allow(dead_code))]
+    pub(crate) fn new(receiver: Receiver<ConnectionStatus>) -> Self {
+        ConnectionStatusStream { receiver }
+    }
+}
+
+impl Stream for ConnectionStatusStream {
+    type Item = ConnectionStatus;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_next_unpin(cx)
+    }
+}
+
+/// Service record of a BT Classic device, found through SDP (Service
+/// Discovery Protocol).
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub struct SdpRecord {
+    service_class_uuid: Uuid,
+    rfcomm_channel: Option<u8>,
+}
+
+impl SdpRecord {
+    /// Construct a new `SdpRecord` instance.
+    pub fn new(service_class_uuid: Uuid, rfcomm_channel: Option<u8>) -> Self {
+        SdpRecord {
+            service_class_uuid,
+            rfcomm_channel,
+        }
+    }
+
+    /// Retrieve the UUID identifying the service class, e.g. the Fast Pair
+    /// Message Stream service.
+    pub fn service_class_uuid(&self) -> Uuid {
+        self.service_class_uuid
+    }
+
+    /// Retrieve the RFCOMM server channel to connect to, if the service is
+    /// reached over RFCOMM and the platform reports it.
+    pub fn rfcomm_channel(&self) -> Option<u8> {
+        self.rfcomm_channel
+    }
+}
+
+/// Class of Device of a BT Classic device, telling what kind of device it is,
+/// e.g. headphones, and which services it provides.
+/// See: Bluetooth Assigned Numbers, Section 2.8.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub struct ClassOfDevice(u32);
+
+impl ClassOfDevice {
+    /// Retrieve the major service class bits, e.g. `0x100` for Audio.
+    pub fn major_service_classes(&self) -> u16 {
+        ((self.0 >> 13) & 0x7FF) as u16
+    }
+
+    /// Retrieve the major device class, e.g. `0x04` for Audio/Video.
+    pub fn major_device_class(&self) -> u8 {
+        ((self.0 >> 8) & 0x1F) as u8
+    }
+
+    /// Retrieve the minor device class, whose meaning depends on the major
+    /// device class.
+    pub fn minor_device_class(&self) -> u8 {
+        ((self.0 >> 2) & 0x3F) as u8
+    }
+}
+
+impl From<u32> for ClassOfDevice {
+    fn from(value: u32) -> Self {
+        ClassOfDevice(value & 0xFFFFFF)
+    }
+}
+
+impl From<ClassOfDevice> for u32 {
+    fn from(class: ClassOfDevice) -> Self {
+        class.0
+    }
+}
+
+/// A BT Classic device found by `api::ClassicAdapter::start_discovery()`.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct DiscoveredClassicDevice {
+    address: ClassicAddress,
+    name: Option<String>,
+    class_of_device: ClassOfDevice,
+}
+
+impl DiscoveredClassicDevice {
+    /// Construct a new `DiscoveredClassicDevice` instance.
+    pub fn new(
+        address: ClassicAddress,
+        name: Option<String>,
+        class_of_device: ClassOfDevice,
+    ) -> Self {
+        DiscoveredClassicDevice {
+            address,
+            name,
+            class_of_device,
+        }
+    }
+
+    /// Retrieve the address of the device.
+    pub fn address(&self) -> ClassicAddress {
+        self.address
+    }
+
+    /// Retrieve the name of the device, if it was resolved.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Retrieve the Class of Device of the device.
+    pub fn class_of_device(&self) -> ClassOfDevice {
+        self.class_of_device
+    }
+}
+
+/// Address of a remote device, along with the transport it is reached over.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub enum DeviceAddress {
+    Ble(BleAddress),
+    Classic(ClassicAddress),
+}
+
+/// A device paired with the local adapter, as listed by
+/// `Platform::list_paired_devices()`.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct PairedDevice {
+    address: DeviceAddress,
+    name: Option<String>,
+}
+
+impl PairedDevice {
+    /// Construct a new `PairedDevice` instance.
+    pub fn new(address: DeviceAddress, name: Option<String>) -> Self {
+        PairedDevice { address, name }
+    }
+
+    /// Retrieve the address of the device, telling whether it was paired
+    /// over BLE or BT Classic.
+    pub fn address(&self) -> DeviceAddress {
+        self.address
+    }
+
+    /// Retrieve the name of the device, if the platform knows it.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
+/// Parse the RFCOMM server channel out of the raw ProtocolDescriptorList
+/// attribute of an SDP record. Returns `None` if the service isn't reached
+/// over RFCOMM or the attribute is malformed.
+#[cfg_attr(not(windows), allow(dead_code))]
+pub(crate) fn parse_rfcomm_channel(attribute: &[u8]) -> Option<u8> {
+    let (data_type, mut protocols, _) = split_data_element(attribute)?;
+    if data_type != SDP_TYPE_SEQUENCE {
+        return None;
+    }
+
+    // Each protocol is a sequence starting with its UUID, followed by its
+    // parameters.
+    while !protocols.is_empty() {
+        let (data_type, protocol, rest) = split_data_element(protocols)?;
+        protocols = rest;
+        if data_type != SDP_TYPE_SEQUENCE {
+            return None;
+        }
+
+        let (data_type, uuid, params) = split_data_element(protocol)?;
+        if data_type == SDP_TYPE_UUID
+            && parse_uuid(uuid) == Some(Uuid::from_u16(RFCOMM_PROTOCOL_UUID))
+        {
+            return match split_data_element(params)? {
+                (SDP_TYPE_UINT, &[channel], _) => Some(channel),
+                _ => None,
+            };
+        }
+    }
+
+    None
+}
+
+/// Split the first SDP data element off `data`, returning its type, its value
+/// and the bytes following it.
+/// See: Bluetooth Core Specification, Vol 3, Part B, Section 3.3.
+fn split_data_element(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&header, rest) = data.split_first()?;
+    let data_type = header >> 3;
+
+    let (len, rest) = match header & 0x07 {
+        _ if data_type == SDP_TYPE_NIL => (0, rest),
+        size_index @ 0..=4 => (1 << size_index, rest),
+        5 => (usize::from(*rest.first()?), &rest[1..]),
+        6 => {
+            let len = u16::from_be_bytes(rest.get(..2)?.try_into().ok()?);
+            (usize::from(len), &rest[2..])
+        }
+        _ => {
+            let len = u32::from_be_bytes(rest.get(..4)?.try_into().ok()?);
+            (usize::try_from(len).ok()?, &rest[4..])
+        }
+    };
+
+    let value = rest.get(..len)?;
+    Some((data_type, value, &rest[len..]))
+}
+
+/// Parse the value of an SDP UUID data element, which is a big-endian 16-bit,
+/// 32-bit or 128-bit UUID.
+fn parse_uuid(value: &[u8]) -> Option<Uuid> {
+    match value.len() {
+        2 => Some(Uuid::from_u16(u16::from_be_bytes(value.try_into().ok()?))),
+        4 => Some(Uuid::from_u32(u32::from_be_bytes(value.try_into().ok()?))),
+        16 => {
+            Some(Uuid::from_u128(u128::from_be_bytes(value.try_into().ok()?)))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connection_status_stream() {
+        let (mut sender, receiver) = futures::channel::mpsc::channel(2);
+        let mut stream = ConnectionStatusStream::new(receiver);
+
+        sender.try_send(ConnectionStatus::Disconnected).unwrap();
+        sender.try_send(ConnectionStatus::Connected).unwrap();
+        drop(sender);
+
+        futures::executor::block_on(async {
+            assert_eq!(
+                stream.next().await,
+                Some(ConnectionStatus::Disconnected)
+            );
+            assert_eq!(stream.next().await, Some(ConnectionStatus::Connected));
+            assert_eq!(stream.next().await, None);
+        });
+    }
+
+    #[test]
+    fn class_of_device_fields() {
+        // Wearable headset, with the Audio and Rendering service classes.
+        let class = ClassOfDevice::from(0x240404);
+        assert_eq!(class.major_service_classes(), 0x120);
+        assert_eq!(class.major_device_class(), 0x04);
+        assert_eq!(class.minor_device_class(), 0x01);
+        assert_eq!(u32::from(class), 0x240404);
+        assert_eq!(u32::from(ClassOfDevice::from(0xFF240404)), 0x240404);
+    }
+
+    #[test]
+    fn parse_rfcomm_channel_spp() {
+        // L2CAP, then RFCOMM on channel 15.
+        let attribute = [
+            0x35, 0x0C, 0x35, 0x03, 0x19, 0x01, 0x00, 0x35, 0x05, 0x19, 0x00,
+            0x03, 0x08, 0x0F,
+        ];
+        assert_eq!(parse_rfcomm_channel(&attribute), Some(15));
+    }
+
+    #[test]
+    fn parse_rfcomm_channel_128bit_uuid() {
+        let mut attribute = vec![0x35, 0x15, 0x35, 0x13, 0x1C];
+        attribute.extend_from_slice(
+            &Uuid::from_u16(RFCOMM_PROTOCOL_UUID).as_u128().to_be_bytes(),
+        );
+        attribute.extend_from_slice(&[0x08, 0x03]);
+        assert_eq!(parse_rfcomm_channel(&attribute), Some(3));
+    }
+
+    #[test]
+    fn parse_rfcomm_channel_without_rfcomm() {
+        // L2CAP only, e.g. an L2CAP-based profile.
+        let attribute =
+            [0x35, 0x08, 0x35, 0x06, 0x19, 0x01, 0x00, 0x09, 0x00, 0x19];
+        assert_eq!(parse_rfcomm_channel(&attribute), None);
+    }
+
+    #[test]
+    fn parse_rfcomm_channel_malformed() {
+        assert_eq!(parse_rfcomm_channel(&[]), None);
+        // Truncated sequence.
+        assert_eq!(
+            parse_rfcomm_channel(&[0x35, 0x0C, 0x35, 0x03, 0x19, 0x01]),
+            None
+        );
+        // Channel that isn't a 1-byte unsigned integer.
+        assert_eq!(
+            parse_rfcomm_channel(&[
+                0x35, 0x06, 0x35, 0x04, 0x19, 0x00, 0x03, 0x00
+            ]),
+            None
+        );
+    }
+}
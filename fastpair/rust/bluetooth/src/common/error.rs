@@ -36,6 +36,30 @@ pub enum BluetoothError {
     /// doesn't support BLE).
     #[error("bluetooth operation not supported by system: {0}")]
     NotSupported(String),
+    /// Reported when a GATT operation can't reach the remote device, e.g.
+    /// because it moved out of range or dropped the connection.
+    #[error("device unreachable: {0}")]
+    Unreachable(String),
+    /// Reported when the remote GATT server rejects an operation with an ATT
+    /// error code, e.g. 0x03 (Write Not Permitted).
+    /// See: Bluetooth Core Specification, Vol 3, Part F, Section 3.4.1.1.
+    #[error("GATT protocol error {0:#04x}")]
+    GattProtocolError(u8),
+    /// Reported when the system denies access to the remote device, e.g.
+    /// because the application lacks the required permission.
+    #[error("access denied: {0}")]
+    AccessDenied(String),
+    /// Reported by the advertisement stream when the scan stopped
+    /// unexpectedly, e.g. because of a radio glitch, and was restarted.
+    /// Advertisements sent meanwhile were missed, and scanning goes on with
+    /// the next call.
+    #[error("scan interrupted: {0}")]
+    ScanInterrupted(String),
+    /// Reported by the advertisement stream when the scan stopped for good,
+    /// e.g. because the radio was turned off, with the reason reported by
+    /// the system. Scanning must be started again once the cause is solved.
+    #[error("scan aborted: {0}")]
+    ScanAborted(String),
     /// Wrapper around OS-level errors, e.g. `windows::core::Error` for Windows.
     /// These typically mean something is very wrong with the system (e.g. OOM).
     #[error("bluetooth system-level error: {0}")]
@@ -47,13 +71,28 @@ pub enum BluetoothError {
     Internal(String),
 }
 
+/// Protection of the link with a paired device. Levels are ordered from the
+/// weakest to the strongest.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Hash)]
+pub enum ProtectionLevel {
+    /// Neither encrypted nor authenticated.
+    None,
+    /// Encrypted, but open to man-in-the-middle attacks, e.g. after a "Just
+    /// Works" ceremony.
+    Encryption,
+    /// Encrypted and authenticated, protecting against man-in-the-middle
+    /// attacks, as required by Fast Pair.
+    EncryptionAndAuthentication,
+}
+
 /// Abstraction around platform-specific pairing status enums.
 /// `PairingResult::Failure` should eventually be converted to
 /// `BluetoothError::PairingFailed`.
 #[non_exhaustive]
-#[derive(Debug)]
+#[derive(PartialEq, Eq, Clone, Debug)]
 pub enum PairingResult {
-    Success,
+    /// The device was paired, with the given protection level.
+    Success(ProtectionLevel),
     AlreadyPaired,
     AlreadyInProgress,
     Failure(String),
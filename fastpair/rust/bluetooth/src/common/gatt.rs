@@ -0,0 +1,397 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    ops::BitOr,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{channel::mpsc::Receiver, Stream, StreamExt};
+
+use super::{BluetoothError, Uuid};
+
+/// A primary service discovered on a GATT server.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub struct GattService {
+    uuid: Uuid,
+}
+
+impl GattService {
+    /// Construct a new `GattService` instance.
+    pub fn new(uuid: Uuid) -> Self {
+        GattService { uuid }
+    }
+
+    /// Retrieve the UUID identifying this service.
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+}
+
+/// A characteristic discovered on a GATT server, identified by its own UUID
+/// and the UUID of the service it belongs to.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub struct GattCharacteristic {
+    service_uuid: Uuid,
+    uuid: Uuid,
+    properties: CharacteristicProperties,
+}
+
+impl GattCharacteristic {
+    /// Construct a new `GattCharacteristic` instance.
+    pub fn new(
+        service_uuid: Uuid,
+        uuid: Uuid,
+        properties: CharacteristicProperties,
+    ) -> Self {
+        GattCharacteristic {
+            service_uuid,
+            uuid,
+            properties,
+        }
+    }
+
+    /// Retrieve the UUID of the service this characteristic belongs to.
+    pub fn service_uuid(&self) -> Uuid {
+        self.service_uuid
+    }
+
+    /// Retrieve the UUID identifying this characteristic.
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    /// Retrieve the operations supported by this characteristic.
+    pub fn properties(&self) -> CharacteristicProperties {
+        self.properties
+    }
+}
+
+/// Stream of the values of a characteristic, as notified or indicated by the
+/// GATT server. The stream ends when the subscription is cancelled or the
+/// connection is lost.
+pub struct CharacteristicValueStream {
+    receiver: Receiver<Vec<u8>>,
+}
+
+impl CharacteristicValueStream {
+    /// Construct a stream yielding the values sent through the channel.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub(crate) fn new(receiver: Receiver<Vec<u8>>) -> Self {
+        CharacteristicValueStream { receiver }
+    }
+}
+
+impl Stream for CharacteristicValueStream {
+    type Item = Vec<u8>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_next_unpin(cx)
+    }
+}
+
+/// Stream yielding each time the services of a GATT server change, e.g.
+/// when it indicates Service Changed after a firmware update. The stream
+/// ends when the watch is replaced or the connection is dropped.
+pub struct ServicesChangedStream {
+    changes: Pin<Box<dyn Stream<Item = ()> + Send + Sync>>,
+}
+
+impl ServicesChangedStream {
+    /// Construct a stream yielding the changes sent through the channel.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub(crate) fn new(receiver: Receiver<()>) -> Self {
+        Self::from_stream(receiver)
+    }
+
+    /// Construct a stream yielding the changes yielded by `changes`.
+    pub(crate) fn from_stream(
+        changes: impl Stream<Item = ()> + Send + Sync + 'static,
+    ) -> Self {
+        ServicesChangedStream {
+            changes: Box::pin(changes),
+        }
+    }
+}
+
+impl Stream for ServicesChangedStream {
+    type Item = ();
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.changes.poll_next_unpin(cx)
+    }
+}
+
+/// Battery information of a device, read from its standard Battery Service.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub struct BatteryInfo {
+    level: u8,
+}
+
+impl BatteryInfo {
+    /// Retrieve the battery level, as a percentage of its full charge.
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+}
+
+impl TryFrom<&[u8]> for BatteryInfo {
+    type Error = BluetoothError;
+
+    /// Parse the value of the Battery Level characteristic, a single byte
+    /// between 0 and 100.
+    /// See: Bluetooth Battery Service Specification, Section 3.1.
+    fn try_from(value: &[u8]) -> Result<Self, Self::Error> {
+        match value {
+            &[level] if level <= 100 => Ok(BatteryInfo { level }),
+            _ => Err(BluetoothError::BadTypeConversion(format!(
+                "invalid battery level {:?}",
+                value
+            ))),
+        }
+    }
+}
+
+/// Information identifying a device, read from its standard Device
+/// Information Service. Each field is `None` if the device doesn't expose the
+/// matching characteristic.
+#[derive(PartialEq, Eq, Clone, Debug, Hash, Default)]
+pub struct DeviceInfo {
+    manufacturer_name: Option<String>,
+    model_number: Option<String>,
+    firmware_revision: Option<String>,
+}
+
+impl DeviceInfo {
+    /// Construct a new `DeviceInfo` instance.
+    pub(crate) fn new(
+        manufacturer_name: Option<String>,
+        model_number: Option<String>,
+        firmware_revision: Option<String>,
+    ) -> Self {
+        DeviceInfo {
+            manufacturer_name,
+            model_number,
+            firmware_revision,
+        }
+    }
+
+    /// Retrieve the name of the manufacturer of the device.
+    pub fn manufacturer_name(&self) -> Option<&str> {
+        self.manufacturer_name.as_deref()
+    }
+
+    /// Retrieve the model number assigned by the manufacturer.
+    pub fn model_number(&self) -> Option<&str> {
+        self.model_number.as_deref()
+    }
+
+    /// Retrieve the revision of the firmware running on the device, e.g. to
+    /// check which features it supports.
+    pub fn firmware_revision(&self) -> Option<&str> {
+        self.firmware_revision.as_deref()
+    }
+}
+
+/// Parse the value of a string characteristic of the Device Information
+/// Service. Some devices pad the value with NUL characters, which are
+/// trimmed.
+/// See: Bluetooth Device Information Service Specification, Section 3.
+pub(crate) fn parse_device_info_string(
+    value: &[u8],
+) -> Result<String, BluetoothError> {
+    let end = value.iter().position(|&b| b == 0).unwrap_or(value.len());
+    String::from_utf8(value[..end].to_vec()).map_err(|err| {
+        BluetoothError::BadTypeConversion(format!(
+            "invalid Device Information string {:?}: {}",
+            value, err
+        ))
+    })
+}
+
+/// How a characteristic value is written.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub enum WriteType {
+    /// Wait for the GATT server to acknowledge the write, so that rejected
+    /// writes are reported as errors.
+    WithResponse,
+    /// Don't wait for an acknowledgement. Requires the characteristic to
+    /// support `CharacteristicProperties::WRITE_WITHOUT_RESPONSE`.
+    WithoutResponse,
+    /// Queue the value on the GATT server with Prepare Write requests,
+    /// checking each part echoed back, then apply it with an Execute Write
+    /// request, so that the value is written whole or not at all. Allows
+    /// values longer than a single write request, e.g. for firmware updates.
+    /// See: Bluetooth Core Specification, Vol 3, Part G, Section 4.9.5.
+    Reliable,
+}
+
+/// Preferred trade-off between latency and power consumption of a BLE
+/// connection. Platforms only let applications pick one of these presets,
+/// which they map to a connection interval, peripheral latency and
+/// supervision timeout.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub enum ConnectionPriority {
+    /// Default parameters, suited to most exchanges.
+    Balanced,
+    /// Short connection interval, e.g. for a burst of latency-sensitive
+    /// messages. Uses more power.
+    LowLatency,
+    /// Long connection interval, trading latency for a lower power
+    /// consumption.
+    LowPower,
+}
+
+/// Bit field of the operations supported by a characteristic.
+/// See: Bluetooth Core Specification, Vol 3, Part G, Section 3.3.1.1.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash, Default)]
+pub struct CharacteristicProperties(u8);
+
+impl CharacteristicProperties {
+    pub const BROADCAST: Self = Self(0x01);
+    pub const READ: Self = Self(0x02);
+    pub const WRITE_WITHOUT_RESPONSE: Self = Self(0x04);
+    pub const WRITE: Self = Self(0x08);
+    pub const NOTIFY: Self = Self(0x10);
+    pub const INDICATE: Self = Self(0x20);
+    pub const AUTHENTICATED_SIGNED_WRITES: Self = Self(0x40);
+    pub const EXTENDED_PROPERTIES: Self = Self(0x80);
+
+    /// Construct `CharacteristicProperties` from the raw bit field.
+    pub const fn from_bits(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    /// Retrieve the raw bit field.
+    pub const fn bits(&self) -> u8 {
+        self.0
+    }
+
+    /// Check whether all the properties in `other` are supported.
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for CharacteristicProperties {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gatt_service_new() {
+        let service = GattService::new(Uuid::from_u16(0xFE2C));
+        assert_eq!(service.uuid(), Uuid::from_u16(0xFE2C));
+    }
+
+    #[test]
+    fn gatt_characteristic_new() {
+        let service_uuid = Uuid::from_u16(0xFE2C);
+        let uuid = Uuid::from_u128(0xFE2C1234_8366_4814_8EB0_01DE32100BEA);
+        let characteristic = GattCharacteristic::new(
+            service_uuid,
+            uuid,
+            CharacteristicProperties::WRITE | CharacteristicProperties::NOTIFY,
+        );
+
+        assert_eq!(characteristic.service_uuid(), service_uuid);
+        assert_eq!(characteristic.uuid(), uuid);
+        assert_eq!(characteristic.properties().bits(), 0x18);
+    }
+
+    #[test]
+    fn characteristic_properties_contains() {
+        let properties = CharacteristicProperties::from_bits(0x1A);
+        assert!(properties.contains(CharacteristicProperties::READ));
+        assert!(properties.contains(
+            CharacteristicProperties::WRITE | CharacteristicProperties::NOTIFY
+        ));
+        assert!(!properties.contains(CharacteristicProperties::INDICATE));
+        assert!(!properties.contains(
+            CharacteristicProperties::READ | CharacteristicProperties::INDICATE
+        ));
+        assert!(properties.contains(CharacteristicProperties::default()));
+    }
+
+    #[test]
+    fn battery_info_from_bytes() {
+        assert_eq!(BatteryInfo::try_from([0u8].as_slice()).unwrap().level(), 0);
+        assert_eq!(
+            BatteryInfo::try_from([100u8].as_slice()).unwrap().level(),
+            100
+        );
+
+        for value in [&[][..], &[101], &[50, 50]] {
+            assert!(matches!(
+                BatteryInfo::try_from(value),
+                Err(BluetoothError::BadTypeConversion(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn device_info_string_from_bytes() {
+        assert_eq!(parse_device_info_string(b"1.2.3").unwrap(), "1.2.3");
+        assert_eq!(parse_device_info_string(b"Acme\0\0").unwrap(), "Acme");
+        assert_eq!(parse_device_info_string(b"").unwrap(), "");
+        assert!(matches!(
+            parse_device_info_string(&[0xFF, 0xFE]),
+            Err(BluetoothError::BadTypeConversion(_))
+        ));
+    }
+
+    #[test]
+    fn characteristic_value_stream() {
+        let (mut sender, receiver) = futures::channel::mpsc::channel(2);
+        let mut stream = CharacteristicValueStream::new(receiver);
+
+        sender.try_send(vec![0x01, 0x02]).unwrap();
+        sender.try_send(vec![0x03]).unwrap();
+        drop(sender);
+
+        futures::executor::block_on(async {
+            assert_eq!(stream.next().await, Some(vec![0x01, 0x02]));
+            assert_eq!(stream.next().await, Some(vec![0x03]));
+            assert_eq!(stream.next().await, None);
+        });
+    }
+
+    #[test]
+    fn services_changed_stream() {
+        let (mut sender, receiver) = futures::channel::mpsc::channel(2);
+        let mut stream = ServicesChangedStream::new(receiver);
+
+        sender.try_send(()).unwrap();
+        drop(sender);
+
+        futures::executor::block_on(async {
+            assert_eq!(stream.next().await, Some(()));
+            assert_eq!(stream.next().await, None);
+        });
+    }
+}
@@ -0,0 +1,411 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use async_trait::async_trait;
+use futures::{task::noop_waker, Stream, StreamExt};
+
+use super::{
+    BleAddress, BluetoothError, CharacteristicValueStream, ConnectionPriority,
+    GattCharacteristic, GattService, ServicesChangedStream, Uuid, WriteType,
+};
+use crate::api;
+
+/// GATT client keeping the services and characteristics discovered through
+/// the wrapped client in memory, so that repeated lookups, e.g. during a
+/// pairing flow, don't run a full discovery again. The cache belongs to the
+/// connection: it's emptied when the peripheral reports that its services
+/// changed, or when `clear_discovery_cache()` is called, and dropped along
+/// with the client.
+pub(crate) struct CachedGattClient<C> {
+    client: C,
+    services: Option<Vec<GattService>>,
+    /// Characteristics discovered since the services were cached, keyed by
+    /// service UUID.
+    characteristics: HashMap<Uuid, Vec<GattCharacteristic>>,
+    watch: Arc<Mutex<ServicesWatch>>,
+    /// Number of service changes reported when the services were cached.
+    generation: u64,
+}
+
+/// Service changes reported by the wrapped client, shared by the cache and
+/// the stream returned by `watch_services_changed()`.
+struct ServicesWatch {
+    /// Stream returned by the wrapped client, or `None` once it ended or if
+    /// the client can't report changes. Nothing is cached then, as the
+    /// cache couldn't be invalidated.
+    changes: Option<ServicesChangedStream>,
+    /// Number of changes reported so far.
+    generation: u64,
+    /// Identifies the stream returned by the latest call to
+    /// `watch_services_changed()`. The previous streams end.
+    watcher: u64,
+    /// Waker of the task polling the latest stream, which has to be woken
+    /// by the changes polled on behalf of the cache.
+    waker: Option<Waker>,
+}
+
+impl ServicesWatch {
+    /// Count the changes reported by the wrapped client, waking the task
+    /// watching them, if any.
+    fn poll_changes(&mut self) {
+        let waker = self.waker.clone().unwrap_or_else(noop_waker);
+        let mut cx = Context::from_waker(&waker);
+        while let Some(changes) = &mut self.changes {
+            match changes.poll_next_unpin(&mut cx) {
+                Poll::Ready(Some(())) => self.generation += 1,
+                Poll::Ready(None) => self.changes = None,
+                Poll::Pending => break,
+            }
+        }
+    }
+}
+
+/// Stream returned by `CachedGattClient::watch_services_changed()`, yielding
+/// once for any number of changes reported since it was last polled.
+struct WatchStream {
+    watch: Arc<Mutex<ServicesWatch>>,
+    watcher: u64,
+    /// Number of changes reported when the stream last yielded.
+    generation: u64,
+}
+
+impl Stream for WatchStream {
+    type Item = ();
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let watch = self.watch.clone();
+        let mut watch = watch.lock().unwrap();
+        if watch.watcher != self.watcher {
+            return Poll::Ready(None);
+        }
+
+        watch.waker = Some(cx.waker().clone());
+        watch.poll_changes();
+        if watch.generation != self.generation {
+            self.generation = watch.generation;
+            Poll::Ready(Some(()))
+        } else if watch.changes.is_none() {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<C: api::GattClient> CachedGattClient<C> {
+    /// Empty the cache if the services changed since they were cached, and
+    /// check whether discoveries can be cached.
+    fn check_cache(&mut self) -> bool {
+        let mut watch = self.watch.lock().unwrap();
+        watch.poll_changes();
+        if watch.generation != self.generation || watch.changes.is_none() {
+            self.services = None;
+            self.characteristics.clear();
+            self.generation = watch.generation;
+        }
+
+        watch.changes.is_some()
+    }
+}
+
+#[async_trait]
+impl<C: api::GattClient> api::GattClient for CachedGattClient<C> {
+    /// Connect with the wrapped client, which is asked to report the service
+    /// changes invalidating the cache.
+    async fn connect(addr: BleAddress) -> Result<Self, BluetoothError> {
+        let mut client = C::connect(addr).await?;
+        let changes = client.watch_services_changed().await.ok();
+
+        Ok(CachedGattClient {
+            client,
+            services: None,
+            characteristics: HashMap::new(),
+            watch: Arc::new(Mutex::new(ServicesWatch {
+                changes,
+                generation: 0,
+                watcher: 0,
+                waker: None,
+            })),
+            generation: 0,
+        })
+    }
+
+    fn address(&self) -> BleAddress {
+        self.client.address()
+    }
+
+    fn current_mtu(&self) -> Result<u16, BluetoothError> {
+        self.client.current_mtu()
+    }
+
+    async fn request_mtu(&mut self, mtu: u16) -> Result<u16, BluetoothError> {
+        self.client.request_mtu(mtu).await
+    }
+
+    fn request_connection_priority(
+        &mut self,
+        priority: ConnectionPriority,
+    ) -> Result<(), BluetoothError> {
+        self.client.request_connection_priority(priority)
+    }
+
+    async fn discover_services(
+        &mut self,
+    ) -> Result<Vec<GattService>, BluetoothError> {
+        if !self.check_cache() {
+            return self.client.discover_services().await;
+        }
+        if let Some(services) = &self.services {
+            return Ok(services.clone());
+        }
+
+        let services = self.client.discover_services().await?;
+        // The discovery reflects the changes reported while it ran, like the
+        // GattServicesChanged event raised by an uncached discovery on
+        // Windows.
+        if self.check_cache() {
+            self.services = Some(services.clone());
+        }
+
+        Ok(services)
+    }
+
+    async fn discover_characteristics(
+        &mut self,
+        service: &GattService,
+    ) -> Result<Vec<GattCharacteristic>, BluetoothError> {
+        if !self.check_cache() {
+            return self.client.discover_characteristics(service).await;
+        }
+        if let Some(characteristics) = self.characteristics.get(&service.uuid())
+        {
+            return Ok(characteristics.clone());
+        }
+
+        let characteristics =
+            self.client.discover_characteristics(service).await?;
+        // The characteristics aren't cached if the services changed in the
+        // meantime.
+        if self.check_cache() && self.services.is_some() {
+            self.characteristics
+                .insert(service.uuid(), characteristics.clone());
+        }
+
+        Ok(characteristics)
+    }
+
+    fn clear_discovery_cache(&mut self) {
+        self.services = None;
+        self.characteristics.clear();
+        self.client.clear_discovery_cache();
+    }
+
+    async fn read_characteristic(
+        &mut self,
+        characteristic: &GattCharacteristic,
+    ) -> Result<Vec<u8>, BluetoothError> {
+        self.client.read_characteristic(characteristic).await
+    }
+
+    async fn write_characteristic(
+        &mut self,
+        characteristic: &GattCharacteristic,
+        value: &[u8],
+        write_type: WriteType,
+    ) -> Result<(), BluetoothError> {
+        self.client
+            .write_characteristic(characteristic, value, write_type)
+            .await
+    }
+
+    async fn subscribe(
+        &mut self,
+        characteristic: &GattCharacteristic,
+    ) -> Result<CharacteristicValueStream, BluetoothError> {
+        self.client.subscribe(characteristic).await
+    }
+
+    async fn unsubscribe(
+        &mut self,
+        characteristic: &GattCharacteristic,
+    ) -> Result<(), BluetoothError> {
+        self.client.unsubscribe(characteristic).await
+    }
+
+    /// The changes are those reported by the stream the wrapped client
+    /// returned on connection, which is shared with the cache.
+    async fn watch_services_changed(
+        &mut self,
+    ) -> Result<ServicesChangedStream, BluetoothError> {
+        if self.watch.lock().unwrap().changes.is_none() {
+            return self.client.watch_services_changed().await;
+        }
+
+        let mut watch = self.watch.lock().unwrap();
+        // Changes reported before the call aren't yielded.
+        watch.poll_changes();
+        watch.watcher += 1;
+        if let Some(waker) = watch.waker.take() {
+            waker.wake();
+        }
+
+        Ok(ServicesChangedStream::from_stream(WatchStream {
+            watch: self.watch.clone(),
+            watcher: watch.watcher,
+            generation: watch.generation,
+        }))
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::{
+        api::{GattClient as _, GattRequestHandler, GattServer as _},
+        common::{
+            AttErrorCode, BleAddressKind, CharacteristicProperties,
+            LocalCharacteristic, LocalService,
+        },
+        mock::{self, MockFixtures},
+    };
+
+    const FAST_PAIR_UUID: Uuid = Uuid::from_u16(0xFE2C);
+    const BATTERY_UUID: Uuid = Uuid::from_u16(0x180F);
+    const CHARACTERISTIC_UUID: Uuid = Uuid::from_u16(0x1234);
+
+    struct TestHandler;
+
+    impl GattRequestHandler for TestHandler {
+        fn on_read(
+            &self,
+            _characteristic: &GattCharacteristic,
+        ) -> Result<Vec<u8>, AttErrorCode> {
+            Ok(Vec::new())
+        }
+
+        fn on_write(
+            &self,
+            _characteristic: &GattCharacteristic,
+            _value: &[u8],
+        ) -> Result<(), AttErrorCode> {
+            Ok(())
+        }
+    }
+
+    fn service(uuid: Uuid) -> LocalService {
+        LocalService::new(uuid).with_characteristic(LocalCharacteristic::new(
+            CHARACTERISTIC_UUID,
+            CharacteristicProperties::READ,
+        ))
+    }
+
+    fn install_fixtures() -> BleAddress {
+        let local = BleAddress::new(0x112233445566, BleAddressKind::Public);
+        MockFixtures::new()
+            .with_local_adapter(local, None)
+            .install();
+        local
+    }
+
+    #[test]
+    fn discoveries_cached_until_cleared() {
+        let local = install_fixtures();
+
+        block_on(async {
+            let server =
+                mock::GattServer::new(&[service(FAST_PAIR_UUID)], TestHandler)
+                    .await
+                    .unwrap();
+            let mut client =
+                CachedGattClient::<mock::GattClient>::connect(local)
+                    .await
+                    .unwrap();
+            let services = client.discover_services().await.unwrap();
+            assert_eq!(services, [GattService::new(FAST_PAIR_UUID)]);
+            let characteristics =
+                client.discover_characteristics(&services[0]).await.unwrap();
+
+            // Withdrawing the server doesn't report a change, so the
+            // discoveries are answered from the cache.
+            drop(server);
+            assert_eq!(client.discover_services().await.unwrap(), services);
+            assert_eq!(
+                client.discover_characteristics(&services[0]).await.unwrap(),
+                characteristics
+            );
+
+            client.clear_discovery_cache();
+            assert!(matches!(
+                client.discover_services().await,
+                Err(BluetoothError::Unreachable(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn services_changed_clears_cache() {
+        let local = install_fixtures();
+
+        block_on(async {
+            let server =
+                mock::GattServer::new(&[service(FAST_PAIR_UUID)], TestHandler)
+                    .await
+                    .unwrap();
+            let mut client =
+                CachedGattClient::<mock::GattClient>::connect(local)
+                    .await
+                    .unwrap();
+            let mut changes = client.watch_services_changed().await.unwrap();
+            assert_eq!(
+                client.discover_services().await.unwrap(),
+                [GattService::new(FAST_PAIR_UUID)]
+            );
+
+            // Hosting another server changes the services.
+            drop(server);
+            let _server =
+                mock::GattServer::new(&[service(BATTERY_UUID)], TestHandler)
+                    .await
+                    .unwrap();
+            let services = client.discover_services().await.unwrap();
+            assert_eq!(services, [GattService::new(BATTERY_UUID)]);
+            assert_eq!(
+                client.discover_characteristics(&services[0]).await.unwrap(),
+                [service(BATTERY_UUID)
+                    .characteristic(CHARACTERISTIC_UUID)
+                    .unwrap()]
+            );
+
+            // The change is still reported to the watcher, once.
+            assert_eq!(changes.next().await, Some(()));
+            assert_eq!(futures::poll!(changes.next()), Poll::Pending);
+
+            // Watching again ends the previous stream.
+            let _changes = client.watch_services_changed().await.unwrap();
+            assert_eq!(changes.next().await, None);
+        });
+    }
+}
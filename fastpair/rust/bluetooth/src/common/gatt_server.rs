@@ -0,0 +1,177 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use super::{CharacteristicProperties, GattCharacteristic, Uuid};
+
+/// Definition of a primary service offered by the local GATT server.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct LocalService {
+    uuid: Uuid,
+    characteristics: Vec<LocalCharacteristic>,
+}
+
+impl LocalService {
+    /// Construct a new `LocalService` instance without characteristics.
+    pub fn new(uuid: Uuid) -> Self {
+        LocalService {
+            uuid,
+            characteristics: Vec::new(),
+        }
+    }
+
+    /// Add a characteristic to the service.
+    pub fn with_characteristic(
+        mut self,
+        characteristic: LocalCharacteristic,
+    ) -> Self {
+        self.characteristics.push(characteristic);
+        self
+    }
+
+    /// Retrieve the UUID identifying this service.
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    /// Retrieve the characteristics of this service.
+    pub fn characteristics(&self) -> &[LocalCharacteristic] {
+        &self.characteristics
+    }
+
+    /// Retrieve the characteristic with the given UUID, as seen by remote
+    /// GATT clients.
+    pub fn characteristic(&self, uuid: Uuid) -> Option<GattCharacteristic> {
+        self.characteristics
+            .iter()
+            .find(|characteristic| characteristic.uuid == uuid)
+            .map(|characteristic| {
+                GattCharacteristic::new(
+                    self.uuid,
+                    characteristic.uuid,
+                    characteristic.properties,
+                )
+            })
+    }
+}
+
+/// Definition of a characteristic offered by the local GATT server. Its value
+/// isn't stored by the server: reads and writes are forwarded to the
+/// `api::GattRequestHandler`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub struct LocalCharacteristic {
+    uuid: Uuid,
+    properties: CharacteristicProperties,
+}
+
+impl LocalCharacteristic {
+    /// Construct a new `LocalCharacteristic` instance.
+    pub fn new(uuid: Uuid, properties: CharacteristicProperties) -> Self {
+        LocalCharacteristic { uuid, properties }
+    }
+
+    /// Retrieve the UUID identifying this characteristic.
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    /// Retrieve the operations supported by this characteristic.
+    pub fn properties(&self) -> CharacteristicProperties {
+        self.properties
+    }
+}
+
+/// ATT error code returned to a remote GATT client to reject a request.
+/// See: Bluetooth Core Specification, Vol 3, Part F, Section 3.4.1.1.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub struct AttErrorCode(u8);
+
+impl AttErrorCode {
+    pub const READ_NOT_PERMITTED: Self = Self(0x02);
+    pub const WRITE_NOT_PERMITTED: Self = Self(0x03);
+    pub const INSUFFICIENT_AUTHENTICATION: Self = Self(0x05);
+    pub const REQUEST_NOT_SUPPORTED: Self = Self(0x06);
+    pub const INVALID_OFFSET: Self = Self(0x07);
+    pub const ATTRIBUTE_NOT_LONG: Self = Self(0x0B);
+    pub const INVALID_ATTRIBUTE_VALUE_LENGTH: Self = Self(0x0D);
+    pub const UNLIKELY_ERROR: Self = Self(0x0E);
+
+    /// Construct an `AttErrorCode` from its raw value, e.g. one of the
+    /// application errors in the 0x80 to 0x9F range.
+    pub const fn from_code(code: u8) -> Self {
+        Self(code)
+    }
+
+    /// Retrieve the raw value of the error code.
+    pub const fn code(&self) -> u8 {
+        self.0
+    }
+}
+
+/// Retrieve the part of a characteristic value a read request starting at
+/// `offset` asks for, as long reads are split into several requests.
+#[cfg_attr(not(windows), allow(dead_code))]
+pub(crate) fn value_at_offset(
+    mut value: Vec<u8>,
+    offset: usize,
+) -> Result<Vec<u8>, AttErrorCode> {
+    if offset > value.len() {
+        return Err(AttErrorCode::INVALID_OFFSET);
+    }
+
+    Ok(value.split_off(offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn local_service_characteristic() {
+        let service_uuid = Uuid::from_u16(0xFE2C);
+        let uuid = Uuid::from_u128(0xFE2C1234_8366_4814_8EB0_01DE32100BEA);
+        let properties =
+            CharacteristicProperties::WRITE | CharacteristicProperties::NOTIFY;
+        let service = LocalService::new(service_uuid)
+            .with_characteristic(LocalCharacteristic::new(uuid, properties));
+
+        assert_eq!(service.uuid(), service_uuid);
+        assert_eq!(service.characteristics().len(), 1);
+        assert_eq!(
+            service.characteristic(uuid),
+            Some(GattCharacteristic::new(service_uuid, uuid, properties))
+        );
+        assert_eq!(service.characteristic(Uuid::from_u16(0x1234)), None);
+    }
+
+    #[test]
+    fn att_error_code() {
+        assert_eq!(AttErrorCode::INVALID_OFFSET.code(), 0x07);
+        assert_eq!(AttErrorCode::from_code(0x80).code(), 0x80);
+    }
+
+    #[test]
+    fn value_at_valid_offset() {
+        assert_eq!(value_at_offset(vec![1, 2, 3], 0), Ok(vec![1, 2, 3]));
+        assert_eq!(value_at_offset(vec![1, 2, 3], 2), Ok(vec![3]));
+        assert_eq!(value_at_offset(vec![1, 2, 3], 3), Ok(vec![]));
+    }
+
+    #[test]
+    fn value_at_invalid_offset() {
+        assert_eq!(
+            value_at_offset(vec![1, 2, 3], 4),
+            Err(AttErrorCode::INVALID_OFFSET)
+        );
+    }
+}
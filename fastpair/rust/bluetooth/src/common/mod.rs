@@ -13,10 +13,35 @@
 // limitations under the License.
 
 /// Module for shared functionality between all Bluetooth platforms.
+mod ad_parser;
+mod adapter;
 mod address;
 mod advertisement;
+mod device;
 mod error;
+mod gatt;
+mod gatt_cache;
+mod gatt_server;
+mod retry;
+mod rfcomm;
+#[cfg_attr(not(any(windows, target_os = "android")), allow(dead_code))]
+mod scan_queue;
+mod uuid;
 
+pub(crate) use ad_parser::*;
+pub use adapter::*;
 pub use address::*;
 pub use advertisement::*;
+pub use device::*;
 pub use error::*;
+pub use gatt::*;
+pub(crate) use gatt_cache::*;
+pub use gatt_server::*;
+pub use retry::*;
+pub use rfcomm::*;
+#[cfg_attr(
+    not(any(windows, target_os = "android")),
+    allow(unused_imports)
+)]
+pub(crate) use scan_queue::*;
+pub use uuid::*;
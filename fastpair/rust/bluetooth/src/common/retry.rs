@@ -0,0 +1,205 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{fmt::Display, future::Future, thread, time::Duration};
+
+use tracing::warn;
+
+/// How operations failing with transient errors, e.g. because the device or
+/// the radio is busy, are retried. Other errors are reported right away.
+/// Backoffs double after each attempt, up to `max_backoff`.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Construct a `RetryPolicy` making at most `max_attempts` attempts,
+    /// including the first one.
+    pub fn new(
+        max_attempts: u32,
+        initial_backoff: Duration,
+        max_backoff: Duration,
+    ) -> Self {
+        RetryPolicy {
+            max_attempts: max_attempts.max(1),
+            initial_backoff,
+            max_backoff,
+        }
+    }
+
+    /// Construct a `RetryPolicy` which never retries.
+    pub fn never() -> Self {
+        RetryPolicy::new(1, Duration::ZERO, Duration::ZERO)
+    }
+
+    /// Retrieve the maximum number of attempts, including the first one.
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    /// Retrieve the time to wait after `attempt` failed attempts.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// Run `operation` until it succeeds, fails with an error which isn't
+/// transient, or runs out of attempts.
+#[cfg_attr(not(windows), allow(dead_code))]
+pub(crate) async fn retry<T, E, F, Fut>(
+    policy: &RetryPolicy,
+    is_transient: fn(&E) -> bool,
+    mut operation: F,
+) -> Result<T, E>
+where
+    E: Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Err(err) if attempt < policy.max_attempts && is_transient(&err) => {
+                let backoff = policy.backoff(attempt);
+                warn!(
+                    "Retrying in {:?} after transient error: {}",
+                    backoff, err
+                );
+                sleep(backoff).await;
+                attempt += 1;
+            }
+            res => break res,
+        }
+    }
+}
+
+/// Blocking version of `retry()`, for synchronous operations.
+#[cfg_attr(not(windows), allow(dead_code))]
+pub(crate) fn retry_blocking<T, E>(
+    policy: &RetryPolicy,
+    is_transient: fn(&E) -> bool,
+    mut operation: impl FnMut() -> Result<T, E>,
+) -> Result<T, E>
+where
+    E: Display,
+{
+    let mut attempt = 1;
+    loop {
+        match operation() {
+            Err(err) if attempt < policy.max_attempts && is_transient(&err) => {
+                let backoff = policy.backoff(attempt);
+                warn!(
+                    "Retrying in {:?} after transient error: {}",
+                    backoff, err
+                );
+                thread::sleep(backoff);
+                attempt += 1;
+            }
+            res => break res,
+        }
+    }
+}
+
+/// Wait for `duration` without blocking the executor, which this crate
+/// doesn't pick.
+pub(crate) async fn sleep(duration: Duration) {
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    thread::spawn(move || {
+        thread::sleep(duration);
+        let _ = sender.send(());
+    });
+    let _ = receiver.await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_transient(err: &&str) -> bool {
+        *err == "busy"
+    }
+
+    #[test]
+    fn retry_policy_backoff() {
+        let policy = RetryPolicy::new(
+            5,
+            Duration::from_millis(100),
+            Duration::from_millis(300),
+        );
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(300));
+        assert_eq!(policy.backoff(40), Duration::from_millis(300));
+        assert_eq!(RetryPolicy::never().max_attempts(), 1);
+        assert_eq!(
+            RetryPolicy::new(0, Duration::ZERO, Duration::ZERO),
+            RetryPolicy::never()
+        );
+    }
+
+    #[test]
+    fn retry_transient_errors() {
+        let policy = RetryPolicy::new(3, Duration::ZERO, Duration::ZERO);
+
+        let mut attempts = 0;
+        let res =
+            futures::executor::block_on(retry(&policy, is_transient, || {
+                attempts += 1;
+                let res = if attempts < 3 {
+                    Err("busy")
+                } else {
+                    Ok(attempts)
+                };
+                async move { res }
+            }));
+        assert_eq!(res, Ok(3));
+
+        let mut attempts = 0;
+        let res = retry_blocking(&policy, is_transient, || {
+            attempts += 1;
+            Err::<(), _>("busy")
+        });
+        assert_eq!(res, Err("busy"));
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn retry_hard_failure() {
+        let policy = RetryPolicy::default();
+
+        let mut attempts = 0;
+        let res = retry_blocking(&policy, is_transient, || {
+            attempts += 1;
+            Err::<(), _>("failed")
+        });
+        assert_eq!(res, Err("failed"));
+        assert_eq!(attempts, 1);
+    }
+}
@@ -0,0 +1,98 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures::{Sink, SinkExt, Stream, StreamExt};
+
+use super::BluetoothError;
+
+/// RFCOMM connection to a service of a remote BT Classic device, e.g. the
+/// Fast Pair Message Stream. The socket is a stream of the data received
+/// from the device, chunked as delivered by the platform rather than by the
+/// messages of the protocol on top. The connection is closed when the
+/// socket is dropped.
+pub struct RfcommSocket {
+    incoming:
+        Pin<Box<dyn Stream<Item = Result<Vec<u8>, BluetoothError>> + Send>>,
+    outgoing: Pin<Box<dyn Sink<Vec<u8>, Error = BluetoothError> + Send>>,
+}
+
+impl RfcommSocket {
+    /// Construct a socket receiving data from `incoming` and writing data to
+    /// `outgoing`.
+    #[cfg_attr(
+        not(any(windows, target_os = "android", feature = "mock")),
+        allow(dead_code)
+    )]
+    pub(crate) fn new(
+        incoming: impl Stream<Item = Result<Vec<u8>, BluetoothError>>
+            + Send
+            + 'static,
+        outgoing: impl Sink<Vec<u8>, Error = BluetoothError> + Send + 'static,
+    ) -> Self {
+        RfcommSocket {
+            incoming: Box::pin(incoming),
+            outgoing: Box::pin(outgoing),
+        }
+    }
+
+    /// Write `data` to the remote device, completing once the platform took
+    /// it over.
+    pub async fn write(&mut self, data: &[u8]) -> Result<(), BluetoothError> {
+        self.outgoing.send(data.to_vec()).await
+    }
+}
+
+impl Stream for RfcommSocket {
+    type Item = Result<Vec<u8>, BluetoothError>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        self.incoming.poll_next_unpin(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::channel::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn rfcomm_socket() {
+        let (mut remote_sender, incoming) = mpsc::unbounded();
+        let (outgoing, mut remote_receiver) = mpsc::unbounded();
+        let mut socket = RfcommSocket::new(
+            incoming,
+            outgoing
+                .sink_map_err(|err| BluetoothError::Internal(err.to_string())),
+        );
+
+        futures::executor::block_on(async {
+            remote_sender.send(Ok(vec![1, 2])).await.unwrap();
+            drop(remote_sender);
+            assert_eq!(socket.next().await, Some(Ok(vec![1, 2])));
+            assert_eq!(socket.next().await, None);
+
+            socket.write(&[3, 4]).await.unwrap();
+            assert_eq!(remote_receiver.next().await, Some(vec![3, 4]));
+        });
+    }
+}
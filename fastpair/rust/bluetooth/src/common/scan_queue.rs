@@ -0,0 +1,267 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use futures::Stream;
+use tracing::warn;
+
+use super::{OverflowPolicy, ScanSettings};
+
+/// State shared by both ends of a scan queue.
+struct Shared<T> {
+    capacity: usize,
+    policy: OverflowPolicy,
+    state: Mutex<State<T>>,
+}
+
+struct State<T> {
+    items: VecDeque<T>,
+    /// Number of items dropped because the queue was full.
+    dropped: u64,
+    /// Set once either end is closed, after which pushed items are ignored.
+    closed: bool,
+    /// Waker of the task waiting for the next item, if any.
+    waker: Option<Waker>,
+}
+
+impl<T> State<T> {
+    fn record_drop(&mut self) {
+        if self.dropped == 0 {
+            warn!("Scan queue is full, dropping advertisements until the consumer catches up.");
+        }
+        self.dropped += 1;
+    }
+
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// Create a queue carrying the events of a scan from the platform callbacks
+/// to the consumer, with the capacity and overflow policy of `settings`.
+/// Unlike `futures::channel::mpsc`, a full queue can make room by dropping
+/// its oldest item.
+pub(crate) fn scan_queue<T>(
+    settings: &ScanSettings,
+) -> (ScanQueueSender<T>, ScanQueue<T>) {
+    let shared = Arc::new(Shared {
+        capacity: settings.queue_capacity(),
+        policy: settings.overflow_policy(),
+        state: Mutex::new(State {
+            items: VecDeque::new(),
+            dropped: 0,
+            closed: false,
+            waker: None,
+        }),
+    });
+
+    (
+        ScanQueueSender {
+            shared: shared.clone(),
+        },
+        ScanQueue { shared },
+    )
+}
+
+/// Sending end of a scan queue, which closes the queue when dropped.
+pub(crate) struct ScanQueueSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> ScanQueueSender<T> {
+    /// Queue an item, applying the overflow policy if the queue is full.
+    pub(crate) fn push(&self, item: T) {
+        self.push_with_policy(item, self.shared.policy);
+    }
+
+    /// Queue an item even if the queue is full, for events which mustn't be
+    /// dropped, e.g. the scan stopping.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub(crate) fn push_unbounded(&self, item: T) {
+        self.push_with_policy(item, OverflowPolicy::Grow);
+    }
+
+    fn push_with_policy(&self, item: T, policy: OverflowPolicy) {
+        let mut state = self.shared.state.lock().unwrap();
+        if state.closed {
+            return;
+        }
+
+        if state.items.len() >= self.shared.capacity {
+            match policy {
+                OverflowPolicy::DropNewest => {
+                    state.record_drop();
+                    return;
+                }
+                OverflowPolicy::DropOldest => {
+                    state.items.pop_front();
+                    state.record_drop();
+                }
+                OverflowPolicy::Grow => (),
+            }
+        }
+
+        state.items.push_back(item);
+        state.wake();
+    }
+}
+
+impl<T> Drop for ScanQueueSender<T> {
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.closed = true;
+        state.wake();
+    }
+}
+
+/// Receiving end of a scan queue. The stream ends once the queue is closed
+/// and every item queued before has been taken.
+pub(crate) struct ScanQueue<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> ScanQueue<T> {
+    /// Take the next item, if one was already queued.
+    pub(crate) fn try_recv(&mut self) -> Option<T> {
+        self.shared.state.lock().unwrap().items.pop_front()
+    }
+
+    /// Close the queue, ignoring the items pushed from now on. Items already
+    /// queued can still be taken.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    pub(crate) fn close(&mut self) {
+        self.shared.state.lock().unwrap().closed = true;
+    }
+
+    /// Retrieve the number of items dropped because the queue was full.
+    pub(crate) fn dropped(&self) -> u64 {
+        self.shared.state.lock().unwrap().dropped
+    }
+}
+
+impl<T> Stream for ScanQueue<T> {
+    type Item = T;
+
+    fn poll_next(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let mut state = self.shared.state.lock().unwrap();
+        if let Some(item) = state.items.pop_front() {
+            Poll::Ready(Some(item))
+        } else if state.closed {
+            Poll::Ready(None)
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use super::*;
+
+    fn settings(capacity: usize, policy: OverflowPolicy) -> ScanSettings {
+        ScanSettings::new()
+            .with_queue_capacity(capacity)
+            .with_overflow_policy(policy)
+    }
+
+    #[test]
+    fn drop_newest_when_full() {
+        let (sender, mut queue) =
+            scan_queue(&settings(2, OverflowPolicy::DropNewest));
+        for i in 0..5 {
+            sender.push(i);
+        }
+
+        assert_eq!(queue.try_recv(), Some(0));
+        assert_eq!(queue.try_recv(), Some(1));
+        assert_eq!(queue.try_recv(), None);
+        assert_eq!(queue.dropped(), 3);
+    }
+
+    #[test]
+    fn drop_oldest_when_full() {
+        let (sender, mut queue) =
+            scan_queue(&settings(2, OverflowPolicy::DropOldest));
+        for i in 0..5 {
+            sender.push(i);
+        }
+
+        assert_eq!(queue.try_recv(), Some(3));
+        assert_eq!(queue.try_recv(), Some(4));
+        assert_eq!(queue.try_recv(), None);
+        assert_eq!(queue.dropped(), 3);
+    }
+
+    #[test]
+    fn grow_when_full() {
+        let (sender, mut queue) =
+            scan_queue(&settings(2, OverflowPolicy::Grow));
+        for i in 0..5 {
+            sender.push(i);
+        }
+
+        for i in 0..5 {
+            assert_eq!(queue.try_recv(), Some(i));
+        }
+        assert_eq!(queue.dropped(), 0);
+    }
+
+    #[test]
+    fn push_unbounded_when_full() {
+        let (sender, mut queue) =
+            scan_queue(&settings(1, OverflowPolicy::DropOldest));
+        sender.push(0);
+        sender.push_unbounded(1);
+
+        assert_eq!(queue.try_recv(), Some(0));
+        assert_eq!(queue.try_recv(), Some(1));
+        assert_eq!(queue.dropped(), 0);
+    }
+
+    #[test]
+    fn stream_ends_once_closed() {
+        let (sender, mut queue) = scan_queue(&ScanSettings::new());
+        sender.push(0);
+        queue.close();
+        sender.push(1);
+
+        futures::executor::block_on(async {
+            assert_eq!(queue.next().await, Some(0));
+            assert_eq!(queue.next().await, None);
+        });
+
+        let (sender, mut queue) = scan_queue(&ScanSettings::new());
+        sender.push(0);
+        drop(sender);
+
+        futures::executor::block_on(async {
+            assert_eq!(queue.next().await, Some(0));
+            assert_eq!(queue.next().await, None);
+        });
+    }
+}
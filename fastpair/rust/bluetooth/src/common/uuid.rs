@@ -0,0 +1,223 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{fmt, str::FromStr};
+
+use super::BluetoothError;
+
+/// 128-bit UUID identifying a GATT attribute, e.g. a service or a
+/// characteristic.
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash, PartialOrd, Ord)]
+pub struct Uuid(u128);
+
+/// Bluetooth Base UUID, 00000000-0000-1000-8000-00805F9B34FB. 16-bit and
+/// 32-bit UUIDs are shorthands for UUIDs built on top of it.
+/// See: Bluetooth Core Specification, Vol 3, Part B, Section 2.5.1.
+const BASE_UUID: u128 = 0x00000000_0000_1000_8000_00805F9B34FB;
+
+impl Uuid {
+    /// Construct a `Uuid` from its 128-bit value.
+    pub const fn from_u128(uuid: u128) -> Self {
+        Uuid(uuid)
+    }
+
+    /// Construct a `Uuid` from a 16-bit UUID assigned by the Bluetooth SIG,
+    /// e.g. 0xFE2C for Fast Pair.
+    pub const fn from_u16(uuid: u16) -> Self {
+        Uuid::from_u32(uuid as u32)
+    }
+
+    /// Construct a `Uuid` from a 32-bit UUID assigned by the Bluetooth SIG.
+    pub const fn from_u32(uuid: u32) -> Self {
+        Uuid(BASE_UUID | ((uuid as u128) << 96))
+    }
+
+    /// Retrieve the 128-bit value of this UUID.
+    pub const fn as_u128(&self) -> u128 {
+        self.0
+    }
+
+    /// Retrieve the 16-bit form of this UUID, if it has one.
+    pub fn as_u16(&self) -> Option<u16> {
+        self.as_u32().and_then(|uuid| u16::try_from(uuid).ok())
+    }
+
+    /// Retrieve the 32-bit form of this UUID, if it has one.
+    pub fn as_u32(&self) -> Option<u32> {
+        if self.0 & ((1 << 96) - 1) == BASE_UUID {
+            Some((self.0 >> 96) as u32)
+        } else {
+            None
+        }
+    }
+}
+
+impl From<u16> for Uuid {
+    fn from(uuid: u16) -> Self {
+        Uuid::from_u16(uuid)
+    }
+}
+
+impl From<u32> for Uuid {
+    fn from(uuid: u32) -> Self {
+        Uuid::from_u32(uuid)
+    }
+}
+
+impl From<u128> for Uuid {
+    fn from(uuid: u128) -> Self {
+        Uuid::from_u128(uuid)
+    }
+}
+
+impl From<Uuid> for u128 {
+    fn from(uuid: Uuid) -> Self {
+        uuid.0
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl From<uuid::Uuid> for Uuid {
+    fn from(uuid: uuid::Uuid) -> Self {
+        Uuid(uuid.as_u128())
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl From<Uuid> for uuid::Uuid {
+    fn from(uuid: Uuid) -> Self {
+        uuid::Uuid::from_u128(uuid.0)
+    }
+}
+
+impl fmt::Display for Uuid {
+    /// Format as hyphenated lowercase hex, e.g.
+    /// "0000fe2c-0000-1000-8000-00805f9b34fb".
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let val = self.0;
+        write!(
+            f,
+            "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+            val >> 96,
+            (val >> 80) & 0xFFFF,
+            (val >> 64) & 0xFFFF,
+            (val >> 48) & 0xFFFF,
+            val & 0xFFFF_FFFF_FFFF,
+        )
+    }
+}
+
+impl FromStr for Uuid {
+    type Err = BluetoothError;
+
+    /// Parse a hyphenated UUID, e.g. "0000fe2c-0000-1000-8000-00805f9b34fb".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err =
+            || BluetoothError::BadTypeConversion(format!("invalid UUID {}", s));
+
+        let groups: Vec<&str> = s.split('-').collect();
+        let lens: Vec<usize> = groups.iter().map(|group| group.len()).collect();
+        if lens != [8, 4, 4, 4, 12] {
+            return Err(err());
+        }
+
+        let hex: String = groups.concat();
+        if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(err());
+        }
+
+        u128::from_str_radix(&hex, 16).map(Uuid).map_err(|_| err())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uuid_from_u16() {
+        let uuid = Uuid::from_u16(0xFE2C);
+        assert_eq!(uuid.as_u128(), 0x0000FE2C_0000_1000_8000_00805F9B34FB);
+        assert_eq!(uuid.as_u16(), Some(0xFE2C));
+        assert_eq!(uuid.as_u32(), Some(0xFE2C));
+        assert_eq!(Uuid::from(0xFE2Cu16), uuid);
+    }
+
+    #[test]
+    fn uuid_from_u32() {
+        let uuid = Uuid::from_u32(0x1234_5678);
+        assert_eq!(uuid.as_u128(), 0x12345678_0000_1000_8000_00805F9B34FB);
+        assert_eq!(uuid.as_u16(), None);
+        assert_eq!(uuid.as_u32(), Some(0x1234_5678));
+    }
+
+    #[test]
+    fn uuid_without_short_form() {
+        let uuid = Uuid::from_u128(0xFE2C1234_8366_4814_8EB0_01DE32100BEA);
+        assert_eq!(uuid.as_u16(), None);
+        assert_eq!(uuid.as_u32(), None);
+        assert_eq!(u128::from(uuid), 0xFE2C1234_8366_4814_8EB0_01DE32100BEA);
+    }
+
+    #[test]
+    fn uuid_display() {
+        assert_eq!(
+            Uuid::from_u16(0xFE2C).to_string(),
+            "0000fe2c-0000-1000-8000-00805f9b34fb"
+        );
+        assert_eq!(
+            Uuid::from_u128(0xFE2C1234_8366_4814_8EB0_01DE32100BEA).to_string(),
+            "fe2c1234-8366-4814-8eb0-01de32100bea"
+        );
+    }
+
+    #[test]
+    fn uuid_from_str() {
+        let uuid: Uuid =
+            "FE2C1234-8366-4814-8EB0-01DE32100BEA".parse().unwrap();
+        assert_eq!(
+            uuid,
+            Uuid::from_u128(0xFE2C1234_8366_4814_8EB0_01DE32100BEA)
+        );
+        assert_eq!(uuid.to_string().parse::<Uuid>().unwrap(), uuid);
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn uuid_crate_conversion() {
+        let other =
+            uuid::Uuid::parse_str("0000fe2c-0000-1000-8000-00805f9b34fb")
+                .unwrap();
+        assert_eq!(Uuid::from(other), Uuid::from_u16(0xFE2C));
+        assert_eq!(uuid::Uuid::from(Uuid::from_u16(0xFE2C)), other);
+    }
+
+    #[test]
+    fn uuid_from_invalid_str() {
+        for s in [
+            "",
+            "fe2c1234",
+            "fe2c1234-8366-4814-8eb0-01de32100be",
+            "fe2c1234-8366-4814-8eb0-01de32100beaa",
+            "fe2c12348366-4814-8eb0-01de-32100bea",
+            "fe2c1234-8366-4814-8eb0-01de32100beg",
+            "+e2c1234-8366-4814-8eb0-01de32100bea",
+        ] {
+            assert!(matches!(
+                s.parse::<Uuid>(),
+                Err(BluetoothError::BadTypeConversion(_))
+            ));
+        }
+    }
+}
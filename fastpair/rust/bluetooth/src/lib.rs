@@ -14,23 +14,58 @@
 
 pub mod api;
 mod common;
+mod message_stream;
+mod types;
 
-use api::{BleAdapter, BleDevice, ClassicDevice};
+use api::{
+    BleAdapter, BleAdvertiser, BleDevice, ClassicAdapter, ClassicDevice,
+    GattClient, GattServer,
+};
+use common::CachedGattClient;
 pub use common::{
+    AdStructure, AdapterState, AdapterStateStream, AdvertisementPayload,
+    AdvertisementStream, AdvertisingParameters, AttErrorCode, BatteryInfo,
     BleAddress, BleAddressKind, BleAdvertisement, BleDataTypeId,
-    BluetoothError, ClassicAddress, PairingResult, ServiceData,
+    BluetoothError, CharacteristicProperties, CharacteristicValueStream,
+    ClassOfDevice, ClassicAddress, ConnectionPriority, ConnectionStatus,
+    ConnectionStatusStream, DeviceAddress, DeviceInfo, DiscoveredClassicDevice,
+    GattCharacteristic, GattService, LocalCharacteristic, LocalService,
+    ManufacturerData, OverflowPolicy, PairedDevice, PairingResult, Phy,
+    ProtectionLevel, RandomAddressKind, RetryPolicy, RfcommSocket, ScanFilter,
+    ScanMode, ScanSettings, SdpRecord, ServiceData, ServicesChangedStream,
+    Uuid, WriteType,
+};
+pub use message_stream::{
+    MessageHandler, MessageResponse, MessageStreamClient,
+    MESSAGE_STREAM_SERVICE_UUID,
+};
+pub use types::packets::{
+    AcknowledgementCode, BluetoothCode, CompanionAppEventCode,
+    DeviceActionEventCode, DeviceInformationEventCode, MessageGroup,
+    MessageStreamPacket, NakReason, SassCode,
 };
 
 cfg_if::cfg_if! {
-    if #[cfg(windows)] {
+    if #[cfg(feature = "mock")] {
+        mod mock;
+        use self::mock as platform;
+        pub use self::mock::{MockAdvertisement, MockDevice, MockFixtures};
+    } else if #[cfg(windows)] {
         mod windows;
         use self::windows as platform;
+    } else if #[cfg(target_os = "android")] {
+        mod android;
+        use self::android as platform;
+        pub use self::android::init_android;
     } else {
         mod unsupported;
         use unsupported as platform;
     }
 }
 
+/// Entry point constructing the types implementing the `api` traits on the
+/// target platform. These types and the futures returned by their methods
+/// are `Send`, so they can be moved to tasks spawned on any thread.
 pub struct Platform;
 
 impl Platform {
@@ -39,15 +74,90 @@ impl Platform {
         platform::BleAdapter::default().await
     }
 
+    pub async fn default_adapter_with_retry_policy(
+        retry_policy: RetryPolicy,
+    ) -> Result<impl api::BleAdapter, BluetoothError> {
+        platform::BleAdapter::default_with_retry_policy(retry_policy).await
+    }
+
+    pub async fn default_classic_adapter(
+    ) -> Result<impl api::ClassicAdapter, BluetoothError> {
+        platform::ClassicAdapter::default().await
+    }
+
+    pub async fn default_advertiser(
+    ) -> Result<impl api::BleAdvertiser, BluetoothError> {
+        platform::BleAdvertiser::default().await
+    }
+
     pub async fn new_ble_device(
         addr: BleAddress,
     ) -> Result<impl api::BleDevice, BluetoothError> {
         platform::BleDevice::new(addr).await
     }
 
+    pub async fn new_ble_device_with_retry_policy(
+        addr: BleAddress,
+        retry_policy: RetryPolicy,
+    ) -> Result<impl api::BleDevice, BluetoothError> {
+        platform::BleDevice::new_with_retry_policy(addr, retry_policy).await
+    }
+
     pub async fn new_classic_device(
         addr: ClassicAddress,
     ) -> Result<impl api::ClassicDevice, BluetoothError> {
         platform::ClassicDevice::new(addr).await
     }
+
+    pub async fn new_classic_device_with_retry_policy(
+        addr: ClassicAddress,
+        retry_policy: RetryPolicy,
+    ) -> Result<impl api::ClassicDevice, BluetoothError> {
+        platform::ClassicDevice::new_with_retry_policy(addr, retry_policy).await
+    }
+
+    /// List the devices paired with the local adapter, over BT Classic or
+    /// BLE, e.g. to check whether a device is already paired.
+    pub async fn list_paired_devices(
+    ) -> Result<Vec<PairedDevice>, BluetoothError> {
+        platform::list_paired_devices().await
+    }
+
+    /// Connect to the GATT server of the peripheral with the given address.
+    /// The client caches the services and characteristics it discovers for
+    /// as long as it's connected, until the services change.
+    pub async fn connect_gatt(
+        addr: BleAddress,
+    ) -> Result<impl api::GattClient, BluetoothError> {
+        CachedGattClient::<platform::GattClient>::connect(addr).await
+    }
+
+    pub async fn new_gatt_server<H: api::GattRequestHandler>(
+        services: &[LocalService],
+        handler: H,
+    ) -> Result<impl api::GattServer, BluetoothError> {
+        platform::GattServer::new(services, handler).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send<T: Send>(_: &T) {}
+
+    #[test]
+    fn platform_futures_are_send() {
+        let ble_addr = BleAddress::new(0x112233445566, BleAddressKind::Public);
+        let classic_addr = ClassicAddress::from(0x112233445566);
+
+        // The futures are only created, never polled.
+        assert_send(&Platform::default_adapter());
+        assert_send(&Platform::default_classic_adapter());
+        assert_send(&Platform::default_advertiser());
+        assert_send(&Platform::new_ble_device(ble_addr));
+        assert_send(&Platform::new_classic_device(classic_addr));
+        assert_send(&Platform::list_paired_devices());
+        assert_send(&Platform::connect_gatt(ble_addr));
+    }
 }
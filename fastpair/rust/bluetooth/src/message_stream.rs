@@ -15,40 +15,511 @@
 // Specification: https://developers.google.com/nearby/fast-pair/specifications/extensions/messagestream
 // This file should be in sync with fastpair/message_stream/message_stream.h.
 
-use crate::types::packets::{MessageGroup, MessageStreamPacket};
+use std::collections::VecDeque;
 
-struct BatteryInfo {
-    is_charging: bool,
+use futures::StreamExt;
+
+use crate::{
+    api::ClassicDevice,
+    common::{BleAddress, BleAddressKind, BluetoothError, RfcommSocket, Uuid},
+    types::packets::{
+        AcknowledgementCode, BluetoothCode, CompanionAppEventCode,
+        DeviceActionEventCode, DeviceInformationEventCode, MessageGroup,
+        MessageStreamPacket, NakReason, SassCode,
+    },
+};
+
+/// UUID of the RFCOMM service carrying the Message Stream.
+pub const MESSAGE_STREAM_SERVICE_UUID: Uuid =
+    Uuid::from_u128(0xdf21fe2c_2515_4fdb_8886_f12c4d67927c);
+
+/// Response sent back by `MessageStreamClient` for a packet received from the
+/// provider.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessageResponse {
+    Ack,
+    Nak(NakReason),
 }
 
-pub struct MessageStream {
-    battery_info: BatteryInfo,
+/// Implemented by the features built on the Message Stream, e.g. battery
+/// notifications or SASS, to handle the packets of the message groups they
+/// care about. Each method receives the message code and additional data of
+/// a packet, and may return the response to send back. The default
+/// implementations ignore the packet.
+pub trait MessageHandler: Send {
+    fn on_bluetooth(
+        &mut self,
+        _code: BluetoothCode,
+        _data: &[u8],
+    ) -> Option<MessageResponse> {
+        None
+    }
+
+    fn on_companion_app_event(
+        &mut self,
+        _code: CompanionAppEventCode,
+        _data: &[u8],
+    ) -> Option<MessageResponse> {
+        None
+    }
+
+    fn on_device_information_event(
+        &mut self,
+        _code: DeviceInformationEventCode,
+        _data: &[u8],
+    ) -> Option<MessageResponse> {
+        None
+    }
+
+    fn on_device_action_event(
+        &mut self,
+        _code: DeviceActionEventCode,
+        _data: &[u8],
+    ) -> Option<MessageResponse> {
+        None
+    }
+
+    fn on_sass(
+        &mut self,
+        _code: SassCode,
+        _data: &[u8],
+    ) -> Option<MessageResponse> {
+        None
+    }
+
+    /// Handle the provider's acknowledgement of a packet sent by the seeker,
+    /// which mustn't be responded to.
+    fn on_acknowledgement(&mut self, _code: AcknowledgementCode, _data: &[u8]) {
+    }
+}
+
+/// Seeker side of the Message Stream with a paired Fast Pair provider.
+/// Packets received from the provider are dispatched to the registered
+/// `MessageHandler`s.
+pub struct MessageStreamClient {
+    socket: RfcommSocket,
+    /// Data received but not parsed yet, e.g. the start of a packet split
+    /// across reads.
+    buffer: Vec<u8>,
+    /// Packets received before the initial exchange completed, which are
+    /// dispatched first.
+    pending: VecDeque<MessageStreamPacket>,
+    model_id: u32,
+    ble_address: BleAddress,
+    handlers: Vec<Box<dyn MessageHandler>>,
+}
+
+impl MessageStreamClient {
+    /// Open the Message Stream to `device`, then wait for the provider to
+    /// send its model ID and BLE address, as it does right after the
+    /// connection is made.
+    pub async fn connect<D: ClassicDevice>(
+        device: &D,
+    ) -> Result<Self, BluetoothError> {
+        let socket = device.connect_rfcomm(MESSAGE_STREAM_SERVICE_UUID).await?;
+        Self::from_socket(socket).await
+    }
+
+    async fn from_socket(
+        mut socket: RfcommSocket,
+    ) -> Result<Self, BluetoothError> {
+        let mut buffer = Vec::new();
+        let mut pending = VecDeque::new();
+        let mut model_id = None;
+        let mut ble_address = None;
+
+        let (model_id, ble_address) = loop {
+            if let (Some(model_id), Some(ble_address)) = (model_id, ble_address)
+            {
+                break (model_id, ble_address);
+            }
+
+            let packet = read_packet(&mut socket, &mut buffer)
+                .await?
+                .ok_or_else(|| {
+                    BluetoothError::Unreachable(String::from(
+                        "the provider closed the Message Stream before \
+                        sending its model ID and BLE address",
+                    ))
+                })?;
+            match packet.group {
+                MessageGroup::DeviceInformationEvent(
+                    DeviceInformationEventCode::ModelId,
+                ) => model_id = Some(parse_model_id(&packet.additional_data)?),
+                MessageGroup::DeviceInformationEvent(
+                    DeviceInformationEventCode::BleAddressUpdated,
+                ) => {
+                    ble_address =
+                        Some(parse_ble_address(&packet.additional_data)?)
+                }
+                _ => pending.push_back(packet),
+            }
+        };
+
+        Ok(MessageStreamClient {
+            socket,
+            buffer,
+            pending,
+            model_id,
+            ble_address,
+            handlers: Vec::new(),
+        })
+    }
+
+    /// Retrieve the model ID sent by the provider.
+    pub fn model_id(&self) -> u32 {
+        self.model_id
+    }
+
+    /// Retrieve the BLE address last sent by the provider, which changes as
+    /// the provider rotates it.
+    pub fn ble_address(&self) -> BleAddress {
+        self.ble_address
+    }
+
+    /// Register `handler` to receive the packets dispatched from now on.
+    pub fn add_handler(&mut self, handler: impl MessageHandler + 'static) {
+        self.handlers.push(Box::new(handler));
+    }
+
+    /// Wait for the next packet from the provider and dispatch it to every
+    /// handler, sending back the first response returned, if any. Returns
+    /// `false` once the provider closed the Message Stream.
+    pub async fn dispatch_next(&mut self) -> Result<bool, BluetoothError> {
+        let packet = match self.pending.pop_front() {
+            Some(packet) => packet,
+            None => {
+                match read_packet(&mut self.socket, &mut self.buffer).await? {
+                    Some(packet) => packet,
+                    None => return Ok(false),
+                }
+            }
+        };
+
+        if packet.group
+            == MessageGroup::DeviceInformationEvent(
+                DeviceInformationEventCode::BleAddressUpdated,
+            )
+        {
+            self.ble_address = parse_ble_address(&packet.additional_data)?;
+        }
+
+        match self.dispatch(&packet) {
+            Some(MessageResponse::Ack) => self.ack(&packet).await?,
+            Some(MessageResponse::Nak(reason)) => {
+                self.nak(&packet, reason).await?
+            }
+            None => (),
+        }
+
+        Ok(true)
+    }
+
+    /// Dispatch the packets from the provider until it closes the Message
+    /// Stream.
+    pub async fn run(&mut self) -> Result<(), BluetoothError> {
+        while self.dispatch_next().await? {}
+        Ok(())
+    }
+
+    /// Send `packet` to the provider.
+    pub async fn send(
+        &mut self,
+        packet: &MessageStreamPacket,
+    ) -> Result<(), BluetoothError> {
+        self.socket.write(&packet.to_bytes()?).await
+    }
+
+    /// Acknowledge `packet`, received from the provider.
+    pub async fn ack(
+        &mut self,
+        packet: &MessageStreamPacket,
+    ) -> Result<(), BluetoothError> {
+        self.send(&MessageStreamPacket::ack(packet)).await
+    }
+
+    /// Reject `packet`, received from the provider, for `reason`.
+    pub async fn nak(
+        &mut self,
+        packet: &MessageStreamPacket,
+        reason: NakReason,
+    ) -> Result<(), BluetoothError> {
+        self.send(&MessageStreamPacket::nak(packet, reason)).await
+    }
+
+    fn dispatch(
+        &mut self,
+        packet: &MessageStreamPacket,
+    ) -> Option<MessageResponse> {
+        let data = packet.additional_data.as_slice();
+        let mut response = None;
+
+        for handler in &mut self.handlers {
+            let handler_response = match packet.group {
+                MessageGroup::Bluetooth(code) => {
+                    handler.on_bluetooth(code, data)
+                }
+                MessageGroup::CompanionAppEvent(code) => {
+                    handler.on_companion_app_event(code, data)
+                }
+                MessageGroup::DeviceInformationEvent(code) => {
+                    handler.on_device_information_event(code, data)
+                }
+                MessageGroup::DeviceActionEvent(code) => {
+                    handler.on_device_action_event(code, data)
+                }
+                MessageGroup::Sass(code) => handler.on_sass(code, data),
+                MessageGroup::Acknowledgement(code) => {
+                    handler.on_acknowledgement(code, data);
+                    None
+                }
+                MessageGroup::Unknown { .. } => None,
+            };
+            response = response.or(handler_response);
+        }
+
+        response
+    }
 }
 
-impl MessageStream {
-    pub fn on_received(&mut self, packet: MessageStreamPacket) {
-        match packet.group {
-            MessageGroup::Bluetooth(b) => println!("It's Bluetooth {:?}", b),
-            MessageGroup::CompanionAppEvent(c) => println!("It's CompanionAppEvent :{:?}", c),
-            _ => println!("whatever else"),
+/// Read the next packet from `socket`, `buffer` holding the data received
+/// but not parsed yet. Returns `None` once the socket is closed.
+async fn read_packet(
+    socket: &mut RfcommSocket,
+    buffer: &mut Vec<u8>,
+) -> Result<Option<MessageStreamPacket>, BluetoothError> {
+    loop {
+        if let Some((packet, len)) = MessageStreamPacket::parse(buffer) {
+            buffer.drain(..len);
+            return Ok(Some(packet));
         }
+
+        match socket.next().await {
+            Some(data) => buffer.extend_from_slice(&data?),
+            None if buffer.is_empty() => return Ok(None),
+            None => {
+                return Err(BluetoothError::Unreachable(String::from(
+                    "the Message Stream was closed in the middle of a packet",
+                )))
+            }
+        }
+    }
+}
+
+fn parse_model_id(data: &[u8]) -> Result<u32, BluetoothError> {
+    match *data {
+        [a, b, c] => Ok(u32::from_be_bytes([0, a, b, c])),
+        _ => Err(BluetoothError::BadTypeConversion(format!(
+            "Model ID event of {} bytes instead of 3.",
+            data.len()
+        ))),
     }
 }
 
+/// Parse the BLE address of the provider, most significant byte first. The
+/// address is random, as it's rotated.
+fn parse_ble_address(data: &[u8]) -> Result<BleAddress, BluetoothError> {
+    let bytes: [u8; 6] = data.try_into().map_err(|_| {
+        BluetoothError::BadTypeConversion(format!(
+            "BLE address updated event of {} bytes instead of 6.",
+            data.len()
+        ))
+    })?;
+    let mut addr = [0; 8];
+    addr[2..].copy_from_slice(&bytes);
+
+    Ok(BleAddress::new(
+        u64::from_be_bytes(addr),
+        BleAddressKind::Random,
+    ))
+}
+
 #[cfg(test)]
 mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use futures::{channel::mpsc, stream, SinkExt};
+
     use super::*;
-    use crate::types::packets::{BluetoothCode, MessageGroup, MessageStreamPacket};
+
+    const MODEL_ID: [u8; 7] = [0x03, 0x01, 0x00, 0x03, 0xAA, 0xBB, 0xCC];
+    const BLE_ADDRESS: [u8; 10] =
+        [0x03, 0x02, 0x00, 0x06, 0xC0, 0x11, 0x22, 0x33, 0x44, 0x55];
+    const RING: [u8; 6] = [0x04, 0x01, 0x00, 0x02, 0x01, 0x3C];
+    const SILENCE_MODE: [u8; 4] = [0x01, 0x01, 0x00, 0x00];
+
+    /// Build a socket receiving `incoming` chunk by chunk, along with the
+    /// receiver of the data written to it.
+    fn socket(
+        incoming: Vec<Vec<u8>>,
+    ) -> (RfcommSocket, mpsc::UnboundedReceiver<Vec<u8>>) {
+        let (outgoing, written) = mpsc::unbounded();
+        let socket = RfcommSocket::new(
+            stream::iter(incoming.into_iter().map(Ok)),
+            outgoing
+                .sink_map_err(|err| BluetoothError::Internal(err.to_string())),
+        );
+        (socket, written)
+    }
+
+    /// Handler recording the packets it gets, which acknowledges rings and
+    /// rejects silence mode changes.
+    #[derive(Clone, Default)]
+    struct RecordingHandler {
+        received: Arc<Mutex<Vec<MessageGroup>>>,
+    }
+
+    impl MessageHandler for RecordingHandler {
+        fn on_bluetooth(
+            &mut self,
+            code: BluetoothCode,
+            _data: &[u8],
+        ) -> Option<MessageResponse> {
+            self.received
+                .lock()
+                .unwrap()
+                .push(MessageGroup::Bluetooth(code));
+            Some(MessageResponse::Nak(NakReason::NotSupported))
+        }
+
+        fn on_device_information_event(
+            &mut self,
+            code: DeviceInformationEventCode,
+            _data: &[u8],
+        ) -> Option<MessageResponse> {
+            self.received
+                .lock()
+                .unwrap()
+                .push(MessageGroup::DeviceInformationEvent(code));
+            None
+        }
+
+        fn on_device_action_event(
+            &mut self,
+            code: DeviceActionEventCode,
+            data: &[u8],
+        ) -> Option<MessageResponse> {
+            self.received
+                .lock()
+                .unwrap()
+                .push(MessageGroup::DeviceActionEvent(code));
+            (data == [0x01, 0x3C]).then_some(MessageResponse::Ack)
+        }
+    }
 
     #[test]
-    fn test_on_received() {
-        let packet = MessageStreamPacket {
-            group: MessageGroup::Bluetooth(BluetoothCode::DisableSilenceMode),
-            additional_data: vec![0, 1, 2],
-        };
-        let mut message = MessageStream {
-            battery_info: BatteryInfo { is_charging: false }
-        };
-        message.on_received(packet);
+    fn initial_exchange() {
+        // A packet sent before the device information, and the model ID
+        // split across reads.
+        let (socket, mut written) = socket(vec![
+            RING.to_vec(),
+            MODEL_ID[..5].to_vec(),
+            [&MODEL_ID[5..], &BLE_ADDRESS[..]].concat(),
+        ]);
+
+        futures::executor::block_on(async {
+            let mut client =
+                MessageStreamClient::from_socket(socket).await.unwrap();
+            assert_eq!(client.model_id(), 0xAABBCC);
+            assert_eq!(
+                client.ble_address(),
+                BleAddress::new(0xC01122334455, BleAddressKind::Random)
+            );
+
+            let handler = RecordingHandler::default();
+            client.add_handler(handler.clone());
+            assert!(client.dispatch_next().await.unwrap());
+            assert!(!client.dispatch_next().await.unwrap());
+            assert_eq!(
+                *handler.received.lock().unwrap(),
+                [MessageGroup::DeviceActionEvent(DeviceActionEventCode::Ring)]
+            );
+            assert_eq!(
+                written.next().await,
+                Some(vec![0xFF, 0x01, 0x00, 0x02, 0x04, 0x01])
+            );
+        });
+    }
+
+    #[test]
+    fn closed_before_initial_exchange() {
+        let (socket, _) = socket(vec![MODEL_ID.to_vec()]);
+
+        futures::executor::block_on(async {
+            assert!(matches!(
+                MessageStreamClient::from_socket(socket).await,
+                Err(BluetoothError::Unreachable(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn malformed_device_information() {
+        let (socket, _) =
+            socket(vec![vec![0x03, 0x01, 0x00, 0x02, 0xAA, 0xBB]]);
+
+        futures::executor::block_on(async {
+            assert!(matches!(
+                MessageStreamClient::from_socket(socket).await,
+                Err(BluetoothError::BadTypeConversion(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn dispatch_and_respond() {
+        let new_address =
+            [0x03, 0x02, 0x00, 0x06, 0xC0, 0x66, 0x77, 0x88, 0x99, 0xAA];
+        let (socket, mut written) = socket(vec![
+            MODEL_ID.to_vec(),
+            BLE_ADDRESS.to_vec(),
+            RING.to_vec(),
+            SILENCE_MODE.to_vec(),
+            new_address.to_vec(),
+            // Unknown message group.
+            vec![0x10, 0x01, 0x00, 0x00],
+            // Truncated packet.
+            vec![0x04, 0x01, 0x00],
+        ]);
+
+        futures::executor::block_on(async {
+            let mut client =
+                MessageStreamClient::from_socket(socket).await.unwrap();
+            let handler = RecordingHandler::default();
+            client.add_handler(handler.clone());
+
+            for _ in 0..4 {
+                assert!(client.dispatch_next().await.unwrap());
+            }
+            assert!(matches!(
+                client.dispatch_next().await,
+                Err(BluetoothError::Unreachable(_))
+            ));
+
+            assert_eq!(
+                *handler.received.lock().unwrap(),
+                [
+                    MessageGroup::DeviceActionEvent(
+                        DeviceActionEventCode::Ring
+                    ),
+                    MessageGroup::Bluetooth(BluetoothCode::EnableSilenceMode),
+                    MessageGroup::DeviceInformationEvent(
+                        DeviceInformationEventCode::BleAddressUpdated
+                    ),
+                ]
+            );
+            assert_eq!(
+                client.ble_address(),
+                BleAddress::new(0xC066778899AA, BleAddressKind::Random)
+            );
+            assert_eq!(
+                written.next().await,
+                Some(vec![0xFF, 0x01, 0x00, 0x02, 0x04, 0x01])
+            );
+            assert_eq!(
+                written.next().await,
+                Some(vec![0xFF, 0x02, 0x00, 0x03, 0x00, 0x01, 0x01])
+            );
+        });
     }
 }
@@ -0,0 +1,566 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::VecDeque;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures::{channel::mpsc, stream};
+
+use super::fixtures::{with_fixtures, MockAdvertisement};
+use crate::{
+    api,
+    common::{
+        ad_structures, parse_service_data_16bit_uuid, scan_queue,
+        AdapterStateStream, AdvertisementStream, BleAdvertisement,
+        BleDataTypeId, BluetoothError, DiscoveredClassicDevice, RetryPolicy,
+        ScanFilter, ScanQueue, ScanQueueSender, ScanSettings, Uuid,
+    },
+};
+
+/// AD type of manufacturer specific data, which starts with the company
+/// identifier in little-endian order.
+/// See: Bluetooth Supplement to the Core Specification, Part A, Section 1.4.
+const MANUFACTURER_SPECIFIC_DATA: u8 = 0xFF;
+
+/// Type implementing `api::BleAdapter` for the mock platform. Scanning
+/// delivers the advertisements scripted in the installed `MockFixtures`,
+/// each to a single scan.
+pub struct BleAdapter {
+    /// Scan started by `start_scan()`, if any.
+    scan: Option<MockScan>,
+}
+
+/// Scan delivering the scripted advertisements matching its filter and
+/// settings. They go through a scan queue as on other platforms: received
+/// into `sender` with their raw payload, as a platform's scan callback would,
+/// and loaded with the selected data types once taken from `receiver`.
+struct MockScan {
+    filter: ScanFilter,
+    settings: ScanSettings,
+    sender: ScanQueueSender<BleAdvertisement>,
+    receiver: ScanQueue<BleAdvertisement>,
+    /// Sequence number of the next advertisement delivered by the scan.
+    next_sequence_number: u64,
+}
+
+#[async_trait]
+impl api::BleAdapter for BleAdapter {
+    /// The fixtures never fail transiently, so `retry_policy` is ignored.
+    async fn default_with_retry_policy(
+        _retry_policy: RetryPolicy,
+    ) -> Result<Self, BluetoothError> {
+        with_fixtures(|_| Ok(BleAdapter { scan: None }))
+    }
+
+    /// Advertisements are delivered as scripted whatever the settings, except
+    /// that those weaker than the in-range threshold are skipped.
+    fn start_scan_with_settings(
+        &mut self,
+        filter: Option<ScanFilter>,
+        settings: ScanSettings,
+    ) -> Result<(), BluetoothError> {
+        self.scan = Some(MockScan::new(filter, settings));
+        Ok(())
+    }
+
+    fn stop_scan(&mut self) -> Result<(), BluetoothError> {
+        match self.scan.take() {
+            Some(_) => Ok(()),
+            None => Err(BluetoothError::FailedPrecondition(String::from(
+                "Scanning has not been started.",
+            ))),
+        }
+    }
+
+    /// The stream ends once every scripted advertisement has been delivered.
+    fn scan(
+        &self,
+        filter: Option<ScanFilter>,
+        settings: ScanSettings,
+        datatype_selector: Vec<BleDataTypeId>,
+    ) -> Result<AdvertisementStream, BluetoothError> {
+        let scan = MockScan::new(filter, settings);
+        Ok(AdvertisementStream::new(stream::unfold(
+            (scan, datatype_selector),
+            |(mut scan, datatype_selector)| async move {
+                match scan.pop_advertisement(&datatype_selector) {
+                    Ok(Some(advertisement)) => {
+                        Some((Ok(advertisement), (scan, datatype_selector)))
+                    }
+                    Ok(None) => None,
+                    Err(err) => Some((Err(err), (scan, datatype_selector))),
+                }
+            },
+        )))
+    }
+
+    /// Fails once every scripted advertisement has been delivered, rather
+    /// than waiting forever.
+    async fn next_advertisement(
+        &mut self,
+        datatype_selector: Option<&Vec<BleDataTypeId>>,
+    ) -> Result<BleAdvertisement, BluetoothError> {
+        self.pop_advertisement(datatype_selector)?.ok_or_else(|| {
+            BluetoothError::Internal(String::from(
+                "No scripted advertisement left.",
+            ))
+        })
+    }
+
+    /// Every scripted advertisement left is delivered in the same batch.
+    async fn next_advertisement_batch(
+        &mut self,
+        datatype_selector: Option<&Vec<BleDataTypeId>>,
+    ) -> Result<Vec<BleAdvertisement>, BluetoothError> {
+        let first = self.next_advertisement(datatype_selector).await?;
+        let mut batch = vec![first];
+        while let Some(advertisement) =
+            self.pop_advertisement(datatype_selector)?
+        {
+            batch.push(advertisement);
+        }
+
+        Ok(batch)
+    }
+
+    /// Scripted advertisements are taken from the fixtures when polled, so
+    /// none are ever dropped.
+    fn dropped_advertisements(&self) -> u64 {
+        0
+    }
+
+    async fn watch_state(
+        &mut self,
+    ) -> Result<AdapterStateStream, BluetoothError> {
+        let (mut sender, receiver) = mpsc::channel(16);
+        with_fixtures(|fixtures| {
+            sender.try_send(fixtures.adapter_state()).map_err(|err| {
+                BluetoothError::Internal(format!(
+                    "Failed to send adapter state: {}",
+                    err
+                ))
+            })?;
+            fixtures.set_state_sender(sender);
+            Ok(())
+        })?;
+
+        Ok(AdapterStateStream::new(receiver))
+    }
+}
+
+impl BleAdapter {
+    /// Take the next scripted advertisement delivered by the scan started
+    /// with `start_scan()`, if any, loaded with the selected data types.
+    fn pop_advertisement(
+        &mut self,
+        datatype_selector: Option<&Vec<BleDataTypeId>>,
+    ) -> Result<Option<BleAdvertisement>, BluetoothError> {
+        let scan = self.scan.as_mut().ok_or_else(|| {
+            BluetoothError::FailedPrecondition(String::from(
+                "Scanning has not been started.",
+            ))
+        })?;
+
+        scan.pop_advertisement(
+            datatype_selector.map_or(&[], |selector| selector),
+        )
+    }
+}
+
+impl MockScan {
+    fn new(filter: Option<ScanFilter>, settings: ScanSettings) -> Self {
+        let (sender, receiver) = scan_queue(&settings);
+        MockScan {
+            filter: filter.unwrap_or_default(),
+            settings,
+            sender,
+            receiver,
+            next_sequence_number: 0,
+        }
+    }
+
+    /// Take the next scripted advertisement matching the filter and
+    /// settings, if any, loaded with the selected data types.
+    fn pop_advertisement(
+        &mut self,
+        datatype_selector: &[BleDataTypeId],
+    ) -> Result<Option<BleAdvertisement>, BluetoothError> {
+        self.receive_advertisements()?;
+        while let Some(mut advertisement) = self.receiver.try_recv() {
+            if self.settings.is_in_range(advertisement.rssi()) {
+                advertisement.load_raw_data(datatype_selector)?;
+                advertisement.set_sequence_number(self.next_sequence_number);
+                self.next_sequence_number += 1;
+
+                return Ok(Some(advertisement));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Queue the scripted advertisements matching the filter. The payload is
+    /// copied, as platforms copy it out of their own buffers, and nothing is
+    /// dropped, whatever the capacity of the queue.
+    fn receive_advertisements(&mut self) -> Result<(), BluetoothError> {
+        while let Some(mock) =
+            with_fixtures(|fixtures| Ok(fixtures.pop_advertisement()))?
+        {
+            if matches_filter(&self.filter, &mock)? {
+                let mut advertisement =
+                    BleAdvertisement::new(mock.address(), mock.rssi(), None);
+                advertisement
+                    .set_raw_data(Bytes::copy_from_slice(mock.raw_data()));
+                self.sender.push_unbounded(advertisement);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Type implementing `api::ClassicAdapter` for the mock platform. Discovery
+/// finds the unpaired BT Classic devices installed in the `MockFixtures`.
+pub struct ClassicAdapter {
+    /// Devices left to deliver, if discovery is running.
+    discovered: Option<VecDeque<DiscoveredClassicDevice>>,
+}
+
+#[async_trait]
+impl api::ClassicAdapter for ClassicAdapter {
+    async fn default() -> Result<Self, BluetoothError> {
+        with_fixtures(|_| Ok(ClassicAdapter { discovered: None }))
+    }
+
+    fn start_discovery(&mut self) -> Result<(), BluetoothError> {
+        let devices = with_fixtures(|fixtures| {
+            Ok(fixtures.discoverable_classic_devices())
+        })?;
+        self.discovered = Some(VecDeque::from(devices));
+        Ok(())
+    }
+
+    fn stop_discovery(&mut self) -> Result<(), BluetoothError> {
+        match self.discovered.take() {
+            Some(_) => Ok(()),
+            None => Err(BluetoothError::FailedPrecondition(String::from(
+                "Discovery has not been started.",
+            ))),
+        }
+    }
+
+    /// Fails once every discoverable device has been delivered, rather than
+    /// waiting forever.
+    async fn next_discovered_device(
+        &mut self,
+    ) -> Result<DiscoveredClassicDevice, BluetoothError> {
+        let discovered = self.discovered.as_mut().ok_or_else(|| {
+            BluetoothError::FailedPrecondition(String::from(
+                "Discovery has not been started.",
+            ))
+        })?;
+
+        discovered.pop_front().ok_or_else(|| {
+            BluetoothError::Internal(String::from(
+                "No discoverable device left.",
+            ))
+        })
+    }
+}
+
+/// Check the criteria of `filter` against a scripted advertisement, as the
+/// controller would do on other platforms.
+fn matches_filter(
+    filter: &ScanFilter,
+    advertisement: &MockAdvertisement,
+) -> Result<bool, BluetoothError> {
+    if !filter.matches_address(advertisement.address()) {
+        return Ok(false);
+    }
+
+    if let Some(uuid) = filter.service_data_uuid() {
+        let mut matched = false;
+        for section in parse_service_data_16bit_uuid(advertisement.raw_data()) {
+            let (data_uuid, _) = section?;
+            matched |= data_uuid == Uuid::from_u16(uuid);
+        }
+        if !matched {
+            return Ok(false);
+        }
+    }
+
+    if let Some(company_id) = filter.manufacturer_id() {
+        let mut matched = false;
+        for structure in ad_structures(advertisement.raw_data()) {
+            let (datatype, data) = structure?;
+            if datatype == MANUFACTURER_SPECIFIC_DATA
+                && data.len() >= 2
+                && u16::from_le_bytes([data[0], data[1]]) == company_id
+            {
+                matched = true;
+            }
+        }
+        if !matched {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::{
+        api::{BleAdapter as _, ClassicAdapter as _},
+        common::{
+            AdapterState, BleAddress, BleAddressKind, ClassOfDevice,
+            ClassicAddress,
+        },
+        mock::{MockDevice, MockFixtures},
+    };
+
+    const FAST_PAIR_ADVERTISEMENT: [u8; 9] =
+        [0x02, 0x01, 0x06, 0x05, 0x16, 0x2C, 0xFE, 0x01, 0x02];
+    const MANUFACTURER_ADVERTISEMENT: [u8; 5] = [0x04, 0xFF, 0xE0, 0x00, 0x42];
+
+    #[test]
+    fn scan_filters_scripted_advertisements() {
+        let fast_pair = BleAddress::new(0x112233445566, BleAddressKind::Public);
+        let other = BleAddress::new(0x665544332211, BleAddressKind::Public);
+        MockFixtures::new()
+            .with_advertisement(MockAdvertisement::new(
+                other,
+                Some(-70),
+                MANUFACTURER_ADVERTISEMENT.to_vec(),
+            ))
+            .with_advertisement(MockAdvertisement::new(
+                fast_pair,
+                Some(-50),
+                FAST_PAIR_ADVERTISEMENT.to_vec(),
+            ))
+            .install();
+
+        futures::executor::block_on(async {
+            let mut adapter = BleAdapter::default().await.unwrap();
+            assert!(adapter.next_advertisement(None).await.is_err());
+
+            adapter
+                .start_scan(Some(
+                    ScanFilter::new().with_service_data_uuid(0xFE2C),
+                ))
+                .unwrap();
+            let selector = vec![BleDataTypeId::ServiceData16BitUuid];
+            let advertisement =
+                adapter.next_advertisement(Some(&selector)).await.unwrap();
+            assert_eq!(advertisement.address(), fast_pair);
+            assert_eq!(advertisement.rssi(), Some(-50));
+            let service_data = advertisement.service_data_16bit_uuid().unwrap();
+            assert_eq!(service_data.len(), 1);
+            assert_eq!(service_data[0].uuid(), Uuid::from_u16(0xFE2C));
+            assert_eq!(service_data[0].data(), [0x01, 0x02]);
+
+            assert!(matches!(
+                adapter.next_advertisement(None).await,
+                Err(BluetoothError::Internal(_))
+            ));
+            adapter.stop_scan().unwrap();
+        });
+    }
+
+    #[test]
+    fn scan_filters_manufacturer_data() {
+        let addr = BleAddress::new(0x112233445566, BleAddressKind::Random);
+        MockFixtures::new()
+            .with_advertisement(MockAdvertisement::new(
+                addr,
+                None,
+                FAST_PAIR_ADVERTISEMENT.to_vec(),
+            ))
+            .with_advertisement(MockAdvertisement::new(
+                addr,
+                None,
+                MANUFACTURER_ADVERTISEMENT.to_vec(),
+            ))
+            .install();
+
+        futures::executor::block_on(async {
+            let mut adapter = BleAdapter::default().await.unwrap();
+            adapter
+                .start_scan(Some(
+                    ScanFilter::new().with_manufacturer_id(0x00E0),
+                ))
+                .unwrap();
+            let advertisement = adapter.next_advertisement(None).await.unwrap();
+            assert_eq!(advertisement.address(), addr);
+            assert!(adapter.next_advertisement(None).await.is_err());
+        });
+    }
+
+    #[test]
+    fn scan_skips_weak_advertisements() {
+        let near = BleAddress::new(0x112233445566, BleAddressKind::Public);
+        let far = BleAddress::new(0x665544332211, BleAddressKind::Public);
+        MockFixtures::new()
+            .with_advertisement(MockAdvertisement::new(
+                far,
+                Some(-90),
+                FAST_PAIR_ADVERTISEMENT.to_vec(),
+            ))
+            .with_advertisement(MockAdvertisement::new(
+                near,
+                Some(-50),
+                FAST_PAIR_ADVERTISEMENT.to_vec(),
+            ))
+            .install();
+
+        futures::executor::block_on(async {
+            let mut adapter = BleAdapter::default().await.unwrap();
+            let settings =
+                ScanSettings::new().with_signal_strength_thresholds(-70, -80);
+            adapter.start_scan_with_settings(None, settings).unwrap();
+            let advertisement = adapter.next_advertisement(None).await.unwrap();
+            assert_eq!(advertisement.address(), near);
+            assert!(adapter.next_advertisement(None).await.is_err());
+        });
+    }
+
+    #[test]
+    fn scan_batches_scripted_advertisements() {
+        let addr = BleAddress::new(0x112233445566, BleAddressKind::Public);
+        let advertisement = MockAdvertisement::new(
+            addr,
+            None,
+            FAST_PAIR_ADVERTISEMENT.to_vec(),
+        );
+        MockFixtures::new()
+            .with_advertisement(advertisement.clone())
+            .with_advertisement(advertisement)
+            .install();
+
+        futures::executor::block_on(async {
+            let mut adapter = BleAdapter::default().await.unwrap();
+            adapter
+                .start_scan_with_settings(None, ScanSettings::background())
+                .unwrap();
+            let batch = adapter.next_advertisement_batch(None).await.unwrap();
+            assert_eq!(batch.len(), 2);
+            assert_eq!(batch[0].sequence_number(), 0);
+            assert_eq!(batch[1].sequence_number(), 1);
+            assert_eq!(batch[1].raw_data(), FAST_PAIR_ADVERTISEMENT);
+            assert!(adapter.next_advertisement_batch(None).await.is_err());
+        });
+    }
+
+    #[test]
+    fn scan_stream_delivers_scripted_advertisements() {
+        let fast_pair = BleAddress::new(0x112233445566, BleAddressKind::Public);
+        let other = BleAddress::new(0x665544332211, BleAddressKind::Public);
+        MockFixtures::new()
+            .with_advertisement(MockAdvertisement::new(
+                fast_pair,
+                None,
+                FAST_PAIR_ADVERTISEMENT.to_vec(),
+            ))
+            .with_advertisement(MockAdvertisement::new(
+                other,
+                None,
+                MANUFACTURER_ADVERTISEMENT.to_vec(),
+            ))
+            .with_advertisement(MockAdvertisement::new(
+                fast_pair,
+                None,
+                FAST_PAIR_ADVERTISEMENT.to_vec(),
+            ))
+            .install();
+
+        futures::executor::block_on(async {
+            let mut adapter = BleAdapter::default().await.unwrap();
+            let filter = ScanFilter::new().with_service_data_uuid(0xFE2C);
+            let advertisements = adapter
+                .scan(
+                    Some(filter),
+                    ScanSettings::new(),
+                    vec![BleDataTypeId::ServiceData16BitUuid],
+                )
+                .unwrap();
+
+            // The stream doesn't borrow the adapter, which remains usable.
+            let mut states = adapter.watch_state().await.unwrap();
+            assert_eq!(states.next().await, Some(AdapterState::PoweredOn));
+
+            let advertisements: Vec<_> = advertisements
+                .map(|advertisement| advertisement.unwrap())
+                .collect()
+                .await;
+            assert_eq!(advertisements.len(), 2);
+            for (i, advertisement) in advertisements.iter().enumerate() {
+                assert_eq!(advertisement.address(), fast_pair);
+                assert_eq!(advertisement.sequence_number(), i as u64);
+                assert!(advertisement.service_data_16bit_uuid().is_ok());
+            }
+        });
+    }
+
+    #[test]
+    fn watch_scripted_adapter_state() {
+        MockFixtures::new()
+            .with_adapter_state(AdapterState::PoweredOff)
+            .install();
+
+        futures::executor::block_on(async {
+            let mut adapter = BleAdapter::default().await.unwrap();
+            let mut states = adapter.watch_state().await.unwrap();
+            MockFixtures::set_adapter_state(AdapterState::PoweredOn).unwrap();
+
+            assert_eq!(states.next().await, Some(AdapterState::PoweredOff));
+            assert_eq!(states.next().await, Some(AdapterState::PoweredOn));
+        });
+    }
+
+    #[test]
+    fn discover_scripted_classic_devices() {
+        let headphones = ClassicAddress::from(0x112233445566);
+        let paired = ClassicAddress::from(0x665544332211);
+        let class = ClassOfDevice::from(0x240404);
+        MockFixtures::new()
+            .with_classic_device(
+                headphones,
+                MockDevice::new("Headphones").with_class_of_device(class),
+            )
+            .with_classic_device(
+                paired,
+                MockDevice::new("Speaker").with_paired(true),
+            )
+            .install();
+
+        futures::executor::block_on(async {
+            let mut adapter = ClassicAdapter::default().await.unwrap();
+            assert!(adapter.next_discovered_device().await.is_err());
+
+            adapter.start_discovery().unwrap();
+            let device = adapter.next_discovered_device().await.unwrap();
+            assert_eq!(device.address(), headphones);
+            assert_eq!(device.name(), Some("Headphones"));
+            assert_eq!(device.class_of_device(), class);
+            assert!(adapter.next_discovered_device().await.is_err());
+
+            adapter.stop_discovery().unwrap();
+            assert!(adapter.stop_discovery().is_err());
+        });
+    }
+}
@@ -0,0 +1,198 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+
+use super::fixtures::with_fixtures;
+use crate::{
+    api,
+    common::{
+        AdStructure, AdvertisementPayload, AdvertisingParameters,
+        BleDataTypeId, BluetoothError,
+    },
+};
+
+/// AD type of manufacturer specific data.
+/// See: Bluetooth Supplement to the Core Specification, Part A, Section 1.4.
+const MANUFACTURER_SPECIFIC_DATA: u8 = 0xFF;
+
+/// TX power advertised when none is requested, in dBm.
+const DEFAULT_TX_POWER: i16 = 0;
+
+/// Type implementing `api::BleAdvertiser` for the mock platform. Starting to
+/// advertise appends a single advertisement to the script delivered to
+/// scanners, sent from the address of the local adapter.
+pub struct BleAdvertiser {
+    advertising: bool,
+}
+
+#[async_trait]
+impl api::BleAdvertiser for BleAdvertiser {
+    async fn default() -> Result<Self, BluetoothError> {
+        with_fixtures(|_| Ok(BleAdvertiser { advertising: false }))
+    }
+
+    fn start_advertising_with_parameters(
+        &mut self,
+        payload: AdvertisementPayload,
+        parameters: AdvertisingParameters,
+    ) -> Result<(), BluetoothError> {
+        parameters.validate()?;
+        let raw_data = raw_data(&payload, &parameters)?;
+        with_fixtures(|fixtures| {
+            fixtures.push_local_advertisement(raw_data);
+            Ok(())
+        })?;
+
+        self.advertising = true;
+        Ok(())
+    }
+
+    /// Advertisements already appended to the script are still delivered.
+    fn stop_advertising(&mut self) -> Result<(), BluetoothError> {
+        if !self.advertising {
+            return Err(BluetoothError::FailedPrecondition(String::from(
+                "Advertising has not been started.",
+            )));
+        }
+
+        self.advertising = false;
+        Ok(())
+    }
+}
+
+/// Build the raw payload broadcast for `payload`, with the TX power level
+/// if `parameters` asks to advertise it.
+fn raw_data(
+    payload: &AdvertisementPayload,
+    parameters: &AdvertisingParameters,
+) -> Result<Vec<u8>, BluetoothError> {
+    let mut structures = Vec::new();
+    if parameters.tx_power_advertised() {
+        // The TX power was validated to fit in a signed byte.
+        let tx_power = parameters.tx_power().unwrap_or(DEFAULT_TX_POWER) as i8;
+        structures.push(AdStructure::new(
+            BleDataTypeId::TxPowerLevel as u8,
+            tx_power.to_le_bytes().to_vec(),
+        ));
+    }
+    for service_data in payload.service_data_16bit_uuid() {
+        structures.push(AdStructure::new(
+            BleDataTypeId::ServiceData16BitUuid as u8,
+            service_data.to_16bit_uuid_bytes()?,
+        ));
+    }
+    for manufacturer_data in payload.manufacturer_data() {
+        let mut data = manufacturer_data.company_id().to_le_bytes().to_vec();
+        data.extend_from_slice(manufacturer_data.data());
+        structures.push(AdStructure::new(MANUFACTURER_SPECIFIC_DATA, data));
+    }
+
+    let mut raw_data = Vec::new();
+    for structure in structures {
+        raw_data.extend(structure.to_bytes()?);
+    }
+
+    Ok(raw_data)
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+    use crate::{
+        api::{BleAdapter as _, BleAdvertiser as _},
+        common::{BleAddress, BleAddressKind, ServiceData, Uuid},
+        mock::{BleAdapter, MockFixtures},
+    };
+
+    #[test]
+    fn scan_local_advertisements() {
+        let local = BleAddress::new(0x112233445566, BleAddressKind::Public);
+        MockFixtures::new()
+            .with_local_adapter(local, Some(-60))
+            .install();
+
+        block_on(async {
+            let mut advertiser = BleAdvertiser::default().await.unwrap();
+            assert!(matches!(
+                advertiser.stop_advertising(),
+                Err(BluetoothError::FailedPrecondition(_))
+            ));
+            advertiser
+                .start_advertising_with_parameters(
+                    AdvertisementPayload::new().with_service_data_16bit_uuid(
+                        ServiceData::new(Uuid::from_u16(0xFE2C), vec![0x01]),
+                    ),
+                    AdvertisingParameters::new()
+                        .with_tx_power(-10)
+                        .with_tx_power_advertised(),
+                )
+                .unwrap();
+            advertiser.stop_advertising().unwrap();
+
+            let mut adapter = BleAdapter::default().await.unwrap();
+            adapter.start_scan(None).unwrap();
+            let selector = vec![
+                BleDataTypeId::TxPowerLevel,
+                BleDataTypeId::ServiceData16BitUuid,
+            ];
+            let advertisement =
+                adapter.next_advertisement(Some(&selector)).await.unwrap();
+            assert_eq!(advertisement.address(), local);
+            assert_eq!(advertisement.rssi(), Some(-60));
+            assert_eq!(advertisement.tx_power(), Some(-10));
+            assert_eq!(
+                advertisement.raw_data(),
+                [0x02, 0x0A, 0xF6, 0x04, 0x16, 0x2C, 0xFE, 0x01]
+            );
+            assert_eq!(
+                advertisement.service_data_16bit_uuid().unwrap()[0].data(),
+                [0x01]
+            );
+
+            // A single advertisement is delivered per call.
+            assert!(adapter.next_advertisement(Some(&selector)).await.is_err());
+        });
+    }
+
+    #[test]
+    fn advertise_invalid_parameters() {
+        MockFixtures::new().install();
+
+        block_on(async {
+            let mut advertiser = BleAdvertiser::default().await.unwrap();
+            assert!(advertiser
+                .start_advertising_with_parameters(
+                    AdvertisementPayload::new().with_service_data_16bit_uuid(
+                        ServiceData::new(
+                            Uuid::from_u128(
+                                0xFE2C1234_8366_4814_8EB0_01DE32100BEA
+                            ),
+                            vec![0x01],
+                        ),
+                    ),
+                    AdvertisingParameters::new(),
+                )
+                .is_err());
+            assert!(advertiser
+                .start_advertising_with_parameters(
+                    AdvertisementPayload::new(),
+                    AdvertisingParameters::new().with_tx_power(21),
+                )
+                .is_err());
+        });
+    }
+}
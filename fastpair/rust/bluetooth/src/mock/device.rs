@@ -0,0 +1,584 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::thread;
+
+use async_trait::async_trait;
+use futures::{
+    channel::{
+        mpsc::{self, Sender},
+        oneshot,
+    },
+    sink, stream,
+};
+
+use super::fixtures::{with_fixtures, MockDevice};
+use crate::{
+    api::{self, PairingDelegate},
+    common::{
+        BleAddress, BluetoothError, ClassicAddress, ConnectionStatus,
+        ConnectionStatusStream, DeviceAddress, PairedDevice, PairingResult,
+        ProtectionLevel, RetryPolicy, RfcommSocket, SdpRecord, Uuid,
+    },
+};
+
+/// Type implementing `api::BleDevice` for the mock platform, backed by the
+/// `MockDevice` installed at its address.
+pub struct BleDevice {
+    addr: BleAddress,
+    status_sender: Option<Sender<ConnectionStatus>>,
+}
+
+#[async_trait]
+impl api::BleDevice for BleDevice {
+    /// The fixtures never fail transiently, so `retry_policy` is ignored.
+    async fn new_with_retry_policy(
+        addr: BleAddress,
+        _retry_policy: RetryPolicy,
+    ) -> Result<Self, BluetoothError> {
+        with_fixtures(|fixtures| fixtures.ble_device_mut(addr).map(|_| ()))?;
+
+        Ok(BleDevice {
+            addr,
+            status_sender: None,
+        })
+    }
+
+    fn name(&self) -> Result<String, BluetoothError> {
+        with_ble_device(self.addr, |device| Ok(String::from(device.name())))
+    }
+
+    fn address(&self) -> BleAddress {
+        self.addr
+    }
+
+    async fn rssi(&self) -> Result<i16, BluetoothError> {
+        with_ble_device(self.addr, |device| rssi(device))
+    }
+
+    /// Only yields the status of the device when called, as the fixtures
+    /// don't script connection changes.
+    async fn watch_connection_status(
+        &mut self,
+    ) -> Result<ConnectionStatusStream, BluetoothError> {
+        let status = with_ble_device(self.addr, |device| {
+            Ok(if device.is_connected() {
+                ConnectionStatus::Connected
+            } else {
+                ConnectionStatus::Disconnected
+            })
+        })?;
+
+        let (mut sender, receiver) = mpsc::channel(1);
+        sender.try_send(status).map_err(|err| {
+            BluetoothError::Internal(format!(
+                "Failed to send connection status: {}",
+                err
+            ))
+        })?;
+        // Keep the sender so that the stream only ends when this method is
+        // called again or the device is dropped.
+        self.status_sender = Some(sender);
+
+        Ok(ConnectionStatusStream::new(receiver))
+    }
+
+    async fn pair_with_protection_level(
+        &self,
+        protection_level: ProtectionLevel,
+    ) -> Result<PairingResult, BluetoothError> {
+        pair(DeviceAddress::Ble(self.addr), None, protection_level).await
+    }
+
+    async fn pair_with_delegate_and_protection_level<D: PairingDelegate>(
+        &self,
+        delegate: D,
+        protection_level: ProtectionLevel,
+    ) -> Result<PairingResult, BluetoothError> {
+        pair(
+            DeviceAddress::Ble(self.addr),
+            Some(Box::new(delegate)),
+            protection_level,
+        )
+        .await
+    }
+
+    /// The fixtures don't track other users of the link, so closing the
+    /// device disconnects it.
+    fn close(self) -> Result<(), BluetoothError> {
+        with_ble_device(self.addr, |device| {
+            device.set_disconnected();
+            Ok(())
+        })
+    }
+}
+
+/// Type implementing `api::ClassicDevice` for the mock platform, backed by
+/// the `MockDevice` installed at its address.
+pub struct ClassicDevice {
+    addr: ClassicAddress,
+}
+
+#[async_trait]
+impl api::ClassicDevice for ClassicDevice {
+    /// The fixtures never fail transiently, so `retry_policy` is ignored.
+    async fn new_with_retry_policy(
+        addr: ClassicAddress,
+        _retry_policy: RetryPolicy,
+    ) -> Result<Self, BluetoothError> {
+        with_fixtures(|fixtures| {
+            fixtures.classic_device_mut(addr).map(|_| ())
+        })?;
+
+        Ok(ClassicDevice { addr })
+    }
+
+    fn name(&self) -> Result<String, BluetoothError> {
+        with_classic_device(self.addr, |device| Ok(String::from(device.name())))
+    }
+
+    fn address(&self) -> ClassicAddress {
+        self.addr
+    }
+
+    async fn rssi(&self) -> Result<i16, BluetoothError> {
+        with_classic_device(self.addr, |device| rssi(device))
+    }
+
+    async fn discover_services(
+        &self,
+    ) -> Result<Vec<SdpRecord>, BluetoothError> {
+        with_classic_device(self.addr, |device| {
+            Ok(device.sdp_records().to_vec())
+        })
+    }
+
+    /// The device closes the connection once it sent its scripted data.
+    async fn connect_rfcomm(
+        &self,
+        service_uuid: Uuid,
+    ) -> Result<RfcommSocket, BluetoothError> {
+        let sent = with_classic_device(self.addr, |device| {
+            device
+                .rfcomm_sent(service_uuid)
+                .map(<[_]>::to_vec)
+                .ok_or_else(|| {
+                    BluetoothError::Unreachable(format!(
+                        "no mock RFCOMM service {}",
+                        service_uuid
+                    ))
+                })
+        })?;
+
+        let addr = self.addr;
+        let outgoing = sink::unfold((), move |(), data: Vec<u8>| async move {
+            with_classic_device(addr, |device| {
+                device.record_rfcomm_write(service_uuid, data);
+                Ok(())
+            })
+        });
+
+        Ok(RfcommSocket::new(
+            stream::iter(sent.into_iter().map(Ok)),
+            outgoing,
+        ))
+    }
+
+    async fn pair_with_protection_level(
+        &self,
+        protection_level: ProtectionLevel,
+    ) -> Result<PairingResult, BluetoothError> {
+        pair(DeviceAddress::Classic(self.addr), None, protection_level).await
+    }
+
+    async fn pair_with_delegate_and_protection_level<D: PairingDelegate>(
+        &self,
+        delegate: D,
+        protection_level: ProtectionLevel,
+    ) -> Result<PairingResult, BluetoothError> {
+        pair(
+            DeviceAddress::Classic(self.addr),
+            Some(Box::new(delegate)),
+            protection_level,
+        )
+        .await
+    }
+
+    /// The fixtures don't track other users of the link, so closing the
+    /// device disconnects it.
+    fn close(self) -> Result<(), BluetoothError> {
+        with_classic_device(self.addr, |device| {
+            device.set_disconnected();
+            Ok(())
+        })
+    }
+}
+
+/// List the devices installed in the `MockFixtures` which are paired.
+pub async fn list_paired_devices() -> Result<Vec<PairedDevice>, BluetoothError>
+{
+    with_fixtures(|fixtures| Ok(fixtures.paired_devices()))
+}
+
+fn with_ble_device<T>(
+    addr: BleAddress,
+    f: impl FnOnce(&mut MockDevice) -> Result<T, BluetoothError>,
+) -> Result<T, BluetoothError> {
+    with_fixtures(|fixtures| f(fixtures.ble_device_mut(addr)?))
+}
+
+fn with_classic_device<T>(
+    addr: ClassicAddress,
+    f: impl FnOnce(&mut MockDevice) -> Result<T, BluetoothError>,
+) -> Result<T, BluetoothError> {
+    with_fixtures(|fixtures| f(fixtures.classic_device_mut(addr)?))
+}
+
+fn with_device<T>(
+    addr: DeviceAddress,
+    f: impl FnOnce(&mut MockDevice) -> Result<T, BluetoothError>,
+) -> Result<T, BluetoothError> {
+    match addr {
+        DeviceAddress::Ble(addr) => with_ble_device(addr, f),
+        DeviceAddress::Classic(addr) => with_classic_device(addr, f),
+    }
+}
+
+fn rssi(device: &MockDevice) -> Result<i16, BluetoothError> {
+    device.rssi().ok_or_else(|| {
+        BluetoothError::Unreachable(String::from("no signal strength scripted"))
+    })
+}
+
+/// Pair with the device at `addr`, mirroring the other platforms:
+/// ceremonies that need user interaction are answered by `delegate`, called
+/// from a thread of its own, or fail without one. Numeric comparison
+/// authenticates the link, while pairing without a passkey only encrypts it.
+async fn pair(
+    addr: DeviceAddress,
+    delegate: Option<Box<dyn PairingDelegate>>,
+    protection_level: ProtectionLevel,
+) -> Result<PairingResult, BluetoothError> {
+    let device = with_device(addr, |device| Ok(device.clone()))?;
+    if device.is_paired() {
+        return Ok(PairingResult::AlreadyPaired);
+    }
+    if !device.can_pair() {
+        return Err(BluetoothError::PairingFailed(String::from(
+            "device can't pair",
+        )));
+    }
+
+    let granted = match device.passkey() {
+        Some(_) => ProtectionLevel::EncryptionAndAuthentication,
+        None => ProtectionLevel::Encryption,
+    };
+    if granted < protection_level {
+        return Err(BluetoothError::PairingFailed(String::from(
+            "protection level could not be met",
+        )));
+    }
+
+    if let Some(passkey) = device.passkey() {
+        let confirmed = match delegate {
+            // The delegate may block, e.g. to exchange the passkey with the
+            // device, so the executor polling this future must stay free.
+            Some(delegate) => {
+                let (sender, receiver) = oneshot::channel();
+                thread::spawn(move || {
+                    let _ = sender.send(delegate.confirm_passkey(passkey));
+                });
+                receiver.await.unwrap_or(false)
+            }
+            None => false,
+        };
+        if !confirmed {
+            return Err(BluetoothError::PairingFailed(String::from(
+                "passkey rejected",
+            )));
+        }
+    }
+
+    with_device(addr, |device| {
+        device.set_paired();
+        Ok(())
+    })?;
+    Ok(PairingResult::Success(granted))
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::StreamExt;
+
+    use super::*;
+    use crate::{
+        api::{BleDevice as _, ClassicDevice as _},
+        common::{BleAddressKind, DeviceAddress, Uuid},
+        mock::MockFixtures,
+    };
+
+    struct PasskeyDelegate(u32);
+
+    impl PairingDelegate for PasskeyDelegate {
+        fn confirm_passkey(&self, passkey: u32) -> bool {
+            passkey == self.0
+        }
+
+        fn provide_pin(&self) -> Option<String> {
+            None
+        }
+
+        fn display_pin(&self, _pin: &str) {}
+    }
+
+    #[test]
+    fn ble_device_from_fixtures() {
+        let addr = BleAddress::new(0x112233445566, BleAddressKind::Public);
+        let unknown = BleAddress::new(0x665544332211, BleAddressKind::Public);
+        MockFixtures::new()
+            .with_ble_device(
+                addr,
+                MockDevice::new("Headphones")
+                    .with_rssi(-60)
+                    .with_connected(true),
+            )
+            .install();
+
+        futures::executor::block_on(async {
+            assert!(matches!(
+                BleDevice::new(unknown).await,
+                Err(BluetoothError::Unreachable(_))
+            ));
+
+            let mut device = BleDevice::new(addr).await.unwrap();
+            assert_eq!(device.name().unwrap(), "Headphones");
+            assert_eq!(device.rssi().await.unwrap(), -60);
+
+            let mut statuses = device.watch_connection_status().await.unwrap();
+            assert_eq!(
+                statuses.next().await,
+                Some(ConnectionStatus::Connected)
+            );
+        });
+    }
+
+    #[test]
+    fn close_devices() {
+        let ble = BleAddress::new(0x112233445566, BleAddressKind::Public);
+        let classic = ClassicAddress::from(0x665544332211);
+        MockFixtures::new()
+            .with_ble_device(
+                ble,
+                MockDevice::new("Earbuds").with_connected(true),
+            )
+            .with_classic_device(
+                classic,
+                MockDevice::new("Headphones").with_connected(true),
+            )
+            .install();
+
+        futures::executor::block_on(async {
+            let mut device = BleDevice::new(ble).await.unwrap();
+            let mut statuses = device.watch_connection_status().await.unwrap();
+            assert_eq!(
+                statuses.next().await,
+                Some(ConnectionStatus::Connected)
+            );
+            device.close().unwrap();
+            assert_eq!(statuses.next().await, None);
+            assert!(!MockFixtures::ble_device(ble).unwrap().is_connected());
+
+            let device = ClassicDevice::new(classic).await.unwrap();
+            device.close().unwrap();
+            assert!(!MockFixtures::classic_device(classic)
+                .unwrap()
+                .is_connected());
+        });
+    }
+
+    #[test]
+    fn classic_device_services() {
+        let addr = ClassicAddress::from(0x112233445566);
+        let record = SdpRecord::new(Uuid::from_u16(0x1101), Some(3));
+        MockFixtures::new()
+            .with_classic_device(
+                addr,
+                MockDevice::new("Headphones").with_sdp_record(record),
+            )
+            .install();
+
+        futures::executor::block_on(async {
+            let device = ClassicDevice::new(addr).await.unwrap();
+            assert_eq!(device.discover_services().await.unwrap(), vec![record]);
+            assert!(device.rssi().await.is_err());
+        });
+    }
+
+    #[test]
+    fn classic_device_rfcomm() {
+        let addr = ClassicAddress::from(0x112233445566);
+        let uuid = Uuid::from_u16(0x1101);
+        MockFixtures::new()
+            .with_classic_device(
+                addr,
+                MockDevice::new("Headphones")
+                    .with_rfcomm_service(uuid, vec![vec![1, 2], vec![3]]),
+            )
+            .install();
+
+        futures::executor::block_on(async {
+            let device = ClassicDevice::new(addr).await.unwrap();
+            assert!(matches!(
+                device.connect_rfcomm(Uuid::from_u16(0x1102)).await,
+                Err(BluetoothError::Unreachable(_))
+            ));
+
+            let mut socket = device.connect_rfcomm(uuid).await.unwrap();
+            socket.write(&[4, 5]).await.unwrap();
+            assert_eq!(socket.next().await, Some(Ok(vec![1, 2])));
+            assert_eq!(socket.next().await, Some(Ok(vec![3])));
+            assert_eq!(socket.next().await, None);
+            assert_eq!(
+                MockFixtures::classic_device(addr)
+                    .unwrap()
+                    .rfcomm_written(uuid),
+                [vec![4, 5]]
+            );
+        });
+    }
+
+    #[test]
+    fn ble_device_pairing() {
+        let addr = BleAddress::new(0x112233445566, BleAddressKind::Random);
+        MockFixtures::new()
+            .with_ble_device(
+                addr,
+                MockDevice::new("Earbuds").with_passkey(123456),
+            )
+            .install();
+
+        futures::executor::block_on(async {
+            let device = BleDevice::new(addr).await.unwrap();
+            assert!(matches!(
+                device.pair().await,
+                Err(BluetoothError::PairingFailed(_))
+            ));
+            assert!(matches!(
+                device.pair_with_delegate(PasskeyDelegate(123456)).await,
+                Ok(PairingResult::Success(
+                    ProtectionLevel::EncryptionAndAuthentication
+                ))
+            ));
+            assert!(MockFixtures::ble_device(addr).unwrap().is_paired());
+        });
+    }
+
+    #[test]
+    fn classic_device_pairing() {
+        let addr = ClassicAddress::from(0x112233445566);
+        MockFixtures::new()
+            .with_classic_device(addr, MockDevice::new("Headphones"))
+            .install();
+
+        futures::executor::block_on(async {
+            let device = ClassicDevice::new(addr).await.unwrap();
+            assert!(matches!(
+                device
+                    .pair_with_protection_level(
+                        ProtectionLevel::EncryptionAndAuthentication
+                    )
+                    .await,
+                Err(BluetoothError::PairingFailed(_))
+            ));
+            assert!(matches!(
+                device.pair().await,
+                Ok(PairingResult::Success(ProtectionLevel::Encryption))
+            ));
+            assert!(MockFixtures::classic_device(addr).unwrap().is_paired());
+            assert!(matches!(
+                device.pair().await,
+                Ok(PairingResult::AlreadyPaired)
+            ));
+        });
+    }
+
+    #[test]
+    fn classic_device_pairing_with_passkey() {
+        let addr = ClassicAddress::from(0x112233445566);
+        MockFixtures::new()
+            .with_classic_device(
+                addr,
+                MockDevice::new("Headphones").with_passkey(123456),
+            )
+            .install();
+
+        futures::executor::block_on(async {
+            let device = ClassicDevice::new(addr).await.unwrap();
+            assert!(matches!(
+                device.pair().await,
+                Err(BluetoothError::PairingFailed(_))
+            ));
+            assert!(matches!(
+                device.pair_with_delegate(PasskeyDelegate(654321)).await,
+                Err(BluetoothError::PairingFailed(_))
+            ));
+            assert!(!MockFixtures::classic_device(addr).unwrap().is_paired());
+
+            assert!(matches!(
+                device
+                    .pair_with_delegate_and_protection_level(
+                        PasskeyDelegate(123456),
+                        ProtectionLevel::EncryptionAndAuthentication
+                    )
+                    .await,
+                Ok(PairingResult::Success(
+                    ProtectionLevel::EncryptionAndAuthentication
+                ))
+            ));
+            assert!(MockFixtures::classic_device(addr).unwrap().is_paired());
+        });
+    }
+
+    #[test]
+    fn list_paired_devices_from_fixtures() {
+        let ble = BleAddress::new(0x112233445566, BleAddressKind::Public);
+        let classic = ClassicAddress::from(0x665544332211);
+        let unpaired = ClassicAddress::from(0x112233445566);
+        MockFixtures::new()
+            .with_ble_device(ble, MockDevice::new("Tag").with_paired(true))
+            .with_classic_device(
+                classic,
+                MockDevice::new("Headphones").with_paired(true),
+            )
+            .with_classic_device(unpaired, MockDevice::new("Speaker"))
+            .install();
+
+        futures::executor::block_on(async {
+            let devices = list_paired_devices().await.unwrap();
+            assert_eq!(
+                devices,
+                vec![
+                    PairedDevice::new(
+                        DeviceAddress::Classic(classic),
+                        Some(String::from("Headphones"))
+                    ),
+                    PairedDevice::new(
+                        DeviceAddress::Ble(ble),
+                        Some(String::from("Tag"))
+                    ),
+                ]
+            );
+        });
+    }
+}
@@ -0,0 +1,523 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
+
+use futures::channel::mpsc::Sender;
+
+use super::gatt_server::MockGattServer;
+use crate::common::{
+    AdapterState, BleAddress, BleAddressKind, BluetoothError, ClassOfDevice,
+    ClassicAddress, DeviceAddress, DiscoveredClassicDevice, PairedDevice,
+    SdpRecord, Uuid,
+};
+
+/// Address of the local adapter, unless set with
+/// `MockFixtures::with_local_adapter()`.
+const DEFAULT_LOCAL_ADDRESS: u64 = 0x0000_0000_0001;
+
+thread_local! {
+    /// Fixtures installed for the current thread. Each test runs on its own
+    /// thread, so tests installing different fixtures can run in parallel.
+    static FIXTURES: RefCell<Option<MockFixtures>> =
+        const { RefCell::new(None) };
+}
+
+/// Run `f` on the installed fixtures.
+pub(crate) fn with_fixtures<T>(
+    f: impl FnOnce(&mut MockFixtures) -> Result<T, BluetoothError>,
+) -> Result<T, BluetoothError> {
+    FIXTURES.with(|fixtures| match fixtures.borrow_mut().as_mut() {
+        Some(fixtures) => f(fixtures),
+        None => Err(BluetoothError::FailedPrecondition(String::from(
+            "No mock fixtures installed on this thread.",
+        ))),
+    })
+}
+
+/// In-memory state backing the mock platform: the adapter state, a script
+/// of advertisements delivered in order to scanners, and the devices which
+/// can be reached by address. The local adapter can also take the role of a
+/// peripheral: its advertisements are appended to the script, and its GATT
+/// server can be reached at its address, so that both sides of a protocol
+/// can be tested together.
+///
+/// Fixtures are installed for the calling thread, so the mock platform
+/// should be driven by a single-threaded executor, e.g.
+/// `futures::executor::block_on()`.
+#[derive(Debug)]
+pub struct MockFixtures {
+    adapter_state: AdapterState,
+    state_sender: Option<Sender<AdapterState>>,
+    advertisements: VecDeque<MockAdvertisement>,
+    ble_devices: HashMap<BleAddress, MockDevice>,
+    classic_devices: HashMap<ClassicAddress, MockDevice>,
+    local_address: BleAddress,
+    /// Signal strength the advertisements of the local adapter are received
+    /// with.
+    local_rssi: Option<i16>,
+    gatt_servers: HashMap<BleAddress, Arc<MockGattServer>>,
+    /// Channels of the clients watching the services of the local GATT
+    /// server.
+    services_changed_senders: Vec<Sender<()>>,
+}
+
+impl Default for MockFixtures {
+    fn default() -> Self {
+        MockFixtures {
+            adapter_state: AdapterState::PoweredOn,
+            state_sender: None,
+            advertisements: VecDeque::new(),
+            ble_devices: HashMap::new(),
+            classic_devices: HashMap::new(),
+            local_address: BleAddress::new(
+                DEFAULT_LOCAL_ADDRESS,
+                BleAddressKind::Public,
+            ),
+            local_rssi: None,
+            gatt_servers: HashMap::new(),
+            services_changed_senders: Vec::new(),
+        }
+    }
+}
+
+impl MockFixtures {
+    /// Construct empty fixtures, with a powered on adapter.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the initial power state of the adapter.
+    pub fn with_adapter_state(mut self, state: AdapterState) -> Self {
+        self.adapter_state = state;
+        self
+    }
+
+    /// Append an advertisement to the script delivered to scanners.
+    pub fn with_advertisement(
+        mut self,
+        advertisement: MockAdvertisement,
+    ) -> Self {
+        self.advertisements.push_back(advertisement);
+        self
+    }
+
+    /// Make a BLE device reachable at `addr`.
+    pub fn with_ble_device(
+        mut self,
+        addr: BleAddress,
+        device: MockDevice,
+    ) -> Self {
+        self.ble_devices.insert(addr, device);
+        self
+    }
+
+    /// Make a BT Classic device reachable at `addr`.
+    pub fn with_classic_device(
+        mut self,
+        addr: ClassicAddress,
+        device: MockDevice,
+    ) -> Self {
+        self.classic_devices.insert(addr, device);
+        self
+    }
+
+    /// Set the address the local adapter advertises from and hosts its GATT
+    /// server at, and the signal strength its advertisements are received
+    /// with.
+    pub fn with_local_adapter(
+        mut self,
+        addr: BleAddress,
+        rssi: Option<i16>,
+    ) -> Self {
+        self.local_address = addr;
+        self.local_rssi = rssi;
+        self
+    }
+
+    /// Install the fixtures for the current thread, replacing the previously
+    /// installed ones, if any.
+    pub fn install(self) {
+        FIXTURES.with(|fixtures| *fixtures.borrow_mut() = Some(self));
+    }
+
+    /// Change the power state of the adapter, notifying the stream returned
+    /// by `api::BleAdapter::watch_state()`, if any.
+    pub fn set_adapter_state(
+        state: AdapterState,
+    ) -> Result<(), BluetoothError> {
+        with_fixtures(|fixtures| {
+            fixtures.adapter_state = state;
+            if let Some(sender) = &mut fixtures.state_sender {
+                // A full or closed channel only means nobody is watching.
+                let _ = sender.try_send(state);
+            }
+            Ok(())
+        })
+    }
+
+    /// Retrieve the current state of the BLE device at `addr`, e.g. to check
+    /// the outcome of the code under test.
+    pub fn ble_device(addr: BleAddress) -> Result<MockDevice, BluetoothError> {
+        with_fixtures(|fixtures| fixtures.ble_device_mut(addr).cloned())
+    }
+
+    /// Retrieve the current state of the BT Classic device at `addr`, e.g. to
+    /// check whether it was paired by the code under test.
+    pub fn classic_device(
+        addr: ClassicAddress,
+    ) -> Result<MockDevice, BluetoothError> {
+        with_fixtures(|fixtures| fixtures.classic_device_mut(addr).cloned())
+    }
+
+    pub(crate) fn adapter_state(&self) -> AdapterState {
+        self.adapter_state
+    }
+
+    pub(crate) fn set_state_sender(&mut self, sender: Sender<AdapterState>) {
+        self.state_sender = Some(sender);
+    }
+
+    pub(crate) fn pop_advertisement(&mut self) -> Option<MockAdvertisement> {
+        self.advertisements.pop_front()
+    }
+
+    /// Append an advertisement broadcast by the local adapter to the script.
+    pub(crate) fn push_local_advertisement(&mut self, raw_data: Vec<u8>) {
+        self.advertisements.push_back(MockAdvertisement::new(
+            self.local_address,
+            self.local_rssi,
+            raw_data,
+        ));
+    }
+
+    /// Host `server` at the address of the local adapter, replacing the
+    /// previous one, if any. The watching clients are told that the
+    /// services changed.
+    pub(crate) fn add_gatt_server(&mut self, server: Arc<MockGattServer>) {
+        self.gatt_servers.insert(self.local_address, server);
+        self.services_changed_senders
+            .retain(|sender| !sender.is_closed());
+        for sender in &mut self.services_changed_senders {
+            // A change pending delivery already covers this one.
+            let _ = sender.try_send(());
+        }
+    }
+
+    /// Report the changes of the services of the local GATT server through
+    /// `sender`, until it's closed.
+    pub(crate) fn watch_gatt_services(&mut self, sender: Sender<()>) {
+        self.services_changed_senders.push(sender);
+    }
+
+    /// Stop hosting `server`, unless it was replaced already.
+    pub(crate) fn remove_gatt_server(&mut self, server: &Arc<MockGattServer>) {
+        if self
+            .gatt_servers
+            .get(&self.local_address)
+            .is_some_and(|hosted| Arc::ptr_eq(hosted, server))
+        {
+            self.gatt_servers.remove(&self.local_address);
+        }
+    }
+
+    pub(crate) fn gatt_server(
+        &self,
+        addr: BleAddress,
+    ) -> Result<Arc<MockGattServer>, BluetoothError> {
+        self.gatt_servers.get(&addr).cloned().ok_or_else(|| {
+            BluetoothError::Unreachable(format!(
+                "no mock GATT server at {}",
+                addr
+            ))
+        })
+    }
+
+    pub(crate) fn ble_device_mut(
+        &mut self,
+        addr: BleAddress,
+    ) -> Result<&mut MockDevice, BluetoothError> {
+        self.ble_devices.get_mut(&addr).ok_or_else(|| {
+            BluetoothError::Unreachable(format!(
+                "no mock BLE device at {}",
+                addr
+            ))
+        })
+    }
+
+    /// List the BT Classic devices found by discovery, i.e. the unpaired
+    /// ones, ordered by address.
+    pub(crate) fn discoverable_classic_devices(
+        &self,
+    ) -> Vec<DiscoveredClassicDevice> {
+        let mut devices: Vec<_> = self
+            .classic_devices
+            .iter()
+            .filter(|(_, device)| !device.is_paired())
+            .map(|(addr, device)| {
+                DiscoveredClassicDevice::new(
+                    *addr,
+                    Some(device.name.clone()),
+                    device.class_of_device,
+                )
+            })
+            .collect();
+        devices.sort_by_key(|device| u64::from(device.address()));
+        devices
+    }
+
+    /// List the paired devices, BT Classic ones first, each ordered by
+    /// address.
+    pub(crate) fn paired_devices(&self) -> Vec<PairedDevice> {
+        let mut classic: Vec<_> = self
+            .classic_devices
+            .iter()
+            .filter(|(_, device)| device.is_paired())
+            .collect();
+        classic.sort_by_key(|(addr, _)| u64::from(**addr));
+        let mut ble: Vec<_> = self
+            .ble_devices
+            .iter()
+            .filter(|(_, device)| device.is_paired())
+            .collect();
+        ble.sort_by_key(|(addr, _)| u64::from(**addr));
+
+        classic
+            .into_iter()
+            .map(|(addr, device)| (DeviceAddress::Classic(*addr), device))
+            .chain(
+                ble.into_iter()
+                    .map(|(addr, device)| (DeviceAddress::Ble(*addr), device)),
+            )
+            .map(|(addr, device)| {
+                PairedDevice::new(addr, Some(device.name.clone()))
+            })
+            .collect()
+    }
+
+    pub(crate) fn classic_device_mut(
+        &mut self,
+        addr: ClassicAddress,
+    ) -> Result<&mut MockDevice, BluetoothError> {
+        self.classic_devices.get_mut(&addr).ok_or_else(|| {
+            BluetoothError::Unreachable(format!(
+                "no mock BT Classic device at {}",
+                addr
+            ))
+        })
+    }
+}
+
+/// A scripted advertisement, holding the raw AD structures the device would
+/// broadcast.
+/// See: Bluetooth Core Specification, Vol 3, Part C, Section 11.
+#[derive(Clone, Debug)]
+pub struct MockAdvertisement {
+    address: BleAddress,
+    rssi: Option<i16>,
+    raw_data: Vec<u8>,
+}
+
+impl MockAdvertisement {
+    /// Construct a new `MockAdvertisement` instance.
+    pub fn new(
+        address: BleAddress,
+        rssi: Option<i16>,
+        raw_data: Vec<u8>,
+    ) -> Self {
+        MockAdvertisement {
+            address,
+            rssi,
+            raw_data,
+        }
+    }
+
+    pub(crate) fn address(&self) -> BleAddress {
+        self.address
+    }
+
+    pub(crate) fn rssi(&self) -> Option<i16> {
+        self.rssi
+    }
+
+    pub(crate) fn raw_data(&self) -> &[u8] {
+        &self.raw_data
+    }
+}
+
+/// A scripted remote device. The same type backs BLE and BT Classic devices,
+/// although SDP records and RFCOMM services only apply to the latter.
+#[derive(Clone, Debug)]
+pub struct MockDevice {
+    name: String,
+    rssi: Option<i16>,
+    connected: bool,
+    paired: bool,
+    can_pair: bool,
+    passkey: Option<u32>,
+    class_of_device: ClassOfDevice,
+    sdp_records: Vec<SdpRecord>,
+    rfcomm_services: HashMap<Uuid, MockRfcommService>,
+}
+
+/// A scripted RFCOMM service, holding the data sent by the device once
+/// connected and the data written to it.
+#[derive(Clone, Debug, Default)]
+struct MockRfcommService {
+    sent: Vec<Vec<u8>>,
+    written: Vec<Vec<u8>>,
+}
+
+impl MockDevice {
+    /// Construct a disconnected, unpaired device which accepts pairing
+    /// without user interaction.
+    pub fn new(name: &str) -> Self {
+        MockDevice {
+            name: String::from(name),
+            rssi: None,
+            connected: false,
+            paired: false,
+            can_pair: true,
+            passkey: None,
+            class_of_device: ClassOfDevice::from(0),
+            sdp_records: Vec::new(),
+            rfcomm_services: HashMap::new(),
+        }
+    }
+
+    /// Set the signal strength of the device in dBm.
+    pub fn with_rssi(mut self, rssi: i16) -> Self {
+        self.rssi = Some(rssi);
+        self
+    }
+
+    /// Mark the device as connected.
+    pub fn with_connected(mut self, connected: bool) -> Self {
+        self.connected = connected;
+        self
+    }
+
+    /// Mark the device as already paired.
+    pub fn with_paired(mut self, paired: bool) -> Self {
+        self.paired = paired;
+        self
+    }
+
+    /// Reject every pairing attempt.
+    pub fn with_pairing_rejected(mut self) -> Self {
+        self.can_pair = false;
+        self
+    }
+
+    /// Require a numeric comparison of `passkey` to pair, which only
+    /// succeeds with a `api::PairingDelegate` confirming it.
+    pub fn with_passkey(mut self, passkey: u32) -> Self {
+        self.passkey = Some(passkey);
+        self
+    }
+
+    /// Set the Class of Device reported to BT Classic discovery.
+    pub fn with_class_of_device(mut self, class: ClassOfDevice) -> Self {
+        self.class_of_device = class;
+        self
+    }
+
+    /// Add a service to the SDP records of the device.
+    pub fn with_sdp_record(mut self, record: SdpRecord) -> Self {
+        self.sdp_records.push(record);
+        self
+    }
+
+    /// Offer an RFCOMM service identified by `uuid`. Once connected, the
+    /// device sends each chunk of `sent` in order, then closes the
+    /// connection.
+    pub fn with_rfcomm_service(
+        mut self,
+        uuid: Uuid,
+        sent: Vec<Vec<u8>>,
+    ) -> Self {
+        self.rfcomm_services.insert(
+            uuid,
+            MockRfcommService {
+                sent,
+                written: Vec::new(),
+            },
+        );
+        self
+    }
+
+    /// Retrieve the data written by the code under test to the RFCOMM
+    /// service `uuid`, one entry per write.
+    pub fn rfcomm_written(&self, uuid: Uuid) -> &[Vec<u8>] {
+        self.rfcomm_services
+            .get(&uuid)
+            .map_or(&[], |service| service.written.as_slice())
+    }
+
+    /// Retrieve the name of the device.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Check whether the device is paired.
+    pub fn is_paired(&self) -> bool {
+        self.paired
+    }
+
+    pub(crate) fn rssi(&self) -> Option<i16> {
+        self.rssi
+    }
+
+    pub(crate) fn is_connected(&self) -> bool {
+        self.connected
+    }
+
+    pub(crate) fn can_pair(&self) -> bool {
+        self.can_pair
+    }
+
+    pub(crate) fn passkey(&self) -> Option<u32> {
+        self.passkey
+    }
+
+    pub(crate) fn set_paired(&mut self) {
+        self.paired = true;
+    }
+
+    pub(crate) fn set_disconnected(&mut self) {
+        self.connected = false;
+    }
+
+    pub(crate) fn sdp_records(&self) -> &[SdpRecord] {
+        &self.sdp_records
+    }
+
+    pub(crate) fn rfcomm_sent(&self, uuid: Uuid) -> Option<&[Vec<u8>]> {
+        self.rfcomm_services
+            .get(&uuid)
+            .map(|service| service.sent.as_slice())
+    }
+
+    pub(crate) fn record_rfcomm_write(&mut self, uuid: Uuid, data: Vec<u8>) {
+        self.rfcomm_services
+            .entry(uuid)
+            .or_default()
+            .written
+            .push(data);
+    }
+}
@@ -0,0 +1,475 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use futures::channel::mpsc::{self, Sender};
+
+use super::{fixtures::with_fixtures, gatt_server::MockGattServer};
+use crate::{
+    api,
+    common::{
+        BleAddress, BluetoothError, CharacteristicProperties,
+        CharacteristicValueStream, ConnectionPriority, GattCharacteristic,
+        GattService, ServicesChangedStream, WriteType,
+    },
+};
+
+/// ATT MTU in effect until another one is negotiated.
+/// See: Bluetooth Core Specification, Vol 3, Part F, Section 3.2.8.
+const DEFAULT_MTU: u16 = 23;
+/// Largest ATT MTU, bounded by the maximum length of an attribute value.
+const MAX_MTU: u16 = 517;
+
+/// Type implementing `api::GattClient` for the mock platform, connected to
+/// the GATT server hosted by the local adapter through the mock
+/// `GattServer`. Other devices don't run GATT servers in the fixtures.
+/// Hosting another `GattServer` changes the services, which are found by
+/// the next discovery.
+pub struct GattClient {
+    addr: BleAddress,
+    server: Arc<MockGattServer>,
+    mtu: u16,
+    /// Channels of the subscriptions, closed to cancel them.
+    subscriptions: HashMap<GattCharacteristic, Sender<Vec<u8>>>,
+    /// Channel of the stream returned by `watch_services_changed()`, closed
+    /// when it's called again or the client is dropped.
+    services_changed_sender: Option<Sender<()>>,
+}
+
+#[async_trait]
+impl api::GattClient for GattClient {
+    async fn connect(addr: BleAddress) -> Result<Self, BluetoothError> {
+        let server = with_fixtures(|fixtures| fixtures.gatt_server(addr))?;
+        Ok(GattClient {
+            addr,
+            server,
+            mtu: DEFAULT_MTU,
+            subscriptions: HashMap::new(),
+            services_changed_sender: None,
+        })
+    }
+
+    fn address(&self) -> BleAddress {
+        self.addr
+    }
+
+    fn current_mtu(&self) -> Result<u16, BluetoothError> {
+        Ok(self.mtu)
+    }
+
+    async fn request_mtu(&mut self, mtu: u16) -> Result<u16, BluetoothError> {
+        self.mtu = mtu.clamp(DEFAULT_MTU, MAX_MTU);
+        Ok(self.mtu)
+    }
+
+    /// The fixtures have no connection parameters, so `priority` is ignored.
+    fn request_connection_priority(
+        &mut self,
+        _priority: ConnectionPriority,
+    ) -> Result<(), BluetoothError> {
+        Ok(())
+    }
+
+    async fn discover_services(
+        &mut self,
+    ) -> Result<Vec<GattService>, BluetoothError> {
+        self.server =
+            with_fixtures(|fixtures| fixtures.gatt_server(self.addr))?;
+        Ok(self
+            .server
+            .services()
+            .iter()
+            .map(|service| GattService::new(service.uuid()))
+            .collect())
+    }
+
+    async fn discover_characteristics(
+        &mut self,
+        service: &GattService,
+    ) -> Result<Vec<GattCharacteristic>, BluetoothError> {
+        let service = self
+            .server
+            .services()
+            .iter()
+            .find(|hosted| hosted.uuid() == service.uuid())
+            .ok_or_else(|| {
+                BluetoothError::FailedPrecondition(format!(
+                    "no service {} on the mock GATT server",
+                    service.uuid()
+                ))
+            })?;
+
+        Ok(service
+            .characteristics()
+            .iter()
+            .filter_map(|characteristic| {
+                service.characteristic(characteristic.uuid())
+            })
+            .collect())
+    }
+
+    async fn read_characteristic(
+        &mut self,
+        characteristic: &GattCharacteristic,
+    ) -> Result<Vec<u8>, BluetoothError> {
+        self.server.read(characteristic)
+    }
+
+    /// Reliable writes are handled as a single write with response, since
+    /// the mock server has no queue of prepared writes.
+    async fn write_characteristic(
+        &mut self,
+        characteristic: &GattCharacteristic,
+        value: &[u8],
+        write_type: WriteType,
+    ) -> Result<(), BluetoothError> {
+        match write_type {
+            WriteType::WithResponse | WriteType::Reliable => self.server.write(
+                characteristic,
+                CharacteristicProperties::WRITE,
+                value,
+            ),
+            WriteType::WithoutResponse => {
+                // Errors aren't reported for writes without response.
+                let _ = self.server.write(
+                    characteristic,
+                    CharacteristicProperties::WRITE_WITHOUT_RESPONSE,
+                    value,
+                );
+                Ok(())
+            }
+        }
+    }
+
+    async fn subscribe(
+        &mut self,
+        characteristic: &GattCharacteristic,
+    ) -> Result<CharacteristicValueStream, BluetoothError> {
+        let (sender, receiver) = self.server.subscribe(characteristic)?;
+        if let Some(mut previous) =
+            self.subscriptions.insert(*characteristic, sender)
+        {
+            previous.close_channel();
+        }
+
+        Ok(CharacteristicValueStream::new(receiver))
+    }
+
+    async fn unsubscribe(
+        &mut self,
+        characteristic: &GattCharacteristic,
+    ) -> Result<(), BluetoothError> {
+        match self.subscriptions.remove(characteristic) {
+            Some(mut sender) => {
+                sender.close_channel();
+                Ok(())
+            }
+            None => Err(BluetoothError::FailedPrecondition(format!(
+                "not subscribed to characteristic {}",
+                characteristic.uuid()
+            ))),
+        }
+    }
+
+    async fn watch_services_changed(
+        &mut self,
+    ) -> Result<ServicesChangedStream, BluetoothError> {
+        let (sender, receiver) = mpsc::channel(1);
+        with_fixtures(|fixtures| {
+            fixtures.watch_gatt_services(sender.clone());
+            Ok(())
+        })?;
+        if let Some(mut previous) = self.services_changed_sender.replace(sender)
+        {
+            previous.close_channel();
+        }
+
+        Ok(ServicesChangedStream::new(receiver))
+    }
+}
+
+impl Drop for GattClient {
+    /// Disconnecting cancels every subscription and ends the watch of the
+    /// services.
+    fn drop(&mut self) {
+        for sender in self.subscriptions.values_mut() {
+            sender.close_channel();
+        }
+        if let Some(sender) = &mut self.services_changed_sender {
+            sender.close_channel();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use futures::{executor::block_on, StreamExt};
+
+    use super::*;
+    use crate::{
+        api::{GattClient as _, GattRequestHandler, GattServer as _},
+        common::{
+            AttErrorCode, BleAddressKind, LocalCharacteristic, LocalService,
+            Uuid,
+        },
+        mock::{GattServer, MockFixtures},
+    };
+
+    const SERVICE_UUID: Uuid = Uuid::from_u16(0xFE2C);
+    const READ_UUID: Uuid = Uuid::from_u16(0x1233);
+    const WRITE_UUID: Uuid = Uuid::from_u16(0x1234);
+
+    /// Handler answering reads with a fixed value, and recording writes.
+    #[derive(Clone, Default)]
+    struct TestHandler {
+        written: Arc<Mutex<Vec<Vec<u8>>>>,
+    }
+
+    impl GattRequestHandler for TestHandler {
+        fn on_read(
+            &self,
+            _characteristic: &GattCharacteristic,
+        ) -> Result<Vec<u8>, AttErrorCode> {
+            Ok(vec![0x01, 0x02, 0x03])
+        }
+
+        fn on_write(
+            &self,
+            _characteristic: &GattCharacteristic,
+            value: &[u8],
+        ) -> Result<(), AttErrorCode> {
+            if value.is_empty() {
+                return Err(AttErrorCode::INVALID_ATTRIBUTE_VALUE_LENGTH);
+            }
+            self.written.lock().unwrap().push(value.to_vec());
+            Ok(())
+        }
+    }
+
+    fn service() -> LocalService {
+        LocalService::new(SERVICE_UUID)
+            .with_characteristic(LocalCharacteristic::new(
+                READ_UUID,
+                CharacteristicProperties::READ,
+            ))
+            .with_characteristic(LocalCharacteristic::new(
+                WRITE_UUID,
+                CharacteristicProperties::WRITE
+                    | CharacteristicProperties::NOTIFY,
+            ))
+    }
+
+    #[test]
+    fn connect_to_local_gatt_server() {
+        let local = BleAddress::new(0x112233445566, BleAddressKind::Public);
+        let other = BleAddress::new(0x665544332211, BleAddressKind::Public);
+        MockFixtures::new()
+            .with_local_adapter(local, None)
+            .install();
+
+        block_on(async {
+            assert!(matches!(
+                GattClient::connect(local).await,
+                Err(BluetoothError::Unreachable(_))
+            ));
+
+            let handler = TestHandler::default();
+            let server = GattServer::new(&[service()], handler.clone())
+                .await
+                .unwrap();
+            assert!(matches!(
+                GattClient::connect(other).await,
+                Err(BluetoothError::Unreachable(_))
+            ));
+
+            let mut client = GattClient::connect(local).await.unwrap();
+            assert_eq!(client.address(), local);
+            let services = client.discover_services().await.unwrap();
+            assert_eq!(services, [GattService::new(SERVICE_UUID)]);
+            let characteristics =
+                client.discover_characteristics(&services[0]).await.unwrap();
+            assert_eq!(
+                characteristics,
+                [
+                    service().characteristic(READ_UUID).unwrap(),
+                    service().characteristic(WRITE_UUID).unwrap(),
+                ]
+            );
+
+            assert_eq!(
+                client
+                    .read_characteristic(&characteristics[0])
+                    .await
+                    .unwrap(),
+                [0x01, 0x02, 0x03]
+            );
+            client
+                .write_characteristic(
+                    &characteristics[1],
+                    &[0x04],
+                    WriteType::WithResponse,
+                )
+                .await
+                .unwrap();
+            assert_eq!(*handler.written.lock().unwrap(), [vec![0x04]]);
+
+            // The server is withdrawn once dropped.
+            drop(server);
+            assert!(matches!(
+                GattClient::connect(local).await,
+                Err(BluetoothError::Unreachable(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn local_gatt_server_rejects_requests() {
+        let local = BleAddress::new(0x112233445566, BleAddressKind::Public);
+        MockFixtures::new()
+            .with_local_adapter(local, None)
+            .install();
+
+        block_on(async {
+            let _server = GattServer::new(&[service()], TestHandler::default())
+                .await
+                .unwrap();
+            let mut client = GattClient::connect(local).await.unwrap();
+            let read = service().characteristic(READ_UUID).unwrap();
+            let write = service().characteristic(WRITE_UUID).unwrap();
+
+            // Write Not Permitted.
+            let result = client
+                .write_characteristic(&read, &[0x04], WriteType::WithResponse)
+                .await;
+            assert!(matches!(
+                result,
+                Err(BluetoothError::GattProtocolError(0x03))
+            ));
+            // Read Not Permitted.
+            let result = client.read_characteristic(&write).await;
+            assert!(matches!(
+                result,
+                Err(BluetoothError::GattProtocolError(0x02))
+            ));
+            // Invalid Attribute Value Length, as answered by the handler.
+            let result = client
+                .write_characteristic(&write, &[], WriteType::WithResponse)
+                .await;
+            assert!(matches!(
+                result,
+                Err(BluetoothError::GattProtocolError(0x0D))
+            ));
+            // Errors aren't reported for writes without response.
+            assert!(client
+                .write_characteristic(&write, &[], WriteType::WithoutResponse)
+                .await
+                .is_ok());
+
+            let unknown = GattCharacteristic::new(
+                SERVICE_UUID,
+                Uuid::from_u16(0x1235),
+                CharacteristicProperties::READ,
+            );
+            assert!(matches!(
+                client.read_characteristic(&unknown).await,
+                Err(BluetoothError::FailedPrecondition(_))
+            ));
+            assert!(matches!(
+                client.subscribe(&read).await,
+                Err(BluetoothError::NotSupported(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn reliable_writes_reach_server_whole() {
+        let local = BleAddress::new(0x112233445566, BleAddressKind::Public);
+        MockFixtures::new()
+            .with_local_adapter(local, None)
+            .install();
+
+        block_on(async {
+            let handler = TestHandler::default();
+            let _server = GattServer::new(&[service()], handler.clone())
+                .await
+                .unwrap();
+            let mut client = GattClient::connect(local).await.unwrap();
+            let characteristic = service().characteristic(WRITE_UUID).unwrap();
+
+            client
+                .write_characteristic(
+                    &characteristic,
+                    &[0x01, 0x02],
+                    WriteType::Reliable,
+                )
+                .await
+                .unwrap();
+            assert_eq!(*handler.written.lock().unwrap(), [vec![0x01, 0x02]]);
+
+            // A value longer than the MTU is still handed to the server in a
+            // single write.
+            let mtu = client.current_mtu().unwrap();
+            let value: Vec<u8> = (0..=u8::MAX).collect();
+            assert!(value.len() > usize::from(mtu));
+            client
+                .write_characteristic(
+                    &characteristic,
+                    &value,
+                    WriteType::Reliable,
+                )
+                .await
+                .unwrap();
+            assert_eq!(
+                *handler.written.lock().unwrap(),
+                [vec![0x01, 0x02], value]
+            );
+        });
+    }
+
+    #[test]
+    fn notify_subscribed_clients() {
+        let local = BleAddress::new(0x112233445566, BleAddressKind::Public);
+        MockFixtures::new()
+            .with_local_adapter(local, None)
+            .install();
+
+        block_on(async {
+            let mut server =
+                GattServer::new(&[service()], TestHandler::default())
+                    .await
+                    .unwrap();
+            let mut client = GattClient::connect(local).await.unwrap();
+            let characteristic = service().characteristic(WRITE_UUID).unwrap();
+
+            let mut values = client.subscribe(&characteristic).await.unwrap();
+            server.notify(&characteristic, &[0x05]).await.unwrap();
+            server.notify(&characteristic, &[0x06]).await.unwrap();
+            assert_eq!(values.next().await, Some(vec![0x05]));
+            assert_eq!(values.next().await, Some(vec![0x06]));
+
+            // Unsubscribing ends the stream.
+            client.unsubscribe(&characteristic).await.unwrap();
+            server.notify(&characteristic, &[0x07]).await.unwrap();
+            assert_eq!(values.next().await, None);
+            assert!(matches!(
+                client.unsubscribe(&characteristic).await,
+                Err(BluetoothError::FailedPrecondition(_))
+            ));
+        });
+    }
+}
@@ -0,0 +1,218 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+use futures::channel::mpsc::{self, Receiver, Sender};
+
+use super::fixtures::with_fixtures;
+use crate::{
+    api::{self, GattRequestHandler},
+    common::{
+        AttErrorCode, BluetoothError, CharacteristicProperties,
+        GattCharacteristic, LocalService,
+    },
+};
+
+/// Capacity of the channels carrying notifications to subscribed clients.
+const NOTIFICATION_CHANNEL_CAPACITY: usize = 16;
+
+/// Channel carrying the notifications of a characteristic to a client: the
+/// sender, to be closed by the client to unsubscribe, and the receiver.
+pub(crate) type Subscription = (Sender<Vec<u8>>, Receiver<Vec<u8>>);
+
+/// Type implementing `api::GattServer` for the mock platform. The services
+/// are hosted at the address of the local adapter, where the mock
+/// `GattClient` can connect to them.
+pub struct GattServer {
+    server: Arc<MockGattServer>,
+}
+
+#[async_trait]
+impl api::GattServer for GattServer {
+    async fn new<H: GattRequestHandler>(
+        services: &[LocalService],
+        handler: H,
+    ) -> Result<Self, BluetoothError> {
+        let server = Arc::new(MockGattServer {
+            services: services.to_vec(),
+            handler: Box::new(handler),
+            subscribers: Mutex::new(HashMap::new()),
+        });
+        with_fixtures(|fixtures| {
+            fixtures.add_gatt_server(server.clone());
+            Ok(())
+        })?;
+
+        Ok(GattServer { server })
+    }
+
+    async fn notify(
+        &mut self,
+        characteristic: &GattCharacteristic,
+        value: &[u8],
+    ) -> Result<(), BluetoothError> {
+        self.server.notify(characteristic, value)
+    }
+}
+
+impl Drop for GattServer {
+    fn drop(&mut self) {
+        // The fixtures may be gone already, e.g. when dropped on another
+        // thread, in which case there's nothing left to withdraw from.
+        let _ = with_fixtures(|fixtures| {
+            fixtures.remove_gatt_server(&self.server);
+            Ok(())
+        });
+    }
+}
+
+/// State of a GATT server hosted by the local adapter, shared with the
+/// clients connected to it. Requests are forwarded to the handler from the
+/// thread of the client.
+pub(crate) struct MockGattServer {
+    services: Vec<LocalService>,
+    handler: Box<dyn GattRequestHandler>,
+    /// Channels of the subscribed clients, by characteristic.
+    subscribers: Mutex<HashMap<GattCharacteristic, Vec<Sender<Vec<u8>>>>>,
+}
+
+impl fmt::Debug for MockGattServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockGattServer")
+            .field("services", &self.services)
+            .finish_non_exhaustive()
+    }
+}
+
+impl MockGattServer {
+    pub(crate) fn services(&self) -> &[LocalService] {
+        &self.services
+    }
+
+    /// Forward a read request to the handler, provided the characteristic
+    /// can be read.
+    pub(crate) fn read(
+        &self,
+        characteristic: &GattCharacteristic,
+    ) -> Result<Vec<u8>, BluetoothError> {
+        self.check_hosted(characteristic)?;
+        if !characteristic
+            .properties()
+            .contains(CharacteristicProperties::READ)
+        {
+            return Err(BluetoothError::GattProtocolError(
+                AttErrorCode::READ_NOT_PERMITTED.code(),
+            ));
+        }
+
+        self.handler
+            .on_read(characteristic)
+            .map_err(|err| BluetoothError::GattProtocolError(err.code()))
+    }
+
+    /// Forward a write request to the handler, provided the characteristic
+    /// can be written with `properties`.
+    pub(crate) fn write(
+        &self,
+        characteristic: &GattCharacteristic,
+        properties: CharacteristicProperties,
+        value: &[u8],
+    ) -> Result<(), BluetoothError> {
+        self.check_hosted(characteristic)?;
+        if !characteristic.properties().contains(properties) {
+            return Err(BluetoothError::GattProtocolError(
+                AttErrorCode::WRITE_NOT_PERMITTED.code(),
+            ));
+        }
+
+        self.handler
+            .on_write(characteristic, value)
+            .map_err(|err| BluetoothError::GattProtocolError(err.code()))
+    }
+
+    /// Subscribe to the notifications of a characteristic. The subscription
+    /// is cancelled by closing the returned channel.
+    pub(crate) fn subscribe(
+        &self,
+        characteristic: &GattCharacteristic,
+    ) -> Result<Subscription, BluetoothError> {
+        self.check_hosted(characteristic)?;
+        let properties = characteristic.properties();
+        if !properties.contains(CharacteristicProperties::NOTIFY)
+            && !properties.contains(CharacteristicProperties::INDICATE)
+        {
+            return Err(BluetoothError::NotSupported(format!(
+                "subscribing to characteristic {}",
+                characteristic.uuid()
+            )));
+        }
+
+        let (sender, receiver) = mpsc::channel(NOTIFICATION_CHANNEL_CAPACITY);
+        self.subscribers
+            .lock()
+            .unwrap()
+            .entry(*characteristic)
+            .or_default()
+            .push(sender.clone());
+        Ok((sender, receiver))
+    }
+
+    /// Send `value` to the clients subscribed to `characteristic`, dropping
+    /// the cancelled subscriptions.
+    fn notify(
+        &self,
+        characteristic: &GattCharacteristic,
+        value: &[u8],
+    ) -> Result<(), BluetoothError> {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        if let Some(senders) = subscribers.get_mut(characteristic) {
+            senders.retain(|sender| !sender.is_closed());
+            for sender in senders {
+                sender.try_send(value.to_vec()).map_err(|err| {
+                    BluetoothError::Internal(format!(
+                        "Failed to notify subscriber: {}",
+                        err
+                    ))
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that `characteristic` belongs to the hosted services.
+    fn check_hosted(
+        &self,
+        characteristic: &GattCharacteristic,
+    ) -> Result<(), BluetoothError> {
+        let hosted = self
+            .services
+            .iter()
+            .filter(|service| service.uuid() == characteristic.service_uuid())
+            .find_map(|service| service.characteristic(characteristic.uuid()));
+        match hosted {
+            Some(hosted) if hosted == *characteristic => Ok(()),
+            _ => Err(BluetoothError::FailedPrecondition(format!(
+                "no characteristic {} on the mock GATT server",
+                characteristic.uuid()
+            ))),
+        }
+    }
+}
@@ -0,0 +1,29 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+/// Bluetooth module backed by in-memory `MockFixtures` rather than a radio,
+/// so that code built on this crate can be tested on any machine.
+mod adapter;
+mod advertiser;
+mod device;
+mod fixtures;
+mod gatt;
+mod gatt_server;
+
+pub use adapter::*;
+pub use advertiser::*;
+pub use device::*;
+pub use fixtures::{MockAdvertisement, MockDevice, MockFixtures};
+pub use gatt::*;
+pub use gatt_server::*;
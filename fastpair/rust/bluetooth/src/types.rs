@@ -12,4 +12,4 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-pub mod packets;
\ No newline at end of file
+pub mod packets;
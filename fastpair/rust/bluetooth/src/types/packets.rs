@@ -4,7 +4,7 @@
 // you may not use this file except in compliance with the License.
 // You may obtain a copy of the License at
 //
-//     http://www.apache.org/licenses/LICENSE-2.0
+//     https://www.apache.org/licenses/LICENSE-2.0
 //
 // Unless required by applicable law or agreed to in writing, software
 // distributed under the License is distributed on an "AS IS" BASIS,
@@ -15,19 +15,61 @@
 // Specification: https://developers.google.com/nearby/fast-pair/specifications/extensions/messagestream
 // This file should be in sync with fastpair/message_stream/message.h.
 
+use crate::common::BluetoothError;
 
-#[derive(Debug)]
+/// Length of a packet header: message group, message code, and length of the
+/// additional data.
+const HEADER_LENGTH: usize = 4;
+
+const BLUETOOTH_GROUP: u8 = 0x01;
+const COMPANION_APP_EVENT_GROUP: u8 = 0x02;
+const DEVICE_INFORMATION_EVENT_GROUP: u8 = 0x03;
+const DEVICE_ACTION_EVENT_GROUP: u8 = 0x04;
+const SASS_GROUP: u8 = 0x07;
+const ACKNOWLEDGEMENT_GROUP: u8 = 0xFF;
+
+/// Implement `TryFrom<u8>` for an enum of message codes, listing its
+/// variants.
+macro_rules! impl_try_from_code {
+    ($name:ident { $($variant:ident),+ $(,)? }) => {
+        impl TryFrom<u8> for $name {
+            type Error = BluetoothError;
+
+            fn try_from(code: u8) -> Result<Self, Self::Error> {
+                match code {
+                    $(code if code == $name::$variant as u8 => {
+                        Ok($name::$variant)
+                    })+
+                    _ => Err(BluetoothError::BadTypeConversion(format!(
+                        "unknown {} {:#04x}",
+                        stringify!($name),
+                        code,
+                    ))),
+                }
+            }
+        }
+    };
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum BluetoothCode {
     EnableSilenceMode = 0x01,
     DisableSilenceMode = 0x02,
 }
 
-#[derive(Debug)]
+impl_try_from_code!(BluetoothCode {
+    EnableSilenceMode,
+    DisableSilenceMode,
+});
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum CompanionAppEventCode {
     LogBufferFull = 0x01,
 }
 
-#[derive(Debug)]
+impl_try_from_code!(CompanionAppEventCode { LogBufferFull });
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum DeviceInformationEventCode {
     ModelId = 0x01,
     BleAddressUpdated = 0x02,
@@ -40,7 +82,19 @@ pub enum DeviceInformationEventCode {
     SessionNonce = 0x0A,
 }
 
-#[derive(Debug)]
+impl_try_from_code!(DeviceInformationEventCode {
+    ModelId,
+    BleAddressUpdated,
+    BatteryUpdated,
+    RemainingBattery,
+    ActiveComponentsRequest,
+    ActiveComponentsResponse,
+    Capabilities,
+    PlatformType,
+    SessionNonce,
+});
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SassCode {
     Acknowledgement = 0xFF,
     SassGetCapability = 0x10,
@@ -60,18 +114,51 @@ pub enum SassCode {
     SassSetDropConnectionTarget = 0x43,
 }
 
-#[derive(Debug)]
+impl_try_from_code!(SassCode {
+    Acknowledgement,
+    SassGetCapability,
+    SassNotifyCapability,
+    SassSetMultipointState,
+    SassSetSwitchingPreference,
+    SassGetSwitchingPreference,
+    SassNotifySwitchingPreference,
+    SassSwitchActiveSourceCode,
+    SassSwitchBackAudioSource,
+    SassNotifyMultipointSwitchEvent,
+    SassGetConnectionStatus,
+    SassNotifyConnectionStatus,
+    SassNotifySassInitiatedConnection,
+    SassInUseAccountKey,
+    SassSendCustomData,
+    SassSetDropConnectionTarget,
+});
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum DeviceActionEventCode {
     Ring = 1,
 }
 
-#[derive(Debug)]
+impl_try_from_code!(DeviceActionEventCode { Ring });
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AcknowledgementCode {
     Ack = 1,
     Nack = 2,
 }
 
-#[derive(Debug)]
+impl_try_from_code!(AcknowledgementCode { Ack, Nack });
+
+/// Reason sent along with a NAK, i.e. a negative acknowledgement.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NakReason {
+    NotSupported = 0x00,
+    DeviceBusy = 0x01,
+    NotAllowedInCurrentState = 0x02,
+    IncorrectMac = 0x03,
+    RedundantDeviceAction = 0x04,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum MessageGroup {
     Bluetooth(BluetoothCode),
     CompanionAppEvent(CompanionAppEventCode),
@@ -79,11 +166,202 @@ pub enum MessageGroup {
     DeviceActionEvent(DeviceActionEventCode),
     Sass(SassCode),
     Acknowledgement(AcknowledgementCode),
+    /// A message group, or a message code within a known group, which isn't
+    /// supported yet.
+    Unknown {
+        group: u8,
+        code: u8,
+    },
 }
 
+impl MessageGroup {
+    /// Construct the `MessageGroup` identified by the raw message group and
+    /// message code of a packet.
+    pub fn new(group: u8, code: u8) -> Self {
+        let known = match group {
+            BLUETOOTH_GROUP => code.try_into().map(MessageGroup::Bluetooth),
+            COMPANION_APP_EVENT_GROUP => {
+                code.try_into().map(MessageGroup::CompanionAppEvent)
+            }
+            DEVICE_INFORMATION_EVENT_GROUP => {
+                code.try_into().map(MessageGroup::DeviceInformationEvent)
+            }
+            DEVICE_ACTION_EVENT_GROUP => {
+                code.try_into().map(MessageGroup::DeviceActionEvent)
+            }
+            SASS_GROUP => code.try_into().map(MessageGroup::Sass),
+            ACKNOWLEDGEMENT_GROUP => {
+                code.try_into().map(MessageGroup::Acknowledgement)
+            }
+            _ => return MessageGroup::Unknown { group, code },
+        };
+
+        known.unwrap_or(MessageGroup::Unknown { group, code })
+    }
+
+    /// Retrieve the raw message group.
+    pub fn group(&self) -> u8 {
+        match self {
+            MessageGroup::Bluetooth(_) => BLUETOOTH_GROUP,
+            MessageGroup::CompanionAppEvent(_) => COMPANION_APP_EVENT_GROUP,
+            MessageGroup::DeviceInformationEvent(_) => {
+                DEVICE_INFORMATION_EVENT_GROUP
+            }
+            MessageGroup::DeviceActionEvent(_) => DEVICE_ACTION_EVENT_GROUP,
+            MessageGroup::Sass(_) => SASS_GROUP,
+            MessageGroup::Acknowledgement(_) => ACKNOWLEDGEMENT_GROUP,
+            MessageGroup::Unknown { group, .. } => *group,
+        }
+    }
+
+    /// Retrieve the raw message code.
+    pub fn code(&self) -> u8 {
+        match self {
+            MessageGroup::Bluetooth(code) => *code as u8,
+            MessageGroup::CompanionAppEvent(code) => *code as u8,
+            MessageGroup::DeviceInformationEvent(code) => *code as u8,
+            MessageGroup::DeviceActionEvent(code) => *code as u8,
+            MessageGroup::Sass(code) => *code as u8,
+            MessageGroup::Acknowledgement(code) => *code as u8,
+            MessageGroup::Unknown { code, .. } => *code,
+        }
+    }
+}
 
 /// A packet that is sent over the RFCOMM Message Stream.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct MessageStreamPacket {
     pub group: MessageGroup,
     pub additional_data: Vec<u8>,
-}
\ No newline at end of file
+}
+
+impl MessageStreamPacket {
+    /// Construct a new `MessageStreamPacket` instance.
+    pub fn new(group: MessageGroup, additional_data: Vec<u8>) -> Self {
+        MessageStreamPacket {
+            group,
+            additional_data,
+        }
+    }
+
+    /// Construct the ACK of `packet`, telling its sender that it was handled.
+    pub fn ack(packet: &MessageStreamPacket) -> Self {
+        MessageStreamPacket::new(
+            MessageGroup::Acknowledgement(AcknowledgementCode::Ack),
+            vec![packet.group.group(), packet.group.code()],
+        )
+    }
+
+    /// Construct the NAK of `packet`, telling its sender why it couldn't be
+    /// handled.
+    pub fn nak(packet: &MessageStreamPacket, reason: NakReason) -> Self {
+        MessageStreamPacket::new(
+            MessageGroup::Acknowledgement(AcknowledgementCode::Nack),
+            vec![reason as u8, packet.group.group(), packet.group.code()],
+        )
+    }
+
+    /// Serialize the packet as sent over the Message Stream.
+    pub(crate) fn to_bytes(&self) -> Result<Vec<u8>, BluetoothError> {
+        let len = u16::try_from(self.additional_data.len()).map_err(|_| {
+            BluetoothError::BadTypeConversion(format!(
+                "Message Stream additional data of {} bytes is too long.",
+                self.additional_data.len()
+            ))
+        })?;
+
+        let mut bytes = Vec::with_capacity(HEADER_LENGTH + usize::from(len));
+        bytes.push(self.group.group());
+        bytes.push(self.group.code());
+        bytes.extend_from_slice(&len.to_be_bytes());
+        bytes.extend_from_slice(&self.additional_data);
+        Ok(bytes)
+    }
+
+    /// Parse the packet at the start of `bytes`, returning it along with the
+    /// number of bytes it spans, or `None` if `bytes` doesn't hold a whole
+    /// packet yet.
+    pub(crate) fn parse(bytes: &[u8]) -> Option<(Self, usize)> {
+        let header = bytes.get(..HEADER_LENGTH)?;
+        let len = usize::from(u16::from_be_bytes([header[2], header[3]]));
+        let additional_data = bytes.get(HEADER_LENGTH..HEADER_LENGTH + len)?;
+
+        Some((
+            MessageStreamPacket::new(
+                MessageGroup::new(header[0], header[1]),
+                additional_data.to_vec(),
+            ),
+            HEADER_LENGTH + len,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn message_group_codes() {
+        let group = MessageGroup::new(0x03, 0x03);
+        assert_eq!(
+            group,
+            MessageGroup::DeviceInformationEvent(
+                DeviceInformationEventCode::BatteryUpdated
+            )
+        );
+        assert_eq!((group.group(), group.code()), (0x03, 0x03));
+
+        for (group, code) in [(0x05, 0x01), (0x01, 0x03)] {
+            let unknown = MessageGroup::new(group, code);
+            assert_eq!(unknown, MessageGroup::Unknown { group, code });
+            assert_eq!((unknown.group(), unknown.code()), (group, code));
+        }
+    }
+
+    #[test]
+    fn packet_round_trip() {
+        let packet = MessageStreamPacket::new(
+            MessageGroup::Bluetooth(BluetoothCode::DisableSilenceMode),
+            vec![0, 1, 2],
+        );
+        let bytes = packet.to_bytes().unwrap();
+        assert_eq!(bytes, [0x01, 0x02, 0x00, 0x03, 0, 1, 2]);
+
+        let mut stream = bytes.clone();
+        stream.extend_from_slice(&[0xFF, 0x01]);
+        assert_eq!(MessageStreamPacket::parse(&stream), Some((packet, 7)));
+        assert_eq!(MessageStreamPacket::parse(&bytes[..6]), None);
+        assert_eq!(MessageStreamPacket::parse(&bytes[..3]), None);
+    }
+
+    #[test]
+    fn acknowledgements() {
+        let packet = MessageStreamPacket::new(
+            MessageGroup::DeviceActionEvent(DeviceActionEventCode::Ring),
+            vec![0x01, 0x3C],
+        );
+
+        assert_eq!(
+            MessageStreamPacket::ack(&packet).to_bytes().unwrap(),
+            [0xFF, 0x01, 0x00, 0x02, 0x04, 0x01]
+        );
+        assert_eq!(
+            MessageStreamPacket::nak(&packet, NakReason::DeviceBusy)
+                .to_bytes()
+                .unwrap(),
+            [0xFF, 0x02, 0x00, 0x03, 0x01, 0x04, 0x01]
+        );
+    }
+
+    #[test]
+    fn packet_too_long() {
+        let packet = MessageStreamPacket::new(
+            MessageGroup::Bluetooth(BluetoothCode::EnableSilenceMode),
+            vec![0; 0x10000],
+        );
+        assert!(matches!(
+            packet.to_bytes(),
+            Err(BluetoothError::BadTypeConversion(_))
+        ));
+    }
+}
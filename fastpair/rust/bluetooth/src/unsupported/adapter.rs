@@ -14,8 +14,14 @@
 
 use async_trait::async_trait;
 
-use super::BleDevice;
-use crate::{api, common::BluetoothError, BleAdvertisement, BleDataTypeId};
+use crate::{
+    api,
+    common::{
+        AdapterStateStream, AdvertisementStream, BluetoothError,
+        DiscoveredClassicDevice, RetryPolicy, ScanFilter, ScanSettings,
+    },
+    BleAdvertisement, BleDataTypeId,
+};
 
 /// Concrete type implementing `Adapter`, used for unsupported devices.
 /// Every method should panic.
@@ -23,11 +29,17 @@ pub struct BleAdapter;
 
 #[async_trait]
 impl api::BleAdapter for BleAdapter {
-    async fn default() -> Result<Self, BluetoothError> {
+    async fn default_with_retry_policy(
+        _retry_policy: RetryPolicy,
+    ) -> Result<Self, BluetoothError> {
         panic!("Unsupported target platform.");
     }
 
-    fn start_scan(&mut self) -> Result<(), BluetoothError> {
+    fn start_scan_with_settings(
+        &mut self,
+        _filter: Option<ScanFilter>,
+        _settings: ScanSettings,
+    ) -> Result<(), BluetoothError> {
         panic!("Unsupported target platform.");
     }
 
@@ -35,16 +47,66 @@ impl api::BleAdapter for BleAdapter {
         panic!("Unsupported target platform.");
     }
 
+    fn scan(
+        &self,
+        _filter: Option<ScanFilter>,
+        _settings: ScanSettings,
+        _datatype_selector: Vec<BleDataTypeId>,
+    ) -> Result<AdvertisementStream, BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
     async fn next_advertisement(
         &mut self,
-        datatype_selector: Option<&Vec<BleDataTypeId>>,
+        _datatype_selector: Option<&Vec<BleDataTypeId>>,
     ) -> Result<BleAdvertisement, BluetoothError> {
         panic!("Unsupported target platform");
     }
+
+    async fn next_advertisement_batch(
+        &mut self,
+        _datatype_selector: Option<&Vec<BleDataTypeId>>,
+    ) -> Result<Vec<BleAdvertisement>, BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
+    fn dropped_advertisements(&self) -> u64 {
+        panic!("Unsupported target platform.");
+    }
+
+    async fn watch_state(
+        &mut self,
+    ) -> Result<AdapterStateStream, BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
 }
 
-mod tests {
-    use super::*;
+/// Concrete type implementing `api::ClassicAdapter`, used for unsupported
+/// devices. Every method should panic.
+pub struct ClassicAdapter;
 
+#[async_trait]
+impl api::ClassicAdapter for ClassicAdapter {
+    async fn default() -> Result<Self, BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
+    fn start_discovery(&mut self) -> Result<(), BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
+    fn stop_discovery(&mut self) -> Result<(), BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
+    async fn next_discovered_device(
+        &mut self,
+    ) -> Result<DiscoveredClassicDevice, BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
     // TODO b/288592509 unit tests
 }
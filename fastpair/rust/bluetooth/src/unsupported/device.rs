@@ -16,7 +16,11 @@ use async_trait::async_trait;
 
 use crate::{
     api,
-    common::{BleAddress, BluetoothError, ClassicAddress, PairingResult},
+    common::{
+        BleAddress, BluetoothError, ClassicAddress, ConnectionStatusStream,
+        PairedDevice, PairingResult, ProtectionLevel, RetryPolicy,
+        RfcommSocket, SdpRecord, Uuid,
+    },
 };
 
 /// Concrete type implementing `api::BleDevice` for unsupported platforms.
@@ -25,7 +29,10 @@ pub struct BleDevice;
 
 #[async_trait]
 impl api::BleDevice for BleDevice {
-    async fn new(addr: BleAddress) -> Result<Self, BluetoothError> {
+    async fn new_with_retry_policy(
+        _addr: BleAddress,
+        _retry_policy: RetryPolicy,
+    ) -> Result<Self, BluetoothError> {
         panic!("Unsupported target platform.");
     }
 
@@ -36,6 +43,37 @@ impl api::BleDevice for BleDevice {
     fn address(&self) -> BleAddress {
         panic!("Unsupported target platform.");
     }
+
+    async fn rssi(&self) -> Result<i16, BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
+    async fn watch_connection_status(
+        &mut self,
+    ) -> Result<ConnectionStatusStream, BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
+    async fn pair_with_protection_level(
+        &self,
+        _protection_level: ProtectionLevel,
+    ) -> Result<PairingResult, BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
+    async fn pair_with_delegate_and_protection_level<
+        D: api::PairingDelegate,
+    >(
+        &self,
+        _delegate: D,
+        _protection_level: ProtectionLevel,
+    ) -> Result<PairingResult, BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
+    fn close(self) -> Result<(), BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
 }
 
 /// Concrete type implementing `api::ClassicDevice` for unsupported platforms.
@@ -44,7 +82,10 @@ pub struct ClassicDevice;
 
 #[async_trait]
 impl api::ClassicDevice for ClassicDevice {
-    async fn new(addr: ClassicAddress) -> Result<Self, BluetoothError> {
+    async fn new_with_retry_policy(
+        _addr: ClassicAddress,
+        _retry_policy: RetryPolicy,
+    ) -> Result<Self, BluetoothError> {
         panic!("Unsupported target platform.");
     }
 
@@ -56,13 +97,52 @@ impl api::ClassicDevice for ClassicDevice {
         panic!("Unsupported target platform.");
     }
 
-    async fn pair(&self) -> Result<PairingResult, BluetoothError> {
+    async fn rssi(&self) -> Result<i16, BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
+    async fn discover_services(
+        &self,
+    ) -> Result<Vec<SdpRecord>, BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
+    async fn connect_rfcomm(
+        &self,
+        _service_uuid: Uuid,
+    ) -> Result<RfcommSocket, BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
+    async fn pair_with_protection_level(
+        &self,
+        _protection_level: ProtectionLevel,
+    ) -> Result<PairingResult, BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
+    async fn pair_with_delegate_and_protection_level<
+        D: api::PairingDelegate,
+    >(
+        &self,
+        _delegate: D,
+        _protection_level: ProtectionLevel,
+    ) -> Result<PairingResult, BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
+    fn close(self) -> Result<(), BluetoothError> {
         panic!("Unsupported target platform.");
     }
 }
 
-mod tests {
-    use super::*;
+/// List paired devices on unsupported platforms. Should panic.
+pub async fn list_paired_devices() -> Result<Vec<PairedDevice>, BluetoothError>
+{
+    panic!("Unsupported target platform.");
+}
 
+#[cfg(test)]
+mod tests {
     // TODO b/288592509 unit tests
 }
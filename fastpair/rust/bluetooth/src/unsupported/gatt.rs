@@ -0,0 +1,107 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+
+use crate::{
+    api,
+    common::{
+        BleAddress, BluetoothError, CharacteristicValueStream,
+        ConnectionPriority, GattCharacteristic, GattService,
+        ServicesChangedStream, WriteType,
+    },
+};
+
+/// Concrete type implementing `api::GattClient` for unsupported platforms.
+/// Every method should panic.
+pub struct GattClient;
+
+#[async_trait]
+impl api::GattClient for GattClient {
+    async fn connect(_addr: BleAddress) -> Result<Self, BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
+    fn address(&self) -> BleAddress {
+        panic!("Unsupported target platform.");
+    }
+
+    fn current_mtu(&self) -> Result<u16, BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
+    async fn request_mtu(&mut self, _mtu: u16) -> Result<u16, BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
+    fn request_connection_priority(
+        &mut self,
+        _priority: ConnectionPriority,
+    ) -> Result<(), BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
+    async fn discover_services(
+        &mut self,
+    ) -> Result<Vec<GattService>, BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
+    async fn discover_characteristics(
+        &mut self,
+        _service: &GattService,
+    ) -> Result<Vec<GattCharacteristic>, BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
+    async fn read_characteristic(
+        &mut self,
+        _characteristic: &GattCharacteristic,
+    ) -> Result<Vec<u8>, BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
+    async fn write_characteristic(
+        &mut self,
+        _characteristic: &GattCharacteristic,
+        _value: &[u8],
+        _write_type: WriteType,
+    ) -> Result<(), BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
+    async fn subscribe(
+        &mut self,
+        _characteristic: &GattCharacteristic,
+    ) -> Result<CharacteristicValueStream, BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
+    async fn unsubscribe(
+        &mut self,
+        _characteristic: &GattCharacteristic,
+    ) -> Result<(), BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+    async fn watch_services_changed(
+        &mut self,
+    ) -> Result<ServicesChangedStream, BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // TODO b/288592509 unit tests
+}
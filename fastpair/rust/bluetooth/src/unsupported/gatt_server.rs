@@ -0,0 +1,47 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+
+use crate::{
+    api,
+    common::{BluetoothError, GattCharacteristic, LocalService},
+};
+
+/// Concrete type implementing `api::GattServer` for unsupported platforms.
+/// Every method should panic.
+pub struct GattServer;
+
+#[async_trait]
+impl api::GattServer for GattServer {
+    async fn new<H: api::GattRequestHandler>(
+        _services: &[LocalService],
+        _handler: H,
+    ) -> Result<Self, BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+
+    async fn notify(
+        &mut self,
+        _characteristic: &GattCharacteristic,
+        _value: &[u8],
+    ) -> Result<(), BluetoothError> {
+        panic!("Unsupported target platform.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // TODO b/288592509 unit tests
+}
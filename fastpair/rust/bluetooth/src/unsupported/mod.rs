@@ -14,7 +14,13 @@
 
 /// Bluetooth LE module for unsupported devices. Every method panics.
 mod adapter;
+mod advertiser;
 mod device;
+mod gatt;
+mod gatt_server;
 
 pub use adapter::*;
+pub use advertiser::*;
 pub use device::*;
+pub use gatt::*;
+pub use gatt_server::*;
@@ -12,14 +12,30 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use async_trait::async_trait;
-use futures::{channel::mpsc::Receiver, StreamExt};
+use futures::{
+    channel::mpsc::{Sender, UnboundedReceiver},
+    stream, StreamExt,
+};
 use tracing::{error, info, warn};
 use windows::{
+    core::{ComInterface, IInspectable, HSTRING},
     Devices::Bluetooth::{
         Advertisement::{
+            // Byte pattern matched against the data sections of received
+            // advertisements.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.advertisement.bluetoothleadvertisementbytepattern?view=winrt-22621
+            BluetoothLEAdvertisementBytePattern,
+
+            // Filter applied to received advertisements by the watcher.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.advertisement.bluetoothleadvertisementfilter?view=winrt-22621
+            BluetoothLEAdvertisementFilter,
+
             // Struct that receives Bluetooth Low Energy (LE) advertisements.
             // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.advertisement.bluetoothleadvertisementwatcher?view=winrt-22621
             BluetoothLEAdvertisementReceivedEventArgs,
@@ -38,6 +54,9 @@ use windows::{
             // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.advertisement.bluetoothleadvertisementwatcherstoppedeventargs?view=winrt-22621
             BluetoothLEAdvertisementWatcherStoppedEventArgs,
 
+            // Struct representing a manufacturer specific data section.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.advertisement.bluetoothlemanufacturerdata?view=winrt-22621
+            BluetoothLEManufacturerData,
             // Defines constants that specify a Bluetooth LE scanning mode.
             // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.advertisement.bluetoothlescanningmode?view=winrt-22621
             BluetoothLEScanningMode,
@@ -46,37 +65,268 @@ use windows::{
         // Bluetooth adapter.
         // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.bluetoothadapter?view=winrt-22621
         BluetoothAdapter,
+
+        // Struct representing a BT Classic device, created from the ID of
+        // a device found by discovery.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.bluetoothdevice?view=winrt-22621
+        BluetoothDevice,
+
+        // Enum describing why the watcher stopped, `Success` meaning it was
+        // stopped on purpose.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.bluetootherror?view=winrt-22621
+        BluetoothError as WindowsBluetoothError,
+
+        // Filter on the signal strength of received advertisements, which
+        // also sets how often the advertisements of a device are reported.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.bluetoothsignalstrengthfilter?view=winrt-22621
+        BluetoothSignalStrengthFilter,
     },
+    Devices::Enumeration::{
+        // Struct describing a device found by a device watcher.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.enumeration.deviceinformation?view=winrt-22621
+        DeviceInformation,
+
+        // Tuple struct to indicate the kind of device an ID refers to.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.enumeration.deviceinformationkind?view=winrt-22621
+        DeviceInformationKind,
+
+        // Struct describing a change to a device found by a device watcher.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.enumeration.deviceinformationupdate?view=winrt-22621
+        DeviceInformationUpdate,
+
+        // Struct enumerating devices as they are found, e.g. through BT
+        // Classic inquiry, and enum describing whether it's running.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.enumeration.devicewatcher?view=winrt-22621
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.enumeration.devicewatcherstatus?view=winrt-22621
+        DeviceWatcher,
+        DeviceWatcherStatus,
+    },
+    // Struct representing the radio of the Bluetooth adapter, and enum
+    // describing whether it's turned on.
+    // https://learn.microsoft.com/en-us/uwp/api/windows.devices.radios.radio?view=winrt-22621
+    // https://learn.microsoft.com/en-us/uwp/api/windows.devices.radios.radiostate?view=winrt-22621
+    Devices::Radios::{Radio, RadioState},
+    // Iterable collection, used to pass the properties requested from the
+    // device watcher.
+    // https://learn.microsoft.com/en-us/uwp/api/windows.foundation.collections.iiterable-1?view=winrt-22621
+    Foundation::Collections::IIterable,
+
     // Wraps a closure for handling events associated with a struct
     // (e.g. Received and Stopped events in BluetoothLEAdvertisementWatcher).
     // https://learn.microsoft.com/en-us/uwp/api/windows.foundation.typedeventhandler-2?view=winrt-22621
-    Foundation::TypedEventHandler,
+    Foundation::{EventRegistrationToken, TypedEventHandler},
+
+    // Boxed time spans and signal strengths, used as the properties of a
+    // signal strength filter.
+    // https://learn.microsoft.com/en-us/uwp/api/windows.foundation.propertyvalue?view=winrt-22621
+    // https://learn.microsoft.com/en-us/uwp/api/windows.foundation.ireference-1?view=winrt-22621
+    // https://learn.microsoft.com/en-us/uwp/api/windows.foundation.timespan?view=winrt-22621
+    Foundation::{IReference, PropertyValue, TimeSpan},
 };
 
+use super::{
+    error::{is_transient, scan_stop_reason},
+    gatt::write_buffer,
+};
 use crate::{
     api,
-    common::{BleAdvertisement, BleDataTypeId, BluetoothError},
+    common::{
+        retry, retry_blocking, scan_queue, sleep, AdapterState,
+        AdapterStateStream, AdvertisementStream, BleAdvertisement,
+        BleDataTypeId, BluetoothError, ClassOfDevice, ClassicAddress,
+        DiscoveredClassicDevice, RetryPolicy, ScanFilter, ScanMode, ScanQueue,
+        ScanSettings,
+    },
 };
 
+/// Event sent by the watcher handlers to `AdvListener::receiver`.
+enum WatcherEvent {
+    /// An advertisement was received at the given time.
+    Received(Instant, BluetoothLEAdvertisementReceivedEventArgs),
+    /// The watcher stopped unexpectedly with the given error.
+    Stopped(WindowsBluetoothError),
+}
+
 /// Struct holding the necessary fields for listening to and handling incoming
 /// BLE advertisements.
 struct AdvListener {
     /// Holds callback for sending received advertisement events to `receiver`.
     watcher: BluetoothLEAdvertisementWatcher,
     /// Can be polled to consume incoming advertisement events.
-    receiver: Receiver<BluetoothLEAdvertisementReceivedEventArgs>,
+    receiver: ScanQueue<WatcherEvent>,
+    /// Criteria of the scan, of which the address must be checked on
+    /// received advertisements.
+    filter: ScanFilter,
+    /// Policy for restarting the watcher after it stopped unexpectedly.
+    retry_policy: RetryPolicy,
+    /// Sequence number of the next advertisement delivered by the scan.
+    next_sequence_number: u64,
+    /// Unexpected stop taken from `receiver` while draining a batch, to be
+    /// handled by the next call to `next_advertisement()`.
+    pending_stop: Option<WindowsBluetoothError>,
+    /// Number of times the watcher stopped unexpectedly since the last
+    /// advertisement was delivered.
+    interruptions: u32,
+    /// Why the scan was aborted, reported once `receiver` ends.
+    abort_reason: Option<String>,
+}
+
+impl AdvListener {
+    /// Wait for the next advertisement delivered by the watcher, loaded with
+    /// the selected data types, or `None` once `receiver` ended. Fails with
+    /// `BluetoothError::ScanInterrupted` after restarting the watcher.
+    async fn next_advertisement(
+        &mut self,
+        datatype_selector: Option<&Vec<BleDataTypeId>>,
+    ) -> Result<Option<BleAdvertisement>, BluetoothError> {
+        // We don't want the end-user to receive empty devices, so this is a
+        // loop to catch and skip trivial errors from advertisements that
+        // can't be turned into devices.
+        loop {
+            let event = match self.pending_stop.take() {
+                Some(err) => WatcherEvent::Stopped(err),
+                None => match self.receiver.next().await {
+                    Some(event) => event,
+                    None => return Ok(None),
+                },
+            };
+
+            match event {
+                WatcherEvent::Received(received_at, event_args) => {
+                    if let Some(advertisement) = self.deliver(
+                        received_at,
+                        &event_args,
+                        datatype_selector,
+                    )? {
+                        break Ok(Some(advertisement));
+                    }
+                }
+                WatcherEvent::Stopped(err) => {
+                    let retry_policy = self.retry_policy;
+                    self.restart(&retry_policy, err).await?;
+                    break Err(BluetoothError::ScanInterrupted(format!(
+                        "{}, the watcher was restarted",
+                        scan_stop_reason(err)
+                    )));
+                }
+            }
+        }
+    }
+
+    /// Stop the watcher, which closes `receiver` once it stopped.
+    fn stop(&self) -> Result<(), BluetoothError> {
+        Ok(self.watcher.Stop()?)
+    }
+
+    /// Convert a received advertisement with `received_advertisement()`,
+    /// numbering the advertisements delivered.
+    fn deliver(
+        &mut self,
+        received_at: Instant,
+        event_args: &BluetoothLEAdvertisementReceivedEventArgs,
+        datatype_selector: Option<&Vec<BleDataTypeId>>,
+    ) -> Result<Option<BleAdvertisement>, BluetoothError> {
+        let advertisement = received_advertisement(
+            event_args,
+            &self.filter,
+            datatype_selector,
+        )?;
+        Ok(advertisement.map(|mut advertisement| {
+            advertisement.set_received_at(received_at);
+            advertisement.set_sequence_number(self.next_sequence_number);
+            self.next_sequence_number += 1;
+            self.interruptions = 0;
+            advertisement
+        }))
+    }
+
+    /// Restart the watcher after it stopped unexpectedly with `err`, waiting
+    /// longer after each consecutive interruption, as described by
+    /// `retry_policy`. Gives up, aborting the scan, if the radio can't be
+    /// used anymore or the policy runs out of attempts.
+    async fn restart(
+        &mut self,
+        retry_policy: &RetryPolicy,
+        err: WindowsBluetoothError,
+    ) -> Result<(), BluetoothError> {
+        let reason = scan_stop_reason(err);
+        let restartable = matches!(
+            err,
+            WindowsBluetoothError::ResourceInUse
+                | WindowsBluetoothError::DeviceNotConnected
+                | WindowsBluetoothError::OtherError
+        );
+        self.interruptions += 1;
+        if !restartable || self.interruptions >= retry_policy.max_attempts() {
+            return Err(self.abort(reason));
+        }
+
+        sleep(retry_policy.backoff(self.interruptions)).await;
+        if let Err(err) =
+            retry_blocking(retry_policy, is_transient, || self.watcher.Start())
+        {
+            return Err(self.abort(format!(
+                "{}, then restarting failed: {}",
+                reason, err
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Abort the scan, ending `receiver`, and build the error reporting it.
+    fn abort(&mut self, reason: String) -> BluetoothError {
+        warn!("Aborting the scan: {}.", reason);
+        self.receiver.close();
+        self.abort_reason = Some(reason.clone());
+        BluetoothError::ScanAborted(reason)
+    }
+
+    /// Build the error reported once `receiver` ended.
+    fn ended(&self) -> BluetoothError {
+        match &self.abort_reason {
+            Some(reason) => BluetoothError::ScanAborted(reason.clone()),
+            None => BluetoothError::Internal(String::from(
+                "Event returned from stream is None.",
+            )),
+        }
+    }
+}
+
+/// Scan delivering its advertisements to an `AdvertisementStream`, stopped
+/// when the stream is dropped.
+struct StreamedScan(AdvListener);
+
+impl Drop for StreamedScan {
+    fn drop(&mut self) {
+        if let Err(err) = self.0.stop() {
+            warn!("Failed to stop scanning: {}", err);
+        }
+    }
 }
 
 /// Concrete type implementing `api::BleAdapter`, used for Windows BLE.
 pub struct BleAdapter {
     inner: BluetoothAdapter,
     listener: Option<AdvListener>,
+    /// Radio watched by `watch_state()`, along with the registration of its
+    /// StateChanged handler.
+    state_watch: Option<(Radio, EventRegistrationToken)>,
+    /// Policy for retrying operations failing transiently, e.g. starting the
+    /// watcher while the radio is busy, and for restarting the watcher after
+    /// it stopped unexpectedly.
+    retry_policy: RetryPolicy,
 }
 
 #[async_trait]
 impl api::BleAdapter for BleAdapter {
-    async fn default() -> Result<Self, BluetoothError> {
-        let inner = BluetoothAdapter::GetDefaultAsync()?.await?;
+    async fn default_with_retry_policy(
+        retry_policy: RetryPolicy,
+    ) -> Result<Self, BluetoothError> {
+        let inner = retry(&retry_policy, is_transient, || async {
+            BluetoothAdapter::GetDefaultAsync()?.await
+        })
+        .await?;
 
         if !inner.IsLowEnergySupported()? {
             return Err(BluetoothError::NotSupported(String::from(
@@ -92,28 +342,170 @@ impl api::BleAdapter for BleAdapter {
         Ok(BleAdapter {
             inner,
             listener: None,
+            state_watch: None,
+            retry_policy,
         })
     }
 
-    fn start_scan(&mut self) -> Result<(), BluetoothError> {
+    /// Windows schedules the scan itself, so the interval and window hints
+    /// are ignored. The report delay is applied as the sampling interval of
+    /// the signal strength filter, coalescing the advertisements of each
+    /// device, along with the signal strength thresholds and out-of-range
+    /// timeout.
+    fn start_scan_with_settings(
+        &mut self,
+        filter: Option<ScanFilter>,
+        settings: ScanSettings,
+    ) -> Result<(), BluetoothError> {
+        self.listener = Some(self.listen(filter, settings)?);
+        Ok(())
+    }
+
+    fn stop_scan(&mut self) -> Result<(), BluetoothError> {
+        if let Some(listener) = self.listener.take() {
+            listener.stop()
+        } else {
+            Err(BluetoothError::FailedPrecondition(String::from(
+                "device scanning hasn't started, please call `start_scan()`",
+            )))
+        }
+    }
+
+    async fn next_advertisement(
+        &mut self,
+        datatype_selector: Option<&Vec<BleDataTypeId>>,
+    ) -> Result<BleAdvertisement, BluetoothError> {
+        if let Some(listener) = &mut self.listener {
+            match listener.next_advertisement(datatype_selector).await? {
+                Some(advertisement) => Ok(advertisement),
+                None => Err(listener.ended()),
+            }
+        } else {
+            Err(BluetoothError::FailedPrecondition(String::from(
+                "device scanning hasn't started, please call `start_scan()`",
+            )))
+        }
+    }
+
+    fn scan(
+        &self,
+        filter: Option<ScanFilter>,
+        settings: ScanSettings,
+        datatype_selector: Vec<BleDataTypeId>,
+    ) -> Result<AdvertisementStream, BluetoothError> {
+        let scan = StreamedScan(self.listen(filter, settings)?);
+        Ok(AdvertisementStream::new(stream::unfold(
+            (scan, datatype_selector),
+            |(mut scan, datatype_selector)| async move {
+                match scan.0.next_advertisement(Some(&datatype_selector)).await
+                {
+                    Ok(Some(advertisement)) => {
+                        Some((Ok(advertisement), (scan, datatype_selector)))
+                    }
+                    Ok(None) => None,
+                    Err(err) => Some((Err(err), (scan, datatype_selector))),
+                }
+            },
+        )))
+    }
+
+    async fn next_advertisement_batch(
+        &mut self,
+        datatype_selector: Option<&Vec<BleDataTypeId>>,
+    ) -> Result<Vec<BleAdvertisement>, BluetoothError> {
+        let first = self.next_advertisement(datatype_selector).await?;
+        let mut batch = vec![first];
+
+        if let Some(listener) = &mut self.listener {
+            // Take the advertisements already received, without waiting.
+            while let Some(event) = listener.receiver.try_recv() {
+                match event {
+                    WatcherEvent::Received(received_at, event_args) => {
+                        if let Some(advertisement) = listener.deliver(
+                            received_at,
+                            &event_args,
+                            datatype_selector,
+                        )? {
+                            batch.push(advertisement);
+                        }
+                    }
+                    WatcherEvent::Stopped(err) => {
+                        // Deliver the batch first, and report the
+                        // interruption with the next call.
+                        listener.pending_stop = Some(err);
+                        break;
+                    }
+                }
+            }
+        }
+
+        Ok(batch)
+    }
+
+    fn dropped_advertisements(&self) -> u64 {
+        self.listener
+            .as_ref()
+            .map_or(0, |listener| listener.receiver.dropped())
+    }
+
+    async fn watch_state(
+        &mut self,
+    ) -> Result<AdapterStateStream, BluetoothError> {
+        self.stop_watching_state()?;
+
+        let radio = self.inner.GetRadioAsync()?.await?;
+        let state = AdapterState::from(radio.State()?);
+
+        // `futures::channel::mpsc` is like `std::sync::mpsc` but `impl Stream`.
+        let (mut sender, receiver) = futures::channel::mpsc::channel(16);
+        sender.try_send(state).map_err(|err| {
+            BluetoothError::Internal(format!(
+                "failed to send the initial adapter state: {}",
+                err
+            ))
+        })?;
+        let token =
+            radio.StateChanged(&state_changed_handler(sender, state))?;
+        self.state_watch = Some((radio, token));
+
+        Ok(AdapterStateStream::new(receiver))
+    }
+}
+
+impl BleAdapter {
+    /// Start a watcher, whose advertisements are received by the returned
+    /// listener.
+    fn listen(
+        &self,
+        filter: Option<ScanFilter>,
+        settings: ScanSettings,
+    ) -> Result<AdvListener, BluetoothError> {
         let watcher = BluetoothLEAdvertisementWatcher::new()?;
-        match watcher.SetScanningMode(BluetoothLEScanningMode::Active) {
+        let mode = BluetoothLEScanningMode::from(settings.mode());
+        match watcher.SetScanningMode(mode) {
             Ok(_) => (),
             Err(err) => {
-                warn!("Failed to turn on active scanning. Error: {}", err)
+                warn!("Failed to set the scanning mode. Error: {}", err)
             }
         };
 
+        if let Some(signal_strength_filter) = signal_strength_filter(&settings)?
+        {
+            watcher.SetSignalStrengthFilter(&signal_strength_filter)?;
+        }
+
         if self.inner.IsExtendedAdvertisingSupported()? {
             watcher.SetAllowExtendedAdvertisements(true)?;
         }
 
-        // `futures::channel::mpsc` is like `std::sync::mpsc` but `impl Stream`.
-        let (sender, receiver) = futures::channel::mpsc::channel(16);
-        let sender = Arc::new(std::sync::Mutex::new(sender));
+        let filter = filter.unwrap_or_default();
+        watcher.SetAdvertisementFilter(&advertisement_filter(&filter)?)?;
 
-        // `received_handler` closure holds non-owning channel reference, to
-        // ensure `stopped_handler` can close the channel when
+        let (sender, receiver) = scan_queue(&settings);
+        let sender = Arc::new(sender);
+
+        // `received_handler` closure holds non-owning queue reference, to
+        // ensure `stopped_handler` can close the queue when
         // `received_handler` is done.
         let weak_sender = Arc::downgrade(&sender);
         let received_handler = TypedEventHandler::new(
@@ -125,16 +517,10 @@ impl api::BleAdapter for BleAdapter {
                 if watcher.is_some() {
                     if let Some(event_args) = event_args {
                         if let Some(sender) = weak_sender.upgrade() {
-                            match sender
-                                .lock()
-                                .unwrap()
-                                .try_send(event_args.clone())
-                            {
-                                Ok(_) => (),
-                                Err(err) => {
-                                    error!("Error while handling Received event: {:?}", err)
-                                }
-                            }
+                            sender.push(WatcherEvent::Received(
+                                Instant::now(),
+                                event_args.clone(),
+                            ));
                         }
                     }
                 }
@@ -143,81 +529,375 @@ impl api::BleAdapter for BleAdapter {
             },
         );
 
-        // `stopped_handler` closure owns channel reference, can close channel.
+        // `stopped_handler` closure owns queue reference, can close the queue.
         let mut sender = Some(sender);
         let stopped_handler = TypedEventHandler::new(
             // Move `sender` into closure.
             move |_watcher,
-                  _event_args: &Option<
+                  event_args: &Option<
                 BluetoothLEAdvertisementWatcherStoppedEventArgs,
             >| {
-                // Drop `sender`, closing the channel.
-                let _sender = sender.take();
-                info!("Watcher stopped receiving BLE advertisements.");
+                let err = match event_args {
+                    Some(event_args) => event_args.Error()?,
+                    None => WindowsBluetoothError::Success,
+                };
+
+                if err == WindowsBluetoothError::Success {
+                    // Drop `sender`, closing the queue.
+                    let _sender = sender.take();
+                    info!("Watcher stopped receiving BLE advertisements.");
+                } else if let Some(sender) = &sender {
+                    // Keep the queue open for `next_advertisement()` to
+                    // restart the watcher. No advertisement is received until
+                    // then, so the stop is queued even if the queue is full.
+                    warn!("Watcher stopped with error {}.", err.0);
+                    sender.push_unbounded(WatcherEvent::Stopped(err));
+                }
+
                 Ok(())
             },
         );
 
         watcher.Received(&received_handler)?;
         watcher.Stopped(&stopped_handler)?;
+        retry_blocking(&self.retry_policy, is_transient, || watcher.Start())?;
+
+        Ok(AdvListener {
+            watcher,
+            receiver,
+            filter,
+            retry_policy: self.retry_policy,
+            next_sequence_number: 0,
+            pending_stop: None,
+            interruptions: 0,
+            abort_reason: None,
+        })
+    }
+
+    /// Remove the StateChanged handler registered by `watch_state()`, if
+    /// any. This drops its sender, ending the stream.
+    fn stop_watching_state(&mut self) -> Result<(), BluetoothError> {
+        if let Some((radio, token)) = self.state_watch.take() {
+            radio.RemoveStateChanged(token)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for BleAdapter {
+    fn drop(&mut self) {
+        if let Err(err) = self.stop_watching_state() {
+            warn!("Failed to stop watching the adapter state: {}", err);
+        }
+    }
+}
+
+/// Struct holding the device watcher running a BT Classic discovery.
+struct DiscoveryListener {
+    /// Sends the information of each device found to `receiver`.
+    watcher: DeviceWatcher,
+    /// Can be polled to consume the devices found, ending once the watcher
+    /// stopped.
+    receiver: UnboundedReceiver<DeviceInformation>,
+}
+
+impl DiscoveryListener {
+    /// Stop the watcher, unless it already stopped on its own.
+    fn stop(&self) -> Result<(), BluetoothError> {
+        let status = self.watcher.Status()?;
+        if status == DeviceWatcherStatus::Started
+            || status == DeviceWatcherStatus::EnumerationCompleted
+        {
+            self.watcher.Stop()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Concrete type implementing `api::ClassicAdapter`, used for Windows BT
+/// Classic.
+pub struct ClassicAdapter {
+    listener: Option<DiscoveryListener>,
+}
+
+#[async_trait]
+impl api::ClassicAdapter for ClassicAdapter {
+    async fn default() -> Result<Self, BluetoothError> {
+        let adapter = BluetoothAdapter::GetDefaultAsync()?.await?;
+        if !adapter.IsClassicSupported()? {
+            return Err(BluetoothError::NotSupported(String::from(
+                "BR/EDR transport type",
+            )));
+        }
+
+        Ok(ClassicAdapter { listener: None })
+    }
+
+    /// Devices are found by a watcher enumerating unpaired BT Classic
+    /// association endpoints, which runs inquiry until it is stopped.
+    fn start_discovery(&mut self) -> Result<(), BluetoothError> {
+        if let Some(listener) = self.listener.take() {
+            listener.stop()?;
+        }
+
+        let properties = IIterable::<HSTRING>::try_from(Vec::new())?;
+        let watcher =
+            DeviceInformation::CreateWatcherWithKindAqsFilterAndAdditionalProperties(
+                &BluetoothDevice::GetDeviceSelectorFromPairingState(false)?,
+                &properties,
+                DeviceInformationKind::AssociationEndpoint,
+            )?;
+
+        // Unbounded, since the watcher adds each device once and a device
+        // dropped by a full channel would never be found again.
+        let (sender, receiver) = futures::channel::mpsc::unbounded();
+        let stopped_sender = sender.clone();
+
+        let added_handler = TypedEventHandler::new(
+            move |_watcher, info: &Option<DeviceInformation>| {
+                if let Some(info) = info {
+                    if let Err(err) = sender.unbounded_send(info.clone()) {
+                        error!("Error while handling Added event: {:?}", err)
+                    }
+                }
+
+                Ok(())
+            },
+        );
+        // The watcher only raises Added events once Updated and Removed
+        // handlers are registered too, although they have nothing to do.
+        let updated_handler = TypedEventHandler::new(
+            |_watcher, _update: &Option<DeviceInformationUpdate>| Ok(()),
+        );
+        let removed_handler = TypedEventHandler::new(
+            |_watcher, _update: &Option<DeviceInformationUpdate>| Ok(()),
+        );
+        let stopped_handler = TypedEventHandler::new(
+            move |_watcher, _event_args: &Option<IInspectable>| {
+                stopped_sender.close_channel();
+                info!("Watcher stopped discovering BT Classic devices.");
+                Ok(())
+            },
+        );
+
+        watcher.Added(&added_handler)?;
+        watcher.Updated(&updated_handler)?;
+        watcher.Removed(&removed_handler)?;
+        watcher.Stopped(&stopped_handler)?;
         watcher.Start()?;
 
-        self.listener = Some(AdvListener { watcher, receiver });
+        self.listener = Some(DiscoveryListener { watcher, receiver });
 
         Ok(())
     }
 
-    fn stop_scan(&mut self) -> Result<(), BluetoothError> {
+    fn stop_discovery(&mut self) -> Result<(), BluetoothError> {
         if let Some(listener) = self.listener.take() {
-            listener.watcher.Stop()?;
-            Ok(())
+            listener.stop()
         } else {
             Err(BluetoothError::FailedPrecondition(String::from(
-                "device scanning hasn't started, please call `start_scan()`",
+                "discovery hasn't started, please call `start_discovery()`",
             )))
         }
     }
 
-    async fn next_advertisement(
+    async fn next_discovered_device(
         &mut self,
-        datatype_selector: Option<&Vec<BleDataTypeId>>,
-    ) -> Result<BleAdvertisement, BluetoothError> {
-        if let Some(listener) = &mut self.listener {
-            let stream = &mut listener.receiver;
-            // We don't want the end-user to receive empty devices, so this is a
-            // loop to catch and skip trivial errors from advertisements that
-            // can't be turned into devices.
-            loop {
-                let event_args =
-                    stream.next().await.ok_or(BluetoothError::Internal(
-                        String::from("Event returned from stream is None."),
-                    ))?;
-
-                match event_args.AdvertisementType()? {
-                    BluetoothLEAdvertisementType::NonConnectableUndirected => {
-                        ()
-                    }
-                    _ => {
-                        let mut advertisement =
-                            BleAdvertisement::try_from(&event_args)?;
+    ) -> Result<DiscoveredClassicDevice, BluetoothError> {
+        let listener = self.listener.as_mut().ok_or_else(|| {
+            BluetoothError::FailedPrecondition(String::from(
+                "discovery hasn't started, please call `start_discovery()`",
+            ))
+        })?;
 
-                        if let Some(datatype_selector) = datatype_selector {
-                            advertisement
-                                .load_data(&event_args, datatype_selector)?;
-                        }
+        let info = match listener.receiver.next().await {
+            Some(info) => info,
+            None => {
+                return Err(BluetoothError::Internal(format!(
+                    "Discovery stopped with watcher status {}.",
+                    listener.watcher.Status()?.0
+                )))
+            }
+        };
 
-                        break Ok(advertisement);
-                    }
-                }
+        let device = BluetoothDevice::FromIdAsync(&info.Id()?)?.await?;
+        let name = device.Name()?.to_string_lossy();
+
+        Ok(DiscoveredClassicDevice::new(
+            ClassicAddress::from(device.BluetoothAddress()?),
+            (!name.is_empty()).then_some(name),
+            ClassOfDevice::from(device.ClassOfDevice()?.RawValue()?),
+        ))
+    }
+}
+
+impl Drop for ClassicAdapter {
+    fn drop(&mut self) {
+        if let Some(listener) = &self.listener {
+            if let Err(err) = listener.stop() {
+                warn!("Failed to stop discovery: {}", err);
             }
-        } else {
-            Err(BluetoothError::FailedPrecondition(String::from(
-                "device scanning hasn't started, please call `start_scan()`",
-            )))
         }
     }
 }
 
+/// Convert a received advertisement event. Non-connectable advertisements
+/// and those sent from an address not matching `filter` are skipped.
+fn received_advertisement(
+    event_args: &BluetoothLEAdvertisementReceivedEventArgs,
+    filter: &ScanFilter,
+    datatype_selector: Option<&Vec<BleDataTypeId>>,
+) -> Result<Option<BleAdvertisement>, BluetoothError> {
+    if event_args.AdvertisementType()?
+        == BluetoothLEAdvertisementType::NonConnectableUndirected
+    {
+        return Ok(None);
+    }
+
+    let mut advertisement = BleAdvertisement::try_from(event_args)?;
+    if !filter.matches_address(advertisement.address()) {
+        return Ok(None);
+    }
+
+    // The selected data types are parsed out of the rebuilt raw payload, as
+    // on the other platforms.
+    if let Some(datatype_selector) = datatype_selector {
+        advertisement.load_raw_data(datatype_selector)?;
+    }
+
+    Ok(Some(advertisement))
+}
+
+/// Build the watcher filter applying the service data and manufacturer
+/// criteria of `filter`. Windows can't filter by address, so that criterion
+/// is checked by `next_advertisement()` instead.
+fn advertisement_filter(
+    filter: &ScanFilter,
+) -> Result<BluetoothLEAdvertisementFilter, BluetoothError> {
+    let advertisement_filter = BluetoothLEAdvertisementFilter::new()?;
+
+    if let Some(uuid) = filter.service_data_uuid() {
+        // Service data sections start with the little-endian UUID.
+        advertisement_filter.BytePatterns()?.Append(
+            &BluetoothLEAdvertisementBytePattern::Create(
+                BleDataTypeId::ServiceData16BitUuid as u8,
+                0,
+                &write_buffer(&uuid.to_le_bytes())?,
+            )?,
+        )?;
+    }
+
+    if let Some(company_id) = filter.manufacturer_id() {
+        let manufacturer_data = BluetoothLEManufacturerData::new()?;
+        manufacturer_data.SetCompanyId(company_id)?;
+        advertisement_filter
+            .Advertisement()?
+            .ManufacturerData()?
+            .Append(&manufacturer_data)?;
+    }
+
+    Ok(advertisement_filter)
+}
+
+/// Build the signal strength filter applying the report delay, signal
+/// strength thresholds and out-of-range timeout of `settings`, if any of them
+/// is set.
+fn signal_strength_filter(
+    settings: &ScanSettings,
+) -> Result<Option<BluetoothSignalStrengthFilter>, BluetoothError> {
+    if settings.report_delay().is_none()
+        && settings.in_range_threshold().is_none()
+        && settings.out_of_range_timeout().is_none()
+    {
+        return Ok(None);
+    }
+
+    let signal_strength_filter = BluetoothSignalStrengthFilter::new()?;
+    if let Some(delay) = settings.report_delay() {
+        signal_strength_filter.SetSamplingInterval(&time_span(delay)?)?;
+    }
+    if let Some(threshold) = settings.in_range_threshold() {
+        signal_strength_filter.SetInRangeThresholdInDBm(
+            &PropertyValue::CreateInt16(threshold)?
+                .cast::<IReference<i16>>()?,
+        )?;
+    }
+    if let Some(threshold) = settings.out_of_range_threshold() {
+        signal_strength_filter.SetOutOfRangeThresholdInDBm(
+            &PropertyValue::CreateInt16(threshold)?
+                .cast::<IReference<i16>>()?,
+        )?;
+    }
+    if let Some(timeout) = settings.out_of_range_timeout() {
+        signal_strength_filter.SetOutOfRangeTimeout(&time_span(timeout)?)?;
+    }
+
+    Ok(Some(signal_strength_filter))
+}
+
+/// Box `duration` for the properties of a signal strength filter.
+fn time_span(
+    duration: Duration,
+) -> Result<IReference<TimeSpan>, BluetoothError> {
+    Ok(PropertyValue::CreateTimeSpan(TimeSpan::from(duration))?
+        .cast::<IReference<TimeSpan>>()?)
+}
+
+// https://learn.microsoft.com/en-us/uwp/api/windows.devices.radios.radiostate?view=winrt-22621
+impl From<RadioState> for AdapterState {
+    fn from(state: RadioState) -> Self {
+        match state {
+            RadioState::On => AdapterState::PoweredOn,
+            RadioState::Off => AdapterState::PoweredOff,
+            _ => AdapterState::Unavailable,
+        }
+    }
+}
+
+// https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.advertisement.bluetoothlescanningmode?view=winrt-22621
+impl From<ScanMode> for BluetoothLEScanningMode {
+    fn from(mode: ScanMode) -> Self {
+        match mode {
+            ScanMode::Active => BluetoothLEScanningMode::Active,
+            ScanMode::Passive => BluetoothLEScanningMode::Passive,
+        }
+    }
+}
+
+/// Create a handler for StateChanged events sending the new adapter states to
+/// `sender`. Windows may raise the event without a change of state, so only
+/// states differing from `state` are sent.
+fn state_changed_handler(
+    mut sender: Sender<AdapterState>,
+    mut state: AdapterState,
+) -> TypedEventHandler<Radio, IInspectable> {
+    TypedEventHandler::new(
+        // Move `sender` into closure.
+        move |radio: &Option<Radio>, _event_args: &Option<IInspectable>| {
+            if let Some(radio) = radio {
+                let new_state = AdapterState::from(radio.State()?);
+                if new_state != state {
+                    state = new_state;
+                    match sender.try_send(new_state) {
+                        Ok(_) => (),
+                        Err(err) if err.is_disconnected() => (),
+                        Err(err) => {
+                            error!(
+                                "Error while handling StateChanged event: {:?}",
+                                err
+                            )
+                        }
+                    }
+                }
+            }
+
+            Ok(())
+        },
+    )
+}
+
 mod tests {
     // TODO b/288592509 unit tests
 }
@@ -12,26 +12,18 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use bytes::Bytes;
 use windows::{
     // Struct that receives Bluetooth Low Energy (LE) advertisements.
     // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.advertisement.bluetoothleadvertisementwatcher?view=winrt-22621
     Devices::Bluetooth::Advertisement::{
-        BluetoothLEAdvertisementDataSection,
-        BluetoothLEAdvertisementReceivedEventArgs,
+        BluetoothLEAdvertisement, BluetoothLEAdvertisementReceivedEventArgs,
     },
-
-    // Struct representing an immutable view into a vector.
-    // https://learn.microsoft.com/en-us/uwp/api/windows.foundation.collections.ivectorview-1?view=winrt-22621
-    Foundation::Collections::IVectorView,
-
-    // Struct for reading data from a Windows stream, like an IVectorView.
-    // https://learn.microsoft.com/en-us/uwp/api/windows.storage.streams.datareader?view=winrt-22621
-    Storage::Streams::DataReader,
 };
 
+use super::gatt::read_buffer;
 use crate::common::{
-    BleAddress, BleAddressKind, BleAdvertisement, BleDataTypeId,
-    BluetoothError, ServiceData,
+    AdStructure, BleAddress, BleAddressKind, BleAdvertisement, BluetoothError,
 };
 
 impl TryFrom<&BluetoothLEAdvertisementReceivedEventArgs> for BleAdvertisement {
@@ -52,64 +44,25 @@ impl TryFrom<&BluetoothLEAdvertisementReceivedEventArgs> for BleAdvertisement {
             Err(_) => None,
         };
 
-        Ok(BleAdvertisement::new(addr, rssi, tx_power))
-    }
-}
-
-impl BleAdvertisement {
-    /// Load data of selected data types into self by parsing the raw Windows
-    /// advertisement.
-    /// See: Supplement to the Bluetooth Core Specification Part A, Section 1.
-    pub(crate) fn load_data(
-        &mut self,
-        adv: &BluetoothLEAdvertisementReceivedEventArgs,
-        datatype_ids: &[BleDataTypeId],
-    ) -> Result<(), BluetoothError> {
-        let adv = adv.Advertisement()?;
+        let mut advertisement = BleAdvertisement::new(addr, rssi, tx_power);
+        advertisement
+            .set_raw_data(Bytes::from(raw_data(&adv.Advertisement()?)?));
 
-        for datatype_id in datatype_ids {
-            // Note `raw_data_sections` is `!Send` and `!Sync`. This means
-            // processing must occur in a synchronous environment. The compiler
-            // will complain if parsing is done in an async function.
-            let raw_data_sections =
-                adv.GetSectionsByType((*datatype_id) as u8)?;
-            match datatype_id {
-                BleDataTypeId::ServiceData16BitUuid => {
-                    let service_data =
-                        parse_service_data_16bit_uuid(raw_data_sections)?;
-                    self.set_service_data_16bit_uuid(service_data)
-                }
-            };
-        }
-
-        Ok(())
+        Ok(advertisement)
     }
 }
 
-/// Parse the advertisement's service data.
-/// Further Reading:
-/// * `BleMedium::AdvertisementReceivedHandler` under
-/// github.com/google/nearby/internal/platform/implementation/windows_ble/ble_medium.cc.
-/// * Bluetooth Supplement to the Core Specification, Part A, Section 1.11.
-/// * go/fast_pair_windows_data_parse.
-#[inline]
-fn parse_service_data_16bit_uuid(
-    raw_data_sections: IVectorView<BluetoothLEAdvertisementDataSection>,
-) -> Result<Vec<ServiceData<u16>>, BluetoothError> {
-    let mut data_vec = Vec::new();
-
-    for raw_data in raw_data_sections {
-        let data_reader = DataReader::FromBuffer(&raw_data.Data()?)?;
-        let uuid = data_reader.ReadUInt16()?;
-
-        let unconsumed_buffer_len =
-            data_reader.UnconsumedBufferLength()? as usize;
-
-        let mut data = vec![0u8; unconsumed_buffer_len];
-        data_reader.ReadBytes(&mut data)?;
-
-        data_vec.push(ServiceData::new(uuid, data));
+/// Rebuild the raw payload of an advertisement from its data sections, each
+/// holding an AD structure.
+fn raw_data(adv: &BluetoothLEAdvertisement) -> Result<Vec<u8>, BluetoothError> {
+    let mut raw_data = Vec::new();
+    for section in adv.DataSections()? {
+        let structure = AdStructure::new(
+            section.DataType()?,
+            read_buffer(&section.Data()?)?,
+        );
+        raw_data.extend(structure.to_bytes()?);
     }
 
-    Ok(data_vec)
+    Ok(raw_data)
 }
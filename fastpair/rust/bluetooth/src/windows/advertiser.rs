@@ -0,0 +1,207 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use async_trait::async_trait;
+use tracing::warn;
+use windows::{
+    core::ComInterface,
+    Devices::Bluetooth::{
+        Advertisement::{
+            // Struct representing the data sections of an advertisement.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.advertisement.bluetoothleadvertisement?view=winrt-22621
+            BluetoothLEAdvertisement,
+
+            // Struct representing a raw data section of an advertisement.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.advertisement.bluetoothleadvertisementdatasection?view=winrt-22621
+            BluetoothLEAdvertisementDataSection,
+
+            // Struct that sends Bluetooth Low Energy (LE) advertisements.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.advertisement.bluetoothleadvertisementpublisher?view=winrt-22621
+            BluetoothLEAdvertisementPublisher,
+
+            // Enum describing the state of a publisher.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.advertisement.bluetoothleadvertisementpublisherstatus?view=winrt-22621
+            BluetoothLEAdvertisementPublisherStatus,
+
+            // Provides data for a StatusChanged event on a publisher.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.advertisement.bluetoothleadvertisementpublisherstatuschangedeventargs?view=winrt-22621
+            BluetoothLEAdvertisementPublisherStatusChangedEventArgs,
+
+            // Struct representing a manufacturer specific data section.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.advertisement.bluetoothlemanufacturerdata?view=winrt-22621
+            BluetoothLEManufacturerData,
+        },
+        // Struct for obtaining global constant information about a computer's
+        // Bluetooth adapter.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.bluetoothadapter?view=winrt-22621
+        BluetoothAdapter,
+    },
+    Foundation::TypedEventHandler,
+
+    // Boxed TX power, used as the preferred power of a publisher.
+    // https://learn.microsoft.com/en-us/uwp/api/windows.foundation.propertyvalue?view=winrt-22621
+    // https://learn.microsoft.com/en-us/uwp/api/windows.foundation.ireference-1?view=winrt-22621
+    Foundation::{IReference, PropertyValue},
+};
+
+use super::{error::check_bluetooth_error, gatt::write_buffer};
+use crate::{
+    api,
+    common::{
+        AdvertisementPayload, AdvertisingParameters, BleDataTypeId,
+        BluetoothError, Phy,
+    },
+};
+
+/// Concrete type implementing `api::BleAdvertiser`, used for Windows BLE.
+pub struct BleAdvertiser {
+    /// Publisher broadcasting the current payload, if advertising.
+    publisher: Option<BluetoothLEAdvertisementPublisher>,
+}
+
+#[async_trait]
+impl api::BleAdvertiser for BleAdvertiser {
+    async fn default() -> Result<Self, BluetoothError> {
+        let adapter = BluetoothAdapter::GetDefaultAsync()?.await?;
+
+        if !adapter.IsLowEnergySupported()? {
+            return Err(BluetoothError::NotSupported(String::from(
+                "LE transport type",
+            )));
+        }
+
+        Ok(BleAdvertiser { publisher: None })
+    }
+
+    /// Windows picks the advertising interval itself, so the interval hint
+    /// is ignored. The TX power can only be advertised in the header of
+    /// extended advertisements.
+    fn start_advertising_with_parameters(
+        &mut self,
+        payload: AdvertisementPayload,
+        parameters: AdvertisingParameters,
+    ) -> Result<(), BluetoothError> {
+        parameters.validate()?;
+        if parameters.primary_phy() != Phy::Le1M
+            || parameters.secondary_phy() != Phy::Le1M
+        {
+            return Err(BluetoothError::NotSupported(String::from(
+                "advertising PHY selection on Windows",
+            )));
+        }
+        if parameters.tx_power_advertised() && !parameters.extended() {
+            return Err(BluetoothError::NotSupported(String::from(
+                "advertising the TX power in legacy advertisements on Windows",
+            )));
+        }
+
+        // A publisher's advertisement can't be changed once started, so
+        // replace the whole publisher.
+        self.stop_advertising()?;
+
+        let publisher = BluetoothLEAdvertisementPublisher::Create(
+            &new_advertisement(&payload)?,
+        )?;
+        if parameters.extended() {
+            publisher.SetUseExtendedAdvertisement(true)?;
+            publisher.SetIsAnonymous(parameters.anonymous())?;
+            publisher.SetIncludeTransmitPowerLevel(
+                parameters.tx_power_advertised(),
+            )?;
+        }
+        if let Some(tx_power) = parameters.tx_power() {
+            publisher.SetPreferredTransmitPowerLevelInDBm(
+                &PropertyValue::CreateInt16(tx_power)?
+                    .cast::<IReference<i16>>()?,
+            )?;
+        }
+        publisher.StatusChanged(&status_changed_handler())?;
+        publisher.Start()?;
+        self.publisher = Some(publisher);
+
+        Ok(())
+    }
+
+    fn stop_advertising(&mut self) -> Result<(), BluetoothError> {
+        if let Some(publisher) = self.publisher.take() {
+            publisher.Stop()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for BleAdvertiser {
+    fn drop(&mut self) {
+        if let Err(err) = api::BleAdvertiser::stop_advertising(self) {
+            warn!("Failed to stop advertising: {}", err);
+        }
+    }
+}
+
+/// Build the Windows advertisement holding the payload's data sections.
+fn new_advertisement(
+    payload: &AdvertisementPayload,
+) -> Result<BluetoothLEAdvertisement, BluetoothError> {
+    let advertisement = BluetoothLEAdvertisement::new()?;
+
+    let data_sections = advertisement.DataSections()?;
+    for service_data in payload.service_data_16bit_uuid() {
+        data_sections.Append(&BluetoothLEAdvertisementDataSection::Create(
+            BleDataTypeId::ServiceData16BitUuid as u8,
+            &write_buffer(&service_data.to_16bit_uuid_bytes()?)?,
+        )?)?;
+    }
+
+    let manufacturer_data = advertisement.ManufacturerData()?;
+    for data in payload.manufacturer_data() {
+        manufacturer_data.Append(&BluetoothLEManufacturerData::Create(
+            data.company_id(),
+            &write_buffer(data.data())?,
+        )?)?;
+    }
+
+    Ok(advertisement)
+}
+
+/// Create a handler for StatusChanged events logging why advertising was
+/// aborted, since `Start()` returns before the radio is configured.
+fn status_changed_handler() -> TypedEventHandler<
+    BluetoothLEAdvertisementPublisher,
+    BluetoothLEAdvertisementPublisherStatusChangedEventArgs,
+> {
+    TypedEventHandler::new(
+        |_publisher: &Option<BluetoothLEAdvertisementPublisher>,
+         event_args: &Option<
+            BluetoothLEAdvertisementPublisherStatusChangedEventArgs,
+        >| {
+            if let Some(event_args) = event_args {
+                if event_args.Status()?
+                    == BluetoothLEAdvertisementPublisherStatus::Aborted
+                {
+                    match check_bluetooth_error(event_args.Error()?) {
+                        Ok(()) => warn!("Advertising was aborted."),
+                        Err(err) => warn!("Advertising was aborted: {}", err),
+                    }
+                }
+            }
+
+            Ok(())
+        },
+    )
+}
+
+mod tests {
+    // TODO b/288592509 unit tests
+}
@@ -12,69 +12,145 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::sync::Arc;
+
 use async_trait::async_trait;
-use tracing::{info, warn};
+use futures::channel::mpsc::Sender;
+use tracing::{error, info, warn};
 use windows::{
+    core::{ComInterface, IInspectable, HSTRING},
     Devices::{
         Bluetooth::{
             // Tuple struct describing the type of address (public, random, unspecified).
             // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.bluetoothaddresstype?view=winrt-22621
             BluetoothAddressType,
-            
+
+            // Enum describing whether the system may answer from its cache rather
+            // than querying the device.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.bluetoothcachemode?view=winrt-22621
+            BluetoothCacheMode,
+
+            // Enum describing whether a device is connected.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.bluetoothconnectionstatus?view=winrt-22621
+            BluetoothConnectionStatus,
+
             // Struct for interacting with a discovered BT Classic device.
             // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.bluetoothdevice?view=winrt-22621
             BluetoothDevice,
-            
+
             // Struct for interacting with a discovered BLE device.
             // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.bluetoothledevice?view=winrt-22621
             BluetoothLEDevice,
+
+            // Struct representing an RFCOMM service of a BT Classic device.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.rfcomm.rfcommdeviceservice?view=winrt-22621
+            Rfcomm::RfcommDeviceService,
         },
         Enumeration::{
+            // Struct describing a device, along with the properties
+            // requested when creating it.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.enumeration.deviceinformation?view=winrt-22621
+            DeviceInformation,
+
             // Struct for custom pairing with a device.
             // https://learn.microsoft.com/en-us/uwp/api/windows.devices.enumeration.deviceinformationcustompairing?view=winrt-22621
             DeviceInformationCustomPairing,
-            
+
+            // Tuple struct to indicate the kind of device an ID refers to.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.enumeration.deviceinformationkind?view=winrt-22621
+            DeviceInformationKind,
+
             // Tuple struct to indicate the kinds of pairing supported by the application.
             // https://learn.microsoft.com/en-us/uwp/api/windows.devices.enumeration.devicepairingkinds?view=winrt-22621
             DevicePairingKinds,
-            
+
+            // Enum describing the protection level required or used by pairing.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.enumeration.devicepairingprotectionlevel?view=winrt-22621
+            DevicePairingProtectionLevel,
+
             // Struct for retrieving data about a PairingRequested event.
             // https://learn.microsoft.com/en-us/uwp/api/windows.devices.enumeration.devicepairingrequestedeventargs?view=winrt-22621
             DevicePairingRequestedEventArgs,
         },
     },
+    // Iterable collection, used to list the device properties to retrieve,
+    // and immutable view into a map, holding raw SDP attributes.
+    // https://learn.microsoft.com/en-us/uwp/api/windows.foundation.collections.iiterable-1?view=winrt-22621
+    // https://learn.microsoft.com/en-us/uwp/api/windows.foundation.collections.imapview-2?view=winrt-22621
+    Foundation::Collections::{IIterable, IMapView},
+
     // Wraps a closure for handling events associated with a struct
     // (e.g. PairingRequested event in `DeviceInformationCustomPairing`).
     // https://learn.microsoft.com/en-us/uwp/api/windows.foundation.typedeventhandler-2?view=winrt-22621
-    Foundation::TypedEventHandler,
+    Foundation::{EventRegistrationToken, TypedEventHandler},
+
+    // Asynchronous operation returning a result, and a boxed value.
+    // https://learn.microsoft.com/en-us/uwp/api/windows.foundation.iasyncoperation-1?view=winrt-22621
+    // https://learn.microsoft.com/en-us/uwp/api/windows.foundation.ireference-1?view=winrt-22621
+    Foundation::{IAsyncOperation, IReference},
+
+    // Windows buffer, holding the value of an SDP attribute.
+    // https://learn.microsoft.com/en-us/uwp/api/windows.storage.streams.ibuffer?view=winrt-22621
+    Storage::Streams::IBuffer,
 };
 
-use crate::{api, common::{BleAddress, ClassicAddress, BluetoothError, PairingResult}};
+use super::{
+    error::{check_bluetooth_error, is_transient},
+    gatt::read_buffer,
+    rfcomm::connect_rfcomm,
+};
+
+use crate::{
+    api::{self, PairingDelegate},
+    common::{
+        parse_rfcomm_channel, retry, BleAddress, BleAddressKind,
+        BluetoothError, ClassicAddress, ConnectionStatus,
+        ConnectionStatusStream, DeviceAddress, PairedDevice, PairingResult,
+        ProtectionLevel, RetryPolicy, RfcommSocket, SdpRecord, Uuid,
+        PROTOCOL_DESCRIPTOR_LIST_ATTRIBUTE_ID,
+    },
+};
 
 /// Concrete type implementing `Device`, used for Windows BLE.
 pub struct BleDevice {
     inner: BluetoothLEDevice,
     addr: BleAddress,
+    /// Registration of the ConnectionStatusChanged handler added by
+    /// `watch_connection_status()`.
+    status_watch: Option<EventRegistrationToken>,
 }
 
 /// Concrete type implementing `Device`, used for Windows Bluetooth Classic.
 pub struct ClassicDevice {
     inner: BluetoothDevice,
     addr: ClassicAddress,
+    /// Policy for retrying operations failing transiently, e.g. querying the
+    /// services while the device is busy.
+    retry_policy: RetryPolicy,
 }
 
 #[async_trait]
 impl api::BleDevice for BleDevice {
-    async fn new(addr: BleAddress) -> Result<Self, BluetoothError> {
+    async fn new_with_retry_policy(
+        addr: BleAddress,
+        retry_policy: RetryPolicy,
+    ) -> Result<Self, BluetoothError> {
         let kind = BluetoothAddressType::from(addr.get_kind());
         let raw_addr = u64::from(addr);
 
-        let inner = BluetoothLEDevice::FromBluetoothAddressWithBluetoothAddressTypeAsync(
-            raw_addr, kind,
-        )?
+        let inner = retry(&retry_policy, is_transient, || async {
+            BluetoothLEDevice::FromBluetoothAddressWithBluetoothAddressTypeAsync(
+                raw_addr, kind,
+            )?
+            .await
+        })
         .await?;
 
-        Ok(BleDevice { inner, addr })
+        Ok(BleDevice {
+            inner,
+            addr,
+            status_watch: None,
+        })
     }
 
     fn name(&self) -> Result<String, BluetoothError> {
@@ -84,19 +160,288 @@ impl api::BleDevice for BleDevice {
     fn address(&self) -> BleAddress {
         self.addr
     }
+
+    async fn rssi(&self) -> Result<i16, BluetoothError> {
+        signal_strength(self.inner.DeviceId()?).await
+    }
+
+    async fn watch_connection_status(
+        &mut self,
+    ) -> Result<ConnectionStatusStream, BluetoothError> {
+        self.stop_watching_connection_status()?;
+
+        let status = ConnectionStatus::from(self.inner.ConnectionStatus()?);
+
+        // `futures::channel::mpsc` is like `std::sync::mpsc` but `impl Stream`.
+        let (mut sender, receiver) = futures::channel::mpsc::channel(16);
+        sender.try_send(status).map_err(|err| {
+            BluetoothError::Internal(format!(
+                "failed to send the initial connection status: {}",
+                err
+            ))
+        })?;
+        let token = self.inner.ConnectionStatusChanged(
+            &connection_status_changed_handler(sender, status),
+        )?;
+        self.status_watch = Some(token);
+
+        Ok(ConnectionStatusStream::new(receiver))
+    }
+
+    async fn pair_with_protection_level(
+        &self,
+        protection_level: ProtectionLevel,
+    ) -> Result<PairingResult, BluetoothError> {
+        pair(self.inner.DeviceInformation()?, None, protection_level).await
+    }
+
+    async fn pair_with_delegate_and_protection_level<D: PairingDelegate>(
+        &self,
+        delegate: D,
+        protection_level: ProtectionLevel,
+    ) -> Result<PairingResult, BluetoothError> {
+        pair(
+            self.inner.DeviceInformation()?,
+            Some(Arc::new(delegate)),
+            protection_level,
+        )
+        .await
+    }
+
+    fn close(mut self) -> Result<(), BluetoothError> {
+        self.stop_watching_connection_status()?;
+        Ok(self.inner.Close()?)
+    }
+}
+
+impl BleDevice {
+    /// Remove the ConnectionStatusChanged handler registered by
+    /// `watch_connection_status()`, if any. This drops its sender, ending the
+    /// stream.
+    fn stop_watching_connection_status(
+        &mut self,
+    ) -> Result<(), BluetoothError> {
+        if let Some(token) = self.status_watch.take() {
+            self.inner.RemoveConnectionStatusChanged(token)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for BleDevice {
+    fn drop(&mut self) {
+        if let Err(err) = self.stop_watching_connection_status() {
+            warn!("Failed to stop watching the connection status: {}", err);
+        }
+    }
+}
+
+/// Retrieve the RFCOMM channel from the raw SDP attributes of a service, keyed
+/// by attribute ID.
+fn rfcomm_channel(
+    attributes: &IMapView<u32, IBuffer>,
+) -> Result<Option<u8>, BluetoothError> {
+    let attribute_id = u32::from(PROTOCOL_DESCRIPTOR_LIST_ATTRIBUTE_ID);
+    if !attributes.HasKey(attribute_id)? {
+        return Ok(None);
+    }
+
+    let attribute = read_buffer(&attributes.Lookup(attribute_id)?)?;
+    Ok(parse_rfcomm_channel(&attribute))
+}
+
+/// Device property holding the last signal strength measured by the system,
+/// in dBm.
+/// https://learn.microsoft.com/en-us/windows/win32/properties/props-system-devices-aep-signalstrength
+const SIGNAL_STRENGTH_PROPERTY: &str = "System.Devices.Aep.SignalStrength";
+
+/// Retrieve the signal strength of the device with the given ID. Bluetooth
+/// device IDs are association endpoint IDs, which the property belongs to.
+async fn signal_strength(device_id: HSTRING) -> Result<i16, BluetoothError> {
+    let info = start_device_information(&device_id)?.await?;
+
+    read_signal_strength(&info)
+}
+
+/// Start retrieving the device information holding the signal strength.
+/// Windows collections are `!Send`, so the property list is created outside
+/// of the async function awaiting the operation.
+fn start_device_information(
+    device_id: &HSTRING,
+) -> Result<IAsyncOperation<DeviceInformation>, BluetoothError> {
+    let properties = IIterable::<HSTRING>::try_from(vec![HSTRING::from(
+        SIGNAL_STRENGTH_PROPERTY,
+    )])?;
+
+    Ok(
+        DeviceInformation::CreateFromIdAsyncWithKindAndAdditionalProperties(
+            device_id,
+            &properties,
+            DeviceInformationKind::AssociationEndpoint,
+        )?,
+    )
+}
+
+/// Read the signal strength property, which is missing when the system has no
+/// recent measurement, e.g. because the device is out of range.
+fn read_signal_strength(
+    info: &DeviceInformation,
+) -> Result<i16, BluetoothError> {
+    let value = info
+        .Properties()?
+        .Lookup(&HSTRING::from(SIGNAL_STRENGTH_PROPERTY))
+        .map_err(|_| {
+            BluetoothError::Unreachable(String::from(
+                "no signal strength measured",
+            ))
+        })?;
+    let rssi = value.cast::<IReference<i32>>()?.Value()?;
+
+    i16::try_from(rssi).map_err(|_| {
+        BluetoothError::Internal(format!("invalid signal strength {}", rssi))
+    })
+}
+
+/// List the paired BT Classic devices, then the paired BLE devices. Windows
+/// pairs dual-mode devices over each transport separately, so they may be
+/// listed twice.
+pub async fn list_paired_devices() -> Result<Vec<PairedDevice>, BluetoothError>
+{
+    let mut devices = Vec::new();
+
+    let selector = BluetoothDevice::GetDeviceSelectorFromPairingState(true)?;
+    for info in find_all_devices(selector).await? {
+        let device = BluetoothDevice::FromIdAsync(&info.Id()?)?.await?;
+        let addr = ClassicAddress::from(device.BluetoothAddress()?);
+        devices.push(PairedDevice::new(
+            DeviceAddress::Classic(addr),
+            device_information_name(&info)?,
+        ));
+    }
+
+    let selector = BluetoothLEDevice::GetDeviceSelectorFromPairingState(true)?;
+    for info in find_all_devices(selector).await? {
+        let device = BluetoothLEDevice::FromIdAsync(&info.Id()?)?.await?;
+        let kind = BleAddressKind::try_from(device.BluetoothAddressType()?)?;
+        let addr = BleAddress::new(device.BluetoothAddress()?, kind);
+        devices.push(PairedDevice::new(
+            DeviceAddress::Ble(addr),
+            device_information_name(&info)?,
+        ));
+    }
+
+    Ok(devices)
+}
+
+/// Find the devices matching an AQS selector. The returned collection is
+/// only iterated through a `!Send` iterator, so it's collected right away.
+async fn find_all_devices(
+    selector: HSTRING,
+) -> Result<Vec<DeviceInformation>, BluetoothError> {
+    let collection =
+        DeviceInformation::FindAllAsyncAqsFilter(&selector)?.await?;
+
+    Ok(collection.into_iter().collect())
+}
+
+/// Retrieve the name of a device, which is empty if it isn't known.
+fn device_information_name(
+    info: &DeviceInformation,
+) -> Result<Option<String>, BluetoothError> {
+    let name = info.Name()?.to_string_lossy();
+
+    Ok((!name.is_empty()).then_some(name))
+}
+
+// https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.bluetoothconnectionstatus?view=winrt-22621
+impl From<BluetoothConnectionStatus> for ConnectionStatus {
+    fn from(status: BluetoothConnectionStatus) -> Self {
+        match status {
+            BluetoothConnectionStatus::Connected => ConnectionStatus::Connected,
+            _ => ConnectionStatus::Disconnected,
+        }
+    }
+}
+
+// https://learn.microsoft.com/en-us/uwp/api/windows.devices.enumeration.devicepairingprotectionlevel?view=winrt-22621
+impl From<ProtectionLevel> for DevicePairingProtectionLevel {
+    fn from(level: ProtectionLevel) -> Self {
+        match level {
+            ProtectionLevel::None => DevicePairingProtectionLevel::None,
+            ProtectionLevel::Encryption => {
+                DevicePairingProtectionLevel::Encryption
+            }
+            ProtectionLevel::EncryptionAndAuthentication => {
+                DevicePairingProtectionLevel::EncryptionAndAuthentication
+            }
+        }
+    }
+}
+
+impl From<DevicePairingProtectionLevel> for ProtectionLevel {
+    fn from(level: DevicePairingProtectionLevel) -> Self {
+        match level {
+            DevicePairingProtectionLevel::Encryption => {
+                ProtectionLevel::Encryption
+            }
+            DevicePairingProtectionLevel::EncryptionAndAuthentication => {
+                ProtectionLevel::EncryptionAndAuthentication
+            }
+            _ => ProtectionLevel::None,
+        }
+    }
+}
+
+/// Create a handler for ConnectionStatusChanged events sending the new
+/// statuses to `sender`. Only statuses differing from `status` are sent, in
+/// case Windows raises the event without a change of status.
+fn connection_status_changed_handler(
+    mut sender: Sender<ConnectionStatus>,
+    mut status: ConnectionStatus,
+) -> TypedEventHandler<BluetoothLEDevice, IInspectable> {
+    TypedEventHandler::new(
+        // Move `sender` into closure.
+        move |device: &Option<BluetoothLEDevice>,
+              _event_args: &Option<IInspectable>| {
+            if let Some(device) = device {
+                let new_status =
+                    ConnectionStatus::from(device.ConnectionStatus()?);
+                if new_status != status {
+                    status = new_status;
+                    match sender.try_send(new_status) {
+                        Ok(_) => (),
+                        Err(err) if err.is_disconnected() => (),
+                        Err(err) => {
+                            error!("Error while handling ConnectionStatusChanged event: {:?}", err)
+                        }
+                    }
+                }
+            }
+
+            Ok(())
+        },
+    )
 }
 
 #[async_trait]
 impl api::ClassicDevice for ClassicDevice {
-    async fn new(addr: ClassicAddress) -> Result<Self, BluetoothError> {
+    async fn new_with_retry_policy(
+        addr: ClassicAddress,
+        retry_policy: RetryPolicy,
+    ) -> Result<Self, BluetoothError> {
         let raw_addr = u64::from(addr);
 
-        let inner = BluetoothDevice::FromBluetoothAddressAsync(
-            raw_addr,
-        )?
+        let inner = retry(&retry_policy, is_transient, || async {
+            BluetoothDevice::FromBluetoothAddressAsync(raw_addr)?.await
+        })
         .await?;
 
-        Ok(ClassicDevice { inner, addr })
+        Ok(ClassicDevice {
+            inner,
+            addr,
+            retry_policy,
+        })
     }
 
     fn name(&self) -> Result<String, BluetoothError> {
@@ -107,51 +452,189 @@ impl api::ClassicDevice for ClassicDevice {
         self.addr
     }
 
-    async fn pair(&self) -> Result<PairingResult, BluetoothError> {
-        let pair_info = self.inner.DeviceInformation()?.Pairing()?;
-        if pair_info.IsPaired()? {
-            info!("Device already paired");
-            Ok(PairingResult::AlreadyPaired)
-        } else if !pair_info.CanPair()? {
-            info!("Device can't pair");
-            Err(BluetoothError::PairingFailed(String::from("device can't pair")))
-        } else {  
-            let custom = pair_info.Custom()?;
-            custom.PairingRequested(&TypedEventHandler::new(
-                |_custom: &Option<DeviceInformationCustomPairing>, 
-                event_args: &Option<DevicePairingRequestedEventArgs>,
-                |  {
-                    if let Some(event_args) = event_args {
-                        match event_args.PairingKind()? {
-                            DevicePairingKinds::ConfirmOnly => {
-                                event_args.Accept()                            
-                            }
-                            _ => {
-                                warn!("Unsupported pairing kind {:?}", event_args.PairingKind());
-                                Ok(())
-                            }
-                        }
-                    } else {
-                        warn!("Empty pairing event arguments");
-                        Ok(())
-                    }
+    async fn rssi(&self) -> Result<i16, BluetoothError> {
+        signal_strength(self.inner.DeviceId()?).await
+    }
 
-                },
-            ))?;
-            let res = custom
-                .PairAsync(
-                    DevicePairingKinds::ConfirmOnly
-                        | DevicePairingKinds::ProvidePin
-                        | DevicePairingKinds::ConfirmPinMatch
-                        | DevicePairingKinds::DisplayPin,
+    /// Only services reached over RFCOMM are reported by Windows.
+    async fn discover_services(
+        &self,
+    ) -> Result<Vec<SdpRecord>, BluetoothError> {
+        let result = retry(&self.retry_policy, is_transient, || async {
+            self.inner
+                .GetRfcommServicesWithCacheModeAsync(
+                    BluetoothCacheMode::Uncached,
                 )?
-                .await?;
-            let status = PairingResult::from(res.Status()?);
+                .await
+        })
+        .await?;
+        check_bluetooth_error(result.Error()?)?;
+
+        // `Services()` returns a `!Send` view, so collect the services before
+        // querying their attributes.
+        let services: Vec<RfcommDeviceService> =
+            result.Services()?.into_iter().collect();
+
+        let mut records = Vec::new();
+        for service in services {
+            let uuid = Uuid::from(service.ServiceId()?.Uuid()?);
+            let channel = rfcomm_channel(
+                &service
+                    .GetSdpRawAttributesWithCacheModeAsync(
+                        BluetoothCacheMode::Uncached,
+                    )?
+                    .await?,
+            )?;
+            records.push(SdpRecord::new(uuid, channel));
+        }
+
+        Ok(records)
+    }
+
+    async fn connect_rfcomm(
+        &self,
+        service_uuid: Uuid,
+    ) -> Result<RfcommSocket, BluetoothError> {
+        connect_rfcomm(&self.inner, service_uuid, &self.retry_policy).await
+    }
+
+    async fn pair_with_protection_level(
+        &self,
+        protection_level: ProtectionLevel,
+    ) -> Result<PairingResult, BluetoothError> {
+        pair(self.inner.DeviceInformation()?, None, protection_level).await
+    }
+
+    async fn pair_with_delegate_and_protection_level<D: PairingDelegate>(
+        &self,
+        delegate: D,
+        protection_level: ProtectionLevel,
+    ) -> Result<PairingResult, BluetoothError> {
+        pair(
+            self.inner.DeviceInformation()?,
+            Some(Arc::new(delegate)),
+            protection_level,
+        )
+        .await
+    }
+
+    fn close(self) -> Result<(), BluetoothError> {
+        Ok(self.inner.Close()?)
+    }
+}
+
+/// Pair with the device, answering the requests that need user interaction
+/// with `delegate`, if any, and requiring at least `protection_level`. For
+/// BLE devices, Windows uses LE Secure Connections when the device supports
+/// it.
+async fn pair(
+    device_information: DeviceInformation,
+    delegate: Option<Arc<dyn PairingDelegate>>,
+    protection_level: ProtectionLevel,
+) -> Result<PairingResult, BluetoothError> {
+    let pair_info = device_information.Pairing()?;
+    if pair_info.IsPaired()? {
+        info!("Device already paired");
+        Ok(PairingResult::AlreadyPaired)
+    } else if !pair_info.CanPair()? {
+        info!("Device can't pair");
+        Err(BluetoothError::PairingFailed(String::from(
+            "device can't pair",
+        )))
+    } else {
+        let custom = pair_info.Custom()?;
+        custom.PairingRequested(&pairing_requested_handler(delegate))?;
+        let res = custom
+            .PairWithProtectionLevelAsync(
+                DevicePairingKinds::ConfirmOnly
+                    | DevicePairingKinds::ProvidePin
+                    | DevicePairingKinds::ConfirmPinMatch
+                    | DevicePairingKinds::DisplayPin,
+                DevicePairingProtectionLevel::from(protection_level),
+            )?
+            .await?;
+        let status = PairingResult::try_from(&res)?;
+
+        match status {
+            PairingResult::Failure(msg) => {
+                Err(BluetoothError::PairingFailed(msg))
+            }
+            _ => Ok(status),
+        }
+    }
+}
 
-            match status {
-                PairingResult::Failure(msg) => Err(BluetoothError::PairingFailed(msg)),
-                _ => Ok(status),
+/// Create a handler for PairingRequested events. `ConfirmOnly` ceremonies are
+/// always accepted, the others are answered by `delegate`, or rejected
+/// without one.
+fn pairing_requested_handler(
+    delegate: Option<Arc<dyn PairingDelegate>>,
+) -> TypedEventHandler<
+    DeviceInformationCustomPairing,
+    DevicePairingRequestedEventArgs,
+> {
+    TypedEventHandler::new(
+        move |_custom: &Option<DeviceInformationCustomPairing>,
+              event_args: &Option<DevicePairingRequestedEventArgs>| {
+            if let Some(event_args) = event_args {
+                let kind = event_args.PairingKind()?;
+                if kind == DevicePairingKinds::ConfirmOnly {
+                    event_args.Accept()
+                } else if let Some(delegate) = &delegate {
+                    // The delegate may wait for the user, so keep the
+                    // ceremony pending until it answers.
+                    let deferral = event_args.GetDeferral()?;
+                    let res = answer_pairing_request(
+                        delegate.as_ref(),
+                        event_args,
+                        kind,
+                    );
+                    deferral.Complete()?;
+                    res
+                } else {
+                    warn!("Unsupported pairing kind {:?}", kind);
+                    Ok(())
+                }
+            } else {
+                warn!("Empty pairing event arguments");
+                Ok(())
             }
+        },
+    )
+}
+
+/// Answer a pairing request with `delegate`. Returning without accepting the
+/// request rejects it.
+fn answer_pairing_request(
+    delegate: &dyn PairingDelegate,
+    event_args: &DevicePairingRequestedEventArgs,
+    kind: DevicePairingKinds,
+) -> windows::core::Result<()> {
+    match kind {
+        DevicePairingKinds::ConfirmPinMatch => {
+            let pin = event_args.Pin()?.to_string_lossy();
+            match pin.parse::<u32>() {
+                Ok(passkey) if delegate.confirm_passkey(passkey) => {
+                    event_args.Accept()
+                }
+                Ok(_) => Ok(()),
+                Err(_) => {
+                    warn!("Invalid passkey {}", pin);
+                    Ok(())
+                }
+            }
+        }
+        DevicePairingKinds::ProvidePin => match delegate.provide_pin() {
+            Some(pin) => event_args.AcceptWithPin(&HSTRING::from(pin.as_str())),
+            None => Ok(()),
+        },
+        DevicePairingKinds::DisplayPin => {
+            delegate.display_pin(&event_args.Pin()?.to_string_lossy());
+            event_args.Accept()
+        }
+        _ => {
+            warn!("Unsupported pairing kind {:?}", kind);
+            Ok(())
         }
     }
 }
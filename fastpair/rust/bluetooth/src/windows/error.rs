@@ -12,9 +12,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use windows::Devices::Enumeration::DevicePairingResultStatus;
+use windows::{
+    core::HRESULT,
+    Devices::{
+        Bluetooth::{
+            BluetoothError as WindowsBluetoothError,
+            GenericAttributeProfile::GattCommunicationStatus,
+        },
+        Enumeration::{DevicePairingResult, DevicePairingResultStatus},
+    },
+    Foundation::IReference,
+};
 
-use crate::common::{BluetoothError, PairingResult};
+use crate::common::{BluetoothError, PairingResult, ProtectionLevel};
 
 impl From<windows::core::Error> for BluetoothError {
     fn from(err: windows::core::Error) -> Self {
@@ -22,11 +32,123 @@ impl From<windows::core::Error> for BluetoothError {
     }
 }
 
+/// Convert the status of a GATT operation into an error, including the ATT
+/// protocol error reported by the device, if any.
+// https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.genericattributeprofile.gattcommunicationstatus?view=winrt-22621
+pub(crate) fn check_gatt_status(
+    status: GattCommunicationStatus,
+    protocol_error: windows::core::Result<IReference<u8>>,
+) -> Result<(), BluetoothError> {
+    match status {
+        GattCommunicationStatus::Success => Ok(()),
+        GattCommunicationStatus::Unreachable => Err(
+            BluetoothError::Unreachable(String::from("no GATT connection.")),
+        ),
+        GattCommunicationStatus::ProtocolError => {
+            match protocol_error.and_then(|err| err.Value()) {
+                Ok(err) => Err(BluetoothError::GattProtocolError(err)),
+                Err(_) => Err(BluetoothError::System(String::from(
+                    "the device reported an unknown ATT error.",
+                ))),
+            }
+        }
+        GattCommunicationStatus::AccessDenied => {
+            Err(BluetoothError::AccessDenied(String::from(
+                "the system denied access to the device.",
+            )))
+        }
+        _ => Err(BluetoothError::System(format!(
+            "unknown GATT communication status {}.",
+            status.0
+        ))),
+    }
+}
+
+/// Convert the error reported by a Bluetooth operation, which is `Success` if
+/// the operation succeeded.
+// https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.bluetootherror?view=winrt-22621
+pub(crate) fn check_bluetooth_error(
+    err: WindowsBluetoothError,
+) -> Result<(), BluetoothError> {
+    match err {
+        WindowsBluetoothError::Success => Ok(()),
+        WindowsBluetoothError::RadioNotAvailable => {
+            Err(BluetoothError::FailedPrecondition(String::from(
+                "the Bluetooth radio isn't available.",
+            )))
+        }
+        WindowsBluetoothError::NotSupported
+        | WindowsBluetoothError::TransportNotSupported => Err(
+            BluetoothError::NotSupported(String::from("Bluetooth operation")),
+        ),
+        WindowsBluetoothError::DisabledByPolicy
+        | WindowsBluetoothError::DisabledByUser
+        | WindowsBluetoothError::ConsentRequired => {
+            Err(BluetoothError::AccessDenied(String::from(
+                "Bluetooth access is disabled or requires consent.",
+            )))
+        }
+        _ => Err(BluetoothError::System(format!(
+            "Bluetooth operation failed with error {}.",
+            err.0
+        ))),
+    }
+}
+
+/// Describe why a watcher stopped scanning, as reported by its Stopped
+/// event.
+// https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.bluetootherror?view=winrt-22621
+pub(crate) fn scan_stop_reason(err: WindowsBluetoothError) -> String {
+    match err {
+        WindowsBluetoothError::RadioNotAvailable => {
+            String::from("the Bluetooth radio isn't available")
+        }
+        WindowsBluetoothError::ResourceInUse => {
+            String::from("the Bluetooth radio is in use")
+        }
+        WindowsBluetoothError::DisabledByPolicy
+        | WindowsBluetoothError::DisabledByUser => {
+            String::from("Bluetooth is disabled")
+        }
+        WindowsBluetoothError::ConsentRequired => {
+            String::from("Bluetooth access requires consent")
+        }
+        WindowsBluetoothError::NotSupported
+        | WindowsBluetoothError::TransportNotSupported => {
+            String::from("scanning isn't supported")
+        }
+        _ => format!("error {}", err.0),
+    }
+}
+
+/// `E_ABORT`, e.g. when starting a watcher while the radio is busy.
+// https://learn.microsoft.com/en-us/windows/win32/seccrypto/common-hresult-values
+const E_ABORT: HRESULT = HRESULT(0x80004004_u32 as i32);
+
+/// `HRESULT_FROM_WIN32(ERROR_BUSY)`.
+// https://learn.microsoft.com/en-us/windows/win32/debug/system-error-codes--0-499-
+const E_BUSY: HRESULT = HRESULT(0x800700AA_u32 as i32);
+
+/// `HRESULT_FROM_WIN32(ERROR_DEVICE_NOT_AVAILABLE)`.
+// https://learn.microsoft.com/en-us/windows/win32/debug/system-error-codes--4000-5999-
+const E_DEVICE_NOT_AVAILABLE: HRESULT = HRESULT(0x800710DF_u32 as i32);
+
+/// Check whether a WinRT call failed transiently, e.g. because the device or
+/// the radio is busy, so that it may succeed if retried.
+pub(crate) fn is_transient(err: &windows::core::Error) -> bool {
+    [E_ABORT, E_BUSY, E_DEVICE_NOT_AVAILABLE].contains(&err.code())
+}
+
+// https://learn.microsoft.com/en-us/uwp/api/windows.devices.enumeration.devicepairingresult?view=winrt-22621
 // https://learn.microsoft.com/en-us/uwp/api/windows.devices.enumeration.devicepairingresultstatus?view=winrt-22621
-impl From<DevicePairingResultStatus> for PairingResult {
-    fn from(status: DevicePairingResultStatus) -> Self {
-        match status {
-            DevicePairingResultStatus::Paired => PairingResult::Success,
+impl TryFrom<&DevicePairingResult> for PairingResult {
+    type Error = BluetoothError;
+
+    fn try_from(result: &DevicePairingResult) -> Result<Self, Self::Error> {
+        Ok(match result.Status()? {
+            DevicePairingResultStatus::Paired => PairingResult::Success(
+                ProtectionLevel::from(result.ProtectionLevelUsed()?),
+            ),
             DevicePairingResultStatus::AlreadyPaired => {
                 PairingResult::AlreadyPaired
             }
@@ -52,6 +174,6 @@ impl From<DevicePairingResultStatus> for PairingResult {
             DevicePairingResultStatus::RejectedByHandler => PairingResult::Failure(String::from("the application handler rejected the pairing.")),
             DevicePairingResultStatus::RemoteDeviceHasAssociation => PairingResult::Failure(String::from("the remote device already has an association.")),
             DevicePairingResultStatus::Failed | _ => PairingResult::Failure(String::from("an unknown failure occurred.")),
-        }
+        })
     }
 }
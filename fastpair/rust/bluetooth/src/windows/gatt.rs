@@ -0,0 +1,625 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use async_trait::async_trait;
+use futures::channel::mpsc::Sender;
+use tracing::{error, warn};
+use windows::{
+    core::IInspectable,
+    Devices::Bluetooth::{
+        // Tuple struct describing the type of address (public, random, unspecified).
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.bluetoothaddresstype?view=winrt-22621
+        BluetoothAddressType,
+
+        // Enum describing whether the system may answer from its cache rather
+        // than querying the device.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.bluetoothcachemode?view=winrt-22621
+        BluetoothCacheMode,
+
+        // Struct for interacting with a discovered BLE device.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.bluetoothledevice?view=winrt-22621
+        BluetoothLEDevice,
+
+        // Preset connection parameters, along with the request applying them
+        // and its status.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.bluetoothlepreferredconnectionparameters?view=winrt-22621
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.bluetoothlepreferredconnectionparametersrequest?view=winrt-22621
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.bluetoothlepreferredconnectionparametersrequeststatus?view=winrt-22621
+        BluetoothLEPreferredConnectionParameters,
+        BluetoothLEPreferredConnectionParametersRequest,
+        BluetoothLEPreferredConnectionParametersRequestStatus,
+        GenericAttributeProfile::{
+            // Struct representing a characteristic of a GATT service.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.genericattributeprofile.gattcharacteristic?view=winrt-22621
+            GattCharacteristic as WindowsGattCharacteristic,
+
+            // Result of a characteristic discovery.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.genericattributeprofile.gattcharacteristicsresult?view=winrt-22621
+            GattCharacteristicsResult,
+
+            // Tuple struct describing the value of a Client Characteristic
+            // Configuration Descriptor (CCCD), which enables notifications or
+            // indications.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.genericattributeprofile.gattclientcharacteristicconfigurationdescriptorvalue?view=winrt-22621
+            GattClientCharacteristicConfigurationDescriptorValue,
+
+            // Struct representing a GATT service of a BLE device.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.genericattributeprofile.gattdeviceservice?view=winrt-22621
+            GattDeviceService,
+
+            // Result of a service discovery.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.genericattributeprofile.gattdeviceservicesresult?view=winrt-22621
+            GattDeviceServicesResult,
+
+            // Result of a characteristic read.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.genericattributeprofile.gattreadresult?view=winrt-22621
+            GattReadResult,
+
+            // Struct queueing characteristic writes which are committed
+            // together with a reliable write.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.genericattributeprofile.gattreliablewritetransaction?view=winrt-22621
+            GattReliableWriteTransaction,
+
+            // Struct representing the GATT session with a BLE device, which
+            // holds the negotiated ATT MTU.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.genericattributeprofile.gattsession?view=winrt-22621
+            GattSession,
+
+            // Provides data for a ValueChanged event on a characteristic.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.genericattributeprofile.gattvaluechangedeventargs?view=winrt-22621
+            GattValueChangedEventArgs,
+
+            // Enum describing whether a write waits for an acknowledgement.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.genericattributeprofile.gattwriteoption?view=winrt-22621
+            GattWriteOption,
+
+            // Result of a characteristic write.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.genericattributeprofile.gattwriteresult?view=winrt-22621
+            GattWriteResult,
+        },
+    },
+    Foundation::{EventRegistrationToken, IAsyncOperation, TypedEventHandler},
+    // Structs for reading and writing Windows buffers.
+    // https://learn.microsoft.com/en-us/uwp/api/windows.storage.streams.datareader?view=winrt-22621
+    // https://learn.microsoft.com/en-us/uwp/api/windows.storage.streams.datawriter?view=winrt-22621
+    Storage::Streams::{DataReader, DataWriter, IBuffer},
+};
+
+use super::error::check_gatt_status;
+use crate::{
+    api,
+    common::{
+        BleAddress, BluetoothError, CharacteristicProperties,
+        CharacteristicValueStream, ConnectionPriority, GattCharacteristic,
+        GattService, ServicesChangedStream, Uuid, WriteType,
+    },
+};
+
+/// State shared with the GattServicesChanged handler of a `GattClient`.
+#[derive(Default)]
+struct ServicesChanged {
+    /// Number of changes reported so far. The cached services and
+    /// characteristics are invalid once it moves past the generation they
+    /// were discovered at.
+    generation: AtomicU64,
+    /// Sender of the stream returned by `watch_services_changed()`, if any.
+    sender: Mutex<Option<Sender<()>>>,
+}
+
+/// Concrete type implementing `api::GattClient`, used for Windows BLE.
+pub struct GattClient {
+    inner: BluetoothLEDevice,
+    addr: BleAddress,
+    session: GattSession,
+    /// Request of the parameters for the last priority passed to
+    /// `request_connection_priority()`, in effect until it's closed.
+    connection_parameters_request:
+        Option<BluetoothLEPreferredConnectionParametersRequest>,
+    /// Services found by the last call to `discover_services()`.
+    services: HashMap<Uuid, GattDeviceService>,
+    /// Characteristics found by `discover_characteristics()`, keyed by service
+    /// UUID and characteristic UUID.
+    characteristics: HashMap<(Uuid, Uuid), WindowsGattCharacteristic>,
+    /// Generation of `services_changed` reflected by the last service
+    /// discovery.
+    services_generation: u64,
+    /// Characteristics subscribed to, along with the registration of their
+    /// ValueChanged handler.
+    subscriptions: HashMap<
+        (Uuid, Uuid),
+        (WindowsGattCharacteristic, EventRegistrationToken),
+    >,
+    /// State updated by the GattServicesChanged handler.
+    services_changed: Arc<ServicesChanged>,
+    /// Registration of the GattServicesChanged handler.
+    services_changed_token: EventRegistrationToken,
+}
+
+#[async_trait]
+impl api::GattClient for GattClient {
+    async fn connect(addr: BleAddress) -> Result<Self, BluetoothError> {
+        let kind = BluetoothAddressType::from(addr.get_kind());
+        let raw_addr = u64::from(addr);
+
+        let inner =
+            BluetoothLEDevice::FromBluetoothAddressWithBluetoothAddressTypeAsync(
+                raw_addr, kind,
+            )?
+            .await?;
+        let session =
+            GattSession::FromDeviceIdAsync(&inner.BluetoothDeviceId()?)?
+                .await?;
+        let services_changed = Arc::new(ServicesChanged::default());
+        let services_changed_token = inner.GattServicesChanged(
+            &services_changed_handler(services_changed.clone()),
+        )?;
+
+        let mut client = GattClient {
+            inner,
+            addr,
+            session,
+            connection_parameters_request: None,
+            services: HashMap::new(),
+            characteristics: HashMap::new(),
+            services_generation: 0,
+            subscriptions: HashMap::new(),
+            services_changed,
+            services_changed_token,
+        };
+        // Windows only opens the connection once the GATT database is
+        // accessed, so discover services to make sure the device is
+        // reachable.
+        client.discover_services().await?;
+
+        Ok(client)
+    }
+
+    fn address(&self) -> BleAddress {
+        self.addr
+    }
+
+    fn current_mtu(&self) -> Result<u16, BluetoothError> {
+        Ok(self.session.MaxPduSize()?)
+    }
+
+    /// Windows negotiates the largest MTU it supports by itself once
+    /// connected, so the request can't change it.
+    async fn request_mtu(&mut self, _mtu: u16) -> Result<u16, BluetoothError> {
+        self.current_mtu()
+    }
+
+    fn request_connection_priority(
+        &mut self,
+        priority: ConnectionPriority,
+    ) -> Result<(), BluetoothError> {
+        let parameters = match priority {
+            ConnectionPriority::Balanced => {
+                BluetoothLEPreferredConnectionParameters::Balanced()?
+            }
+            ConnectionPriority::LowLatency => {
+                BluetoothLEPreferredConnectionParameters::ThroughputOptimized()?
+            }
+            ConnectionPriority::LowPower => {
+                BluetoothLEPreferredConnectionParameters::PowerOptimized()?
+            }
+        };
+
+        // Only the latest request should be in effect.
+        if let Some(request) = self.connection_parameters_request.take() {
+            request.Close()?;
+        }
+
+        let request = self
+            .inner
+            .RequestPreferredConnectionParameters(&parameters)?;
+        match request.Status()? {
+            BluetoothLEPreferredConnectionParametersRequestStatus::Success => {
+                self.connection_parameters_request = Some(request);
+                Ok(())
+            }
+            BluetoothLEPreferredConnectionParametersRequestStatus::DeviceNotAvailable => {
+                Err(BluetoothError::Unreachable(String::from(
+                    "the device isn't connected.",
+                )))
+            }
+            BluetoothLEPreferredConnectionParametersRequestStatus::AccessDenied => {
+                Err(BluetoothError::AccessDenied(String::from(
+                    "the system denied the connection parameters request.",
+                )))
+            }
+            status => Err(BluetoothError::System(format!(
+                "unknown connection parameters request status {}.",
+                status.0
+            ))),
+        }
+    }
+
+    async fn discover_services(
+        &mut self,
+    ) -> Result<Vec<GattService>, BluetoothError> {
+        let result = self
+            .inner
+            .GetGattServicesWithCacheModeAsync(BluetoothCacheMode::Uncached)?
+            .await?;
+        // The uncached enumeration reflects the changes reported while it
+        // ran, including the GattServicesChanged event it raises itself, so
+        // only later changes invalidate its result.
+        let generation =
+            self.services_changed.generation.load(Ordering::SeqCst);
+
+        let services = self.load_services(&result)?;
+        self.services_generation = generation;
+
+        Ok(services)
+    }
+
+    async fn discover_characteristics(
+        &mut self,
+        service: &GattService,
+    ) -> Result<Vec<GattCharacteristic>, BluetoothError> {
+        self.check_services_unchanged()?;
+
+        let service_uuid = service.uuid();
+        let windows_service = self
+            .services
+            .get(&service_uuid)
+            .ok_or_else(|| {
+                BluetoothError::FailedPrecondition(format!(
+                    "service {} hasn't been discovered, please call \
+                    `discover_services()`",
+                    service_uuid,
+                ))
+            })?
+            .clone();
+
+        let result = windows_service
+            .GetCharacteristicsWithCacheModeAsync(BluetoothCacheMode::Uncached)?
+            .await?;
+
+        self.load_characteristics(service_uuid, &result)
+    }
+
+    async fn read_characteristic(
+        &mut self,
+        characteristic: &GattCharacteristic,
+    ) -> Result<Vec<u8>, BluetoothError> {
+        let result = self
+            .windows_characteristic(characteristic)?
+            .ReadValueWithCacheModeAsync(BluetoothCacheMode::Uncached)?
+            .await?;
+
+        read_value(&result)
+    }
+
+    async fn write_characteristic(
+        &mut self,
+        characteristic: &GattCharacteristic,
+        value: &[u8],
+        write_type: WriteType,
+    ) -> Result<(), BluetoothError> {
+        let windows_characteristic =
+            self.windows_characteristic(characteristic)?;
+        let result =
+            start_write(&windows_characteristic, value, write_type)?.await?;
+
+        check_gatt_status(result.Status()?, result.ProtocolError())
+    }
+
+    async fn subscribe(
+        &mut self,
+        characteristic: &GattCharacteristic,
+    ) -> Result<CharacteristicValueStream, BluetoothError> {
+        let key = (characteristic.service_uuid(), characteristic.uuid());
+        if self.subscriptions.contains_key(&key) {
+            return Err(BluetoothError::FailedPrecondition(format!(
+                "already subscribed to characteristic {}",
+                characteristic.uuid(),
+            )));
+        }
+
+        let properties = characteristic.properties();
+        let cccd_value =
+            if properties.contains(CharacteristicProperties::NOTIFY) {
+                GattClientCharacteristicConfigurationDescriptorValue::Notify
+            } else if properties.contains(CharacteristicProperties::INDICATE) {
+                GattClientCharacteristicConfigurationDescriptorValue::Indicate
+            } else {
+                return Err(BluetoothError::NotSupported(format!(
+                    "characteristic {} doesn't support notifications or \
+                    indications",
+                    characteristic.uuid(),
+                )));
+            };
+
+        let windows_characteristic =
+            self.windows_characteristic(characteristic)?;
+
+        // `futures::channel::mpsc` is like `std::sync::mpsc` but `impl Stream`.
+        let (sender, receiver) = futures::channel::mpsc::channel(16);
+        let token = windows_characteristic
+            .ValueChanged(&value_changed_handler(sender))?;
+
+        // The handler is only called once the server has been told to send
+        // value changes, through the CCCD.
+        if let Err(err) = write_cccd(&windows_characteristic, cccd_value).await
+        {
+            windows_characteristic.RemoveValueChanged(token)?;
+            return Err(err);
+        }
+
+        self.subscriptions
+            .insert(key, (windows_characteristic, token));
+
+        Ok(CharacteristicValueStream::new(receiver))
+    }
+
+    async fn unsubscribe(
+        &mut self,
+        characteristic: &GattCharacteristic,
+    ) -> Result<(), BluetoothError> {
+        let key = (characteristic.service_uuid(), characteristic.uuid());
+        let (windows_characteristic, token) =
+            self.subscriptions.remove(&key).ok_or_else(|| {
+                BluetoothError::FailedPrecondition(format!(
+                    "not subscribed to characteristic {}, please call \
+                    `subscribe()`",
+                    characteristic.uuid(),
+                ))
+            })?;
+
+        // Removing the handler drops its sender, ending the stream.
+        windows_characteristic.RemoveValueChanged(token)?;
+        write_cccd(
+            &windows_characteristic,
+            GattClientCharacteristicConfigurationDescriptorValue::None,
+        )
+        .await
+    }
+
+    async fn watch_services_changed(
+        &mut self,
+    ) -> Result<ServicesChangedStream, BluetoothError> {
+        // `futures::channel::mpsc` is like `std::sync::mpsc` but `impl Stream`.
+        // A single slot is enough, as changes pending delivery are merged.
+        let (sender, receiver) = futures::channel::mpsc::channel(1);
+        // Replacing the sender ends the previous stream.
+        *self.services_changed.sender.lock().unwrap() = Some(sender);
+
+        Ok(ServicesChangedStream::new(receiver))
+    }
+}
+
+impl Drop for GattClient {
+    fn drop(&mut self) {
+        if let Err(err) = self
+            .inner
+            .RemoveGattServicesChanged(self.services_changed_token)
+        {
+            warn!("Failed to stop watching the services: {}", err);
+        }
+    }
+}
+
+impl GattClient {
+    /// Replace the cached services with the result of a service discovery.
+    /// Note the collections returned by Windows are `!Send`, so they're
+    /// processed outside of the async functions.
+    fn load_services(
+        &mut self,
+        result: &GattDeviceServicesResult,
+    ) -> Result<Vec<GattService>, BluetoothError> {
+        check_gatt_status(result.Status()?, result.ProtocolError())?;
+
+        self.services.clear();
+        self.characteristics.clear();
+
+        let mut services = Vec::new();
+        for windows_service in result.Services()? {
+            let uuid = Uuid::from(windows_service.Uuid()?);
+            services.push(GattService::new(uuid));
+            self.services.insert(uuid, windows_service);
+        }
+
+        Ok(services)
+    }
+
+    /// Cache the result of a characteristic discovery.
+    fn load_characteristics(
+        &mut self,
+        service_uuid: Uuid,
+        result: &GattCharacteristicsResult,
+    ) -> Result<Vec<GattCharacteristic>, BluetoothError> {
+        check_gatt_status(result.Status()?, result.ProtocolError())?;
+
+        let mut characteristics = Vec::new();
+        for windows_characteristic in result.Characteristics()? {
+            let uuid = Uuid::from(windows_characteristic.Uuid()?);
+            // The properties defined by the Bluetooth Core Specification
+            // occupy the low byte, the others are Windows extensions.
+            let properties = CharacteristicProperties::from_bits(
+                windows_characteristic.CharacteristicProperties()?.0 as u8,
+            );
+            characteristics.push(GattCharacteristic::new(
+                service_uuid,
+                uuid,
+                properties,
+            ));
+            self.characteristics
+                .insert((service_uuid, uuid), windows_characteristic);
+        }
+
+        Ok(characteristics)
+    }
+
+    /// Fail if the services changed since they were last discovered, as the
+    /// services and characteristics may no longer match the device.
+    fn check_services_unchanged(&self) -> Result<(), BluetoothError> {
+        if self.services_changed.generation.load(Ordering::SeqCst)
+            != self.services_generation
+        {
+            return Err(BluetoothError::FailedPrecondition(String::from(
+                "the services of the device changed, please call \
+                `discover_services()`",
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Retrieve the Windows object of a discovered characteristic.
+    fn windows_characteristic(
+        &self,
+        characteristic: &GattCharacteristic,
+    ) -> Result<WindowsGattCharacteristic, BluetoothError> {
+        self.check_services_unchanged()?;
+
+        self.characteristics
+            .get(&(characteristic.service_uuid(), characteristic.uuid()))
+            .cloned()
+            .ok_or_else(|| {
+                BluetoothError::FailedPrecondition(format!(
+                    "characteristic {} hasn't been discovered, please call \
+                    `discover_characteristics()`",
+                    characteristic.uuid(),
+                ))
+            })
+    }
+}
+
+/// Copy the contents of a Windows buffer. Windows buffers are `!Send`, so
+/// this can't happen inside an async function.
+pub(crate) fn read_buffer(buffer: &IBuffer) -> Result<Vec<u8>, BluetoothError> {
+    let data_reader = DataReader::FromBuffer(buffer)?;
+    let mut value = vec![0u8; data_reader.UnconsumedBufferLength()? as usize];
+    data_reader.ReadBytes(&mut value)?;
+
+    Ok(value)
+}
+
+/// Copy bytes into a new Windows buffer.
+pub(crate) fn write_buffer(value: &[u8]) -> Result<IBuffer, BluetoothError> {
+    let data_writer = DataWriter::new()?;
+    data_writer.WriteBytes(value)?;
+
+    Ok(data_writer.DetachBuffer()?)
+}
+
+/// Copy the value out of the result of a characteristic read.
+fn read_value(result: &GattReadResult) -> Result<Vec<u8>, BluetoothError> {
+    check_gatt_status(result.Status()?, result.ProtocolError())?;
+
+    read_buffer(&result.Value()?)
+}
+
+/// Write the Client Characteristic Configuration Descriptor of a
+/// characteristic, enabling or disabling notifications or indications.
+async fn write_cccd(
+    characteristic: &WindowsGattCharacteristic,
+    value: GattClientCharacteristicConfigurationDescriptorValue,
+) -> Result<(), BluetoothError> {
+    let result = characteristic
+        .WriteClientCharacteristicConfigurationDescriptorWithResultAsync(value)?
+        .await?;
+
+    check_gatt_status(result.Status()?, result.ProtocolError())
+}
+
+/// Create a handler for ValueChanged events sending the new values to
+/// `sender`.
+fn value_changed_handler(
+    mut sender: Sender<Vec<u8>>,
+) -> TypedEventHandler<WindowsGattCharacteristic, GattValueChangedEventArgs> {
+    TypedEventHandler::new(
+        // Move `sender` into closure.
+        move |_characteristic: &Option<WindowsGattCharacteristic>,
+              event_args: &Option<GattValueChangedEventArgs>| {
+            if let Some(event_args) = event_args {
+                match read_buffer(&event_args.CharacteristicValue()?) {
+                    Ok(value) => {
+                        match sender.try_send(value) {
+                            Ok(_) => (),
+                            Err(err) if err.is_disconnected() => (),
+                            Err(err) => {
+                                error!("Error while handling ValueChanged event: {:?}", err)
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        warn!("Failed to read characteristic value: {}", err)
+                    }
+                }
+            }
+
+            Ok(())
+        },
+    )
+}
+
+/// Create a handler for GattServicesChanged events, which are raised when
+/// the device indicates Service Changed or its services are enumerated
+/// again.
+fn services_changed_handler(
+    services_changed: Arc<ServicesChanged>,
+) -> TypedEventHandler<BluetoothLEDevice, IInspectable> {
+    TypedEventHandler::new(
+        // Move `services_changed` into closure.
+        move |_device: &Option<BluetoothLEDevice>,
+              _event_args: &Option<IInspectable>| {
+            services_changed.generation.fetch_add(1, Ordering::SeqCst);
+            if let Some(sender) =
+                services_changed.sender.lock().unwrap().as_mut()
+            {
+                // A full channel already holds a pending change, and a
+                // closed one means nobody is watching.
+                let _ = sender.try_send(());
+            }
+
+            Ok(())
+        },
+    )
+}
+
+/// Start writing a characteristic value. Windows buffers are `!Send`, so the
+/// buffer is created outside of the async function awaiting the write.
+fn start_write(
+    characteristic: &WindowsGattCharacteristic,
+    value: &[u8],
+    write_type: WriteType,
+) -> Result<IAsyncOperation<GattWriteResult>, BluetoothError> {
+    let buffer = write_buffer(value)?;
+    let write_option = match write_type {
+        WriteType::WithResponse => GattWriteOption::WriteWithResponse,
+        WriteType::WithoutResponse => GattWriteOption::WriteWithoutResponse,
+        WriteType::Reliable => {
+            let transaction = GattReliableWriteTransaction::new()?;
+            transaction.WriteValue(characteristic, &buffer)?;
+            return Ok(transaction.CommitWithResultAsync()?);
+        }
+    };
+
+    Ok(characteristic
+        .WriteValueWithResultAndOptionAsync(&buffer, write_option)?)
+}
+
+mod tests {
+    // TODO b/288592509 unit tests
+}
@@ -0,0 +1,322 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use tracing::warn;
+use windows::{
+    core::GUID,
+    Devices::Bluetooth::GenericAttributeProfile::{
+        // Bit field of the operations supported by a characteristic.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.genericattributeprofile.gattcharacteristicproperties?view=winrt-22621
+        GattCharacteristicProperties,
+
+        // Result of sending a notification or indication to a client.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.genericattributeprofile.gattclientnotificationresult?view=winrt-22621
+        GattClientNotificationResult,
+
+        // Struct representing a characteristic of a local GATT service.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.genericattributeprofile.gattlocalcharacteristic?view=winrt-22621
+        GattLocalCharacteristic,
+
+        // Parameters used to create a local characteristic.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.genericattributeprofile.gattlocalcharacteristicparameters?view=winrt-22621
+        GattLocalCharacteristicParameters,
+
+        // Provides data for a ReadRequested event on a local characteristic.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.genericattributeprofile.gattreadrequestedeventargs?view=winrt-22621
+        GattReadRequestedEventArgs,
+
+        // Struct publishing a local GATT service.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.genericattributeprofile.gattserviceprovider?view=winrt-22621
+        GattServiceProvider,
+
+        // Parameters describing how a local GATT service is advertised.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.genericattributeprofile.gattserviceprovideradvertisingparameters?view=winrt-22621
+        GattServiceProviderAdvertisingParameters,
+
+        // Enum describing whether a write waits for an acknowledgement.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.genericattributeprofile.gattwriteoption?view=winrt-22621
+        GattWriteOption,
+
+        // Provides data for a WriteRequested event on a local characteristic.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.genericattributeprofile.gattwriterequestedeventargs?view=winrt-22621
+        GattWriteRequestedEventArgs,
+    },
+    Foundation::{
+        Collections::IVectorView, IAsyncOperation, TypedEventHandler,
+    },
+};
+
+use super::{
+    error::{check_bluetooth_error, check_gatt_status},
+    gatt::{read_buffer, write_buffer},
+};
+use crate::{
+    api::{self, GattRequestHandler},
+    common::{
+        value_at_offset, AttErrorCode, BluetoothError, GattCharacteristic,
+        LocalCharacteristic, LocalService, Uuid,
+    },
+};
+
+/// Concrete type implementing `api::GattServer`, used for Windows BLE.
+pub struct GattServer {
+    /// One provider per published service.
+    providers: Vec<GattServiceProvider>,
+    /// Characteristics of the published services, keyed by service UUID and
+    /// characteristic UUID.
+    characteristics: HashMap<(Uuid, Uuid), GattLocalCharacteristic>,
+}
+
+#[async_trait]
+impl api::GattServer for GattServer {
+    async fn new<H: GattRequestHandler>(
+        services: &[LocalService],
+        handler: H,
+    ) -> Result<Self, BluetoothError> {
+        let handler: Arc<dyn GattRequestHandler> = Arc::new(handler);
+        let mut server = GattServer {
+            providers: Vec::new(),
+            characteristics: HashMap::new(),
+        };
+
+        for service in services {
+            let result =
+                GattServiceProvider::CreateAsync(GUID::from(service.uuid()))?
+                    .await?;
+            check_bluetooth_error(result.Error()?)?;
+            let provider = result.ServiceProvider()?;
+            let local_service = provider.Service()?;
+
+            for characteristic in service.characteristics() {
+                let parameters = characteristic_parameters(characteristic)?;
+                let result = local_service
+                    .CreateCharacteristicAsync(
+                        GUID::from(characteristic.uuid()),
+                        &parameters,
+                    )?
+                    .await?;
+                check_bluetooth_error(result.Error()?)?;
+                let local_characteristic = result.Characteristic()?;
+
+                let gatt_characteristic = GattCharacteristic::new(
+                    service.uuid(),
+                    characteristic.uuid(),
+                    characteristic.properties(),
+                );
+                local_characteristic.ReadRequested(&read_requested_handler(
+                    gatt_characteristic,
+                    handler.clone(),
+                ))?;
+                local_characteristic.WriteRequested(
+                    &write_requested_handler(
+                        gatt_characteristic,
+                        handler.clone(),
+                    ),
+                )?;
+                server.characteristics.insert(
+                    (service.uuid(), characteristic.uuid()),
+                    local_characteristic,
+                );
+            }
+
+            // Services are only visible to clients while advertised.
+            let advertising_parameters =
+                GattServiceProviderAdvertisingParameters::new()?;
+            advertising_parameters.SetIsConnectable(true)?;
+            advertising_parameters.SetIsDiscoverable(true)?;
+            provider.StartAdvertisingWithParameters(&advertising_parameters)?;
+            server.providers.push(provider);
+        }
+
+        Ok(server)
+    }
+
+    async fn notify(
+        &mut self,
+        characteristic: &GattCharacteristic,
+        value: &[u8],
+    ) -> Result<(), BluetoothError> {
+        let local_characteristic = self
+            .characteristics
+            .get(&(characteristic.service_uuid(), characteristic.uuid()))
+            .cloned()
+            .ok_or_else(|| {
+                BluetoothError::FailedPrecondition(format!(
+                    "characteristic {} isn't offered by the server",
+                    characteristic.uuid(),
+                ))
+            })?;
+
+        let results = start_notify(&local_characteristic, value)?.await?;
+
+        check_notification_results(results)
+    }
+}
+
+impl Drop for GattServer {
+    fn drop(&mut self) {
+        for provider in &self.providers {
+            if let Err(err) = provider.StopAdvertising() {
+                warn!("Failed to stop advertising GATT service: {}", err);
+            }
+        }
+    }
+}
+
+/// Create the parameters of a local characteristic.
+fn characteristic_parameters(
+    characteristic: &LocalCharacteristic,
+) -> Result<GattLocalCharacteristicParameters, BluetoothError> {
+    let parameters = GattLocalCharacteristicParameters::new()?;
+    parameters.SetCharacteristicProperties(GattCharacteristicProperties(
+        u32::from(characteristic.properties().bits()),
+    ))?;
+
+    Ok(parameters)
+}
+
+/// Create a handler for ReadRequested events answering with the value
+/// provided by `handler`.
+fn read_requested_handler(
+    characteristic: GattCharacteristic,
+    handler: Arc<dyn GattRequestHandler>,
+) -> TypedEventHandler<GattLocalCharacteristic, GattReadRequestedEventArgs> {
+    TypedEventHandler::new(
+        move |_local_characteristic: &Option<GattLocalCharacteristic>,
+              event_args: &Option<GattReadRequestedEventArgs>| {
+            if let Some(event_args) = event_args {
+                // The request stays pending until the deferral completes.
+                let deferral = event_args.GetDeferral()?;
+                if let Err(err) =
+                    handle_read_request(&characteristic, &*handler, event_args)
+                {
+                    warn!("Failed to answer read request: {}", err);
+                }
+                deferral.Complete()?;
+            }
+
+            Ok(())
+        },
+    )
+}
+
+/// Answer a read request with the value provided by `handler`. Event handlers
+/// run on a system thread, so the request can be waited for synchronously.
+fn handle_read_request(
+    characteristic: &GattCharacteristic,
+    handler: &dyn GattRequestHandler,
+    event_args: &GattReadRequestedEventArgs,
+) -> Result<(), BluetoothError> {
+    let request = event_args.GetRequestAsync()?.get()?;
+    let offset = request.Offset()? as usize;
+
+    match handler
+        .on_read(characteristic)
+        .and_then(|value| value_at_offset(value, offset))
+    {
+        Ok(value) => request.RespondWithValue(&write_buffer(&value)?)?,
+        Err(err) => request.RespondWithProtocolError(err.code())?,
+    }
+
+    Ok(())
+}
+
+/// Create a handler for WriteRequested events forwarding the written values
+/// to `handler`.
+fn write_requested_handler(
+    characteristic: GattCharacteristic,
+    handler: Arc<dyn GattRequestHandler>,
+) -> TypedEventHandler<GattLocalCharacteristic, GattWriteRequestedEventArgs> {
+    TypedEventHandler::new(
+        move |_local_characteristic: &Option<GattLocalCharacteristic>,
+              event_args: &Option<GattWriteRequestedEventArgs>| {
+            if let Some(event_args) = event_args {
+                // The request stays pending until the deferral completes.
+                let deferral = event_args.GetDeferral()?;
+                if let Err(err) =
+                    handle_write_request(&characteristic, &*handler, event_args)
+                {
+                    warn!("Failed to answer write request: {}", err);
+                }
+                deferral.Complete()?;
+            }
+
+            Ok(())
+        },
+    )
+}
+
+/// Forward a write request to `handler`, acknowledging it if the client asked
+/// for a response.
+fn handle_write_request(
+    characteristic: &GattCharacteristic,
+    handler: &dyn GattRequestHandler,
+    event_args: &GattWriteRequestedEventArgs,
+) -> Result<(), BluetoothError> {
+    let request = event_args.GetRequestAsync()?.get()?;
+
+    let res = if request.Offset()? != 0 {
+        Err(AttErrorCode::ATTRIBUTE_NOT_LONG)
+    } else {
+        match read_buffer(&request.Value()?) {
+            Ok(value) => handler.on_write(characteristic, &value),
+            Err(err) => {
+                warn!("Failed to read written characteristic value: {}", err);
+                Err(AttErrorCode::UNLIKELY_ERROR)
+            }
+        }
+    };
+
+    if request.Option()? == GattWriteOption::WriteWithResponse {
+        match res {
+            Ok(()) => request.Respond()?,
+            Err(err) => request.RespondWithProtocolError(err.code())?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Start sending a value to the subscribed clients. Windows buffers are
+/// `!Send`, so the buffer is created outside of the async function awaiting
+/// the notification.
+fn start_notify(
+    characteristic: &GattLocalCharacteristic,
+    value: &[u8],
+) -> Result<
+    IAsyncOperation<IVectorView<GattClientNotificationResult>>,
+    BluetoothError,
+> {
+    let buffer = write_buffer(value)?;
+
+    Ok(characteristic.NotifyValueAsync(&buffer)?)
+}
+
+/// Check that every subscribed client received a notification or indication.
+fn check_notification_results(
+    results: IVectorView<GattClientNotificationResult>,
+) -> Result<(), BluetoothError> {
+    for result in results {
+        check_gatt_status(result.Status()?, result.ProtocolError())?;
+    }
+
+    Ok(())
+}
+
+mod tests {
+    // TODO b/288592509 unit tests
+}
@@ -16,10 +16,19 @@
 mod adapter;
 mod address;
 mod advertisement;
+mod advertiser;
 mod device;
 mod error;
+mod gatt;
+mod gatt_server;
+mod rfcomm;
+mod uuid;
 
 pub use adapter::*;
 pub use address::*;
 pub use advertisement::*;
+pub use advertiser::*;
 pub use device::*;
+pub use gatt::*;
+pub use gatt_server::*;
+pub use uuid::*;
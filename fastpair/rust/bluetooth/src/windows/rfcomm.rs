@@ -0,0 +1,161 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use futures::{sink, stream};
+use tracing::warn;
+use windows::{
+    core::GUID,
+    Devices::Bluetooth::{
+        // Enum describing whether the system may answer from its cache
+        // rather than querying the device.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.bluetoothcachemode?view=winrt-22621
+        BluetoothCacheMode,
+
+        // Struct for interacting with a discovered BT Classic device.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.bluetoothdevice?view=winrt-22621
+        BluetoothDevice,
+
+        Rfcomm::{
+            // Struct representing an RFCOMM service of a BT Classic device,
+            // and the result of looking such services up.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.rfcomm.rfcommdeviceservice?view=winrt-22621
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.rfcomm.rfcommdeviceservicesresult?view=winrt-22621
+            RfcommDeviceService,
+            RfcommDeviceServicesResult,
+
+            // Struct identifying an RFCOMM service by its UUID.
+            // https://learn.microsoft.com/en-us/uwp/api/windows.devices.bluetooth.rfcomm.rfcommserviceid?view=winrt-22621
+            RfcommServiceId,
+        },
+    },
+    // Struct for a TCP or Bluetooth RFCOMM connection.
+    // https://learn.microsoft.com/en-us/uwp/api/windows.networking.sockets.streamsocket?view=winrt-22621
+    Networking::Sockets::StreamSocket,
+    Storage::Streams::{
+        // Structs reading from an input stream and writing to an output
+        // stream, e.g. those of a socket.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.storage.streams.datareader?view=winrt-22621
+        // https://learn.microsoft.com/en-us/uwp/api/windows.storage.streams.datawriter?view=winrt-22621
+        DataReader,
+        DataWriter,
+
+        // Tuple struct describing when a read completes.
+        // https://learn.microsoft.com/en-us/uwp/api/windows.storage.streams.inputstreamoptions?view=winrt-22621
+        InputStreamOptions,
+    },
+};
+
+use super::error::{check_bluetooth_error, is_transient};
+use crate::common::{retry, BluetoothError, RetryPolicy, RfcommSocket, Uuid};
+
+/// Maximum number of bytes returned by a single read.
+const READ_BUFFER_SIZE: u32 = 1024;
+
+/// Socket closed when dropped, which completes the read pending on it.
+struct Socket(StreamSocket);
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        if let Err(err) = self.0.Close() {
+            warn!("Failed to close RFCOMM socket: {}", err);
+        }
+    }
+}
+
+/// Open an RFCOMM connection to the service `service_uuid` of `device`,
+/// retrying the service lookup as described by `retry_policy`.
+pub(crate) async fn connect_rfcomm(
+    device: &BluetoothDevice,
+    service_uuid: Uuid,
+    retry_policy: &RetryPolicy,
+) -> Result<RfcommSocket, BluetoothError> {
+    let service_id = RfcommServiceId::FromUuid(GUID::from(service_uuid))?;
+    let result = retry(retry_policy, is_transient, || async {
+        device
+            .GetRfcommServicesForIdWithCacheModeAsync(
+                &service_id,
+                BluetoothCacheMode::Uncached,
+            )?
+            .await
+    })
+    .await?;
+    check_bluetooth_error(result.Error()?)?;
+    let service = first_service(&result, service_uuid)?;
+
+    let socket = StreamSocket::new()?;
+    socket
+        .ConnectAsync(
+            &service.ConnectionHostName()?,
+            &service.ConnectionServiceName()?,
+        )?
+        .await?;
+
+    let reader = DataReader::CreateDataReader(&socket.InputStream()?)?;
+    // Complete reads as soon as some data arrived, rather than once the
+    // buffer is full.
+    reader.SetInputStreamOptions(InputStreamOptions::Partial)?;
+    let writer = DataWriter::CreateDataWriter(&socket.OutputStream()?)?;
+    let socket = Arc::new(Socket(socket));
+
+    let incoming =
+        stream::unfold(Some((reader, socket.clone())), |state| async move {
+            let (reader, socket) = state?;
+            match read(&reader).await {
+                Ok(Some(data)) => Some((Ok(data), Some((reader, socket)))),
+                Ok(None) => None,
+                // The socket can't be read from after a failure.
+                Err(err) => Some((Err(err), None)),
+            }
+        });
+    let outgoing = sink::unfold(
+        (writer, socket),
+        |(writer, socket), data: Vec<u8>| async move {
+            writer.WriteBytes(&data)?;
+            writer.StoreAsync()?.await?;
+            Ok((writer, socket))
+        },
+    );
+
+    Ok(RfcommSocket::new(incoming, outgoing))
+}
+
+/// Retrieve the first service found by an RFCOMM service lookup. The
+/// services are returned in a `!Send` view, so they're not kept across
+/// awaits.
+fn first_service(
+    result: &RfcommDeviceServicesResult,
+    service_uuid: Uuid,
+) -> Result<RfcommDeviceService, BluetoothError> {
+    result.Services()?.into_iter().next().ok_or_else(|| {
+        BluetoothError::NotSupported(format!(
+            "RFCOMM service {} on this device",
+            service_uuid
+        ))
+    })
+}
+
+/// Read the data received since the last read, waiting for some if there's
+/// none yet. Returns `None` once the remote device closed the connection.
+async fn read(reader: &DataReader) -> Result<Option<Vec<u8>>, BluetoothError> {
+    let len = reader.LoadAsync(READ_BUFFER_SIZE)?.await?;
+    if len == 0 {
+        return Ok(None);
+    }
+
+    let mut data = vec![0; len as usize];
+    reader.ReadBytes(&mut data)?;
+    Ok(Some(data))
+}
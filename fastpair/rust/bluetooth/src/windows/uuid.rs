@@ -0,0 +1,29 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use windows::core::GUID;
+
+use crate::common::Uuid;
+
+impl From<GUID> for Uuid {
+    fn from(guid: GUID) -> Self {
+        Uuid::from_u128(guid.to_u128())
+    }
+}
+
+impl From<Uuid> for GUID {
+    fn from(uuid: Uuid) -> Self {
+        GUID::from_u128(uuid.as_u128())
+    }
+}
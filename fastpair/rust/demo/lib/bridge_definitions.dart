@@ -7,36 +7,87 @@ import 'dart:async';
 import 'package:meta/meta.dart';
 import 'package:flutter_rust_bridge/flutter_rust_bridge.dart';
 import 'package:uuid/uuid.dart';
+import 'package:freezed_annotation/freezed_annotation.dart' hide protected;
 
 import 'package:collection/collection.dart';
 
+part 'bridge_definitions.freezed.dart';
+
 abstract class Rust {
   /// Sets up initial constructs and infinitely polls for advertisements.
   Future<void> init({dynamic hint});
 
   FlutterRustBridgeTaskConstMeta get kInitConstMeta;
 
-  /// Sets up `StreamSink` for Dart-Rust FFI.
-  Stream<StringArray2?> eventStream({dynamic hint});
+  /// Sets up `StreamSink` for Dart-Rust FFI, which receives the nearby devices
+  /// every time they change.
+  Stream<List<NearbyDevice>> eventStream({dynamic hint});
 
   FlutterRustBridgeTaskConstMeta get kEventStreamConstMeta;
 
-  /// Attempt classic pairing with currently displayed device.
-  Future<String> pair({dynamic hint});
+  /// Attempt pairing with the nearby device with ID `device_id`, through Fast
+  /// Pair if it's one of the user's devices or its model has an anti-spoofing
+  /// key, sending its progress to `events` until it completes or fails. Once
+  /// paired, the battery updates sent by the device are displayed.
+  Stream<PairingEvent> pair({required String deviceId, dynamic hint});
 
   FlutterRustBridgeTaskConstMeta get kPairConstMeta;
 
-  /// Remove this device from display and add it to the TTL cache blacklist.
-  Future<void> dismiss({dynamic hint});
+  /// Remove the device with ID `device_id` from display and add it to the TTL
+  /// cache blacklist.
+  Future<void> dismiss({required String deviceId, dynamic hint});
 
   FlutterRustBridgeTaskConstMeta get kDismissConstMeta;
 }
 
-class StringArray2 extends NonGrowableListView<String> {
-  static const arraySize = 2;
-  StringArray2(List<String> inner)
-      : assert(inner.length == arraySize),
-        super(inner);
-  StringArray2.unchecked(List<String> inner) : super(inner);
-  StringArray2.init(String fill) : super(List<String>.filled(arraySize, fill));
+/// A nearby Fast Pair device, as displayed by Flutter.
+class NearbyDevice {
+  /// Address of the device, identifying it to `pair` and `dismiss`.
+  final String deviceId;
+  final String modelId;
+  final String name;
+
+  /// Path of the image of the model, empty until it's downloaded.
+  final String imagePath;
+
+  /// Battery status of the device, empty if it's unknown.
+  final String batteryStatus;
+
+  const NearbyDevice({
+    required this.deviceId,
+    required this.modelId,
+    required this.name,
+    required this.imagePath,
+    required this.batteryStatus,
+  });
+}
+
+/// Progress of pairing with the selected device, sent to Flutter as it
+/// goes.
+@freezed
+sealed class PairingEvent with _$PairingEvent {
+  /// Pairing with the device was requested.
+  const factory PairingEvent.discovered() = PairingEvent_Discovered;
+
+  /// The device information of its model is being fetched.
+  const factory PairingEvent.fetchingMetadata() = PairingEvent_FetchingMetadata;
+
+  /// Pairing with the device itself started.
+  const factory PairingEvent.pairingStarted() = PairingEvent_PairingStarted;
+
+  /// The device proved it displays the same passkey.
+  const factory PairingEvent.passkeyConfirmed() = PairingEvent_PasskeyConfirmed;
+
+  /// The device was added to the user's devices, after initial pairing.
+  const factory PairingEvent.accountKeyWritten() =
+      PairingEvent_AccountKeyWritten;
+
+  /// The device is paired. Always the last event, unless pairing failed.
+  const factory PairingEvent.completed() = PairingEvent_Completed;
+
+  /// Pairing failed, for the given reason. Always the last event, unless
+  /// pairing completed.
+  const factory PairingEvent.failed({
+    required String reason,
+  }) = PairingEvent_Failed;
 }
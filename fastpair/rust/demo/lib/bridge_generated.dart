@@ -41,10 +41,10 @@ class RustImpl implements Rust {
         argNames: [],
       );
 
-  Stream<StringArray2?> eventStream({dynamic hint}) {
+  Stream<List<NearbyDevice>> eventStream({dynamic hint}) {
     return _platform.executeStream(FlutterRustBridgeTask(
       callFfi: (port_) => _platform.inner.wire_event_stream(port_),
-      parseSuccessData: _wire2api_opt_String_array_2,
+      parseSuccessData: _wire2api_list_nearby_device,
       constMeta: kEventStreamConstMeta,
       argValues: [],
       hint: hint,
@@ -57,12 +57,13 @@ class RustImpl implements Rust {
         argNames: [],
       );
 
-  Future<String> pair({dynamic hint}) {
-    return _platform.executeNormal(FlutterRustBridgeTask(
-      callFfi: (port_) => _platform.inner.wire_pair(port_),
-      parseSuccessData: _wire2api_String,
+  Stream<PairingEvent> pair({required String deviceId, dynamic hint}) {
+    var arg0 = _platform.api2wire_String(deviceId);
+    return _platform.executeStream(FlutterRustBridgeTask(
+      callFfi: (port_) => _platform.inner.wire_pair(port_, arg0),
+      parseSuccessData: _wire2api_pairing_event,
       constMeta: kPairConstMeta,
-      argValues: [],
+      argValues: [deviceId],
       hint: hint,
     ));
   }
@@ -70,15 +71,16 @@ class RustImpl implements Rust {
   FlutterRustBridgeTaskConstMeta get kPairConstMeta =>
       const FlutterRustBridgeTaskConstMeta(
         debugName: "pair",
-        argNames: [],
+        argNames: ["deviceId"],
       );
 
-  Future<void> dismiss({dynamic hint}) {
+  Future<void> dismiss({required String deviceId, dynamic hint}) {
+    var arg0 = _platform.api2wire_String(deviceId);
     return _platform.executeNormal(FlutterRustBridgeTask(
-      callFfi: (port_) => _platform.inner.wire_dismiss(port_),
+      callFfi: (port_) => _platform.inner.wire_dismiss(port_, arg0),
       parseSuccessData: _wire2api_unit,
       constMeta: kDismissConstMeta,
-      argValues: [],
+      argValues: [deviceId],
       hint: hint,
     ));
   }
@@ -86,7 +88,7 @@ class RustImpl implements Rust {
   FlutterRustBridgeTaskConstMeta get kDismissConstMeta =>
       const FlutterRustBridgeTaskConstMeta(
         debugName: "dismiss",
-        argNames: [],
+        argNames: ["deviceId"],
       );
 
   void dispose() {
@@ -98,16 +100,44 @@ class RustImpl implements Rust {
     return raw as String;
   }
 
-  StringArray2 _wire2api_String_array_2(dynamic raw) {
-    return StringArray2((raw as List<dynamic>).map(_wire2api_String).toList());
+  List<NearbyDevice> _wire2api_list_nearby_device(dynamic raw) {
+    return (raw as List<dynamic>).map(_wire2api_nearby_device).toList();
   }
 
-  List<String> _wire2api_list_String(dynamic raw) {
-    return (raw as List<dynamic>).map(_wire2api_String).toList();
+  NearbyDevice _wire2api_nearby_device(dynamic raw) {
+    final arr = raw as List<dynamic>;
+    if (arr.length != 5)
+      throw Exception('unexpected arr length: expect 5 but see ${arr.length}');
+    return NearbyDevice(
+      deviceId: _wire2api_String(arr[0]),
+      modelId: _wire2api_String(arr[1]),
+      name: _wire2api_String(arr[2]),
+      imagePath: _wire2api_String(arr[3]),
+      batteryStatus: _wire2api_String(arr[4]),
+    );
   }
 
-  StringArray2? _wire2api_opt_String_array_2(dynamic raw) {
-    return raw == null ? null : _wire2api_String_array_2(raw);
+  PairingEvent _wire2api_pairing_event(dynamic raw) {
+    switch (raw[0]) {
+      case 0:
+        return PairingEvent_Discovered();
+      case 1:
+        return PairingEvent_FetchingMetadata();
+      case 2:
+        return PairingEvent_PairingStarted();
+      case 3:
+        return PairingEvent_PasskeyConfirmed();
+      case 4:
+        return PairingEvent_AccountKeyWritten();
+      case 5:
+        return PairingEvent_Completed();
+      case 6:
+        return PairingEvent_Failed(
+          reason: _wire2api_String(raw[1]),
+        );
+      default:
+        throw Exception("unreachable");
+    }
   }
 
   int _wire2api_u8(dynamic raw) {
@@ -132,6 +162,18 @@ class RustPlatform extends FlutterRustBridgeBase<RustWire> {
 
 // Section: api2wire
 
+  @protected
+  ffi.Pointer<wire_uint_8_list> api2wire_String(String raw) {
+    return api2wire_uint_8_list(utf8.encoder.convert(raw));
+  }
+
+  @protected
+  ffi.Pointer<wire_uint_8_list> api2wire_uint_8_list(Uint8List raw) {
+    final ans = inner.new_uint_8_list_0(raw.length);
+    ans.ref.ptr.asTypedList(raw.length).setAll(0, raw);
+    return ans;
+  }
+
 // Section: finalizer
 
 // Section: api_fill_to_wire
@@ -260,27 +302,52 @@ class RustWire implements FlutterRustBridgeWireBase {
 
   void wire_pair(
     int port_,
+    ffi.Pointer<wire_uint_8_list> device_id,
   ) {
     return _wire_pair(
       port_,
+      device_id,
     );
   }
 
-  late final _wire_pairPtr =
-      _lookup<ffi.NativeFunction<ffi.Void Function(ffi.Int64)>>('wire_pair');
-  late final _wire_pair = _wire_pairPtr.asFunction<void Function(int)>();
+  late final _wire_pairPtr = _lookup<
+      ffi.NativeFunction<
+          ffi.Void Function(
+              ffi.Int64, ffi.Pointer<wire_uint_8_list>)>>('wire_pair');
+  late final _wire_pair = _wire_pairPtr
+      .asFunction<void Function(int, ffi.Pointer<wire_uint_8_list>)>();
 
   void wire_dismiss(
     int port_,
+    ffi.Pointer<wire_uint_8_list> device_id,
   ) {
     return _wire_dismiss(
       port_,
+      device_id,
     );
   }
 
-  late final _wire_dismissPtr =
-      _lookup<ffi.NativeFunction<ffi.Void Function(ffi.Int64)>>('wire_dismiss');
-  late final _wire_dismiss = _wire_dismissPtr.asFunction<void Function(int)>();
+  late final _wire_dismissPtr = _lookup<
+      ffi.NativeFunction<
+          ffi.Void Function(
+              ffi.Int64, ffi.Pointer<wire_uint_8_list>)>>('wire_dismiss');
+  late final _wire_dismiss = _wire_dismissPtr
+      .asFunction<void Function(int, ffi.Pointer<wire_uint_8_list>)>();
+
+  ffi.Pointer<wire_uint_8_list> new_uint_8_list_0(
+    int len,
+  ) {
+    return _new_uint_8_list_0(
+      len,
+    );
+  }
+
+  late final _new_uint_8_list_0Ptr = _lookup<
+      ffi.NativeFunction<
+          ffi.Pointer<wire_uint_8_list> Function(
+              ffi.Int32)>>('new_uint_8_list_0');
+  late final _new_uint_8_list_0 = _new_uint_8_list_0Ptr
+      .asFunction<ffi.Pointer<wire_uint_8_list> Function(int)>();
 
   void free_WireSyncReturn(
     WireSyncReturn ptr,
@@ -299,6 +366,13 @@ class RustWire implements FlutterRustBridgeWireBase {
 
 final class _Dart_Handle extends ffi.Opaque {}
 
+final class wire_uint_8_list extends ffi.Struct {
+  external ffi.Pointer<ffi.Uint8> ptr;
+
+  @ffi.Int32()
+  external int len;
+}
+
 typedef DartPostCObjectFnType = ffi.Pointer<
     ffi.NativeFunction<
         ffi.Bool Function(DartPort port_id, ffi.Pointer<ffi.Void> message)>>;
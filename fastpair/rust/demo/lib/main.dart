@@ -12,7 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+import 'dart:io';
+
 import 'package:flutter/material.dart';
+import 'package:demo/bridge_definitions.dart';
 import 'package:demo/rust.dart';
 
 void main() {
@@ -40,91 +43,119 @@ class HomePage extends StatelessWidget {
         appBar: AppBar(
           title: const Text("Fast Pair"),
         ),
-        body: Center(
-          child: StreamBuilder(
-            // Retrieve device info stream from Rust side.
-            stream: api.eventStream(),
-            builder: (context, deviceInfo) {
-              var deviceName = deviceInfo.data?[0];
-              var deviceImageUrl = deviceInfo.data?[1];
+        body: StreamBuilder(
+          // Retrieve nearby devices stream from Rust side, closest first.
+          stream: api.eventStream(),
+          builder: (context, nearbyDevices) {
+            var devices = nearbyDevices.data;
 
-              if (deviceInfo.hasData &&
-                  deviceName != null &&
-                  deviceImageUrl != null) {
-                return Column(
-                    mainAxisAlignment: MainAxisAlignment.center,
-                    crossAxisAlignment: CrossAxisAlignment.center,
-                    children: [
-                      Expanded(
-                          child: Image.network(deviceImageUrl,
-                              fit: BoxFit.contain)),
-                      Text(deviceName),
-                      // Spacing between device name text and buttons.
-                      const SizedBox(height: 20),
-                      Row(
-                        mainAxisAlignment: MainAxisAlignment.start,
-                        children: [
-                          // Spacing between left edge of screen and first button.
-                          const SizedBox(width: 20),
-                          OutlinedButton(
-                            // Invoke pairing dialog.
-                            onPressed: () => pairing(context),
-                            child: const Text('Pair'),
-                          ),
-                          // Spacing between buttons.
-                          const SizedBox(width: 20),
-                          OutlinedButton(
-                              onPressed: () => api.dismiss(),
-                              child: const Text('Dismiss'))
-                        ],
-                      ),
-                      // Spacing between buttons and bottom of screen.
-                      const SizedBox(height: 20),
-                    ]);
-              }
+            if (devices == null || devices.isEmpty) {
               return const Center(
                 child: CircularProgressIndicator(),
               );
-            },
-          ),
+            }
+            return ListView(
+              children: [
+                for (var device in devices) NearbyDeviceTile(device: device)
+              ],
+            );
+          },
         ),
       );
 }
 
-// Displays pairing dialog box.
-Future<String?> pairing(BuildContext context) => showDialog<String>(
-    context: context,
-    // Rust functions are invoked as futures.
-    builder: (context) => FutureBuilder(
-        future: api.pair(),
-        builder: (context, pairResult) {
-          var pairResultValue = pairResult.data;
+class NearbyDeviceTile extends StatelessWidget {
+  const NearbyDeviceTile({super.key, required this.device});
+
+  final NearbyDevice device;
 
-          return pairResult.hasData && pairResultValue != null
-              ? AlertDialog(
-                  title: const Text('Pairing result'),
-                  content: Text(pairResultValue),
-                  actions: <Widget>[
-                    TextButton(
-                      onPressed: () => Navigator.pop(context, 'OK'),
-                      child: const Text('OK'),
-                    )
-                  ],
-                )
-              : const AlertDialog(
-                  title: Text('Pairing...'),
-                  // Ensures the progress indicator has sensible dimensions,
-                  // otherwise it follows the height/width of the alert dialog.
-                  content: Column(
-                    mainAxisAlignment: MainAxisAlignment.center,
-                    mainAxisSize: MainAxisSize.min,
-                    children: <Widget>[
-                      SizedBox(
-                        width: 50,
-                        height: 50,
-                        child: CircularProgressIndicator(),
-                      ),
-                    ],
+  @override
+  Widget build(BuildContext context) => ListTile(
+        // Image cached by Rust, or a placeholder while it's downloading.
+        leading: SizedBox(
+            width: 60,
+            height: 60,
+            child: device.imagePath.isNotEmpty
+                ? Image.file(File(device.imagePath), fit: BoxFit.contain)
+                : const Icon(Icons.headphones, size: 40)),
+        title: Text(device.name),
+        // Battery status, if known.
+        subtitle: device.batteryStatus.isNotEmpty
+            ? Text(device.batteryStatus)
+            : null,
+        trailing: Row(
+          mainAxisSize: MainAxisSize.min,
+          children: [
+            OutlinedButton(
+              // Invoke pairing dialog.
+              onPressed: () => pairing(context, device.deviceId),
+              child: const Text('Pair'),
+            ),
+            // Spacing between buttons.
+            const SizedBox(width: 20),
+            OutlinedButton(
+                onPressed: () => api.dismiss(deviceId: device.deviceId),
+                child: const Text('Dismiss'))
+          ],
+        ),
+      );
+}
+
+// Displays pairing dialog box for the device with ID `deviceId`, following
+// the progress of pairing until it completes or fails.
+Future<String?> pairing(BuildContext context, String deviceId) {
+  // Rust streams are started once, rather than every time the dialog is built.
+  var pairingEvents = api.pair(deviceId: deviceId);
+
+  return showDialog<String>(
+      context: context,
+      builder: (context) => StreamBuilder(
+          stream: pairingEvents,
+          builder: (context, pairingEvent) => switch (pairingEvent.data) {
+                PairingEvent_Completed() =>
+                  pairingResult(context, 'Pairing success!'),
+                PairingEvent_Failed(:final reason) =>
+                  pairingResult(context, reason),
+                var event => AlertDialog(
+                    title: const Text('Pairing...'),
+                    // Ensures the progress indicator has sensible dimensions,
+                    // otherwise it follows the height/width of the alert
+                    // dialog.
+                    content: Column(
+                      mainAxisAlignment: MainAxisAlignment.center,
+                      mainAxisSize: MainAxisSize.min,
+                      children: <Widget>[
+                        const SizedBox(
+                          width: 50,
+                          height: 50,
+                          child: CircularProgressIndicator(),
+                        ),
+                        // Spacing between progress indicator and step.
+                        const SizedBox(height: 20),
+                        Text(pairingStep(event)),
+                      ],
+                    ),
                   ),
-                );
-        }));
+              }));
+}
+
+// Describes the step of pairing following `event`.
+String pairingStep(PairingEvent? event) => switch (event) {
+      PairingEvent_FetchingMetadata() => 'Fetching device information...',
+      PairingEvent_PairingStarted() => 'Connecting to the device...',
+      PairingEvent_PasskeyConfirmed() => 'Passkey confirmed, bonding...',
+      PairingEvent_AccountKeyWritten() => 'Saved to your devices.',
+      _ => 'Starting...',
+    };
+
+// Displays the result of pairing, until dismissed.
+Widget pairingResult(BuildContext context, String result) => AlertDialog(
+      title: const Text('Pairing result'),
+      content: Text(result),
+      actions: <Widget>[
+        TextButton(
+          onPressed: () => Navigator.pop(context, 'OK'),
+          child: const Text('OK'),
+        )
+      ],
+    );
@@ -0,0 +1,112 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use rand::{rngs::OsRng, RngCore};
+
+use crate::{advertisement::ModelId, error::FpError};
+
+/// Length of an account key, in bytes.
+pub(crate) const ACCOUNT_KEY_LENGTH: usize = 16;
+
+/// First byte of every account key, identifying its type.
+const ACCOUNT_KEY_TYPE: u8 = 0x04;
+
+/// Key written by the Seeker to a Provider at the end of initial pairing,
+/// shared by all the Seekers of the user. It lets the Provider advertise
+/// itself to them when not discoverable, and pair again without the
+/// anti-spoofing key.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub(crate) struct AccountKey([u8; ACCOUNT_KEY_LENGTH]);
+
+impl AccountKey {
+    /// Generate a new random account key.
+    pub(crate) fn generate() -> Self {
+        let mut key = [0; ACCOUNT_KEY_LENGTH];
+        OsRng.fill_bytes(&mut key[1..]);
+        key[0] = ACCOUNT_KEY_TYPE;
+        AccountKey(key)
+    }
+
+    /// Construct an account key from raw bytes without checking its type,
+    /// e.g. for the keys of the specification's test cases.
+    #[cfg(test)]
+    pub(crate) fn from_raw(key: [u8; ACCOUNT_KEY_LENGTH]) -> Self {
+        AccountKey(key)
+    }
+
+    /// Retrieve the raw bytes of the account key.
+    pub(crate) fn as_bytes(&self) -> &[u8; ACCOUNT_KEY_LENGTH] {
+        &self.0
+    }
+}
+
+impl TryFrom<&[u8]> for AccountKey {
+    type Error = FpError;
+
+    fn try_from(key: &[u8]) -> Result<Self, Self::Error> {
+        let key = <[u8; ACCOUNT_KEY_LENGTH]>::try_from(key).map_err(|_| {
+            FpError::ContractViolation(format!(
+                "account key of length {}, expected {}",
+                key.len(),
+                ACCOUNT_KEY_LENGTH
+            ))
+        })?;
+
+        if key[0] != ACCOUNT_KEY_TYPE {
+            return Err(FpError::ContractViolation(format!(
+                "account key of type {:#04x}",
+                key[0]
+            )));
+        }
+
+        Ok(AccountKey(key))
+    }
+}
+
+/// Types that can persist the account keys written to the user's devices,
+/// so that they can be recognized and paired again later.
+pub(crate) trait AccountKeyStore {
+    /// Retrieve all stored account keys.
+    fn account_keys(&self) -> Result<Vec<AccountKey>, FpError>;
+
+    /// Retrieve the model ID of the device `account_key` was written to, if
+    /// the key is stored.
+    fn model_id(&self, account_key: &AccountKey) -> Result<Option<ModelId>, FpError>;
+
+    /// Store a new account key written to a device with the given model ID,
+    /// if not already stored.
+    fn add_account_key(&self, account_key: AccountKey, model_id: ModelId) -> Result<(), FpError>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_account_key() {
+        let key = AccountKey::generate();
+        assert_eq!(key.as_bytes()[0], ACCOUNT_KEY_TYPE);
+        assert_ne!(key, AccountKey::generate());
+        assert_eq!(AccountKey::try_from(&key.as_bytes()[..]), Ok(key));
+    }
+
+    #[test]
+    fn test_account_key_try_from_invalid() {
+        let result = AccountKey::try_from(&[ACCOUNT_KEY_TYPE; 15][..]);
+        assert!(matches!(result, Err(FpError::ContractViolation(_))));
+
+        let result = AccountKey::try_from(&[0x05; ACCOUNT_KEY_LENGTH][..]);
+        assert!(matches!(result, Err(FpError::ContractViolation(_))));
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::{account_key::AccountKey, crypto::sha256};
+
+/// Bloom filter of the account keys stored on a Provider, advertised when it
+/// isn't discoverable so that the Seekers of its owner can recognize it.
+/// See: https://developers.google.com/nearby/fast-pair/specifications/service/provider#AccountKeyFilter
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct AccountKeyFilter(Vec<u8>);
+
+impl AccountKeyFilter {
+    /// Construct a filter from its advertised bytes.
+    pub(crate) fn new(filter: Vec<u8>) -> Self {
+        AccountKeyFilter(filter)
+    }
+
+    /// Build the filter a Provider storing `account_keys` advertises along
+    /// with `salt` and the raw `battery` field.
+    #[cfg(test)]
+    pub(crate) fn build(account_keys: &[AccountKey], salt: &[u8], battery: Option<&[u8]>) -> Self {
+        let size = (account_keys.len() * 6 / 5) + 3;
+        let mut filter = AccountKeyFilter(vec![0; size]);
+        for account_key in account_keys {
+            for index in filter.bit_indices(account_key, salt, battery) {
+                filter.0[index / 8] |= 1 << (index % 8);
+            }
+        }
+        filter
+    }
+
+    /// Retrieve the advertised bytes of the filter.
+    #[cfg(test)]
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Check whether `account_key` is in the filter, advertised along with
+    /// `salt` and the raw `battery` field. False positives are possible,
+    /// at a low rate.
+    pub(crate) fn contains(
+        &self,
+        account_key: &AccountKey,
+        salt: &[u8],
+        battery: Option<&[u8]>,
+    ) -> bool {
+        !self.0.is_empty()
+            && self
+                .bit_indices(account_key, salt, battery)
+                .all(|index| self.0[index / 8] & (1 << (index % 8)) != 0)
+    }
+
+    /// Compute the indices of the bits set for `account_key`, from the
+    /// SHA-256 hash of the key concatenated with the salt and the battery
+    /// field, split into 8 big-endian integers.
+    fn bit_indices(
+        &self,
+        account_key: &AccountKey,
+        salt: &[u8],
+        battery: Option<&[u8]>,
+    ) -> impl Iterator<Item = usize> {
+        let hash = sha256(&[account_key.as_bytes(), salt, battery.unwrap_or_default()]);
+
+        let size = self.0.len() as u32 * 8;
+        (0..8).map(move |i| {
+            let chunk = [
+                hash[4 * i],
+                hash[4 * i + 1],
+                hash[4 * i + 2],
+                hash[4 * i + 3],
+            ];
+            (u32::from_be_bytes(chunk) % size) as usize
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account_key(byte: u8) -> AccountKey {
+        let mut key = [byte; 16];
+        key[0] = 0x04;
+        AccountKey::try_from(&key[..]).unwrap()
+    }
+
+    #[test]
+    fn test_account_key_filter_contains() {
+        let account_keys = [account_key(0x11), account_key(0x22)];
+        let salt = [0xC7];
+        let battery = [0x33, 0x40, 0x40, 0xE4];
+        let filter = AccountKeyFilter::build(&account_keys, &salt, Some(&battery));
+
+        for account_key in &account_keys {
+            assert!(filter.contains(account_key, &salt, Some(&battery)));
+        }
+        assert!(!filter.contains(&account_keys[0], &salt, None));
+    }
+
+    #[test]
+    fn test_account_key_filter_spec() {
+        // Fast Pair specification, account key filter test cases.
+        let account_key = AccountKey::from_raw([
+            0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0x00, 0xAA, 0xBB, 0xCC, 0xDD,
+            0xEE, 0xFF,
+        ]);
+        let salt = [0xC7];
+        let battery = [0x33, 0x40, 0x40, 0xE4];
+
+        let filter = AccountKeyFilter::build(&[account_key], &salt, None);
+        assert_eq!(filter.as_bytes(), [0x0A, 0x42, 0x88, 0x10]);
+        assert!(filter.contains(&account_key, &salt, None));
+
+        let filter = AccountKeyFilter::build(&[account_key], &salt, Some(&battery));
+        assert_eq!(filter.as_bytes(), [0x10, 0x10, 0x82, 0x2A]);
+        assert!(filter.contains(&account_key, &salt, Some(&battery)));
+    }
+
+    #[test]
+    fn test_account_key_filter_missing_key() {
+        let filter = AccountKeyFilter::build(&[account_key(0x11)], &[0xC7], None);
+
+        assert!(!filter.contains(&account_key(0x22), &[0xC7], None));
+        assert!(!filter.contains(&account_key(0x11), &[0xC8], None));
+        assert!(!AccountKeyFilter::new(Vec::new()).contains(&account_key(0x11), &[0xC7], None));
+    }
+}
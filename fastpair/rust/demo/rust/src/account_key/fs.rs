@@ -0,0 +1,141 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{fs, io::ErrorKind};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    account_key::{AccountKey, AccountKeyStore},
+    advertisement::ModelId,
+    error::FpError,
+};
+
+/// Account key as saved in JSON, encoded in base64, along with the model ID
+/// of the device it was written to.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AccountKeyJson {
+    account_key: String,
+    model_id: ModelId,
+}
+
+/// Top-level contents of the JSON file.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AccountKeysJson {
+    account_keys: Vec<AccountKeyJson>,
+}
+
+/// A struct for storing account keys in a JSON file on the local
+/// filesystem.
+pub(crate) struct AccountKeyStoreFs {
+    path: String,
+}
+
+impl AccountKeyStoreFs {
+    pub(crate) fn new(path: String) -> Self {
+        AccountKeyStoreFs { path }
+    }
+
+    /// Read the account keys saved in the file along with their model IDs,
+    /// or none if the file doesn't exist yet.
+    fn read(&self) -> Result<Vec<(AccountKey, ModelId)>, FpError> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(FpError::AccessDenied(err.to_string())),
+        };
+
+        let json: AccountKeysJson = serde_json::from_str(&contents)
+            .map_err(|err| FpError::ContractViolation(err.to_string()))?;
+
+        json.account_keys
+            .into_iter()
+            .map(|entry| {
+                let key = STANDARD
+                    .decode(entry.account_key)
+                    .map_err(|err| FpError::ContractViolation(err.to_string()))?;
+                Ok((AccountKey::try_from(key.as_slice())?, entry.model_id))
+            })
+            .collect()
+    }
+
+    /// Save the account keys along with their model IDs, rewriting the
+    /// whole file.
+    fn write(&self, entries: Vec<(AccountKey, ModelId)>) -> Result<(), FpError> {
+        let json = AccountKeysJson {
+            account_keys: entries
+                .into_iter()
+                .map(|(account_key, model_id)| AccountKeyJson {
+                    account_key: STANDARD.encode(account_key.as_bytes()),
+                    model_id,
+                })
+                .collect(),
+        };
+        let contents = serde_json::to_string_pretty(&json)
+            .map_err(|err| FpError::Internal(err.to_string()))?;
+
+        fs::write(&self.path, contents).map_err(|err| FpError::AccessDenied(err.to_string()))
+    }
+}
+
+impl AccountKeyStore for AccountKeyStoreFs {
+    fn account_keys(&self) -> Result<Vec<AccountKey>, FpError> {
+        Ok(self.read()?.into_iter().map(|(key, _)| key).collect())
+    }
+
+    fn model_id(&self, account_key: &AccountKey) -> Result<Option<ModelId>, FpError> {
+        Ok(self
+            .read()?
+            .into_iter()
+            .find(|(key, _)| key == account_key)
+            .map(|(_, model_id)| model_id))
+    }
+
+    fn add_account_key(&self, account_key: AccountKey, model_id: ModelId) -> Result<(), FpError> {
+        let mut entries = self.read()?;
+        if entries.iter().any(|(key, _)| *key == account_key) {
+            return Ok(());
+        }
+        entries.push((account_key, model_id));
+        self.write(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{env, process};
+
+    #[test]
+    fn test_account_key_store_fs() {
+        let path = env::temp_dir().join(format!("account_keys_{}.json", process::id()));
+        let store = AccountKeyStoreFs::new(path.to_string_lossy().into_owned());
+        assert_eq!(store.account_keys(), Ok(Vec::new()));
+
+        let key1 = AccountKey::generate();
+        let key2 = AccountKey::generate();
+        store.add_account_key(key1, String::from("525296")).unwrap();
+        store.add_account_key(key2, String::from("706908")).unwrap();
+        store.add_account_key(key1, String::from("525296")).unwrap();
+        assert_eq!(store.account_keys(), Ok(vec![key1, key2]));
+        assert_eq!(store.model_id(&key2), Ok(Some(String::from("706908"))));
+        assert_eq!(store.model_id(&AccountKey::generate()), Ok(None));
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -14,7 +14,10 @@
 
 use bluetooth::{BleAddress, BleAdvertisement, ServiceData};
 
-use crate::{decoder::FpDecoder, error::FpError, fetcher::FpFetcher};
+use crate::{
+    account_key::AccountKey, battery::BatteryStatus, decoder::FpDecoder, error::FpError,
+    fetcher::FpFetcher,
+};
 
 /// Represents a FP device model ID.
 pub(crate) type ModelId = String;
@@ -29,13 +32,54 @@ pub(crate) struct FpPairingAdvertisement {
     model_id: ModelId,
     device_name: String,
     image_url: String,
+    /// Account key stored on the device, if it's one of the user's devices
+    /// advertising while not in pairing mode.
+    account_key: Option<AccountKey>,
+    /// Latest battery status of the device, either advertised or sent
+    /// through the Message Stream once paired.
+    battery_status: Option<BatteryStatus>,
 }
 
 impl FpPairingAdvertisement {
     /// Create a new Fast Pair advertisement instance.
     pub(crate) fn new(
         adv: BleAdvertisement,
-        service_data: &ServiceData<u16>,
+        service_data: &ServiceData,
+        fetcher: &Box<dyn FpFetcher>,
+    ) -> Result<Self, FpError> {
+        // Extract model ID from service data. We don't need to store service
+        // data in the `FpPairingAdvertisement` since it's easily accessible from
+        // `FpPairingAdvertisement.inner`, but it's convenient to save the parsed
+        // model ID.
+        let model_id = FpDecoder::get_model_id_from_service_data(service_data)?;
+
+        // Pad with 0 at the beginning to successfully call `from_be_bytes`.
+        // Model IDs are at most 14 bytes long, so they fit in a `u128`.
+        let mut padded = [0; 16];
+        padded[16 - model_id.len()..].copy_from_slice(&model_id);
+        let model_id = format!("{}", u128::from_be_bytes(padded));
+
+        Self::from_model_id(adv, model_id, None, None, fetcher)
+    }
+
+    /// Create a Fast Pair advertisement instance for one of the user's
+    /// devices, recognized by the account key it stores although it isn't
+    /// in pairing mode.
+    pub(crate) fn new_user_device(
+        adv: BleAdvertisement,
+        model_id: ModelId,
+        account_key: AccountKey,
+        battery_status: Option<BatteryStatus>,
+        fetcher: &Box<dyn FpFetcher>,
+    ) -> Result<Self, FpError> {
+        Self::from_model_id(adv, model_id, Some(account_key), battery_status, fetcher)
+    }
+
+    fn from_model_id(
+        adv: BleAdvertisement,
+        model_id: ModelId,
+        account_key: Option<AccountKey>,
+        battery_status: Option<BatteryStatus>,
         fetcher: &Box<dyn FpFetcher>,
     ) -> Result<Self, FpError> {
         let rssi = adv.rssi().ok_or(FpError::ContractViolation(String::from(
@@ -49,28 +93,6 @@ impl FpPairingAdvertisement {
 
         let distance = distance_from_rssi_and_tx_power(rssi, tx_power);
 
-        // Extract model ID from service data. We don't need to store service
-        // data in the `FpPairingAdvertisement` since it's easily accessible from
-        // `FpPairingAdvertisement.inner`, but it's convenient to save the parsed
-        // model ID.
-        let mut model_id = FpDecoder::get_model_id_from_service_data(service_data)?;
-
-        if model_id.len() != 3 {
-            // In this demo of Fast Pair Rust, only model ID's
-            // of length 3 bytes are supported. Therefore, if a
-            // larger model ID makes it this far, log an error.
-            // TODO b/294453912
-            return Err(FpError::Internal(String::from(
-                "creating `model_id` should have already failed",
-            )));
-        }
-
-        // Pad with 0 at the beginning to successfully call `from_be_bytes`.
-        // Assumes `model_id.len() == 3` before the call to `insert`, otherwise
-        // this will panic.
-        model_id.insert(0, 0);
-        let model_id = format!("{}", u32::from_be_bytes(model_id.try_into().unwrap()));
-
         // Retrieve device info of the device corresponding to this model ID.
         let device_info = fetcher.get_device_info_from_model_id(&model_id)?;
 
@@ -80,6 +102,8 @@ impl FpPairingAdvertisement {
             model_id,
             device_name: device_info.name().to_string(),
             image_url: device_info.image_url().to_string(),
+            account_key,
+            battery_status,
         })
     }
 
@@ -107,6 +131,23 @@ impl FpPairingAdvertisement {
     pub(crate) fn image_url(&self) -> &String {
         &self.image_url
     }
+
+    /// Retrieve the account key stored on the device, if it's one of the
+    /// user's devices.
+    pub(crate) fn account_key(&self) -> Option<&AccountKey> {
+        self.account_key.as_ref()
+    }
+
+    /// Retrieve the latest known battery status of the device.
+    pub(crate) fn battery_status(&self) -> Option<BatteryStatus> {
+        self.battery_status
+    }
+
+    /// Update the battery status of the device, e.g. after it sent one
+    /// through the Message Stream.
+    pub(crate) fn set_battery_status(&mut self, battery_status: BatteryStatus) {
+        self.battery_status = Some(battery_status);
+    }
 }
 
 /// Convert RSSI and transmit power to distance using log-distance path loss
@@ -146,7 +187,7 @@ mod tests {
     use super::*;
     use crate::fetcher::{mock::FpFetcherMock, DeviceInfo};
 
-    use bluetooth::BleAddressKind;
+    use bluetooth::{BleAddressKind, Uuid};
 
     #[test]
     fn test_new_fp_pairing_advertisement() {
@@ -155,7 +196,7 @@ mod tests {
 
         let raw_data = vec![3, 2, 1];
         let expected_model_id = "197121"; // (3 << 16) + (2 << 8) + 1.
-        let service_data = ServiceData::new(0x123 as u16, raw_data);
+        let service_data = ServiceData::new(Uuid::from_u16(0x123), raw_data);
 
         let image_url = String::from("image_url");
         let device_name = String::from("name");
@@ -172,13 +213,66 @@ mod tests {
         assert_eq!(fp_adv.model_id(), &expected_model_id);
     }
 
+    #[test]
+    fn test_new_fp_pairing_advertisement_long_model_id() {
+        let addr = BleAddress::new(0x112233, BleAddressKind::Public);
+        let ble_adv = BleAdvertisement::new(addr, Some(-60), Some(10));
+
+        // Header with a length of 4, then the model ID.
+        let raw_data = vec![0b00001000, 4, 3, 2, 1];
+        let expected_model_id = "67305985"; // (4 << 24) + (3 << 16) + (2 << 8) + 1.
+        let service_data = ServiceData::new(Uuid::from_u16(0x123), raw_data);
+
+        let device_info = Ok(DeviceInfo::new(
+            String::from("image_url"),
+            String::from("name"),
+        ));
+        let fetcher: Box<dyn FpFetcher> = Box::new(FpFetcherMock::new(device_info));
+
+        let fp_adv = FpPairingAdvertisement::new(ble_adv, &service_data, &fetcher);
+
+        assert_eq!(fp_adv.unwrap().model_id(), expected_model_id);
+    }
+
+    #[test]
+    fn test_new_user_device_advertisement() {
+        let addr = BleAddress::new(0x112233, BleAddressKind::Public);
+        let ble_adv = BleAdvertisement::new(addr, Some(-60), Some(10));
+
+        let device_info = Ok(DeviceInfo::new(
+            String::from("image_url"),
+            String::from("name"),
+        ));
+        let fetcher: Box<dyn FpFetcher> = Box::new(FpFetcherMock::new(device_info));
+        let account_key = AccountKey::generate();
+        let battery_status = BatteryStatus::try_from([0x40, 0xE4].as_slice()).unwrap();
+
+        let fp_adv = FpPairingAdvertisement::new_user_device(
+            ble_adv,
+            String::from("197121"),
+            account_key,
+            Some(battery_status),
+            &fetcher,
+        );
+
+        assert!(fp_adv.is_ok());
+        let mut fp_adv = fp_adv.unwrap();
+        assert_eq!(fp_adv.model_id(), "197121");
+        assert_eq!(fp_adv.account_key(), Some(&account_key));
+        assert_eq!(fp_adv.battery_status(), Some(battery_status));
+
+        let battery_status = BatteryStatus::try_from([0x7F].as_slice()).unwrap();
+        fp_adv.set_battery_status(battery_status);
+        assert_eq!(fp_adv.battery_status(), Some(battery_status));
+    }
+
     #[test]
     fn test_new_fp_pairing_advertisement_bad_rssi() {
         let addr = BleAddress::new(0x112233, BleAddressKind::Public);
         let ble_adv = BleAdvertisement::new(addr, None, Some(10));
 
         let raw_data = vec![3, 2, 1];
-        let service_data = ServiceData::new(0x123 as u16, raw_data);
+        let service_data = ServiceData::new(Uuid::from_u16(0x123), raw_data);
 
         let device_info = Ok(DeviceInfo::new(
             String::from("image_url"),
@@ -198,7 +292,7 @@ mod tests {
         let ble_adv = BleAdvertisement::new(addr, Some(-60), None);
 
         let raw_data = vec![3, 2, 1];
-        let service_data = ServiceData::new(0x123 as u16, raw_data);
+        let service_data = ServiceData::new(Uuid::from_u16(0x123), raw_data);
 
         let device_info = Ok(DeviceInfo::new(
             String::from("image_url"),
@@ -218,7 +312,7 @@ mod tests {
         let ble_adv = BleAdvertisement::new(addr, Some(-60), Some(10));
 
         let raw_data = vec![3, 2, 1];
-        let service_data = ServiceData::new(0x123 as u16, raw_data);
+        let service_data = ServiceData::new(Uuid::from_u16(0x123), raw_data);
 
         let fetcher: Box<dyn FpFetcher> = Box::new(FpFetcherMock::new(Err(FpError::Test)));
 
@@ -12,71 +12,221 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{collections::HashMap, sync::RwLock, time::Duration};
+use std::{
+    sync::RwLock,
+    thread,
+    time::{Duration, Instant},
+};
 
 use bluetooth::{
-    api::{BleAdapter, ClassicDevice},
-    BleAdvertisement, BleDataTypeId, ClassicAddress, PairingResult, Platform, ServiceData,
+    api::BleAdapter, BleAddress, BleAddressKind, BleAdvertisement, BleDataTypeId, BluetoothError,
+    ClassicAddress, MessageStreamClient, PairingResult, Platform, RetryPolicy, ServiceData, Uuid,
 };
 use flutter_rust_bridge::StreamSink;
-use futures::executor;
+use futures::{
+    executor,
+    future::{self, Either},
+    pin_mut,
+};
 use tracing::{info, warn};
 use ttl_cache::TtlCache;
 
 use crate::{
-    advertisement::{FpPairingAdvertisement, ModelId},
-    fetcher::{FpFetcher, FpFetcherFs},
+    account_key::{AccountKeyStore, AccountKeyStoreFs},
+    advertisement::FpPairingAdvertisement,
+    battery::{BatteryHandler, BatteryStatus},
+    decoder::FpDecoder,
+    error::FpError,
+    fetcher::{FpFetcher, FpFetcherCache, FpFetcherFs, FpImageCache, HttpImageDownloader},
+    nearby::NearbyDevices,
+    seeker::{FastPairSeeker, SeekerState},
+    timer::sleep_until,
 };
 
-// Sends a device name to Flutter via `StreamSink` FFI layer.
-static DEVICE_STREAM: RwLock<Option<StreamSink<Option<[String; 2]>>>> = RwLock::new(None);
+// Sends the nearby devices, closest first, to Flutter via `StreamSink` FFI
+// layer.
+static DEVICE_STREAM: RwLock<Option<StreamSink<Vec<NearbyDevice>>>> = RwLock::new(None);
+
+// Saves the latest advertisement of each nearby device, to be used for
+// pairing.
+static NEARBY_DEVICES: RwLock<Option<NearbyDevices>> = RwLock::new(None);
 
-// Saves the currently displayed device's advertisement, to be used for pairing.
-static CURR_DEVICE_ADV: RwLock<Option<FpPairingAdvertisement>> = RwLock::new(None);
+// Saves the devices last sent to Flutter, so that they're only sent again
+// once they change.
+static DISPLAYED_DEVICES: RwLock<Vec<NearbyDevice>> = RwLock::new(Vec::new());
 
-// Temporarily restricts which model IDs can be displayed.
-static MODEL_ID_BLACKLIST: RwLock<Option<TtlCache<ModelId, ()>>> = RwLock::new(None);
+// Caches the images of the models displayed on the local filesystem.
+static IMAGE_CACHE: RwLock<Option<FpImageCache<HttpImageDownloader>>> = RwLock::new(None);
+
+// Temporarily restricts which devices can be displayed.
+static DEVICE_BLACKLIST: RwLock<Option<TtlCache<BleAddress, ()>>> = RwLock::new(None);
 
 // Specifies how long entries should blacklisted for.
 const TTL_BLACKLIST: Duration = Duration::from_secs(10);
 
-/// Updates the device name as displayed by Flutter.
-#[inline]
-async fn update_best_device(best_adv: FpPairingAdvertisement) {
+// Specifies how many devices are displayed at most, and how long they're
+// displayed for after they stop advertising.
+const MAX_NEARBY_DEVICES: usize = 16;
+const TTL_NEARBY_DEVICE: Duration = Duration::from_secs(10);
+
+// Directory of the JSON files holding the device information of each model.
+const JSON_PATH: &str = "./local";
+
+// File storing the account keys written to the user's devices.
+const ACCOUNT_KEYS_PATH: &str = "./local/account_keys.json";
+
+// Directory caching the device information of the models seen.
+const DEVICE_INFO_CACHE_PATH: &str = "./local/cache";
+
+// Specifies how long cached device information is used before being
+// fetched again.
+const TTL_DEVICE_INFO: Duration = Duration::from_secs(24 * 60 * 60);
+
+// Directory caching the images of the models displayed.
+const IMAGE_CACHE_PATH: &str = "./local/images";
+
+// Specifies how long to wait for an image to download, and before
+// downloading it again after failing to.
+const TIMEOUT_IMAGE_DOWNLOAD: Duration = Duration::from_secs(10);
+const RETRY_IMAGE_DOWNLOAD: Duration = Duration::from_secs(60);
+
+// Specifies how long each GATT step of pairing and bonding may take, and how
+// many times Key-based Pairing is attempted, backing off in between.
+const TIMEOUT_GATT: Duration = Duration::from_secs(10);
+const TIMEOUT_BONDING: Duration = Duration::from_secs(30);
+const ATTEMPTS_KEY_BASED_PAIRING: u32 = 3;
+const BACKOFF_KEY_BASED_PAIRING: Duration = Duration::from_millis(500);
+
+/// A nearby Fast Pair device, as displayed by Flutter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NearbyDevice {
+    /// Address of the device in the "AA:BB:CC:DD:EE:FF" form, identifying it
+    /// to `pair` and `dismiss`.
+    pub device_id: String,
+    pub model_id: String,
+    pub name: String,
+    /// Path of the image of the model, empty until it's downloaded.
+    pub image_path: String,
+    /// Battery status of the device, empty if it's unknown.
+    pub battery_status: String,
+}
+
+/// Progress of pairing with the selected device, sent to Flutter as it
+/// goes.
+#[derive(Debug, PartialEq)]
+pub enum PairingEvent {
+    /// Pairing with the device was requested.
+    Discovered,
+    /// The device information of its model is being fetched.
+    FetchingMetadata,
+    /// Pairing with the device itself started.
+    PairingStarted,
+    /// The device proved it displays the same passkey.
+    PasskeyConfirmed,
+    /// The device was added to the user's devices, after initial pairing.
+    AccountKeyWritten,
+    /// The device is paired. Always the last event, unless pairing failed.
+    Completed,
+    /// Pairing failed, for the given reason. Always the last event, unless
+    /// pairing completed.
+    Failed { reason: String },
+}
+
+/// Retrieves the information of a device to be displayed by Flutter. Once
+/// its image is downloaded, the devices are displayed again.
+fn nearby_device(adv: &FpPairingAdvertisement) -> NearbyDevice {
+    let image_path = match IMAGE_CACHE.read().unwrap().as_ref() {
+        Some(cache) => cache.image_path(adv.model_id(), adv.image_url(), |_| display_devices()),
+        None => None,
+    };
+
+    NearbyDevice {
+        device_id: adv.address().to_string(),
+        model_id: adv.model_id().to_owned(),
+        name: adv.device_name().to_string(),
+        image_path: image_path.unwrap_or_default(),
+        battery_status: adv
+            .battery_status()
+            .map(|status| status.to_string())
+            .unwrap_or_default(),
+    }
+}
+
+/// Sends the nearby devices to be displayed by Flutter, closest first, unless
+/// they're the same as last sent.
+fn display_devices() {
+    let devices: Vec<_> = match NEARBY_DEVICES.write().unwrap().as_mut() {
+        Some(nearby_devices) => nearby_devices
+            .ranked()
+            .into_iter()
+            .map(nearby_device)
+            .collect(),
+        None => return,
+    };
+
+    let mut displayed_devices = DISPLAYED_DEVICES.write().unwrap();
+    if *displayed_devices == devices {
+        return;
+    }
     match DEVICE_STREAM.read().unwrap().as_ref() {
         Some(stream) => {
-            stream.add(Some([
-                best_adv.device_name().to_string(),
-                best_adv.image_url().to_string(),
-            ]));
+            stream.add(devices.clone());
         }
-        None => info!("Name stream is None"),
+        None => info!("Device stream is None"),
+    }
+    *displayed_devices = devices;
+}
+
+/// Saves the latest advertisement of a nearby device, and updates the
+/// devices displayed by Flutter.
+fn update_nearby_device(adv: FpPairingAdvertisement) {
+    if let Some(nearby_devices) = NEARBY_DEVICES.write().unwrap().as_mut() {
+        nearby_devices.update(adv);
     }
-    let mut curr_adv = CURR_DEVICE_ADV.write().unwrap();
-    *curr_adv = Some(best_adv);
+    display_devices();
 }
 
-/// Determines whether the device advertised by the provided service data is the
-/// closest Fast Pair device.
-/// If this device has been seen previously but has now moved further away,
-/// decide which other seen device is now closer.
+/// Updates the battery status displayed for the device with address
+/// `address`, if it's still nearby.
+fn update_battery_status(address: &BleAddress, battery_status: BatteryStatus) {
+    if let Some(adv) = NEARBY_DEVICES
+        .write()
+        .unwrap()
+        .as_mut()
+        .and_then(|nearby_devices| nearby_devices.get_mut(address))
+    {
+        adv.set_battery_status(battery_status);
+    }
+    display_devices();
+}
+
+/// Creates an advertisement for the Fast Pair device advertising the provided
+/// service data, if it's to be displayed.
 #[inline]
-fn new_best_fp_advertisement(
+fn new_fp_advertisement(
     advertisement: BleAdvertisement,
-    service_data: &ServiceData<u16>,
+    service_data: &ServiceData,
     fetcher: &Box<dyn FpFetcher>,
-    latest_advertisement_map: &mut HashMap<String, FpPairingAdvertisement>,
+    account_key_store: &dyn AccountKeyStore,
 ) -> Option<FpPairingAdvertisement> {
     // Analyze service data sections.
     let uuid = service_data.uuid();
 
     // This is not a Fast Pair device.
-    if uuid != 0x2cfe {
+    if uuid != Uuid::from_u16(0xFE2C) {
         return None;
     }
 
-    let fp_adv = match FpPairingAdvertisement::new(advertisement, service_data, fetcher) {
-        Ok(fp_adv) => fp_adv,
+    let fp_adv = if FpDecoder::is_discoverable(service_data) {
+        FpPairingAdvertisement::new(advertisement, service_data, fetcher).map(Some)
+    } else {
+        // Devices not in pairing mode are only displayed if they're the user's.
+        new_user_device_advertisement(advertisement, service_data, fetcher, account_key_store)
+    };
+
+    let fp_adv = match fp_adv {
+        Ok(Some(fp_adv)) => fp_adv,
+        Ok(None) => return None,
         Err(err) => {
             // If error during construction (e.g. non-discoverable
             // Fast Pair device not advertising tx_power or
@@ -88,88 +238,125 @@ fn new_best_fp_advertisement(
     };
 
     // If blacklisted in TTL cache, skip this advertisement.
-    let blacklisted = match MODEL_ID_BLACKLIST.read().unwrap().as_ref() {
-        Some(cache) => cache.get(fp_adv.model_id()).is_some(),
+    let blacklisted = match DEVICE_BLACKLIST.read().unwrap().as_ref() {
+        Some(cache) => cache.get(&fp_adv.address()).is_some(),
         None => false,
     };
     if blacklisted {
-        latest_advertisement_map.remove(fp_adv.model_id());
         return None;
     }
 
-    // Else, this advertisement is now the most recent advertisement by the
-    // device with model ID `fp_adv.model_id()`.
-    latest_advertisement_map.insert(fp_adv.model_id().to_owned(), fp_adv.clone());
-
-    if let Some(best_adv) = CURR_DEVICE_ADV.read().unwrap().as_ref() {
-        if best_adv.distance() >= fp_adv.distance() {
-            // New advertised distance is closer.
-            Some(fp_adv)
-        } else if best_adv.model_id() == fp_adv.model_id() {
-            // New advertised distance by the previous best device has
-            // increased, so select new closest device.
-            let next_best_adv_ref =
-                latest_advertisement_map
-                    .values()
-                    .into_iter()
-                    .min_by(|adv1, adv2| {
-                        // We should never get NaN, so it's okay to unwrap.
-                        adv1.distance().partial_cmp(&adv2.distance()).unwrap()
-                    });
-
-            // If next closest device exists, it's now the closest!
-            if let Some(next_best_adv) = next_best_adv_ref {
-                Some(next_best_adv.to_owned())
-            } else {
-                None
-            }
-        } else {
-            None
-        }
-    } else {
-        // First discovered device, must be closest.
-        Some(fp_adv)
+    Some(fp_adv)
+}
+
+/// Creates an advertisement for a device not in pairing mode if it's one of
+/// the user's, i.e. it advertises one of the stored account keys, and it
+/// asks Seekers to notify the user.
+fn new_user_device_advertisement(
+    advertisement: BleAdvertisement,
+    service_data: &ServiceData,
+    fetcher: &Box<dyn FpFetcher>,
+    account_key_store: &dyn AccountKeyStore,
+) -> Result<Option<FpPairingAdvertisement>, FpError> {
+    let data = FpDecoder::get_non_discoverable_data_from_service_data(service_data)?;
+    if !data.show_ui() {
+        return Ok(None);
     }
+
+    let account_key = match data.find_account_key(&account_key_store.account_keys()?) {
+        Some(account_key) => account_key,
+        None => return Ok(None),
+    };
+    let model_id = account_key_store
+        .model_id(&account_key)?
+        .ok_or_else(|| FpError::Internal(String::from("stored account key without model ID")))?;
+
+    info!("Recognized the user's device {}", advertisement.address());
+    FpPairingAdvertisement::new_user_device(
+        advertisement,
+        model_id,
+        account_key,
+        data.battery_status(),
+        fetcher,
+    )
+    .map(Some)
 }
 
-/// Sets up necessary constructs to maintain a TTL blacklist of model IDs.
+/// Retrieves the instant the next nearby device expires at, if any.
+fn next_expiry() -> Option<Instant> {
+    NEARBY_DEVICES
+        .write()
+        .unwrap()
+        .as_mut()
+        .and_then(NearbyDevices::next_expiry)
+}
+
+/// Sets up necessary constructs to maintain a TTL blacklist of devices and
+/// the nearby devices, and to cache the images of the models displayed.
 #[inline]
 fn init_cache() {
-    let mut cache = MODEL_ID_BLACKLIST.write().unwrap();
+    let mut cache = DEVICE_BLACKLIST.write().unwrap();
     *cache = Some(TtlCache::new(16));
+
+    let mut nearby_devices = NEARBY_DEVICES.write().unwrap();
+    *nearby_devices = Some(NearbyDevices::new(MAX_NEARBY_DEVICES, TTL_NEARBY_DEVICE));
+
+    let mut image_cache = IMAGE_CACHE.write().unwrap();
+    *image_cache = Some(FpImageCache::new(
+        HttpImageDownloader::new(TIMEOUT_IMAGE_DOWNLOAD),
+        String::from(IMAGE_CACHE_PATH),
+        RETRY_IMAGE_DOWNLOAD,
+    ));
+}
+
+/// Creates the fetcher of device information, backed by the JSON files of
+/// `JSON_PATH` and cached.
+fn new_fetcher() -> FpFetcherCache<FpFetcherFs> {
+    FpFetcherCache::new(
+        FpFetcherFs::new(String::from(JSON_PATH)),
+        String::from(DEVICE_INFO_CACHE_PATH),
+        TTL_DEVICE_INFO,
+    )
 }
 
 /// Sets up initial constructs and infinitely polls for advertisements.
 pub fn init() {
-    const JSON_PATH: &str = "./local";
-
     let run = async {
         info!("start making adapter");
 
         let mut adapter = Platform::default_adapter().await.unwrap();
-        adapter.start_scan().unwrap();
+        adapter.start_scan(None).unwrap();
 
         init_cache();
 
-        let mut latest_advertisement_map = HashMap::new();
         let datatype_selector = vec![BleDataTypeId::ServiceData16BitUuid];
-        let fetcher: Box<dyn FpFetcher> = Box::new(FpFetcherFs::new(String::from(JSON_PATH)));
+        let fetcher: Box<dyn FpFetcher> = Box::new(new_fetcher());
+        let account_key_store = AccountKeyStoreFs::new(String::from(ACCOUNT_KEYS_PATH));
 
         loop {
-            // Retrieve the next received advertisement.
-            let advertisement = adapter
-                .next_advertisement(Some(&datatype_selector))
-                .await
-                .unwrap();
+            // Retrieve the next received advertisement, displaying the
+            // devices again whenever one expires in the meantime.
+            let next_advertisement = adapter.next_advertisement(Some(&datatype_selector));
+            pin_mut!(next_advertisement);
+            let advertisement = loop {
+                let expiry = match next_expiry() {
+                    Some(expiry) => Either::Left(sleep_until(expiry)),
+                    None => Either::Right(future::pending()),
+                };
+                match future::select(next_advertisement.as_mut(), expiry).await {
+                    Either::Left((advertisement, _)) => break advertisement.unwrap(),
+                    Either::Right(_) => display_devices(),
+                }
+            };
 
             for service_data in advertisement.service_data_16bit_uuid().unwrap() {
-                if let Some(best_adv) = new_best_fp_advertisement(
+                if let Some(fp_adv) = new_fp_advertisement(
                     advertisement.clone(),
                     service_data,
                     &fetcher,
-                    &mut latest_advertisement_map,
+                    &account_key_store,
                 ) {
-                    update_best_device(best_adv).await;
+                    update_nearby_device(fp_adv);
                 }
             }
         }
@@ -178,72 +365,238 @@ pub fn init() {
     executor::block_on(run)
 }
 
-/// Sets up `StreamSink` for Dart-Rust FFI.
-pub fn event_stream(s: StreamSink<Option<[String; 2]>>) {
+/// Sets up `StreamSink` for Dart-Rust FFI, which receives the nearby devices
+/// every time they change.
+pub fn event_stream(s: StreamSink<Vec<NearbyDevice>>) {
     let mut stream = DEVICE_STREAM.write().unwrap();
     *stream = Some(s);
 }
 
-/// Attempt classic pairing with currently displayed device.
-pub fn pair() -> String {
-    let result = match CURR_DEVICE_ADV.read().unwrap().as_ref() {
-        Some(adv) => {
-            let run = async {
-                let classic_addr = ClassicAddress::try_from(adv.address()).unwrap();
-                let classic_device = Platform::new_classic_device(classic_addr).await.unwrap();
-
-                match classic_device.pair().await {
-                    Ok(result) => match result {
-                        PairingResult::Success => String::from("Pairing success!"),
-                        PairingResult::AlreadyPaired => {
-                            String::from("This device is already paired.")
-                        }
-                        PairingResult::AlreadyInProgress => {
-                            String::from("Pairing already in progress.")
-                        }
-                        _ => String::from("Unknown result."),
-                    },
-                    Err(err) => {
-                        format!("Error {}", err)
-                    }
-                }
-            };
+/// Follows the battery updates a paired device sends through the Message
+/// Stream, on a thread of its own, until the device disconnects. They're
+/// displayed for the nearby device with address `ble_address`.
+fn watch_battery(address: ClassicAddress, ble_address: BleAddress) {
+    thread::spawn(move || {
+        let run = async {
+            let device = Platform::new_classic_device(address).await?;
+            let mut client = MessageStreamClient::connect(&device).await?;
+            client.add_handler(BatteryHandler::new(move |battery_status| {
+                update_battery_status(&ble_address, battery_status)
+            }));
+            client.run().await
+        };
 
-            executor::block_on(run)
+        if let Err(err) = executor::block_on(run) {
+            warn!("Message Stream with {} closed: {}", address, err);
         }
-        None => String::from("No device available to pair."),
-    };
-    info!(result);
-    result
+    });
 }
 
-/// Remove this device from display and add it to the TTL cache blacklist.
-pub fn dismiss() {
-    let run = async {
-        let mut adv = CURR_DEVICE_ADV.write().unwrap();
-        match MODEL_ID_BLACKLIST.write().unwrap().as_mut() {
-            Some(cache) => {
-                // Get rid of the best (i.e. currently displayed) device.
-                let adv = adv.take();
-                match adv {
-                    Some(adv) => {
-                        // Add device to TTL blacklist.
-                        cache.insert(adv.model_id().to_string(), (), TTL_BLACKLIST);
-                    }
-                    None => (),
-                }
+/// Translates `state`, entered by `FastPairSeeker` after `previous`, into
+/// the events sent to Flutter, if any.
+fn pairing_events(previous: Option<&SeekerState>, state: &SeekerState) -> Vec<PairingEvent> {
+    match (previous, state) {
+        (_, SeekerState::Discovered) => vec![PairingEvent::Discovered],
+        (_, SeekerState::FetchingMetadata) => vec![PairingEvent::FetchingMetadata],
+        // Pairing starts with Key-based Pairing, or with bonding right away
+        // for models without an anti-spoofing key. Retries aren't reported.
+        (
+            Some(SeekerState::FetchingMetadata),
+            SeekerState::KeyBasedPairing { .. } | SeekerState::Bonding(_),
+        ) => vec![PairingEvent::PairingStarted],
+        (_, SeekerState::PasskeyConfirmed) => vec![PairingEvent::PasskeyConfirmed],
+        (_, SeekerState::Completed(result)) => {
+            let mut events = Vec::new();
+            if previous == Some(&SeekerState::WritingAccountKey) {
+                events.push(PairingEvent::AccountKeyWritten);
+            }
+            events.push(match result {
+                PairingResult::Success(_) | PairingResult::AlreadyPaired => PairingEvent::Completed,
+                PairingResult::AlreadyInProgress => PairingEvent::Failed {
+                    reason: String::from("Pairing already in progress."),
+                },
+                PairingResult::Failure(reason) => PairingEvent::Failed {
+                    reason: reason.clone(),
+                },
+                _ => PairingEvent::Failed {
+                    reason: String::from("Unknown result."),
+                },
+            });
+            events
+        }
+        (_, SeekerState::Failed(err)) => vec![PairingEvent::Failed {
+            reason: err.to_string(),
+        }],
+        _ => Vec::new(),
+    }
+}
+
+/// Retrieves the address of the nearby device with ID `device_id`. The ID
+/// doesn't tell random addresses apart from public ones, so the address is
+/// random if a nearby device has the random one.
+fn nearby_device_address(device_id: &str) -> Result<BleAddress, BluetoothError> {
+    let address = device_id.parse::<BleAddress>()?;
+    let random = BleAddress::new(address.into(), BleAddressKind::Random);
+    let is_random = NEARBY_DEVICES
+        .read()
+        .unwrap()
+        .as_ref()
+        .is_some_and(|nearby_devices| nearby_devices.get(&random).is_some());
+
+    Ok(if is_random { random } else { address })
+}
+
+/// Attempt pairing with the nearby device with ID `device_id`, through Fast
+/// Pair if it's one of the user's devices or its model has an anti-spoofing
+/// key, sending its progress to `events` until it completes or fails. Once
+/// paired, the battery updates sent by the device are displayed.
+pub fn pair(device_id: String, events: StreamSink<PairingEvent>) {
+    // Don't hold the lock while pairing, as advertisements keep coming.
+    let adv = nearby_device_address(&device_id).ok().and_then(|address| {
+        NEARBY_DEVICES
+            .read()
+            .unwrap()
+            .as_ref()
+            .and_then(|nearby_devices| nearby_devices.get(&address).cloned())
+    });
 
-                // Ensure the currently-displayed device is no longer displayed.
-                match DEVICE_STREAM.read().unwrap().as_ref() {
-                    Some(stream) => {
-                        stream.add(None);
-                    }
-                    None => (),
+    match adv {
+        Some(adv) => {
+            let fetcher = new_fetcher();
+            let store = AccountKeyStoreFs::new(String::from(ACCOUNT_KEYS_PATH));
+            let seeker = FastPairSeeker::new(&fetcher, &store)
+                .with_timeouts(TIMEOUT_GATT, TIMEOUT_BONDING)
+                .with_retry_policy(RetryPolicy::new(
+                    ATTEMPTS_KEY_BASED_PAIRING,
+                    BACKOFF_KEY_BASED_PAIRING,
+                    BACKOFF_KEY_BASED_PAIRING * 4,
+                ));
+
+            let mut previous = None;
+            let run = seeker.pair(&adv, |state| {
+                info!("Pairing state: {:?}", state);
+                for event in pairing_events(previous.as_ref(), state) {
+                    events.add(event);
                 }
+                previous = Some(state.clone());
+            });
+
+            if let Ok((PairingResult::Success(_), address)) = executor::block_on(run) {
+                watch_battery(address, adv.address());
             }
-            None => (),
+        }
+        None => {
+            events.add(PairingEvent::Failed {
+                reason: String::from("This device is no longer nearby."),
+            });
+        }
+    }
+    events.close();
+}
+
+/// Remove the device with ID `device_id` from display and add it to the TTL
+/// cache blacklist.
+pub fn dismiss(device_id: String) {
+    let address = match nearby_device_address(&device_id) {
+        Ok(address) => address,
+        Err(err) => {
+            warn!("Cannot dismiss device {}: {}", device_id, err);
+            return;
         }
     };
+    if let Some(nearby_devices) = NEARBY_DEVICES.write().unwrap().as_mut() {
+        nearby_devices.remove(&address);
+    }
+    if let Some(cache) = DEVICE_BLACKLIST.write().unwrap().as_mut() {
+        cache.insert(address, (), TTL_BLACKLIST);
+    }
 
-    executor::block_on(run);
+    // Ensure the dismissed device is no longer displayed.
+    display_devices();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bluetooth::ProtectionLevel;
+
+    /// Translate `states`, entered in order, into the events sent to Flutter.
+    fn events_of(states: &[SeekerState]) -> Vec<PairingEvent> {
+        let mut previous = None;
+        states
+            .iter()
+            .flat_map(|state| pairing_events(previous.replace(state), state))
+            .collect()
+    }
+
+    #[test]
+    fn test_pairing_events() {
+        let address = ClassicAddress::from(0xAABBCCDDEEFF);
+        let success = PairingResult::Success(ProtectionLevel::EncryptionAndAuthentication);
+        let events = events_of(&[
+            SeekerState::Discovered,
+            SeekerState::FetchingMetadata,
+            SeekerState::KeyBasedPairing { attempt: 1 },
+            SeekerState::KeyBasedPairing { attempt: 2 },
+            SeekerState::Bonding(address),
+            SeekerState::PasskeyConfirmed,
+            SeekerState::WritingAccountKey,
+            SeekerState::Completed(success.clone()),
+        ]);
+        assert_eq!(
+            events,
+            [
+                PairingEvent::Discovered,
+                PairingEvent::FetchingMetadata,
+                PairingEvent::PairingStarted,
+                PairingEvent::PasskeyConfirmed,
+                PairingEvent::AccountKeyWritten,
+                PairingEvent::Completed,
+            ]
+        );
+
+        // Models without an anti-spoofing key start pairing with bonding.
+        let events = events_of(&[
+            SeekerState::Discovered,
+            SeekerState::FetchingMetadata,
+            SeekerState::Bonding(address),
+            SeekerState::Completed(success),
+        ]);
+        assert_eq!(
+            events,
+            [
+                PairingEvent::Discovered,
+                PairingEvent::FetchingMetadata,
+                PairingEvent::PairingStarted,
+                PairingEvent::Completed,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_pairing_events_failed() {
+        let address = ClassicAddress::from(0xAABBCCDDEEFF);
+        let events = events_of(&[
+            SeekerState::Bonding(address),
+            SeekerState::Completed(PairingResult::Failure(String::from("rejected"))),
+        ]);
+        assert_eq!(
+            events,
+            [PairingEvent::Failed {
+                reason: String::from("rejected")
+            }]
+        );
+
+        let events = events_of(&[
+            SeekerState::KeyBasedPairing { attempt: 1 },
+            SeekerState::Failed(FpError::Timeout(String::from("bonding"))),
+        ]);
+        assert_eq!(
+            events,
+            [PairingEvent::Failed {
+                reason: String::from("timed out: bonding")
+            }]
+        );
+    }
 }
@@ -0,0 +1,215 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+
+use bluetooth::{DeviceInformationEventCode, MessageHandler, MessageResponse};
+use tracing::warn;
+
+use crate::error::FpError;
+
+/// Bit of a battery value telling whether the component is charging.
+const CHARGING_BIT: u8 = 0x80;
+/// Battery level reported for components whose level is unknown.
+const UNKNOWN_LEVEL: u8 = 0x7F;
+/// Maximum number of battery values: left bud, right bud and case.
+const MAX_BATTERY_VALUES: usize = 3;
+
+/// Battery state of a component of a Fast Pair device, e.g. its left bud.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct BatteryLevel {
+    /// Battery level in percent, or `None` if the device doesn't know it.
+    level: Option<u8>,
+    charging: bool,
+}
+
+impl BatteryLevel {
+    /// Parse a battery value, whose highest bit tells whether the component
+    /// is charging and the others its level in percent.
+    fn from_byte(value: u8) -> Self {
+        let level = value & !CHARGING_BIT;
+        BatteryLevel {
+            level: (level != UNKNOWN_LEVEL).then_some(level),
+            charging: value & CHARGING_BIT != 0,
+        }
+    }
+}
+
+impl fmt::Display for BatteryLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.level {
+            Some(level) => write!(f, "{}%", level)?,
+            None => write!(f, "?")?,
+        }
+        if self.charging {
+            write!(f, " (charging)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Battery state of a Fast Pair device, as advertised or sent through the
+/// Message Stream. Devices only report the components they have, e.g. a
+/// single value for a speaker.
+/// https://developers.google.com/nearby/fast-pair/specifications/extensions/batterynotification
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct BatteryStatus {
+    left: Option<BatteryLevel>,
+    right: Option<BatteryLevel>,
+    case: Option<BatteryLevel>,
+}
+
+impl TryFrom<&[u8]> for BatteryStatus {
+    type Error = FpError;
+
+    /// Parse up to 3 battery values, in order those of the left bud, of the
+    /// right bud and of the case.
+    fn try_from(values: &[u8]) -> Result<Self, Self::Error> {
+        if values.is_empty() || values.len() > MAX_BATTERY_VALUES {
+            return Err(FpError::ContractViolation(format!(
+                "Battery field with {} values.",
+                values.len()
+            )));
+        }
+
+        let mut levels = values.iter().map(|value| BatteryLevel::from_byte(*value));
+        Ok(BatteryStatus {
+            left: levels.next(),
+            right: levels.next(),
+            case: levels.next(),
+        })
+    }
+}
+
+impl fmt::Display for BatteryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let components = [
+            ("Left", self.left),
+            ("Right", self.right),
+            ("Case", self.case),
+        ];
+        let mut separator = "";
+        for (name, level) in components {
+            if let Some(level) = level {
+                write!(f, "{}{}: {}", separator, name, level)?;
+                separator = ", ";
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Message Stream handler passing the battery updates sent by a Provider
+/// to `on_update`.
+pub(crate) struct BatteryHandler<F: FnMut(BatteryStatus) + Send> {
+    on_update: F,
+}
+
+impl<F: FnMut(BatteryStatus) + Send> BatteryHandler<F> {
+    pub(crate) fn new(on_update: F) -> Self {
+        BatteryHandler { on_update }
+    }
+}
+
+impl<F: FnMut(BatteryStatus) + Send> MessageHandler for BatteryHandler<F> {
+    fn on_device_information_event(
+        &mut self,
+        code: DeviceInformationEventCode,
+        data: &[u8],
+    ) -> Option<MessageResponse> {
+        if code == DeviceInformationEventCode::BatteryUpdated {
+            // Battery updates aren't acknowledged, even invalid ones.
+            match BatteryStatus::try_from(data) {
+                Ok(status) => (self.on_update)(status),
+                Err(err) => warn!("Invalid battery update: {}", err),
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn battery_status_from_values() {
+        let status = BatteryStatus::try_from([0x40, 0xE4, 0x7F].as_slice()).unwrap();
+        assert_eq!(
+            status.left,
+            Some(BatteryLevel {
+                level: Some(64),
+                charging: false
+            })
+        );
+        assert_eq!(
+            status.right,
+            Some(BatteryLevel {
+                level: Some(100),
+                charging: true
+            })
+        );
+        assert_eq!(
+            status.case,
+            Some(BatteryLevel {
+                level: None,
+                charging: false
+            })
+        );
+        assert_eq!(
+            status.to_string(),
+            "Left: 64%, Right: 100% (charging), Case: ?"
+        );
+    }
+
+    #[test]
+    fn battery_status_single_value() {
+        let status = BatteryStatus::try_from([0x32].as_slice()).unwrap();
+        assert_eq!(status.right, None);
+        assert_eq!(status.case, None);
+        assert_eq!(status.to_string(), "Left: 50%");
+    }
+
+    #[test]
+    fn battery_status_invalid() {
+        assert!(matches!(
+            BatteryStatus::try_from([].as_slice()),
+            Err(FpError::ContractViolation(_))
+        ));
+        assert!(matches!(
+            BatteryStatus::try_from([1, 2, 3, 4].as_slice()),
+            Err(FpError::ContractViolation(_))
+        ));
+    }
+
+    #[test]
+    fn battery_handler() {
+        let mut updates = Vec::new();
+        let mut handler = BatteryHandler::new(|status| updates.push(status));
+        assert_eq!(
+            handler.on_device_information_event(
+                DeviceInformationEventCode::BatteryUpdated,
+                &[0x40, 0x40]
+            ),
+            None
+        );
+        handler.on_device_information_event(DeviceInformationEventCode::BatteryUpdated, &[]);
+        handler.on_device_information_event(DeviceInformationEventCode::ModelId, &[1, 2, 3]);
+
+        assert_eq!(
+            updates,
+            vec![BatteryStatus::try_from([0x40, 0x40].as_slice()).unwrap()]
+        );
+    }
+}
@@ -26,23 +26,54 @@ pub extern "C" fn wire_event_stream(port_: i64) {
 }
 
 #[no_mangle]
-pub extern "C" fn wire_pair(port_: i64) {
-    wire_pair_impl(port_)
+pub extern "C" fn wire_pair(port_: i64, device_id: *mut wire_uint_8_list) {
+    wire_pair_impl(port_, device_id)
 }
 
 #[no_mangle]
-pub extern "C" fn wire_dismiss(port_: i64) {
-    wire_dismiss_impl(port_)
+pub extern "C" fn wire_dismiss(port_: i64, device_id: *mut wire_uint_8_list) {
+    wire_dismiss_impl(port_, device_id)
 }
 
 // Section: allocate functions
 
+#[no_mangle]
+pub extern "C" fn new_uint_8_list_0(len: i32) -> *mut wire_uint_8_list {
+    let ans = wire_uint_8_list {
+        ptr: support::new_leak_vec_ptr(Default::default(), len),
+        len,
+    };
+    support::new_leak_box_ptr(ans)
+}
+
 // Section: related functions
 
 // Section: impl Wire2Api
 
+impl Wire2Api<String> for *mut wire_uint_8_list {
+    fn wire2api(self) -> String {
+        let vec: Vec<u8> = self.wire2api();
+        String::from_utf8_lossy(&vec).into_owned()
+    }
+}
+
+impl Wire2Api<Vec<u8>> for *mut wire_uint_8_list {
+    fn wire2api(self) -> Vec<u8> {
+        unsafe {
+            let wrap = support::box_from_leak_ptr(self);
+            support::vec_from_leak_ptr(wrap.ptr, wrap.len)
+        }
+    }
+}
 // Section: wire structs
 
+#[repr(C)]
+#[derive(Clone)]
+pub struct wire_uint_8_list {
+    ptr: *mut u8,
+    len: i32,
+}
+
 // Section: impl NewWithNullPtr
 
 pub trait NewWithNullPtr {
@@ -56,30 +56,41 @@ fn wire_event_stream_impl(port_: MessagePort) {
         move || {
             move |task_callback| {
                 Ok(event_stream(
-                    task_callback.stream_sink::<_, Option<[String; 2]>>(),
+                    task_callback.stream_sink::<_, Vec<NearbyDevice>>(),
                 ))
             }
         },
     )
 }
-fn wire_pair_impl(port_: MessagePort) {
-    FLUTTER_RUST_BRIDGE_HANDLER.wrap::<_, _, _, String>(
+fn wire_pair_impl(port_: MessagePort, device_id: impl Wire2Api<String> + UnwindSafe) {
+    FLUTTER_RUST_BRIDGE_HANDLER.wrap::<_, _, _, ()>(
         WrapInfo {
             debug_name: "pair",
             port: Some(port_),
-            mode: FfiCallMode::Normal,
+            mode: FfiCallMode::Stream,
+        },
+        move || {
+            let api_device_id = device_id.wire2api();
+            move |task_callback| {
+                Ok(pair(
+                    api_device_id,
+                    task_callback.stream_sink::<_, PairingEvent>(),
+                ))
+            }
         },
-        move || move |task_callback| Ok(pair()),
     )
 }
-fn wire_dismiss_impl(port_: MessagePort) {
+fn wire_dismiss_impl(port_: MessagePort, device_id: impl Wire2Api<String> + UnwindSafe) {
     FLUTTER_RUST_BRIDGE_HANDLER.wrap::<_, _, _, ()>(
         WrapInfo {
             debug_name: "dismiss",
             port: Some(port_),
             mode: FfiCallMode::Normal,
         },
-        move || move |task_callback| Ok(dismiss()),
+        move || {
+            let api_device_id = device_id.wire2api();
+            move |task_callback| Ok(dismiss(api_device_id))
+        },
     )
 }
 // Section: wrapper structs
@@ -104,8 +115,55 @@ where
         (!self.is_null()).then(|| self.wire2api())
     }
 }
+
+impl Wire2Api<u8> for u8 {
+    fn wire2api(self) -> u8 {
+        self
+    }
+}
+
 // Section: impl IntoDart
 
+impl support::IntoDart for NearbyDevice {
+    fn into_dart(self) -> support::DartAbi {
+        vec![
+            self.device_id.into_into_dart().into_dart(),
+            self.model_id.into_into_dart().into_dart(),
+            self.name.into_into_dart().into_dart(),
+            self.image_path.into_into_dart().into_dart(),
+            self.battery_status.into_into_dart().into_dart(),
+        ]
+        .into_dart()
+    }
+}
+impl support::IntoDartExceptPrimitive for NearbyDevice {}
+impl rust2dart::IntoIntoDart<NearbyDevice> for NearbyDevice {
+    fn into_into_dart(self) -> Self {
+        self
+    }
+}
+
+impl support::IntoDart for PairingEvent {
+    fn into_dart(self) -> support::DartAbi {
+        match self {
+            Self::Discovered => vec![0.into_dart()],
+            Self::FetchingMetadata => vec![1.into_dart()],
+            Self::PairingStarted => vec![2.into_dart()],
+            Self::PasskeyConfirmed => vec![3.into_dart()],
+            Self::AccountKeyWritten => vec![4.into_dart()],
+            Self::Completed => vec![5.into_dart()],
+            Self::Failed { reason } => vec![6.into_dart(), reason.into_into_dart().into_dart()],
+        }
+        .into_dart()
+    }
+}
+impl support::IntoDartExceptPrimitive for PairingEvent {}
+impl rust2dart::IntoIntoDart<PairingEvent> for PairingEvent {
+    fn into_into_dart(self) -> Self {
+        self
+    }
+}
+
 // Section: executor
 
 support::lazy_static! {
@@ -0,0 +1,134 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use rand::{rngs::OsRng, RngCore};
+
+use super::{hmac_sha256, AesKey, BLOCK_LENGTH, NONCE_LENGTH};
+use crate::error::FpError;
+
+/// Length of the truncated HMAC-SHA256 tag authenticating additional data.
+const TAG_LENGTH: usize = 8;
+
+/// Encrypt `data` exchanged over the Additional Data characteristic, e.g. the
+/// personalized name of a device, with `key`, usually its account key.
+/// The packet is made of the first 8 bytes of the HMAC-SHA256 of the rest,
+/// a random nonce, then the data encrypted in CTR mode. See:
+/// https://developers.google.com/nearby/fast-pair/specifications/characteristics#AdditionalData
+#[cfg_attr(not(test), allow(dead_code))]
+pub(crate) fn encrypt_additional_data(key: &[u8; BLOCK_LENGTH], data: &[u8]) -> Vec<u8> {
+    let mut nonce = [0; NONCE_LENGTH];
+    OsRng.fill_bytes(&mut nonce);
+    encrypt_additional_data_with_nonce(key, &nonce, data)
+}
+
+fn encrypt_additional_data_with_nonce(
+    key: &[u8; BLOCK_LENGTH],
+    nonce: &[u8; NONCE_LENGTH],
+    data: &[u8],
+) -> Vec<u8> {
+    let mut encrypted = data.to_vec();
+    AesKey::new(key).apply_ctr(nonce, &mut encrypted);
+
+    let tag = hmac_sha256(key, &[nonce, &encrypted]);
+    [&tag[..TAG_LENGTH], nonce, &encrypted].concat()
+}
+
+/// Decrypt a packet received over the Additional Data characteristic, once
+/// its tag is verified.
+#[cfg_attr(not(test), allow(dead_code))]
+pub(crate) fn decrypt_additional_data(
+    key: &[u8; BLOCK_LENGTH],
+    packet: &[u8],
+) -> Result<Vec<u8>, FpError> {
+    if packet.len() < TAG_LENGTH + NONCE_LENGTH {
+        return Err(FpError::ContractViolation(format!(
+            "additional data packet of length {}",
+            packet.len()
+        )));
+    }
+    let (tag, rest) = packet.split_at(TAG_LENGTH);
+    let (nonce, encrypted) = rest.split_at(NONCE_LENGTH);
+
+    if hmac_sha256(key, &[nonce, encrypted])[..TAG_LENGTH] != *tag {
+        return Err(FpError::ContractViolation(String::from(
+            "additional data packet with invalid tag",
+        )));
+    }
+
+    let mut data = encrypted.to_vec();
+    // The nonce has the expected length after the split.
+    AesKey::new(key).apply_ctr(nonce.try_into().unwrap(), &mut data);
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; BLOCK_LENGTH] = [
+        0xA0, 0xBA, 0xF0, 0xBB, 0x95, 0x1F, 0xF7, 0xB6, 0xCF, 0x5E, 0x3F, 0x45, 0x61, 0xC3, 0x32,
+        0x1D,
+    ];
+
+    /// Fast Pair specification, HMAC-SHA256 and encrypted data test cases.
+    const SPEC_KEY: [u8; BLOCK_LENGTH] = [
+        0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF, 0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD,
+        0xEF,
+    ];
+    const SPEC_PACKET: [u8; 42] = [
+        0x55, 0xEC, 0x5E, 0x60, 0x55, 0xAF, 0x6E, 0x92, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06,
+        0x07, 0xEE, 0x4A, 0x24, 0x83, 0x73, 0x80, 0x52, 0xE4, 0x4E, 0x9B, 0x2A, 0x14, 0x5E, 0x5D,
+        0xDF, 0xAA, 0x44, 0xB9, 0xE5, 0x53, 0x6A, 0xF4, 0x38, 0xE1, 0xE5, 0xC6,
+    ];
+
+    #[test]
+    fn test_encrypt_additional_data() {
+        let nonce = [0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07];
+        assert_eq!(
+            encrypt_additional_data_with_nonce(&SPEC_KEY, &nonce, b"Someone's Google Headphone"),
+            SPEC_PACKET
+        );
+        assert_eq!(
+            decrypt_additional_data(&SPEC_KEY, &SPEC_PACKET).unwrap(),
+            b"Someone's Google Headphone"
+        );
+    }
+
+    #[test]
+    fn test_decrypt_additional_data() {
+        let packet = encrypt_additional_data(&KEY, b"Headphones");
+        assert_eq!(
+            decrypt_additional_data(&KEY, &packet).unwrap(),
+            b"Headphones"
+        );
+
+        // Nonces are random.
+        assert_ne!(packet, encrypt_additional_data(&KEY, b"Headphones"));
+    }
+
+    #[test]
+    fn test_decrypt_additional_data_invalid() {
+        let mut packet = encrypt_additional_data(&KEY, b"Headphones");
+        packet[TAG_LENGTH + NONCE_LENGTH] ^= 0x01;
+        assert!(matches!(
+            decrypt_additional_data(&KEY, &packet),
+            Err(FpError::ContractViolation(_))
+        ));
+
+        assert!(matches!(
+            decrypt_additional_data(&KEY, &[0x00; TAG_LENGTH]),
+            Err(FpError::ContractViolation(_))
+        ));
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use aes::{
+    cipher::{BlockDecrypt, BlockEncrypt, KeyInit},
+    Aes128,
+};
+
+/// Length of an AES-128 block, and of AES-128 keys, in bytes.
+pub(crate) const BLOCK_LENGTH: usize = 16;
+
+/// Length of the nonce of AES-CTR, in bytes.
+pub(crate) const NONCE_LENGTH: usize = 8;
+
+/// A single AES-128 block.
+pub(crate) type Block = [u8; BLOCK_LENGTH];
+
+/// AES-128 key, e.g. the key shared by a Seeker and a Provider during
+/// Key-based Pairing, or an account key.
+#[derive(Clone)]
+pub(crate) struct AesKey(Aes128);
+
+impl AesKey {
+    /// Construct a key from its 16 raw bytes.
+    pub(crate) fn new(key: &[u8; BLOCK_LENGTH]) -> Self {
+        AesKey(Aes128::new(&(*key).into()))
+    }
+
+    /// Encrypt a single block in ECB mode.
+    pub(crate) fn encrypt(&self, block: &Block) -> Block {
+        let mut block = aes::Block::from(*block);
+        self.0.encrypt_block(&mut block);
+        block.into()
+    }
+
+    /// Decrypt a single block in ECB mode.
+    pub(crate) fn decrypt(&self, block: &Block) -> Block {
+        let mut block = aes::Block::from(*block);
+        self.0.decrypt_block(&mut block);
+        block.into()
+    }
+
+    /// Encrypt or decrypt `data` in place in CTR mode, as Fast Pair does: the
+    /// `i`th block is XORed with the encryption of the block made of `i` as a
+    /// single byte, 7 zero bytes, then `nonce`.
+    pub(crate) fn apply_ctr(&self, nonce: &[u8; NONCE_LENGTH], data: &mut [u8]) {
+        for (i, chunk) in data.chunks_mut(BLOCK_LENGTH).enumerate() {
+            let mut counter = [0; BLOCK_LENGTH];
+            counter[0] = i as u8;
+            counter[BLOCK_LENGTH - NONCE_LENGTH..].copy_from_slice(nonce);
+
+            let keystream = self.encrypt(&counter);
+            for (byte, key) in chunk.iter_mut().zip(keystream) {
+                *byte ^= key;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: [u8; BLOCK_LENGTH] = [
+        0xA0, 0xBA, 0xF0, 0xBB, 0x95, 0x1F, 0xF7, 0xB6, 0xCF, 0x5E, 0x3F, 0x45, 0x61, 0xC3, 0x32,
+        0x1D,
+    ];
+
+    #[test]
+    fn test_aes_ecb() {
+        // Fast Pair specification, AES-ECB-128 test case.
+        let key = AesKey::new(&KEY);
+        let plaintext = [
+            0xF3, 0x0F, 0x4E, 0x78, 0x6C, 0x59, 0xA7, 0xBB, 0xF3, 0x87, 0x3B, 0x5A, 0x49, 0xBA,
+            0x97, 0xEA,
+        ];
+        let ciphertext = [
+            0xAC, 0x9A, 0x16, 0xF0, 0x95, 0x3A, 0x3F, 0x22, 0x3D, 0xD1, 0x0C, 0xF5, 0x36, 0xE0,
+            0x9E, 0x9C,
+        ];
+
+        assert_eq!(key.encrypt(&plaintext), ciphertext);
+        assert_eq!(key.decrypt(&ciphertext), plaintext);
+    }
+
+    #[test]
+    fn test_aes_ecb_fips_197() {
+        // FIPS-197, Appendix C.1.
+        let key = AesKey::new(&[
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d,
+            0x0e, 0x0f,
+        ]);
+        let plaintext = [
+            0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd,
+            0xee, 0xff,
+        ];
+        let ciphertext = [
+            0x69, 0xc4, 0xe0, 0xd8, 0x6a, 0x7b, 0x04, 0x30, 0xd8, 0xcd, 0xb7, 0x80, 0x70, 0xb4,
+            0xc5, 0x5a,
+        ];
+
+        assert_eq!(key.encrypt(&plaintext), ciphertext);
+        assert_eq!(key.decrypt(&ciphertext), plaintext);
+    }
+
+    #[test]
+    fn test_aes_ctr() {
+        // Fast Pair specification, encrypted data test case.
+        let key = AesKey::new(&[
+            0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF, 0x01, 0x23, 0x45, 0x67, 0x89, 0xAB,
+            0xCD, 0xEF,
+        ]);
+        let nonce = [0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07];
+        let plaintext = b"Someone's Google Headphone";
+        let ciphertext = [
+            0xEE, 0x4A, 0x24, 0x83, 0x73, 0x80, 0x52, 0xE4, 0x4E, 0x9B, 0x2A, 0x14, 0x5E, 0x5D,
+            0xDF, 0xAA, 0x44, 0xB9, 0xE5, 0x53, 0x6A, 0xF4, 0x38, 0xE1, 0xE5, 0xC6,
+        ];
+
+        let mut data = plaintext.to_vec();
+        key.apply_ctr(&nonce, &mut data);
+        assert_eq!(data, ciphertext);
+
+        key.apply_ctr(&nonce, &mut data);
+        assert_eq!(data, plaintext);
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use p256::{
+    ecdh::{diffie_hellman, EphemeralSecret},
+    EncodedPoint, PublicKey, SecretKey,
+};
+use rand::rngs::OsRng;
+
+use super::{sha256, AesKey, BLOCK_LENGTH};
+use crate::error::FpError;
+
+/// Length of the public keys exchanged for ECDH, i.e. the X and Y
+/// coordinates of a secp256r1 point, without the SEC1 tag.
+pub(crate) const PUBLIC_KEY_LENGTH: usize = 64;
+
+/// SEC1 tag of uncompressed points.
+const UNCOMPRESSED_TAG: u8 = 0x04;
+
+/// Parse a secp256r1 public key made of the X and Y coordinates of its point.
+pub(crate) fn parse_public_key(key: &[u8]) -> Result<PublicKey, FpError> {
+    if key.len() != PUBLIC_KEY_LENGTH {
+        return Err(FpError::ContractViolation(format!(
+            "public key of length {}, expected {}",
+            key.len(),
+            PUBLIC_KEY_LENGTH
+        )));
+    }
+
+    PublicKey::from_sec1_bytes(&[&[UNCOMPRESSED_TAG], key].concat()).map_err(|_| {
+        FpError::ContractViolation(String::from("public key is not a point on secp256r1"))
+    })
+}
+
+/// Encode a secp256r1 public key as the X and Y coordinates of its point.
+pub(crate) fn encode_public_key(key: &PublicKey) -> [u8; PUBLIC_KEY_LENGTH] {
+    let mut encoded = [0; PUBLIC_KEY_LENGTH];
+    // Skip the SEC1 tag of the uncompressed point.
+    encoded.copy_from_slice(&EncodedPoint::from(key).as_bytes()[1..]);
+    encoded
+}
+
+/// Derive an AES-128 key from an ECDH shared secret, as the first 16 bytes
+/// of its SHA-256 hash.
+pub(crate) fn key_from_shared_secret(shared_secret: &[u8]) -> AesKey {
+    let mut key = [0; BLOCK_LENGTH];
+    key.copy_from_slice(&sha256(&[shared_secret])[..BLOCK_LENGTH]);
+    AesKey::new(&key)
+}
+
+/// Derive an AES-128 key from ECDH between the remote `public_key`, e.g.
+/// the anti-spoofing public key of a Provider, and a fresh key pair.
+/// Returns the key along with the public key of the pair, which the remote
+/// side needs to derive the same key.
+pub(crate) fn ecdh_with_ephemeral_key(
+    public_key: &[u8],
+) -> Result<(AesKey, [u8; PUBLIC_KEY_LENGTH]), FpError> {
+    let public_key = parse_public_key(public_key)?;
+    let secret = EphemeralSecret::random(&mut OsRng);
+    let shared_secret = secret.diffie_hellman(&public_key);
+
+    Ok((
+        key_from_shared_secret(shared_secret.raw_secret_bytes()),
+        encode_public_key(&secret.public_key()),
+    ))
+}
+
+/// Derive an AES-128 key from ECDH between the local `private_key`, e.g.
+/// the anti-spoofing private key of a Provider, and the remote
+/// `public_key` sent along with a Key-based Pairing request.
+#[cfg_attr(not(test), allow(dead_code))]
+pub(crate) fn ecdh_with_private_key(
+    private_key: &SecretKey,
+    public_key: &[u8],
+) -> Result<AesKey, FpError> {
+    let public_key = parse_public_key(public_key)?;
+    let shared_secret = diffie_hellman(private_key.to_nonzero_scalar(), public_key.as_affine());
+    Ok(key_from_shared_secret(shared_secret.raw_secret_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fast Pair specification, ECDH key exchange test case.
+    const ALICE_PRIVATE_KEY: [u8; 32] = [
+        0xD7, 0x5E, 0x54, 0xC7, 0x7D, 0x76, 0x24, 0x89, 0xE5, 0x7C, 0xFA, 0x92, 0x37, 0x43, 0xF1,
+        0x67, 0x77, 0xA4, 0x28, 0x3D, 0x99, 0x80, 0x0B, 0xAC, 0x55, 0x58, 0x48, 0x38, 0x93, 0xE5,
+        0xB0, 0x6D,
+    ];
+    const ALICE_PUBLIC_KEY: [u8; PUBLIC_KEY_LENGTH] = [
+        0x36, 0xAC, 0x68, 0x2C, 0x50, 0x82, 0x15, 0x66, 0x8F, 0xBE, 0xFE, 0x24, 0x7D, 0x01, 0xD5,
+        0xEB, 0x96, 0xE6, 0x31, 0x8E, 0x85, 0x5B, 0x2D, 0x64, 0xB5, 0x19, 0x5D, 0x38, 0xEE, 0x7E,
+        0x37, 0xBE, 0x18, 0x38, 0xC0, 0xB9, 0x48, 0xC3, 0xF7, 0x55, 0x20, 0xE0, 0x7E, 0x70, 0xF0,
+        0x72, 0x91, 0x41, 0x9A, 0xCE, 0x2D, 0x28, 0x14, 0x3C, 0x5A, 0xDB, 0x2D, 0xBD, 0x98, 0xEE,
+        0x3C, 0x8E, 0x4F, 0xBF,
+    ];
+    const BOB_PRIVATE_KEY: [u8; 32] = [
+        0x02, 0xB4, 0x37, 0xB0, 0xED, 0xD6, 0xBB, 0xD4, 0x29, 0x06, 0x4A, 0x4E, 0x52, 0x9F, 0xCB,
+        0xF1, 0xC4, 0x8D, 0x0D, 0x62, 0x49, 0x24, 0xD5, 0x92, 0x27, 0x4B, 0x7E, 0xD8, 0x11, 0x93,
+        0xD7, 0x63,
+    ];
+    const BOB_PUBLIC_KEY: [u8; PUBLIC_KEY_LENGTH] = [
+        0xF7, 0xD4, 0x96, 0xA6, 0x2E, 0xCA, 0x41, 0x63, 0x51, 0x54, 0x0A, 0xA3, 0x43, 0xBC, 0x69,
+        0x0A, 0x61, 0x09, 0xF5, 0x51, 0x50, 0x06, 0x66, 0xB8, 0x3B, 0x12, 0x51, 0xFB, 0x84, 0xFA,
+        0x28, 0x60, 0x79, 0x5E, 0xBD, 0x63, 0xD3, 0xB8, 0x83, 0x6F, 0x44, 0xA9, 0xA3, 0xE2, 0x8B,
+        0xB3, 0x40, 0x17, 0xE0, 0x15, 0xF5, 0x97, 0x93, 0x05, 0xD8, 0x49, 0xFD, 0xF8, 0xDE, 0x10,
+        0x12, 0x3B, 0x61, 0xD2,
+    ];
+
+    #[test]
+    fn test_key_from_shared_secret() {
+        let alice_private_key = SecretKey::from_slice(&ALICE_PRIVATE_KEY).unwrap();
+        let bob_public_key = parse_public_key(&BOB_PUBLIC_KEY).unwrap();
+        let shared_secret = diffie_hellman(
+            alice_private_key.to_nonzero_scalar(),
+            bob_public_key.as_affine(),
+        );
+        assert_eq!(
+            shared_secret.raw_secret_bytes()[..],
+            [
+                0x9D, 0xAD, 0xE4, 0xF8, 0x6A, 0xC3, 0x48, 0x8B, 0xBA, 0xC2, 0xAC, 0x34, 0xB5, 0xFE,
+                0x68, 0xA0, 0xEE, 0x5A, 0x67, 0x06, 0xF5, 0x43, 0xD9, 0x06, 0x1A, 0xD5, 0x78, 0x89,
+                0x49, 0x8A, 0xE6, 0xBA,
+            ]
+        );
+
+        let key = key_from_shared_secret(shared_secret.raw_secret_bytes());
+        let block = [0x11; BLOCK_LENGTH];
+        let expected_key = AesKey::new(&[
+            0xB0, 0x7F, 0x1F, 0x17, 0xC2, 0x36, 0xCB, 0xD3, 0x35, 0x23, 0xC5, 0x15, 0xF3, 0x50,
+            0xAE, 0x57,
+        ]);
+        assert_eq!(key.encrypt(&block), expected_key.encrypt(&block));
+
+        // Bob derives the same key from Alice's public key.
+        let bob_private_key = SecretKey::from_slice(&BOB_PRIVATE_KEY).unwrap();
+        let key = ecdh_with_private_key(&bob_private_key, &ALICE_PUBLIC_KEY).unwrap();
+        assert_eq!(key.encrypt(&block), expected_key.encrypt(&block));
+    }
+
+    #[test]
+    fn test_ecdh_with_ephemeral_key() {
+        let remote_secret = SecretKey::random(&mut OsRng);
+        let remote_public_key = encode_public_key(&remote_secret.public_key());
+
+        let (key, public_key) = ecdh_with_ephemeral_key(&remote_public_key).unwrap();
+
+        // The remote side derives the key from the local public key.
+        let shared_secret = diffie_hellman(
+            remote_secret.to_nonzero_scalar(),
+            parse_public_key(&public_key).unwrap().as_affine(),
+        );
+        let remote_key = key_from_shared_secret(shared_secret.raw_secret_bytes());
+
+        let block = [0x22; BLOCK_LENGTH];
+        assert_eq!(remote_key.decrypt(&key.encrypt(&block)), block);
+
+        // As does the remote side holding a private key.
+        let remote_key = ecdh_with_private_key(&remote_secret, &public_key).unwrap();
+        assert_eq!(remote_key.decrypt(&key.encrypt(&block)), block);
+    }
+
+    #[test]
+    fn test_parse_public_key_invalid() {
+        assert!(matches!(
+            parse_public_key(&[0x01; 32]),
+            Err(FpError::ContractViolation(_))
+        ));
+        assert!(matches!(
+            parse_public_key(&[0x01; PUBLIC_KEY_LENGTH]),
+            Err(FpError::ContractViolation(_))
+        ));
+    }
+}
@@ -14,51 +14,210 @@
 
 use bluetooth::ServiceData;
 
-use crate::error::FpError;
+use crate::{
+    account_key::{AccountKey, AccountKeyFilter},
+    battery::BatteryStatus,
+    error::FpError,
+};
+
+/// Bounds of the length of model IDs, in bytes.
+const MIN_MODEL_ID_LENGTH: usize = 3;
+const MAX_MODEL_ID_LENGTH: usize = 14;
+
+/// Types of the fields of non-discoverable advertisements.
+const SHOW_UI_ACCOUNT_KEY_DATA: u8 = 0b0000;
+const SALT: u8 = 0b0001;
+const HIDE_UI_ACCOUNT_KEY_DATA: u8 = 0b0010;
+const SHOW_UI_BATTERY: u8 = 0b0011;
+const HIDE_UI_BATTERY: u8 = 0b0100;
+
+/// Fields of the advertisement of a Fast Pair device that isn't in pairing
+/// mode, which let the Seekers of its owner recognize it.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct FpNonDiscoverableData {
+    /// Filter of the account keys stored on the device, or `None` if it
+    /// stores none.
+    account_key_filter: Option<AccountKeyFilter>,
+    /// Whether Seekers should notify the user about the device.
+    show_ui: bool,
+    salt: Vec<u8>,
+    /// Raw battery field, header included, as hashed in the filter.
+    battery: Option<Vec<u8>>,
+    /// Battery status parsed from the battery field, if Seekers should
+    /// display it.
+    battery_status: Option<BatteryStatus>,
+}
+
+impl FpNonDiscoverableData {
+    /// Retrieve whether Seekers should notify the user about the device.
+    pub(crate) fn show_ui(&self) -> bool {
+        self.show_ui
+    }
+
+    /// Retrieve the battery status of the device, if it advertises one to be
+    /// displayed.
+    pub(crate) fn battery_status(&self) -> Option<BatteryStatus> {
+        self.battery_status
+    }
+
+    /// Find which of the user's `account_keys` the device stores, if any.
+    pub(crate) fn find_account_key(&self, account_keys: &[AccountKey]) -> Option<AccountKey> {
+        let filter = self.account_key_filter.as_ref()?;
+        account_keys
+            .iter()
+            .find(|account_key| filter.contains(account_key, &self.salt, self.battery.as_deref()))
+            .copied()
+    }
+}
 
 /// Unit struct providing parsing operations for Fast Pair advertisements.
 pub(crate) struct FpDecoder;
 
 impl FpDecoder {
+    /// Check whether a service data payload comes from a device in pairing
+    /// mode, whose advertisements carry its model ID rather than the fields
+    /// of non-discoverable advertisements.
+    pub(crate) fn is_discoverable(service_data: &ServiceData) -> bool {
+        Self::get_model_id_from_service_data(service_data).is_ok()
+    }
+
     /// Retrieve the Fast Pair device model ID from a service data payload.
     /// https://developers.google.com/nearby/fast-pair/specifications/service/provider.
     /// * Length < 3: invalid payload
     /// * Length == 3: entire payload is the model ID
-    /// * Length > 3: first byte specifies the length of the model ID, in bytes.
-    /// Currently unavailable in Fast Pair devices and not supported.
-    pub(crate) fn get_model_id_from_service_data<U: Copy>(
-        service_data: &ServiceData<U>,
+    /// * Length > 3: first byte is a header `0bVVVLLLLR`, with the version
+    ///   `V` of the format and the length `L` of the model ID, in bytes.
+    ///   The big-endian model ID follows, then fields added by later
+    ///   versions of the specification, which are skipped.
+    ///
+    /// Leading zeros of long model IDs are trimmed, down to 3 bytes.
+    pub(crate) fn get_model_id_from_service_data(
+        service_data: &ServiceData,
     ) -> Result<Vec<u8>, FpError> {
-        const MIN_MODEL_ID_LENGTH: usize = 3;
         let data = service_data.data();
 
         if data.len() < MIN_MODEL_ID_LENGTH {
             // If service data too small, invalid payload.
-            Err(FpError::ContractViolation(format!(
+            return Err(FpError::ContractViolation(format!(
                 "Invalid model ID for Fast Pair advertisement of length {}.",
                 data.len()
-            )))
+            )));
         } else if data.len() == MIN_MODEL_ID_LENGTH {
             // Else if service data length is exactly 3, all bytes are the ID.
-            Ok(data.clone())
-        } else {
-            // Else, this Fast Pair advertisement is currently unsupported.
-            // b/294453912
-            Err(FpError::NotImplemented(String::from(
-                "This Fast Pair device is currently unsupported.",
-            )))
+            return Ok(data.to_vec());
+        }
+
+        // Else, the first byte is a header describing the model ID.
+        let (header, rest) = data.split_first().unwrap();
+        let version = header >> 5;
+        if version != 0 {
+            // A different version means a breaking change in the format.
+            return Err(FpError::NotImplemented(format!(
+                "Fast Pair advertisement version {}.",
+                version
+            )));
+        }
+
+        let length = usize::from((header >> 1) & 0x0F);
+        if !(MIN_MODEL_ID_LENGTH..=MAX_MODEL_ID_LENGTH).contains(&length) || length > rest.len() {
+            return Err(FpError::ContractViolation(format!(
+                "Invalid model ID of length {} for Fast Pair advertisement of length {}.",
+                length,
+                data.len()
+            )));
         }
+
+        let model_id = &rest[..length];
+        let leading_zeros = model_id
+            .iter()
+            .take(length - MIN_MODEL_ID_LENGTH)
+            .take_while(|byte| **byte == 0)
+            .count();
+        Ok(model_id[leading_zeros..].to_vec())
+    }
+
+    /// Retrieve the fields of a non-discoverable advertisement from a service
+    /// data payload: a version byte followed by fields made of a header,
+    /// whose high nibble is the length of the value and low nibble its type,
+    /// then the value.
+    /// https://developers.google.com/nearby/fast-pair/specifications/service/provider#AdvertisingPayloadFastPairAccountData
+    pub(crate) fn get_non_discoverable_data_from_service_data(
+        service_data: &ServiceData,
+    ) -> Result<FpNonDiscoverableData, FpError> {
+        let (version, mut fields) = service_data.data().split_first().ok_or_else(|| {
+            FpError::ContractViolation(String::from("Empty non-discoverable advertisement."))
+        })?;
+
+        if version >> 4 != 0 {
+            return Err(FpError::NotImplemented(format!(
+                "Non-discoverable advertisement version {}.",
+                version >> 4
+            )));
+        }
+
+        let mut data = FpNonDiscoverableData {
+            account_key_filter: None,
+            show_ui: false,
+            salt: Vec::new(),
+            battery: None,
+            battery_status: None,
+        };
+
+        while let Some((header, rest)) = fields.split_first() {
+            let length = usize::from(header >> 4);
+            if rest.len() < length {
+                return Err(FpError::ContractViolation(format!(
+                    "Non-discoverable advertisement field of length {}, {} bytes left.",
+                    length,
+                    rest.len()
+                )));
+            }
+            let value = &rest[..length];
+
+            match header & 0x0F {
+                field_type @ (SHOW_UI_ACCOUNT_KEY_DATA | HIDE_UI_ACCOUNT_KEY_DATA) => {
+                    data.show_ui = field_type == SHOW_UI_ACCOUNT_KEY_DATA;
+                    // An empty filter means the device stores no account key.
+                    if !value.is_empty() {
+                        data.account_key_filter = Some(AccountKeyFilter::new(value.to_vec()));
+                    }
+                }
+                SALT => data.salt = value.to_vec(),
+                field_type @ (SHOW_UI_BATTERY | HIDE_UI_BATTERY) => {
+                    data.battery = Some(fields[..=length].to_vec());
+                    // The status is parsed either way, so that invalid
+                    // battery fields are reported.
+                    let status = BatteryStatus::try_from(value)?;
+                    if field_type == SHOW_UI_BATTERY {
+                        data.battery_status = Some(status);
+                    }
+                }
+                // Skip fields added by later versions of the specification.
+                _ => (),
+            }
+
+            fields = &rest[length..];
+        }
+
+        if data.account_key_filter.is_some() && data.salt.is_empty() {
+            return Err(FpError::ContractViolation(String::from(
+                "Non-discoverable advertisement with account key filter but no salt.",
+            )));
+        }
+
+        Ok(data)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use bluetooth::Uuid;
 
     #[test]
     fn test_get_model_id_valid() {
         // Valid scenario: Length == 3
-        let uuid: u16 = 0x1234;
+        let uuid = Uuid::from_u16(0x1234);
         let data = vec![0xAA, 0xBB, 0xCC];
         let service_data = ServiceData::new(uuid, data.clone());
         let result = FpDecoder::get_model_id_from_service_data(&service_data);
@@ -69,7 +228,7 @@ mod tests {
     #[test]
     fn test_get_model_id_invalid() {
         // Invalid scenario: Length < 3
-        let uuid: u16 = 0x1234;
+        let uuid = Uuid::from_u16(0x1234);
         let data = vec![0xAA, 0xBB];
         let service_data = ServiceData::new(uuid, data);
         let result = FpDecoder::get_model_id_from_service_data(&service_data);
@@ -78,13 +237,104 @@ mod tests {
     }
 
     #[test]
-    fn test_get_model_id_unsupported() {
-        // Unsupported scenario: Length > 3
-        let uuid: u16 = 0x1234;
-        let data = vec![0xAA, 0xBB, 0xCC, 0xDD];
+    fn test_get_model_id_long() {
+        // Valid scenario: header with a length of 4, the model ID, then an
+        // extra field.
+        let uuid = Uuid::from_u16(0x1234);
+        let data = vec![0b00001000, 0x11, 0x22, 0x33, 0x44, 0xEE];
         let service_data = ServiceData::new(uuid, data);
         let result = FpDecoder::get_model_id_from_service_data(&service_data);
-        assert!(result.is_err());
+        assert_eq!(result.unwrap(), vec![0x11, 0x22, 0x33, 0x44]);
+        assert!(FpDecoder::is_discoverable(&service_data));
+
+        // Leading zeros are trimmed, down to 3 bytes.
+        let data = vec![0b00001010, 0x00, 0x00, 0x00, 0x22, 0x33];
+        let service_data = ServiceData::new(uuid, data);
+        let result = FpDecoder::get_model_id_from_service_data(&service_data);
+        assert_eq!(result.unwrap(), vec![0x00, 0x22, 0x33]);
+    }
+
+    #[test]
+    fn test_get_model_id_long_invalid() {
+        let uuid = Uuid::from_u16(0x1234);
+
+        // Unsupported scenario: version 1.
+        let data = vec![0b00101000, 0x11, 0x22, 0x33, 0x44];
+        let service_data = ServiceData::new(uuid, data);
+        let result = FpDecoder::get_model_id_from_service_data(&service_data);
+        assert!(matches!(result.unwrap_err(), FpError::NotImplemented(_)));
+
+        // Invalid scenario: model ID longer than the payload.
+        let data = vec![0b00001010, 0x11, 0x22, 0x33, 0x44];
+        let service_data = ServiceData::new(uuid, data);
+        let result = FpDecoder::get_model_id_from_service_data(&service_data);
+        assert!(matches!(result.unwrap_err(), FpError::ContractViolation(_)));
+
+        // Invalid scenario: model ID shorter than 3 bytes, e.g. the version
+        // byte of a non-discoverable advertisement.
+        let data = vec![0x00, 0x00, 0x11, 0xC7];
+        let service_data = ServiceData::new(uuid, data);
+        let result = FpDecoder::get_model_id_from_service_data(&service_data);
+        assert!(matches!(result.unwrap_err(), FpError::ContractViolation(_)));
+        assert!(!FpDecoder::is_discoverable(&service_data));
+    }
+
+    #[test]
+    fn test_get_non_discoverable_data() {
+        let account_key = AccountKey::try_from(&[0x04; 16][..]).unwrap();
+        let battery = [0x33, 0x40, 0x40, 0xE4];
+        let filter = AccountKeyFilter::build(&[account_key], &[0xC7], Some(&battery));
+
+        let filter = filter.as_bytes();
+        let mut data = vec![0x00, ((filter.len() as u8) << 4) | SHOW_UI_ACCOUNT_KEY_DATA];
+        data.extend(filter);
+        data.extend([0x11, 0xC7]);
+        data.extend(battery);
+        let service_data = ServiceData::new(Uuid::from_u16(0xFE2C), data);
+
+        let result = FpDecoder::get_non_discoverable_data_from_service_data(&service_data);
+        assert!(result.is_ok());
+        let result = result.unwrap();
+        assert!(result.show_ui());
+        assert_eq!(result.battery, Some(battery.to_vec()));
+        assert_eq!(
+            result.battery_status(),
+            Some(BatteryStatus::try_from(&battery[1..]).unwrap())
+        );
+        assert_eq!(result.find_account_key(&[account_key]), Some(account_key));
+
+        let other_key = AccountKey::try_from(&[&[0x04], &[0x11; 15][..]].concat()[..]).unwrap();
+        assert_eq!(result.find_account_key(&[other_key]), None);
+    }
+
+    #[test]
+    fn test_get_non_discoverable_data_no_account_keys() {
+        let service_data = ServiceData::new(Uuid::from_u16(0xFE2C), vec![0x00, 0x00]);
+        let result = FpDecoder::get_non_discoverable_data_from_service_data(&service_data);
+        assert!(result.is_ok());
+        let result = result.unwrap();
+        assert!(result.show_ui());
+        assert_eq!(result.find_account_key(&[AccountKey::generate()]), None);
+    }
+
+    #[test]
+    fn test_get_non_discoverable_data_invalid() {
+        // Field longer than the payload.
+        let data = vec![0x00, 0x40, 0xAA, 0xBB];
+        let service_data = ServiceData::new(Uuid::from_u16(0xFE2C), data);
+        let result = FpDecoder::get_non_discoverable_data_from_service_data(&service_data);
+        assert!(matches!(result.unwrap_err(), FpError::ContractViolation(_)));
+
+        // Filter without salt.
+        let data = vec![0x00, 0x20, 0xAA, 0xBB];
+        let service_data = ServiceData::new(Uuid::from_u16(0xFE2C), data);
+        let result = FpDecoder::get_non_discoverable_data_from_service_data(&service_data);
+        assert!(matches!(result.unwrap_err(), FpError::ContractViolation(_)));
+
+        // Unknown version.
+        let data = vec![0x10, 0x00];
+        let service_data = ServiceData::new(Uuid::from_u16(0xFE2C), data);
+        let result = FpDecoder::get_non_discoverable_data_from_service_data(&service_data);
         assert!(matches!(result.unwrap_err(), FpError::NotImplemented(_)));
     }
 }
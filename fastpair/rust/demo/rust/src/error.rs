@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use bluetooth::BluetoothError;
 use thiserror::Error;
 
 /// Library error type.
@@ -36,8 +37,22 @@ pub enum FpError {
     /// return this error instead.
     #[error("internal error: {0}")]
     Internal(String),
+    /// Reported when a Bluetooth operation fails, e.g. because the Provider
+    /// moved out of range during a procedure.
+    #[error("bluetooth error: {0}")]
+    Bluetooth(String),
+    /// Reported when a step of a procedure didn't complete in time, e.g.
+    /// because the Provider stopped answering.
+    #[error("timed out: {0}")]
+    Timeout(String),
     /// Reported when an error was intentionally raised by test code.
     #[error("intentional error")]
     #[cfg(test)]
     Test,
 }
+
+impl From<BluetoothError> for FpError {
+    fn from(err: BluetoothError) -> Self {
+        FpError::Bluetooth(err.to_string())
+    }
+}
@@ -0,0 +1,315 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::ErrorKind,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::{
+    advertisement::ModelId,
+    error::FpError,
+    fetcher::{DeviceInfo, FpFetcher},
+};
+
+/// Device information as cached in memory and saved in JSON, along with
+/// when it was fetched, in seconds since the Unix epoch.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CacheEntry {
+    fetched_at: u64,
+    device: DeviceInfo,
+    /// Whether a thread is fetching this entry again.
+    #[serde(skip)]
+    revalidating: bool,
+}
+
+/// State shared with the threads revalidating stale entries.
+struct CacheState<F: FpFetcher> {
+    inner: F,
+    /// Directory the entries are saved in, one JSON file per model ID.
+    path: String,
+    entries: Mutex<HashMap<ModelId, CacheEntry>>,
+}
+
+/// Caching decorator over another `FpFetcher`, keeping the device
+/// information it fetched both in memory and on the local filesystem, so
+/// that it's still available offline.
+///
+/// Entries are fresh for `ttl` after being fetched. Stale entries are
+/// still returned right away, while a thread fetches them again in the
+/// background. They're kept as long as fetching them again fails.
+pub(crate) struct FpFetcherCache<F: FpFetcher> {
+    state: Arc<CacheState<F>>,
+    ttl: Duration,
+}
+
+impl<F: FpFetcher + Send + Sync + 'static> FpFetcherCache<F> {
+    pub(crate) fn new(inner: F, path: String, ttl: Duration) -> Self {
+        FpFetcherCache {
+            state: Arc::new(CacheState {
+                inner,
+                path,
+                entries: Mutex::new(HashMap::new()),
+            }),
+            ttl,
+        }
+    }
+
+    /// Fetch the entry for `model_id` again in the background, unless it's
+    /// already being fetched.
+    fn revalidate(&self, model_id: &ModelId) {
+        match self.state.entries.lock().unwrap().get_mut(model_id) {
+            Some(entry) if !entry.revalidating => entry.revalidating = true,
+            _ => return,
+        }
+
+        let state = self.state.clone();
+        let model_id = model_id.to_owned();
+        thread::spawn(move || {
+            let result = state.fetch(&model_id);
+            if let Some(entry) = state.entries.lock().unwrap().get_mut(&model_id) {
+                entry.revalidating = false;
+            }
+            if let Err(err) = result {
+                warn!("Keeping stale device info of model {}: {}", model_id, err);
+            }
+        });
+    }
+}
+
+impl<F: FpFetcher> CacheState<F> {
+    /// Retrieve the entry for `model_id` from memory, or else from the
+    /// filesystem.
+    fn cached(&self, model_id: &ModelId) -> Option<CacheEntry> {
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.get(model_id) {
+            return Some(entry.clone());
+        }
+
+        let entry = match self.read(model_id) {
+            Ok(entry) => entry?,
+            Err(err) => {
+                warn!("Ignoring cached device info of model {}: {}", model_id, err);
+                return None;
+            }
+        };
+        entries.insert(model_id.to_owned(), entry.clone());
+        Some(entry)
+    }
+
+    /// Fetch the device information of `model_id` from the inner fetcher,
+    /// and cache it.
+    fn fetch(&self, model_id: &ModelId) -> Result<DeviceInfo, FpError> {
+        let device = self.inner.get_device_info_from_model_id(model_id)?;
+        let entry = CacheEntry {
+            fetched_at: now(),
+            device: device.clone(),
+            revalidating: false,
+        };
+
+        if let Err(err) = self.write(model_id, &entry) {
+            warn!("Failed to save device info of model {}: {}", model_id, err);
+        }
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(model_id.to_owned(), entry);
+        Ok(device)
+    }
+
+    fn file_path(&self, model_id: &ModelId) -> String {
+        format!("{}/{}.json", self.path, model_id)
+    }
+
+    /// Read the entry saved for `model_id`, if any.
+    fn read(&self, model_id: &ModelId) -> Result<Option<CacheEntry>, FpError> {
+        let contents = match fs::read_to_string(self.file_path(model_id)) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(FpError::AccessDenied(err.to_string())),
+        };
+
+        serde_json::from_str(&contents)
+            .map(Some)
+            .map_err(|err| FpError::ContractViolation(err.to_string()))
+    }
+
+    fn write(&self, model_id: &ModelId, entry: &CacheEntry) -> Result<(), FpError> {
+        let contents = serde_json::to_string_pretty(entry)
+            .map_err(|err| FpError::Internal(err.to_string()))?;
+
+        fs::create_dir_all(&self.path)
+            .and_then(|_| fs::write(self.file_path(model_id), contents))
+            .map_err(|err| FpError::AccessDenied(err.to_string()))
+    }
+}
+
+impl<F: FpFetcher + Send + Sync + 'static> FpFetcher for FpFetcherCache<F> {
+    /// Retrieve device information for the provided Model ID from the cache,
+    /// fetching it from the inner fetcher on a miss.
+    fn get_device_info_from_model_id(&self, model_id: &ModelId) -> Result<DeviceInfo, FpError> {
+        match self.state.cached(model_id) {
+            Some(entry) => {
+                if now().saturating_sub(entry.fetched_at) >= self.ttl.as_secs() {
+                    self.revalidate(model_id);
+                }
+                Ok(entry.device)
+            }
+            None => self.state.fetch(model_id),
+        }
+    }
+}
+
+/// Retrieve the current time, in seconds since the Unix epoch.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        env, process,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Instant,
+    };
+
+    /// Fetcher returning the latest device information set, and counting
+    /// how many times it's called.
+    #[derive(Clone, Default)]
+    struct TestFetcher {
+        name: Arc<Mutex<Option<String>>>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl TestFetcher {
+        fn set_name(&self, name: Option<&str>) {
+            *self.name.lock().unwrap() = name.map(String::from);
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    impl FpFetcher for TestFetcher {
+        fn get_device_info_from_model_id(
+            &self,
+            _model_id: &ModelId,
+        ) -> Result<DeviceInfo, FpError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            match self.name.lock().unwrap().as_ref() {
+                Some(name) => Ok(DeviceInfo::new(String::from("image_url"), name.clone())),
+                None => Err(FpError::Test),
+            }
+        }
+    }
+
+    fn cache_path(name: &str) -> String {
+        env::temp_dir()
+            .join(format!("{}_{}", name, process::id()))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Wait for `condition` to hold, failing after a second.
+    fn wait_for(condition: impl Fn() -> bool) {
+        let start = Instant::now();
+        while !condition() {
+            assert!(start.elapsed() < Duration::from_secs(1));
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_cache_fresh() {
+        let path = cache_path("device_info_fresh");
+        let fetcher = TestFetcher::default();
+        fetcher.set_name(Some("name"));
+        let cache = FpFetcherCache::new(fetcher.clone(), path.clone(), Duration::from_secs(60));
+        let model_id = String::from("525296");
+
+        for _ in 0..3 {
+            let device = cache.get_device_info_from_model_id(&model_id).unwrap();
+            assert_eq!(device.name(), "name");
+        }
+        assert_eq!(fetcher.calls(), 1);
+
+        // A new cache finds the entry on the filesystem, while offline.
+        fetcher.set_name(None);
+        let cache = FpFetcherCache::new(fetcher.clone(), path.clone(), Duration::from_secs(60));
+        let device = cache.get_device_info_from_model_id(&model_id).unwrap();
+        assert_eq!(device.name(), "name");
+        assert_eq!(fetcher.calls(), 1);
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_cache_stale() {
+        let path = cache_path("device_info_stale");
+        let fetcher = TestFetcher::default();
+        fetcher.set_name(Some("name"));
+        // Entries are stale right away.
+        let cache = FpFetcherCache::new(fetcher.clone(), path.clone(), Duration::ZERO);
+        let model_id = String::from("525296");
+        cache.get_device_info_from_model_id(&model_id).unwrap();
+
+        // The stale entry is returned while it's fetched again.
+        fetcher.set_name(Some("new name"));
+        let device = cache.get_device_info_from_model_id(&model_id).unwrap();
+        assert_eq!(device.name(), "name");
+        wait_for(|| {
+            cache
+                .get_device_info_from_model_id(&model_id)
+                .unwrap()
+                .name()
+                == "new name"
+        });
+
+        // The stale entry is kept when fetching it again fails.
+        fetcher.set_name(None);
+        let calls = fetcher.calls();
+        let device = cache.get_device_info_from_model_id(&model_id).unwrap();
+        assert_eq!(device.name(), "new name");
+        wait_for(|| fetcher.calls() > calls);
+        let device = cache.get_device_info_from_model_id(&model_id).unwrap();
+        assert_eq!(device.name(), "new name");
+
+        wait_for(|| !cache.state.entries.lock().unwrap()[&model_id].revalidating);
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_cache_miss_error() {
+        let path = cache_path("device_info_miss");
+        let fetcher = TestFetcher::default();
+        let cache = FpFetcherCache::new(fetcher, path, Duration::from_secs(60));
+
+        let result = cache.get_device_info_from_model_id(&String::from("525296"));
+        assert!(matches!(result, Err(FpError::Test)));
+    }
+}
@@ -12,7 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use serde::Deserialize;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
 
 use crate::{advertisement::ModelId, error::FpError};
 
@@ -23,11 +24,22 @@ pub(crate) trait FpFetcher {
 }
 
 /// Holds Fast Pair device information parsed from JSON.
-#[derive(Clone, Deserialize)]
+#[derive(Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct DeviceInfo {
     image_url: String,
     name: String,
+    #[serde(default)]
+    anti_spoofing_key_pair: AntiSpoofingKeyPair,
+}
+
+/// Holds the anti-spoofing key pair of a device model, of which only the
+/// public key is distributed to Seekers. Models certified before Fast Pair
+/// version 2 have none.
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct AntiSpoofingKeyPair {
+    public_key: Option<String>,
 }
 
 /// Holds top-level Fast Pair information parsed from JSON. See `local`
@@ -43,7 +55,20 @@ impl DeviceInfo {
     // warnings. Can be removed in the future.
     #[cfg(test)]
     pub(crate) fn new(image_url: String, name: String) -> Self {
-        DeviceInfo { image_url, name }
+        DeviceInfo {
+            image_url,
+            name,
+            anti_spoofing_key_pair: AntiSpoofingKeyPair::default(),
+        }
+    }
+
+    /// Set the anti-spoofing public key of the model, encoding it in base64
+    /// as in JSON.
+    #[cfg(test)]
+    #[cfg_attr(not(feature = "mock"), allow(dead_code))]
+    pub(crate) fn with_anti_spoofing_public_key(mut self, key: &[u8]) -> Self {
+        self.anti_spoofing_key_pair.public_key = Some(STANDARD.encode(key));
+        self
     }
 
     pub(crate) fn name(&self) -> &String {
@@ -53,6 +78,20 @@ impl DeviceInfo {
     pub(crate) fn image_url(&self) -> &String {
         &self.image_url
     }
+
+    /// Decode the base64 anti-spoofing public key of the model, if it has
+    /// one.
+    pub(crate) fn anti_spoofing_public_key(&self) -> Result<Option<Vec<u8>>, FpError> {
+        self.anti_spoofing_key_pair
+            .public_key
+            .as_ref()
+            .map(|key| {
+                STANDARD
+                    .decode(key)
+                    .map_err(|err| FpError::ContractViolation(err.to_string()))
+            })
+            .transpose()
+    }
 }
 
 impl JsonData {
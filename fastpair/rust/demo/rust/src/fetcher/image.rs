@@ -0,0 +1,295 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::Read,
+    path::Path,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use tracing::warn;
+
+use crate::{advertisement::ModelId, error::FpError};
+
+/// Types that can download the image of a device model from its URL.
+pub(crate) trait ImageDownloader {
+    fn download(&self, url: &str) -> Result<Vec<u8>, FpError>;
+}
+
+/// A struct for downloading images over HTTP(S).
+pub(crate) struct HttpImageDownloader {
+    timeout: Duration,
+}
+
+impl HttpImageDownloader {
+    pub(crate) fn new(timeout: Duration) -> Self {
+        HttpImageDownloader { timeout }
+    }
+}
+
+impl ImageDownloader for HttpImageDownloader {
+    fn download(&self, url: &str) -> Result<Vec<u8>, FpError> {
+        let response = ureq::get(url)
+            .timeout(self.timeout)
+            .call()
+            .map_err(|err| FpError::AccessDenied(err.to_string()))?;
+
+        let mut image = Vec::new();
+        response
+            .into_reader()
+            .read_to_end(&mut image)
+            .map_err(|err| FpError::AccessDenied(err.to_string()))?;
+        Ok(image)
+    }
+}
+
+/// State of the download of an image missing from the cache.
+enum Download {
+    InProgress,
+    Failed(Instant),
+}
+
+/// State shared with the threads downloading images.
+struct ImageCacheState<D: ImageDownloader> {
+    downloader: D,
+    /// Directory the images are saved in, one file per model ID.
+    path: String,
+    downloads: Mutex<HashMap<ModelId, Download>>,
+}
+
+/// Cache of the images of device models on the local filesystem, so that
+/// they're downloaded once rather than every time a device is displayed.
+///
+/// Missing images are downloaded on a thread of their own. A failed download
+/// is only attempted again after `retry_after`.
+pub(crate) struct FpImageCache<D: ImageDownloader> {
+    state: Arc<ImageCacheState<D>>,
+    retry_after: Duration,
+}
+
+impl<D: ImageDownloader + Send + Sync + 'static> FpImageCache<D> {
+    pub(crate) fn new(downloader: D, path: String, retry_after: Duration) -> Self {
+        FpImageCache {
+            state: Arc::new(ImageCacheState {
+                downloader,
+                path,
+                downloads: Mutex::new(HashMap::new()),
+            }),
+            retry_after,
+        }
+    }
+
+    /// Retrieve the path of the cached image of `model_id`. If it's missing,
+    /// it's downloaded from `url` in the background, and `on_downloaded` is
+    /// called with its path once it's cached.
+    pub(crate) fn image_path<F>(
+        &self,
+        model_id: &ModelId,
+        url: &str,
+        on_downloaded: F,
+    ) -> Option<String>
+    where
+        F: FnOnce(String) + Send + 'static,
+    {
+        let file_path = self.state.file_path(model_id);
+        if Path::new(&file_path).is_file() {
+            return Some(file_path);
+        }
+
+        {
+            let mut downloads = self.state.downloads.lock().unwrap();
+            match downloads.get(model_id) {
+                Some(Download::InProgress) => return None,
+                Some(Download::Failed(time)) if time.elapsed() < self.retry_after => return None,
+                _ => downloads.insert(model_id.to_owned(), Download::InProgress),
+            };
+        }
+
+        let state = self.state.clone();
+        let model_id = model_id.to_owned();
+        let url = url.to_owned();
+        thread::spawn(move || match state.download(&model_id, &url) {
+            Ok(file_path) => {
+                state.downloads.lock().unwrap().remove(&model_id);
+                on_downloaded(file_path);
+            }
+            Err(err) => {
+                warn!("Failed to download image of model {}: {}", model_id, err);
+                state
+                    .downloads
+                    .lock()
+                    .unwrap()
+                    .insert(model_id, Download::Failed(Instant::now()));
+            }
+        });
+        None
+    }
+}
+
+impl<D: ImageDownloader> ImageCacheState<D> {
+    fn file_path(&self, model_id: &ModelId) -> String {
+        format!("{}/{}.png", self.path, model_id)
+    }
+
+    /// Download the image of `model_id` from `url` and save it, returning
+    /// its path.
+    fn download(&self, model_id: &ModelId, url: &str) -> Result<String, FpError> {
+        let image = self.downloader.download(url)?;
+
+        // Write to a temporary file first, so that a partially written
+        // image is never served.
+        let file_path = self.file_path(model_id);
+        let temp_path = format!("{}.tmp", file_path);
+        fs::create_dir_all(&self.path)
+            .and_then(|_| fs::write(&temp_path, image))
+            .and_then(|_| fs::rename(&temp_path, &file_path))
+            .map_err(|err| FpError::AccessDenied(err.to_string()))?;
+        Ok(file_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        env, process,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc,
+        },
+    };
+
+    const MODEL_ID: &str = "525296";
+    const URL: &str = "https://example.com/image.png";
+
+    /// Downloader returning the latest image set, and counting how many
+    /// times it's called.
+    #[derive(Clone, Default)]
+    struct TestDownloader {
+        image: Arc<Mutex<Option<Vec<u8>>>>,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl TestDownloader {
+        fn set_image(&self, image: Option<&[u8]>) {
+            *self.image.lock().unwrap() = image.map(<[u8]>::to_vec);
+        }
+
+        fn calls(&self) -> usize {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    impl ImageDownloader for TestDownloader {
+        fn download(&self, url: &str) -> Result<Vec<u8>, FpError> {
+            assert_eq!(url, URL);
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            self.image.lock().unwrap().clone().ok_or(FpError::Test)
+        }
+    }
+
+    fn cache_path(name: &str) -> String {
+        env::temp_dir()
+            .join(format!("{}_{}", name, process::id()))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Wait for the download of `MODEL_ID` to be over, failing after a
+    /// second.
+    fn wait_for_download<D: ImageDownloader>(cache: &FpImageCache<D>) {
+        let start = Instant::now();
+        while matches!(
+            cache.state.downloads.lock().unwrap().get(MODEL_ID),
+            Some(Download::InProgress)
+        ) {
+            assert!(start.elapsed() < Duration::from_secs(1));
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_image_cache() {
+        let path = cache_path("image");
+        let downloader = TestDownloader::default();
+        downloader.set_image(Some(&[0x89, 0x50, 0x4E, 0x47]));
+        let cache = FpImageCache::new(downloader.clone(), path.clone(), Duration::from_secs(60));
+        let model_id = String::from(MODEL_ID);
+
+        let (sender, receiver) = mpsc::channel();
+        assert_eq!(
+            cache.image_path(&model_id, URL, move |path| sender.send(path).unwrap()),
+            None
+        );
+        let file_path = receiver.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(fs::read(&file_path).unwrap(), [0x89, 0x50, 0x4E, 0x47]);
+
+        // The image is now served from the filesystem, even by a new cache.
+        let cache = FpImageCache::new(downloader.clone(), path.clone(), Duration::from_secs(60));
+        assert_eq!(
+            cache.image_path(&model_id, URL, |_| panic!("downloaded again")),
+            Some(file_path)
+        );
+        assert_eq!(downloader.calls(), 1);
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn test_image_cache_failed_download() {
+        let path = cache_path("image_failed");
+        let downloader = TestDownloader::default();
+        let cache = FpImageCache::new(downloader.clone(), path.clone(), Duration::from_secs(60));
+        let model_id = String::from(MODEL_ID);
+
+        assert_eq!(
+            cache.image_path(&model_id, URL, |_| panic!("downloaded")),
+            None
+        );
+        wait_for_download(&cache);
+        assert_eq!(downloader.calls(), 1);
+
+        // The download isn't attempted again right away.
+        downloader.set_image(Some(&[0x89, 0x50, 0x4E, 0x47]));
+        assert_eq!(
+            cache.image_path(&model_id, URL, |_| panic!("downloaded")),
+            None
+        );
+        assert_eq!(downloader.calls(), 1);
+
+        // Unless the retry delay is over.
+        downloader.set_image(None);
+        let cache = FpImageCache::new(downloader.clone(), path.clone(), Duration::ZERO);
+        assert_eq!(
+            cache.image_path(&model_id, URL, |_| panic!("downloaded")),
+            None
+        );
+        wait_for_download(&cache);
+        downloader.set_image(Some(&[0x89, 0x50, 0x4E, 0x47]));
+        let (sender, receiver) = mpsc::channel();
+        assert_eq!(
+            cache.image_path(&model_id, URL, move |path| sender.send(path).unwrap()),
+            None
+        );
+        receiver.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(downloader.calls(), 3);
+
+        fs::remove_dir_all(path).unwrap();
+    }
+}
@@ -12,11 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub(crate) mod cache;
 pub(crate) mod common;
 pub(crate) mod fs;
+pub(crate) mod image;
 
 #[cfg(test)]
 pub(crate) mod mock;
 
+pub(crate) use cache::*;
 pub(crate) use common::*;
 pub(crate) use fs::*;
+pub(crate) use image::*;
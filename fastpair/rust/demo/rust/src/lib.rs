@@ -12,9 +12,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod account_key;
 mod advertisement;
 mod api;
+mod battery;
 mod bridge_generated; /* AUTO INJECTED BY flutter_rust_bridge. This line may not be accurate, and you can change it according to your needs. */
+mod crypto;
 mod decoder;
 mod error;
 mod fetcher;
+mod nearby;
+mod procedures;
+mod seeker;
+mod timer;
+
+#[cfg(test)]
+mod simulator;
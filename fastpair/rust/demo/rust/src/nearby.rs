@@ -0,0 +1,193 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::{Duration, Instant};
+
+use bluetooth::BleAddress;
+use ttl_cache::TtlCache;
+
+use crate::advertisement::FpPairingAdvertisement;
+
+/// The Fast Pair devices advertising nearby, each known by the latest
+/// advertisement sent from its address. Devices are forgotten once they
+/// haven't advertised for `ttl`, or when the list is full and they're the
+/// ones that advertised least recently.
+pub(crate) struct NearbyDevices {
+    /// Latest advertisement of each device, along with the instant it
+    /// expires at. The cache drops it at about the same time, but is only
+    /// relied on for capacity, so that `next_expiry()` agrees with which
+    /// devices are still nearby.
+    advertisements: TtlCache<BleAddress, (FpPairingAdvertisement, Instant)>,
+    ttl: Duration,
+}
+
+impl NearbyDevices {
+    pub(crate) fn new(capacity: usize, ttl: Duration) -> Self {
+        NearbyDevices {
+            advertisements: TtlCache::new(capacity),
+            ttl,
+        }
+    }
+
+    /// Replace the latest advertisement of the device which sent `adv`.
+    pub(crate) fn update(&mut self, adv: FpPairingAdvertisement) {
+        let expiry = Instant::now() + self.ttl;
+        self.advertisements
+            .insert(adv.address(), (adv, expiry), self.ttl);
+    }
+
+    /// Retrieve the latest advertisement of the device with address
+    /// `address`, if it's still nearby.
+    pub(crate) fn get(&self, address: &BleAddress) -> Option<&FpPairingAdvertisement> {
+        self.advertisements
+            .get(address)
+            .filter(|(_, expiry)| *expiry > Instant::now())
+            .map(|(adv, _)| adv)
+    }
+
+    pub(crate) fn get_mut(&mut self, address: &BleAddress) -> Option<&mut FpPairingAdvertisement> {
+        self.advertisements
+            .get_mut(address)
+            .filter(|(_, expiry)| *expiry > Instant::now())
+            .map(|(adv, _)| adv)
+    }
+
+    /// Forget the device with address `address`, until it advertises again.
+    pub(crate) fn remove(&mut self, address: &BleAddress) -> Option<FpPairingAdvertisement> {
+        self.advertisements.remove(address).map(|(adv, _)| adv)
+    }
+
+    /// Retrieve the latest advertisements of the devices still nearby,
+    /// closest first.
+    pub(crate) fn ranked(&mut self) -> Vec<&FpPairingAdvertisement> {
+        let now = Instant::now();
+        let mut ranked: Vec<_> = self
+            .advertisements
+            .iter()
+            .filter(|(_, (_, expiry))| *expiry > now)
+            .map(|(_, (adv, _))| adv)
+            .collect();
+        // We should never get NaN, so it's okay to unwrap.
+        ranked.sort_by(|adv1, adv2| adv1.distance().partial_cmp(&adv2.distance()).unwrap());
+        ranked
+    }
+
+    /// Retrieve the instant the next device is forgotten at, unless it
+    /// advertises again, or `None` if no device is nearby.
+    pub(crate) fn next_expiry(&mut self) -> Option<Instant> {
+        let now = Instant::now();
+        self.advertisements
+            .iter()
+            .map(|(_, (_, expiry))| *expiry)
+            .filter(|expiry| *expiry > now)
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::thread;
+
+    use bluetooth::{BleAddressKind, BleAdvertisement, ServiceData, Uuid};
+
+    use crate::fetcher::{mock::FpFetcherMock, DeviceInfo, FpFetcher};
+
+    const MODEL_ID: [u8; 3] = [0x52, 0x52, 0x96];
+
+    /// Address of the `n`th test device.
+    fn address(n: u64) -> BleAddress {
+        BleAddress::new(0x112233445500 + n, BleAddressKind::Public)
+    }
+
+    /// Advertisement of the `n`th test device, received with `rssi` from a
+    /// device transmitting at 0 dBm. Every device has the same model.
+    fn advertisement(n: u64, rssi: i16) -> FpPairingAdvertisement {
+        let device_info = Ok(DeviceInfo::new(
+            String::from("image_url"),
+            String::from("name"),
+        ));
+        let fetcher: Box<dyn FpFetcher> = Box::new(FpFetcherMock::new(device_info));
+
+        FpPairingAdvertisement::new(
+            BleAdvertisement::new(address(n), Some(rssi), Some(0)),
+            &ServiceData::new(Uuid::from_u16(0xFE2C), MODEL_ID.to_vec()),
+            &fetcher,
+        )
+        .unwrap()
+    }
+
+    fn addresses(devices: &mut NearbyDevices) -> Vec<BleAddress> {
+        devices
+            .ranked()
+            .into_iter()
+            .map(|adv| adv.address())
+            .collect()
+    }
+
+    #[test]
+    fn test_ranked_by_distance() {
+        let mut devices = NearbyDevices::new(16, Duration::from_secs(60));
+        devices.update(advertisement(1, -70));
+        devices.update(advertisement(2, -50));
+        devices.update(advertisement(3, -60));
+        // Devices of the same model are told apart by their address.
+        assert_eq!(
+            addresses(&mut devices),
+            [address(2), address(3), address(1)]
+        );
+
+        // The latest advertisement of a device replaces the previous one.
+        devices.update(advertisement(1, -40));
+        assert_eq!(
+            addresses(&mut devices),
+            [address(1), address(2), address(3)]
+        );
+
+        assert!(devices.remove(&address(2)).is_some());
+        assert_eq!(addresses(&mut devices), [address(1), address(3)]);
+        assert!(devices.get(&address(2)).is_none());
+    }
+
+    #[test]
+    fn test_ttl() {
+        let mut devices = NearbyDevices::new(16, Duration::from_millis(200));
+        assert!(devices.next_expiry().is_none());
+
+        devices.update(advertisement(1, -70));
+        let first_expiry = devices.next_expiry().unwrap();
+        thread::sleep(Duration::from_millis(120));
+        devices.update(advertisement(2, -50));
+        assert_eq!(addresses(&mut devices), [address(2), address(1)]);
+        assert_eq!(devices.next_expiry(), Some(first_expiry));
+
+        // Devices which stopped advertising are forgotten once they expire.
+        thread::sleep(first_expiry.saturating_duration_since(Instant::now()));
+        assert_eq!(addresses(&mut devices), [address(2)]);
+        assert!(devices.get(&address(1)).is_none());
+        assert!(devices.next_expiry().unwrap() > first_expiry);
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut devices = NearbyDevices::new(2, Duration::from_secs(60));
+        devices.update(advertisement(1, -50));
+        devices.update(advertisement(2, -60));
+        devices.update(advertisement(3, -70));
+
+        // The device which advertised least recently is forgotten.
+        assert_eq!(addresses(&mut devices), [address(2), address(3)]);
+    }
+}
@@ -0,0 +1,379 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use bluetooth::{
+    api::{ClassicDevice, GattClient, PairingDelegate},
+    BleAddress, CharacteristicValueStream, ClassicAddress, GattCharacteristic, PairingResult,
+    Platform, ProtectionLevel, Uuid, WriteType,
+};
+use futures::{channel::mpsc::UnboundedSender, executor, lock::Mutex, StreamExt};
+use rand::{rngs::OsRng, RngCore};
+use tracing::{info, warn};
+
+use crate::{
+    account_key::AccountKey,
+    crypto::{ecdh_with_ephemeral_key, AesKey, Block, PUBLIC_KEY_LENGTH},
+    error::FpError,
+};
+
+/// UUID of the Fast Pair GATT service.
+pub(crate) const FAST_PAIR_SERVICE_UUID: Uuid = Uuid::from_u16(0xFE2C);
+/// UUIDs of the Key-based Pairing characteristic, and the 16-bit UUID it had
+/// before version 2 of the specification.
+pub(crate) const KEY_BASED_PAIRING_UUID: Uuid =
+    Uuid::from_u128(0xFE2C1234_8366_4814_8EB0_01DE32100BEA);
+const LEGACY_KEY_BASED_PAIRING_UUID: Uuid = Uuid::from_u16(0x1234);
+/// UUIDs of the Passkey characteristic, and the 16-bit UUID it had before
+/// version 2 of the specification.
+pub(crate) const PASSKEY_UUID: Uuid = Uuid::from_u128(0xFE2C1235_8366_4814_8EB0_01DE32100BEA);
+const LEGACY_PASSKEY_UUID: Uuid = Uuid::from_u16(0x1235);
+/// UUIDs of the Account Key characteristic, and the 16-bit UUID it had
+/// before version 2 of the specification.
+pub(crate) const ACCOUNT_KEY_UUID: Uuid = Uuid::from_u128(0xFE2C1236_8366_4814_8EB0_01DE32100BEA);
+const LEGACY_ACCOUNT_KEY_UUID: Uuid = Uuid::from_u16(0x1236);
+
+/// Message types of the blocks exchanged over the Key-based Pairing and
+/// Passkey characteristics.
+pub(crate) const KEY_BASED_PAIRING_REQUEST: u8 = 0x00;
+pub(crate) const KEY_BASED_PAIRING_RESPONSE: u8 = 0x01;
+pub(crate) const SEEKER_PASSKEY: u8 = 0x02;
+pub(crate) const PROVIDER_PASSKEY: u8 = 0x03;
+
+/// Build a block starting with `header`, and filled up with random salt so
+/// that no two encrypted blocks are alike.
+pub(crate) fn salted_block(header: &[u8]) -> Block {
+    let mut block = [0; 16];
+    OsRng.fill_bytes(&mut block[header.len()..]);
+    block[..header.len()].copy_from_slice(header);
+    block
+}
+
+/// Build the raw Key-based Pairing request addressed to the Provider with
+/// the given BLE address, asking it to reply with its BR/EDR address.
+fn key_based_pairing_request(provider_address: BleAddress) -> Block {
+    // Message type, flags, then the Provider's current BLE address.
+    let mut header = [0; 8];
+    header[0] = KEY_BASED_PAIRING_REQUEST;
+    header[2..].copy_from_slice(&u64::from(provider_address).to_be_bytes()[2..]);
+    salted_block(&header)
+}
+
+/// Parse the decrypted Key-based Pairing response, returning the BR/EDR
+/// address of the Provider. A response of any other type means the Provider
+/// didn't derive the same key, so the Seeker must give up.
+fn parse_key_based_pairing_response(response: &Block) -> Result<ClassicAddress, FpError> {
+    if response[0] != KEY_BASED_PAIRING_RESPONSE {
+        return Err(FpError::ContractViolation(format!(
+            "Key-based Pairing response of type {:#04x}",
+            response[0]
+        )));
+    }
+
+    let mut address = [0; 8];
+    address[2..].copy_from_slice(&response[1..7]);
+    Ok(ClassicAddress::from(u64::from_be_bytes(address)))
+}
+
+/// Build the raw block carrying the passkey displayed to the Seeker.
+fn seeker_passkey(passkey: u32) -> Block {
+    let mut header = [0; 4];
+    header[0] = SEEKER_PASSKEY;
+    header[1..].copy_from_slice(&passkey.to_be_bytes()[1..]);
+    salted_block(&header)
+}
+
+/// Parse the decrypted block carrying the passkey displayed to the Provider.
+fn parse_provider_passkey(block: &Block) -> Result<u32, FpError> {
+    if block[0] != PROVIDER_PASSKEY {
+        return Err(FpError::ContractViolation(format!(
+            "Provider passkey block of type {:#04x}",
+            block[0]
+        )));
+    }
+
+    Ok(u32::from_be_bytes([0, block[1], block[2], block[3]]))
+}
+
+/// Wait for the next value notified on `notifications`, which must be a
+/// single block.
+async fn next_block(notifications: &mut CharacteristicValueStream) -> Result<Block, FpError> {
+    let value = notifications.next().await.ok_or_else(|| {
+        FpError::Bluetooth(String::from(
+            "connection lost while waiting for a notification",
+        ))
+    })?;
+
+    Block::try_from(value.as_slice()).map_err(|_| {
+        FpError::ContractViolation(format!(
+            "notification of length {}, expected a single block",
+            value.len()
+        ))
+    })
+}
+
+/// State of a pairing shared with the `PasskeyVerifier` passed to the
+/// platform, which calls it back from a system thread.
+struct Session<G: GattClient> {
+    client: G,
+    /// Key shared with the Provider, which encrypts every message.
+    key: AesKey,
+    passkey: GattCharacteristic,
+    passkey_notifications: CharacteristicValueStream,
+    account_key: GattCharacteristic,
+}
+
+/// Fast Pair initial pairing with a Provider, following the Key-based
+/// Pairing procedure. See:
+/// https://developers.google.com/nearby/fast-pair/specifications/characteristics#procedure
+pub(crate) struct KeyBasedPairing<G: GattClient> {
+    session: Arc<Mutex<Session<G>>>,
+    provider_address: ClassicAddress,
+}
+
+impl<G: GattClient + 'static> KeyBasedPairing<G> {
+    /// Exchange the Key-based Pairing request and response with the Provider
+    /// connected to `client`, whose model has the given anti-spoofing public
+    /// key. On success, both sides share a key and the Provider expects
+    /// pairing over BR/EDR.
+    pub(crate) async fn start(client: G, anti_spoofing_key: &[u8]) -> Result<Self, FpError> {
+        let (key, public_key) = ecdh_with_ephemeral_key(anti_spoofing_key)?;
+        Self::exchange(client, key, Some(public_key)).await
+    }
+
+    /// Exchange the Key-based Pairing request and response with a Provider
+    /// which already stores `account_key`, used as the shared key. Unlike
+    /// initial pairing, the Provider needn't be in pairing mode, so the
+    /// user's devices can be paired to any of their Seekers.
+    pub(crate) async fn start_with_account_key(
+        client: G,
+        account_key: &AccountKey,
+    ) -> Result<Self, FpError> {
+        Self::exchange(client, AesKey::new(account_key.as_bytes()), None).await
+    }
+
+    /// Write the Key-based Pairing request encrypted with `key`, followed by
+    /// `public_key` for the Provider to derive the same key if it doesn't
+    /// know it already, then wait for the response.
+    async fn exchange(
+        mut client: G,
+        key: AesKey,
+        public_key: Option<[u8; PUBLIC_KEY_LENGTH]>,
+    ) -> Result<Self, FpError> {
+        let service = client
+            .discover_services()
+            .await?
+            .into_iter()
+            .find(|service| service.uuid() == FAST_PAIR_SERVICE_UUID)
+            .ok_or_else(|| {
+                FpError::ContractViolation(String::from("Provider without Fast Pair service"))
+            })?;
+        let characteristics = client.discover_characteristics(&service).await?;
+        let find = |uuids: [Uuid; 2]| {
+            characteristics
+                .iter()
+                .find(|characteristic| uuids.contains(&characteristic.uuid()))
+                .copied()
+                .ok_or_else(|| {
+                    FpError::ContractViolation(format!(
+                        "Provider without characteristic {}",
+                        uuids[0]
+                    ))
+                })
+        };
+        let key_based_pairing = find([KEY_BASED_PAIRING_UUID, LEGACY_KEY_BASED_PAIRING_UUID])?;
+        let passkey = find([PASSKEY_UUID, LEGACY_PASSKEY_UUID])?;
+        let account_key = find([ACCOUNT_KEY_UUID, LEGACY_ACCOUNT_KEY_UUID])?;
+
+        // Subscribe before writing, so that no notification is missed.
+        let mut notifications = client.subscribe(&key_based_pairing).await?;
+        let passkey_notifications = client.subscribe(&passkey).await?;
+
+        let mut request = key
+            .encrypt(&key_based_pairing_request(client.address()))
+            .to_vec();
+        if let Some(public_key) = public_key {
+            request.extend(public_key);
+        }
+        client
+            .write_characteristic(&key_based_pairing, &request, WriteType::WithResponse)
+            .await?;
+
+        let response = key.decrypt(&next_block(&mut notifications).await?);
+        let provider_address = parse_key_based_pairing_response(&response)?;
+        client.unsubscribe(&key_based_pairing).await?;
+        info!("Key-based Pairing with Provider {}", provider_address);
+
+        Ok(KeyBasedPairing {
+            session: Arc::new(Mutex::new(Session {
+                client,
+                key,
+                passkey,
+                passkey_notifications,
+                account_key,
+            })),
+            provider_address,
+        })
+    }
+
+    /// Retrieve the BR/EDR address of the Provider, sent in its Key-based
+    /// Pairing response.
+    pub(crate) fn provider_address(&self) -> ClassicAddress {
+        self.provider_address
+    }
+
+    /// Pair with the Provider over BR/EDR, accepting the passkey of the
+    /// numeric comparison only if the Provider proves it displays the same
+    /// one, encrypted with the shared key. `passkey_confirmed` is sent to as
+    /// soon as the passkey is accepted, before pairing completes.
+    pub(crate) async fn pair(
+        &self,
+        passkey_confirmed: UnboundedSender<()>,
+    ) -> Result<PairingResult, FpError> {
+        let device = Platform::new_classic_device(self.provider_address).await?;
+        let verifier = PasskeyVerifier {
+            session: self.session.clone(),
+            passkey_confirmed,
+        };
+
+        Ok(device
+            .pair_with_delegate_and_protection_level(
+                verifier,
+                ProtectionLevel::EncryptionAndAuthentication,
+            )
+            .await?)
+    }
+
+    /// Write `account_key` to the Provider, encrypted with the shared key,
+    /// once pairing succeeded. The Provider keeps it to recognize the
+    /// user's Seekers from then on.
+    pub(crate) async fn write_account_key(&self, account_key: &AccountKey) -> Result<(), FpError> {
+        let mut session = self.session.lock().await;
+        let Session {
+            client,
+            key,
+            account_key: characteristic,
+            ..
+        } = &mut *session;
+
+        let block = key.encrypt(account_key.as_bytes());
+        Ok(client
+            .write_characteristic(characteristic, &block, WriteType::WithResponse)
+            .await?)
+    }
+}
+
+/// Answers the numeric comparison of the BR/EDR pairing by exchanging
+/// passkeys with the Provider over the Passkey characteristic.
+struct PasskeyVerifier<G: GattClient> {
+    session: Arc<Mutex<Session<G>>>,
+    passkey_confirmed: UnboundedSender<()>,
+}
+
+impl<G: GattClient> PasskeyVerifier<G> {
+    async fn verify(&self, passkey: u32) -> Result<bool, FpError> {
+        let mut session = self.session.lock().await;
+        let Session {
+            client,
+            key,
+            passkey: characteristic,
+            passkey_notifications,
+            ..
+        } = &mut *session;
+
+        let block = key.encrypt(&seeker_passkey(passkey));
+        client
+            .write_characteristic(characteristic, &block, WriteType::WithResponse)
+            .await?;
+
+        let block = key.decrypt(&next_block(passkey_notifications).await?);
+        Ok(parse_provider_passkey(&block)? == passkey)
+    }
+}
+
+impl<G: GattClient + 'static> PairingDelegate for PasskeyVerifier<G> {
+    fn confirm_passkey(&self, passkey: u32) -> bool {
+        match executor::block_on(self.verify(passkey)) {
+            Ok(true) => {
+                // The Seeker may have stopped waiting for pairing already.
+                let _ = self.passkey_confirmed.unbounded_send(());
+                true
+            }
+            Ok(false) => {
+                warn!("Provider displays another passkey, rejecting pairing.");
+                false
+            }
+            Err(err) => {
+                warn!("Error verifying passkey: {}", err);
+                false
+            }
+        }
+    }
+
+    fn provide_pin(&self) -> Option<String> {
+        // Fast Pair only allows numeric comparison.
+        None
+    }
+
+    fn display_pin(&self, _pin: &str) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use bluetooth::BleAddressKind;
+
+    #[test]
+    fn test_key_based_pairing_request() {
+        let addr = BleAddress::new(0xAABBCCDDEEFF, BleAddressKind::Public);
+        let request = key_based_pairing_request(addr);
+
+        assert_eq!(
+            request[..8],
+            [0x00, 0x00, 0xAA, 0xBB, 0xCC, 0xDD, 0xEE, 0xFF]
+        );
+        // Salt differs from one request to the next.
+        assert_ne!(request, key_based_pairing_request(addr));
+    }
+
+    #[test]
+    fn test_parse_key_based_pairing_response() {
+        let mut response = salted_block(&[0x01, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66]);
+        assert_eq!(
+            parse_key_based_pairing_response(&response).unwrap(),
+            ClassicAddress::from(0x112233445566)
+        );
+
+        response[0] = 0x02;
+        assert!(matches!(
+            parse_key_based_pairing_response(&response),
+            Err(FpError::ContractViolation(_))
+        ));
+    }
+
+    #[test]
+    fn test_passkey_blocks() {
+        let block = seeker_passkey(123456);
+        assert_eq!(block[..4], [0x02, 0x01, 0xE2, 0x40]);
+        assert!(matches!(
+            parse_provider_passkey(&block),
+            Err(FpError::ContractViolation(_))
+        ));
+
+        let block = salted_block(&[0x03, 0x01, 0xE2, 0x40]);
+        assert_eq!(parse_provider_passkey(&block).unwrap(), 123456);
+    }
+}
@@ -0,0 +1,634 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{future::Future, time::Duration};
+
+use bluetooth::{
+    api::{ClassicDevice, GattClient},
+    BleAddress, ClassicAddress, PairingResult, Platform, RetryPolicy,
+};
+use futures::{
+    channel::mpsc,
+    future::{self, Either},
+    pin_mut, StreamExt,
+};
+use tracing::{info, warn};
+
+use crate::{
+    account_key::{AccountKey, AccountKeyStore},
+    advertisement::{FpPairingAdvertisement, ModelId},
+    error::FpError,
+    fetcher::FpFetcher,
+    procedures::KeyBasedPairing,
+    timer::sleep,
+};
+
+/// Default time allowed for each GATT step, i.e. Key-based Pairing and the
+/// account key write.
+const DEFAULT_GATT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default time allowed for bonding, which includes the passkey exchange.
+const DEFAULT_BONDING_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// States `FastPairSeeker` goes through while pairing with a Provider, as
+/// reported to the caller.
+#[derive(Clone, PartialEq, Debug)]
+pub(crate) enum SeekerState {
+    /// The Provider was discovered, and pairing with it was requested.
+    Discovered,
+    /// Fetching the device information of the model, for its anti-spoofing
+    /// key.
+    FetchingMetadata,
+    /// Exchanging the Key-based Pairing request and response over GATT, in
+    /// the given attempt, starting at 1.
+    KeyBasedPairing { attempt: u32 },
+    /// Pairing over BR/EDR with the Provider at the given address.
+    Bonding(ClassicAddress),
+    /// The Provider proved it displays the same passkey, while bonding.
+    PasskeyConfirmed,
+    /// Writing a new account key to the Provider, once paired.
+    WritingAccountKey,
+    /// Pairing is over, with the given result.
+    Completed(PairingResult),
+    /// Pairing failed with the given error.
+    Failed(FpError),
+}
+
+/// Key proving the Seeker's identity to the Provider during Key-based
+/// Pairing.
+enum PairingKey {
+    /// Initial pairing, with the anti-spoofing public key of the model.
+    AntiSpoofing(Vec<u8>),
+    /// Subsequent pairing with one of the user's devices.
+    Account(AccountKey),
+}
+
+/// How the Seeker bonds with the Provider.
+enum Bonding<G: GattClient> {
+    /// Fast Pair, checking the passkey with the Provider.
+    FastPair(KeyBasedPairing<G>),
+    /// Plain pairing, for models without an anti-spoofing key.
+    Classic(ClassicAddress),
+}
+
+/// States of `FastPairSeeker`, holding what the next step needs.
+enum Step<G: GattClient> {
+    Discovered,
+    FetchingMetadata,
+    KeyBasedPairing { key: PairingKey, attempt: u32 },
+    Bonding(Bonding<G>),
+    WritingAccountKey(KeyBasedPairing<G>, PairingResult),
+    Completed(PairingResult, ClassicAddress),
+}
+
+impl<G: GattClient + 'static> Step<G> {
+    fn state(&self) -> SeekerState {
+        match self {
+            Step::Discovered => SeekerState::Discovered,
+            Step::FetchingMetadata => SeekerState::FetchingMetadata,
+            Step::KeyBasedPairing { attempt, .. } => {
+                SeekerState::KeyBasedPairing { attempt: *attempt }
+            }
+            Step::Bonding(Bonding::FastPair(key_based_pairing)) => {
+                SeekerState::Bonding(key_based_pairing.provider_address())
+            }
+            Step::Bonding(Bonding::Classic(address)) => SeekerState::Bonding(*address),
+            Step::WritingAccountKey(..) => SeekerState::WritingAccountKey,
+            Step::Completed(result, _) => SeekerState::Completed(result.clone()),
+        }
+    }
+}
+
+/// Fast Pair with a discovered Provider, as an explicit state machine:
+/// metadata fetch, Key-based Pairing, bonding over BR/EDR, then the account
+/// key write after initial pairing. See:
+/// https://developers.google.com/nearby/fast-pair/specifications/characteristics#procedure
+///
+/// GATT steps and bonding each fail with `FpError::Timeout` if they don't
+/// complete in time. Key-based Pairing, which connects to the Provider, is
+/// attempted again after Bluetooth errors and timeouts following the retry
+/// policy. Bonding isn't, as a passkey mismatch may be an attack.
+pub(crate) struct FastPairSeeker<'a> {
+    fetcher: &'a dyn FpFetcher,
+    store: &'a dyn AccountKeyStore,
+    gatt_timeout: Duration,
+    bonding_timeout: Duration,
+    retry_policy: RetryPolicy,
+}
+
+impl<'a> FastPairSeeker<'a> {
+    /// Construct a `FastPairSeeker` fetching device information from
+    /// `fetcher`, and storing the account keys it writes in `store`.
+    pub(crate) fn new(fetcher: &'a dyn FpFetcher, store: &'a dyn AccountKeyStore) -> Self {
+        FastPairSeeker {
+            fetcher,
+            store,
+            gatt_timeout: DEFAULT_GATT_TIMEOUT,
+            bonding_timeout: DEFAULT_BONDING_TIMEOUT,
+            retry_policy: RetryPolicy::default(),
+        }
+    }
+
+    /// Set the time allowed for each GATT step, and for bonding.
+    pub(crate) fn with_timeouts(
+        mut self,
+        gatt_timeout: Duration,
+        bonding_timeout: Duration,
+    ) -> Self {
+        self.gatt_timeout = gatt_timeout;
+        self.bonding_timeout = bonding_timeout;
+        self
+    }
+
+    /// Set how Key-based Pairing is attempted again after failing.
+    pub(crate) fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Pair with the Provider which sent `adv`, through subsequent pairing
+    /// if it's one of the user's devices, else initial pairing if its model
+    /// has an anti-spoofing key, else plain pairing. `on_state` is called
+    /// with each state entered, up to the final `SeekerState::Completed` or
+    /// `SeekerState::Failed`. Returns the pairing result along with the
+    /// BR/EDR address of the Provider.
+    pub(crate) async fn pair(
+        &self,
+        adv: &FpPairingAdvertisement,
+        mut on_state: impl FnMut(&SeekerState),
+    ) -> Result<(PairingResult, ClassicAddress), FpError> {
+        let result = self.run(adv, &mut on_state).await;
+        if let Err(err) = &result {
+            warn!("Pairing with {} failed: {}", adv.address(), err);
+            on_state(&SeekerState::Failed(err.clone()));
+        }
+        result
+    }
+
+    /// Run the steps from discovery until pairing completes or fails.
+    async fn run(
+        &self,
+        adv: &FpPairingAdvertisement,
+        on_state: &mut impl FnMut(&SeekerState),
+    ) -> Result<(PairingResult, ClassicAddress), FpError> {
+        let mut step = Step::Discovered;
+        loop {
+            on_state(&step.state());
+            step = match step {
+                Step::Discovered => Step::FetchingMetadata,
+                Step::FetchingMetadata => match adv.account_key() {
+                    // The user's devices are paired again with their account
+                    // key, which doesn't need the device information.
+                    Some(account_key) => Step::KeyBasedPairing {
+                        key: PairingKey::Account(*account_key),
+                        attempt: 1,
+                    },
+                    None => match self.fetch_anti_spoofing_key(adv.model_id())? {
+                        Some(key) => Step::KeyBasedPairing {
+                            key: PairingKey::AntiSpoofing(key),
+                            attempt: 1,
+                        },
+                        None => {
+                            warn!("No anti-spoofing key for model {}.", adv.model_id());
+                            let address = ClassicAddress::try_from(adv.address())?;
+                            Step::Bonding(Bonding::Classic(address))
+                        }
+                    },
+                },
+                Step::KeyBasedPairing { key, attempt } => {
+                    let result = timeout(
+                        self.gatt_timeout,
+                        "Key-based Pairing",
+                        key_based_pairing(adv.address(), &key),
+                    )
+                    .await
+                    .and_then(|result| result);
+
+                    match result {
+                        Ok(key_based_pairing) => {
+                            Step::Bonding(Bonding::FastPair(key_based_pairing))
+                        }
+                        Err(err)
+                            if attempt < self.retry_policy.max_attempts() && is_transient(&err) =>
+                        {
+                            let backoff = self.retry_policy.backoff(attempt);
+                            warn!("Retrying Key-based Pairing in {:?}: {}", backoff, err);
+                            sleep(backoff).await;
+                            Step::KeyBasedPairing {
+                                key,
+                                attempt: attempt + 1,
+                            }
+                        }
+                        Err(err) => return Err(err),
+                    }
+                }
+                Step::Bonding(Bonding::FastPair(key_based_pairing)) => {
+                    let result = self.bond(&key_based_pairing, on_state).await?;
+                    match result {
+                        // Only initial pairing writes an account key.
+                        PairingResult::Success(_) if adv.account_key().is_none() => {
+                            Step::WritingAccountKey(key_based_pairing, result)
+                        }
+                        _ => Step::Completed(result, key_based_pairing.provider_address()),
+                    }
+                }
+                Step::Bonding(Bonding::Classic(address)) => {
+                    let device = Platform::new_classic_device(address).await?;
+                    let result = timeout(self.bonding_timeout, "bonding", device.pair()).await??;
+                    Step::Completed(result, address)
+                }
+                Step::WritingAccountKey(key_based_pairing, result) => {
+                    let account_key = AccountKey::generate();
+                    timeout(
+                        self.gatt_timeout,
+                        "account key write",
+                        key_based_pairing.write_account_key(&account_key),
+                    )
+                    .await??;
+                    self.store
+                        .add_account_key(account_key, adv.model_id().to_owned())?;
+                    Step::Completed(result, key_based_pairing.provider_address())
+                }
+                Step::Completed(result, address) => {
+                    info!("Pairing with {} completed: {:?}", address, result);
+                    return Ok((result, address));
+                }
+            }
+        }
+    }
+
+    /// Pair over BR/EDR after Key-based Pairing, reporting
+    /// `SeekerState::PasskeyConfirmed` as soon as the passkey is, while
+    /// pairing goes on.
+    async fn bond<G: GattClient + 'static>(
+        &self,
+        key_based_pairing: &KeyBasedPairing<G>,
+        on_state: &mut impl FnMut(&SeekerState),
+    ) -> Result<PairingResult, FpError> {
+        let (passkey_confirmed, mut confirmations) = mpsc::unbounded();
+        let bonding = timeout(
+            self.bonding_timeout,
+            "bonding",
+            key_based_pairing.pair(passkey_confirmed),
+        );
+        pin_mut!(bonding);
+
+        let result = match future::select(bonding, confirmations.next()).await {
+            Either::Left((result, _)) => result,
+            Either::Right((confirmed, bonding)) => {
+                if confirmed.is_some() {
+                    on_state(&SeekerState::PasskeyConfirmed);
+                }
+                bonding.await
+            }
+        }??;
+
+        // Bonding may complete before the confirmation is polled.
+        if confirmations.try_recv().is_ok() {
+            on_state(&SeekerState::PasskeyConfirmed);
+        }
+        Ok(result)
+    }
+
+    fn fetch_anti_spoofing_key(&self, model_id: &ModelId) -> Result<Option<Vec<u8>>, FpError> {
+        self.fetcher
+            .get_device_info_from_model_id(model_id)?
+            .anti_spoofing_public_key()
+    }
+}
+
+/// Connect to the Provider at `address` and exchange the Key-based Pairing
+/// request and response with it.
+async fn key_based_pairing(
+    address: BleAddress,
+    key: &PairingKey,
+) -> Result<KeyBasedPairing<impl GattClient>, FpError> {
+    let client = Platform::connect_gatt(address).await?;
+    match key {
+        PairingKey::AntiSpoofing(key) => KeyBasedPairing::start(client, key).await,
+        PairingKey::Account(account_key) => {
+            KeyBasedPairing::start_with_account_key(client, account_key).await
+        }
+    }
+}
+
+/// Check whether a failed step may succeed if attempted again, e.g. after
+/// the connection dropped.
+fn is_transient(err: &FpError) -> bool {
+    matches!(err, FpError::Bluetooth(_) | FpError::Timeout(_))
+}
+
+/// Run `future` for at most `duration`, failing with `FpError::Timeout`
+/// naming the `step` it runs otherwise.
+async fn timeout<F: Future>(
+    duration: Duration,
+    step: &str,
+    future: F,
+) -> Result<F::Output, FpError> {
+    let sleep = sleep(duration);
+    pin_mut!(future, sleep);
+    match future::select(future, sleep).await {
+        Either::Left((output, _)) => Ok(output),
+        Either::Right(_) => Err(FpError::Timeout(format!("{} after {:?}", step, duration))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+
+    #[test]
+    fn test_timeout() {
+        let result = block_on(timeout(Duration::from_secs(1), "step", async { 1 }));
+        assert_eq!(result, Ok(1));
+
+        let result = block_on(timeout(
+            Duration::from_millis(10),
+            "step",
+            future::pending::<()>(),
+        ));
+        assert!(matches!(result, Err(FpError::Timeout(_))));
+    }
+
+    #[cfg(feature = "mock")]
+    mod mock {
+        use super::*;
+
+        use std::{env, fs, process};
+
+        use bluetooth::{
+            api::GattRequestHandler, AttErrorCode, BleAddressKind, BleAdvertisement,
+            GattCharacteristic, MockDevice, MockFixtures, ProtectionLevel, ServiceData, Uuid,
+        };
+
+        use crate::{
+            account_key::AccountKeyStoreFs,
+            fetcher::{mock::FpFetcherMock, DeviceInfo},
+            simulator::{fast_pair_service, ProviderSimulator},
+        };
+
+        const MODEL_ID: [u8; 3] = [0x52, 0x52, 0x96];
+        const ADDRESS: u64 = 0xAABBCCDDEEFF;
+        const PASSKEY: u32 = 123456;
+
+        fn ble_address() -> BleAddress {
+            BleAddress::new(ADDRESS, BleAddressKind::Public)
+        }
+
+        fn classic_address() -> ClassicAddress {
+            ClassicAddress::from(ADDRESS)
+        }
+
+        /// Install fixtures where the local adapter is the simulated
+        /// Provider, whose BR/EDR side displays `passkey`, if any.
+        fn install_fixtures(passkey: Option<u32>) {
+            let mut device = MockDevice::new("Simulated Provider");
+            if let Some(passkey) = passkey {
+                device = device.with_passkey(passkey);
+            }
+            MockFixtures::new()
+                .with_local_adapter(ble_address(), Some(-60))
+                .with_classic_device(classic_address(), device)
+                .install();
+        }
+
+        fn fetcher(anti_spoofing_key: Option<&[u8]>) -> Box<dyn FpFetcher> {
+            let mut device_info = DeviceInfo::new(String::from("image_url"), String::from("name"));
+            if let Some(key) = anti_spoofing_key {
+                device_info = device_info.with_anti_spoofing_public_key(key);
+            }
+            Box::new(FpFetcherMock::new(Ok(device_info)))
+        }
+
+        /// Advertisement of the Provider in pairing mode.
+        fn advertisement(fetcher: &Box<dyn FpFetcher>) -> FpPairingAdvertisement {
+            FpPairingAdvertisement::new(
+                BleAdvertisement::new(ble_address(), Some(-60), Some(0)),
+                &ServiceData::new(Uuid::from_u16(0xFE2C), MODEL_ID.to_vec()),
+                fetcher,
+            )
+            .unwrap()
+        }
+
+        fn store(name: &str) -> (AccountKeyStoreFs, String) {
+            let path = env::temp_dir()
+                .join(format!("{}_{}.json", name, process::id()))
+                .to_string_lossy()
+                .into_owned();
+            (AccountKeyStoreFs::new(path.clone()), path)
+        }
+
+        #[test]
+        fn test_initial_pairing() {
+            install_fixtures(Some(PASSKEY));
+            let simulator =
+                ProviderSimulator::new(MODEL_ID, classic_address()).with_ble_address(ble_address());
+            let fetcher = fetcher(Some(&simulator.anti_spoofing_public_key()));
+            let (store, path) = store("seeker_initial");
+            let seeker = FastPairSeeker::new(fetcher.as_ref(), &store);
+            let adv = advertisement(&fetcher);
+
+            let mut states = Vec::new();
+            let result = block_on(
+                simulator.run_until(seeker.pair(&adv, |state| states.push(state.clone()))),
+            )
+            .unwrap();
+
+            let success = PairingResult::Success(ProtectionLevel::EncryptionAndAuthentication);
+            assert_eq!(result, Ok((success.clone(), classic_address())));
+            assert_eq!(
+                states,
+                [
+                    SeekerState::Discovered,
+                    SeekerState::FetchingMetadata,
+                    SeekerState::KeyBasedPairing { attempt: 1 },
+                    SeekerState::Bonding(classic_address()),
+                    SeekerState::PasskeyConfirmed,
+                    SeekerState::WritingAccountKey,
+                    SeekerState::Completed(success),
+                ]
+            );
+            assert_eq!(store.account_keys().unwrap(), simulator.account_keys());
+            assert_eq!(simulator.account_keys().len(), 1);
+
+            fs::remove_file(path).unwrap();
+        }
+
+        #[test]
+        fn test_subsequent_pairing() {
+            install_fixtures(Some(PASSKEY));
+            let simulator = ProviderSimulator::new(MODEL_ID, classic_address());
+            let fetcher = fetcher(Some(&simulator.anti_spoofing_public_key()));
+            let (store, path) = store("seeker_subsequent");
+            let seeker = FastPairSeeker::new(fetcher.as_ref(), &store);
+            let adv = advertisement(&fetcher);
+            block_on(simulator.run_until(seeker.pair(&adv, |_| ())))
+                .unwrap()
+                .unwrap();
+
+            // The Provider was unpaired, then recognized as the user's.
+            install_fixtures(Some(PASSKEY));
+            let account_key = store.account_keys().unwrap()[0];
+            let adv = FpPairingAdvertisement::new_user_device(
+                BleAdvertisement::new(ble_address(), Some(-60), Some(0)),
+                adv.model_id().to_owned(),
+                account_key,
+                None,
+                &fetcher,
+            )
+            .unwrap();
+
+            let mut states = Vec::new();
+            let result = block_on(
+                simulator.run_until(seeker.pair(&adv, |state| states.push(state.clone()))),
+            )
+            .unwrap();
+
+            let success = PairingResult::Success(ProtectionLevel::EncryptionAndAuthentication);
+            assert_eq!(result, Ok((success.clone(), classic_address())));
+            assert_eq!(
+                states,
+                [
+                    SeekerState::Discovered,
+                    SeekerState::FetchingMetadata,
+                    SeekerState::KeyBasedPairing { attempt: 1 },
+                    SeekerState::Bonding(classic_address()),
+                    SeekerState::PasskeyConfirmed,
+                    SeekerState::Completed(success),
+                ]
+            );
+            assert_eq!(store.account_keys().unwrap(), [account_key]);
+
+            fs::remove_file(path).unwrap();
+        }
+
+        #[test]
+        fn test_classic_pairing() {
+            install_fixtures(None);
+            let fetcher = fetcher(None);
+            let (store, _) = store("seeker_classic");
+            let seeker = FastPairSeeker::new(fetcher.as_ref(), &store);
+            let adv = advertisement(&fetcher);
+
+            let mut states = Vec::new();
+            let result = block_on(seeker.pair(&adv, |state| states.push(state.clone())));
+
+            let success = PairingResult::Success(ProtectionLevel::Encryption);
+            assert_eq!(result, Ok((success.clone(), classic_address())));
+            assert_eq!(
+                states,
+                [
+                    SeekerState::Discovered,
+                    SeekerState::FetchingMetadata,
+                    SeekerState::Bonding(classic_address()),
+                    SeekerState::Completed(success),
+                ]
+            );
+            assert_eq!(store.account_keys(), Ok(Vec::new()));
+        }
+
+        #[test]
+        fn test_key_based_pairing_retries() {
+            // Nothing hosts a GATT server at the address of the Provider.
+            install_fixtures(Some(PASSKEY));
+            let fetcher = fetcher(Some(&[0x01; 64]));
+            let (store, _) = store("seeker_retries");
+            let seeker = FastPairSeeker::new(fetcher.as_ref(), &store).with_retry_policy(
+                RetryPolicy::new(3, Duration::from_millis(1), Duration::from_millis(1)),
+            );
+            let adv = advertisement(&fetcher);
+
+            let mut states = Vec::new();
+            let result = block_on(seeker.pair(&adv, |state| states.push(state.clone())));
+
+            let err = result.unwrap_err();
+            assert!(matches!(err, FpError::Bluetooth(_)));
+            assert_eq!(
+                states,
+                [
+                    SeekerState::Discovered,
+                    SeekerState::FetchingMetadata,
+                    SeekerState::KeyBasedPairing { attempt: 1 },
+                    SeekerState::KeyBasedPairing { attempt: 2 },
+                    SeekerState::KeyBasedPairing { attempt: 3 },
+                    SeekerState::Failed(err),
+                ]
+            );
+        }
+
+        /// Accepts every write without ever answering.
+        struct SilentHandler;
+
+        impl GattRequestHandler for SilentHandler {
+            fn on_read(
+                &self,
+                _characteristic: &GattCharacteristic,
+            ) -> Result<Vec<u8>, AttErrorCode> {
+                Err(AttErrorCode::READ_NOT_PERMITTED)
+            }
+
+            fn on_write(
+                &self,
+                _characteristic: &GattCharacteristic,
+                _value: &[u8],
+            ) -> Result<(), AttErrorCode> {
+                Ok(())
+            }
+        }
+
+        #[test]
+        fn test_key_based_pairing_timeout() {
+            install_fixtures(Some(PASSKEY));
+            let simulator = ProviderSimulator::new(MODEL_ID, classic_address());
+            let fetcher = fetcher(Some(&simulator.anti_spoofing_public_key()));
+            let (store, _) = store("seeker_timeout");
+            let seeker = FastPairSeeker::new(fetcher.as_ref(), &store)
+                .with_timeouts(Duration::from_millis(10), Duration::from_millis(10))
+                .with_retry_policy(RetryPolicy::never());
+            let adv = advertisement(&fetcher);
+
+            let _server = block_on(Platform::new_gatt_server(
+                &[fast_pair_service()],
+                SilentHandler,
+            ))
+            .unwrap();
+            let result = block_on(seeker.pair(&adv, |_| ()));
+            assert!(matches!(result, Err(FpError::Timeout(_))));
+        }
+
+        #[test]
+        fn test_fetching_metadata_fails() {
+            install_fixtures(Some(PASSKEY));
+            let adv = advertisement(&fetcher(None));
+            let fetcher: Box<dyn FpFetcher> = Box::new(FpFetcherMock::new(Err(FpError::Test)));
+            let (store, _) = store("seeker_metadata");
+            let seeker = FastPairSeeker::new(fetcher.as_ref(), &store);
+
+            let mut states = Vec::new();
+            let result = block_on(seeker.pair(&adv, |state| states.push(state.clone())));
+
+            assert_eq!(result, Err(FpError::Test));
+            assert_eq!(
+                states,
+                [
+                    SeekerState::Discovered,
+                    SeekerState::FetchingMetadata,
+                    SeekerState::Failed(FpError::Test),
+                ]
+            );
+        }
+    }
+}
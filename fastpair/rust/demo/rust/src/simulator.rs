@@ -0,0 +1,543 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use bluetooth::{
+    api::{BleAdvertiser, GattRequestHandler, GattServer},
+    AdvertisementPayload, AdvertisingParameters, AttErrorCode, BleAddress,
+    CharacteristicProperties, ClassicAddress, GattCharacteristic, LocalCharacteristic,
+    LocalService, Platform, ServiceData,
+};
+use futures::{
+    channel::mpsc::{self, UnboundedSender},
+    future::{self, Either},
+    pin_mut, StreamExt,
+};
+use p256::SecretKey;
+use tracing::{info, warn};
+
+use crate::{
+    account_key::AccountKey,
+    crypto::{
+        ecdh_with_private_key, encode_public_key, AesKey, Block, BLOCK_LENGTH, PUBLIC_KEY_LENGTH,
+    },
+    error::FpError,
+    procedures::{
+        salted_block, ACCOUNT_KEY_UUID, FAST_PAIR_SERVICE_UUID, KEY_BASED_PAIRING_REQUEST,
+        KEY_BASED_PAIRING_RESPONSE, KEY_BASED_PAIRING_UUID, PASSKEY_UUID, PROVIDER_PASSKEY,
+        SEEKER_PASSKEY,
+    },
+};
+
+/// Anti-spoofing private key of the simulated model. It's only meant for
+/// tests: Seekers get the matching public key from
+/// `ProviderSimulator::anti_spoofing_public_key()` rather than from the
+/// device information of a real model.
+const TEST_ANTI_SPOOFING_KEY: [u8; 32] = [
+    0x2D, 0x94, 0x22, 0xF1, 0xD4, 0x79, 0xD4, 0x5B, 0x09, 0x9B, 0x6F, 0x9B, 0x08, 0x3C, 0x51, 0x9C,
+    0x5A, 0xBD, 0x02, 0x02, 0xA7, 0x69, 0x76, 0x46, 0x40, 0x1C, 0x14, 0xF7, 0xFD, 0x2A, 0x7D, 0xB8,
+];
+
+/// State of the simulated Provider, shared with the handler of the requests
+/// to its GATT server.
+struct ProviderState {
+    address: ClassicAddress,
+    /// Address Key-based Pairing requests must be addressed to, if known.
+    ble_address: Option<BleAddress>,
+    anti_spoofing_key: SecretKey,
+    /// Passkey displayed during BR/EDR pairing, if not the Seeker's.
+    passkey: Option<u32>,
+    /// Key shared with the Seeker during Key-based Pairing, if any.
+    shared_key: Option<AesKey>,
+    account_keys: Vec<AccountKey>,
+}
+
+/// Simulated Fast Pair Provider in pairing mode, so that the Seeker side can
+/// be tested end to end without physical devices, either on the mock
+/// platform or with a second machine.
+///
+/// It advertises its model ID, and hosts the Fast Pair service with the
+/// Key-based Pairing, Passkey and Account Key characteristics. Pairing over
+/// BR/EDR is left to the platform, e.g. to a `bluetooth::MockDevice` at the
+/// BR/EDR address of the simulator.
+pub(crate) struct ProviderSimulator {
+    model_id: [u8; 3],
+    state: Arc<Mutex<ProviderState>>,
+}
+
+impl ProviderSimulator {
+    /// Construct a simulated Provider of model `model_id` using the test
+    /// anti-spoofing key, which pairs over BR/EDR at `address`.
+    pub(crate) fn new(model_id: [u8; 3], address: ClassicAddress) -> Self {
+        ProviderSimulator {
+            model_id,
+            state: Arc::new(Mutex::new(ProviderState {
+                address,
+                ble_address: None,
+                anti_spoofing_key: SecretKey::from_slice(&TEST_ANTI_SPOOFING_KEY).unwrap(),
+                passkey: None,
+                shared_key: None,
+                account_keys: Vec::new(),
+            })),
+        }
+    }
+
+    /// Reject Key-based Pairing requests not addressed to `ble_address`, the
+    /// address the local adapter advertises from. Without it, the address in
+    /// requests isn't checked.
+    #[cfg_attr(not(feature = "mock"), allow(dead_code))]
+    pub(crate) fn with_ble_address(self, ble_address: BleAddress) -> Self {
+        self.state.lock().unwrap().ble_address = Some(ble_address);
+        self
+    }
+
+    /// Send `passkey` to the Seeker during pairing. By default, the Seeker's
+    /// own passkey is sent back, as if both displayed the same one.
+    #[cfg_attr(not(feature = "mock"), allow(dead_code))]
+    pub(crate) fn with_passkey(self, passkey: u32) -> Self {
+        self.state.lock().unwrap().passkey = Some(passkey);
+        self
+    }
+
+    /// Retrieve the anti-spoofing public key Seekers need for initial
+    /// pairing.
+    pub(crate) fn anti_spoofing_public_key(&self) -> [u8; PUBLIC_KEY_LENGTH] {
+        encode_public_key(&self.state.lock().unwrap().anti_spoofing_key.public_key())
+    }
+
+    /// Retrieve the account keys written by Seekers so far.
+    #[cfg_attr(not(feature = "mock"), allow(dead_code))]
+    pub(crate) fn account_keys(&self) -> Vec<AccountKey> {
+        self.state.lock().unwrap().account_keys.clone()
+    }
+
+    /// Advertise and answer the requests of Seekers until `until` completes,
+    /// e.g. the Seeker under test, returning its output.
+    pub(crate) async fn run_until<F: Future>(&self, until: F) -> Result<F::Output, FpError> {
+        let (sender, mut notifications) = mpsc::unbounded();
+        let handler = ProviderHandler {
+            state: self.state.clone(),
+            notifications: sender,
+        };
+        let mut server = Platform::new_gatt_server(&[fast_pair_service()], handler).await?;
+
+        let mut advertiser = Platform::default_advertiser().await?;
+        advertiser.start_advertising_with_parameters(
+            AdvertisementPayload::new().with_service_data_16bit_uuid(ServiceData::new(
+                FAST_PAIR_SERVICE_UUID,
+                self.model_id.to_vec(),
+            )),
+            AdvertisingParameters::new().with_tx_power_advertised(),
+        )?;
+        info!("Simulating a Provider of model {:02X?}", self.model_id);
+
+        // The handler can't await, so it queues the notifications it sends.
+        let notify = async {
+            while let Some((characteristic, value)) = notifications.next().await {
+                server.notify(&characteristic, &value).await?;
+            }
+            Err(FpError::Internal(String::from("GATT server closed")))
+        };
+
+        pin_mut!(until, notify);
+        match future::select(until, notify).await {
+            Either::Left((output, _)) => Ok(output),
+            Either::Right((result, _)) => result,
+        }
+    }
+}
+
+/// Build the Fast Pair service hosted by the simulator.
+pub(crate) fn fast_pair_service() -> LocalService {
+    let write_notify = CharacteristicProperties::WRITE | CharacteristicProperties::NOTIFY;
+    LocalService::new(FAST_PAIR_SERVICE_UUID)
+        .with_characteristic(LocalCharacteristic::new(
+            KEY_BASED_PAIRING_UUID,
+            write_notify,
+        ))
+        .with_characteristic(LocalCharacteristic::new(PASSKEY_UUID, write_notify))
+        .with_characteristic(LocalCharacteristic::new(
+            ACCOUNT_KEY_UUID,
+            CharacteristicProperties::WRITE,
+        ))
+}
+
+/// Parse a characteristic value made of a single block.
+fn parse_block(value: &[u8]) -> Result<Block, FpError> {
+    Block::try_from(value).map_err(|_| {
+        FpError::ContractViolation(format!(
+            "value of length {}, expected a single block",
+            value.len()
+        ))
+    })
+}
+
+impl ProviderState {
+    /// Handle a Key-based Pairing request, encrypted either with a key
+    /// derived from the anti-spoofing key and the public key following the
+    /// request, or with one of the account keys. Returns the encrypted
+    /// response carrying the BR/EDR address.
+    fn on_key_based_pairing_request(&mut self, value: &[u8]) -> Result<Block, FpError> {
+        let (encrypted, public_key) = value.split_at(value.len().min(BLOCK_LENGTH));
+        let encrypted = parse_block(encrypted)?;
+        let keys = if public_key.is_empty() {
+            self.account_keys
+                .iter()
+                .map(|account_key| AesKey::new(account_key.as_bytes()))
+                .collect()
+        } else {
+            vec![ecdh_with_private_key(&self.anti_spoofing_key, public_key)?]
+        };
+
+        let key = keys
+            .into_iter()
+            .find(|key| self.is_request(&key.decrypt(&encrypted)))
+            .ok_or_else(|| {
+                FpError::ContractViolation(String::from(
+                    "Key-based Pairing request not encrypted with a known key",
+                ))
+            })?;
+
+        // Message type, then the BR/EDR address to pair with.
+        let mut header = [0; 7];
+        header[0] = KEY_BASED_PAIRING_RESPONSE;
+        header[1..].copy_from_slice(&u64::from(self.address).to_be_bytes()[2..]);
+        let response = key.encrypt(&salted_block(&header));
+        self.shared_key = Some(key);
+        info!("Key-based Pairing with a Seeker");
+        Ok(response)
+    }
+
+    /// Check whether `block` is a decrypted Key-based Pairing request
+    /// addressed to this Provider.
+    fn is_request(&self, block: &Block) -> bool {
+        block[0] == KEY_BASED_PAIRING_REQUEST
+            && self
+                .ble_address
+                .is_none_or(|ble_address| block[2..8] == u64::from(ble_address).to_be_bytes()[2..])
+    }
+
+    /// Handle the passkey the Seeker displays, returning the encrypted
+    /// passkey of the Provider.
+    fn on_seeker_passkey(&self, value: &[u8]) -> Result<Block, FpError> {
+        let key = self.shared_key()?;
+        let block = key.decrypt(&parse_block(value)?);
+        if block[0] != SEEKER_PASSKEY {
+            return Err(FpError::ContractViolation(format!(
+                "passkey block of type {:#04x}",
+                block[0]
+            )));
+        }
+
+        let passkey = self
+            .passkey
+            .unwrap_or_else(|| u32::from_be_bytes([0, block[1], block[2], block[3]]));
+        let mut header = [0; 4];
+        header[0] = PROVIDER_PASSKEY;
+        header[1..].copy_from_slice(&passkey.to_be_bytes()[1..]);
+        Ok(key.encrypt(&salted_block(&header)))
+    }
+
+    /// Handle the account key written by the Seeker once paired, which ends
+    /// Key-based Pairing.
+    fn on_account_key(&mut self, value: &[u8]) -> Result<(), FpError> {
+        let block = self.shared_key()?.decrypt(&parse_block(value)?);
+        let account_key = AccountKey::try_from(&block[..])?;
+        if !self.account_keys.contains(&account_key) {
+            self.account_keys.push(account_key);
+        }
+        self.shared_key = None;
+        Ok(())
+    }
+
+    fn shared_key(&self) -> Result<&AesKey, FpError> {
+        self.shared_key.as_ref().ok_or_else(|| {
+            FpError::ContractViolation(String::from("no Key-based Pairing in progress"))
+        })
+    }
+}
+
+/// Answers the requests to the GATT server of the simulator.
+struct ProviderHandler {
+    state: Arc<Mutex<ProviderState>>,
+    /// Sends the notifications to the simulator, with the characteristic to
+    /// notify.
+    notifications: UnboundedSender<(GattCharacteristic, Block)>,
+}
+
+impl GattRequestHandler for ProviderHandler {
+    fn on_read(&self, _characteristic: &GattCharacteristic) -> Result<Vec<u8>, AttErrorCode> {
+        Err(AttErrorCode::READ_NOT_PERMITTED)
+    }
+
+    /// Unlike real Providers, which ignore invalid writes, the simulator
+    /// rejects them, so that a failing Seeker errors out rather than waits
+    /// for a notification.
+    fn on_write(
+        &self,
+        characteristic: &GattCharacteristic,
+        value: &[u8],
+    ) -> Result<(), AttErrorCode> {
+        let mut state = self.state.lock().unwrap();
+        let uuid = characteristic.uuid();
+        let result = if uuid == KEY_BASED_PAIRING_UUID {
+            state.on_key_based_pairing_request(value).map(Some)
+        } else if uuid == PASSKEY_UUID {
+            state.on_seeker_passkey(value).map(Some)
+        } else if uuid == ACCOUNT_KEY_UUID {
+            state.on_account_key(value).map(|_| None)
+        } else {
+            Err(FpError::ContractViolation(format!(
+                "write to characteristic {}",
+                uuid
+            )))
+        };
+
+        match result {
+            Ok(notification) => {
+                if let Some(value) = notification {
+                    // Only fails once the simulator stopped running.
+                    let _ = self.notifications.unbounded_send((*characteristic, value));
+                }
+                Ok(())
+            }
+            Err(err) => {
+                warn!("Simulated Provider rejected write to {}: {}", uuid, err);
+                Err(AttErrorCode::UNLIKELY_ERROR)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::executor::block_on;
+
+    const MODEL_ID: [u8; 3] = [0x52, 0x52, 0x96];
+
+    #[cfg(feature = "mock")]
+    mod mock {
+        use super::*;
+
+        use bluetooth::{
+            api::{BleAdapter, GattClient},
+            BleAddressKind, BleDataTypeId, MockDevice, MockFixtures, PairingResult,
+            ProtectionLevel, WriteType,
+        };
+
+        use crate::{decoder::FpDecoder, procedures::KeyBasedPairing};
+
+        const PASSKEY: u32 = 123456;
+
+        fn ble_address() -> BleAddress {
+            BleAddress::new(0x112233445566, BleAddressKind::Random)
+        }
+
+        fn classic_address() -> ClassicAddress {
+            ClassicAddress::from(0xAABBCCDDEEFF)
+        }
+        /// Install fixtures where the local adapter is the simulated
+        /// Provider, whose BR/EDR side is a device displaying `PASSKEY`.
+        fn install_fixtures() {
+            MockFixtures::new()
+                .with_local_adapter(ble_address(), Some(-60))
+                .with_classic_device(
+                    classic_address(),
+                    MockDevice::new("Simulated Provider").with_passkey(PASSKEY),
+                )
+                .install();
+        }
+
+        /// Scan for a Provider in pairing mode, returning its address and
+        /// model ID.
+        async fn discover() -> Result<(BleAddress, Vec<u8>), FpError> {
+            let mut adapter = Platform::default_adapter().await?;
+            adapter.start_scan(None)?;
+            let advertisement = adapter
+                .next_advertisement(Some(&vec![BleDataTypeId::ServiceData16BitUuid]))
+                .await?;
+            let service_data = advertisement
+                .service_data_16bit_uuid()?
+                .iter()
+                .find(|service_data| service_data.uuid() == FAST_PAIR_SERVICE_UUID)
+                .ok_or(FpError::Test)?;
+
+            Ok((
+                advertisement.address(),
+                FpDecoder::get_model_id_from_service_data(service_data)?,
+            ))
+        }
+
+        /// Initial pairing, writing `account_key` once paired.
+        async fn initial_pairing(
+            anti_spoofing_key: &[u8],
+            account_key: &AccountKey,
+        ) -> Result<PairingResult, FpError> {
+            let (address, model_id) = discover().await?;
+            assert_eq!(address, ble_address());
+            assert_eq!(model_id, MODEL_ID);
+
+            let client = Platform::connect_gatt(address).await?;
+            let key_based_pairing = KeyBasedPairing::start(client, anti_spoofing_key).await?;
+            assert_eq!(key_based_pairing.provider_address(), classic_address());
+            let (passkey_confirmed, mut confirmations) = mpsc::unbounded();
+            let result = key_based_pairing.pair(passkey_confirmed).await?;
+            // Pairing only goes on once the passkey is confirmed.
+            assert_eq!(confirmations.next().await, Some(()));
+            key_based_pairing.write_account_key(account_key).await?;
+            Ok(result)
+        }
+
+        #[test]
+        fn test_initial_pairing() {
+            install_fixtures();
+            let simulator =
+                ProviderSimulator::new(MODEL_ID, classic_address()).with_ble_address(ble_address());
+            let account_key = AccountKey::generate();
+
+            let result = block_on(simulator.run_until(initial_pairing(
+                &simulator.anti_spoofing_public_key(),
+                &account_key,
+            )))
+            .unwrap()
+            .unwrap();
+            assert!(matches!(
+                result,
+                PairingResult::Success(ProtectionLevel::EncryptionAndAuthentication)
+            ));
+            assert_eq!(simulator.account_keys(), [account_key]);
+        }
+
+        #[test]
+        fn test_initial_pairing_passkey_mismatch() {
+            install_fixtures();
+            let simulator =
+                ProviderSimulator::new(MODEL_ID, classic_address()).with_passkey(654321);
+            let account_key = AccountKey::generate();
+
+            let result = block_on(simulator.run_until(initial_pairing(
+                &simulator.anti_spoofing_public_key(),
+                &account_key,
+            )))
+            .unwrap();
+            assert!(matches!(result, Err(FpError::Bluetooth(_))));
+            assert!(simulator.account_keys().is_empty());
+        }
+
+        #[test]
+        fn test_subsequent_pairing() {
+            install_fixtures();
+            let simulator =
+                ProviderSimulator::new(MODEL_ID, classic_address()).with_ble_address(ble_address());
+            let account_key = AccountKey::generate();
+            block_on(simulator.run_until(initial_pairing(
+                &simulator.anti_spoofing_public_key(),
+                &account_key,
+            )))
+            .unwrap()
+            .unwrap();
+
+            let start_with_account_key = |account_key| async move {
+                let client = Platform::connect_gatt(ble_address()).await?;
+                let key_based_pairing =
+                    KeyBasedPairing::start_with_account_key(client, &account_key).await?;
+                Ok::<_, FpError>(key_based_pairing.provider_address())
+            };
+
+            let result = block_on(simulator.run_until(start_with_account_key(account_key)));
+            assert_eq!(result.unwrap().unwrap(), classic_address());
+
+            // Unknown account keys are rejected.
+            let result =
+                block_on(simulator.run_until(start_with_account_key(AccountKey::generate())));
+            assert!(matches!(result.unwrap(), Err(FpError::Bluetooth(_))));
+        }
+
+        #[test]
+        fn test_reject_misaddressed_request() {
+            install_fixtures();
+            let simulator = ProviderSimulator::new(MODEL_ID, classic_address())
+                .with_ble_address(BleAddress::new(0x665544332211, BleAddressKind::Random));
+
+            let start = async {
+                let client = Platform::connect_gatt(ble_address()).await?;
+                KeyBasedPairing::start(client, &simulator.anti_spoofing_public_key()).await?;
+                Ok::<_, FpError>(())
+            };
+            let result = block_on(simulator.run_until(start));
+            assert!(matches!(result.unwrap(), Err(FpError::Bluetooth(_))));
+        }
+
+        #[test]
+        fn test_reject_unexpected_writes() {
+            install_fixtures();
+            let simulator = ProviderSimulator::new(MODEL_ID, classic_address());
+
+            let write = async {
+                let mut client = Platform::connect_gatt(ble_address()).await?;
+                let service = client
+                    .discover_services()
+                    .await?
+                    .into_iter()
+                    .find(|service| service.uuid() == FAST_PAIR_SERVICE_UUID)
+                    .ok_or(FpError::Test)?;
+                let account_key = client
+                    .discover_characteristics(&service)
+                    .await?
+                    .into_iter()
+                    .find(|characteristic| characteristic.uuid() == ACCOUNT_KEY_UUID)
+                    .ok_or(FpError::Test)?;
+
+                // An account key is only expected after Key-based Pairing.
+                let value = [0x04; BLOCK_LENGTH];
+                client
+                    .write_characteristic(&account_key, &value, WriteType::WithResponse)
+                    .await?;
+                Ok::<_, FpError>(())
+            };
+            let result = block_on(simulator.run_until(write));
+            assert!(matches!(result.unwrap(), Err(FpError::Bluetooth(_))));
+            assert!(simulator.account_keys().is_empty());
+        }
+    }
+
+    /// Run the simulator on the local adapter until the process is killed,
+    /// for a Seeker on another machine to pair with. The BR/EDR address of
+    /// the local adapter is read from `FP_SIMULATOR_ADDRESS`, and the
+    /// anti-spoofing public key to add to the device information of
+    /// `MODEL_ID` is printed.
+    #[cfg(not(feature = "mock"))]
+    #[test]
+    #[ignore]
+    fn run_simulator() {
+        use base64::{engine::general_purpose::STANDARD, Engine};
+
+        let address = std::env::var("FP_SIMULATOR_ADDRESS")
+            .expect("FP_SIMULATOR_ADDRESS not set")
+            .parse()
+            .unwrap();
+        let simulator = ProviderSimulator::new(MODEL_ID, address);
+        println!(
+            "Anti-spoofing public key: {}",
+            STANDARD.encode(simulator.anti_spoofing_public_key())
+        );
+        block_on(simulator.run_until(future::pending::<()>())).unwrap();
+    }
+}
@@ -0,0 +1,183 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    pin::Pin,
+    sync::{Condvar, Mutex, OnceLock},
+    task::{Context, Poll, Waker},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Key of a pending timer: its deadline, and a sequence number telling apart
+/// the timers expiring at the same instant.
+type TimerKey = (Instant, u64);
+
+/// Timers shared by every `Sleep`, driven by a single thread which waits for
+/// the earliest deadline, so that waiting doesn't take a thread per timer.
+struct Timers {
+    pending: Mutex<PendingTimers>,
+    /// Signaled when a timer expiring before the others is added.
+    earliest_changed: Condvar,
+}
+
+struct PendingTimers {
+    /// Wakers of the tasks waiting on each timer, in the order they expire.
+    wakers: BTreeMap<TimerKey, Waker>,
+    next_sequence_number: u64,
+}
+
+/// Retrieve the shared timers, starting their thread on first use.
+fn timers() -> &'static Timers {
+    static TIMERS: OnceLock<Timers> = OnceLock::new();
+    TIMERS.get_or_init(|| {
+        thread::Builder::new()
+            .name(String::from("timers"))
+            .spawn(run_timers)
+            .expect("Failed to spawn the timer thread");
+        Timers {
+            pending: Mutex::new(PendingTimers {
+                wakers: BTreeMap::new(),
+                next_sequence_number: 0,
+            }),
+            earliest_changed: Condvar::new(),
+        }
+    })
+}
+
+/// Wake the tasks waiting on the timers as they expire.
+fn run_timers() {
+    let timers = timers();
+    let mut pending = timers.pending.lock().unwrap();
+    loop {
+        let now = Instant::now();
+        while let Some(entry) = pending.wakers.first_entry() {
+            if entry.key().0 > now {
+                break;
+            }
+            entry.remove().wake();
+        }
+
+        pending = match pending.wakers.keys().next() {
+            Some(&(deadline, _)) => {
+                timers
+                    .earliest_changed
+                    .wait_timeout(pending, deadline - now)
+                    .unwrap()
+                    .0
+            }
+            None => timers.earliest_changed.wait(pending).unwrap(),
+        };
+    }
+}
+
+/// Future completing once its deadline has passed, without blocking the
+/// executor. Dropping it cancels the timer.
+pub(crate) struct Sleep {
+    deadline: Instant,
+    /// Key of the pending timer, once polled.
+    key: Option<TimerKey>,
+}
+
+/// Wait for `duration`.
+pub(crate) fn sleep(duration: Duration) -> Sleep {
+    sleep_until(Instant::now() + duration)
+}
+
+/// Wait until `deadline`.
+pub(crate) fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep {
+        deadline,
+        key: None,
+    }
+}
+
+impl Sleep {
+    fn cancel(&mut self) {
+        if let Some(key) = self.key.take() {
+            timers().pending.lock().unwrap().wakers.remove(&key);
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if Instant::now() >= self.deadline {
+            self.cancel();
+            return Poll::Ready(());
+        }
+
+        let timers = timers();
+        let mut pending = timers.pending.lock().unwrap();
+        let key = match self.key {
+            Some(key) => key,
+            None => {
+                let key = (self.deadline, pending.next_sequence_number);
+                pending.next_sequence_number += 1;
+                self.key = Some(key);
+                key
+            }
+        };
+        pending.wakers.insert(key, cx.waker().clone());
+        if pending.wakers.keys().next() == Some(&key) {
+            timers.earliest_changed.notify_one();
+        }
+
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::{
+        executor::block_on,
+        future::{self, Either},
+    };
+
+    #[test]
+    fn test_sleep() {
+        let start = Instant::now();
+        block_on(future::join(
+            sleep(Duration::from_millis(50)),
+            sleep(Duration::from_millis(20)),
+        ));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_sleep_cancelled_when_dropped() {
+        let long = sleep(Duration::from_secs(60));
+        let short = sleep(Duration::from_millis(10));
+        let result = block_on(future::select(long, short));
+        let Either::Right((_, long)) = result else {
+            panic!("The longer sleep completed first");
+        };
+        let key = long.key.unwrap();
+
+        drop(long);
+        assert!(!timers().pending.lock().unwrap().wakers.contains_key(&key));
+    }
+}
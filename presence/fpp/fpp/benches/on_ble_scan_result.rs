@@ -0,0 +1,78 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Measures the time spent processing a BLE scan result on the detector hot
+//! path, with the default options and with RSSI filtering and outlier
+//! rejection enabled, for a growing number of tracked devices.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use fpp::clock::FakeClock;
+use fpp::fused_presence_utils::{
+    AdvertisingChannel, BleScanResult, MaybeTxPower, ProximityStateOptions, RssiFilter,
+    RssiOutlierRejection,
+};
+use fpp::presence_detector::PresenceDetector;
+
+const SCAN_INTERVAL_MILLIS: u64 = 10;
+
+fn bench_options(c: &mut Criterion, name: &str, options: ProximityStateOptions) {
+    let mut group = c.benchmark_group(name);
+    for device_count in [1, 16, 64] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(device_count),
+            &device_count,
+            |b, device_count| {
+                let clock = FakeClock::new();
+                let mut presence_detector =
+                    PresenceDetector::with_clock(options, Box::new(clock.clone()));
+                let mut scan_count: u64 = 0;
+                b.iter(|| {
+                    scan_count += 1;
+                    clock.advance_millis(SCAN_INTERVAL_MILLIS);
+                    presence_detector.on_ble_scan_result(BleScanResult {
+                        device_id: scan_count % device_count,
+                        tx_power: MaybeTxPower::Invalid,
+                        rssi: -60 - (scan_count % 7) as i32,
+                        elapsed_real_time_millis: 0,
+                        advertising_channel: AdvertisingChannel::Unknown,
+                    })
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+fn bench_on_ble_scan_result(c: &mut Criterion) {
+    bench_options(
+        c,
+        "on_ble_scan_result/default",
+        ProximityStateOptions::default(),
+    );
+    bench_options(
+        c,
+        "on_ble_scan_result/filtered",
+        ProximityStateOptions {
+            rssi_outlier_rejection: RssiOutlierRejection::MedianAbsoluteDeviation {
+                window: 16,
+                max_deviations: 3.0,
+            },
+            rssi_filter: RssiFilter::Median { window: 8 },
+            ..ProximityStateOptions::default()
+        },
+    );
+}
+
+criterion_group!(benches, bench_on_ble_scan_result);
+criterion_main!(benches);
@@ -0,0 +1,144 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::collections::Map;
+use crate::fspl_converter::ADVERTISE_TX_POWER_HIGH_DB;
+use crate::fused_presence_utils::{AdvertisingChannel, PresenceDataSource};
+
+const DEFAULT_TX_POWER_LEARNING_WEIGHT: f64 = 0.2;
+
+/// Corrections applied to scan results of a known device model
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct CalibrationEntry {
+    /// Difference in dBm between the actual and the advertised tx power
+    pub tx_power_correction_db: i32,
+    /// Offset in dB added to the measured RSSI
+    pub rssi_offset_db: i32,
+}
+
+/// Maps device models to the corrections applied before distance conversion
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct CalibrationTable {
+    entries: Map<u32, CalibrationEntry>,
+}
+
+impl CalibrationTable {
+    /// Creates an empty calibration table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the corrections for a device model, replacing any existing
+    /// entry for the model
+    pub fn register(&mut self, model_id: u32, calibration_entry: CalibrationEntry) {
+        self.entries.insert(model_id, calibration_entry);
+    }
+
+    /// Removes the corrections for a device model
+    pub fn unregister(&mut self, model_id: u32) {
+        self.entries.remove(&model_id);
+    }
+
+    /// Returns the corrections for a device model
+    pub fn get(&self, model_id: u32) -> Option<&CalibrationEntry> {
+        self.entries.get(&model_id)
+    }
+
+    /// Applies the corrections of a device model to an RSSI value, returning
+    /// the RSSI unchanged if the model is not registered
+    pub fn apply(&self, model_id: u32, rssi: i32) -> i32 {
+        self.get(model_id).map_or(rssi, |calibration_entry| {
+            rssi + calibration_entry.rssi_offset_db - calibration_entry.tx_power_correction_db
+        })
+    }
+}
+
+/// Offsets in dB added to the RSSI of scan results received on each BLE
+/// advertising channel, compensating for frequency dependent antenna gain and
+/// path loss
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct ChannelRssiCorrection {
+    /// Offset in dB added to RSSI values measured on channel 37
+    pub channel_37_offset_db: i32,
+    /// Offset in dB added to RSSI values measured on channel 38
+    pub channel_38_offset_db: i32,
+    /// Offset in dB added to RSSI values measured on channel 39
+    pub channel_39_offset_db: i32,
+}
+
+impl ChannelRssiCorrection {
+    /// Applies the offset of an advertising channel to an RSSI value, returning
+    /// the RSSI unchanged if the channel is unknown
+    pub fn apply(&self, advertising_channel: AdvertisingChannel, rssi: i32) -> i32 {
+        match advertising_channel {
+            AdvertisingChannel::Unknown => rssi,
+            AdvertisingChannel::Channel37 => rssi + self.channel_37_offset_db,
+            AdvertisingChannel::Channel38 => rssi + self.channel_38_offset_db,
+            AdvertisingChannel::Channel39 => rssi + self.channel_39_offset_db,
+        }
+    }
+}
+
+/// Tx power assumptions used to convert the signal strength of scan results
+/// into distance
+#[derive(Clone, PartialEq, Debug)]
+pub struct TxPowerModel {
+    default_tx_power_dbm_per_source: Map<PresenceDataSource, i32>,
+    learning_weight: f64,
+}
+
+impl TxPowerModel {
+    /// Creates a model assuming high tx power for scan results without an
+    /// advertised tx power
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the tx power in dBm assumed for scan results of a data source that
+    /// carry no advertised tx power, until one is learned for the device
+    pub fn set_default_tx_power_dbm(&mut self, source: PresenceDataSource, tx_power_dbm: i32) {
+        self.default_tx_power_dbm_per_source.insert(source, tx_power_dbm);
+    }
+
+    /// Returns the tx power in dBm assumed for scan results of a data source
+    /// that carry no advertised tx power
+    pub fn get_default_tx_power_dbm(&self, source: PresenceDataSource) -> i32 {
+        self.default_tx_power_dbm_per_source
+            .get(&source)
+            .copied()
+            .unwrap_or(ADVERTISE_TX_POWER_HIGH_DB)
+    }
+
+    /// Sets the weight in [0, 1] given to each advertised tx power when
+    /// updating the tx power estimate learned for a device, 0 disabling
+    /// learning
+    pub fn set_learning_weight(&mut self, learning_weight: f64) {
+        self.learning_weight = learning_weight.clamp(0.0, 1.0);
+    }
+
+    /// Returns the weight given to each advertised tx power when updating the
+    /// tx power estimate learned for a device
+    pub fn get_learning_weight(&self) -> f64 {
+        self.learning_weight
+    }
+}
+
+impl Default for TxPowerModel {
+    fn default() -> Self {
+        TxPowerModel {
+            default_tx_power_dbm_per_source: Map::new(),
+            learning_weight: DEFAULT_TX_POWER_LEARNING_WEIGHT,
+        }
+    }
+}
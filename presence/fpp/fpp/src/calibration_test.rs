@@ -0,0 +1,89 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::calibration::*;
+use crate::fused_presence_utils::{AdvertisingChannel, PresenceDataSource};
+
+const MODEL_ID: u32 = 0x2C_FE_01;
+
+#[test]
+fn test_apply_unregistered_model() {
+    assert_eq!(CalibrationTable::new().apply(MODEL_ID, -60), -60);
+}
+
+#[test]
+fn test_apply_registered_model() {
+    let mut calibration_table = CalibrationTable::new();
+    calibration_table.register(
+        MODEL_ID,
+        CalibrationEntry {
+            tx_power_correction_db: -4,
+            rssi_offset_db: 2,
+        },
+    );
+    assert_eq!(calibration_table.apply(MODEL_ID, -60), -54);
+}
+
+#[test]
+fn test_unregister_model() {
+    let mut calibration_table = CalibrationTable::new();
+    calibration_table.register(
+        MODEL_ID,
+        CalibrationEntry {
+            tx_power_correction_db: 0,
+            rssi_offset_db: 10,
+        },
+    );
+    calibration_table.unregister(MODEL_ID);
+    assert_eq!(calibration_table.get(MODEL_ID), None);
+}
+
+#[test]
+fn test_channel_rssi_correction() {
+    let channel_rssi_correction = ChannelRssiCorrection {
+        channel_37_offset_db: 1,
+        channel_38_offset_db: -2,
+        channel_39_offset_db: 3,
+    };
+    assert_eq!(
+        channel_rssi_correction.apply(AdvertisingChannel::Unknown, -60),
+        -60
+    );
+    assert_eq!(
+        channel_rssi_correction.apply(AdvertisingChannel::Channel37, -60),
+        -59
+    );
+    assert_eq!(
+        channel_rssi_correction.apply(AdvertisingChannel::Channel38, -60),
+        -62
+    );
+    assert_eq!(
+        channel_rssi_correction.apply(AdvertisingChannel::Channel39, -60),
+        -57
+    );
+}
+
+#[test]
+fn test_tx_power_model_defaults_per_source() {
+    let mut tx_power_model = TxPowerModel::new();
+    tx_power_model.set_default_tx_power_dbm(PresenceDataSource::Ble, -7);
+    assert_eq!(
+        tx_power_model.get_default_tx_power_dbm(PresenceDataSource::Ble),
+        -7
+    );
+    assert_eq!(
+        tx_power_model.get_default_tx_power_dbm(PresenceDataSource::Nan),
+        1
+    );
+}
@@ -0,0 +1,87 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "std")]
+use std::sync::Arc;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+/// Source of the monotonic time used for all expiry computations
+pub trait Clock: Send {
+    /// Returns the milliseconds elapsed since an arbitrary, fixed start time
+    fn elapsed_real_time_millis(&self) -> u64;
+}
+
+/// Clock measuring the time elapsed since its creation
+#[cfg(feature = "std")]
+pub struct SystemClock {
+    start_time: Instant,
+}
+
+#[cfg(feature = "std")]
+impl SystemClock {
+    /// Creates a new clock starting at the current instant
+    pub fn new() -> Self {
+        SystemClock { start_time: Instant::now() }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn elapsed_real_time_millis(&self) -> u64 {
+        Instant::now().duration_since(self.start_time).as_millis() as u64
+    }
+}
+
+/// Manually advanced clock for deterministic tests. Clones share the same
+/// time, so a test can keep a clone to advance the clock given to a detector
+#[cfg(feature = "std")]
+#[derive(Clone, Default)]
+pub struct FakeClock {
+    elapsed_real_time_millis: Arc<AtomicU64>,
+}
+
+#[cfg(feature = "std")]
+impl FakeClock {
+    /// Creates a new clock starting at zero
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves the clock forward by the given number of milliseconds
+    pub fn advance_millis(&self, millis: u64) {
+        self.elapsed_real_time_millis.fetch_add(millis, Ordering::SeqCst);
+    }
+
+    /// Sets the clock to the given number of milliseconds
+    pub fn set_millis(&self, millis: u64) {
+        self.elapsed_real_time_millis.store(millis, Ordering::SeqCst);
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for FakeClock {
+    fn elapsed_real_time_millis(&self) -> u64 {
+        self.elapsed_real_time_millis.load(Ordering::SeqCst)
+    }
+}
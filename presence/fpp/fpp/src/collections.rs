@@ -0,0 +1,25 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub(crate) use alloc::boxed::Box;
+pub(crate) use alloc::collections::VecDeque;
+pub(crate) use alloc::vec::Vec;
+
+/// Map keyed by device, a HashMap with std and a BTreeMap without it
+#[cfg(feature = "std")]
+pub(crate) use std::collections::HashMap as Map;
+
+/// Map keyed by device, a HashMap with std and a BTreeMap without it
+#[cfg(not(feature = "std"))]
+pub(crate) use alloc::collections::BTreeMap as Map;
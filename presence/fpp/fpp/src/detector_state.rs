@@ -0,0 +1,55 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::{Deserialize, Serialize};
+
+use crate::collections::Vec;
+use crate::fused_presence_utils::{ProximityEstimate, ProximityState};
+
+/// Serializable snapshot of the per-device state of a presence detector. All
+/// timestamps are stored as ages relative to the time of the export, so that
+/// the state can be imported into a detector whose clock started later
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub struct PresenceDetectorState {
+    /// State of every tracked device
+    pub devices: Vec<DeviceState>,
+}
+
+/// Serializable snapshot of the state of a single device
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct DeviceState {
+    /// Device ID of the nearby device
+    pub device_id: u64,
+    /// Milliseconds since any data source last saw the device
+    pub last_seen_age_millis: u64,
+    /// Latest estimate of every data source
+    pub estimates: Vec<AgedProximityEstimate>,
+    /// Recent distinct estimates reported for the device, oldest first
+    pub estimate_history: Vec<AgedProximityEstimate>,
+    /// Last confirmed BLE proximity state
+    pub current_proximity_state: ProximityState,
+    /// Unconfirmed BLE proximity states, most recent first
+    pub transition_history: Vec<ProximityState>,
+    /// Milliseconds since the transition history was last updated
+    pub transition_history_age_millis: Option<u64>,
+}
+
+/// Proximity estimate paired with its age at the time of the export
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct AgedProximityEstimate {
+    /// Milliseconds since the estimate was obtained
+    pub age_millis: u64,
+    /// The estimate, whose timestamp is rebased on import
+    pub estimate: ProximityEstimate,
+}
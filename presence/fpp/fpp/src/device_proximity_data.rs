@@ -0,0 +1,218 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::calibration::TxPowerModel;
+use crate::collections::Vec;
+use crate::distance_filter::DistanceFilterState;
+use crate::fused_presence_utils::{
+    MaybeTxPower, MeasurementConfidence, PresenceDataSource, ProximityState, ProximityStateOptions,
+};
+use crate::math::square;
+use crate::ring_buffer::RingBuffer;
+use crate::rssi_filter::RssiFilterState;
+use crate::rssi_outlier_filter::RssiOutlierFilterState;
+use crate::tx_power_estimator::TxPowerEstimatorState;
+use crate::zone_tracker::{get_zone_index, ZoneTracker};
+
+const CONFIDENCE_RSSI_WINDOW_SIZE: usize = 8;
+const MIN_RSSI_SAMPLES_FOR_CONFIDENCE: usize = 3;
+const MAX_STABLE_RSSI_VARIANCE: f64 = 4.0;
+
+/// Zones ordered from closest to farthest, Unknown is not part of the order
+const PROXIMITY_STATES_BY_ZONE_INDEX: [ProximityState; 5] = [
+    ProximityState::Tap,
+    ProximityState::Reach,
+    ProximityState::ShortRange,
+    ProximityState::LongRange,
+    ProximityState::Far,
+];
+
+fn get_upper_bounds_meters(options: &ProximityStateOptions) -> [f64; 4] {
+    [
+        options.tap_distance_threshold_meters,
+        options.reach_distance_threshold_meters,
+        options.short_range_distance_threshold_meters,
+        options.long_range_distance_threshold_meters,
+    ]
+}
+
+fn get_proximity_state(zone_index: usize) -> ProximityState {
+    PROXIMITY_STATES_BY_ZONE_INDEX.get(zone_index).copied().unwrap_or(ProximityState::Far)
+}
+
+pub(crate) fn get_zone_index_of_state(proximity_state: ProximityState) -> Option<usize> {
+    PROXIMITY_STATES_BY_ZONE_INDEX.iter().position(|state| *state == proximity_state)
+}
+
+/// Returns the number of consecutive scans required to confirm a transition
+/// into a zone
+fn get_consecutive_scans_required(options: &ProximityStateOptions, zone_index: usize) -> u8 {
+    match options.consecutive_scans_required_per_zone.get(zone_index) {
+        Some(consecutive_scans_required) if *consecutive_scans_required > 0 => {
+            *consecutive_scans_required
+        }
+        _ => options.consecutive_scans_required,
+    }
+}
+
+/// Static function for getting proximity state from the thresholds in options
+pub(crate) fn get_proximity_state_from_threshold(
+    distance_meters: f64,
+    options: &ProximityStateOptions,
+) -> ProximityState {
+    get_proximity_state(get_zone_index(distance_meters, &get_upper_bounds_meters(options)))
+}
+
+/// Tracks the filters and hysteresis-adjusted proximity state of a single device
+pub(crate) struct DeviceProximityData {
+    zone_tracker: ZoneTracker,
+    recent_rssi: RingBuffer<i32>,
+    last_scan_millis: Option<u64>,
+    rssi_outlier_filter: RssiOutlierFilterState,
+    rssi_filter: RssiFilterState,
+    distance_filter: DistanceFilterState,
+    tx_power_estimator: TxPowerEstimatorState,
+}
+
+impl DeviceProximityData {
+    pub(crate) fn new(options: &ProximityStateOptions) -> Self {
+        DeviceProximityData {
+            zone_tracker: ZoneTracker::new(
+                options
+                    .consecutive_scans_required_per_zone
+                    .iter()
+                    .copied()
+                    .fold(options.consecutive_scans_required, u8::max),
+            ),
+            recent_rssi: RingBuffer::with_capacity(CONFIDENCE_RSSI_WINDOW_SIZE),
+            last_scan_millis: None,
+            rssi_outlier_filter: RssiOutlierFilterState::new(options.rssi_outlier_rejection),
+            rssi_filter: RssiFilterState::new(options.rssi_filter),
+            distance_filter: DistanceFilterState::new(options.distance_filter),
+            tx_power_estimator: TxPowerEstimatorState::new(),
+        }
+    }
+
+    /// Returns whether a scan result obtained at the given time is older than
+    /// the latest scan result of the device, recording the time otherwise
+    pub(crate) fn is_out_of_order_scan(&mut self, elapsed_real_time_millis: u64) -> bool {
+        if self
+            .last_scan_millis
+            .is_some_and(|last_scan_millis| elapsed_real_time_millis < last_scan_millis)
+        {
+            return true;
+        }
+        self.last_scan_millis = Some(elapsed_real_time_millis);
+        false
+    }
+
+    /// Returns whether a new RSSI value is an outlier that must not update the
+    /// device's estimate
+    pub(crate) fn is_rssi_outlier(&mut self, rssi: i32) -> bool {
+        self.rssi_outlier_filter.is_outlier(rssi)
+    }
+
+    /// Feeds a new RSSI value into the device's RSSI filter, recording the raw
+    /// value for confidence computation
+    pub(crate) fn filter_rssi(&mut self, rssi: i32) -> i32 {
+        self.recent_rssi.push(rssi);
+        self.rssi_filter.update(rssi)
+    }
+
+    /// Returns the confidence of BLE distance measurements based on the variance
+    /// of the recent raw RSSI values
+    pub(crate) fn get_measurement_confidence(&self) -> MeasurementConfidence {
+        if self.recent_rssi.len() < MIN_RSSI_SAMPLES_FOR_CONFIDENCE {
+            return MeasurementConfidence::Low;
+        }
+        let sample_count = self.recent_rssi.len() as f64;
+        let mean = self.recent_rssi.iter().map(f64::from).sum::<f64>() / sample_count;
+        let variance =
+            self.recent_rssi.iter().map(|rssi| square(f64::from(rssi) - mean)).sum::<f64>()
+                / sample_count;
+        if variance <= MAX_STABLE_RSSI_VARIANCE {
+            MeasurementConfidence::Medium
+        } else {
+            MeasurementConfidence::Low
+        }
+    }
+
+    /// Feeds the tx power of a new scan result into the device's tx power
+    /// estimate and returns the tx power in dBm to use for the scan result
+    pub(crate) fn estimate_tx_power(
+        &mut self,
+        tx_power: MaybeTxPower,
+        source: PresenceDataSource,
+        tx_power_model: &TxPowerModel,
+    ) -> i32 {
+        self.tx_power_estimator.update(tx_power, source, tx_power_model)
+    }
+
+    /// Returns the tx power in dBm learned from the device's scan results
+    pub(crate) fn get_tx_power_estimate(&self) -> Option<i32> {
+        self.tx_power_estimator.get_estimate_dbm()
+    }
+
+    /// Feeds a new distance into the device's distance filter
+    pub(crate) fn filter_distance(&mut self, distance_meters: f64) -> f64 {
+        self.distance_filter.update(distance_meters)
+    }
+
+    /// Returns the confirmed state, the unconfirmed transition history (most
+    /// recent first) and the time of its last update
+    pub(crate) fn export_hysteresis_state(
+        &self,
+    ) -> (ProximityState, Vec<ProximityState>, Option<u64>) {
+        let (current_zone_index, transition_history, last_update_millis) =
+            self.zone_tracker.export_state();
+        (
+            current_zone_index.map_or(ProximityState::Unknown, get_proximity_state),
+            transition_history.map(get_proximity_state).collect(),
+            last_update_millis,
+        )
+    }
+
+    /// Restores state previously returned by `export_hysteresis_state`
+    pub(crate) fn import_hysteresis_state(
+        &mut self,
+        current_proximity_state: ProximityState,
+        transition_history: &[ProximityState],
+        last_update_millis: Option<u64>,
+    ) {
+        self.zone_tracker.import_state(
+            get_zone_index_of_state(current_proximity_state),
+            transition_history.iter().copied().filter_map(get_zone_index_of_state),
+            last_update_millis,
+        );
+    }
+
+    /// Records a new distance measurement and returns the proximity state once
+    /// enough consecutive scans agree on it
+    pub(crate) fn update_current_proximity_state(
+        &mut self,
+        distance_meters: f64,
+        elapsed_real_time_millis: u64,
+        options: &ProximityStateOptions,
+    ) -> Option<ProximityState> {
+        self.zone_tracker
+            .update(
+                distance_meters,
+                elapsed_real_time_millis,
+                &get_upper_bounds_meters(options),
+                options.hysteresis_meters,
+                |zone_index| get_consecutive_scans_required(options, zone_index),
+            )
+            .map(get_proximity_state)
+    }
+}
@@ -0,0 +1,172 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::device_proximity_data::*;
+use crate::fused_presence_utils::*;
+
+const OPTIONS: ProximityStateOptions = ProximityStateOptions {
+    tap_distance_threshold_meters: 0.1,
+    reach_distance_threshold_meters: 0.5,
+    short_range_distance_threshold_meters: 1.0,
+    long_range_distance_threshold_meters: 3.0,
+    hysteresis_meters: 0.2,
+    consecutive_scans_required: 2,
+    consecutive_scans_required_per_zone: [0; 5],
+    rssi_outlier_rejection: RssiOutlierRejection::None,
+    rssi_filter: RssiFilter::None,
+    distance_filter: DistanceFilter::None,
+};
+
+#[test]
+fn test_get_proximity_state_from_threshold() {
+    assert_eq!(
+        get_proximity_state_from_threshold(0.05, &OPTIONS),
+        ProximityState::Tap
+    );
+    assert_eq!(
+        get_proximity_state_from_threshold(0.5, &OPTIONS),
+        ProximityState::Reach
+    );
+    assert_eq!(
+        get_proximity_state_from_threshold(0.7, &OPTIONS),
+        ProximityState::ShortRange
+    );
+    assert_eq!(
+        get_proximity_state_from_threshold(2.0, &OPTIONS),
+        ProximityState::LongRange
+    );
+    assert_eq!(
+        get_proximity_state_from_threshold(3.5, &OPTIONS),
+        ProximityState::Far
+    );
+}
+
+#[test]
+fn test_consecutive_scans_required() {
+    let mut device_proximity_data = DeviceProximityData::new(&OPTIONS);
+    assert_eq!(
+        device_proximity_data.update_current_proximity_state(0.3, 0, &OPTIONS),
+        None
+    );
+    assert_eq!(
+        device_proximity_data.update_current_proximity_state(0.3, 100, &OPTIONS),
+        Some(ProximityState::Reach)
+    );
+}
+
+#[test]
+fn test_hysteresis_keeps_current_zone_near_boundary() {
+    let mut device_proximity_data = DeviceProximityData::new(&OPTIONS);
+    device_proximity_data.update_current_proximity_state(0.3, 0, &OPTIONS);
+    device_proximity_data.update_current_proximity_state(0.3, 100, &OPTIONS);
+    // Past the reach boundary, but within the hysteresis margin
+    device_proximity_data.update_current_proximity_state(0.6, 200, &OPTIONS);
+    assert_eq!(
+        device_proximity_data.update_current_proximity_state(0.6, 300, &OPTIONS),
+        Some(ProximityState::Reach)
+    );
+    // Past the reach boundary by more than the hysteresis margin
+    device_proximity_data.update_current_proximity_state(0.8, 400, &OPTIONS);
+    assert_eq!(
+        device_proximity_data.update_current_proximity_state(0.8, 500, &OPTIONS),
+        Some(ProximityState::ShortRange)
+    );
+    // Back within the reach boundary, but within the hysteresis margin
+    device_proximity_data.update_current_proximity_state(0.4, 600, &OPTIONS);
+    assert_eq!(
+        device_proximity_data.update_current_proximity_state(0.4, 700, &OPTIONS),
+        Some(ProximityState::ShortRange)
+    );
+}
+
+#[test]
+fn test_stale_transition_history_is_cleared() {
+    let mut device_proximity_data = DeviceProximityData::new(&OPTIONS);
+    device_proximity_data.update_current_proximity_state(0.3, 0, &OPTIONS);
+    assert_eq!(
+        device_proximity_data.update_current_proximity_state(0.3, 10_000, &OPTIONS),
+        None
+    );
+}
+
+#[test]
+fn test_confidence_is_low_without_enough_samples() {
+    let mut device_proximity_data = DeviceProximityData::new(&OPTIONS);
+    device_proximity_data.filter_rssi(-60);
+    device_proximity_data.filter_rssi(-60);
+    assert_eq!(
+        device_proximity_data.get_measurement_confidence(),
+        MeasurementConfidence::Low
+    );
+}
+
+#[test]
+fn test_confidence_is_medium_for_stable_rssi() {
+    let mut device_proximity_data = DeviceProximityData::new(&OPTIONS);
+    for rssi in [-60, -61, -59, -60, -62] {
+        device_proximity_data.filter_rssi(rssi);
+    }
+    assert_eq!(
+        device_proximity_data.get_measurement_confidence(),
+        MeasurementConfidence::Medium
+    );
+}
+
+#[test]
+fn test_confidence_is_low_for_noisy_rssi() {
+    let mut device_proximity_data = DeviceProximityData::new(&OPTIONS);
+    for rssi in [-60, -72, -55, -80, -62] {
+        device_proximity_data.filter_rssi(rssi);
+    }
+    assert_eq!(
+        device_proximity_data.get_measurement_confidence(),
+        MeasurementConfidence::Low
+    );
+}
+
+#[test]
+fn test_consecutive_scans_required_per_zone() {
+    let options = ProximityStateOptions {
+        consecutive_scans_required_per_zone: [4, 0, 0, 0, 1],
+        ..OPTIONS
+    };
+    let mut device_proximity_data = DeviceProximityData::new(&options);
+    for elapsed_real_time_millis in [0, 100, 200] {
+        assert_eq!(
+            device_proximity_data.update_current_proximity_state(
+                0.05,
+                elapsed_real_time_millis,
+                &options
+            ),
+            None
+        );
+    }
+    assert_eq!(
+        device_proximity_data.update_current_proximity_state(0.05, 300, &options),
+        Some(ProximityState::Tap)
+    );
+    assert_eq!(
+        device_proximity_data.update_current_proximity_state(5.0, 400, &options),
+        Some(ProximityState::Far)
+    );
+    // Zones without a count of their own use consecutive_scans_required
+    assert_eq!(
+        device_proximity_data.update_current_proximity_state(0.7, 500, &options),
+        None
+    );
+    assert_eq!(
+        device_proximity_data.update_current_proximity_state(0.7, 600, &options),
+        Some(ProximityState::ShortRange)
+    );
+}
@@ -0,0 +1,52 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::fused_presence_utils::DistanceFilter;
+
+/// Per-device state of a distance filter
+pub(crate) struct DistanceFilterState {
+    filter: DistanceFilter,
+    estimate: Option<f64>,
+    error_covariance: f64,
+}
+
+impl DistanceFilterState {
+    pub(crate) fn new(filter: DistanceFilter) -> Self {
+        DistanceFilterState { filter, estimate: None, error_covariance: 0.0 }
+    }
+
+    /// Feeds a new distance measurement into the filter and returns the
+    /// smoothed distance
+    pub(crate) fn update(&mut self, distance_meters: f64) -> f64 {
+        let filtered_distance_meters = match (self.filter, self.estimate) {
+            (DistanceFilter::None, _) | (_, None) => {
+                if let DistanceFilter::Kalman { measurement_noise, .. } = self.filter {
+                    self.error_covariance = measurement_noise;
+                }
+                distance_meters
+            }
+            (DistanceFilter::Exponential { alpha }, Some(estimate)) => {
+                alpha * distance_meters + (1.0 - alpha) * estimate
+            }
+            (DistanceFilter::Kalman { process_noise, measurement_noise }, Some(estimate)) => {
+                let predicted_covariance = self.error_covariance + process_noise;
+                let gain = predicted_covariance / (predicted_covariance + measurement_noise);
+                self.error_covariance = (1.0 - gain) * predicted_covariance;
+                estimate + gain * (distance_meters - estimate)
+            }
+        };
+        self.estimate = Some(filtered_distance_meters);
+        filtered_distance_meters
+    }
+}
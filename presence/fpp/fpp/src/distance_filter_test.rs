@@ -0,0 +1,91 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::distance_filter::DistanceFilterState;
+use crate::fspl_converter::{compute_distance_meters_at_tx_power, ADVERTISE_TX_POWER_HIGH_DB};
+use crate::fused_presence_utils::{DistanceFilter, EnvironmentProfile};
+
+// Synthetic RSSI trace of a stationary device at the edge of the long range zone:
+// readings scattered by a few dB around -80 dBm.
+const SYNTHETIC_STATIONARY_RSSI_TRACE: [i32; 12] =
+    [-79, -81, -78, -83, -80, -79, -84, -78, -80, -82, -79, -81];
+
+fn filter_trace(filter: DistanceFilter) -> Vec<f64> {
+    let mut filter_state = DistanceFilterState::new(filter);
+    SYNTHETIC_STATIONARY_RSSI_TRACE
+        .iter()
+        .map(|rssi| {
+            filter_state.update(compute_distance_meters_at_tx_power(
+                *rssi,
+                ADVERTISE_TX_POWER_HIGH_DB,
+                &EnvironmentProfile::FreeSpace,
+            ))
+        })
+        .collect()
+}
+
+fn spread(distances: &[f64]) -> f64 {
+    let max = distances.iter().copied().fold(f64::MIN, f64::max);
+    let min = distances.iter().copied().fold(f64::MAX, f64::min);
+    max - min
+}
+
+#[test]
+fn test_no_filter_returns_raw_distances() {
+    let raw_distances: Vec<f64> = SYNTHETIC_STATIONARY_RSSI_TRACE
+        .iter()
+        .map(|rssi| {
+            compute_distance_meters_at_tx_power(
+                *rssi,
+                ADVERTISE_TX_POWER_HIGH_DB,
+                &EnvironmentProfile::FreeSpace,
+            )
+        })
+        .collect();
+    assert_eq!(filter_trace(DistanceFilter::None), raw_distances);
+}
+
+#[test]
+fn test_first_measurement_is_not_smoothed() {
+    let mut filter_state = DistanceFilterState::new(DistanceFilter::Kalman {
+        process_noise: 0.01,
+        measurement_noise: 4.0,
+    });
+    assert_eq!(filter_state.update(10.0), 10.0);
+}
+
+#[test]
+fn test_exponential_filter() {
+    let mut filter_state = DistanceFilterState::new(DistanceFilter::Exponential { alpha: 0.25 });
+    assert_eq!(filter_state.update(1.0), 1.0);
+    assert_eq!(filter_state.update(5.0), 2.0);
+    assert_eq!(filter_state.update(2.0), 2.0);
+}
+
+#[test]
+fn test_exponential_filter_reduces_spread_on_stationary_trace() {
+    let raw_spread = spread(&filter_trace(DistanceFilter::None));
+    let filtered = filter_trace(DistanceFilter::Exponential { alpha: 0.3 });
+    assert!(spread(filtered.get(1..).unwrap_or_default()) < raw_spread);
+}
+
+#[test]
+fn test_kalman_filter_reduces_spread_on_stationary_trace() {
+    let raw_spread = spread(&filter_trace(DistanceFilter::None));
+    let filtered = filter_trace(DistanceFilter::Kalman {
+        process_noise: 0.01,
+        measurement_noise: 4.0,
+    });
+    assert!(spread(filtered.get(4..).unwrap_or_default()) < raw_spread / 2.0);
+}
@@ -12,26 +12,50 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-const ADVERTISE_TX_POWER_HIGH_DB: i32 = 1;
+use crate::fused_presence_utils::EnvironmentProfile;
+use crate::math::powf;
+
+/// Nominal tx power in dBm of advertisements at high tx power
+pub(crate) const ADVERTISE_TX_POWER_HIGH_DB: i32 = 1;
 
 const FSPL_AT_1_METER_DB: i32 = 40;
 
 const MEASURED_POWER_AT_1_METER_DB_AT_HIGH_TX_POWER: i32 = -60;
 
-pub fn compute_distance_meters_at_high_tx_power(rssi: i32) -> f64 {
-    let nominal_tx_power = ADVERTISE_TX_POWER_HIGH_DB;
-    let antenna_gain =
-        (nominal_tx_power - FSPL_AT_1_METER_DB) - MEASURED_POWER_AT_1_METER_DB_AT_HIGH_TX_POWER;
-    let tx_power_at_0_meters = nominal_tx_power - antenna_gain;
-    compute_distance_meters(tx_power_at_0_meters, rssi)
+/// Computes the distance to a device advertising at the given tx power in
+/// dBm, assuming the antenna gain measured at high tx power
+pub fn compute_distance_meters_at_tx_power(
+    rssi: i32,
+    tx_power_dbm: i32,
+    environment_profile: &EnvironmentProfile,
+) -> f64 {
+    let antenna_gain = (ADVERTISE_TX_POWER_HIGH_DB - FSPL_AT_1_METER_DB)
+        - MEASURED_POWER_AT_1_METER_DB_AT_HIGH_TX_POWER;
+    let tx_power_at_0_meters = tx_power_dbm - antenna_gain;
+    compute_distance_meters(tx_power_at_0_meters, rssi, environment_profile)
 }
 
-pub fn compute_distance_meters(tx_power_at_0_meters: i32, rssi: i32) -> f64 {
-    let fspl = tx_power_at_0_meters - rssi;
-    ble_fspl_to_meters(fspl)
+pub fn compute_distance_meters(
+    tx_power_at_0_meters: i32,
+    rssi: i32,
+    environment_profile: &EnvironmentProfile,
+) -> f64 {
+    let path_loss = tx_power_at_0_meters - rssi;
+    ble_path_loss_to_meters(path_loss, environment_profile)
 }
 
-fn ble_fspl_to_meters(fspl: i32) -> f64 {
+fn ble_path_loss_to_meters(path_loss: i32, environment_profile: &EnvironmentProfile) -> f64 {
     let base: f64 = 10.0;
-    base.powi((fspl - FSPL_AT_1_METER_DB) / 20)
+    match environment_profile {
+        // Free space keeps the original conversion, which moves in steps of
+        // 20 dB, so that the default profile doesn't change existing zones.
+        EnvironmentProfile::FreeSpace => {
+            powf(base, f64::from((path_loss - FSPL_AT_1_METER_DB) / 20))
+        }
+        _ => powf(
+            base,
+            (f64::from(path_loss) - environment_profile.reference_loss_at_1_meter_db())
+                / (10.0 * environment_profile.path_loss_exponent()),
+        ),
+    }
 }
@@ -12,19 +12,112 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::fspl_converter::compute_distance_meters_at_high_tx_power;
+use crate::fspl_converter::{compute_distance_meters_at_tx_power, ADVERTISE_TX_POWER_HIGH_DB};
+use crate::fused_presence_utils::EnvironmentProfile;
 
 #[test]
 fn test_short_distance() {
-    assert_eq!(compute_distance_meters_at_high_tx_power(-40), 0.1);
+    assert_eq!(
+        compute_distance_meters_at_tx_power(
+            -40,
+            ADVERTISE_TX_POWER_HIGH_DB,
+            &EnvironmentProfile::FreeSpace
+        ),
+        0.1
+    );
 }
 
 #[test]
 fn test_medium_distance() {
-    assert_eq!(compute_distance_meters_at_high_tx_power(-60), 1.0);
+    assert_eq!(
+        compute_distance_meters_at_tx_power(
+            -60,
+            ADVERTISE_TX_POWER_HIGH_DB,
+            &EnvironmentProfile::FreeSpace
+        ),
+        1.0
+    );
 }
 
 #[test]
 fn test_large_distance() {
-    assert_eq!(compute_distance_meters_at_high_tx_power(-80), 10.0);
+    assert_eq!(
+        compute_distance_meters_at_tx_power(
+            -80,
+            ADVERTISE_TX_POWER_HIGH_DB,
+            &EnvironmentProfile::FreeSpace
+        ),
+        10.0
+    );
+}
+
+#[test]
+fn test_free_space_profile_is_quantized() {
+    assert_eq!(
+        compute_distance_meters_at_tx_power(
+            -70,
+            ADVERTISE_TX_POWER_HIGH_DB,
+            &EnvironmentProfile::FreeSpace
+        ),
+        1.0
+    );
+    assert_eq!(
+        compute_distance_meters_at_tx_power(
+            -79,
+            ADVERTISE_TX_POWER_HIGH_DB,
+            &EnvironmentProfile::FreeSpace
+        ),
+        1.0
+    );
+}
+
+#[test]
+fn test_indoor_profile_is_not_quantized() {
+    let distance_meters = compute_distance_meters_at_tx_power(
+        -70,
+        ADVERTISE_TX_POWER_HIGH_DB,
+        &EnvironmentProfile::Indoor,
+    );
+    assert!((distance_meters - 10f64.powf(10.0 / 27.0)).abs() < 1e-9);
+}
+
+#[test]
+fn test_lossy_profiles_shorten_distance() {
+    let free_space_distance = compute_distance_meters_at_tx_power(
+        -80,
+        ADVERTISE_TX_POWER_HIGH_DB,
+        &EnvironmentProfile::FreeSpace,
+    );
+    let indoor_distance = compute_distance_meters_at_tx_power(
+        -80,
+        ADVERTISE_TX_POWER_HIGH_DB,
+        &EnvironmentProfile::Indoor,
+    );
+    let crowded_distance = compute_distance_meters_at_tx_power(
+        -80,
+        ADVERTISE_TX_POWER_HIGH_DB,
+        &EnvironmentProfile::Crowded,
+    );
+    assert!(indoor_distance < free_space_distance);
+    assert!(crowded_distance < indoor_distance);
+}
+
+#[test]
+fn test_custom_profile() {
+    let environment_profile = EnvironmentProfile::Custom {
+        path_loss_exponent: 4.0,
+        reference_loss_at_1_meter_db: 20.0,
+    };
+    assert_eq!(
+        compute_distance_meters_at_tx_power(-80, ADVERTISE_TX_POWER_HIGH_DB, &environment_profile),
+        10.0
+    );
+}
+
+#[test]
+fn test_lower_tx_power_shortens_distance() {
+    assert_eq!(
+        compute_distance_meters_at_tx_power(-60, -19, &EnvironmentProfile::FreeSpace),
+        0.1
+    );
 }
@@ -12,15 +12,24 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use serde::{Deserialize, Serialize};
+
 pub(crate) const DEFAULT_TAP_DISTANCE_THRESHOLD_METERS: f64 = AMBIGUITY_METERS + 0.02;
 pub(crate) const DEFAULT_REACH_DISTANCE_THRESHOLD_METERS: f64 = AMBIGUITY_METERS + 0.5;
 pub(crate) const DEFAULT_SHORT_RANGE_DISTANCE_THRESHOLD_METERS: f64 = AMBIGUITY_METERS + 1.2;
 pub(crate) const DEFAULT_LONG_RANGE_DISTANCE_THRESHOLD_METERS: f64 = AMBIGUITY_METERS + 3.0;
 pub(crate) const DEFAULT_CONSECUTIVE_SCANS_REQUIRED: u8 = 2;
+pub(crate) const DEFAULT_HYSTERESIS_METERS: f64 = AMBIGUITY_METERS;
+pub(crate) const DEFAULT_ESTIMATE_FRESHNESS_MILLIS: u64 = 4000;
 const AMBIGUITY_METERS: f64 = 0.06;
+const FREE_SPACE_PATH_LOSS_EXPONENT: f64 = 2.0;
+const INDOOR_PATH_LOSS_EXPONENT: f64 = 2.7;
+const CROWDED_PATH_LOSS_EXPONENT: f64 = 3.3;
+const FREE_SPACE_REFERENCE_LOSS_AT_1_METER_DB: f64 = 40.0;
+const CROWDED_REFERENCE_LOSS_AT_1_METER_DB: f64 = 45.0;
 
 /// Proximity state from device to another in terms of actionability
-#[derive(Eq, Hash, Copy, Clone, PartialEq, Debug)]
+#[derive(Eq, Hash, Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
 #[repr(C)]
 pub enum ProximityState {
     /// Unknown proximity state
@@ -38,7 +47,7 @@ pub enum ProximityState {
 }
 
 /// Represents the confidence levels for a given measurement
-#[derive(Copy, Clone, PartialEq, Debug)]
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
 #[repr(C)]
 pub enum MeasurementConfidence {
     /// Measurement confidence is low, the default for BLE medium
@@ -52,7 +61,7 @@ pub enum MeasurementConfidence {
 }
 
 /// Data sources that are used to track presence
-#[derive(Copy, Clone, PartialEq, Debug)]
+#[derive(Eq, Hash, Ord, PartialOrd, Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
 #[repr(C)]
 pub enum PresenceDataSource {
     /// Data source for proximity estimate is BLE
@@ -65,7 +74,220 @@ pub enum PresenceDataSource {
     Unknown,
 }
 
+/// Strategy used to combine proximity estimates from different data sources for
+/// the same device
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum FusionStrategy {
+    /// The fresh estimate from the source with the highest precedence is used
+    Precedence,
+    /// Fresh estimates from all sources are averaged, weighted by their
+    /// measurement confidence
+    ConfidenceWeighted,
+}
+
+/// Configures how proximity estimates from BLE, UWB and NAN are fused for the
+/// same device
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct FusionPolicy {
+    /// Strategy used to combine fresh estimates
+    pub strategy: FusionStrategy,
+    /// Data sources in order of precedence, highest first. Sources missing from
+    /// the list are ignored
+    pub source_precedence: [PresenceDataSource; 3],
+    /// Time in milliseconds a BLE estimate is considered fresh
+    pub ble_freshness_millis: u64,
+    /// Time in milliseconds a UWB estimate is considered fresh
+    pub uwb_freshness_millis: u64,
+    /// Time in milliseconds a NAN estimate is considered fresh
+    pub nan_freshness_millis: u64,
+}
+
+impl FusionPolicy {
+    /// Returns the freshness window in milliseconds for a given data source
+    pub fn freshness_millis(&self, source: PresenceDataSource) -> u64 {
+        match source {
+            PresenceDataSource::Ble => self.ble_freshness_millis,
+            PresenceDataSource::Uwb => self.uwb_freshness_millis,
+            PresenceDataSource::Nan => self.nan_freshness_millis,
+            PresenceDataSource::Unknown => 0,
+        }
+    }
+}
+
+impl Default for FusionPolicy {
+    fn default() -> Self {
+        FusionPolicy {
+            strategy: FusionStrategy::Precedence,
+            source_precedence: [
+                PresenceDataSource::Uwb,
+                PresenceDataSource::Nan,
+                PresenceDataSource::Ble,
+            ],
+            ble_freshness_millis: DEFAULT_ESTIMATE_FRESHNESS_MILLIS,
+            uwb_freshness_millis: DEFAULT_ESTIMATE_FRESHNESS_MILLIS,
+            nan_freshness_millis: DEFAULT_ESTIMATE_FRESHNESS_MILLIS,
+        }
+    }
+}
+
+/// Path loss model used to convert signal strength into distance
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum EnvironmentProfile {
+    /// Line of sight without obstacles. Distances are rounded down to a power
+    /// of ten meters, as computed before profiles were configurable.
+    FreeSpace,
+    /// Typical indoor environment with walls and furniture
+    Indoor,
+    /// Environment with many people or obstacles absorbing the signal
+    Crowded,
+    /// Path loss model with explicit parameters
+    Custom {
+        /// Rate at which the path loss grows with the logarithm of the distance
+        path_loss_exponent: f64,
+        /// Path loss in dB at a distance of 1 meter
+        reference_loss_at_1_meter_db: f64,
+    },
+}
+
+impl EnvironmentProfile {
+    /// Returns the rate at which the path loss grows with the logarithm of the
+    /// distance
+    pub fn path_loss_exponent(&self) -> f64 {
+        match self {
+            Self::FreeSpace => FREE_SPACE_PATH_LOSS_EXPONENT,
+            Self::Indoor => INDOOR_PATH_LOSS_EXPONENT,
+            Self::Crowded => CROWDED_PATH_LOSS_EXPONENT,
+            Self::Custom { path_loss_exponent, .. } => *path_loss_exponent,
+        }
+    }
+
+    /// Returns the path loss in dB at a distance of 1 meter
+    pub fn reference_loss_at_1_meter_db(&self) -> f64 {
+        match self {
+            Self::FreeSpace | Self::Indoor => FREE_SPACE_REFERENCE_LOSS_AT_1_METER_DB,
+            Self::Crowded => CROWDED_REFERENCE_LOSS_AT_1_METER_DB,
+            Self::Custom { reference_loss_at_1_meter_db, .. } => *reference_loss_at_1_meter_db,
+        }
+    }
+}
+
+/// Filter used to smooth the distance computed from consecutive scan results of
+/// the same device
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[repr(C)]
+pub enum DistanceFilter {
+    /// Raw distances are used as is
+    None,
+    /// Exponential moving average, where alpha in (0, 1] is the weight given to
+    /// the newest measurement
+    Exponential {
+        /// Smoothing factor
+        alpha: f64,
+    },
+    /// One dimensional Kalman filter assuming a stationary device
+    Kalman {
+        /// Variance of the change in distance between two measurements
+        process_noise: f64,
+        /// Variance of the measured distance
+        measurement_noise: f64,
+    },
+}
+
+/// Filter applied to the RSSI of consecutive scan results of the same device
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[repr(C)]
+pub enum RssiFilter {
+    /// Raw RSSI values are used as is
+    None,
+    /// Mean of the most recent RSSI values
+    MovingAverage {
+        /// Number of recent RSSI values to average
+        window: usize,
+    },
+    /// Median of the most recent RSSI values
+    Median {
+        /// Number of recent RSSI values to take the median of
+        window: usize,
+    },
+}
+
+/// Rejection of outlying RSSI values, e.g. single reflected or attenuated
+/// packets, before they reach the RSSI filter
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[repr(C)]
+pub enum RssiOutlierRejection {
+    /// Every RSSI value is accepted
+    None,
+    /// Rejects RSSI values farther from the median of the recent RSSI values
+    /// than a multiple of their median absolute deviation
+    MedianAbsoluteDeviation {
+        /// Number of recent RSSI values the median is computed over
+        window: usize,
+        /// Number of median absolute deviations past which a value is rejected
+        max_deviations: f64,
+    },
+}
+
+/// Options used to compute the proximity state of nearby devices
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[repr(C)]
+pub struct ProximityStateOptions {
+    /// Upper bound of the tap zone in meters
+    pub tap_distance_threshold_meters: f64,
+    /// Upper bound of the reach zone in meters
+    pub reach_distance_threshold_meters: f64,
+    /// Upper bound of the short range zone in meters
+    pub short_range_distance_threshold_meters: f64,
+    /// Upper bound of the long range zone in meters
+    pub long_range_distance_threshold_meters: f64,
+    /// Distance in meters a device must move past a zone boundary before it is
+    /// considered to have left its current zone
+    pub hysteresis_meters: f64,
+    /// Number of consecutive scans that must agree on a new zone before a
+    /// transition is confirmed
+    pub consecutive_scans_required: u8,
+    /// Number of consecutive scans that must agree before a transition into
+    /// each zone, ordered from tap to far, is confirmed. Zones set to 0 use
+    /// `consecutive_scans_required`
+    pub consecutive_scans_required_per_zone: [u8; 5],
+    /// Outlier rejection applied to BLE RSSI values before filtering
+    pub rssi_outlier_rejection: RssiOutlierRejection,
+    /// Filter applied to BLE RSSI values before distance conversion
+    pub rssi_filter: RssiFilter,
+    /// Filter applied to BLE distances before zone classification
+    pub distance_filter: DistanceFilter,
+}
+
+impl Default for ProximityStateOptions {
+    fn default() -> Self {
+        ProximityStateOptions {
+            tap_distance_threshold_meters: DEFAULT_TAP_DISTANCE_THRESHOLD_METERS,
+            reach_distance_threshold_meters: DEFAULT_REACH_DISTANCE_THRESHOLD_METERS,
+            short_range_distance_threshold_meters: DEFAULT_SHORT_RANGE_DISTANCE_THRESHOLD_METERS,
+            long_range_distance_threshold_meters: DEFAULT_LONG_RANGE_DISTANCE_THRESHOLD_METERS,
+            hysteresis_meters: DEFAULT_HYSTERESIS_METERS,
+            consecutive_scans_required: DEFAULT_CONSECUTIVE_SCANS_REQUIRED,
+            consecutive_scans_required_per_zone: [0; 5],
+            rssi_outlier_rejection: RssiOutlierRejection::None,
+            rssi_filter: RssiFilter::None,
+            distance_filter: DistanceFilter::None,
+        }
+    }
+}
+
+/// A user-defined proximity zone, reported alongside the built-in proximity
+/// states
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[repr(C)]
+pub struct CustomZone {
+    /// Caller-assigned identifier of the zone
+    pub zone_id: u32,
+    /// Upper bound of the zone in meters
+    pub upper_bound_meters: f64,
+}
+
 /// A PII-stripped subset of Bluetooth scan result
+#[derive(Copy, Clone, PartialEq, Debug)]
 #[repr(C)]
 pub struct BleScanResult {
     /// Device ID of the nearby device
@@ -74,21 +296,39 @@ pub struct BleScanResult {
     pub tx_power: MaybeTxPower,
     /// RSSI value
     pub rssi: i32,
-    /// Time scan result was obtained
+    /// Time scan result was obtained, on the same time base as the presence
+    /// detector's clock, or 0 if unknown
     pub elapsed_real_time_millis: u64,
+    /// Advertising channel the scan result was received on
+    pub advertising_channel: AdvertisingChannel,
+}
+
+/// BLE primary advertising channel a scan result was received on
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+#[repr(C)]
+pub enum AdvertisingChannel {
+    /// The advertising channel is unknown
+    Unknown,
+    /// Advertising channel 37 (2402 MHz)
+    Channel37,
+    /// Advertising channel 38 (2426 MHz)
+    Channel38,
+    /// Advertising channel 39 (2480 MHz)
+    Channel39,
 }
 
 /// Enum representing an optional tx power value
+#[derive(Copy, Clone, PartialEq, Debug)]
 #[repr(C)]
 pub enum MaybeTxPower {
-    /// Valid TX power with associated data value
+    /// Valid TX power with the advertised tx power level in dBm
     Valid(i32),
     /// Absent Tx Power
     Invalid,
 }
 
 /// Describes the most accurate and recent measurement for a given device
-#[derive(Copy, Clone, PartialEq, Debug)]
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
 #[repr(C)]
 pub struct ProximityEstimate {
     /// Device ID of the nearby device
@@ -105,3 +345,32 @@ pub struct ProximityEstimate {
     /// Medium through which the proximity estimate was computed
     pub source: PresenceDataSource,
 }
+
+/// Radial motion of a nearby device relative to this device
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[repr(C)]
+pub enum RadialMotion {
+    /// The device is moving closer
+    Approaching,
+    /// The device is moving away
+    Retreating,
+    /// The device is not moving noticeably closer or farther
+    Stationary,
+}
+
+/// Describes how fast a nearby device is approaching or retreating, derived
+/// from its recent proximity estimates
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[repr(C)]
+pub struct MotionEstimate {
+    /// Device ID of the nearby device
+    pub device_id: u64,
+    /// Signed rate of change of the distance in meters per second, negative
+    /// when the device is approaching
+    pub radial_velocity_meters_per_second: f64,
+    /// Radial motion of the nearby device
+    pub radial_motion: RadialMotion,
+    /// The time of the most recent proximity estimate the motion estimate is
+    /// based on (milliseconds since the program start time)
+    pub elapsed_real_time_millis: u64,
+}
@@ -0,0 +1,127 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::collections::Map;
+use crate::device_proximity_data::get_zone_index_of_state;
+use crate::fused_presence_utils::{ProximityEstimate, ProximityState};
+
+const DEFAULT_STOP_RANGING_DELAY_MILLIS: u64 = 5000;
+
+/// Conditions under which UWB ranging with a device is started and stopped
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct HandOffPolicy {
+    /// Ranging is started once a device is at or closer than this state
+    pub start_ranging_state: ProximityState,
+    /// Ranging is stopped once a device has stayed at or farther than this
+    /// state for the stop ranging delay
+    pub stop_ranging_state: ProximityState,
+    /// Time in milliseconds a device must stay at or farther than the stop
+    /// ranging state before ranging is stopped
+    pub stop_ranging_delay_millis: u64,
+}
+
+impl Default for HandOffPolicy {
+    fn default() -> Self {
+        HandOffPolicy {
+            start_ranging_state: ProximityState::Reach,
+            stop_ranging_state: ProximityState::Far,
+            stop_ranging_delay_millis: DEFAULT_STOP_RANGING_DELAY_MILLIS,
+        }
+    }
+}
+
+/// Recommendation to change the UWB ranging session of a device
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum HandOffRecommendation {
+    /// Start UWB ranging with the device
+    StartRanging {
+        /// Device ID of the nearby device
+        device_id: u64,
+    },
+    /// Stop UWB ranging with the device
+    StopRanging {
+        /// Device ID of the nearby device
+        device_id: u64,
+    },
+}
+
+/// Drives UWB power management from proximity estimates, recommending to start
+/// ranging when a device comes close and to stop once it stays far away
+pub struct HandOffController {
+    policy: HandOffPolicy,
+    // Ranging devices, with the time since which they are at or farther than
+    // the stop ranging state
+    stop_pending_since_millis_per_device: Map<u64, Option<u64>>,
+}
+
+impl HandOffController {
+    /// Creates a new controller applying the given policy
+    pub fn new(policy: HandOffPolicy) -> Self {
+        HandOffController { policy, stop_pending_since_millis_per_device: Map::new() }
+    }
+
+    /// Feeds a new proximity estimate of a device, e.g. one returned by the
+    /// presence detector, and returns a recommendation if the device's ranging
+    /// session should change
+    pub fn on_proximity_estimate(
+        &mut self,
+        proximity_estimate: &ProximityEstimate,
+    ) -> Option<HandOffRecommendation> {
+        let device_id = proximity_estimate.device_id;
+        let zone_index = get_zone_index_of_state(proximity_estimate.proximity_state)?;
+        let Some(stop_pending_since_millis) =
+            self.stop_pending_since_millis_per_device.get_mut(&device_id)
+        else {
+            let start_zone_index = get_zone_index_of_state(self.policy.start_ranging_state)?;
+            if zone_index > start_zone_index {
+                return None;
+            }
+            self.stop_pending_since_millis_per_device.insert(device_id, None);
+            return Some(HandOffRecommendation::StartRanging { device_id });
+        };
+        let stop_zone_index = get_zone_index_of_state(self.policy.stop_ranging_state)?;
+        if zone_index < stop_zone_index {
+            *stop_pending_since_millis = None;
+            return None;
+        }
+        let elapsed_real_time_millis = proximity_estimate.elapsed_real_time_millis;
+        let since_millis = *stop_pending_since_millis.get_or_insert(elapsed_real_time_millis);
+        if elapsed_real_time_millis.saturating_sub(since_millis)
+            < self.policy.stop_ranging_delay_millis
+        {
+            return None;
+        }
+        self.stop_pending_since_millis_per_device.remove(&device_id);
+        Some(HandOffRecommendation::StopRanging { device_id })
+    }
+
+    /// Notifies the controller that a device was lost, returning a
+    /// recommendation to stop ranging if the device was ranging
+    pub fn on_device_lost(&mut self, device_id: u64) -> Option<HandOffRecommendation> {
+        self.stop_pending_since_millis_per_device
+            .remove(&device_id)
+            .map(|_| HandOffRecommendation::StopRanging { device_id })
+    }
+
+    /// Returns whether ranging with a device is currently recommended
+    pub fn is_ranging(&self, device_id: u64) -> bool {
+        self.stop_pending_since_millis_per_device.contains_key(&device_id)
+    }
+}
+
+impl Default for HandOffController {
+    fn default() -> Self {
+        Self::new(HandOffPolicy::default())
+    }
+}
@@ -0,0 +1,113 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::fused_presence_utils::*;
+use crate::hand_off_controller::*;
+
+const FAR_PROXIMITY_ESTIMATE: ProximityEstimate = ProximityEstimate {
+    device_id: 1234,
+    distance_meters: 5.0,
+    distance_confidence: MeasurementConfidence::Low,
+    elapsed_real_time_millis: 0,
+    proximity_state: ProximityState::Far,
+    source: PresenceDataSource::Ble,
+};
+
+fn estimate_at(
+    proximity_state: ProximityState,
+    elapsed_real_time_millis: u64,
+) -> ProximityEstimate {
+    ProximityEstimate {
+        proximity_state,
+        elapsed_real_time_millis,
+        ..FAR_PROXIMITY_ESTIMATE
+    }
+}
+
+#[test]
+fn test_start_ranging_when_device_reaches_start_state() {
+    let mut hand_off_controller = HandOffController::default();
+    assert_eq!(
+        hand_off_controller.on_proximity_estimate(&estimate_at(ProximityState::ShortRange, 0)),
+        None
+    );
+    assert_eq!(
+        hand_off_controller.on_proximity_estimate(&estimate_at(ProximityState::Reach, 100)),
+        Some(HandOffRecommendation::StartRanging { device_id: 1234 })
+    );
+    assert!(hand_off_controller.is_ranging(1234));
+    assert_eq!(
+        hand_off_controller.on_proximity_estimate(&estimate_at(ProximityState::Tap, 200)),
+        None
+    );
+}
+
+#[test]
+fn test_stop_ranging_after_delay_at_stop_state() {
+    let mut hand_off_controller = HandOffController::new(HandOffPolicy {
+        stop_ranging_delay_millis: 1000,
+        ..HandOffPolicy::default()
+    });
+    hand_off_controller.on_proximity_estimate(&estimate_at(ProximityState::Reach, 0));
+    assert_eq!(
+        hand_off_controller.on_proximity_estimate(&estimate_at(ProximityState::Far, 100)),
+        None
+    );
+    assert_eq!(
+        hand_off_controller.on_proximity_estimate(&estimate_at(ProximityState::Far, 600)),
+        None
+    );
+    assert_eq!(
+        hand_off_controller.on_proximity_estimate(&estimate_at(ProximityState::Far, 1100)),
+        Some(HandOffRecommendation::StopRanging { device_id: 1234 })
+    );
+    assert!(!hand_off_controller.is_ranging(1234));
+}
+
+#[test]
+fn test_coming_closer_resets_stop_delay() {
+    let mut hand_off_controller = HandOffController::new(HandOffPolicy {
+        stop_ranging_delay_millis: 1000,
+        ..HandOffPolicy::default()
+    });
+    hand_off_controller.on_proximity_estimate(&estimate_at(ProximityState::Reach, 0));
+    hand_off_controller.on_proximity_estimate(&estimate_at(ProximityState::Far, 100));
+    hand_off_controller.on_proximity_estimate(&estimate_at(ProximityState::LongRange, 600));
+    assert_eq!(
+        hand_off_controller.on_proximity_estimate(&estimate_at(ProximityState::Far, 1100)),
+        None
+    );
+    assert!(hand_off_controller.is_ranging(1234));
+}
+
+#[test]
+fn test_unknown_state_is_ignored() {
+    let mut hand_off_controller = HandOffController::default();
+    assert_eq!(
+        hand_off_controller.on_proximity_estimate(&estimate_at(ProximityState::Unknown, 0)),
+        None
+    );
+    assert!(!hand_off_controller.is_ranging(1234));
+}
+
+#[test]
+fn test_stop_ranging_when_device_lost() {
+    let mut hand_off_controller = HandOffController::default();
+    assert_eq!(hand_off_controller.on_device_lost(1234), None);
+    hand_off_controller.on_proximity_estimate(&estimate_at(ProximityState::Tap, 0));
+    assert_eq!(
+        hand_off_controller.on_device_lost(1234),
+        Some(HandOffRecommendation::StopRanging { device_id: 1234 })
+    );
+}
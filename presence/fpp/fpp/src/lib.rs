@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#![cfg_attr(not(feature = "std"), no_std)]
 #![deny(
     missing_docs,
     clippy::indexing_slicing,
@@ -22,16 +23,82 @@
 
 //! Processes raw scan results from BLE, UWB and NAN and outputs proximity estimates/zones
 
+extern crate alloc;
+
+/// Calibration of known device models
+pub mod calibration;
+
+/// Time sources used for expiry computations
+pub mod clock;
+
+/// Serializable presence detector state
+pub mod detector_state;
+
+mod collections;
+
+mod device_proximity_data;
+
+mod distance_filter;
+
 mod fspl_converter;
 
+mod math;
+
+mod ring_buffer;
+
+mod rssi_filter;
+
+mod rssi_outlier_filter;
+
+mod tx_power_estimator;
+
+mod zone_tracker;
+
 /// Fused presence Utils
 pub mod fused_presence_utils;
 
+/// BLE to UWB hand-off recommendations
+pub mod hand_off_controller;
+
 /// Presence detector module
 pub mod presence_detector;
 
+/// Tokio-based Sink/Stream wrapper around the presence detector
+#[cfg(feature = "tokio")]
+pub mod presence_stream;
+
+#[cfg(test)]
+mod calibration_test;
+
+#[cfg(test)]
+mod device_proximity_data_test;
+
+#[cfg(test)]
+mod distance_filter_test;
+
 #[cfg(test)]
 mod fspl_converter_test;
 
+#[cfg(test)]
+mod hand_off_controller_test;
+
 #[cfg(test)]
 mod presence_detector_test;
+
+#[cfg(all(test, feature = "tokio"))]
+mod presence_stream_test;
+
+#[cfg(test)]
+mod ring_buffer_test;
+
+#[cfg(test)]
+mod rssi_filter_test;
+
+#[cfg(test)]
+mod rssi_outlier_filter_test;
+
+#[cfg(test)]
+mod tx_power_estimator_test;
+
+#[cfg(test)]
+mod zone_tracker_test;
@@ -0,0 +1,40 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Floating point functions that are only provided by std, backed by libm
+//! without it
+
+#[cfg(feature = "std")]
+pub(crate) fn powf(base: f64, exponent: f64) -> f64 {
+    base.powf(exponent)
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn powf(base: f64, exponent: f64) -> f64 {
+    libm::pow(base, exponent)
+}
+
+#[cfg(feature = "std")]
+pub(crate) fn round(value: f64) -> f64 {
+    value.round()
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) fn round(value: f64) -> f64 {
+    libm::round(value)
+}
+
+pub(crate) fn square(value: f64) -> f64 {
+    value * value
+}
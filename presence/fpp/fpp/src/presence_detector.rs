@@ -12,118 +12,820 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::{HashMap, VecDeque};
-use std::time::{Instant, SystemTime};
-
 use itertools::Itertools;
 
-use crate::fspl_converter::compute_distance_meters_at_high_tx_power;
+use crate::calibration::{CalibrationTable, ChannelRssiCorrection, TxPowerModel};
+use crate::clock::Clock;
+#[cfg(feature = "std")]
+use crate::clock::SystemClock;
+use crate::collections::{Box, Map, Vec, VecDeque};
+use crate::detector_state::{AgedProximityEstimate, DeviceState, PresenceDetectorState};
+use crate::device_proximity_data::{get_proximity_state_from_threshold, DeviceProximityData};
+use crate::fspl_converter::compute_distance_meters_at_tx_power;
 use crate::fused_presence_utils::{
-    BleScanResult, MaybeTxPower, MeasurementConfidence, PresenceDataSource, ProximityEstimate,
-    ProximityState, DEFAULT_CONSECUTIVE_SCANS_REQUIRED,
-    DEFAULT_LONG_RANGE_DISTANCE_THRESHOLD_METERS, DEFAULT_REACH_DISTANCE_THRESHOLD_METERS,
-    DEFAULT_SHORT_RANGE_DISTANCE_THRESHOLD_METERS, DEFAULT_TAP_DISTANCE_THRESHOLD_METERS,
+    BleScanResult, CustomZone, EnvironmentProfile, FusionPolicy, FusionStrategy,
+    MeasurementConfidence, MotionEstimate, PresenceDataSource, ProximityEstimate, ProximityState,
+    ProximityStateOptions, RadialMotion,
 };
+use crate::math::square;
+use crate::zone_tracker::ZoneTracker;
 
 const MAX_RSSI_FILTER_VALUE: i32 = 10;
-const DEFAULT_ESTIMATED_DISTANCE_DATA_TTL_MILLIS: u128 = 4000;
+const DEFAULT_DEVICE_TTL_MILLIS: u64 = 30000;
+const MAX_ESTIMATE_HISTORY_SIZE: usize = 64;
+const SOURCE_COUNT: usize = 3;
+const MOTION_ESTIMATE_WINDOW_SIZE: usize = 8;
+const STATIONARY_SPEED_METERS_PER_SECOND: f64 = 0.1;
 
-/// Static function for getting proximity state from threshold
-fn get_proximity_state_from_threshold(distance_meters: f64) -> ProximityState {
-    if distance_meters <= DEFAULT_TAP_DISTANCE_THRESHOLD_METERS {
-        return ProximityState::Tap;
-    }
-    if distance_meters <= DEFAULT_REACH_DISTANCE_THRESHOLD_METERS {
-        return ProximityState::Reach;
-    }
-    if distance_meters <= DEFAULT_SHORT_RANGE_DISTANCE_THRESHOLD_METERS {
-        return ProximityState::ShortRange;
+/// Static function for getting the weight of an estimate when fusing by confidence
+fn get_weight_from_confidence(confidence: MeasurementConfidence) -> f64 {
+    match confidence {
+        MeasurementConfidence::High => 4.0,
+        MeasurementConfidence::Medium => 2.0,
+        MeasurementConfidence::Low => 1.0,
+        MeasurementConfidence::Unknown => 0.5,
     }
-    if distance_meters <= DEFAULT_LONG_RANGE_DISTANCE_THRESHOLD_METERS {
-        return ProximityState::LongRange;
-    }
-    ProximityState::Far
 }
 
-/// Tracks and computes proximity/presence state events.
-pub struct PresenceDetector {
-    start_time: Instant,
-    last_range_update_time: RangingUpdateTime,
-    best_proximity_estimate_per_device: HashMap<u64, ProximityEstimate>,
-    transition_history: VecDeque<ProximityState>,
-}
+/// Receives confirmed proximity zone transitions from a presence detector
+pub trait ProximityStateListener: Send {
+    /// Called when the reported proximity state of a device changes
+    fn on_zone_changed(
+        &mut self,
+        device_id: u64,
+        old_state: ProximityState,
+        new_state: ProximityState,
+        proximity_estimate: ProximityEstimate,
+    );
 
-struct RangingUpdateTime(u128);
+    /// Called when a device is evicted after not being seen by any data source
+    /// for longer than the device TTL
+    fn on_device_lost(&mut self, _device_id: u64) {}
 
-impl RangingUpdateTime {
-    pub fn is_expired(&self) -> bool {
-        let elapsed_real_time_millis = Instant::now().elapsed().as_millis();
-        elapsed_real_time_millis - self.0 > DEFAULT_ESTIMATED_DISTANCE_DATA_TTL_MILLIS
+    /// Called when the confirmed custom zone of a device changes, a zone ID of
+    /// None standing for a device beyond every custom zone
+    fn on_custom_zone_changed(
+        &mut self,
+        _device_id: u64,
+        _old_zone_id: Option<u32>,
+        _new_zone_id: Option<u32>,
+        _proximity_estimate: ProximityEstimate,
+    ) {
     }
+}
 
-    pub fn update(&mut self, start_time: Instant) {
-        self.0 = Instant::now().duration_since(start_time).as_millis();
-    }
+/// Tracks and computes proximity/presence state events.
+pub struct PresenceDetector {
+    clock: Box<dyn Clock>,
+    proximity_data_per_source: Map<(u64, PresenceDataSource), DeviceProximityData>,
+    proximity_estimate_per_source: Map<(u64, PresenceDataSource), ProximityEstimate>,
+    fusion_policy: FusionPolicy,
+    options: ProximityStateOptions,
+    options_per_device: Map<u64, ProximityStateOptions>,
+    reported_state_per_device: Map<u64, ProximityState>,
+    listener: Option<Box<dyn ProximityStateListener>>,
+    last_seen_millis_per_device: Map<u64, u64>,
+    device_ttl_millis: u64,
+    calibration_table: CalibrationTable,
+    channel_rssi_correction: ChannelRssiCorrection,
+    tx_power_model: TxPowerModel,
+    model_id_per_device: Map<u64, u32>,
+    environment_profile: EnvironmentProfile,
+    estimate_history_per_device: Map<u64, VecDeque<ProximityEstimate>>,
+    custom_zones: Vec<CustomZone>,
+    custom_zone_upper_bounds_meters: Vec<f64>,
+    custom_zone_tracker_per_device: Map<u64, ZoneTracker>,
 }
 
 impl PresenceDetector {
     /// Creates a new instance of presence detector
+    #[cfg(feature = "std")]
     pub fn new() -> Self {
+        Self::with_options(ProximityStateOptions::default())
+    }
+
+    /// Creates a new instance of presence detector using the given options for
+    /// zone thresholds, hysteresis and filtering
+    #[cfg(feature = "std")]
+    pub fn with_options(options: ProximityStateOptions) -> Self {
+        Self::with_clock(options, Box::new(SystemClock::new()))
+    }
+
+    /// Creates a new instance of presence detector using the given options and
+    /// clock for all TTL and expiry computations
+    pub fn with_clock(options: ProximityStateOptions, clock: Box<dyn Clock>) -> Self {
         PresenceDetector {
-            start_time: Instant::now(),
-            last_range_update_time: RangingUpdateTime(0),
-            best_proximity_estimate_per_device: HashMap::new(),
-            transition_history: VecDeque::with_capacity(
-                (DEFAULT_CONSECUTIVE_SCANS_REQUIRED + 1).into(),
-            ),
+            clock,
+            proximity_data_per_source: Map::new(),
+            proximity_estimate_per_source: Map::new(),
+            fusion_policy: FusionPolicy::default(),
+            options,
+            options_per_device: Map::new(),
+            reported_state_per_device: Map::new(),
+            listener: None,
+            last_seen_millis_per_device: Map::new(),
+            device_ttl_millis: DEFAULT_DEVICE_TTL_MILLIS,
+            calibration_table: CalibrationTable::new(),
+            channel_rssi_correction: ChannelRssiCorrection::default(),
+            tx_power_model: TxPowerModel::default(),
+            model_id_per_device: Map::new(),
+            environment_profile: EnvironmentProfile::FreeSpace,
+            estimate_history_per_device: Map::new(),
+            custom_zones: Vec::new(),
+            custom_zone_upper_bounds_meters: Vec::new(),
+            custom_zone_tracker_per_device: Map::new(),
         }
     }
 
     /// Updates the presence detector with a new scan result and returns the
-    /// current proximity estimate
+    /// current proximity estimate. The scan result's timestamp is used for the
+    /// device's TTL, hysteresis and estimate history, so that delayed batches
+    /// of scan results are processed at the time they were obtained. Scan
+    /// results older than the device TTL or than a previously processed scan
+    /// result of the same device are ignored.
     pub fn on_ble_scan_result(
         &mut self,
         ble_scan_result: BleScanResult,
     ) -> Option<ProximityEstimate> {
-        let device_id = ble_scan_result.device_id;
-        if ble_scan_result.rssi > MAX_RSSI_FILTER_VALUE {
-            return self.best_proximity_estimate_per_device.get(&device_id).copied();
+        self.on_scan_result(PresenceDataSource::Ble, ble_scan_result)
+    }
+
+    /// Updates the presence detector with a new scan result of a data source
+    /// reporting signal strength, e.g. a NAN discovery result, and returns the
+    /// current proximity estimate. Scan results are processed as BLE scan
+    /// results are, with filters and zone state kept per data source, and
+    /// scan results without an advertised tx power use the default tx power of
+    /// their data source. The advertising channel and model calibration only
+    /// apply to BLE scan results.
+    pub fn on_scan_result(
+        &mut self,
+        source: PresenceDataSource,
+        scan_result: BleScanResult,
+    ) -> Option<ProximityEstimate> {
+        let device_id = scan_result.device_id;
+        let elapsed_real_time_millis =
+            self.get_scan_time_millis(scan_result.elapsed_real_time_millis);
+        self.evict_devices_stale_at(self.elapsed_real_time_millis());
+        if !self.is_within_ttl(elapsed_real_time_millis) {
+            return self.get_proximity_estimate(device_id);
+        }
+        self.mark_device_seen(device_id, elapsed_real_time_millis);
+        if scan_result.rssi > MAX_RSSI_FILTER_VALUE {
+            return self.get_proximity_estimate(device_id);
         }
-        if self.last_range_update_time.is_expired() {
-            self.transition_history.clear();
+        let options = self.options_per_device.get(&device_id).unwrap_or(&self.options);
+        let device_proximity_data = self
+            .proximity_data_per_source
+            .entry((device_id, source))
+            .or_insert_with(|| DeviceProximityData::new(options));
+        let channel_corrected_rssi = match source {
+            PresenceDataSource::Ble => self
+                .channel_rssi_correction
+                .apply(scan_result.advertising_channel, scan_result.rssi),
+            _ => scan_result.rssi,
+        };
+        if device_proximity_data.is_out_of_order_scan(elapsed_real_time_millis)
+            || device_proximity_data.is_rssi_outlier(channel_corrected_rssi)
+        {
+            return self.get_proximity_estimate(device_id);
+        }
+        let tx_power_dbm = device_proximity_data.estimate_tx_power(
+            scan_result.tx_power,
+            source,
+            &self.tx_power_model,
+        );
+        let mut rssi = device_proximity_data.filter_rssi(channel_corrected_rssi);
+        if let (PresenceDataSource::Ble, Some(model_id)) =
+            (source, self.model_id_per_device.get(&device_id))
+        {
+            rssi = self.calibration_table.apply(*model_id, rssi);
         }
-        let mut tx_power: i32 = 0;
-        if let MaybeTxPower::Valid(some_tx_power) = ble_scan_result.tx_power {
-            tx_power = some_tx_power;
+        let distance_meters = device_proximity_data.filter_distance(
+            compute_distance_meters_at_tx_power(rssi, tx_power_dbm, &self.environment_profile),
+        );
+        if let Some(proximity_state) = device_proximity_data.update_current_proximity_state(
+            distance_meters,
+            elapsed_real_time_millis,
+            options,
+        ) {
+            let new_proximity_estimate = ProximityEstimate {
+                device_id,
+                distance_confidence: device_proximity_data.get_measurement_confidence(),
+                distance_meters,
+                proximity_state,
+                elapsed_real_time_millis,
+                source,
+            };
+            self.proximity_estimate_per_source.insert((device_id, source), new_proximity_estimate);
         }
-        let rssi = ble_scan_result.rssi + tx_power;
-        let distance_meters = compute_distance_meters_at_high_tx_power(rssi);
+        self.publish_proximity_estimate(device_id)
+    }
+
+    /// Updates the presence detector with a new UWB ranging result and returns
+    /// the current proximity estimate. With the default fusion policy, UWB
+    /// estimates take precedence over BLE estimates for the same device for as
+    /// long as they are fresh.
+    pub fn on_uwb_ranging_result(
+        &mut self,
+        device_id: u64,
+        distance_meters: f64,
+        confidence: MeasurementConfidence,
+    ) -> Option<ProximityEstimate> {
+        self.on_ranging_result(PresenceDataSource::Uwb, device_id, distance_meters, confidence)
+    }
+
+    /// Updates the presence detector with a new NAN (WiFi Aware) ranging result
+    /// and returns the current proximity estimate. With the default fusion
+    /// policy, fresh NAN estimates take precedence over BLE estimates, but not
+    /// over fresh UWB estimates.
+    pub fn on_nan_ranging_result(
+        &mut self,
+        device_id: u64,
+        distance_meters: f64,
+    ) -> Option<ProximityEstimate> {
+        self.on_ranging_result(
+            PresenceDataSource::Nan,
+            device_id,
+            distance_meters,
+            MeasurementConfidence::Medium,
+        )
+    }
+
+    /// Replaces the detector-wide options, resetting the filters and zone
+    /// state of every device without an options override
+    pub fn set_options(&mut self, options: ProximityStateOptions) {
+        self.options = options;
+        let options_per_device = &self.options_per_device;
+        self.proximity_data_per_source
+            .retain(|(device_id, _), _| options_per_device.contains_key(device_id));
+    }
+
+    /// Overrides the detector-wide options for a given device, e.g. with
+    /// thresholds calibrated for a known device model. The device's filter and
+    /// hysteresis state is reset so that the new options apply from the next
+    /// scan result
+    pub fn set_device_options(&mut self, device_id: u64, options: ProximityStateOptions) {
+        self.options_per_device.insert(device_id, options);
+        self.clear_device_proximity_data(device_id);
+    }
+
+    /// Clears the options override for a given device, reverting it to the
+    /// detector-wide options
+    pub fn clear_device_options(&mut self, device_id: u64) {
+        if self.options_per_device.remove(&device_id).is_some() {
+            self.clear_device_proximity_data(device_id);
+        }
+    }
+
+    /// Stops tracking a device, e.g. when it disconnected or was dismissed,
+    /// clearing its estimates, transition history, options override and model
+    pub fn remove_device(&mut self, device_id: u64) {
+        self.clear_device_state(device_id);
+        self.options_per_device.remove(&device_id);
+        self.model_id_per_device.remove(&device_id);
+    }
+
+    /// Clears the estimates, filters and timers of every tracked device, e.g.
+    /// when Bluetooth is toggled or the scan session restarts. Configuration,
+    /// including per-device options overrides and models, is kept.
+    pub fn reset(&mut self) {
+        self.proximity_data_per_source.clear();
+        self.proximity_estimate_per_source.clear();
+        self.reported_state_per_device.clear();
+        self.last_seen_millis_per_device.clear();
+        self.estimate_history_per_device.clear();
+        self.custom_zone_tracker_per_device.clear();
+    }
+
+    /// Sets user-defined zones reported in addition to the built-in proximity
+    /// states, replacing any previous custom zones. Zones are ordered by their
+    /// upper bounds, and the same hysteresis and consecutive scan options as for
+    /// the built-in states apply to their boundaries.
+    pub fn set_custom_zones(&mut self, mut custom_zones: Vec<CustomZone>) {
+        custom_zones.sort_by(|zone, other_zone| {
+            zone.upper_bound_meters.total_cmp(&other_zone.upper_bound_meters)
+        });
+        self.custom_zone_upper_bounds_meters =
+            custom_zones.iter().map(|custom_zone| custom_zone.upper_bound_meters).collect();
+        self.custom_zones = custom_zones;
+        self.custom_zone_tracker_per_device.clear();
+    }
+
+    /// Returns the ID of the confirmed custom zone of a device, None if the
+    /// device is not tracked, no zone is confirmed yet or the device is beyond
+    /// every custom zone
+    pub fn get_custom_zone(&self, device_id: u64) -> Option<u32> {
+        if !self.is_tracked(device_id) {
+            return None;
+        }
+        self.get_confirmed_custom_zone(device_id)
+    }
+
+    /// Sets the table of per-model corrections applied to BLE scan results
+    pub fn set_calibration_table(&mut self, calibration_table: CalibrationTable) {
+        self.calibration_table = calibration_table;
+    }
+
+    /// Sets the per-channel offsets applied to the RSSI of BLE scan results
+    /// received on a known advertising channel, before any filtering
+    pub fn set_channel_rssi_correction(&mut self, channel_rssi_correction: ChannelRssiCorrection) {
+        self.channel_rssi_correction = channel_rssi_correction;
+    }
+
+    /// Sets the tx power assumptions used to convert the signal strength of BLE
+    /// scan results into distance
+    pub fn set_tx_power_model(&mut self, tx_power_model: TxPowerModel) {
+        self.tx_power_model = tx_power_model;
+    }
+
+    /// Returns the tx power in dBm learned from the advertised tx power of a
+    /// device's BLE scan results, None if the device has not advertised one
+    pub fn get_tx_power_estimate(&self, device_id: u64) -> Option<i32> {
+        self.proximity_data_per_source
+            .get(&(device_id, PresenceDataSource::Ble))?
+            .get_tx_power_estimate()
+    }
+
+    /// Associates a device with its model so that the model's calibration
+    /// entry is applied to the device's scan results
+    pub fn set_device_model(&mut self, device_id: u64, model_id: u32) {
+        self.model_id_per_device.insert(device_id, model_id);
+    }
+
+    /// Sets the path loss model used to convert BLE signal strength into
+    /// distance, free space by default
+    pub fn set_environment_profile(&mut self, environment_profile: EnvironmentProfile) {
+        self.environment_profile = environment_profile;
+    }
+
+    /// Sets the time in milliseconds after which devices that have not been
+    /// seen by any data source are evicted along with their estimates
+    pub fn set_device_ttl_millis(&mut self, device_ttl_millis: u64) {
+        self.device_ttl_millis = device_ttl_millis;
+    }
+
+    /// Evicts the devices that have not been seen within the device TTL,
+    /// notifying the listener, and returns their IDs. Stale devices are also
+    /// evicted whenever a new measurement arrives; this allows clients to learn
+    /// about lost devices while no measurements arrive at all.
+    pub fn evict_stale_devices(&mut self) -> Vec<u64> {
+        let elapsed_real_time_millis = self.elapsed_real_time_millis();
+        self.evict_devices_stale_at(elapsed_real_time_millis)
+    }
+
+    /// Returns the IDs of the devices seen within the device TTL
+    pub fn get_tracked_devices(&self) -> Vec<u64> {
+        self.last_seen_millis_per_device
+            .keys()
+            .copied()
+            .filter(|device_id| self.is_tracked(*device_id))
+            .sorted()
+            .collect()
+    }
+
+    /// Sets the listener notified of confirmed zone transitions, replacing any
+    /// previously set listener
+    pub fn set_proximity_state_listener(&mut self, listener: Box<dyn ProximityStateListener>) {
+        self.listener = Some(listener);
+    }
+
+    /// Removes the listener notified of confirmed zone transitions
+    pub fn clear_proximity_state_listener(&mut self) {
+        self.listener = None;
+    }
+
+    /// Sets the policy used to fuse estimates from different data sources
+    pub fn set_fusion_policy(&mut self, fusion_policy: FusionPolicy) {
+        self.fusion_policy = fusion_policy;
+    }
+
+    /// Returns the current proximity estimate for a given device, fused from
+    /// the estimates of all data sources according to the fusion policy
+    pub fn get_proximity_estimate(&self, device_id: u64) -> Option<ProximityEstimate> {
+        if !self.is_tracked(device_id) {
+            return None;
+        }
+        let source_precedence = &self.fusion_policy.source_precedence;
+        // Collected on the stack, this is called for every scan result
+        let mut estimates = [None; SOURCE_COUNT];
+        for ((index, source), estimate) in
+            source_precedence.iter().enumerate().zip(estimates.iter_mut())
+        {
+            // Sources listed more than once only count at their highest precedence
+            if !source_precedence.iter().take(index).any(|s| s == source) {
+                *estimate = self.proximity_estimate_per_source.get(&(device_id, *source));
+            }
+        }
+        let estimates = estimates.iter().flatten().copied();
+        let fresh_estimates = estimates.clone().filter(|estimate| self.is_fresh(estimate));
+        match (self.fusion_policy.strategy, fresh_estimates.clone().next()) {
+            (_, None) => {
+                estimates.max_by_key(|estimate| estimate.elapsed_real_time_millis).copied()
+            }
+            (FusionStrategy::Precedence, Some(best_estimate)) => Some(*best_estimate),
+            (FusionStrategy::ConfidenceWeighted, _) => {
+                self.fuse_by_confidence(device_id, fresh_estimates)
+            }
+        }
+    }
+
+    /// Returns up to `max_entries` of the most recent distinct proximity
+    /// estimates reported for a given device, oldest first
+    pub fn get_estimate_history(
+        &self,
+        device_id: u64,
+        max_entries: usize,
+    ) -> Vec<ProximityEstimate> {
+        self.estimate_history_per_device
+            .get(&device_id)
+            .map(|estimate_history| {
+                estimate_history
+                    .iter()
+                    .skip(estimate_history.len().saturating_sub(max_entries))
+                    .copied()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the radial velocity of a given device, computed as the least
+    /// squares slope of its recent distance history
+    pub fn get_motion_estimate(&self, device_id: u64) -> Option<MotionEstimate> {
+        let estimate_history = self.get_estimate_history(device_id, MOTION_ESTIMATE_WINDOW_SIZE);
+        let latest_estimate = estimate_history.last()?;
+        let first_estimate = estimate_history.first()?;
+        let sample_count = estimate_history.len() as f64;
+        // Times are relative to the first estimate to keep the sums small
+        let samples = estimate_history.iter().map(|estimate| {
+            let seconds = estimate
+                .elapsed_real_time_millis
+                .saturating_sub(first_estimate.elapsed_real_time_millis)
+                as f64
+                / 1000.0;
+            (seconds, estimate.distance_meters)
+        });
+        let mean_seconds = samples.clone().map(|(seconds, _)| seconds).sum::<f64>() / sample_count;
+        let mean_distance =
+            samples.clone().map(|(_, distance)| distance).sum::<f64>() / sample_count;
+        let (covariance, time_variance) =
+            samples.fold((0.0, 0.0), |(covariance, time_variance), (seconds, distance)| {
+                (
+                    covariance + (seconds - mean_seconds) * (distance - mean_distance),
+                    time_variance + square(seconds - mean_seconds),
+                )
+            });
+        if time_variance <= 0.0 {
+            return None;
+        }
+        let radial_velocity_meters_per_second = covariance / time_variance;
+        let radial_motion =
+            if radial_velocity_meters_per_second < -STATIONARY_SPEED_METERS_PER_SECOND {
+                RadialMotion::Approaching
+            } else if radial_velocity_meters_per_second > STATIONARY_SPEED_METERS_PER_SECOND {
+                RadialMotion::Retreating
+            } else {
+                RadialMotion::Stationary
+            };
+        Some(MotionEstimate {
+            device_id,
+            radial_velocity_meters_per_second,
+            radial_motion,
+            elapsed_real_time_millis: latest_estimate.elapsed_real_time_millis,
+        })
+    }
+
+    /// Exports the per-device state of the detector, with timestamps relative
+    /// to the current time, so that it can be persisted across restarts
+    pub fn export_state(&self) -> PresenceDetectorState {
+        let elapsed_real_time_millis = self.elapsed_real_time_millis();
+        let to_aged_estimate = |estimate: &ProximityEstimate| AgedProximityEstimate {
+            age_millis: elapsed_real_time_millis.saturating_sub(estimate.elapsed_real_time_millis),
+            estimate: *estimate,
+        };
+        let devices = self
+            .get_tracked_devices()
+            .into_iter()
+            .map(|device_id| {
+                let (current_proximity_state, transition_history, last_update_millis) = self
+                    .proximity_data_per_source
+                    .get(&(device_id, PresenceDataSource::Ble))
+                    .map(DeviceProximityData::export_hysteresis_state)
+                    .unwrap_or((ProximityState::Unknown, Vec::new(), None));
+                DeviceState {
+                    device_id,
+                    last_seen_age_millis: self.last_seen_millis_per_device.get(&device_id).map_or(
+                        0,
+                        |last_seen_millis| {
+                            elapsed_real_time_millis.saturating_sub(*last_seen_millis)
+                        },
+                    ),
+                    estimates: self
+                        .proximity_estimate_per_source
+                        .iter()
+                        .filter(|((estimate_device_id, _), _)| *estimate_device_id == device_id)
+                        .map(|(_, estimate)| to_aged_estimate(estimate))
+                        .collect(),
+                    estimate_history: self
+                        .estimate_history_per_device
+                        .get(&device_id)
+                        .map(|estimate_history| {
+                            estimate_history.iter().map(to_aged_estimate).collect()
+                        })
+                        .unwrap_or_default(),
+                    current_proximity_state,
+                    transition_history,
+                    transition_history_age_millis: last_update_millis.map(|last_update_millis| {
+                        elapsed_real_time_millis.saturating_sub(last_update_millis)
+                    }),
+                }
+            })
+            .collect();
+        PresenceDetectorState { devices }
+    }
+
+    /// Imports previously exported state, replacing the state of the devices it
+    /// contains. Timestamps are rebased onto this detector's clock, and devices
+    /// whose TTL has expired in the meantime are skipped
+    pub fn import_state(&mut self, state: PresenceDetectorState) {
+        let elapsed_real_time_millis = self.elapsed_real_time_millis();
+        let to_estimate = |aged_estimate: &AgedProximityEstimate| ProximityEstimate {
+            elapsed_real_time_millis: elapsed_real_time_millis
+                .saturating_sub(aged_estimate.age_millis),
+            ..aged_estimate.estimate
+        };
+        for device_state in state.devices {
+            let device_id = device_state.device_id;
+            if device_state.last_seen_age_millis > self.device_ttl_millis {
+                continue;
+            }
+            self.clear_device_state(device_id);
+            self.last_seen_millis_per_device.insert(
+                device_id,
+                elapsed_real_time_millis.saturating_sub(device_state.last_seen_age_millis),
+            );
+            for aged_estimate in &device_state.estimates {
+                let estimate = ProximityEstimate { device_id, ..to_estimate(aged_estimate) };
+                self.proximity_estimate_per_source.insert((device_id, estimate.source), estimate);
+            }
+            self.estimate_history_per_device.insert(
+                device_id,
+                device_state
+                    .estimate_history
+                    .iter()
+                    .rev()
+                    .take(MAX_ESTIMATE_HISTORY_SIZE)
+                    .rev()
+                    .map(|aged_estimate| ProximityEstimate {
+                        device_id,
+                        ..to_estimate(aged_estimate)
+                    })
+                    .collect(),
+            );
+            let mut device_proximity_data =
+                DeviceProximityData::new(self.get_device_options(device_id));
+            device_proximity_data.import_hysteresis_state(
+                device_state.current_proximity_state,
+                &device_state.transition_history,
+                device_state.transition_history_age_millis.map(|transition_history_age_millis| {
+                    elapsed_real_time_millis.saturating_sub(transition_history_age_millis)
+                }),
+            );
+            self.proximity_data_per_source
+                .insert((device_id, PresenceDataSource::Ble), device_proximity_data);
+            if let Some(proximity_estimate) = self.get_proximity_estimate(device_id) {
+                self.reported_state_per_device
+                    .insert(device_id, proximity_estimate.proximity_state);
+            }
+        }
+    }
+
+    fn fuse_by_confidence<'a>(
+        &self,
+        device_id: u64,
+        estimates: impl DoubleEndedIterator<Item = &'a ProximityEstimate> + Clone,
+    ) -> Option<ProximityEstimate> {
+        // Ties are resolved in favor of the source with the highest precedence
+        let most_confident_estimate = estimates.clone().rev().max_by(|a, b| {
+            get_weight_from_confidence(a.distance_confidence)
+                .total_cmp(&get_weight_from_confidence(b.distance_confidence))
+        })?;
+        let total_weight: f64 = estimates
+            .clone()
+            .map(|estimate| get_weight_from_confidence(estimate.distance_confidence))
+            .sum();
+        let distance_meters = estimates
+            .clone()
+            .map(|estimate| {
+                get_weight_from_confidence(estimate.distance_confidence) * estimate.distance_meters
+            })
+            .sum::<f64>()
+            / total_weight;
+        Some(ProximityEstimate {
+            device_id,
+            distance_meters,
+            distance_confidence: most_confident_estimate.distance_confidence,
+            elapsed_real_time_millis: estimates
+                .map(|estimate| estimate.elapsed_real_time_millis)
+                .max()
+                .unwrap_or_default(),
+            proximity_state: get_proximity_state_from_threshold(
+                distance_meters,
+                self.get_device_options(device_id),
+            ),
+            source: most_confident_estimate.source,
+        })
+    }
+
+    fn on_ranging_result(
+        &mut self,
+        source: PresenceDataSource,
+        device_id: u64,
+        distance_meters: f64,
+        confidence: MeasurementConfidence,
+    ) -> Option<ProximityEstimate> {
+        let elapsed_real_time_millis = self.elapsed_real_time_millis();
+        self.evict_devices_stale_at(elapsed_real_time_millis);
+        self.mark_device_seen(device_id, elapsed_real_time_millis);
         let new_proximity_estimate = ProximityEstimate {
             device_id,
-            distance_confidence: MeasurementConfidence::Low,
+            distance_confidence: confidence,
             distance_meters,
-            proximity_state: get_proximity_state_from_threshold(distance_meters),
-            elapsed_real_time_millis: Instant::now().duration_since(self.start_time).as_millis()
-                as u64,
-            source: PresenceDataSource::Ble,
+            proximity_state: get_proximity_state_from_threshold(
+                distance_meters,
+                self.get_device_options(device_id),
+            ),
+            elapsed_real_time_millis,
+            source,
         };
-        self.transition_history.push_front(new_proximity_estimate.proximity_state);
-        self.transition_history.truncate(DEFAULT_CONSECUTIVE_SCANS_REQUIRED.into());
-        if self.transition_history.iter().unique().count() == 1
-            && self.transition_history.len() == DEFAULT_CONSECUTIVE_SCANS_REQUIRED.into()
-        {
-            self.best_proximity_estimate_per_device.insert(device_id, new_proximity_estimate);
-            self.last_range_update_time.update(self.start_time);
+        self.proximity_estimate_per_source.insert((device_id, source), new_proximity_estimate);
+        self.publish_proximity_estimate(device_id)
+    }
+
+    /// Records that the given device has been seen at the given time, unless it
+    /// has been seen more recently
+    fn mark_device_seen(&mut self, device_id: u64, elapsed_real_time_millis: u64) {
+        let last_seen_millis =
+            self.last_seen_millis_per_device.entry(device_id).or_insert(elapsed_real_time_millis);
+        *last_seen_millis = (*last_seen_millis).max(elapsed_real_time_millis);
+    }
+
+    /// Returns the time a scan result was obtained according to its timestamp,
+    /// falling back to the current time if the timestamp is unset (0) or lies
+    /// in the future
+    fn get_scan_time_millis(&self, scan_elapsed_real_time_millis: u64) -> u64 {
+        let elapsed_real_time_millis = self.elapsed_real_time_millis();
+        if scan_elapsed_real_time_millis == 0 {
+            return elapsed_real_time_millis;
         }
-        self.best_proximity_estimate_per_device.get(&device_id).copied()
+        scan_elapsed_real_time_millis.min(elapsed_real_time_millis)
     }
 
-    /// Returns the current proximity estimate for a given device
-    pub fn get_proximity_estimate(&self, device_id: u64) -> Option<ProximityEstimate> {
-        self.best_proximity_estimate_per_device.get(&device_id).copied()
+    /// Evicts all devices whose TTL has expired at the given time, notifying
+    /// the listener, and returns their IDs
+    fn evict_devices_stale_at(&mut self, elapsed_real_time_millis: u64) -> Vec<u64> {
+        let device_ttl_millis = self.device_ttl_millis;
+        let stale_device_ids: Vec<u64> = self
+            .last_seen_millis_per_device
+            .iter()
+            .filter(|(_, last_seen_millis)| {
+                elapsed_real_time_millis.saturating_sub(**last_seen_millis) > device_ttl_millis
+            })
+            .map(|(device_id, _)| *device_id)
+            .sorted()
+            .collect();
+        for stale_device_id in &stale_device_ids {
+            self.clear_device_state(*stale_device_id);
+            if let Some(listener) = self.listener.as_mut() {
+                listener.on_device_lost(*stale_device_id);
+            }
+        }
+        stale_device_ids
+    }
+
+    /// Clears the filter and zone state of a device for every data source
+    fn clear_device_proximity_data(&mut self, device_id: u64) {
+        self.proximity_data_per_source
+            .retain(|(data_device_id, _), _| *data_device_id != device_id);
+    }
+
+    /// Clears all estimates and filter state tracked for a device, keeping its
+    /// options override
+    fn clear_device_state(&mut self, device_id: u64) {
+        self.last_seen_millis_per_device.remove(&device_id);
+        self.clear_device_proximity_data(device_id);
+        self.reported_state_per_device.remove(&device_id);
+        self.estimate_history_per_device.remove(&device_id);
+        self.custom_zone_tracker_per_device.remove(&device_id);
+        self.proximity_estimate_per_source
+            .retain(|(estimate_device_id, _), _| *estimate_device_id != device_id);
+    }
+
+    /// Returns the current proximity estimate for a device, recording it in the
+    /// device's history and notifying the listener if its proximity state
+    /// differs from the last reported one
+    fn publish_proximity_estimate(&mut self, device_id: u64) -> Option<ProximityEstimate> {
+        let proximity_estimate = self.get_proximity_estimate(device_id)?;
+        let estimate_history = self
+            .estimate_history_per_device
+            .entry(device_id)
+            .or_insert_with(|| VecDeque::with_capacity(MAX_ESTIMATE_HISTORY_SIZE));
+        if estimate_history.back() != Some(&proximity_estimate) {
+            if estimate_history.len() == MAX_ESTIMATE_HISTORY_SIZE {
+                estimate_history.pop_front();
+            }
+            // Estimates of different sources may be published out of order,
+            // e.g. for a delayed batch of scan results
+            let index = estimate_history
+                .iter()
+                .rposition(|estimate| {
+                    estimate.elapsed_real_time_millis <= proximity_estimate.elapsed_real_time_millis
+                })
+                .map_or(0, |index| index + 1);
+            estimate_history.insert(index, proximity_estimate);
+            self.update_custom_zone(proximity_estimate);
+        }
+        let new_state = proximity_estimate.proximity_state;
+        let old_state = self
+            .reported_state_per_device
+            .insert(device_id, new_state)
+            .unwrap_or(ProximityState::Unknown);
+        if old_state != new_state {
+            if let Some(listener) = self.listener.as_mut() {
+                listener.on_zone_changed(device_id, old_state, new_state, proximity_estimate);
+            }
+        }
+        Some(proximity_estimate)
+    }
+
+    /// Feeds a new proximity estimate into the device's custom zone tracker,
+    /// notifying the listener if the confirmed custom zone changes
+    fn update_custom_zone(&mut self, proximity_estimate: ProximityEstimate) {
+        if self.custom_zones.is_empty() {
+            return;
+        }
+        let device_id = proximity_estimate.device_id;
+        let options = *self.get_device_options(device_id);
+        let old_zone_id = self.get_confirmed_custom_zone(device_id);
+        let zone_tracker = self
+            .custom_zone_tracker_per_device
+            .entry(device_id)
+            .or_insert_with(|| ZoneTracker::new(options.consecutive_scans_required));
+        let old_zone_index = zone_tracker.current_zone_index();
+        let new_zone_index = zone_tracker.update(
+            proximity_estimate.distance_meters,
+            proximity_estimate.elapsed_real_time_millis,
+            &self.custom_zone_upper_bounds_meters,
+            options.hysteresis_meters,
+            |_| options.consecutive_scans_required,
+        );
+        if new_zone_index.is_none() || new_zone_index == old_zone_index {
+            return;
+        }
+        let new_zone_id = self.get_confirmed_custom_zone(device_id);
+        if let Some(listener) = self.listener.as_mut() {
+            listener.on_custom_zone_changed(
+                device_id,
+                old_zone_id,
+                new_zone_id,
+                proximity_estimate,
+            );
+        }
+    }
+
+    fn get_confirmed_custom_zone(&self, device_id: u64) -> Option<u32> {
+        let zone_index =
+            self.custom_zone_tracker_per_device.get(&device_id)?.current_zone_index()?;
+        self.custom_zones.get(zone_index).map(|custom_zone| custom_zone.zone_id)
+    }
+
+    fn is_tracked(&self, device_id: u64) -> bool {
+        self.last_seen_millis_per_device
+            .get(&device_id)
+            .is_some_and(|last_seen_millis| self.is_within_ttl(*last_seen_millis))
+    }
+
+    fn is_within_ttl(&self, elapsed_real_time_millis: u64) -> bool {
+        self.elapsed_real_time_millis().saturating_sub(elapsed_real_time_millis)
+            <= self.device_ttl_millis
+    }
+
+    fn is_fresh(&self, proximity_estimate: &ProximityEstimate) -> bool {
+        let age_millis = self
+            .elapsed_real_time_millis()
+            .saturating_sub(proximity_estimate.elapsed_real_time_millis);
+        age_millis <= self.fusion_policy.freshness_millis(proximity_estimate.source)
+    }
+
+    fn get_device_options(&self, device_id: u64) -> &ProximityStateOptions {
+        self.options_per_device.get(&device_id).unwrap_or(&self.options)
+    }
+
+    fn elapsed_real_time_millis(&self) -> u64 {
+        self.clock.elapsed_real_time_millis()
     }
 }
 
+#[cfg(feature = "std")]
 impl Default for PresenceDetector {
     fn default() -> Self {
         Self::new()
@@ -12,14 +12,21 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::calibration::*;
+use crate::clock::FakeClock;
 use crate::fused_presence_utils::*;
 use crate::presence_detector::*;
 
+// Time at which the scan results below were obtained, and the current time of
+// the clocks used by the presence detectors under test
+const SCAN_TIME_MILLIS: u64 = 123456;
+
 const BLE_SCAN_RESULT_REACH_ZONE: BleScanResult = BleScanResult {
     device_id: 1234,
     tx_power: { MaybeTxPower::Invalid },
     rssi: -40,
-    elapsed_real_time_millis: 123456,
+    elapsed_real_time_millis: SCAN_TIME_MILLIS,
+    advertising_channel: AdvertisingChannel::Unknown,
 };
 
 const BLE_SCAN_RESULT_BAD_RSSI: BleScanResult = BleScanResult {
@@ -36,7 +43,7 @@ const REACH_PROXIMITY_ESTIMATE: ProximityEstimate = ProximityEstimate {
     device_id: 1234,
     distance_meters: 0.1,
     distance_confidence: MeasurementConfidence::Low,
-    elapsed_real_time_millis: 0,
+    elapsed_real_time_millis: SCAN_TIME_MILLIS,
     proximity_state: ProximityState::Reach,
     source: PresenceDataSource::Ble,
 };
@@ -47,11 +54,22 @@ const SHORT_RANGE_PROXIMITY_ESTIMATE: ProximityEstimate = ProximityEstimate {
     ..REACH_PROXIMITY_ESTIMATE
 };
 
+/// Creates a presence detector whose clock is at the time of the scan results
+fn create_presence_detector() -> PresenceDetector {
+    create_presence_detector_with_options(ProximityStateOptions::default())
+}
+
+fn create_presence_detector_with_options(options: ProximityStateOptions) -> PresenceDetector {
+    let clock = FakeClock::new();
+    clock.set_millis(SCAN_TIME_MILLIS);
+    PresenceDetector::with_clock(options, Box::new(clock))
+}
+
 #[test]
 fn test_on_ble_scan_result_success() {
     // Tests that the proximity state stored for each device is the accurate one after two
     // consecutive scan results
-    let mut presence_detector = PresenceDetector::new();
+    let mut presence_detector = create_presence_detector();
     assert_eq!(
         presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE),
         None
@@ -62,7 +80,7 @@ fn test_on_ble_scan_result_success() {
             device_id: 1234,
             distance_meters: 0.1,
             distance_confidence: MeasurementConfidence::Low,
-            elapsed_real_time_millis: 0,
+            elapsed_real_time_millis: SCAN_TIME_MILLIS,
             proximity_state: ProximityState::Reach,
             source: PresenceDataSource::Ble
         })
@@ -72,7 +90,7 @@ fn test_on_ble_scan_result_success() {
 #[test]
 fn test_on_ble_scan_result_bad_rssi() {
     // Tests that scan results with bad RSSIs are ignored
-    let mut presence_detector = PresenceDetector::new();
+    let mut presence_detector = create_presence_detector();
     assert_eq!(
         presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE),
         None
@@ -85,7 +103,7 @@ fn test_on_ble_scan_result_bad_rssi() {
 }
 #[test]
 fn test_on_ble_scan_result_transition_to_new_zone() {
-    let mut presence_detector = PresenceDetector::new();
+    let mut presence_detector = create_presence_detector();
     assert_eq!(
         presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE),
         None
@@ -103,3 +121,1008 @@ fn test_on_ble_scan_result_transition_to_new_zone() {
         Some(SHORT_RANGE_PROXIMITY_ESTIMATE)
     );
 }
+
+#[test]
+fn test_on_uwb_ranging_result_success() {
+    let mut presence_detector = create_presence_detector();
+    assert_eq!(
+        presence_detector.on_uwb_ranging_result(1234, 0.3, MeasurementConfidence::High),
+        Some(ProximityEstimate {
+            device_id: 1234,
+            distance_meters: 0.3,
+            distance_confidence: MeasurementConfidence::High,
+            elapsed_real_time_millis: SCAN_TIME_MILLIS,
+            proximity_state: ProximityState::Reach,
+            source: PresenceDataSource::Uwb
+        })
+    );
+    assert_eq!(
+        presence_detector
+            .get_proximity_estimate(1234)
+            .map(|estimate| estimate.source),
+        Some(PresenceDataSource::Uwb)
+    );
+}
+
+#[test]
+fn test_fresh_uwb_estimate_takes_precedence_over_ble() {
+    let mut presence_detector = create_presence_detector();
+    presence_detector.on_uwb_ranging_result(1234, 2.0, MeasurementConfidence::High);
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    let proximity_estimate = presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    assert_eq!(
+        proximity_estimate.map(|estimate| (estimate.source, estimate.proximity_state)),
+        Some((PresenceDataSource::Uwb, ProximityState::LongRange))
+    );
+}
+
+#[test]
+fn test_on_nan_ranging_result_success() {
+    let mut presence_detector = create_presence_detector();
+    assert_eq!(
+        presence_detector.on_nan_ranging_result(1234, 1.0),
+        Some(ProximityEstimate {
+            device_id: 1234,
+            distance_meters: 1.0,
+            distance_confidence: MeasurementConfidence::Medium,
+            elapsed_real_time_millis: SCAN_TIME_MILLIS,
+            proximity_state: ProximityState::ShortRange,
+            source: PresenceDataSource::Nan
+        })
+    );
+}
+
+#[test]
+fn test_fresh_uwb_estimate_takes_precedence_over_nan() {
+    let mut presence_detector = create_presence_detector();
+    presence_detector.on_uwb_ranging_result(1234, 0.3, MeasurementConfidence::High);
+    assert_eq!(
+        presence_detector
+            .on_nan_ranging_result(1234, 2.0)
+            .map(|estimate| estimate.source),
+        Some(PresenceDataSource::Uwb)
+    );
+}
+
+#[test]
+fn test_fusion_policy_precedence() {
+    let mut presence_detector = create_presence_detector();
+    presence_detector.set_fusion_policy(FusionPolicy {
+        source_precedence: [
+            PresenceDataSource::Nan,
+            PresenceDataSource::Uwb,
+            PresenceDataSource::Ble,
+        ],
+        ..FusionPolicy::default()
+    });
+    presence_detector.on_uwb_ranging_result(1234, 0.3, MeasurementConfidence::High);
+    assert_eq!(
+        presence_detector
+            .on_nan_ranging_result(1234, 2.0)
+            .map(|estimate| estimate.source),
+        Some(PresenceDataSource::Nan)
+    );
+}
+
+#[test]
+fn test_fusion_policy_confidence_weighted() {
+    let mut presence_detector = create_presence_detector();
+    presence_detector.set_fusion_policy(FusionPolicy {
+        strategy: FusionStrategy::ConfidenceWeighted,
+        ..FusionPolicy::default()
+    });
+    presence_detector.on_uwb_ranging_result(1234, 1.0, MeasurementConfidence::High);
+    let proximity_estimate = presence_detector.on_nan_ranging_result(1234, 4.0);
+    assert_eq!(
+        proximity_estimate.map(|estimate| (
+            estimate.distance_meters,
+            estimate.distance_confidence,
+            estimate.proximity_state,
+            estimate.source
+        )),
+        Some((
+            2.0,
+            MeasurementConfidence::High,
+            ProximityState::LongRange,
+            PresenceDataSource::Uwb
+        ))
+    );
+}
+
+#[test]
+fn test_fusion_policy_ignores_stale_sources() {
+    let clock = FakeClock::new();
+    let mut presence_detector =
+        PresenceDetector::with_clock(ProximityStateOptions::default(), Box::new(clock.clone()));
+    presence_detector.set_fusion_policy(FusionPolicy {
+        uwb_freshness_millis: 100,
+        ..FusionPolicy::default()
+    });
+    presence_detector.on_uwb_ranging_result(1234, 0.3, MeasurementConfidence::High);
+    clock.advance_millis(101);
+    assert_eq!(
+        presence_detector
+            .on_nan_ranging_result(1234, 2.0)
+            .map(|estimate| estimate.source),
+        Some(PresenceDataSource::Nan)
+    );
+}
+
+#[test]
+fn test_on_ble_scan_result_with_distance_filter() {
+    let mut presence_detector = create_presence_detector_with_options(ProximityStateOptions {
+        distance_filter: DistanceFilter::Exponential { alpha: 0.5 },
+        ..ProximityStateOptions::default()
+    });
+    let ble_scan_result_far_zone = || BleScanResult {
+        rssi: -80,
+        ..BLE_SCAN_RESULT_REACH_ZONE
+    };
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    presence_detector.on_ble_scan_result(ble_scan_result_far_zone());
+    assert_eq!(
+        presence_detector
+            .on_ble_scan_result(ble_scan_result_far_zone())
+            .map(|estimate| (estimate.distance_meters, estimate.proximity_state)),
+        Some((7.525, ProximityState::Far))
+    );
+}
+
+#[test]
+fn test_on_ble_scan_result_with_rssi_filter() {
+    // Tests that a single attenuated packet is filtered out before distance conversion
+    let mut presence_detector = create_presence_detector_with_options(ProximityStateOptions {
+        rssi_filter: RssiFilter::Median { window: 3 },
+        ..ProximityStateOptions::default()
+    });
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    presence_detector.on_ble_scan_result(BleScanResult {
+        rssi: -80,
+        ..BLE_SCAN_RESULT_REACH_ZONE
+    });
+    assert_eq!(
+        presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE),
+        Some(REACH_PROXIMITY_ESTIMATE)
+    );
+}
+
+#[test]
+fn test_on_ble_scan_result_rejects_rssi_outliers() {
+    let mut presence_detector = create_presence_detector_with_options(ProximityStateOptions {
+        rssi_outlier_rejection: RssiOutlierRejection::MedianAbsoluteDeviation {
+            window: 5,
+            max_deviations: 3.0,
+        },
+        ..ProximityStateOptions::default()
+    });
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    let proximity_estimate = presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    assert_eq!(
+        presence_detector.on_ble_scan_result(BleScanResult {
+            rssi: -80,
+            ..BLE_SCAN_RESULT_REACH_ZONE
+        }),
+        proximity_estimate
+    );
+}
+
+#[test]
+fn test_with_options_honors_thresholds_and_consecutive_scans() {
+    let mut presence_detector = create_presence_detector_with_options(ProximityStateOptions {
+        tap_distance_threshold_meters: 0.02,
+        reach_distance_threshold_meters: 0.05,
+        consecutive_scans_required: 3,
+        ..ProximityStateOptions::default()
+    });
+    assert_eq!(
+        presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE),
+        None
+    );
+    assert_eq!(
+        presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE),
+        None
+    );
+    assert_eq!(
+        presence_detector
+            .on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE)
+            .map(|estimate| estimate.proximity_state),
+        Some(ProximityState::ShortRange)
+    );
+}
+
+#[test]
+fn test_transition_history_is_tracked_per_device() {
+    let mut presence_detector = create_presence_detector();
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    presence_detector.on_ble_scan_result(BleScanResult {
+        device_id: 5678,
+        ..BLE_SCAN_RESULT_SHORT_RANGE_ZONE
+    });
+    assert_eq!(
+        presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE),
+        Some(REACH_PROXIMITY_ESTIMATE)
+    );
+}
+
+#[test]
+fn test_set_options() {
+    let mut presence_detector = create_presence_detector();
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    presence_detector.set_options(ProximityStateOptions {
+        tap_distance_threshold_meters: 0.2,
+        consecutive_scans_required: 1,
+        ..ProximityStateOptions::default()
+    });
+    assert_eq!(
+        presence_detector
+            .on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE)
+            .map(|estimate| estimate.proximity_state),
+        Some(ProximityState::Tap)
+    );
+}
+
+#[test]
+fn test_device_options_override() {
+    let mut presence_detector = create_presence_detector();
+    presence_detector.set_device_options(
+        5678,
+        ProximityStateOptions {
+            tap_distance_threshold_meters: 0.2,
+            ..ProximityStateOptions::default()
+        },
+    );
+    let ble_scan_result_overridden_device = || BleScanResult {
+        device_id: 5678,
+        ..BLE_SCAN_RESULT_REACH_ZONE
+    };
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    presence_detector.on_ble_scan_result(ble_scan_result_overridden_device());
+    assert_eq!(
+        presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE),
+        Some(REACH_PROXIMITY_ESTIMATE)
+    );
+    assert_eq!(
+        presence_detector
+            .on_ble_scan_result(ble_scan_result_overridden_device())
+            .map(|estimate| estimate.proximity_state),
+        Some(ProximityState::Tap)
+    );
+}
+
+#[test]
+fn test_clear_device_options() {
+    let mut presence_detector = create_presence_detector();
+    presence_detector.set_device_options(
+        1234,
+        ProximityStateOptions {
+            tap_distance_threshold_meters: 0.2,
+            ..ProximityStateOptions::default()
+        },
+    );
+    presence_detector.clear_device_options(1234);
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    assert_eq!(
+        presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE),
+        Some(REACH_PROXIMITY_ESTIMATE)
+    );
+}
+
+#[test]
+fn test_remove_device() {
+    let mut presence_detector = create_presence_detector();
+    presence_detector.set_device_options(
+        1234,
+        ProximityStateOptions {
+            tap_distance_threshold_meters: 0.2,
+            ..ProximityStateOptions::default()
+        },
+    );
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    presence_detector.remove_device(1234);
+    assert_eq!(presence_detector.get_proximity_estimate(1234), None);
+    assert_eq!(presence_detector.get_estimate_history(1234, 8), vec![]);
+    assert!(presence_detector.get_tracked_devices().is_empty());
+    // The transition history and the options override are gone as well
+    assert_eq!(
+        presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE),
+        None
+    );
+    assert_eq!(
+        presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE),
+        Some(REACH_PROXIMITY_ESTIMATE)
+    );
+}
+
+#[test]
+fn test_reset() {
+    let mut presence_detector = create_presence_detector();
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    presence_detector.on_uwb_ranging_result(5678, 0.1, MeasurementConfidence::High);
+    presence_detector.reset();
+    assert!(presence_detector.get_tracked_devices().is_empty());
+    assert_eq!(presence_detector.get_proximity_estimate(1234), None);
+    assert_eq!(presence_detector.get_proximity_estimate(5678), None);
+    assert_eq!(presence_detector.get_estimate_history(1234, 8), vec![]);
+    assert_eq!(presence_detector.get_motion_estimate(1234), None);
+    // A single scan after the reset is not enough to confirm a zone again
+    assert_eq!(
+        presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE),
+        None
+    );
+}
+
+#[test]
+fn test_reset_keeps_device_options() {
+    let mut presence_detector = create_presence_detector();
+    presence_detector.set_device_options(
+        1234,
+        ProximityStateOptions {
+            tap_distance_threshold_meters: 0.2,
+            ..ProximityStateOptions::default()
+        },
+    );
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    presence_detector.reset();
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    assert_eq!(
+        presence_detector
+            .on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE)
+            .map(|estimate| estimate.proximity_state),
+        Some(ProximityState::Tap)
+    );
+}
+
+struct RecordingListener {
+    zone_changes: std::sync::Arc<std::sync::Mutex<Vec<(u64, ProximityState, ProximityState)>>>,
+}
+
+impl ProximityStateListener for RecordingListener {
+    fn on_zone_changed(
+        &mut self,
+        device_id: u64,
+        old_state: ProximityState,
+        new_state: ProximityState,
+        _proximity_estimate: ProximityEstimate,
+    ) {
+        if let Ok(mut zone_changes) = self.zone_changes.lock() {
+            zone_changes.push((device_id, old_state, new_state));
+        }
+    }
+}
+
+#[test]
+fn test_listener_notified_on_confirmed_transitions_only() {
+    let zone_changes = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mut presence_detector = create_presence_detector();
+    presence_detector.set_proximity_state_listener(Box::new(RecordingListener {
+        zone_changes: zone_changes.clone(),
+    }));
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_SHORT_RANGE_ZONE);
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_SHORT_RANGE_ZONE);
+    assert_eq!(
+        *zone_changes.lock().unwrap_or_else(|err| err.into_inner()),
+        vec![
+            (1234, ProximityState::Unknown, ProximityState::Reach),
+            (1234, ProximityState::Reach, ProximityState::ShortRange)
+        ]
+    );
+}
+
+#[test]
+fn test_cleared_listener_is_not_notified() {
+    let zone_changes = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mut presence_detector = create_presence_detector();
+    presence_detector.set_proximity_state_listener(Box::new(RecordingListener {
+        zone_changes: zone_changes.clone(),
+    }));
+    presence_detector.clear_proximity_state_listener();
+    presence_detector.on_uwb_ranging_result(1234, 0.3, MeasurementConfidence::High);
+    assert!(zone_changes
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .is_empty());
+}
+
+#[test]
+fn test_get_tracked_devices() {
+    let mut presence_detector = create_presence_detector();
+    presence_detector.on_ble_scan_result(BleScanResult {
+        device_id: 5678,
+        ..BLE_SCAN_RESULT_REACH_ZONE
+    });
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    assert_eq!(presence_detector.get_tracked_devices(), vec![1234, 5678]);
+}
+
+#[test]
+fn test_stale_devices_are_evicted() {
+    let clock = FakeClock::new();
+    let mut presence_detector =
+        PresenceDetector::with_clock(ProximityStateOptions::default(), Box::new(clock.clone()));
+    presence_detector.set_device_ttl_millis(1000);
+    presence_detector.on_uwb_ranging_result(5678, 0.3, MeasurementConfidence::High);
+    clock.advance_millis(1000);
+    assert_eq!(presence_detector.get_tracked_devices(), vec![5678]);
+    clock.advance_millis(1);
+    assert_eq!(presence_detector.get_proximity_estimate(5678), None);
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    assert_eq!(presence_detector.get_tracked_devices(), vec![1234]);
+}
+
+struct LostDeviceListener {
+    lost_devices: std::sync::Arc<std::sync::Mutex<Vec<u64>>>,
+}
+
+impl ProximityStateListener for LostDeviceListener {
+    fn on_zone_changed(
+        &mut self,
+        _device_id: u64,
+        _old_state: ProximityState,
+        _new_state: ProximityState,
+        _proximity_estimate: ProximityEstimate,
+    ) {
+    }
+
+    fn on_device_lost(&mut self, device_id: u64) {
+        if let Ok(mut lost_devices) = self.lost_devices.lock() {
+            lost_devices.push(device_id);
+        }
+    }
+}
+
+#[test]
+fn test_listener_notified_on_device_lost() {
+    let lost_devices = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let clock = FakeClock::new();
+    let mut presence_detector =
+        PresenceDetector::with_clock(ProximityStateOptions::default(), Box::new(clock.clone()));
+    presence_detector.set_proximity_state_listener(Box::new(LostDeviceListener {
+        lost_devices: lost_devices.clone(),
+    }));
+    presence_detector.set_device_ttl_millis(1000);
+    presence_detector.on_uwb_ranging_result(5678, 0.3, MeasurementConfidence::High);
+    clock.advance_millis(1001);
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    assert_eq!(
+        *lost_devices.lock().unwrap_or_else(|err| err.into_inner()),
+        vec![5678]
+    );
+}
+
+#[test]
+fn test_evict_stale_devices() {
+    let lost_devices = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let clock = FakeClock::new();
+    let mut presence_detector =
+        PresenceDetector::with_clock(ProximityStateOptions::default(), Box::new(clock.clone()));
+    presence_detector.set_proximity_state_listener(Box::new(LostDeviceListener {
+        lost_devices: lost_devices.clone(),
+    }));
+    presence_detector.set_device_ttl_millis(1000);
+    presence_detector.on_uwb_ranging_result(5678, 0.3, MeasurementConfidence::High);
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    clock.advance_millis(1000);
+    assert!(presence_detector.evict_stale_devices().is_empty());
+    clock.advance_millis(1);
+    assert_eq!(presence_detector.evict_stale_devices(), vec![1234, 5678]);
+    assert!(presence_detector.evict_stale_devices().is_empty());
+    assert_eq!(
+        *lost_devices.lock().unwrap_or_else(|err| err.into_inner()),
+        vec![1234, 5678]
+    );
+}
+
+const CUSTOM_ZONES: [CustomZone; 2] = [
+    CustomZone {
+        zone_id: 20,
+        upper_bound_meters: 2.0,
+    },
+    CustomZone {
+        zone_id: 10,
+        upper_bound_meters: 0.3,
+    },
+];
+
+#[test]
+fn test_custom_zones() {
+    let mut presence_detector = create_presence_detector();
+    presence_detector.set_custom_zones(CUSTOM_ZONES.to_vec());
+    presence_detector.on_uwb_ranging_result(1234, 0.1, MeasurementConfidence::High);
+    assert_eq!(presence_detector.get_custom_zone(1234), None);
+    presence_detector.on_uwb_ranging_result(1234, 0.15, MeasurementConfidence::High);
+    assert_eq!(presence_detector.get_custom_zone(1234), Some(10));
+    // Hysteresis keeps the device in its zone just past the boundary
+    presence_detector.on_uwb_ranging_result(1234, 0.32, MeasurementConfidence::High);
+    presence_detector.on_uwb_ranging_result(1234, 0.33, MeasurementConfidence::High);
+    assert_eq!(presence_detector.get_custom_zone(1234), Some(10));
+    presence_detector.on_uwb_ranging_result(1234, 1.0, MeasurementConfidence::High);
+    presence_detector.on_uwb_ranging_result(1234, 1.1, MeasurementConfidence::High);
+    assert_eq!(presence_detector.get_custom_zone(1234), Some(20));
+    presence_detector.on_uwb_ranging_result(1234, 3.0, MeasurementConfidence::High);
+    presence_detector.on_uwb_ranging_result(1234, 3.1, MeasurementConfidence::High);
+    assert_eq!(presence_detector.get_custom_zone(1234), None);
+}
+
+type CustomZoneChanges = std::sync::Arc<std::sync::Mutex<Vec<(u64, Option<u32>, Option<u32>)>>>;
+
+struct CustomZoneListener {
+    zone_changes: CustomZoneChanges,
+}
+
+impl ProximityStateListener for CustomZoneListener {
+    fn on_zone_changed(
+        &mut self,
+        _device_id: u64,
+        _old_state: ProximityState,
+        _new_state: ProximityState,
+        _proximity_estimate: ProximityEstimate,
+    ) {
+    }
+
+    fn on_custom_zone_changed(
+        &mut self,
+        device_id: u64,
+        old_zone_id: Option<u32>,
+        new_zone_id: Option<u32>,
+        _proximity_estimate: ProximityEstimate,
+    ) {
+        if let Ok(mut zone_changes) = self.zone_changes.lock() {
+            zone_changes.push((device_id, old_zone_id, new_zone_id));
+        }
+    }
+}
+
+#[test]
+fn test_listener_notified_on_custom_zone_changes() {
+    let zone_changes = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let mut presence_detector = create_presence_detector();
+    presence_detector.set_custom_zones(CUSTOM_ZONES.to_vec());
+    presence_detector.set_proximity_state_listener(Box::new(CustomZoneListener {
+        zone_changes: zone_changes.clone(),
+    }));
+    for distance_meters in [0.1, 0.15, 0.2, 1.0, 1.1, 3.0, 3.1] {
+        presence_detector.on_nan_ranging_result(1234, distance_meters);
+    }
+    assert_eq!(
+        *zone_changes.lock().unwrap_or_else(|err| err.into_inner()),
+        vec![
+            (1234, None, Some(10)),
+            (1234, Some(10), Some(20)),
+            (1234, Some(20), None)
+        ]
+    );
+}
+
+#[test]
+fn test_stable_ble_scan_results_have_medium_confidence() {
+    let mut presence_detector = create_presence_detector();
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    assert_eq!(
+        presence_detector
+            .on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE)
+            .map(|estimate| estimate.distance_confidence),
+        Some(MeasurementConfidence::Medium)
+    );
+}
+
+#[test]
+fn test_calibration_applied_to_device_model() {
+    let mut calibration_table = CalibrationTable::new();
+    calibration_table.register(
+        42,
+        CalibrationEntry {
+            tx_power_correction_db: 0,
+            rssi_offset_db: -20,
+        },
+    );
+    let mut presence_detector = create_presence_detector();
+    presence_detector.set_calibration_table(calibration_table);
+    presence_detector.set_device_model(1234, 42);
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    assert_eq!(
+        presence_detector
+            .on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE)
+            .map(|estimate| estimate.distance_meters),
+        Some(1.0)
+    );
+}
+
+#[test]
+fn test_channel_rssi_correction_applied_to_advertising_channel() {
+    let mut presence_detector = create_presence_detector();
+    presence_detector.set_channel_rssi_correction(ChannelRssiCorrection {
+        channel_37_offset_db: 0,
+        channel_38_offset_db: 0,
+        channel_39_offset_db: -20,
+    });
+    let ble_scan_result_on_channel = |advertising_channel| BleScanResult {
+        advertising_channel,
+        ..BLE_SCAN_RESULT_REACH_ZONE
+    };
+    presence_detector.on_ble_scan_result(ble_scan_result_on_channel(AdvertisingChannel::Channel37));
+    assert_eq!(
+        presence_detector
+            .on_ble_scan_result(ble_scan_result_on_channel(AdvertisingChannel::Channel37)),
+        Some(REACH_PROXIMITY_ESTIMATE)
+    );
+    presence_detector.on_ble_scan_result(ble_scan_result_on_channel(AdvertisingChannel::Channel39));
+    assert_eq!(
+        presence_detector
+            .on_ble_scan_result(ble_scan_result_on_channel(AdvertisingChannel::Channel39))
+            .map(|estimate| estimate.proximity_state),
+        Some(ProximityState::ShortRange)
+    );
+}
+
+#[test]
+fn test_environment_profile_used_for_distance() {
+    let mut presence_detector = create_presence_detector();
+    presence_detector.set_environment_profile(EnvironmentProfile::Custom {
+        path_loss_exponent: 4.0,
+        reference_loss_at_1_meter_db: 20.0,
+    });
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    assert_eq!(
+        presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE),
+        Some(SHORT_RANGE_PROXIMITY_ESTIMATE)
+    );
+}
+
+#[test]
+fn test_on_ble_scan_result_reports_hysteresis_adjusted_zone() {
+    // Tests that a device just past the reach boundary, but within the hysteresis
+    // margin, stays in the reach zone until it moves further away. The indoor
+    // profile is used because free space distances only move in 20 dB steps.
+    let mut presence_detector = create_presence_detector();
+    presence_detector.set_environment_profile(EnvironmentProfile::Indoor);
+    let ble_scan_result = |rssi| BleScanResult {
+        rssi,
+        ..BLE_SCAN_RESULT_REACH_ZONE
+    };
+    presence_detector.on_ble_scan_result(ble_scan_result(-50));
+    presence_detector.on_ble_scan_result(ble_scan_result(-50));
+    presence_detector.on_ble_scan_result(ble_scan_result(-54));
+    assert_eq!(
+        presence_detector
+            .on_ble_scan_result(ble_scan_result(-54))
+            .map(|estimate| estimate.proximity_state),
+        Some(ProximityState::Reach)
+    );
+    presence_detector.on_ble_scan_result(ble_scan_result(-55));
+    assert_eq!(
+        presence_detector
+            .on_ble_scan_result(ble_scan_result(-55))
+            .map(|estimate| estimate.proximity_state),
+        Some(ProximityState::ShortRange)
+    );
+}
+
+#[test]
+fn test_estimates_carry_clock_time() {
+    let clock = FakeClock::new();
+    clock.set_millis(5000);
+    let mut presence_detector =
+        PresenceDetector::with_clock(ProximityStateOptions::default(), Box::new(clock.clone()));
+    assert_eq!(
+        presence_detector
+            .on_uwb_ranging_result(1234, 0.3, MeasurementConfidence::High)
+            .map(|estimate| estimate.elapsed_real_time_millis),
+        Some(5000)
+    );
+}
+
+#[test]
+fn test_stale_transition_history_is_not_confirmed() {
+    let clock = FakeClock::new();
+    let mut presence_detector =
+        PresenceDetector::with_clock(ProximityStateOptions::default(), Box::new(clock.clone()));
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    clock.advance_millis(10_000);
+    assert_eq!(
+        presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE),
+        None
+    );
+}
+
+#[test]
+fn test_get_estimate_history() {
+    let clock = FakeClock::new();
+    let mut presence_detector =
+        PresenceDetector::with_clock(ProximityStateOptions::default(), Box::new(clock.clone()));
+    for distance_meters in [3.0, 2.0, 1.0] {
+        presence_detector.on_uwb_ranging_result(1234, distance_meters, MeasurementConfidence::High);
+        clock.advance_millis(100);
+    }
+    assert_eq!(
+        presence_detector
+            .get_estimate_history(1234, 2)
+            .iter()
+            .map(|estimate| (estimate.distance_meters, estimate.elapsed_real_time_millis))
+            .collect::<Vec<_>>(),
+        vec![(2.0, 100), (1.0, 200)]
+    );
+    assert_eq!(presence_detector.get_estimate_history(1234, 10).len(), 3);
+    assert!(presence_detector.get_estimate_history(5678, 10).is_empty());
+}
+
+#[test]
+fn test_estimate_history_skips_unchanged_estimates() {
+    let mut presence_detector = create_presence_detector();
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_SHORT_RANGE_ZONE);
+    assert_eq!(
+        presence_detector.get_estimate_history(1234, 10),
+        vec![REACH_PROXIMITY_ESTIMATE]
+    );
+}
+
+#[test]
+fn test_motion_estimate_approaching() {
+    let clock = FakeClock::new();
+    let mut presence_detector =
+        PresenceDetector::with_clock(ProximityStateOptions::default(), Box::new(clock.clone()));
+    for distance_meters in [3.0, 2.5, 2.0, 1.5] {
+        presence_detector.on_uwb_ranging_result(1234, distance_meters, MeasurementConfidence::High);
+        clock.advance_millis(500);
+    }
+    assert_eq!(
+        presence_detector.get_motion_estimate(1234),
+        Some(MotionEstimate {
+            device_id: 1234,
+            radial_velocity_meters_per_second: -1.0,
+            radial_motion: RadialMotion::Approaching,
+            elapsed_real_time_millis: 1500,
+        })
+    );
+}
+
+#[test]
+fn test_motion_estimate_stationary() {
+    let clock = FakeClock::new();
+    let mut presence_detector =
+        PresenceDetector::with_clock(ProximityStateOptions::default(), Box::new(clock.clone()));
+    for distance_meters in [1.0, 1.02, 0.98, 1.0] {
+        presence_detector.on_uwb_ranging_result(1234, distance_meters, MeasurementConfidence::High);
+        clock.advance_millis(500);
+    }
+    assert_eq!(
+        presence_detector
+            .get_motion_estimate(1234)
+            .map(|estimate| estimate.radial_motion),
+        Some(RadialMotion::Stationary)
+    );
+}
+
+#[test]
+fn test_motion_estimate_requires_history() {
+    let mut presence_detector = create_presence_detector();
+    presence_detector.on_uwb_ranging_result(1234, 1.0, MeasurementConfidence::High);
+    assert_eq!(presence_detector.get_motion_estimate(1234), None);
+}
+
+#[test]
+fn test_export_and_import_state() {
+    let clock = FakeClock::new();
+    clock.set_millis(10_000);
+    let mut presence_detector =
+        PresenceDetector::with_clock(ProximityStateOptions::default(), Box::new(clock.clone()));
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_REACH_ZONE);
+    clock.advance_millis(500);
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_SHORT_RANGE_ZONE);
+    clock.advance_millis(500);
+    let serialized_state = serde_json::to_string(&presence_detector.export_state());
+
+    let restored_clock = FakeClock::new();
+    restored_clock.set_millis(2000);
+    let mut restored_presence_detector = PresenceDetector::with_clock(
+        ProximityStateOptions::default(),
+        Box::new(restored_clock.clone()),
+    );
+    let state = serialized_state.and_then(|state| serde_json::from_str(&state));
+    assert!(state.is_ok());
+    restored_presence_detector.import_state(state.unwrap_or_default());
+    assert_eq!(
+        restored_presence_detector.get_proximity_estimate(1234),
+        Some(ProximityEstimate {
+            elapsed_real_time_millis: 1000,
+            ..REACH_PROXIMITY_ESTIMATE
+        })
+    );
+    assert_eq!(restored_presence_detector.get_tracked_devices(), vec![1234]);
+    // The unconfirmed transition to short range survives the restart
+    assert_eq!(
+        restored_presence_detector
+            .on_ble_scan_result(BLE_SCAN_RESULT_SHORT_RANGE_ZONE)
+            .map(|estimate| estimate.proximity_state),
+        Some(ProximityState::ShortRange)
+    );
+}
+
+#[test]
+fn test_import_state_skips_expired_devices() {
+    let clock = FakeClock::new();
+    let mut presence_detector =
+        PresenceDetector::with_clock(ProximityStateOptions::default(), Box::new(clock.clone()));
+    presence_detector.on_uwb_ranging_result(1234, 0.3, MeasurementConfidence::High);
+    clock.advance_millis(1000);
+    let state = presence_detector.export_state();
+
+    let mut restored_presence_detector = create_presence_detector();
+    restored_presence_detector.set_device_ttl_millis(500);
+    restored_presence_detector.import_state(state);
+    assert!(restored_presence_detector.get_tracked_devices().is_empty());
+}
+
+#[test]
+fn test_ble_scan_results_use_scan_timestamps() {
+    let clock = FakeClock::new();
+    clock.set_millis(10_000);
+    let mut presence_detector =
+        PresenceDetector::with_clock(ProximityStateOptions::default(), Box::new(clock.clone()));
+    let delayed_scan_result = BleScanResult {
+        elapsed_real_time_millis: 8000,
+        ..BLE_SCAN_RESULT_REACH_ZONE
+    };
+    presence_detector.on_ble_scan_result(delayed_scan_result);
+    assert_eq!(
+        presence_detector.on_ble_scan_result(delayed_scan_result),
+        Some(ProximityEstimate {
+            elapsed_real_time_millis: 8000,
+            ..REACH_PROXIMITY_ESTIMATE
+        })
+    );
+    // Timestamps in the future are clamped to the current time
+    assert_eq!(
+        presence_detector
+            .on_ble_scan_result(BleScanResult {
+                elapsed_real_time_millis: 20_000,
+                ..BLE_SCAN_RESULT_REACH_ZONE
+            })
+            .map(|estimate| estimate.elapsed_real_time_millis),
+        Some(10_000)
+    );
+}
+
+#[test]
+fn test_out_of_order_ble_scan_results_are_ignored() {
+    let clock = FakeClock::new();
+    clock.set_millis(10_000);
+    let mut presence_detector =
+        PresenceDetector::with_clock(ProximityStateOptions::default(), Box::new(clock.clone()));
+    presence_detector.on_ble_scan_result(BleScanResult {
+        elapsed_real_time_millis: 5000,
+        ..BLE_SCAN_RESULT_REACH_ZONE
+    });
+    presence_detector.on_ble_scan_result(BleScanResult {
+        elapsed_real_time_millis: 6000,
+        ..BLE_SCAN_RESULT_REACH_ZONE
+    });
+    let out_of_order_scan_result = BleScanResult {
+        elapsed_real_time_millis: 5500,
+        ..BLE_SCAN_RESULT_SHORT_RANGE_ZONE
+    };
+    presence_detector.on_ble_scan_result(out_of_order_scan_result);
+    assert_eq!(
+        presence_detector.on_ble_scan_result(out_of_order_scan_result),
+        Some(ProximityEstimate {
+            elapsed_real_time_millis: 6000,
+            ..REACH_PROXIMITY_ESTIMATE
+        })
+    );
+}
+
+#[test]
+fn test_expired_ble_scan_results_are_ignored() {
+    let clock = FakeClock::new();
+    clock.set_millis(10_000);
+    let mut presence_detector =
+        PresenceDetector::with_clock(ProximityStateOptions::default(), Box::new(clock.clone()));
+    presence_detector.set_device_ttl_millis(1000);
+    let expired_scan_result = BleScanResult {
+        elapsed_real_time_millis: 5000,
+        ..BLE_SCAN_RESULT_REACH_ZONE
+    };
+    presence_detector.on_ble_scan_result(expired_scan_result);
+    assert_eq!(
+        presence_detector.on_ble_scan_result(expired_scan_result),
+        None
+    );
+    assert!(presence_detector.get_tracked_devices().is_empty());
+}
+
+#[test]
+fn test_estimate_history_is_ordered_by_time() {
+    let clock = FakeClock::new();
+    clock.set_millis(10_000);
+    let mut presence_detector =
+        PresenceDetector::with_clock(ProximityStateOptions::default(), Box::new(clock.clone()));
+    presence_detector.set_fusion_policy(FusionPolicy {
+        source_precedence: [
+            PresenceDataSource::Ble,
+            PresenceDataSource::Uwb,
+            PresenceDataSource::Nan,
+        ],
+        ..FusionPolicy::default()
+    });
+    presence_detector.on_uwb_ranging_result(1234, 1.0, MeasurementConfidence::High);
+    let delayed_scan_result = BleScanResult {
+        elapsed_real_time_millis: 9000,
+        ..BLE_SCAN_RESULT_REACH_ZONE
+    };
+    presence_detector.on_ble_scan_result(delayed_scan_result);
+    presence_detector.on_ble_scan_result(delayed_scan_result);
+    let estimate_times: Vec<u64> = presence_detector
+        .get_estimate_history(1234, 8)
+        .iter()
+        .map(|estimate| estimate.elapsed_real_time_millis)
+        .collect();
+    assert_eq!(estimate_times, vec![9000, 10_000]);
+}
+
+#[test]
+fn test_advertised_tx_power_corrects_distance() {
+    let mut presence_detector = create_presence_detector();
+    let low_tx_power_scan_result = BleScanResult {
+        tx_power: MaybeTxPower::Valid(-19),
+        ..BLE_SCAN_RESULT_SHORT_RANGE_ZONE
+    };
+    presence_detector.on_ble_scan_result(low_tx_power_scan_result);
+    assert_eq!(
+        presence_detector
+            .on_ble_scan_result(low_tx_power_scan_result)
+            .map(|estimate| estimate.proximity_state),
+        Some(ProximityState::Reach)
+    );
+    assert_eq!(presence_detector.get_tx_power_estimate(1234), Some(-19));
+}
+
+#[test]
+fn test_default_tx_power_applied_to_scan_results_without_tx_power() {
+    let mut tx_power_model = TxPowerModel::new();
+    tx_power_model.set_default_tx_power_dbm(PresenceDataSource::Ble, -19);
+    let mut presence_detector = create_presence_detector();
+    presence_detector.set_tx_power_model(tx_power_model);
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_SHORT_RANGE_ZONE);
+    assert_eq!(
+        presence_detector
+            .on_ble_scan_result(BLE_SCAN_RESULT_SHORT_RANGE_ZONE)
+            .map(|estimate| estimate.proximity_state),
+        Some(ProximityState::Reach)
+    );
+    assert_eq!(presence_detector.get_tx_power_estimate(1234), None);
+}
+
+#[test]
+fn test_default_tx_power_of_scan_result_source() {
+    let mut tx_power_model = TxPowerModel::new();
+    tx_power_model.set_default_tx_power_dbm(PresenceDataSource::Nan, -19);
+    let mut presence_detector = create_presence_detector();
+    presence_detector.set_tx_power_model(tx_power_model);
+    presence_detector.on_ble_scan_result(BLE_SCAN_RESULT_SHORT_RANGE_ZONE);
+    assert_eq!(
+        presence_detector
+            .on_ble_scan_result(BLE_SCAN_RESULT_SHORT_RANGE_ZONE)
+            .map(|estimate| estimate.proximity_state),
+        Some(ProximityState::ShortRange)
+    );
+    // NAN scan results use the NAN default tx power, with their own filters
+    presence_detector.on_scan_result(PresenceDataSource::Nan, BLE_SCAN_RESULT_SHORT_RANGE_ZONE);
+    assert_eq!(
+        presence_detector
+            .on_scan_result(PresenceDataSource::Nan, BLE_SCAN_RESULT_SHORT_RANGE_ZONE)
+            .map(|estimate| (estimate.source, estimate.proximity_state)),
+        Some((PresenceDataSource::Nan, ProximityState::Reach))
+    );
+}
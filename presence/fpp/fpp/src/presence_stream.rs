@@ -0,0 +1,170 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+use futures_sink::Sink;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::fused_presence_utils::{BleScanResult, ProximityEstimate, ProximityState};
+use crate::presence_detector::{PresenceDetector, ProximityStateListener};
+
+/// Events emitted by a presence event stream
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum PresenceEvent {
+    /// A new proximity estimate was confirmed for a device
+    ProximityEstimate(ProximityEstimate),
+    /// The proximity state of a device changed
+    ZoneChanged {
+        /// Device ID of the nearby device
+        device_id: u64,
+        /// Previously reported proximity state
+        old_state: ProximityState,
+        /// Newly reported proximity state
+        new_state: ProximityState,
+        /// Estimate that caused the transition
+        proximity_estimate: ProximityEstimate,
+    },
+    /// A device was evicted after not being seen for longer than the device TTL
+    DeviceLost {
+        /// Device ID of the nearby device
+        device_id: u64,
+    },
+}
+
+/// Error returned when sending a scan result into a closed sink
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct SinkClosedError;
+
+struct EventForwarder {
+    sender: UnboundedSender<PresenceEvent>,
+}
+
+impl ProximityStateListener for EventForwarder {
+    fn on_zone_changed(
+        &mut self,
+        device_id: u64,
+        old_state: ProximityState,
+        new_state: ProximityState,
+        proximity_estimate: ProximityEstimate,
+    ) {
+        // The stream may have been dropped, in which case nobody is interested
+        let _ = self.sender.send(PresenceEvent::ZoneChanged {
+            device_id,
+            old_state,
+            new_state,
+            proximity_estimate,
+        });
+    }
+
+    fn on_device_lost(&mut self, device_id: u64) {
+        let _ = self.sender.send(PresenceEvent::DeviceLost { device_id });
+    }
+}
+
+/// Sink of BLE scan results feeding a presence detector. Scan results are
+/// processed as soon as they are sent, so the sink is always ready
+pub struct ScanResultSink {
+    presence_detector: PresenceDetector,
+    sender: Option<UnboundedSender<PresenceEvent>>,
+    last_estimate_per_device: HashMap<u64, ProximityEstimate>,
+}
+
+impl ScanResultSink {
+    /// Changes the configuration of the wrapped presence detector. The
+    /// stream's proximity state listener is reinstalled afterwards, so any
+    /// listener set by `configure` is discarded rather than ending the stream
+    pub fn configure(&mut self, configure: impl FnOnce(&mut PresenceDetector)) {
+        configure(&mut self.presence_detector);
+        match self.sender.clone() {
+            Some(sender) => self
+                .presence_detector
+                .set_proximity_state_listener(Box::new(EventForwarder { sender })),
+            None => self.presence_detector.clear_proximity_state_listener(),
+        }
+    }
+}
+
+impl Sink<BleScanResult> for ScanResultSink {
+    type Error = SinkClosedError;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(if self.sender.is_some() { Ok(()) } else { Err(SinkClosedError) })
+    }
+
+    fn start_send(self: Pin<&mut Self>, ble_scan_result: BleScanResult) -> Result<(), Self::Error> {
+        let this = self.get_mut();
+        let sender = this.sender.as_ref().ok_or(SinkClosedError)?;
+        let device_id = ble_scan_result.device_id;
+        if let Some(proximity_estimate) = this.presence_detector.on_ble_scan_result(ble_scan_result)
+        {
+            if this.last_estimate_per_device.insert(device_id, proximity_estimate)
+                != Some(proximity_estimate)
+            {
+                let _ = sender.send(PresenceEvent::ProximityEstimate(proximity_estimate));
+            }
+        }
+        let presence_detector = &this.presence_detector;
+        this.last_estimate_per_device
+            .retain(|device_id, _| presence_detector.get_proximity_estimate(*device_id).is_some());
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        // Dropping every sender ends the event stream
+        this.presence_detector.clear_proximity_state_listener();
+        this.sender = None;
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Stream of confirmed proximity estimates and zone changes
+pub struct PresenceEventStream {
+    receiver: UnboundedReceiver<PresenceEvent>,
+}
+
+impl Stream for PresenceEventStream {
+    type Item = PresenceEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+/// Wraps a presence detector into a sink of scan results and a stream of the
+/// events they produce. The detector's proximity state listener is replaced
+/// by one forwarding zone changes and lost devices to the stream
+pub fn presence_event_channel(
+    mut presence_detector: PresenceDetector,
+) -> (ScanResultSink, PresenceEventStream) {
+    let (sender, receiver) = mpsc::unbounded_channel();
+    presence_detector
+        .set_proximity_state_listener(Box::new(EventForwarder { sender: sender.clone() }));
+    (
+        ScanResultSink {
+            presence_detector,
+            sender: Some(sender),
+            last_estimate_per_device: HashMap::new(),
+        },
+        PresenceEventStream { receiver },
+    )
+}
@@ -0,0 +1,103 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use futures::{SinkExt, StreamExt};
+
+use crate::clock::FakeClock;
+use crate::fused_presence_utils::*;
+use crate::presence_detector::PresenceDetector;
+use crate::presence_stream::*;
+
+const BLE_SCAN_RESULT_REACH_ZONE: BleScanResult = BleScanResult {
+    device_id: 1234,
+    tx_power: { MaybeTxPower::Invalid },
+    rssi: -40,
+    elapsed_real_time_millis: 123456,
+    advertising_channel: AdvertisingChannel::Unknown,
+};
+
+#[tokio::test]
+async fn test_stream_emits_confirmed_estimates_and_zone_changes() {
+    let (mut sink, stream) = presence_event_channel(PresenceDetector::new());
+    for _ in 0..2 {
+        assert_eq!(sink.send(BLE_SCAN_RESULT_REACH_ZONE).await, Ok(()));
+    }
+    assert_eq!(sink.close().await, Ok(()));
+    let events: Vec<PresenceEvent> = stream.collect().await;
+    assert_eq!(events.len(), 2);
+    assert!(matches!(
+        events.first(),
+        Some(PresenceEvent::ZoneChanged {
+            device_id: 1234,
+            old_state: ProximityState::Unknown,
+            new_state: ProximityState::Reach,
+            ..
+        })
+    ));
+    assert!(matches!(
+        events.get(1),
+        Some(PresenceEvent::ProximityEstimate(ProximityEstimate {
+            device_id: 1234,
+            proximity_state: ProximityState::Reach,
+            ..
+        }))
+    ));
+}
+
+#[tokio::test]
+async fn test_closed_sink_rejects_scan_results() {
+    let (mut sink, _stream) = presence_event_channel(PresenceDetector::new());
+    assert_eq!(sink.close().await, Ok(()));
+    assert_eq!(
+        sink.send(BLE_SCAN_RESULT_REACH_ZONE).await,
+        Err(SinkClosedError)
+    );
+}
+
+#[tokio::test]
+async fn test_stream_emits_lost_devices() {
+    let clock = FakeClock::new();
+    let mut presence_detector =
+        PresenceDetector::with_clock(ProximityStateOptions::default(), Box::new(clock.clone()));
+    presence_detector.set_device_ttl_millis(1000);
+    presence_detector.on_uwb_ranging_result(5678, 0.3, MeasurementConfidence::High);
+    let (mut sink, stream) = presence_event_channel(presence_detector);
+    clock.advance_millis(1001);
+    assert_eq!(sink.send(BLE_SCAN_RESULT_REACH_ZONE).await, Ok(()));
+    assert_eq!(sink.close().await, Ok(()));
+    let events: Vec<PresenceEvent> = stream.collect().await;
+    assert_eq!(events, vec![PresenceEvent::DeviceLost { device_id: 5678 }]);
+}
+
+#[tokio::test]
+async fn test_configure_keeps_stream_listener() {
+    let (mut sink, stream) = presence_event_channel(PresenceDetector::new());
+    sink.configure(|presence_detector| {
+        presence_detector.clear_proximity_state_listener();
+        presence_detector.set_device_ttl_millis(1000);
+    });
+    for _ in 0..2 {
+        assert_eq!(sink.send(BLE_SCAN_RESULT_REACH_ZONE).await, Ok(()));
+    }
+    assert_eq!(sink.close().await, Ok(()));
+    let events: Vec<PresenceEvent> = stream.collect().await;
+    assert!(matches!(
+        events.first(),
+        Some(PresenceEvent::ZoneChanged {
+            device_id: 1234,
+            new_state: ProximityState::Reach,
+            ..
+        })
+    ));
+}
@@ -0,0 +1,78 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::collections::Vec;
+
+/// Fixed capacity buffer of the most recent values, overwriting the oldest
+/// value once full. Its storage is allocated once, so that pushing values on
+/// the scan result hot path never allocates.
+pub(crate) struct RingBuffer<T> {
+    values: Vec<T>,
+    capacity: usize,
+    next_index: usize,
+    len: usize,
+}
+
+impl<T: Copy> RingBuffer<T> {
+    /// Creates an empty buffer holding up to `capacity` values, at least one
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        RingBuffer { values: Vec::with_capacity(capacity), capacity, next_index: 0, len: 0 }
+    }
+
+    /// Pushes a new value, dropping the oldest one if the buffer is full
+    pub(crate) fn push(&mut self, value: T) {
+        if self.values.len() < self.capacity {
+            self.values.push(value);
+        } else if let Some(slot) = self.values.get_mut(self.next_index) {
+            *slot = value;
+        }
+        self.next_index = (self.next_index + 1) % self.capacity;
+        self.len = (self.len + 1).min(self.capacity);
+    }
+
+    /// Drops all but the `len` most recent values
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.len = self.len.min(len);
+    }
+
+    /// Drops all values
+    pub(crate) fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Returns the number of values in the buffer
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Grows the buffer to hold at least `capacity` values, keeping its values
+    pub(crate) fn reserve(&mut self, capacity: usize) {
+        if capacity <= self.capacity {
+            return;
+        }
+        let mut ring_buffer = RingBuffer::with_capacity(capacity);
+        for value in self.iter().collect::<Vec<T>>().into_iter().rev() {
+            ring_buffer.push(value);
+        }
+        *self = ring_buffer;
+    }
+
+    /// Returns an iterator over the values, most recent first
+    pub(crate) fn iter(&self) -> impl Iterator<Item = T> + Clone + '_ {
+        (1..=self.len).filter_map(move |age| {
+            self.values.get((self.next_index + self.capacity - age) % self.capacity).copied()
+        })
+    }
+}
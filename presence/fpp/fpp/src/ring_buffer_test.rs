@@ -0,0 +1,61 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::ring_buffer::RingBuffer;
+
+fn values(ring_buffer: &RingBuffer<i32>) -> Vec<i32> {
+    ring_buffer.iter().collect()
+}
+
+#[test]
+fn test_push_overwrites_oldest_value() {
+    let mut ring_buffer = RingBuffer::with_capacity(3);
+    for value in 1..=5 {
+        ring_buffer.push(value);
+    }
+    assert_eq!(ring_buffer.len(), 3);
+    assert_eq!(values(&ring_buffer), vec![5, 4, 3]);
+}
+
+#[test]
+fn test_truncate_keeps_most_recent_values() {
+    let mut ring_buffer = RingBuffer::with_capacity(4);
+    for value in 1..=4 {
+        ring_buffer.push(value);
+    }
+    ring_buffer.truncate(2);
+    assert_eq!(values(&ring_buffer), vec![4, 3]);
+    ring_buffer.push(5);
+    assert_eq!(values(&ring_buffer), vec![5, 4, 3]);
+}
+
+#[test]
+fn test_clear() {
+    let mut ring_buffer = RingBuffer::with_capacity(2);
+    ring_buffer.push(1);
+    ring_buffer.clear();
+    assert!(values(&ring_buffer).is_empty());
+}
+
+#[test]
+fn test_reserve_keeps_values() {
+    let mut ring_buffer = RingBuffer::with_capacity(2);
+    for value in 1..=3 {
+        ring_buffer.push(value);
+    }
+    ring_buffer.reserve(4);
+    ring_buffer.push(4);
+    ring_buffer.push(5);
+    assert_eq!(values(&ring_buffer), vec![5, 4, 3, 2]);
+}
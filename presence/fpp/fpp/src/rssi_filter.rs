@@ -0,0 +1,69 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::collections::Vec;
+use crate::fused_presence_utils::RssiFilter;
+use crate::math::round;
+use crate::ring_buffer::RingBuffer;
+
+/// Per-device state of an RSSI filter
+pub(crate) struct RssiFilterState {
+    filter: RssiFilter,
+    recent_rssi: RingBuffer<i32>,
+    // Preallocated buffer the recent values are sorted in
+    sorted_rssi: Vec<i32>,
+}
+
+impl RssiFilterState {
+    pub(crate) fn new(filter: RssiFilter) -> Self {
+        let window = match filter {
+            RssiFilter::None => 0,
+            RssiFilter::MovingAverage { window } | RssiFilter::Median { window } => window.max(1),
+        };
+        RssiFilterState {
+            filter,
+            recent_rssi: RingBuffer::with_capacity(window),
+            sorted_rssi: Vec::with_capacity(window),
+        }
+    }
+
+    /// Feeds a new RSSI value into the filter and returns the filtered RSSI
+    pub(crate) fn update(&mut self, rssi: i32) -> i32 {
+        if self.filter == RssiFilter::None {
+            return rssi;
+        }
+        self.recent_rssi.push(rssi);
+        match self.filter {
+            RssiFilter::None => rssi,
+            RssiFilter::MovingAverage { .. } => {
+                let sum: i64 = self.recent_rssi.iter().map(i64::from).sum();
+                round(sum as f64 / self.recent_rssi.len() as f64) as i32
+            }
+            RssiFilter::Median { .. } => {
+                let sorted_rssi = &mut self.sorted_rssi;
+                sorted_rssi.clear();
+                sorted_rssi.extend(self.recent_rssi.iter());
+                sorted_rssi.sort_unstable();
+                let middle = sorted_rssi.len() / 2;
+                let upper = sorted_rssi.get(middle).copied().unwrap_or(rssi);
+                if sorted_rssi.len().is_multiple_of(2) {
+                    let lower = sorted_rssi.get(middle - 1).copied().unwrap_or(upper);
+                    round(f64::from(lower + upper) / 2.0) as i32
+                } else {
+                    upper
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::fused_presence_utils::RssiFilter;
+use crate::rssi_filter::RssiFilterState;
+
+fn filter_rssi(filter: RssiFilter, rssi_values: &[i32]) -> Vec<i32> {
+    let mut filter_state = RssiFilterState::new(filter);
+    rssi_values
+        .iter()
+        .map(|rssi| filter_state.update(*rssi))
+        .collect()
+}
+
+#[test]
+fn test_no_filter_returns_raw_rssi() {
+    assert_eq!(
+        filter_rssi(RssiFilter::None, &[-60, -80, -40]),
+        vec![-60, -80, -40]
+    );
+}
+
+#[test]
+fn test_moving_average_filter() {
+    assert_eq!(
+        filter_rssi(
+            RssiFilter::MovingAverage { window: 3 },
+            &[-60, -80, -40, -90]
+        ),
+        vec![-60, -70, -60, -70]
+    );
+}
+
+#[test]
+fn test_median_filter_rejects_spikes() {
+    assert_eq!(
+        filter_rssi(RssiFilter::Median { window: 3 }, &[-60, -61, -95, -59, -60]),
+        vec![-60, -61, -61, -61, -60]
+    );
+}
+
+#[test]
+fn test_median_filter_even_window() {
+    assert_eq!(
+        filter_rssi(RssiFilter::Median { window: 2 }, &[-60, -70]),
+        vec![-60, -65]
+    );
+}
+
+#[test]
+fn test_zero_window_behaves_like_no_filter() {
+    assert_eq!(
+        filter_rssi(RssiFilter::Median { window: 0 }, &[-60, -70]),
+        vec![-60, -70]
+    );
+}
@@ -0,0 +1,88 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::collections::Vec;
+use crate::fused_presence_utils::RssiOutlierRejection;
+use crate::ring_buffer::RingBuffer;
+
+/// Number of recent RSSI values required before any value is rejected
+const MIN_RSSI_SAMPLES_FOR_REJECTION: usize = 3;
+/// Lower bound of the median absolute deviation, since RSSI values are
+/// quantized to whole dBm and a perfectly stable window would otherwise reject
+/// any change at all
+const MIN_MEDIAN_ABSOLUTE_DEVIATION: f64 = 1.0;
+
+fn median(values: &mut [f64]) -> Option<f64> {
+    values.sort_unstable_by(f64::total_cmp);
+    let middle = values.len() / 2;
+    let upper = *values.get(middle)?;
+    if values.len().is_multiple_of(2) {
+        let lower = *values.get(middle - 1)?;
+        Some((lower + upper) / 2.0)
+    } else {
+        Some(upper)
+    }
+}
+
+/// Per-device state of an RSSI outlier rejection stage
+pub(crate) struct RssiOutlierFilterState {
+    rejection: RssiOutlierRejection,
+    recent_rssi: RingBuffer<i32>,
+    // Preallocated buffer the medians are computed in
+    values: Vec<f64>,
+}
+
+impl RssiOutlierFilterState {
+    pub(crate) fn new(rejection: RssiOutlierRejection) -> Self {
+        let window = match rejection {
+            RssiOutlierRejection::None => 0,
+            RssiOutlierRejection::MedianAbsoluteDeviation { window, .. } => window,
+        };
+        RssiOutlierFilterState {
+            rejection,
+            recent_rssi: RingBuffer::with_capacity(window),
+            values: Vec::with_capacity(window),
+        }
+    }
+
+    /// Feeds a new RSSI value into the rejection stage and returns whether it is
+    /// an outlier with respect to the previous values. Rejected values are still
+    /// recorded, so that a lasting change in RSSI is accepted once it makes up
+    /// most of the window.
+    pub(crate) fn is_outlier(&mut self, rssi: i32) -> bool {
+        let RssiOutlierRejection::MedianAbsoluteDeviation { max_deviations, .. } = self.rejection
+        else {
+            return false;
+        };
+        let is_outlier = self.recent_rssi.len() >= MIN_RSSI_SAMPLES_FOR_REJECTION
+            && self.is_far_from_median(f64::from(rssi), max_deviations);
+        self.recent_rssi.push(rssi);
+        is_outlier
+    }
+
+    fn is_far_from_median(&mut self, rssi: f64, max_deviations: f64) -> bool {
+        let values = &mut self.values;
+        values.clear();
+        values.extend(self.recent_rssi.iter().map(f64::from));
+        let Some(median_rssi) = median(values) else {
+            return false;
+        };
+        for value in values.iter_mut() {
+            *value = (*value - median_rssi).abs();
+        }
+        let median_absolute_deviation =
+            median(values).unwrap_or(0.0).max(MIN_MEDIAN_ABSOLUTE_DEVIATION);
+        (rssi - median_rssi).abs() > max_deviations * median_absolute_deviation
+    }
+}
@@ -0,0 +1,70 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use crate::fused_presence_utils::RssiOutlierRejection;
+use crate::rssi_outlier_filter::RssiOutlierFilterState;
+
+const MAD_REJECTION: RssiOutlierRejection = RssiOutlierRejection::MedianAbsoluteDeviation {
+    window: 5,
+    max_deviations: 3.0,
+};
+
+fn find_outliers(rejection: RssiOutlierRejection, rssi_values: &[i32]) -> Vec<bool> {
+    let mut filter_state = RssiOutlierFilterState::new(rejection);
+    rssi_values
+        .iter()
+        .map(|rssi| filter_state.is_outlier(*rssi))
+        .collect()
+}
+
+#[test]
+fn test_no_rejection_accepts_every_value() {
+    assert_eq!(
+        find_outliers(RssiOutlierRejection::None, &[-60, -60, -60, -95]),
+        vec![false, false, false, false]
+    );
+}
+
+#[test]
+fn test_single_spike_is_rejected() {
+    assert_eq!(
+        find_outliers(MAD_REJECTION, &[-60, -62, -61, -95, -60]),
+        vec![false, false, false, true, false]
+    );
+}
+
+#[test]
+fn test_small_deviation_of_stable_window_is_accepted() {
+    assert_eq!(
+        find_outliers(MAD_REJECTION, &[-60, -60, -60, -63, -57]),
+        vec![false, false, false, false, false]
+    );
+}
+
+#[test]
+fn test_too_few_samples_are_never_rejected() {
+    assert_eq!(
+        find_outliers(MAD_REJECTION, &[-60, -60, -95]),
+        vec![false, false, false]
+    );
+}
+
+#[test]
+fn test_lasting_change_is_accepted() {
+    assert_eq!(
+        find_outliers(MAD_REJECTION, &[-60, -60, -60, -80, -80, -80, -80]),
+        vec![false, false, false, true, true, true, false]
+    );
+}
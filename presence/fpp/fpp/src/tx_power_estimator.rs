@@ -0,0 +1,73 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::calibration::TxPowerModel;
+use crate::fused_presence_utils::{MaybeTxPower, PresenceDataSource};
+use crate::math::round;
+
+/// Range of valid advertised tx power levels in dBm. Values outside of it,
+/// e.g. 127 used by platforms for a missing tx power, are ignored
+const MIN_TX_POWER_DBM: i32 = -127;
+const MAX_TX_POWER_DBM: i32 = 20;
+
+/// Per-device estimate of the tx power, learned from advertised values
+pub(crate) struct TxPowerEstimatorState {
+    estimate_dbm: Option<f64>,
+}
+
+impl TxPowerEstimatorState {
+    pub(crate) fn new() -> Self {
+        TxPowerEstimatorState { estimate_dbm: None }
+    }
+
+    /// Feeds the tx power of a new scan result into the estimate and returns
+    /// the tx power in dBm to use for the scan result. Scan results without a
+    /// valid tx power use the learned estimate, or the model's default for
+    /// their data source before any tx power is advertised
+    pub(crate) fn update(
+        &mut self,
+        tx_power: MaybeTxPower,
+        source: PresenceDataSource,
+        tx_power_model: &TxPowerModel,
+    ) -> i32 {
+        let advertised_tx_power_dbm = match tx_power {
+            MaybeTxPower::Valid(tx_power_dbm)
+                if (MIN_TX_POWER_DBM..=MAX_TX_POWER_DBM).contains(&tx_power_dbm) =>
+            {
+                tx_power_dbm
+            }
+            _ => {
+                return self
+                    .get_estimate_dbm()
+                    .unwrap_or_else(|| tx_power_model.get_default_tx_power_dbm(source))
+            }
+        };
+        let learning_weight = tx_power_model.get_learning_weight();
+        if learning_weight <= 0.0 {
+            return advertised_tx_power_dbm;
+        }
+        let advertised_tx_power_dbm = f64::from(advertised_tx_power_dbm);
+        let estimate_dbm = self.estimate_dbm.map_or(advertised_tx_power_dbm, |estimate_dbm| {
+            estimate_dbm + learning_weight * (advertised_tx_power_dbm - estimate_dbm)
+        });
+        self.estimate_dbm = Some(estimate_dbm);
+        round(estimate_dbm) as i32
+    }
+
+    /// Returns the learned tx power estimate in dBm, None if the device has not
+    /// advertised a valid tx power yet
+    pub(crate) fn get_estimate_dbm(&self) -> Option<i32> {
+        self.estimate_dbm.map(|estimate_dbm| round(estimate_dbm) as i32)
+    }
+}
@@ -0,0 +1,89 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::calibration::TxPowerModel;
+use crate::fused_presence_utils::{MaybeTxPower, PresenceDataSource};
+use crate::tx_power_estimator::TxPowerEstimatorState;
+
+#[test]
+fn test_absent_tx_power_uses_source_default() {
+    let mut tx_power_model = TxPowerModel::new();
+    tx_power_model.set_default_tx_power_dbm(PresenceDataSource::Ble, -12);
+    let mut tx_power_estimator = TxPowerEstimatorState::new();
+    assert_eq!(
+        tx_power_estimator.update(MaybeTxPower::Invalid, PresenceDataSource::Ble, &tx_power_model),
+        -12
+    );
+    assert_eq!(tx_power_estimator.get_estimate_dbm(), None);
+}
+
+#[test]
+fn test_out_of_range_tx_power_is_ignored() {
+    let mut tx_power_estimator = TxPowerEstimatorState::new();
+    assert_eq!(
+        tx_power_estimator.update(
+            MaybeTxPower::Valid(127),
+            PresenceDataSource::Ble,
+            &TxPowerModel::new()
+        ),
+        1
+    );
+    assert_eq!(tx_power_estimator.get_estimate_dbm(), None);
+}
+
+#[test]
+fn test_tx_power_estimate_is_learned() {
+    let mut tx_power_estimator = TxPowerEstimatorState::new();
+    let mut tx_power_model = TxPowerModel::new();
+    tx_power_model.set_learning_weight(0.5);
+    assert_eq!(
+        tx_power_estimator.update(
+            MaybeTxPower::Valid(-20),
+            PresenceDataSource::Ble,
+            &tx_power_model
+        ),
+        -20
+    );
+    assert_eq!(
+        tx_power_estimator.update(
+            MaybeTxPower::Valid(-10),
+            PresenceDataSource::Ble,
+            &tx_power_model
+        ),
+        -15
+    );
+    // Scan results without tx power use the learned estimate
+    assert_eq!(
+        tx_power_estimator.update(MaybeTxPower::Invalid, PresenceDataSource::Ble, &tx_power_model),
+        -15
+    );
+    assert_eq!(tx_power_estimator.get_estimate_dbm(), Some(-15));
+}
+
+#[test]
+fn test_disabled_learning_uses_advertised_tx_power() {
+    let mut tx_power_estimator = TxPowerEstimatorState::new();
+    let mut tx_power_model = TxPowerModel::new();
+    tx_power_model.set_learning_weight(0.0);
+    tx_power_estimator.update(MaybeTxPower::Valid(-20), PresenceDataSource::Ble, &tx_power_model);
+    assert_eq!(
+        tx_power_estimator.update(
+            MaybeTxPower::Valid(-10),
+            PresenceDataSource::Ble,
+            &tx_power_model
+        ),
+        -10
+    );
+    assert_eq!(tx_power_estimator.get_estimate_dbm(), None);
+}
@@ -0,0 +1,138 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use itertools::Itertools;
+
+use crate::collections::Vec;
+use crate::ring_buffer::RingBuffer;
+
+const TRANSITION_HISTORY_TTL_MILLIS: u64 = 4000;
+
+/// Returns the index of the zone a distance falls into, given the increasing
+/// upper bounds of the zones. The index past the last bound stands for a
+/// distance beyond every bound.
+pub(crate) fn get_zone_index(distance_meters: f64, upper_bounds_meters: &[f64]) -> usize {
+    upper_bounds_meters.iter().take_while(|upper_bound| distance_meters > **upper_bound).count()
+}
+
+/// Tracks the confirmed zone of a single device among zones delimited by
+/// increasing upper bounds, applying hysteresis at the zone boundaries and
+/// requiring consecutive scans to agree before confirming a transition
+pub(crate) struct ZoneTracker {
+    current_zone_index: Option<usize>,
+    transition_history: RingBuffer<usize>,
+    last_update_millis: Option<u64>,
+}
+
+impl ZoneTracker {
+    pub(crate) fn new(consecutive_scans_required: u8) -> Self {
+        ZoneTracker {
+            current_zone_index: None,
+            transition_history: RingBuffer::with_capacity(consecutive_scans_required.into()),
+            last_update_millis: None,
+        }
+    }
+
+    /// Returns the confirmed zone index, None until a zone is confirmed
+    pub(crate) fn current_zone_index(&self) -> Option<usize> {
+        self.current_zone_index
+    }
+
+    /// Returns the zone the distance falls into, keeping the current zone until
+    /// the distance is past its boundaries by more than the hysteresis margin
+    fn get_hysteresis_adjusted_zone_index(
+        &self,
+        distance_meters: f64,
+        upper_bounds_meters: &[f64],
+        hysteresis_meters: f64,
+    ) -> usize {
+        let Some(current_zone_index) = self.current_zone_index else {
+            return get_zone_index(distance_meters, upper_bounds_meters);
+        };
+        let farther_zone_index =
+            get_zone_index(distance_meters - hysteresis_meters, upper_bounds_meters);
+        if farther_zone_index > current_zone_index {
+            return farther_zone_index;
+        }
+        let closer_zone_index =
+            get_zone_index(distance_meters + hysteresis_meters, upper_bounds_meters);
+        if closer_zone_index < current_zone_index {
+            return closer_zone_index;
+        }
+        current_zone_index
+    }
+
+    /// Returns the confirmed zone index, the unconfirmed transition history
+    /// (most recent first) and the time of its last update
+    pub(crate) fn export_state(
+        &self,
+    ) -> (Option<usize>, impl Iterator<Item = usize> + '_, Option<u64>) {
+        (self.current_zone_index, self.transition_history.iter(), self.last_update_millis)
+    }
+
+    /// Restores state previously returned by `export_state`
+    pub(crate) fn import_state(
+        &mut self,
+        current_zone_index: Option<usize>,
+        transition_history: impl IntoIterator<Item = usize>,
+        last_update_millis: Option<u64>,
+    ) {
+        self.current_zone_index = current_zone_index;
+        self.transition_history.clear();
+        let transition_history: Vec<usize> = transition_history.into_iter().collect();
+        self.transition_history.reserve(transition_history.len());
+        for zone_index in transition_history.into_iter().rev() {
+            self.transition_history.push(zone_index);
+        }
+        self.last_update_millis = last_update_millis;
+    }
+
+    /// Records a new distance measurement and returns the zone index once
+    /// enough consecutive scans agree on it, the number of scans required
+    /// depending on the zone
+    pub(crate) fn update(
+        &mut self,
+        distance_meters: f64,
+        elapsed_real_time_millis: u64,
+        upper_bounds_meters: &[f64],
+        hysteresis_meters: f64,
+        consecutive_scans_required: impl Fn(usize) -> u8,
+    ) -> Option<usize> {
+        if self.last_update_millis.is_some_and(|last_update_millis| {
+            elapsed_real_time_millis.saturating_sub(last_update_millis)
+                > TRANSITION_HISTORY_TTL_MILLIS
+        }) {
+            self.transition_history.clear();
+        }
+        self.last_update_millis = Some(elapsed_real_time_millis);
+        let zone_index = self.get_hysteresis_adjusted_zone_index(
+            distance_meters,
+            upper_bounds_meters,
+            hysteresis_meters,
+        );
+        // Older entries can't be part of the run of scans agreeing on the zone
+        let consecutive_scans_required = usize::from(consecutive_scans_required(zone_index).max(1));
+        // Only grows if the options changed since the tracker was created
+        self.transition_history.reserve(consecutive_scans_required);
+        self.transition_history.push(zone_index);
+        self.transition_history.truncate(consecutive_scans_required);
+        if self.transition_history.iter().all_equal()
+            && self.transition_history.len() == consecutive_scans_required
+        {
+            self.current_zone_index = Some(zone_index);
+            return Some(zone_index);
+        }
+        None
+    }
+}
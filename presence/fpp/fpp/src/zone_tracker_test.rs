@@ -0,0 +1,82 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::zone_tracker::*;
+
+const UPPER_BOUNDS_METERS: [f64; 3] = [0.3, 1.5, 5.0];
+
+#[test]
+fn test_get_zone_index() {
+    assert_eq!(get_zone_index(0.1, &UPPER_BOUNDS_METERS), 0);
+    assert_eq!(get_zone_index(0.3, &UPPER_BOUNDS_METERS), 0);
+    assert_eq!(get_zone_index(1.0, &UPPER_BOUNDS_METERS), 1);
+    assert_eq!(get_zone_index(4.0, &UPPER_BOUNDS_METERS), 2);
+    assert_eq!(get_zone_index(6.0, &UPPER_BOUNDS_METERS), 3);
+    assert_eq!(get_zone_index(6.0, &[]), 0);
+}
+
+#[test]
+fn test_update_requires_consecutive_scans() {
+    let mut zone_tracker = ZoneTracker::new(3);
+    assert_eq!(
+        zone_tracker.update(1.0, 0, &UPPER_BOUNDS_METERS, 0.1, |_| 3),
+        None
+    );
+    assert_eq!(
+        zone_tracker.update(1.0, 100, &UPPER_BOUNDS_METERS, 0.1, |_| 3),
+        None
+    );
+    assert_eq!(zone_tracker.current_zone_index(), None);
+    assert_eq!(
+        zone_tracker.update(1.0, 200, &UPPER_BOUNDS_METERS, 0.1, |_| 3),
+        Some(1)
+    );
+    assert_eq!(zone_tracker.current_zone_index(), Some(1));
+}
+
+#[test]
+fn test_update_applies_hysteresis() {
+    let mut zone_tracker = ZoneTracker::new(1);
+    assert_eq!(
+        zone_tracker.update(1.0, 0, &UPPER_BOUNDS_METERS, 0.1, |_| 1),
+        Some(1)
+    );
+    // Within the hysteresis margin of both boundaries of the current zone
+    assert_eq!(
+        zone_tracker.update(1.55, 100, &UPPER_BOUNDS_METERS, 0.1, |_| 1),
+        Some(1)
+    );
+    assert_eq!(
+        zone_tracker.update(0.1, 200, &UPPER_BOUNDS_METERS, 0.1, |_| 1),
+        Some(0)
+    );
+    assert_eq!(
+        zone_tracker.update(0.35, 300, &UPPER_BOUNDS_METERS, 0.1, |_| 1),
+        Some(0)
+    );
+    assert_eq!(
+        zone_tracker.update(0.45, 400, &UPPER_BOUNDS_METERS, 0.1, |_| 1),
+        Some(1)
+    );
+}
+
+#[test]
+fn test_stale_transition_history_is_cleared() {
+    let mut zone_tracker = ZoneTracker::new(2);
+    zone_tracker.update(1.0, 0, &UPPER_BOUNDS_METERS, 0.1, |_| 2);
+    assert_eq!(
+        zone_tracker.update(1.0, 10_000, &UPPER_BOUNDS_METERS, 0.1, |_| 2),
+        None
+    );
+}
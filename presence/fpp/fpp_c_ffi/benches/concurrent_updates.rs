@@ -0,0 +1,63 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Measures the throughput of scan result updates issued concurrently on
+//! distinct presence detectors. With a single global lock, the time per batch
+//! grows linearly with the number of threads; with the sharded handle map it
+//! stays roughly flat until the threads outnumber the cores.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use fpp::fused_presence_utils::{AdvertisingChannel, BleScanResult, MaybeTxPower};
+use fpp_c_ffi::{presence_detector_create, presence_detector_free, update_ble_scan_result};
+use std::thread;
+
+const UPDATES_PER_THREAD: u64 = 1000;
+
+fn update_concurrently(thread_count: usize) {
+    thread::scope(|scope| {
+        for _ in 0..thread_count {
+            scope.spawn(|| {
+                let presence_detector_handle = presence_detector_create();
+                for update in 0..UPDATES_PER_THREAD {
+                    update_ble_scan_result(
+                        presence_detector_handle,
+                        BleScanResult {
+                            device_id: update % 8,
+                            tx_power: MaybeTxPower::Invalid,
+                            rssi: -60,
+                            elapsed_real_time_millis: update,
+                            advertising_channel: AdvertisingChannel::Unknown,
+                        },
+                    );
+                }
+                presence_detector_free(presence_detector_handle);
+            });
+        }
+    });
+}
+
+fn bench_concurrent_updates(c: &mut Criterion) {
+    let mut group = c.benchmark_group("concurrent_updates");
+    for thread_count in [1, 2, 4, 8] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(thread_count),
+            &thread_count,
+            |b, thread_count| b.iter(|| update_concurrently(*thread_count)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_concurrent_updates);
+criterion_main!(benches);
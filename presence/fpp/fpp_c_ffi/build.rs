@@ -0,0 +1,30 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// Compiles the C header of the crate, so that a header left out of sync with
+// the API, or broken while editing it, fails the build instead of its C++
+// consumers. The object isn't linked into the crate.
+fn main() {
+    println!("cargo:rerun-if-changed=include/presence_detector.h");
+    println!("cargo:rerun-if-changed=src/header_check.cc");
+
+    cc::Build::new()
+        .cpp(true)
+        .std("c++17")
+        .include("include")
+        .file("src/header_check.cc")
+        .warnings_into_errors(true)
+        .cargo_metadata(false)
+        .compile("presence_detector_header_check");
+}
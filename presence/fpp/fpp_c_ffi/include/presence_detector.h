@@ -21,6 +21,28 @@
 #include <ostream>
 #include <new>
 
+/// Enum class representing possible outputs of proximity data processing call.
+/// Status codes 100+ are considered errors
+enum class ComputationStatus {
+  /// Returned if the proximity estimate calculation was successful
+  Success = 1,
+  /// Returned if there is no computed proximity estimate
+  NoComputedProximityEstimate = 2,
+  /// Returned if the scan results were queued for the presence detector's
+  /// worker thread
+  Queued = 3,
+  /// Returned if the handle is invalid
+  InvalidPresenceDetectorHandleError = 101,
+  /// Returned if the output parameter is null
+  NullOutputParameterError = 102,
+  /// Returned if an input array is null
+  NullInputParameterError = 103,
+  /// Returned if an output array is too small for the result
+  BufferTooSmallError = 104,
+  /// Returned if the maximum number of presence detectors is reached
+  CapacityExceededError = 105,
+};
+
 // Represents the confidence levels for a given measurement
 enum class MeasurementConfidence {
   /// Measurement confidence is low, the default for BLE medium
@@ -61,6 +83,18 @@ enum class ProximityState {
   Far,
 };
 
+/// BLE primary advertising channel a scan result was received on
+enum class AdvertisingChannel {
+  /// The advertising channel is unknown
+  Unknown,
+  /// Advertising channel 37 (2402 MHz)
+  Channel37,
+  /// Advertising channel 38 (2426 MHz)
+  Channel38,
+  /// Advertising channel 39 (2480 MHz)
+  Channel39,
+};
+
 /// Wraps the handle ID to an underlying PresenceDetector object
 struct PresenceDetectorHandle {
   uint64_t handle;
@@ -69,7 +103,7 @@ struct PresenceDetectorHandle {
 /// Enum representing an optional tx power value
 struct MaybeTxPower {
   enum class Tag {
-    /// Valid TX power with associated data value
+    /// Valid TX power with the advertised tx power level in dBm
     Valid,
     /// Absent Tx Power
     Invalid,
@@ -93,8 +127,122 @@ struct BleScanResult {
   MaybeTxPower tx_power;
   /// RSSI value
   int32_t rssi;
-  /// Time scan result was obtained
+  /// Time scan result was obtained, on the same time base as the presence
+  /// detector's clock, or 0 if unknown
   uint64_t elapsed_real_time_millis;
+  /// Advertising channel the scan result was received on
+  AdvertisingChannel advertising_channel;
+};
+
+/// Filter applied to consecutive BLE distance measurements of the same device
+struct DistanceFilter {
+  enum class Tag {
+    /// Raw distances are used as is
+    None,
+    /// Exponential moving average, where alpha in (0, 1] is the weight given
+    /// to the newest measurement
+    Exponential,
+    /// One dimensional Kalman filter assuming a stationary device
+    Kalman,
+  };
+
+  struct Exponential_Body {
+    /// Smoothing factor
+    double alpha;
+  };
+
+  struct Kalman_Body {
+    /// Variance of the change in distance between two measurements
+    double process_noise;
+    /// Variance of the measured distance
+    double measurement_noise;
+  };
+
+  Tag tag;
+  union {
+    Exponential_Body exponential;
+    Kalman_Body kalman;
+  };
+};
+
+/// Filter applied to the RSSI of consecutive scan results of the same device
+struct RssiFilter {
+  enum class Tag {
+    /// Raw RSSI values are used as is
+    None,
+    /// Mean of the most recent RSSI values
+    MovingAverage,
+    /// Median of the most recent RSSI values
+    Median,
+  };
+
+  struct MovingAverage_Body {
+    /// Number of recent RSSI values to average
+    uintptr_t window;
+  };
+
+  struct Median_Body {
+    /// Number of recent RSSI values to take the median of
+    uintptr_t window;
+  };
+
+  Tag tag;
+  union {
+    MovingAverage_Body moving_average;
+    Median_Body median;
+  };
+};
+
+/// Rejection of outlying RSSI values, e.g. single reflected or attenuated
+/// packets, before they reach the RSSI filter
+struct RssiOutlierRejection {
+  enum class Tag {
+    /// Every RSSI value is accepted
+    None,
+    /// Rejects RSSI values farther from the median of the recent RSSI values
+    /// than a multiple of their median absolute deviation
+    MedianAbsoluteDeviation,
+  };
+
+  struct MedianAbsoluteDeviation_Body {
+    /// Number of recent RSSI values the median is computed over
+    uintptr_t window;
+    /// Number of median absolute deviations past which a value is rejected
+    double max_deviations;
+  };
+
+  Tag tag;
+  union {
+    MedianAbsoluteDeviation_Body median_absolute_deviation;
+  };
+};
+
+/// Options used to compute the proximity state of nearby devices
+struct ProximityStateOptions {
+  /// Upper bound of the tap zone in meters
+  double tap_distance_threshold_meters;
+  /// Upper bound of the reach zone in meters
+  double reach_distance_threshold_meters;
+  /// Upper bound of the short range zone in meters
+  double short_range_distance_threshold_meters;
+  /// Upper bound of the long range zone in meters
+  double long_range_distance_threshold_meters;
+  /// Distance in meters a device must move past a zone boundary before it is
+  /// considered to have left its current zone
+  double hysteresis_meters;
+  /// Number of consecutive scans that must agree on a new zone before a
+  /// transition is confirmed
+  uint8_t consecutive_scans_required;
+  /// Number of consecutive scans that must agree before a transition into
+  /// each zone, ordered from tap to far, is confirmed. Zones set to 0 use
+  /// `consecutive_scans_required`
+  uint8_t consecutive_scans_required_per_zone[5];
+  /// Outlier rejection applied to BLE RSSI values before filtering
+  RssiOutlierRejection rssi_outlier_rejection;
+  /// Filter applied to BLE RSSI values before distance conversion
+  RssiFilter rssi_filter;
+  /// Filter applied to BLE distances before zone classification
+  DistanceFilter distance_filter;
 };
 
 /// Describes the most accurate and recent measurement for a given device
@@ -113,33 +261,153 @@ struct ProximityEstimate {
   PresenceDataSource source;
 };
 
+/// Result of every presence detector call: a status and, if the call computed
+/// a single proximity estimate successfully, that estimate
+struct PresenceDetectorResult {
+  /// Outcome of the call
+  ComputationStatus status;
+  /// Proximity estimate computed by the call, only meaningful if the status
+  /// is Success and the call computes a single estimate
+  ProximityEstimate proximity_estimate;
+};
+
+/// Callback returning the caller's monotonic time in milliseconds, in the same
+/// time base as the timestamps of the scan results it provides
+using ElapsedRealTimeCallback = uint64_t (*)(void *user_data);
+
+/// Callback invoked with the caller's user data on confirmed zone transitions
+using ZoneChangedCallback = void (*)(void *user_data, uint64_t device_id,
+                                     ProximityState old_state,
+                                     ProximityState new_state,
+                                     ProximityEstimate proximity_estimate);
+
 extern "C" {
 
 /// Creates a new presence detector object and returns the handle for the new
-/// object
+/// object, or a null handle (0) if the maximum number of presence detectors is
+/// reached
 PresenceDetectorHandle presence_detector_create();
 
-/// Updates PresenceDetector with a new scan result and returns an error code
-/// if unsuccessful
+/// Creates a new presence detector object reading the current time from a
+/// callback, and returns the handle for the new object, or a null handle (0)
+/// if the maximum number of presence detectors is reached. Use it when scan
+/// results carry timestamps from a platform clock, e.g. Android's
+/// elapsedRealtime: presence detectors created otherwise count time from
+/// their creation, and clamp later timestamps to that time
+///
+/// # Safety
+///
+/// Ensure that the callback and its user data remain valid until the presence
+/// detector is freed, and that they may be used from any thread using the
+/// presence detector
+PresenceDetectorHandle presence_detector_create_with_clock(
+    ElapsedRealTimeCallback clock, void *user_data);
+
+/// Creates a new presence detector object, writing the handle for the new
+/// object to the output parameter, and returns the status of the call
 ///
 /// # Safety
 ///
 /// Ensure that the output parameter refers to an initialized instance
-int32_t update_ble_scan_result(PresenceDetectorHandle presence_detector_handle,
-                               BleScanResult ble_scan_result,
-                               ProximityEstimate *proximity_estimate);
+PresenceDetectorResult presence_detector_try_create(
+    PresenceDetectorHandle *presence_detector_handle);
+
+/// Returns the number of presence detector objects that have not been freed
+uintptr_t presence_detector_count();
+
+/// Limits the number of presence detector objects that can exist at the same
+/// time, 0 meaning unlimited. Existing objects beyond the capacity are kept,
+/// but creating new ones fails until enough of them are freed
+void presence_detector_set_capacity(uintptr_t capacity);
+
+/// Updates PresenceDetector with a new scan result and returns the current
+/// proximity estimate of the scanned device. In worker thread mode, the scan
+/// result is queued instead and a Queued status is returned immediately
+PresenceDetectorResult update_ble_scan_result(
+    PresenceDetectorHandle presence_detector_handle,
+    BleScanResult ble_scan_result);
+
+/// Updates PresenceDetector with a batch of scan results under a single lock.
+/// One result is written to the output array per scan result, at the same
+/// index: the device's proximity estimate with a Success status, or a
+/// NoComputedProximityEstimate status if none was computed. Returns a Success
+/// status if any proximity estimate was computed. In worker thread mode, the
+/// scan results are queued instead, every output result gets a Queued status
+/// and a Queued status is returned immediately. If the worker stops because
+/// the handle is freed while the scan results are queued, an error status is
+/// returned and some of them may still be processed
+///
+/// # Safety
+///
+/// Ensure that the scan results point to `count` initialized instances and
+/// that the output array has room for `count` results
+PresenceDetectorResult update_ble_scan_results(
+    PresenceDetectorHandle presence_detector_handle,
+    const BleScanResult *ble_scan_results, uintptr_t count,
+    PresenceDetectorResult *results);
+
+/// Replaces the options used to compute proximity states, e.g. zone thresholds,
+/// hysteresis and the number of consecutive scans required for a transition,
+/// and returns the status of the call
+PresenceDetectorResult presence_detector_configure_options(
+    PresenceDetectorHandle presence_detector_handle,
+    ProximityStateOptions options);
+
+/// Registers a callback invoked on confirmed zone transitions of the presence
+/// detector's devices, replacing any previously registered callback. Passing a
+/// null callback clears the registration. Returns the status of the call. The
+/// callback is invoked after the presence detector is released, so it may
+/// call the functions of this API, including with the same handle
+///
+/// # Safety
+///
+/// Ensure that the callback and its user data remain valid until the callback
+/// is cleared or the presence detector is freed, and that they may be used
+/// from any thread updating the presence detector
+PresenceDetectorResult presence_detector_set_zone_callback(
+    PresenceDetectorHandle presence_detector_handle,
+    ZoneChangedCallback callback, void *user_data);
+
+/// Enables or disables the worker thread mode of a presence detector, and
+/// returns the status of the call. In worker thread mode, scan result updates
+/// are queued to a thread owned by the presence detector and return
+/// immediately, so that the calling thread never waits for the computation.
+/// Zone transitions are then delivered through the zone callback, invoked
+/// from the worker thread, and scan results should carry their timestamp
+/// since they are processed later. Disabling the mode waits until the queued
+/// scan results are processed, except when called from the zone callback on
+/// the worker thread: the worker then processes the remaining scan results
+/// after the callback returns. The zone callback may likewise free the
+/// presence detector, and may call any other function of this API
+PresenceDetectorResult presence_detector_set_worker_mode(
+    PresenceDetectorHandle presence_detector_handle, bool enabled);
 
 /// Gets the current proximity estimate for a given device ID
+PresenceDetectorResult get_proximity_estimate(
+    PresenceDetectorHandle presence_detector_handle, uint64_t device_id);
+
+/// Gets the current proximity estimates of all tracked devices, ordered by
+/// device ID, writing them to the output array and their number to the output
+/// count. If the output array's capacity is too small, nothing is written to
+/// it, the output count is set to the required capacity and an error status
+/// is returned
 ///
 /// # Safety
 ///
-/// Ensure that the output parameter refers to an initialized instance
-int32_t get_proximity_estimate(PresenceDetectorHandle presence_detector_handle,
-                               uint64_t device_id,
-                               ProximityEstimate *proximity_estimate);
+/// Ensure that the output array has room for `capacity` proximity estimates
+/// and that the output count refers to an initialized instance
+PresenceDetectorResult get_all_proximity_estimates(
+    PresenceDetectorHandle presence_detector_handle,
+    ProximityEstimate *proximity_estimates, uintptr_t capacity,
+    uintptr_t *proximity_estimate_count);
+
+/// De-allocates memory for all presence detector objects, invalidating every
+/// handle, and returns the number of freed objects
+uintptr_t presence_detector_free_all();
 
 /// De-allocates memory for a presence detector object
-int presence_detector_free(PresenceDetectorHandle presence_detector_handle);
+PresenceDetectorResult presence_detector_free(
+    PresenceDetectorHandle presence_detector_handle);
 
 #ifdef __cplusplus
 }  // extern "C"
@@ -0,0 +1,47 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::os::raw::c_void;
+
+use fpp::clock::Clock;
+
+/// Callback returning the caller's monotonic time in milliseconds, in the same
+/// time base as the timestamps of the scan results it provides
+pub type ElapsedRealTimeCallback = extern "C" fn(user_data: *mut c_void) -> u64;
+
+/// Clock reading the time from a C callback
+pub(crate) struct CallbackClock {
+    callback: ElapsedRealTimeCallback,
+    user_data: *mut c_void,
+}
+
+// The user data is opaque to the clock and only handed back to the callback.
+// Callers providing a clock guarantee that it may be read from any thread
+// using the presence detector.
+unsafe impl Send for CallbackClock {}
+
+impl CallbackClock {
+    pub(crate) fn new(callback: ElapsedRealTimeCallback, user_data: *mut c_void) -> Self {
+        CallbackClock {
+            callback,
+            user_data,
+        }
+    }
+}
+
+impl Clock for CallbackClock {
+    fn elapsed_real_time_millis(&self) -> u64 {
+        (self.callback)(self.user_data)
+    }
+}
@@ -0,0 +1,542 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ptr;
+
+use fpp::fused_presence_utils::*;
+
+use crate::handle_map::get_presence_detector_handle_map;
+use crate::*;
+
+const BLE_SCAN_RESULT_REACH_ZONE: BleScanResult = BleScanResult {
+    device_id: 1234,
+    tx_power: MaybeTxPower::Invalid,
+    rssi: -40,
+    elapsed_real_time_millis: 123456,
+    advertising_channel: AdvertisingChannel::Unknown,
+};
+
+const INVALID_HANDLE: PresenceDetectorHandle = PresenceDetectorHandle { handle: 0 };
+
+fn empty_proximity_estimate() -> ProximityEstimate {
+    PresenceDetectorResult::from(ComputationStatus::Success).proximity_estimate
+}
+
+fn create_presence_detector_in_reach_zone() -> PresenceDetectorHandle {
+    let presence_detector_handle = presence_detector_create();
+    update_ble_scan_result(presence_detector_handle, BLE_SCAN_RESULT_REACH_ZONE);
+    update_ble_scan_result(presence_detector_handle, BLE_SCAN_RESULT_REACH_ZONE);
+    presence_detector_handle
+}
+
+#[test]
+fn test_update_ble_scan_result() {
+    let presence_detector_handle = presence_detector_create();
+    assert_eq!(
+        update_ble_scan_result(presence_detector_handle, BLE_SCAN_RESULT_REACH_ZONE).status,
+        ComputationStatus::NoComputedProximityEstimate
+    );
+    let result = update_ble_scan_result(presence_detector_handle, BLE_SCAN_RESULT_REACH_ZONE);
+    assert_eq!(result.status, ComputationStatus::Success);
+    assert_eq!(result.proximity_estimate.device_id, 1234);
+    assert_eq!(
+        result.proximity_estimate.proximity_state,
+        ProximityState::Reach
+    );
+    presence_detector_free(presence_detector_handle);
+}
+
+extern "C" fn scan_time_clock(_user_data: *mut std::os::raw::c_void) -> u64 {
+    BLE_SCAN_RESULT_REACH_ZONE.elapsed_real_time_millis
+}
+
+#[test]
+fn test_create_with_clock_uses_scan_timestamps() {
+    let presence_detector_handle =
+        unsafe { presence_detector_create_with_clock(scan_time_clock, ptr::null_mut()) };
+    update_ble_scan_result(presence_detector_handle, BLE_SCAN_RESULT_REACH_ZONE);
+    let result = update_ble_scan_result(presence_detector_handle, BLE_SCAN_RESULT_REACH_ZONE);
+    assert_eq!(result.status, ComputationStatus::Success);
+    assert_eq!(result.proximity_estimate.elapsed_real_time_millis, 123456);
+    presence_detector_free(presence_detector_handle);
+}
+
+#[test]
+fn test_update_ble_scan_result_invalid_handle() {
+    assert_eq!(
+        update_ble_scan_result(INVALID_HANDLE, BLE_SCAN_RESULT_REACH_ZONE).status,
+        ComputationStatus::InvalidPresenceDetectorHandleError
+    );
+}
+
+#[test]
+fn test_get_proximity_estimate() {
+    let presence_detector_handle = create_presence_detector_in_reach_zone();
+    let result = get_proximity_estimate(presence_detector_handle, 1234);
+    assert_eq!(result.status, ComputationStatus::Success);
+    assert_eq!(
+        result.proximity_estimate.proximity_state,
+        ProximityState::Reach
+    );
+    presence_detector_free(presence_detector_handle);
+}
+
+#[test]
+fn test_get_proximity_estimate_no_computed_estimate() {
+    let presence_detector_handle = presence_detector_create();
+    assert_eq!(
+        get_proximity_estimate(presence_detector_handle, 1234).status,
+        ComputationStatus::NoComputedProximityEstimate
+    );
+    presence_detector_free(presence_detector_handle);
+}
+
+#[test]
+fn test_get_proximity_estimate_invalid_handle() {
+    assert_eq!(
+        get_proximity_estimate(INVALID_HANDLE, 1234).status,
+        ComputationStatus::InvalidPresenceDetectorHandleError
+    );
+}
+
+#[test]
+fn test_update_ble_scan_results() {
+    let presence_detector_handle = presence_detector_create();
+    let ble_scan_results = [
+        BLE_SCAN_RESULT_REACH_ZONE,
+        BleScanResult {
+            device_id: 5678,
+            ..BLE_SCAN_RESULT_REACH_ZONE
+        },
+        BLE_SCAN_RESULT_REACH_ZONE,
+    ];
+    let mut results = [PresenceDetectorResult::from(ComputationStatus::Success); 3];
+    let result = unsafe {
+        update_ble_scan_results(
+            presence_detector_handle,
+            ble_scan_results.as_ptr(),
+            ble_scan_results.len(),
+            results.as_mut_ptr(),
+        )
+    };
+    assert_eq!(result.status, ComputationStatus::Success);
+    // Results are written at the index of their scan result
+    assert_eq!(
+        results.map(|result| result.status),
+        [
+            ComputationStatus::NoComputedProximityEstimate,
+            ComputationStatus::NoComputedProximityEstimate,
+            ComputationStatus::Success
+        ]
+    );
+    assert_eq!(results[2].proximity_estimate.device_id, 1234);
+    assert_eq!(
+        results[2].proximity_estimate.proximity_state,
+        ProximityState::Reach
+    );
+    presence_detector_free(presence_detector_handle);
+}
+
+#[test]
+fn test_update_ble_scan_results_no_computed_estimate() {
+    let presence_detector_handle = presence_detector_create();
+    let ble_scan_results = [BLE_SCAN_RESULT_REACH_ZONE];
+    let mut results = [PresenceDetectorResult::from(ComputationStatus::Success)];
+    let result = unsafe {
+        update_ble_scan_results(
+            presence_detector_handle,
+            ble_scan_results.as_ptr(),
+            ble_scan_results.len(),
+            results.as_mut_ptr(),
+        )
+    };
+    assert_eq!(
+        result.status,
+        ComputationStatus::NoComputedProximityEstimate
+    );
+    assert_eq!(
+        results[0].status,
+        ComputationStatus::NoComputedProximityEstimate
+    );
+    presence_detector_free(presence_detector_handle);
+}
+
+#[test]
+fn test_update_ble_scan_results_null_parameters() {
+    let presence_detector_handle = presence_detector_create();
+    let ble_scan_results = [BLE_SCAN_RESULT_REACH_ZONE];
+    let mut results = [PresenceDetectorResult::from(ComputationStatus::Success)];
+    unsafe {
+        assert_eq!(
+            update_ble_scan_results(
+                presence_detector_handle,
+                ptr::null(),
+                1,
+                results.as_mut_ptr()
+            )
+            .status,
+            ComputationStatus::NullInputParameterError
+        );
+        assert_eq!(
+            update_ble_scan_results(
+                presence_detector_handle,
+                ble_scan_results.as_ptr(),
+                1,
+                ptr::null_mut(),
+            )
+            .status,
+            ComputationStatus::NullOutputParameterError
+        );
+    }
+    presence_detector_free(presence_detector_handle);
+}
+
+#[test]
+fn test_get_all_proximity_estimates() {
+    let presence_detector_handle = create_presence_detector_in_reach_zone();
+    let mut proximity_estimates = [empty_proximity_estimate(); 2];
+    let mut proximity_estimate_count = 0;
+    let result = unsafe {
+        get_all_proximity_estimates(
+            presence_detector_handle,
+            proximity_estimates.as_mut_ptr(),
+            proximity_estimates.len(),
+            &mut proximity_estimate_count,
+        )
+    };
+    assert_eq!(result.status, ComputationStatus::Success);
+    assert_eq!(proximity_estimate_count, 1);
+    assert_eq!(proximity_estimates[0].device_id, 1234);
+    presence_detector_free(presence_detector_handle);
+}
+
+#[test]
+fn test_get_all_proximity_estimates_buffer_too_small() {
+    let presence_detector_handle = create_presence_detector_in_reach_zone();
+    let mut proximity_estimate_count = 0;
+    let result = unsafe {
+        get_all_proximity_estimates(
+            presence_detector_handle,
+            ptr::null_mut(),
+            0,
+            &mut proximity_estimate_count,
+        )
+    };
+    assert_eq!(result.status, ComputationStatus::BufferTooSmallError);
+    assert_eq!(proximity_estimate_count, 1);
+    presence_detector_free(presence_detector_handle);
+}
+
+#[test]
+fn test_configure_options() {
+    let presence_detector_handle = presence_detector_create();
+    let options = ProximityStateOptions {
+        consecutive_scans_required: 1,
+        ..ProximityStateOptions::default()
+    };
+    assert_eq!(
+        presence_detector_configure_options(presence_detector_handle, options).status,
+        ComputationStatus::Success
+    );
+    assert_eq!(
+        update_ble_scan_result(presence_detector_handle, BLE_SCAN_RESULT_REACH_ZONE).status,
+        ComputationStatus::Success
+    );
+    assert_eq!(
+        presence_detector_configure_options(INVALID_HANDLE, options).status,
+        ComputationStatus::InvalidPresenceDetectorHandleError
+    );
+    presence_detector_free(presence_detector_handle);
+}
+
+#[test]
+fn test_presence_detector_free() {
+    let presence_detector_handle = presence_detector_create();
+    assert_eq!(
+        presence_detector_free(presence_detector_handle).status,
+        ComputationStatus::Success
+    );
+    assert_eq!(
+        presence_detector_free(presence_detector_handle).status,
+        ComputationStatus::InvalidPresenceDetectorHandleError
+    );
+}
+
+extern "C" fn count_zone_changes(
+    user_data: *mut std::os::raw::c_void,
+    _device_id: u64,
+    _old_state: ProximityState,
+    _new_state: ProximityState,
+    _proximity_estimate: ProximityEstimate,
+) {
+    if let Some(zone_change_count) = unsafe { user_data.cast::<u32>().as_mut() } {
+        *zone_change_count += 1;
+    }
+}
+
+#[test]
+fn test_set_zone_callback() {
+    let presence_detector_handle = presence_detector_create();
+    let mut zone_change_count: u32 = 0;
+    let result = unsafe {
+        presence_detector_set_zone_callback(
+            presence_detector_handle,
+            Some(count_zone_changes),
+            ptr::addr_of_mut!(zone_change_count).cast(),
+        )
+    };
+    assert_eq!(result.status, ComputationStatus::Success);
+    for _ in 0..3 {
+        update_ble_scan_result(presence_detector_handle, BLE_SCAN_RESULT_REACH_ZONE);
+    }
+    let result = unsafe {
+        presence_detector_set_zone_callback(presence_detector_handle, None, ptr::null_mut())
+    };
+    assert_eq!(result.status, ComputationStatus::Success);
+    assert_eq!(zone_change_count, 1);
+    presence_detector_free(presence_detector_handle);
+}
+
+/// Records the proximity state read back through the API from a zone callback
+struct ReentrantZoneCallbackData {
+    presence_detector_handle: PresenceDetectorHandle,
+    proximity_state: ProximityState,
+}
+
+extern "C" fn read_estimate_on_zone_change(
+    user_data: *mut std::os::raw::c_void,
+    device_id: u64,
+    _old_state: ProximityState,
+    _new_state: ProximityState,
+    _proximity_estimate: ProximityEstimate,
+) {
+    if let Some(data) = unsafe { user_data.cast::<ReentrantZoneCallbackData>().as_mut() } {
+        data.proximity_state = get_proximity_estimate(data.presence_detector_handle, device_id)
+            .proximity_estimate
+            .proximity_state;
+    }
+}
+
+#[test]
+fn test_zone_callback_may_call_back_into_api() {
+    let presence_detector_handle = presence_detector_create();
+    let mut data = ReentrantZoneCallbackData {
+        presence_detector_handle,
+        proximity_state: ProximityState::Unknown,
+    };
+    unsafe {
+        presence_detector_set_zone_callback(
+            presence_detector_handle,
+            Some(read_estimate_on_zone_change),
+            ptr::addr_of_mut!(data).cast(),
+        );
+    }
+    for _ in 0..2 {
+        update_ble_scan_result(presence_detector_handle, BLE_SCAN_RESULT_REACH_ZONE);
+    }
+    assert_eq!(data.proximity_state, ProximityState::Reach);
+    presence_detector_free(presence_detector_handle);
+}
+
+#[test]
+fn test_worker_mode() {
+    let presence_detector_handle = presence_detector_create();
+    let mut zone_change_count: u32 = 0;
+    unsafe {
+        presence_detector_set_zone_callback(
+            presence_detector_handle,
+            Some(count_zone_changes),
+            ptr::addr_of_mut!(zone_change_count).cast(),
+        );
+    }
+    assert_eq!(
+        presence_detector_set_worker_mode(presence_detector_handle, true).status,
+        ComputationStatus::Success
+    );
+    for _ in 0..2 {
+        assert_eq!(
+            update_ble_scan_result(presence_detector_handle, BLE_SCAN_RESULT_REACH_ZONE).status,
+            ComputationStatus::Queued
+        );
+    }
+    // Disabling the worker thread mode waits for the queued scan results
+    assert_eq!(
+        presence_detector_set_worker_mode(presence_detector_handle, false).status,
+        ComputationStatus::Success
+    );
+    assert_eq!(zone_change_count, 1);
+    assert_eq!(
+        get_proximity_estimate(presence_detector_handle, 1234)
+            .proximity_estimate
+            .proximity_state,
+        ProximityState::Reach
+    );
+    assert_eq!(
+        update_ble_scan_result(presence_detector_handle, BLE_SCAN_RESULT_REACH_ZONE).status,
+        ComputationStatus::Success
+    );
+    presence_detector_free(presence_detector_handle);
+}
+
+#[test]
+fn test_worker_mode_batch_update() {
+    let presence_detector_handle = presence_detector_create();
+    presence_detector_set_worker_mode(presence_detector_handle, true);
+    let ble_scan_results = [BLE_SCAN_RESULT_REACH_ZONE; 2];
+    let mut results = [PresenceDetectorResult::from(ComputationStatus::Success); 2];
+    let result = unsafe {
+        update_ble_scan_results(
+            presence_detector_handle,
+            ble_scan_results.as_ptr(),
+            ble_scan_results.len(),
+            results.as_mut_ptr(),
+        )
+    };
+    assert_eq!(result.status, ComputationStatus::Queued);
+    assert_eq!(
+        results.map(|result| result.status),
+        [ComputationStatus::Queued; 2]
+    );
+    presence_detector_set_worker_mode(presence_detector_handle, false);
+    assert_eq!(
+        get_proximity_estimate(presence_detector_handle, 1234).status,
+        ComputationStatus::Success
+    );
+    presence_detector_free(presence_detector_handle);
+}
+
+#[test]
+fn test_worker_mode_stopped_worker() {
+    let presence_detector_handle = presence_detector_create();
+    presence_detector_set_worker_mode(presence_detector_handle, true);
+    // Removing the presence detector behind the worker's back stops the worker
+    // at the next scan result it processes
+    get_presence_detector_handle_map().remove(&presence_detector_handle.handle);
+    let mut status = ComputationStatus::Queued;
+    for _ in 0..1000 {
+        status =
+            update_ble_scan_result(presence_detector_handle, BLE_SCAN_RESULT_REACH_ZONE).status;
+        if status != ComputationStatus::Queued {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    assert_eq!(
+        status,
+        ComputationStatus::InvalidPresenceDetectorHandleError
+    );
+    let ble_scan_results = [BLE_SCAN_RESULT_REACH_ZONE; 2];
+    let mut results = [PresenceDetectorResult::from(ComputationStatus::Success); 2];
+    let result = unsafe {
+        update_ble_scan_results(
+            presence_detector_handle,
+            ble_scan_results.as_ptr(),
+            ble_scan_results.len(),
+            results.as_mut_ptr(),
+        )
+    };
+    assert_eq!(
+        result.status,
+        ComputationStatus::InvalidPresenceDetectorHandleError
+    );
+    crate::worker::stop_worker(presence_detector_handle.handle);
+}
+
+extern "C" fn disable_worker_mode_on_zone_change(
+    user_data: *mut std::os::raw::c_void,
+    _device_id: u64,
+    _old_state: ProximityState,
+    _new_state: ProximityState,
+    _proximity_estimate: ProximityEstimate,
+) {
+    if let Some(presence_detector_handle) =
+        unsafe { user_data.cast::<PresenceDetectorHandle>().as_ref() }
+    {
+        presence_detector_set_worker_mode(*presence_detector_handle, false);
+    }
+}
+
+extern "C" fn free_on_zone_change(
+    user_data: *mut std::os::raw::c_void,
+    _device_id: u64,
+    _old_state: ProximityState,
+    _new_state: ProximityState,
+    _proximity_estimate: ProximityEstimate,
+) {
+    if let Some(presence_detector_handle) =
+        unsafe { user_data.cast::<PresenceDetectorHandle>().as_ref() }
+    {
+        presence_detector_free(*presence_detector_handle);
+    }
+}
+
+/// Waits up to a second for a condition to hold, and returns whether it did
+fn wait_until(condition: impl Fn() -> bool) -> bool {
+    for _ in 0..1000 {
+        if condition() {
+            return true;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    false
+}
+
+#[test]
+fn test_zone_callback_may_disable_worker_mode() {
+    let mut presence_detector_handle = presence_detector_create();
+    unsafe {
+        presence_detector_set_zone_callback(
+            presence_detector_handle,
+            Some(disable_worker_mode_on_zone_change),
+            ptr::addr_of_mut!(presence_detector_handle).cast(),
+        );
+    }
+    presence_detector_set_worker_mode(presence_detector_handle, true);
+    for _ in 0..2 {
+        update_ble_scan_result(presence_detector_handle, BLE_SCAN_RESULT_REACH_ZONE);
+    }
+    assert!(wait_until(|| {
+        update_ble_scan_result(presence_detector_handle, BLE_SCAN_RESULT_REACH_ZONE).status
+            != ComputationStatus::Queued
+    }));
+    presence_detector_free(presence_detector_handle);
+}
+
+#[test]
+fn test_zone_callback_may_free_presence_detector() {
+    let mut presence_detector_handle = presence_detector_create();
+    unsafe {
+        presence_detector_set_zone_callback(
+            presence_detector_handle,
+            Some(free_on_zone_change),
+            ptr::addr_of_mut!(presence_detector_handle).cast(),
+        );
+    }
+    presence_detector_set_worker_mode(presence_detector_handle, true);
+    for _ in 0..2 {
+        update_ble_scan_result(presence_detector_handle, BLE_SCAN_RESULT_REACH_ZONE);
+    }
+    assert!(wait_until(|| {
+        get_proximity_estimate(presence_detector_handle, 1234).status
+            == ComputationStatus::InvalidPresenceDetectorHandleError
+    }));
+}
+
+#[test]
+fn test_worker_mode_invalid_handle() {
+    assert_eq!(
+        presence_detector_set_worker_mode(INVALID_HANDLE, true).status,
+        ComputationStatus::InvalidPresenceDetectorHandleError
+    );
+}
@@ -12,61 +12,120 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use core::marker::PhantomData;
 use fpp::presence_detector::PresenceDetector;
 use lazy_static::lazy_static;
 use rand::Rng;
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Mutex, MutexGuard};
 
+/// Number of independently locked shards, so that callers using different
+/// handles rarely contend on the same lock
+const SHARD_COUNT: usize = 16;
+
+/// Handle never assigned to an entry, returned when an entry can't be inserted
+pub(crate) const NULL_HANDLE: u64 = 0;
+
 pub(crate) struct HandleMap<T> {
-    _marker: PhantomData<T>,
-    map: HashMap<u64, T>,
+    shards: [Mutex<HashMap<u64, T>>; SHARD_COUNT],
+    len: AtomicUsize,
+    // Maximum number of entries, 0 if unlimited
+    capacity: AtomicUsize,
 }
 
 impl<T> HandleMap<T> {
     pub(crate) fn init() -> Self {
         Self {
-            _marker: Default::default(),
-            map: HashMap::new(),
+            shards: std::array::from_fn(|_| Mutex::new(HashMap::new())),
+            len: AtomicUsize::new(0),
+            capacity: AtomicUsize::new(0),
         }
     }
 
-    /// inserts an entry into the map and returns the randomly generated handle to the entry
-    pub(crate) fn insert(&mut self, data: T) -> u64 {
+    /// Locks the shard holding the entry at a given handle
+    fn lock_shard(&self, handle: u64) -> Option<MutexGuard<'_, HashMap<u64, T>>> {
+        let shard = self.shards.get((handle % SHARD_COUNT as u64) as usize)?;
+        Some(
+            shard
+                .lock()
+                .unwrap_or_else(|err_guard| err_guard.into_inner()),
+        )
+    }
+
+    /// inserts an entry into the map and returns the randomly generated handle to the entry,
+    /// or None if the map is at capacity
+    pub(crate) fn insert(&self, data: T) -> Option<u64> {
+        let capacity = self.capacity.load(Ordering::SeqCst);
+        self.len
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |len| {
+                (capacity == 0 || len < capacity).then_some(len + 1)
+            })
+            .ok()?;
         let mut rng = rand::thread_rng();
-        let mut handle: u64 = rng.gen();
+        loop {
+            let handle: u64 = rng.gen();
+            if handle == NULL_HANDLE {
+                continue;
+            }
+            if let Some(Entry::Vacant(entry)) = self
+                .lock_shard(handle)
+                .as_mut()
+                .map(|shard| shard.entry(handle))
+            {
+                entry.insert(data);
+                return Some(handle);
+            }
+        }
+    }
 
-        while self.map.contains_key(&handle) {
-            handle = rng.gen();
+    /// Removes an entry at a given handle returning an Option of the owned value
+    pub(crate) fn remove(&self, handle: &u64) -> Option<T> {
+        let data = self.lock_shard(*handle)?.remove(handle)?;
+        self.len.fetch_sub(1, Ordering::SeqCst);
+        Some(data)
+    }
+
+    /// Removes all entries, returning the number of removed entries
+    pub(crate) fn clear(&self) -> usize {
+        let mut removed_count = 0;
+        for shard in &self.shards {
+            let mut shard = shard
+                .lock()
+                .unwrap_or_else(|err_guard| err_guard.into_inner());
+            removed_count += shard.len();
+            self.len.fetch_sub(shard.len(), Ordering::SeqCst);
+            shard.clear();
         }
+        removed_count
+    }
 
-        assert!(self.map.insert(handle, data).is_none());
-        handle
+    /// Returns the number of entries in the map
+    pub(crate) fn len(&self) -> usize {
+        self.len.load(Ordering::SeqCst)
     }
 
-    /// Removes an entry at a given handle returning an Option of the owned value
-    pub(crate) fn remove(&mut self, handle: &u64) -> Option<T> {
-        self.map.remove(handle)
+    /// Limits the number of entries in the map, 0 meaning unlimited. Existing
+    /// entries beyond the capacity are kept, but no new entries are inserted
+    /// until enough of them are removed
+    pub(crate) fn set_capacity(&self, capacity: usize) {
+        self.capacity.store(capacity, Ordering::SeqCst);
     }
 
-    /// Gets a reference to the entry stored at the specified handle
-    pub(crate) fn get(&mut self, handle: &u64) -> Option<&mut T> {
-        self.map.get_mut(handle)
+    /// Calls a function with the entry stored at the specified handle, holding
+    /// only the lock of the entry's shard
+    pub(crate) fn with<R>(&self, handle: &u64, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        self.lock_shard(*handle)?.get_mut(handle).map(f)
     }
 }
 
-// Returns a threadsafe instance of the global static hashmap tracking the PresenceDetector handles
-pub(crate) fn get_presence_detector_handle_map(
-) -> MutexGuard<'static, HandleMap<Box<PresenceDetector>>> {
-    PRESENCE_DETECTOR_HANDLE_MAP
-        .lock()
-        .unwrap_or_else(|err_guard| err_guard.into_inner())
+// Returns the global static sharded hashmap tracking the PresenceDetector handles
+pub(crate) fn get_presence_detector_handle_map() -> &'static HandleMap<Box<PresenceDetector>> {
+    &PRESENCE_DETECTOR_HANDLE_MAP
 }
 
 // Global hashmap to track valid pointers, this is a safety precaution to make sure we are not
 // reading from unsafe memory address's passed in by the caller
 lazy_static! {
-    static ref PRESENCE_DETECTOR_HANDLE_MAP: Mutex<HandleMap<Box<PresenceDetector>>> =
-        Mutex::new(HandleMap::init());
+    static ref PRESENCE_DETECTOR_HANDLE_MAP: HandleMap<Box<PresenceDetector>> = HandleMap::init();
 }
@@ -0,0 +1,54 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::handle_map::{HandleMap, NULL_HANDLE};
+
+#[test]
+fn test_insert_and_remove() {
+    let handle_map = HandleMap::init();
+    let handle = handle_map.insert(42);
+    assert!(handle.is_some_and(|handle| handle != NULL_HANDLE));
+    assert_eq!(handle_map.len(), 1);
+    let handle = handle.unwrap_or(NULL_HANDLE);
+    assert_eq!(handle_map.with(&handle, |data| *data), Some(42));
+    assert_eq!(handle_map.remove(&handle), Some(42));
+    assert_eq!(handle_map.remove(&handle), None);
+    assert_eq!(handle_map.len(), 0);
+}
+
+#[test]
+fn test_clear() {
+    let handle_map = HandleMap::init();
+    for data in 0..20 {
+        handle_map.insert(data);
+    }
+    assert_eq!(handle_map.len(), 20);
+    assert_eq!(handle_map.clear(), 20);
+    assert_eq!(handle_map.len(), 0);
+}
+
+#[test]
+fn test_capacity() {
+    let handle_map = HandleMap::init();
+    handle_map.set_capacity(2);
+    let handle = handle_map.insert(1).unwrap_or(NULL_HANDLE);
+    assert!(handle_map.insert(2).is_some());
+    assert_eq!(handle_map.insert(3), None);
+    assert_eq!(handle_map.len(), 2);
+    handle_map.remove(&handle);
+    assert!(handle_map.insert(3).is_some());
+    handle_map.set_capacity(0);
+    assert!(handle_map.insert(4).is_some());
+    assert_eq!(handle_map.len(), 3);
+}
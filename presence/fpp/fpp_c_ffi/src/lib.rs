@@ -25,117 +25,419 @@
 use fpp::fused_presence_utils::*;
 use fpp::presence_detector::*;
 
-use crate::handle_map::get_presence_detector_handle_map;
+use crate::clock_callback::CallbackClock;
+pub use crate::clock_callback::ElapsedRealTimeCallback;
+use crate::handle_map::{get_presence_detector_handle_map, NULL_HANDLE};
+use crate::worker::{
+    enqueue_scan_results, start_worker, stop_all_workers, stop_worker, EnqueueResult,
+};
+use crate::zone_callback::{dispatch_pending_zone_changes, ZoneCallbackListener};
+pub use crate::zone_callback::ZoneChangedCallback;
 
+mod clock_callback;
 mod handle_map;
+mod worker;
+mod zone_callback;
+
+#[cfg(test)]
+mod ffi_test;
+
+#[cfg(test)]
+mod handle_map_test;
 
 /// Wraps the handle ID to an underlying PresenceDetector object
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[repr(C)]
 pub struct PresenceDetectorHandle {
     handle: u64,
 }
 
-/// Enum class representing possible outputs of proximity data processing call
+/// Enum class representing possible outputs of proximity data processing call.
+/// Status codes 100+ are considered errors
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 #[repr(C)]
 pub enum ComputationStatus {
     /// Returned if the proximity estimate calculation was successful
-    Success,
+    Success = 1,
     /// Returned if there is no computed proximity estimate
-    NoComputedProximityEstimate,
+    NoComputedProximityEstimate = 2,
+    /// Returned if the scan results were queued for the presence detector's
+    /// worker thread
+    Queued = 3,
     /// Returned if the handle is invalid
-    InvalidPresenceDetectorHandleError,
+    InvalidPresenceDetectorHandleError = 101,
     /// Returned if the output parameter is null
-    NullOutputParameterError,
+    NullOutputParameterError = 102,
+    /// Returned if an input array is null
+    NullInputParameterError = 103,
+    /// Returned if an output array is too small for the result
+    BufferTooSmallError = 104,
+    /// Returned if the maximum number of presence detectors is reached
+    CapacityExceededError = 105,
+}
+
+/// Result of every presence detector call: a status and, if the call computed
+/// a single proximity estimate successfully, that estimate
+#[derive(Copy, Clone, PartialEq, Debug)]
+#[repr(C)]
+pub struct PresenceDetectorResult {
+    /// Outcome of the call
+    pub status: ComputationStatus,
+    /// Proximity estimate computed by the call, only meaningful if the status
+    /// is Success and the call computes a single estimate
+    pub proximity_estimate: ProximityEstimate,
 }
 
-impl ComputationStatus {
-    fn to_status_code(&self) -> i32 {
-        match self {
-            /// Status codes 100+ are considered errors
-            Self::Success => 1,
-            Self::NoComputedProximityEstimate => 2,
-            Self::InvalidPresenceDetectorHandleError => 101,
-            Self::NullOutputParameterError => 102,
+impl PresenceDetectorResult {
+    fn success(proximity_estimate: ProximityEstimate) -> Self {
+        PresenceDetectorResult { status: ComputationStatus::Success, proximity_estimate }
+    }
+}
+
+impl From<ComputationStatus> for PresenceDetectorResult {
+    fn from(status: ComputationStatus) -> Self {
+        PresenceDetectorResult {
+            status,
+            proximity_estimate: ProximityEstimate {
+                device_id: 0,
+                distance_meters: 0.0,
+                distance_confidence: MeasurementConfidence::Unknown,
+                elapsed_real_time_millis: 0,
+                proximity_state: ProximityState::Unknown,
+                source: PresenceDataSource::Unknown,
+            },
         }
     }
 }
 
+/// Calls a function with the presence detector behind a handle, holding the
+/// lock of its handle map shard, and returns an error status if the handle is
+/// invalid. Zone callbacks are invoked once the lock is released
+fn with_presence_detector(
+    presence_detector_handle: PresenceDetectorHandle,
+    f: impl FnOnce(&mut PresenceDetector) -> PresenceDetectorResult,
+) -> PresenceDetectorResult {
+    let result = get_presence_detector_handle_map()
+        .with(&presence_detector_handle.handle, |presence_detector| f(presence_detector))
+        .unwrap_or_else(|| ComputationStatus::InvalidPresenceDetectorHandleError.into());
+    dispatch_pending_zone_changes();
+    result
+}
+
 /// Creates a new presence detector object and returns the handle for the new
-/// object
+/// object, or a null handle (0) if the maximum number of presence detectors is
+/// reached
 #[no_mangle]
 pub extern "C" fn presence_detector_create() -> PresenceDetectorHandle {
-    let handle = get_presence_detector_handle_map().insert(Box::new(PresenceDetector::new()));
+    let handle = get_presence_detector_handle_map()
+        .insert(Box::new(PresenceDetector::new()))
+        .unwrap_or(NULL_HANDLE);
+    PresenceDetectorHandle { handle }
+}
+
+/// Creates a new presence detector object reading the current time from a
+/// callback, and returns the handle for the new object, or a null handle (0)
+/// if the maximum number of presence detectors is reached. Use it when scan
+/// results carry timestamps from a platform clock, e.g. Android's
+/// elapsedRealtime: presence detectors created otherwise count time from
+/// their creation, and clamp later timestamps to that time
+///
+/// # Safety
+///
+/// Ensure that the callback and its user data remain valid until the presence
+/// detector is freed, and that they may be used from any thread using the
+/// presence detector
+#[no_mangle]
+pub unsafe extern "C" fn presence_detector_create_with_clock(
+    clock: ElapsedRealTimeCallback,
+    user_data: *mut std::os::raw::c_void,
+) -> PresenceDetectorHandle {
+    let presence_detector = PresenceDetector::with_clock(
+        ProximityStateOptions::default(),
+        Box::new(CallbackClock::new(clock, user_data)),
+    );
+    let handle = get_presence_detector_handle_map()
+        .insert(Box::new(presence_detector))
+        .unwrap_or(NULL_HANDLE);
     PresenceDetectorHandle { handle }
 }
 
-/// Updates PresenceDetector with a new scan result and returns an error code if
-/// unsuccessful
+/// Creates a new presence detector object, writing the handle for the new
+/// object to the output parameter, and returns the status of the call
 ///
 /// # Safety
 ///
 /// Ensure that the output parameter refers to an initialized instance
 #[no_mangle]
-pub unsafe extern "C" fn update_ble_scan_result(
+pub unsafe extern "C" fn presence_detector_try_create(
+    presence_detector_handle: *mut PresenceDetectorHandle,
+) -> PresenceDetectorResult {
+    let Some(presence_detector_handle) = presence_detector_handle.as_mut() else {
+        return ComputationStatus::NullOutputParameterError.into();
+    };
+    match get_presence_detector_handle_map().insert(Box::new(PresenceDetector::new())) {
+        Some(handle) => {
+            *presence_detector_handle = PresenceDetectorHandle { handle };
+            ComputationStatus::Success.into()
+        }
+        None => ComputationStatus::CapacityExceededError.into(),
+    }
+}
+
+/// Returns the number of presence detector objects that have not been freed
+#[no_mangle]
+pub extern "C" fn presence_detector_count() -> usize {
+    get_presence_detector_handle_map().len()
+}
+
+/// Limits the number of presence detector objects that can exist at the same
+/// time, 0 meaning unlimited. Existing objects beyond the capacity are kept,
+/// but creating new ones fails until enough of them are freed
+#[no_mangle]
+pub extern "C" fn presence_detector_set_capacity(capacity: usize) {
+    get_presence_detector_handle_map().set_capacity(capacity);
+}
+
+/// Updates PresenceDetector with a new scan result and returns the current
+/// proximity estimate of the scanned device. In worker thread mode, the scan
+/// result is queued instead and a Queued status is returned immediately
+#[no_mangle]
+pub extern "C" fn update_ble_scan_result(
     presence_detector_handle: PresenceDetectorHandle,
     ble_scan_result: BleScanResult,
-    proximity_estimate: *mut ProximityEstimate,
-) -> i32 {
-    if let Some(presence_detector) =
-        get_presence_detector_handle_map().get(&presence_detector_handle.handle)
-    {
-        if let Some(current_proximity_estimate) =
-            presence_detector.on_ble_scan_result(ble_scan_result)
-        {
-            if let Some(proximity_estimate) = proximity_estimate.as_mut() {
-                *proximity_estimate = current_proximity_estimate;
-                ComputationStatus::Success.to_status_code()
-            } else {
-                ComputationStatus::NullOutputParameterError.to_status_code()
+) -> PresenceDetectorResult {
+    match enqueue_scan_results(presence_detector_handle.handle, &[ble_scan_result]) {
+        EnqueueResult::Queued => return ComputationStatus::Queued.into(),
+        EnqueueResult::WorkerStopped => {
+            return ComputationStatus::InvalidPresenceDetectorHandleError.into()
+        }
+        EnqueueResult::NoWorker => {}
+    }
+    with_presence_detector(presence_detector_handle, |presence_detector| {
+        presence_detector.on_ble_scan_result(ble_scan_result).map_or(
+            ComputationStatus::NoComputedProximityEstimate.into(),
+            PresenceDetectorResult::success,
+        )
+    })
+}
+
+/// Updates PresenceDetector with a batch of scan results under a single lock.
+/// One result is written to the output array per scan result, at the same
+/// index: the device's proximity estimate with a Success status, or a
+/// NoComputedProximityEstimate status if none was computed. Returns a Success
+/// status if any proximity estimate was computed. In worker thread mode, the
+/// scan results are queued instead, every output result gets a Queued status
+/// and a Queued status is returned immediately. If the worker stops because
+/// the handle is freed while the scan results are queued, an error status is
+/// returned and some of them may still be processed
+///
+/// # Safety
+///
+/// Ensure that the scan results point to `count` initialized instances and
+/// that the output array has room for `count` results
+#[no_mangle]
+pub unsafe extern "C" fn update_ble_scan_results(
+    presence_detector_handle: PresenceDetectorHandle,
+    ble_scan_results: *const BleScanResult,
+    count: usize,
+    results: *mut PresenceDetectorResult,
+) -> PresenceDetectorResult {
+    let (ble_scan_results, results) = if count == 0 {
+        (&[][..], &mut [][..])
+    } else if ble_scan_results.is_null() {
+        return ComputationStatus::NullInputParameterError.into();
+    } else if results.is_null() {
+        return ComputationStatus::NullOutputParameterError.into();
+    } else {
+        (
+            std::slice::from_raw_parts(ble_scan_results, count),
+            std::slice::from_raw_parts_mut(results, count),
+        )
+    };
+    match enqueue_scan_results(presence_detector_handle.handle, ble_scan_results) {
+        EnqueueResult::Queued => {
+            results.fill(ComputationStatus::Queued.into());
+            return ComputationStatus::Queued.into();
+        }
+        EnqueueResult::WorkerStopped => {
+            return ComputationStatus::InvalidPresenceDetectorHandleError.into()
+        }
+        EnqueueResult::NoWorker => {}
+    }
+    with_presence_detector(presence_detector_handle, |presence_detector| {
+        let mut status = ComputationStatus::NoComputedProximityEstimate;
+        for (result, ble_scan_result) in results.iter_mut().zip(ble_scan_results) {
+            *result = presence_detector.on_ble_scan_result(*ble_scan_result).map_or(
+                ComputationStatus::NoComputedProximityEstimate.into(),
+                PresenceDetectorResult::success,
+            );
+            if result.status == ComputationStatus::Success {
+                status = ComputationStatus::Success;
             }
-        } else {
-            ComputationStatus::NoComputedProximityEstimate.to_status_code()
+        }
+        status.into()
+    })
+}
+
+/// Replaces the options used to compute proximity states, e.g. zone thresholds,
+/// hysteresis and the number of consecutive scans required for a transition,
+/// and returns the status of the call
+#[no_mangle]
+pub extern "C" fn presence_detector_configure_options(
+    presence_detector_handle: PresenceDetectorHandle,
+    options: ProximityStateOptions,
+) -> PresenceDetectorResult {
+    with_presence_detector(presence_detector_handle, |presence_detector| {
+        presence_detector.set_options(options);
+        ComputationStatus::Success.into()
+    })
+}
+
+/// Registers a callback invoked on confirmed zone transitions of the presence
+/// detector's devices, replacing any previously registered callback. Passing a
+/// null callback clears the registration. Returns the status of the call. The
+/// callback is invoked after the presence detector is released, so it may
+/// call the functions of this API, including with the same handle
+///
+/// # Safety
+///
+/// Ensure that the callback and its user data remain valid until the callback
+/// is cleared or the presence detector is freed, and that they may be used
+/// from any thread updating the presence detector
+#[no_mangle]
+pub unsafe extern "C" fn presence_detector_set_zone_callback(
+    presence_detector_handle: PresenceDetectorHandle,
+    callback: Option<ZoneChangedCallback>,
+    user_data: *mut std::os::raw::c_void,
+) -> PresenceDetectorResult {
+    with_presence_detector(presence_detector_handle, |presence_detector| {
+        match callback {
+            Some(callback) => presence_detector.set_proximity_state_listener(Box::new(
+                ZoneCallbackListener::new(callback, user_data),
+            )),
+            None => presence_detector.clear_proximity_state_listener(),
+        }
+        ComputationStatus::Success.into()
+    })
+}
+
+/// Enables or disables the worker thread mode of a presence detector, and
+/// returns the status of the call. In worker thread mode, scan result updates
+/// are queued to a thread owned by the presence detector and return
+/// immediately, so that the calling thread never waits for the computation.
+/// Zone transitions are then delivered through the zone callback, invoked
+/// from the worker thread, and scan results should carry their timestamp
+/// since they are processed later. Disabling the mode waits until the queued
+/// scan results are processed, except when called from the zone callback on
+/// the worker thread: the worker then processes the remaining scan results
+/// after the callback returns. The zone callback may likewise free the
+/// presence detector, and may call any other function of this API
+#[no_mangle]
+pub extern "C" fn presence_detector_set_worker_mode(
+    presence_detector_handle: PresenceDetectorHandle,
+    enabled: bool,
+) -> PresenceDetectorResult {
+    let handle = presence_detector_handle.handle;
+    if enabled {
+        if !start_worker(handle) {
+            return ComputationStatus::InvalidPresenceDetectorHandleError.into();
         }
     } else {
-        ComputationStatus::InvalidPresenceDetectorHandleError.to_status_code()
+        if get_presence_detector_handle_map().with(&handle, |_| ()).is_none() {
+            return ComputationStatus::InvalidPresenceDetectorHandleError.into();
+        }
+        stop_worker(handle);
     }
+    ComputationStatus::Success.into()
 }
 
 /// Gets the current proximity estimate for a given device ID
+#[no_mangle]
+pub extern "C" fn get_proximity_estimate(
+    presence_detector_handle: PresenceDetectorHandle,
+    device_id: u64,
+) -> PresenceDetectorResult {
+    with_presence_detector(presence_detector_handle, |presence_detector| {
+        presence_detector.get_proximity_estimate(device_id).map_or(
+            ComputationStatus::NoComputedProximityEstimate.into(),
+            PresenceDetectorResult::success,
+        )
+    })
+}
+
+/// Gets the current proximity estimates of all tracked devices, ordered by
+/// device ID, writing them to the output array and their number to the output
+/// count. If the output array's capacity is too small, nothing is written to
+/// it, the output count is set to the required capacity and an error status
+/// is returned
 ///
 /// # Safety
 ///
-/// Ensure that the output parameter refers to an initialized instance
+/// Ensure that the output array has room for `capacity` proximity estimates
+/// and that the output count refers to an initialized instance
 #[no_mangle]
-pub unsafe extern "C" fn get_proximity_estimate(
+pub unsafe extern "C" fn get_all_proximity_estimates(
     presence_detector_handle: PresenceDetectorHandle,
-    device_id: u64,
-    proximity_estimate: *mut ProximityEstimate,
-) -> i32 {
-    if let Some(presence_detector) =
-        get_presence_detector_handle_map().get(&presence_detector_handle.handle)
-    {
-        presence_detector.get_proximity_estimate(device_id).map(|current_proximity_estimate| {
-            if let Some(proximity_estimate) = proximity_estimate.as_mut() {
-                *proximity_estimate = current_proximity_estimate;
-                return ComputationStatus::Success.to_status_code();
-            }
-            ComputationStatus::NullOutputParameterError.to_status_code()
-        });
+    proximity_estimates: *mut ProximityEstimate,
+    capacity: usize,
+    proximity_estimate_count: *mut usize,
+) -> PresenceDetectorResult {
+    let Some(proximity_estimate_count) = proximity_estimate_count.as_mut() else {
+        return ComputationStatus::NullOutputParameterError.into();
+    };
+    *proximity_estimate_count = 0;
+    let Some(current_proximity_estimates) = get_presence_detector_handle_map().with(
+        &presence_detector_handle.handle,
+        |presence_detector| {
+            presence_detector
+                .get_tracked_devices()
+                .into_iter()
+                .filter_map(|device_id| presence_detector.get_proximity_estimate(device_id))
+                .collect::<Vec<ProximityEstimate>>()
+        },
+    ) else {
+        return ComputationStatus::InvalidPresenceDetectorHandleError.into();
+    };
+    if current_proximity_estimates.is_empty() {
+        return ComputationStatus::NoComputedProximityEstimate.into();
+    }
+    if current_proximity_estimates.len() > capacity {
+        *proximity_estimate_count = current_proximity_estimates.len();
+        return ComputationStatus::BufferTooSmallError.into();
     }
+    if proximity_estimates.is_null() {
+        return ComputationStatus::NullOutputParameterError.into();
+    }
+    std::slice::from_raw_parts_mut(proximity_estimates, capacity)
+        .iter_mut()
+        .zip(current_proximity_estimates)
+        .for_each(|(proximity_estimate, current_proximity_estimate)| {
+            *proximity_estimate = current_proximity_estimate;
+            *proximity_estimate_count += 1;
+        });
+    ComputationStatus::Success.into()
+}
 
-    ComputationStatus::InvalidPresenceDetectorHandleError.to_status_code()
+/// De-allocates memory for all presence detector objects, invalidating every
+/// handle, and returns the number of freed objects
+#[no_mangle]
+pub extern "C" fn presence_detector_free_all() -> usize {
+    let freed_count = get_presence_detector_handle_map().clear();
+    stop_all_workers();
+    freed_count
 }
 
 /// De-allocates memory for a presence detector object
 #[no_mangle]
 pub extern "C" fn presence_detector_free(
     presence_detector_handle: PresenceDetectorHandle,
-) -> std::os::raw::c_int {
+) -> PresenceDetectorResult {
     if let Some(presence_detector) =
         get_presence_detector_handle_map().remove(&presence_detector_handle.handle)
     {
         let _ = *presence_detector;
-        return ComputationStatus::Success.to_status_code();
+        stop_worker(presence_detector_handle.handle);
+        return ComputationStatus::Success.into();
     }
-    ComputationStatus::InvalidPresenceDetectorHandleError.to_status_code()
+    ComputationStatus::InvalidPresenceDetectorHandleError.into()
 }
@@ -0,0 +1,170 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+
+use fpp::fused_presence_utils::BleScanResult;
+use lazy_static::lazy_static;
+
+use crate::handle_map::get_presence_detector_handle_map;
+use crate::zone_callback::dispatch_pending_zone_changes;
+
+/// Thread processing the scan results queued for a single presence detector
+pub(crate) struct ScanResultWorker {
+    sender: Option<Sender<BleScanResult>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ScanResultWorker {
+    /// Spawns a worker feeding queued scan results to the presence detector
+    /// behind a handle, until the worker is dropped or the handle is freed
+    fn spawn(handle: u64) -> Self {
+        let (sender, receiver) = mpsc::channel::<BleScanResult>();
+        let thread = thread::spawn(move || {
+            for ble_scan_result in receiver {
+                let is_valid_handle = get_presence_detector_handle_map()
+                    .with(&handle, |presence_detector| {
+                        presence_detector.on_ble_scan_result(ble_scan_result);
+                    })
+                    .is_some();
+                dispatch_pending_zone_changes();
+                if !is_valid_handle {
+                    break;
+                }
+            }
+        });
+        ScanResultWorker {
+            sender: Some(sender),
+            thread: Some(thread),
+        }
+    }
+
+    /// Queues a scan result, returning false if the worker has stopped
+    fn enqueue(&self, ble_scan_result: BleScanResult) -> bool {
+        self.sender
+            .as_ref()
+            .is_some_and(|sender| sender.send(ble_scan_result).is_ok())
+    }
+}
+
+impl Drop for ScanResultWorker {
+    /// Processes the scan results still queued, then stops the worker thread
+    fn drop(&mut self) {
+        self.sender = None;
+        if let Some(thread) = self.thread.take() {
+            // A worker stopped from its own thread, e.g. by a zone callback,
+            // can't wait for itself. It exits once the queue is drained
+            if thread.thread().id() != thread::current().id() {
+                let _ = thread.join();
+            }
+        }
+    }
+}
+
+fn lock_workers() -> MutexGuard<'static, HashMap<u64, ScanResultWorker>> {
+    SCAN_RESULT_WORKERS
+        .lock()
+        .unwrap_or_else(|err_guard| err_guard.into_inner())
+}
+
+/// Starts a worker for the presence detector behind a handle, if it has none.
+/// Returns false if the handle is invalid
+pub(crate) fn start_worker(handle: u64) -> bool {
+    // The handle is checked under the workers lock, so that a concurrent free
+    // either fails the check or stops the worker started here
+    let mut workers = lock_workers();
+    if get_presence_detector_handle_map()
+        .with(&handle, |_| ())
+        .is_none()
+    {
+        return false;
+    }
+    workers
+        .entry(handle)
+        .or_insert_with(|| ScanResultWorker::spawn(handle));
+    WORKER_COUNT.store(workers.len(), Ordering::SeqCst);
+    true
+}
+
+/// Stops the worker of the presence detector behind a handle, after it has
+/// processed the scan results still queued. Returns false if there was none
+pub(crate) fn stop_worker(handle: u64) -> bool {
+    // Dropped outside of the lock, so that other workers remain usable while
+    // the queue is drained
+    let worker = {
+        let mut workers = lock_workers();
+        let worker = workers.remove(&handle);
+        WORKER_COUNT.store(workers.len(), Ordering::SeqCst);
+        worker
+    };
+    worker.is_some()
+}
+
+/// Stops the workers of all presence detectors
+pub(crate) fn stop_all_workers() {
+    let workers: Vec<ScanResultWorker> = lock_workers().drain().map(|(_, worker)| worker).collect();
+    WORKER_COUNT.store(0, Ordering::SeqCst);
+    drop(workers);
+}
+
+/// Outcome of queueing scan results for a presence detector's worker
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) enum EnqueueResult {
+    /// The presence detector has no worker, so nothing was queued
+    NoWorker,
+    /// Every scan result was queued
+    Queued,
+    /// The worker stopped because the handle was freed, possibly after some
+    /// of the scan results were queued
+    WorkerStopped,
+}
+
+/// Queues scan results for the worker of the presence detector behind a
+/// handle
+pub(crate) fn enqueue_scan_results(
+    handle: u64,
+    ble_scan_results: &[BleScanResult],
+) -> EnqueueResult {
+    // Keeps presence detectors without a worker off the workers lock
+    if WORKER_COUNT.load(Ordering::SeqCst) == 0 {
+        return EnqueueResult::NoWorker;
+    }
+    match lock_workers().get(&handle) {
+        None => EnqueueResult::NoWorker,
+        Some(worker) => {
+            if ble_scan_results
+                .iter()
+                .all(|ble_scan_result| worker.enqueue(*ble_scan_result))
+            {
+                EnqueueResult::Queued
+            } else {
+                EnqueueResult::WorkerStopped
+            }
+        }
+    }
+}
+
+// Workers of the presence detectors in worker thread mode, kept apart from the
+// handle map so that queueing a scan result never waits for a presence
+// detector to finish processing the previous ones
+static WORKER_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+lazy_static! {
+    static ref SCAN_RESULT_WORKERS: Mutex<HashMap<u64, ScanResultWorker>> =
+        Mutex::new(HashMap::new());
+}
@@ -0,0 +1,103 @@
+// Copyright 2023 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cell::RefCell;
+use std::os::raw::c_void;
+
+use fpp::fused_presence_utils::{ProximityEstimate, ProximityState};
+use fpp::presence_detector::ProximityStateListener;
+
+/// Callback invoked with the caller's user data on confirmed zone transitions
+pub type ZoneChangedCallback = extern "C" fn(
+    user_data: *mut c_void,
+    device_id: u64,
+    old_state: ProximityState,
+    new_state: ProximityState,
+    proximity_estimate: ProximityEstimate,
+);
+
+/// Forwards confirmed zone transitions of a presence detector to a C callback
+pub(crate) struct ZoneCallbackListener {
+    callback: ZoneChangedCallback,
+    user_data: *mut c_void,
+}
+
+// The user data is opaque to the listener and only handed back to the
+// callback. Callers registering a callback guarantee that it may be invoked
+// from whichever thread updates the presence detector.
+unsafe impl Send for ZoneCallbackListener {}
+
+impl ZoneCallbackListener {
+    pub(crate) fn new(callback: ZoneChangedCallback, user_data: *mut c_void) -> Self {
+        ZoneCallbackListener {
+            callback,
+            user_data,
+        }
+    }
+}
+
+impl ProximityStateListener for ZoneCallbackListener {
+    fn on_zone_changed(
+        &mut self,
+        device_id: u64,
+        old_state: ProximityState,
+        new_state: ProximityState,
+        proximity_estimate: ProximityEstimate,
+    ) {
+        // Invoked while the presence detector's handle map shard is locked, so
+        // the callback is deferred until the lock is released
+        PENDING_ZONE_CHANGES.with(|pending_zone_changes| {
+            pending_zone_changes.borrow_mut().push(PendingZoneChange {
+                callback: self.callback,
+                user_data: self.user_data,
+                device_id,
+                old_state,
+                new_state,
+                proximity_estimate,
+            })
+        });
+    }
+}
+
+/// Zone transition waiting for its callback to be invoked
+struct PendingZoneChange {
+    callback: ZoneChangedCallback,
+    user_data: *mut c_void,
+    device_id: u64,
+    old_state: ProximityState,
+    new_state: ProximityState,
+    proximity_estimate: ProximityEstimate,
+}
+
+/// Invokes the callbacks of the zone transitions confirmed by the calling
+/// thread, in order. Must be called without holding any handle map lock, so
+/// that the callbacks may call back into the presence detector API
+pub(crate) fn dispatch_pending_zone_changes() {
+    let pending_zone_changes = PENDING_ZONE_CHANGES.with(RefCell::take);
+    for zone_change in pending_zone_changes {
+        (zone_change.callback)(
+            zone_change.user_data,
+            zone_change.device_id,
+            zone_change.old_state,
+            zone_change.new_state,
+            zone_change.proximity_estimate,
+        );
+    }
+}
+
+// Zone transitions are confirmed by the thread updating a presence detector,
+// which dispatches them once it has released the presence detector
+thread_local! {
+    static PENDING_ZONE_CHANGES: RefCell<Vec<PendingZoneChange>> = const { RefCell::new(Vec::new()) };
+}
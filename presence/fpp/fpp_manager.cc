@@ -20,6 +20,8 @@
 #include <utility>
 
 #include "absl/status/status.h"
+#include "absl/time/time.h"
+#include "internal/platform/implementation/system_clock.h"
 #include "internal/platform/logging.h"
 #include "presence/fpp/fpp_c_ffi/include/presence_detector.h"
 #include "presence/implementation/sensor_fusion.h"
@@ -32,10 +34,11 @@ namespace {
 // See
 // https://source.corp.google.com/piper///depot/google3/third_party/nearby/presence/fpp/fpp_c_ffi/src/lib.rs;l=49
 // for constants definition
-constexpr int kSuccess = 1;
-constexpr int kNoComputedProximityEstimate = 2;
 constexpr int kInvalidPresenceDetectorHandleError = 101;
 constexpr int kNullOutputParameterError = 102;
+constexpr int kNullInputParameterError = 103;
+constexpr int kBufferTooSmallError = 104;
+constexpr int kCapacityExceededError = 105;
 
 // Converts optional tx power to the rust api compatible equivalent
 MaybeTxPower ConvertTxPower(std::optional<int8_t> txPower) {
@@ -65,6 +68,10 @@ PresenceZone::DistanceBoundary::RangeType ConvertProximityStateToRangeType(
 }
 }  // namespace
 
+uint64_t FppManager::ElapsedRealtimeMillis(void* user_data) {
+  return absl::ToUnixMillis(SystemClock::ElapsedRealtime());
+}
+
 absl::Status FppManager::UpdateBleScanResult(uint64_t device_id,
                                              std::optional<int8_t> txPower,
                                              int rssi,
@@ -73,7 +80,8 @@ absl::Status FppManager::UpdateBleScanResult(uint64_t device_id,
     return absl::InternalError("No callback registered");
   }
   BleScanResult ble_scan_result = {device_id, ConvertTxPower(txPower), rssi,
-                                   elapsed_realtime_millis};
+                                   elapsed_realtime_millis,
+                                   AdvertisingChannel::Unknown};
   ProximityEstimate default_proximity_estimate =
       ProximityEstimate{device_id,
                         /*distanceMeters=*/0.0,
@@ -85,20 +93,20 @@ absl::Status FppManager::UpdateBleScanResult(uint64_t device_id,
       current_proximity_estimates_.contains(device_id)
           ? current_proximity_estimates_[device_id]
           : default_proximity_estimate;
-  ProximityEstimate new_proximity_estimate = default_proximity_estimate;
-  int status_code = update_ble_scan_result(
-      presence_detector_handle_, ble_scan_result, &new_proximity_estimate);
-  if (status_code == kNoComputedProximityEstimate) {
+  PresenceDetectorResult result =
+      update_ble_scan_result(presence_detector_handle_, ble_scan_result);
+  if (result.status == ComputationStatus::NoComputedProximityEstimate) {
     NEARBY_LOGS(INFO) << "Insufficient number of scan results available to "
                          "compute proximity state";
     return absl::OkStatus();
   }
-  if (status_code == kSuccess) {
-    current_proximity_estimates_[device_id] = new_proximity_estimate;
+  if (result.status == ComputationStatus::Success) {
+    current_proximity_estimates_[device_id] = result.proximity_estimate;
     CheckPresenceZoneChanged(device_id, old_proximity_estimate,
-                             new_proximity_estimate);
+                             result.proximity_estimate);
     return absl::OkStatus();
   }
+  int status_code = static_cast<int>(result.status);
   NEARBY_LOGS(WARNING)
       << "Could not successfully update FPP with new scan result: Error code="
       << status_code;
@@ -159,6 +167,12 @@ std::string FppManager::GetStatusStringFromCode(int status_code) {
       return "INVALID_PRESENCE_DETECTOR_HANDLE";
     case kNullOutputParameterError:
       return "NULL_OUTPUT_PARAMETER";
+    case kNullInputParameterError:
+      return "NULL_INPUT_PARAMETER";
+    case kBufferTooSmallError:
+      return "BUFFER_TOO_SMALL";
+    case kCapacityExceededError:
+      return "CAPACITY_EXCEEDED";
     default:
       NEARBY_LOGS(WARNING) << "Error code is unknown";
       return "UNKNOWN_ERROR";
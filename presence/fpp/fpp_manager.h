@@ -33,10 +33,18 @@ class FppManager {
  public:
   using RangeType = PresenceZone::DistanceBoundary::RangeType;
 
-  FppManager() { presence_detector_handle_ = presence_detector_create(); }
+  FppManager() {
+    presence_detector_handle_ =
+        presence_detector_create_with_clock(&ElapsedRealtimeMillis, nullptr);
+  }
   ~FppManager() { presence_detector_free(presence_detector_handle_); }
 
-  /** Updates FPP with new BLE scan results. Returns status code */
+  /**
+   * Updates FPP with new BLE scan results. `txPower` is the tx power level in
+   * dBm advertised by the device, or nullopt if it advertised none, in which
+   * case the device's learned tx power or the BLE default is used. Returns
+   * status code
+   */
   absl::Status UpdateBleScanResult(uint64_t device_id,
                                    std::optional<int8_t> txPower, int rssi,
                                    uint64_t elapsed_realtime_millis);
@@ -72,6 +80,9 @@ class FppManager {
   std::string GetStatusStringFromCode(int status_code);
 
  private:
+  // Clock of the presence detector, in the time base of the scan results'
+  // elapsed_realtime_millis
+  static uint64_t ElapsedRealtimeMillis(void* user_data);
   void CheckPresenceZoneChanged(uint64_t device_id,
                                 ProximityEstimate old_estimate,
                                 ProximityEstimate new_estimate);
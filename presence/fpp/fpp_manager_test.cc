@@ -20,6 +20,8 @@
 #include "protobuf-matchers/protocol-buffer-matchers.h"
 #include "gtest/gtest.h"
 #include "absl/status/status.h"
+#include "absl/time/time.h"
+#include "internal/platform/implementation/system_clock.h"
 #include "presence/implementation/sensor_fusion.h"
 
 namespace nearby {
@@ -30,6 +32,12 @@ constexpr int kReachRssi = -40;
 constexpr int kShortRangeRssi = -60;
 constexpr int kCallbackId = 12345;
 
+// Timestamp of a scan result obtained now, in the time base of the presence
+// detector's clock
+uint64_t NowMillis() {
+  return absl::ToUnixMillis(SystemClock::ElapsedRealtime());
+}
+
 TEST(FppManager, UpdateBleScanResultSuccess) {
   FppManager manager;
   bool callback_called = false;
@@ -45,9 +53,9 @@ TEST(FppManager, UpdateBleScanResultSuccess) {
                                         kReachRssi,
                                         /*elapsed_real_time_millis=*/0));
   // State is only computed after second consecutive scan is fulfilled
-  EXPECT_OK(manager.UpdateBleScanResult(kDeviceId, /*txPower=*/std::nullopt,
-                                        kReachRssi,
-                                        /*elapsed_real_time_millis=*/2000));
+  EXPECT_OK(manager.UpdateBleScanResult(
+      kDeviceId, /*txPower=*/std::nullopt, kReachRssi,
+      /*elapsed_real_time_millis=*/NowMillis()));
   EXPECT_EQ(manager.GetRangingData(kDeviceId)
                 ->zone_transition.value()
                 .distance_range_type,
@@ -70,9 +78,9 @@ TEST(FppManager, ZoneTransitionDetected) {
   EXPECT_OK(manager.UpdateBleScanResult(kDeviceId, /*txPower=*/std::nullopt,
                                         kReachRssi,
                                         /*elapsed_real_time_millis=*/0));
-  EXPECT_OK(manager.UpdateBleScanResult(kDeviceId, /*txPower=*/std::nullopt,
-                                        kReachRssi,
-                                        /*elapsed_real_time_millis=*/2000));
+  EXPECT_OK(manager.UpdateBleScanResult(
+      kDeviceId, /*txPower=*/std::nullopt, kReachRssi,
+      /*elapsed_real_time_millis=*/NowMillis()));
   EXPECT_EQ(manager.GetRangingData(kDeviceId)
                 ->zone_transition.value()
                 .distance_range_type,
@@ -153,12 +161,15 @@ TEST(FppManager, UpdateBleScanResultWithTxPowerSuccess) {
            }});
   EXPECT_OK(manager.UpdateBleScanResult(kDeviceId, /*txPower=*/20, kReachRssi,
                                         /*elapsed_real_time_millis=*/0));
-  EXPECT_OK(manager.UpdateBleScanResult(kDeviceId, /*txPower=*/20, kReachRssi,
-                                        /*elapsed_real_time_millis=*/2000));
+  EXPECT_OK(manager.UpdateBleScanResult(
+      kDeviceId, /*txPower=*/20, kReachRssi,
+      /*elapsed_real_time_millis=*/NowMillis()));
+  // An advertised tx power of 20 dBm received at -40 dBm puts the device about
+  // 1 m away, in the short range zone
   EXPECT_EQ(manager.GetRangingData(kDeviceId)
                 ->zone_transition.value()
                 .distance_range_type,
-            PresenceZone::DistanceBoundary::RangeType::kWithinTap);
+            PresenceZone::DistanceBoundary::RangeType::kFar);
   EXPECT_TRUE(callback_called);
 }
 
@@ -176,9 +187,9 @@ TEST(FppManager, UnregisterZoneTransitionListener) {
   EXPECT_OK(manager.UpdateBleScanResult(kDeviceId, /*txPower=*/std::nullopt,
                                         kReachRssi,
                                         /*elapsed_real_time_millis=*/0));
-  EXPECT_OK(manager.UpdateBleScanResult(kDeviceId, /*txPower=*/std::nullopt,
-                                        kReachRssi,
-                                        /*elapsed_real_time_millis=*/2000));
+  EXPECT_OK(manager.UpdateBleScanResult(
+      kDeviceId, /*txPower=*/std::nullopt, kReachRssi,
+      /*elapsed_real_time_millis=*/NowMillis()));
   EXPECT_TRUE(callback_called);
   callback_called = false;
 
@@ -214,9 +225,9 @@ TEST(FppManager, ResetProximityStateData) {
                                         kReachRssi,
                                         /*elapsed_real_time_millis=*/0));
   // State is only computed after second consecutive scan is fulfilled
-  ASSERT_OK(manager.UpdateBleScanResult(kDeviceId, /*txPower=*/std::nullopt,
-                                        kReachRssi,
-                                        /*elapsed_real_time_millis=*/2000));
+  ASSERT_OK(manager.UpdateBleScanResult(
+      kDeviceId, /*txPower=*/std::nullopt, kReachRssi,
+      /*elapsed_real_time_millis=*/NowMillis()));
   EXPECT_EQ(manager.GetRangingData(kDeviceId)
                 ->zone_transition.value()
                 .distance_range_type,